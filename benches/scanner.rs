@@ -0,0 +1,83 @@
+//! Scanner throughput: the `RegexSet` single-pass scanner against the
+//! sequential regex-per-detector approach it replaced, over a corpus of
+//! mixed values. Most production cells are plain words, numbers, and
+//! timestamps, so the corpus is weighted that way — the win comes from
+//! rejecting those cheaply rather than probing every detector.
+
+use std::hint::black_box;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use iron_veil::scanner::PiiScanner;
+use regex::Regex;
+
+/// Mixed values in roughly production proportions: a few PII shapes
+/// among the ordinary data that dominates real rows
+const CORPUS: &[&str] = &[
+    "alice@example.com",
+    "4111-1111-1111-1111",
+    "123-45-6789",
+    "415-555-0133",
+    "192.168.1.50",
+    "1985-06-15",
+    "DE44500105175407324931",
+    "AB1234567",
+    "42",
+    "1999",
+    "active",
+    "Portland",
+    "order confirmed",
+    "2024-01-15 10:30:00",
+    "f47ac10b-58cc-4372-a567-0e02b2c3d479",
+    "Lorem ipsum dolor sit amet, consectetur adipiscing elit",
+    "t-shirt (large)",
+    "https://example.com/orders/99817",
+    "0.0314",
+    "NULL",
+];
+
+/// The pre-`RegexSet` scanner: every detector's anchored regex probed in
+/// sequence, no cheap rejects
+fn sequential_detectors() -> Vec<Regex> {
+    [
+        r"^(?i)(?:[a-z0-9._%+-]+@[a-z0-9.-]+\.[a-z]{2,})$",
+        r"^(?:(?:\d{4}[-\s]?){3}\d{4})$",
+        r"^(?:(\d{3})-\d{2}-\d{4}|(\d{3})\d{6})$",
+        r"^[0-9A-Fa-f.:]{2,45}$",
+        r"^(?:\d{4}[-/]\d{2}[-/]\d{2}|\d{2}[-/]\d{2}[-/]\d{4})$",
+        r"^(?:(?:\+\d{1,3}[-.\s])?\(?\d{3}\)?[-.\s]?\d{3}[-.\s]?\d{4}|\+[1-9]\d{7,14})$",
+        r"^(?:[A-Z]{2}\d{2}[A-Z0-9]{1,30})$",
+        r"^[A-Z]{1,2}\d{6,8}$",
+    ]
+    .iter()
+    .map(|pattern| Regex::new(pattern).unwrap())
+    .collect()
+}
+
+fn bench_scan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scan_corpus");
+
+    let scanner = PiiScanner::new();
+    group.bench_function("regex_set", |b| {
+        b.iter(|| {
+            for value in CORPUS {
+                black_box(scanner.scan(black_box(value)));
+            }
+        })
+    });
+
+    let detectors = sequential_detectors();
+    group.bench_function("sequential", |b| {
+        b.iter(|| {
+            for value in CORPUS {
+                for detector in &detectors {
+                    black_box(detector.is_match(black_box(value)));
+                }
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_scan);
+criterion_main!(benches);
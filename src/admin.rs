@@ -0,0 +1,498 @@
+//! The admin console pseudo-database.
+//!
+//! Operators coming from PgBouncer expect to `psql` into an admin database
+//! and run `SHOW STATS` / `SHOW CLIENTS`. When a client's StartupMessage
+//! names [`AdminConfig::database_name`], the proxy serves this minimal
+//! SQL-ish console in-process instead of connecting upstream: each command
+//! answers with a proper RowDescription/DataRow result set so psql renders
+//! tables. Access is restricted by a dedicated password (cleartext auth) or
+//! by source CIDR; everything else is rejected before the console starts.
+
+use std::net::SocketAddr;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use futures::{SinkExt, StreamExt};
+use tokio::io::AsyncWriteExt;
+use tokio_util::codec::{Framed, FramedParts};
+use tracing::{info, warn};
+
+use crate::audit::{AuditLogger, AuthMethod};
+use crate::config::AdminConfig;
+use crate::error::ProxyError;
+use crate::protocol::postgres::{
+    DataRow, FieldDescription, PgMessage, PostgresCodec, QueryMessage, RegularMessage,
+    RowDescription,
+};
+use crate::state::AppState;
+
+/// What a console command answers with
+enum AdminResponse {
+    /// A result set: column names plus rows of text values (None renders
+    /// as SQL NULL)
+    Table {
+        columns: Vec<&'static str>,
+        rows: Vec<Vec<Option<String>>>,
+    },
+    /// A row-less command that succeeded, e.g. RELOAD
+    Complete(String),
+    /// An ErrorResponse; the session stays open
+    Error(String),
+}
+
+/// Serves one admin session over an accepted socket. `buffered_startup`
+/// carries the raw startup packet already consumed while peeking at the
+/// database name; it is replayed into the codec the same way database
+/// routing does.
+pub(crate) async fn serve_admin_console<S>(
+    socket: S,
+    state: AppState,
+    connection_id: usize,
+    client_addr: SocketAddr,
+    admin: AdminConfig,
+    buffered_startup: Vec<u8>,
+) -> Result<(), ProxyError>
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+{
+    let mut parts = FramedParts::new::<PgMessage>(socket, PostgresCodec::new());
+    parts.read_buf = BytesMut::from(&buffered_startup[..]);
+    let mut framed = Framed::from_parts(parts);
+
+    let startup = match framed.next().await {
+        Some(Ok(PgMessage::Startup(startup))) => startup,
+        Some(Ok(_)) | None => return Ok(()),
+        Some(Err(e)) => return Err(e),
+    };
+    let username = startup
+        .parameters
+        .iter()
+        .find(|(name, _)| name == "user")
+        .map(|(_, value)| value.clone());
+
+    // A matching source network admits the session outright; otherwise the
+    // dedicated password is required
+    let trusted = admin
+        .allow_cidr
+        .as_deref()
+        .is_some_and(|cidr| {
+            cidr.parse::<crate::hooks::Cidr>()
+                .is_ok_and(|c| c.contains(client_addr.ip()))
+        });
+    if trusted {
+        state
+            .audit_logger
+            .log(AuditLogger::auth_success(AuthMethod::None, username.clone()))
+            .await;
+    } else {
+        let Some(password) = admin.password.clone() else {
+            warn!(%client_addr, "Admin console rejected: source outside allow_cidr");
+            framed
+                .send(error_response(
+                    "FATAL",
+                    "28000",
+                    "admin console is not available from this address",
+                ))
+                .await?;
+            return Ok(());
+        };
+        framed.send(authentication_cleartext_password()).await?;
+        let offered = match framed.next().await {
+            Some(Ok(PgMessage::Regular(msg))) if msg.message_type == b'p' => {
+                // PasswordMessage: a single NUL-terminated string
+                let bytes = msg.payload.strip_suffix(b"\0").unwrap_or(&msg.payload);
+                String::from_utf8_lossy(bytes).into_owned()
+            }
+            Some(Err(e)) => return Err(e),
+            _ => return Ok(()),
+        };
+        if offered != password {
+            warn!(%client_addr, "Admin console rejected: bad password");
+            state
+                .audit_logger
+                .log(AuditLogger::auth_failure(
+                    AuthMethod::Password,
+                    "admin console password mismatch",
+                ))
+                .await;
+            framed
+                .send(error_response(
+                    "FATAL",
+                    "28P01",
+                    "password authentication failed for admin console",
+                ))
+                .await?;
+            return Ok(());
+        }
+        state
+            .audit_logger
+            .log(AuditLogger::auth_success(AuthMethod::Password, username.clone()))
+            .await;
+    }
+
+    info!(%client_addr, user = ?username, "Admin console session started");
+    state
+        .set_client_session(
+            connection_id,
+            username,
+            Some(admin.database_name.clone()),
+            None,
+        )
+        .await;
+
+    framed.send(authentication_ok()).await?;
+    framed
+        .send(parameter_status(
+            "server_version",
+            concat!(env!("CARGO_PKG_VERSION"), " (iron-veil admin)"),
+        ))
+        .await?;
+    framed.send(parameter_status("client_encoding", "UTF8")).await?;
+    framed.send(ready_for_query()).await?;
+
+    while let Some(msg) = framed.next().await {
+        match msg? {
+            PgMessage::Query(QueryMessage { query }) => {
+                let sql = String::from_utf8_lossy(&query).into_owned();
+                state.record_client_query(connection_id).await;
+                match dispatch(&state, &sql).await {
+                    AdminResponse::Table { columns, rows } => {
+                        let row_count = rows.len();
+                        framed.send(row_description(&columns)).await?;
+                        for row in rows {
+                            framed.send(data_row(row)).await?;
+                        }
+                        framed
+                            .send(command_complete(&format!("SELECT {}", row_count)))
+                            .await?;
+                    }
+                    AdminResponse::Complete(tag) => {
+                        framed.send(command_complete(&tag)).await?;
+                    }
+                    AdminResponse::Error(message) => {
+                        framed.send(error_response("ERROR", "42601", &message)).await?;
+                    }
+                }
+                framed.send(ready_for_query()).await?;
+            }
+            PgMessage::Regular(RegularMessage { message_type: b'X', .. }) => return Ok(()),
+            PgMessage::SSLRequest => {
+                framed.get_mut().write_all(b"N").await?;
+            }
+            _ => {
+                framed
+                    .send(error_response(
+                        "ERROR",
+                        "0A000",
+                        "admin console only supports the simple query protocol",
+                    ))
+                    .await?;
+                framed.send(ready_for_query()).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Resolves one console command against live state
+async fn dispatch(state: &AppState, sql: &str) -> AdminResponse {
+    let command = sql.trim().trim_end_matches(';').trim();
+    let words: Vec<String> = command
+        .split_whitespace()
+        .map(|w| w.to_ascii_uppercase())
+        .collect();
+    let words: Vec<&str> = words.iter().map(String::as_str).collect();
+
+    match words.as_slice() {
+        ["SHOW", "STATS"] => show_stats(state).await,
+        ["SHOW", "CLIENTS"] => show_clients(state).await,
+        ["SHOW", "RULES"] => show_rules(state).await,
+        ["SHOW", "HEALTH"] => show_health(state).await,
+        ["RELOAD"] => match state.reload_config().await {
+            Ok(rules) => {
+                info!(rules, "Config reloaded from admin console");
+                AdminResponse::Complete("RELOAD".to_string())
+            }
+            Err(e) => AdminResponse::Error(format!("reload failed: {}", e)),
+        },
+        _ => AdminResponse::Error(format!(
+            "unknown admin command {:?}; try SHOW STATS, SHOW CLIENTS, SHOW RULES, \
+             SHOW HEALTH, or RELOAD",
+            command
+        )),
+    }
+}
+
+async fn show_stats(state: &AppState) -> AdminResponse {
+    let stats = state.get_stats().await;
+    let active = state
+        .active_connections
+        .load(std::sync::atomic::Ordering::Relaxed);
+    let metric = |name: &str, value: u64| vec![Some(name.to_string()), Some(value.to_string())];
+    let rows = vec![
+        metric("active_connections", active as u64),
+        metric("total_connections", stats.total_connections),
+        metric("total_queries", stats.queries.total_queries),
+        metric("select_queries", stats.queries.select_count),
+        metric("insert_queries", stats.queries.insert_count),
+        metric("update_queries", stats.queries.update_count),
+        metric("delete_queries", stats.queries.delete_count),
+        metric("other_queries", stats.queries.other_count),
+        metric("total_masked", stats.masking.total()),
+        metric("condition_skipped", stats.masking.condition_skipped),
+    ];
+    AdminResponse::Table {
+        columns: vec!["metric", "value"],
+        rows,
+    }
+}
+
+async fn show_clients(state: &AppState) -> AdminResponse {
+    let rows = state
+        .client_snapshot()
+        .await
+        .into_iter()
+        .map(|(id, client)| {
+            vec![
+                Some(id.to_string()),
+                Some(client.client_addr.to_string()),
+                client.username,
+                client.database,
+                Some(client.queries.to_string()),
+                Some(client.rows_masked.to_string()),
+                Some(client.connected_at.to_rfc3339()),
+            ]
+        })
+        .collect();
+    AdminResponse::Table {
+        columns: vec![
+            "connection_id",
+            "address",
+            "user",
+            "database",
+            "queries",
+            "rows_masked",
+            "connected_at",
+        ],
+        rows,
+    }
+}
+
+async fn show_rules(state: &AppState) -> AdminResponse {
+    let config = state.config.read().await;
+    let rows = config
+        .rules
+        .iter()
+        .map(|rule| {
+            vec![
+                rule.id.clone(),
+                rule.table.clone(),
+                Some(rule.column.clone()),
+                Some(rule.strategy.to_string()),
+            ]
+        })
+        .collect();
+    AdminResponse::Table {
+        columns: vec!["id", "table", "column", "strategy"],
+        rows,
+    }
+}
+
+async fn show_health(state: &AppState) -> AdminResponse {
+    let health_row = |target: String, health: &crate::state::HealthStatus| {
+        vec![
+            Some(target),
+            Some(health.healthy.to_string()),
+            health.latency_ms.map(|ms| ms.to_string()),
+            Some(health.consecutive_failures.to_string()),
+            health.last_error.clone(),
+        ]
+    };
+    let mut rows = vec![health_row(
+        "upstream".to_string(),
+        &*state.health_status.read().await,
+    )];
+    let route_health = state.route_health.read().await;
+    let mut routes: Vec<&String> = route_health.keys().collect();
+    routes.sort();
+    for route in routes {
+        rows.push(health_row(format!("route:{}", route), &route_health[route]));
+    }
+    AdminResponse::Table {
+        columns: vec![
+            "target",
+            "healthy",
+            "latency_ms",
+            "consecutive_failures",
+            "last_error",
+        ],
+        rows,
+    }
+}
+
+/// AuthenticationCleartextPassword ('R' with code 3)
+fn authentication_cleartext_password() -> PgMessage {
+    let mut payload = BytesMut::new();
+    payload.put_u32(3);
+    PgMessage::Regular(RegularMessage {
+        message_type: b'R',
+        payload,
+    })
+}
+
+/// AuthenticationOk ('R' with code 0)
+fn authentication_ok() -> PgMessage {
+    let mut payload = BytesMut::new();
+    payload.put_u32(0);
+    PgMessage::Regular(RegularMessage {
+        message_type: b'R',
+        payload,
+    })
+}
+
+fn parameter_status(name: &str, value: &str) -> PgMessage {
+    let mut payload = BytesMut::new();
+    payload.put_slice(name.as_bytes());
+    payload.put_u8(0);
+    payload.put_slice(value.as_bytes());
+    payload.put_u8(0);
+    PgMessage::Regular(RegularMessage {
+        message_type: b'S',
+        payload,
+    })
+}
+
+/// ReadyForQuery, always idle: the console has no transactions
+fn ready_for_query() -> PgMessage {
+    PgMessage::Regular(RegularMessage {
+        message_type: b'Z',
+        payload: BytesMut::from(&b"I"[..]),
+    })
+}
+
+fn command_complete(tag: &str) -> PgMessage {
+    let mut payload = BytesMut::new();
+    payload.put_slice(tag.as_bytes());
+    payload.put_u8(0);
+    PgMessage::Regular(RegularMessage {
+        message_type: b'C',
+        payload,
+    })
+}
+
+fn error_response(severity: &str, sqlstate: &str, message: &str) -> PgMessage {
+    let mut payload = BytesMut::new();
+    payload.put_u8(b'S');
+    payload.put_slice(severity.as_bytes());
+    payload.put_u8(0);
+    payload.put_u8(b'C');
+    payload.put_slice(sqlstate.as_bytes());
+    payload.put_u8(0);
+    payload.put_u8(b'M');
+    payload.put_slice(message.as_bytes());
+    payload.put_u8(0);
+    payload.put_u8(0); // Terminator
+    PgMessage::Regular(RegularMessage {
+        message_type: b'E',
+        payload,
+    })
+}
+
+/// A RowDescription of all-text columns
+fn row_description(columns: &[&str]) -> PgMessage {
+    PgMessage::RowDescription(RowDescription {
+        fields: columns
+            .iter()
+            .map(|name| FieldDescription {
+                name: Bytes::copy_from_slice(name.as_bytes()),
+                table_oid: 0,
+                column_index: 0,
+                type_oid: 25, // text
+                type_len: -1,
+                type_modifier: -1,
+                format_code: 0,
+            })
+            .collect(),
+    })
+}
+
+fn data_row(values: Vec<Option<String>>) -> PgMessage {
+    PgMessage::DataRow(DataRow {
+        values: values
+            .into_iter()
+            .map(|value| value.map(|v| BytesMut::from(v.as_bytes())))
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+
+    fn test_state() -> AppState {
+        AppState::new_for_test(AppConfig::default(), "proxy.yaml".to_string())
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_show_stats_renders_counters() {
+        let state = test_state();
+        state.record_query("SELECT").await;
+        state.record_query("UPDATE").await;
+        state.record_masking("email").await;
+
+        let response = dispatch(&state, " show stats ;").await;
+        let AdminResponse::Table { columns, rows } = response else {
+            panic!("expected a table");
+        };
+        assert_eq!(columns, vec!["metric", "value"]);
+        let lookup = |metric: &str| {
+            rows.iter()
+                .find(|row| row[0].as_deref() == Some(metric))
+                .and_then(|row| row[1].clone())
+        };
+        assert_eq!(lookup("total_queries").as_deref(), Some("2"));
+        assert_eq!(lookup("select_queries").as_deref(), Some("1"));
+        assert_eq!(lookup("total_masked").as_deref(), Some("1"));
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_show_clients_tracks_sessions() {
+        let state = test_state();
+        state
+            .register_client(7, "10.0.0.1:5000".parse().unwrap())
+            .await;
+        state
+            .set_client_session(7, Some("alice".to_string()), Some("app".to_string()), None)
+            .await;
+        state.record_client_query(7).await;
+        state.record_client_masked_row(7, 2).await;
+
+        let AdminResponse::Table { rows, .. } = dispatch(&state, "SHOW CLIENTS").await else {
+            panic!("expected a table");
+        };
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0][0].as_deref(), Some("7"));
+        assert_eq!(rows[0][1].as_deref(), Some("10.0.0.1:5000"));
+        assert_eq!(rows[0][2].as_deref(), Some("alice"));
+        assert_eq!(rows[0][3].as_deref(), Some("app"));
+        assert_eq!(rows[0][4].as_deref(), Some("1"));
+        assert_eq!(rows[0][5].as_deref(), Some("1"));
+
+        // A finished connection disappears from the listing
+        state.clear_client(7).await;
+        let AdminResponse::Table { rows, .. } = dispatch(&state, "SHOW CLIENTS").await else {
+            panic!("expected a table");
+        };
+        assert!(rows.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_unknown_command_names_the_alternatives() {
+        let state = test_state();
+        let AdminResponse::Error(message) = dispatch(&state, "DROP TABLE users").await else {
+            panic!("expected an error");
+        };
+        assert!(message.contains("SHOW STATS"), "{}", message);
+        assert!(message.contains("RELOAD"), "{}", message);
+    }
+}
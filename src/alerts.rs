@@ -0,0 +1,371 @@
+//! Alerting on heuristic PII detections.
+//!
+//! Masking rules cover the columns we know about; a heuristic detection on
+//! a column with no rule is a potential leak worth paging on, not just
+//! counting. Detection events from the interceptors go over a channel to a
+//! small background aggregator ([`run_alert_task`]), which feeds them into
+//! an [`AlertEvaluator`]: a sliding-window counter per configured alert.
+//! When a window crosses its threshold the alert fires its actions — a
+//! webhook POST, an `Alert` audit entry, and optionally quarantining the
+//! offending column (hard redaction for subsequent result sets until an
+//! operator clears it via the API). Alert state is exposed at `GET /alerts`.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tracing::warn;
+
+use crate::audit::AuditLogger;
+use crate::config::{AlertRuleConfig, AlertsConfig, PatternSeverity, PolicyAction};
+use crate::scanner::PiiType;
+use crate::state::AppState;
+
+/// One heuristic PII detection, reported by an interceptor when a column
+/// with no masking rule scanned as PII
+#[derive(Debug, Clone)]
+pub struct Detection {
+    pub pii_type: PiiType,
+    /// Display name of the column the value arrived in, when known
+    pub column: Option<String>,
+    /// Source policy the connection runs under
+    pub policy: PolicyAction,
+    /// Severity of the matching `scanner.custom_patterns` entry, `None`
+    /// for built-in detectors
+    pub severity: Option<PatternSeverity>,
+    /// Confidence of the winning candidate, `None` on paths that scan
+    /// without scores (substrings, nested values)
+    pub confidence: Option<f32>,
+}
+
+/// Sliding-window state for every configured alert.
+///
+/// Purely synchronous: callers pass `now` in, so the window math is
+/// testable with simulated detection streams.
+pub struct AlertEvaluator {
+    rules: Vec<AlertRuleConfig>,
+    windows: Vec<VecDeque<Instant>>,
+    firing: Vec<bool>,
+    last_fired: Vec<Option<DateTime<Utc>>>,
+}
+
+/// An alert that just crossed its threshold, with everything the aggregator
+/// needs to run its actions
+#[derive(Debug, Clone)]
+pub struct FiredAlert {
+    pub name: String,
+    pub pii_type: Option<String>,
+    pub detections_in_window: u64,
+    pub threshold: u64,
+    pub webhook: Option<String>,
+    pub quarantine: bool,
+    /// Column of the detection that tipped the window, when known
+    pub column: Option<String>,
+    /// Severity of the detection that tipped the window, set when it came
+    /// from a custom pattern
+    pub severity: Option<PatternSeverity>,
+}
+
+/// One alert's state as reported by `GET /alerts`
+#[derive(Debug, Clone, Serialize)]
+pub struct AlertState {
+    pub name: String,
+    pub firing: bool,
+    pub detections_in_window: u64,
+    pub threshold: u64,
+    pub window_secs: u64,
+    pub last_fired: Option<DateTime<Utc>>,
+}
+
+impl AlertEvaluator {
+    pub fn new(config: Option<&AlertsConfig>) -> Self {
+        let rules = config.map(|c| c.rules.clone()).unwrap_or_default();
+        let n = rules.len();
+        Self {
+            rules,
+            windows: vec![VecDeque::new(); n],
+            firing: vec![false; n],
+            last_fired: vec![None; n],
+        }
+    }
+
+    /// Feed one detection into every matching window, returning the alerts
+    /// this event fired. Firing is edge-triggered: an alert already firing
+    /// stays silent until its window drains below the threshold and it
+    /// clears.
+    pub fn record(&mut self, detection: &Detection, now: Instant) -> Vec<FiredAlert> {
+        let mut fired = Vec::new();
+        for (idx, rule) in self.rules.iter().enumerate() {
+            if rule
+                .pii_type
+                .as_deref()
+                .is_some_and(|t| t != detection.pii_type.name())
+            {
+                continue;
+            }
+            if rule.policy.is_some_and(|p| p != detection.policy) {
+                continue;
+            }
+            let window = &mut self.windows[idx];
+            window.push_back(now);
+            evict(window, now, rule.window_secs);
+            let count = window.len() as u64;
+            if count > rule.threshold && !self.firing[idx] {
+                self.firing[idx] = true;
+                self.last_fired[idx] = Some(Utc::now());
+                fired.push(FiredAlert {
+                    name: rule.name.clone(),
+                    pii_type: rule.pii_type.clone(),
+                    detections_in_window: count,
+                    threshold: rule.threshold,
+                    webhook: rule.webhook.clone(),
+                    quarantine: rule.quarantine,
+                    column: detection.column.clone(),
+                    severity: detection.severity,
+                });
+            }
+        }
+        fired
+    }
+
+    /// Re-evaluates every window against `now`, clearing alerts whose
+    /// window drained below the threshold, and reports the state of each
+    pub fn states(&mut self, now: Instant) -> Vec<AlertState> {
+        self.rules
+            .iter()
+            .enumerate()
+            .map(|(idx, rule)| {
+                let window = &mut self.windows[idx];
+                evict(window, now, rule.window_secs);
+                let count = window.len() as u64;
+                if count <= rule.threshold {
+                    self.firing[idx] = false;
+                }
+                AlertState {
+                    name: rule.name.clone(),
+                    firing: self.firing[idx],
+                    detections_in_window: count,
+                    threshold: rule.threshold,
+                    window_secs: rule.window_secs,
+                    last_fired: self.last_fired[idx],
+                }
+            })
+            .collect()
+    }
+}
+
+/// Drop window entries older than `window_secs` before `now`
+fn evict(window: &mut VecDeque<Instant>, now: Instant, window_secs: u64) {
+    let horizon = Duration::from_secs(window_secs);
+    while window
+        .front()
+        .is_some_and(|&t| now.duration_since(t) > horizon)
+    {
+        window.pop_front();
+    }
+}
+
+/// Background aggregator: consumes detection events and runs the actions
+/// for alerts that fire. Lives on its own task so webhook POSTs and audit
+/// writes never stall the row path.
+pub(crate) async fn run_alert_task(
+    state: AppState,
+    mut rx: tokio::sync::mpsc::UnboundedReceiver<Detection>,
+) {
+    while let Some(detection) = rx.recv().await {
+        let fired = state.alerts.write().await.record(&detection, Instant::now());
+        for alert in fired {
+            warn!(
+                alert = %alert.name,
+                detections = alert.detections_in_window,
+                threshold = alert.threshold,
+                column = ?alert.column,
+                severity = alert.severity.map(|s| s.as_str()),
+                "PII detection alert fired"
+            );
+            state
+                .audit_logger
+                .log(AuditLogger::alert_fired(
+                    &alert.name,
+                    alert.pii_type.as_deref(),
+                    alert.detections_in_window,
+                    alert.column.as_deref().filter(|_| alert.quarantine),
+                ))
+                .await;
+            crate::metrics::record_alert_fired(&alert.name);
+            if alert.quarantine
+                && let Some(column) = &alert.column
+            {
+                state
+                    .quarantined_columns
+                    .write()
+                    .await
+                    .insert(column.to_lowercase());
+            }
+            if let Some(url) = alert.webhook.clone() {
+                let payload = serde_json::json!({
+                    "alert": alert.name,
+                    "pii_type": alert.pii_type,
+                    "detections_in_window": alert.detections_in_window,
+                    "threshold": alert.threshold,
+                    "column": alert.column,
+                    "severity": alert.severity.map(|s| s.as_str()),
+                    "quarantined": alert.quarantine && alert.column.is_some(),
+                });
+                tokio::spawn(async move {
+                    if let Err(e) = post_webhook(&url, &payload).await {
+                        warn!(url = %url, error = %e, "Alert webhook delivery failed");
+                    }
+                });
+            }
+        }
+    }
+}
+
+/// POSTs the alert payload to `url` as JSON. Hand-rolled HTTP/1.1 over a
+/// plain socket: webhooks are fire-and-forget notifications to an endpoint
+/// inside the operator's network, so only `http://` URLs are accepted
+/// (validation rejects anything else at load).
+async fn post_webhook(url: &str, payload: &serde_json::Value) -> anyhow::Result<()> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| anyhow::anyhow!("webhook URLs must start with http://"))?;
+    let (authority, path) = match rest.split_once('/') {
+        Some((authority, path)) => (authority, format!("/{}", path)),
+        None => (rest, "/".to_string()),
+    };
+    let address = if authority.contains(':') {
+        authority.to_string()
+    } else {
+        format!("{}:80", authority)
+    };
+    let body = payload.to_string();
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        authority,
+        body.len(),
+        body
+    );
+
+    let deliver = async {
+        let mut socket = tokio::net::TcpStream::connect(&address).await?;
+        socket.write_all(request.as_bytes()).await?;
+        // Drain whatever the endpoint answers; delivery is best-effort and
+        // the status line is not acted on
+        let mut response = [0u8; 512];
+        let _ = socket.read(&mut response).await;
+        anyhow::Ok(())
+    };
+    tokio::time::timeout(Duration::from_secs(10), deliver)
+        .await
+        .map_err(|_| anyhow::anyhow!("webhook request timed out"))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alert(name: &str, pii_type: Option<&str>, threshold: u64, window_secs: u64) -> AlertRuleConfig {
+        AlertRuleConfig {
+            name: name.to_string(),
+            pii_type: pii_type.map(str::to_string),
+            threshold,
+            window_secs,
+            policy: None,
+            webhook: None,
+            quarantine: false,
+        }
+    }
+
+    fn evaluator(rules: Vec<AlertRuleConfig>) -> AlertEvaluator {
+        AlertEvaluator::new(Some(&AlertsConfig { rules }))
+    }
+
+    fn detection(pii_type: PiiType) -> Detection {
+        Detection {
+            pii_type,
+            column: Some("email".to_string()),
+            policy: PolicyAction::Mask,
+            severity: None,
+            confidence: None,
+        }
+    }
+
+    #[test]
+    fn test_evaluator_fires_once_past_threshold() {
+        let mut eval = evaluator(vec![alert("burst", Some("email"), 3, 60)]);
+        let t0 = Instant::now();
+
+        for i in 0..3 {
+            let fired = eval.record(&detection(PiiType::Email), t0 + Duration::from_secs(i));
+            assert!(fired.is_empty(), "fired at {} detections", i + 1);
+        }
+        let fired = eval.record(&detection(PiiType::Email), t0 + Duration::from_secs(3));
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].name, "burst");
+        assert_eq!(fired[0].detections_in_window, 4);
+
+        // Edge-triggered: while firing, further detections stay silent
+        let fired = eval.record(&detection(PiiType::Email), t0 + Duration::from_secs(4));
+        assert!(fired.is_empty());
+        let states = eval.states(t0 + Duration::from_secs(5));
+        assert!(states[0].firing);
+    }
+
+    #[test]
+    fn test_evaluator_window_expiry_clears_and_rearms() {
+        let mut eval = evaluator(vec![alert("burst", Some("email"), 1, 10)]);
+        let t0 = Instant::now();
+
+        assert!(eval.record(&detection(PiiType::Email), t0).is_empty());
+        assert_eq!(
+            eval.record(&detection(PiiType::Email), t0 + Duration::from_secs(1))
+                .len(),
+            1
+        );
+
+        // Once the window drains the alert clears...
+        let states = eval.states(t0 + Duration::from_secs(30));
+        assert!(!states[0].firing);
+        assert_eq!(states[0].detections_in_window, 0);
+
+        // ...and can fire again on a fresh burst
+        let t1 = t0 + Duration::from_secs(60);
+        assert!(eval.record(&detection(PiiType::Email), t1).is_empty());
+        assert_eq!(
+            eval.record(&detection(PiiType::Email), t1 + Duration::from_secs(1))
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_evaluator_filters_pii_type_and_policy() {
+        let mut typed = alert("emails-on-partial", Some("email"), 0, 60);
+        typed.policy = Some(PolicyAction::Partial);
+        let mut eval = evaluator(vec![typed]);
+        let t0 = Instant::now();
+
+        // Wrong detector: not counted
+        assert!(eval.record(&detection(PiiType::Ssn), t0).is_empty());
+        // Right detector, wrong policy: not counted
+        assert!(eval.record(&detection(PiiType::Email), t0).is_empty());
+
+        let mut hit = detection(PiiType::Email);
+        hit.policy = PolicyAction::Partial;
+        assert_eq!(eval.record(&hit, t0).len(), 1);
+    }
+
+    #[test]
+    fn test_evaluator_untyped_rule_counts_every_detector() {
+        let mut eval = evaluator(vec![alert("any-pii", None, 1, 60)]);
+        let t0 = Instant::now();
+
+        assert!(eval.record(&detection(PiiType::Email), t0).is_empty());
+        let fired = eval.record(&detection(PiiType::Phone), t0 + Duration::from_secs(1));
+        assert_eq!(fired.len(), 1);
+    }
+}
@@ -1,15 +1,19 @@
 use crate::audit::{AuditEventType, AuditLogger, AuditOutcome, AuthMethod};
-use crate::config::MaskingRule;
-use crate::db_scanner::{DbScanner, ScanConfig};
+use crate::config::{ApiAuthConfig, ApiRole, ApiTokenConfig, MaskingRule, StrategyChain};
+#[cfg(feature = "postgres")]
+use crate::db_scanner::{
+    DbScanner, ScanConfig, ScanError, ScanJob, ScanJobStatus, ScanProgress, SchemaInfo,
+    SchemaSnapshot,
+};
 use crate::state::AppState;
 use axum::{
     Json, Router,
     body::Body,
-    extract::State,
+    extract::{Path, State},
     http::{Request, StatusCode},
     middleware::{self, Next},
     response::{IntoResponse, Response},
-    routing::{get, post},
+    routing::{get, post, put},
 };
 use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode};
 use serde::{Deserialize, Serialize};
@@ -42,7 +46,30 @@ fn validate_jwt(token: &str, secret: &str) -> Result<Claims, jsonwebtoken::error
 }
 
 /// Middleware to validate API key or JWT for protected endpoints
-async fn api_auth(State(state): State<AppState>, request: Request<Body>, next: Next) -> Response {
+/// Lowercase hex SHA-256 of a presented token, for comparison against
+/// the configured `token_sha256` hashes
+fn sha256_hex(value: &str) -> String {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(value.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// The configured token whose hash matches the presented bearer value
+fn match_bearer_token<'a>(auth: &'a ApiAuthConfig, presented: &str) -> Option<&'a ApiTokenConfig> {
+    let hash = sha256_hex(presented);
+    auth.tokens
+        .iter()
+        .find(|token| token.token_sha256.eq_ignore_ascii_case(&hash))
+}
+
+/// Who authenticated a request, stashed in request extensions by the
+/// auth middleware so handlers can attribute audit events
+#[derive(Debug, Clone)]
+struct ApiIdentity(String);
+
+async fn api_auth(State(state): State<AppState>, mut request: Request<Body>, next: Next) -> Response {
     let config = state.config.read().await;
     let endpoint = request.uri().path().to_string();
     let method = request.method().to_string();
@@ -50,13 +77,84 @@ async fn api_auth(State(state): State<AppState>, request: Request<Body>, next: N
     let api_config = config.api.as_ref();
     let api_key = api_config.and_then(|c| c.api_key.as_ref());
     let jwt_secret = api_config.and_then(|c| c.jwt_secret.as_ref());
+    let auth_tokens = api_config.and_then(|c| c.auth.as_ref());
 
-    // If neither API key nor JWT is configured, allow all requests
-    if api_key.is_none() && jwt_secret.is_none() {
+    // If no authentication is configured, allow all requests
+    if api_key.is_none() && jwt_secret.is_none() && auth_tokens.is_none() {
         drop(config);
         return next.run(request).await;
     }
 
+    // Try static bearer tokens first; the same header may carry a JWT,
+    // which keeps its chance below when no token hash matches
+    if let Some(auth) = auth_tokens
+        && let Some(presented) = request
+            .headers()
+            .get("Authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+    {
+        if let Some(matched) = match_bearer_token(auth, presented) {
+            let name = matched.name.clone();
+            let role = matched.role;
+            drop(config);
+
+            // Read tokens may only GET; mutation requires admin
+            if role == ApiRole::Read && !matches!(method.as_str(), "GET" | "HEAD") {
+                state
+                    .audit_logger
+                    .log(
+                        AuditLogger::api_access(Some(&name), false)
+                            .with_endpoint(&endpoint)
+                            .with_method(&method),
+                    )
+                    .await;
+                return (
+                    StatusCode::FORBIDDEN,
+                    Json(json!({
+                        "error": format!("token '{}' has read-only access", name)
+                    })),
+                )
+                    .into_response();
+            }
+
+            state
+                .audit_logger
+                .log(
+                    AuditLogger::api_access(Some(&name), true)
+                        .with_endpoint(&endpoint)
+                        .with_method(&method),
+                )
+                .await;
+            // The role rides along so handlers guarding admin-only reads
+            // (GET is otherwise open to read tokens) can check it
+            request.extensions_mut().insert(role);
+            request.extensions_mut().insert(ApiIdentity(name));
+            return next.run(request).await;
+        }
+
+        // Unknown token, and no JWT secret that could still accept the
+        // header: reject without ever logging the presented value
+        if jwt_secret.is_none() {
+            drop(config);
+            state
+                .audit_logger
+                .log(
+                    AuditLogger::api_access(None, false)
+                        .with_endpoint(&endpoint)
+                        .with_method(&method),
+                )
+                .await;
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(json!({
+                    "error": "Invalid bearer token"
+                })),
+            )
+                .into_response();
+        }
+    }
+
     // Try API key authentication first
     if let Some(expected_key) = api_key
         && let Some(provided_key) = request
@@ -75,6 +173,7 @@ async fn api_auth(State(state): State<AppState>, request: Request<Body>, next: N
                         .with_method(&method),
                 )
                 .await;
+            request.extensions_mut().insert(ApiIdentity("api-key".to_string()));
             return next.run(request).await;
         } else {
             drop(config);
@@ -112,11 +211,12 @@ async fn api_auth(State(state): State<AppState>, request: Request<Body>, next: N
                 state
                     .audit_logger
                     .log(
-                        AuditLogger::auth_success(AuthMethod::Jwt, Some(claims.sub))
+                        AuditLogger::auth_success(AuthMethod::Jwt, Some(claims.sub.clone()))
                             .with_endpoint(&endpoint)
                             .with_method(&method),
                     )
                     .await;
+                request.extensions_mut().insert(ApiIdentity(claims.sub));
                 return next.run(request).await;
             }
             Err(e) => {
@@ -161,9 +261,13 @@ async fn api_auth(State(state): State<AppState>, request: Request<Body>, next: N
     let api_config = config.api.as_ref();
     let api_key = api_config.and_then(|c| c.api_key.as_ref());
     let jwt_secret = api_config.and_then(|c| c.jwt_secret.as_ref());
+    let auth_tokens = api_config.and_then(|c| c.auth.as_ref());
     let auth_methods: Vec<&str> = [
         api_key.map(|_| "X-API-Key header"),
-        jwt_secret.map(|_| "Authorization: Bearer <token>"),
+        jwt_secret
+            .map(|_| ())
+            .or(auth_tokens.map(|_| ()))
+            .map(|_| "Authorization: Bearer <token>"),
     ]
     .into_iter()
     .flatten()
@@ -179,54 +283,426 @@ async fn api_auth(State(state): State<AppState>, request: Request<Body>, next: N
         .into_response()
 }
 
-pub async fn start_api_server(port: u16, state: AppState) -> anyhow::Result<()> {
-    // Public routes (no auth required)
-    let public_routes = Router::new()
+/// Token-bucket rate limit per caller, driven by `api.rate_limit`. The
+/// limit is re-read from the live config on every request, so a reload
+/// changes it without restarting. Runs inside the auth layer, so an
+/// authenticated request is charged to its token's bucket rather than its
+/// source address — one NATed dashboard can't starve its neighbours.
+/// `/health` (and `/health/live`) stay exempt for orchestrator probes.
+async fn api_rate_limit(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    if request.uri().path().starts_with("/health") {
+        return next.run(request).await;
+    }
+    let Some(limit) = state
+        .config
+        .read()
+        .await
+        .api
+        .as_ref()
+        .and_then(|api| api.rate_limit.clone())
+    else {
+        return next.run(request).await;
+    };
+
+    let key = request
+        .extensions()
+        .get::<ApiIdentity>()
+        .map(|identity| format!("token:{}", identity.0))
+        .or_else(|| {
+            request
+                .extensions()
+                .get::<axum::extract::ConnectInfo<SocketAddr>>()
+                .map(|info| info.0.ip())
+                .or_else(|| {
+                    request
+                        .extensions()
+                        .get::<axum::extract::ConnectInfo<TlsClientAddr>>()
+                        .map(|info| info.0.0.ip())
+                })
+                .map(|ip| format!("ip:{}", ip))
+        })
+        // Reached only in direct handler tests; axum::serve always
+        // provides ConnectInfo
+        .unwrap_or_else(|| "unknown".to_string());
+
+    match take_rate_token(&state, &limit, key).await {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => {
+            crate::metrics::record_api_rate_limited();
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(axum::http::header::RETRY_AFTER, retry_after.to_string())],
+                Json(json!({
+                    "status": "error",
+                    "error": "rate limit exceeded",
+                    "retry_after_secs": retry_after
+                })),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Refill `key`'s bucket and take one token from it, or report how many
+/// whole seconds the caller should wait before retrying
+async fn take_rate_token(
+    state: &AppState,
+    limit: &crate::config::ApiRateLimitConfig,
+    key: String,
+) -> Result<(), u64> {
+    let burst = limit.burst_size();
+    let now = std::time::Instant::now();
+    let mut buckets = state.api_rate_buckets.lock().await;
+    // Callers that have been idle long enough to refill completely carry
+    // no state worth keeping; prune them before the map can grow unbounded
+    if buckets.len() >= 1024 {
+        buckets.retain(|_, bucket| {
+            now.duration_since(bucket.last_refill).as_secs_f64() * limit.requests_per_second
+                < burst
+        });
+    }
+    let bucket = buckets.entry(key).or_insert(crate::state::RateBucket {
+        tokens: burst,
+        last_refill: now,
+    });
+    let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+    bucket.tokens = (bucket.tokens + elapsed * limit.requests_per_second).min(burst);
+    bucket.last_refill = now;
+    if bucket.tokens >= 1.0 {
+        bucket.tokens -= 1.0;
+        Ok(())
+    } else {
+        Err((((1.0 - bucket.tokens) / limit.requests_per_second).ceil() as u64).max(1))
+    }
+}
+
+/// The machine-readable contract for the management API, generated from
+/// the handler annotations so it cannot drift from the code. The serde
+/// types themselves ([`MaskingRule`], [`crate::state::LogEntry`],
+/// [`crate::state::HealthStatus`]) are the source of truth for the schemas.
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    paths(
+        health_check,
+        liveness_check,
+        get_rules,
+        add_rule,
+        replace_rule,
+        remove_rule,
+        test_rule,
+        get_connections,
+        get_connection,
+        terminate_connection,
+        get_logs,
+    ),
+    components(schemas(
+        MaskingRule,
+        crate::state::LogEntry,
+        crate::state::HealthStatus
+    )),
+    tags(
+        (name = "health", description = "Liveness and upstream health"),
+        (name = "rules", description = "Masking rule CRUD and dry-runs"),
+        (name = "connections", description = "Live proxied connections"),
+        (name = "logs", description = "In-memory event log"),
+    )
+)]
+struct ApiDoc;
+
+/// The endpoints that exist only when the postgres protocol is compiled in
+#[cfg(feature = "postgres")]
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    paths(get_cached_schema, get_schema, start_scan, get_scan_status),
+    tags(
+        (name = "schema", description = "Upstream schema introspection"),
+        (name = "scan", description = "Background PII scans"),
+    )
+)]
+struct ScanDoc;
+
+/// Assemble the full document for the features this binary carries
+fn api_doc() -> utoipa::openapi::OpenApi {
+    #[allow(unused_mut)]
+    let mut doc = <ApiDoc as utoipa::OpenApi>::openapi();
+    #[cfg(feature = "postgres")]
+    doc.merge(<ScanDoc as utoipa::OpenApi>::openapi());
+    doc
+}
+
+/// `GET /openapi.json` — the OpenAPI 3 document for this server
+async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(api_doc())
+}
+
+/// `GET /docs` — interactive Swagger UI over `/openapi.json`, enabled by
+/// `api.docs`. The flag is read per request, so a reload flips it without
+/// a restart. The UI assets load from the jsdelivr CDN rather than being
+/// bundled into the binary; the spec itself never leaves this server.
+async fn api_docs(State(state): State<AppState>) -> Response {
+    let enabled = state
+        .config
+        .read()
+        .await
+        .api
+        .as_ref()
+        .is_some_and(|api| api.docs);
+    if !enabled {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "status": "error", "error": "API docs are not enabled" })),
+        )
+            .into_response();
+    }
+    axum::response::Html(
+        r##"<!DOCTYPE html>
+<html>
+<head>
+  <title>IronVeil management API</title>
+  <link rel="stylesheet" href="https://cdn.jsdelivr.net/npm/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://cdn.jsdelivr.net/npm/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });
+  </script>
+</body>
+</html>"##,
+    )
+    .into_response()
+}
+
+/// TLS wrapper over an API TCP listener. The acceptor is re-read from
+/// [`AppState`] per connection, so a config reload rotates certificates
+/// without rebinding. Handshakes run inline in the accept loop under a
+/// short timeout; the management API's connection rate is nowhere near
+/// where that serializes anything.
+struct TlsListener {
+    inner: tokio::net::TcpListener,
+    state: AppState,
+}
+
+impl axum::serve::Listener for TlsListener {
+    type Io = tokio_rustls::server::TlsStream<tokio::net::TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        loop {
+            let Ok((stream, addr)) = self.inner.accept().await else {
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                continue;
+            };
+            // A reload that removes `api.tls` cannot turn this bound
+            // listener into plaintext, so it fails closed and drops
+            // connections until a restart rebinds without TLS
+            let Some(acceptor) = self.state.api_tls_acceptor.read().await.clone() else {
+                tracing::warn!("API TLS listener has no acceptor; dropping connection");
+                continue;
+            };
+            match tokio::time::timeout(
+                std::time::Duration::from_secs(10),
+                acceptor.accept(stream),
+            )
+            .await
+            {
+                Ok(Ok(tls)) => return (tls, addr),
+                Ok(Err(e)) => {
+                    tracing::debug!("API TLS handshake from {} failed: {}", addr, e);
+                }
+                Err(_) => tracing::debug!("API TLS handshake from {} timed out", addr),
+            }
+        }
+    }
+
+    fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+/// The peer address behind [`TlsListener`]. axum only wires
+/// `ConnectInfo<SocketAddr>` up for its own listener types, so the TLS
+/// path carries this local wrapper instead; the rate limiter accepts
+/// either form.
+#[derive(Debug, Clone, Copy)]
+struct TlsClientAddr(SocketAddr);
+
+impl axum::extract::connect_info::Connected<axum::serve::IncomingStream<'_, TlsListener>>
+    for TlsClientAddr
+{
+    fn connect_info(stream: axum::serve::IncomingStream<'_, TlsListener>) -> Self {
+        TlsClientAddr(*stream.remote_addr())
+    }
+}
+
+pub async fn start_api_server(
+    port: u16,
+    bind_override: Option<std::net::IpAddr>,
+    state: AppState,
+) -> anyhow::Result<()> {
+    // Public routes (no auth required). /metrics only exists when the
+    // prometheus backend is installed; statsd pushes instead of being scraped.
+    let mut public_routes = Router::new()
         .route("/health", get(health_check))
-        .route("/metrics", get(get_metrics));
+        .route("/health/live", get(liveness_check))
+        .route("/openapi.json", get(openapi_json))
+        .route("/docs", get(api_docs));
+    if state.metrics.is_prometheus() {
+        public_routes = public_routes.route("/metrics", get(get_metrics));
+    }
+    let public_routes =
+        public_routes.layer(middleware::from_fn_with_state(state.clone(), api_rate_limit));
 
     // Protected routes (require API key or JWT if configured)
     let protected_routes = Router::new()
         .route("/rules", get(get_rules).post(add_rule))
+        .route("/rules/{id}", put(replace_rule).delete(remove_rule))
         .route("/rules/delete", post(delete_rule))
         .route("/rules/export", get(export_rules))
         .route("/rules/import", post(import_rules))
+        .route("/rules/test", post(test_rule))
         .route("/config", get(get_config).post(update_config))
         .route("/config/reload", post(reload_config))
-        .route("/scan", post(scan_database))
+        .route("/reload", post(reload_config))
+        .route("/masking", get(get_masking).put(set_masking))
         .route("/connections", get(get_connections))
+        .route(
+            "/connections/{id}",
+            get(get_connection).delete(terminate_connection),
+        )
         .route("/stats", get(get_stats))
-        .route("/schema", post(get_schema))
         .route("/logs", get(get_logs))
+        .route("/logs/stream", get(stream_logs))
         .route("/audit", get(get_audit_logs))
+        .route("/alerts", get(get_alerts))
+        .route("/alerts/clear", post(clear_quarantine));
+    // The scanner endpoints talk to the upstream with the Postgres client
+    #[cfg(feature = "postgres")]
+    let protected_routes = protected_routes
+        .route("/scan", post(start_scan))
+        .route("/scan/{id}", get(get_scan_status))
+        .route("/scan/{id}/suggested-rules", get(get_suggested_rules))
+        .route("/scan/{id}/apply-rules", post(apply_scan_rules))
+        .route("/schema", post(get_schema).get(get_cached_schema))
+        .route("/rules/match", post(match_rule));
+    // The auth layer wraps the rate limiter so the limiter sees the
+    // request's [`ApiIdentity`] and can key buckets by token
+    let protected_routes = protected_routes
+        .layer(middleware::from_fn_with_state(state.clone(), api_rate_limit))
         .layer(middleware::from_fn_with_state(state.clone(), api_auth));
 
+    let (listen_addresses, api_tls, auth_mode) = {
+        let config = state.config.read().await;
+        let api = config.api.as_ref();
+        // --api-bind beats api.bind_address beats loopback; api.addresses
+        // sidesteps all three for multi-socket setups. The default is
+        // deliberately localhost: exposing the control plane is opt-in
+        let bind_ip = bind_override
+            .or_else(|| {
+                api.and_then(|api| api.bind_address.as_ref())
+                    // Validated at config load
+                    .and_then(|bind| bind.parse().ok())
+            })
+            .unwrap_or(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST));
+        let auth_mode = match api {
+            Some(api) if api.auth.is_some() => "bearer tokens",
+            Some(api) if api.api_key.is_some() && api.jwt_secret.is_some() => "api-key or jwt",
+            Some(api) if api.api_key.is_some() => "api-key",
+            Some(api) if api.jwt_secret.is_some() => "jwt",
+            _ => "none (open)",
+        };
+        (
+            api.and_then(|api| api.addresses.clone())
+                .unwrap_or_else(|| vec![SocketAddr::new(bind_ip, port).to_string()]),
+            api.and_then(|api| api.tls.clone()).filter(|tls| tls.enabled),
+            auth_mode,
+        )
+    };
+
+    // A cert or key that does not parse must abort startup: an API that
+    // quietly fell back to plaintext would carry bearer tokens in the clear
+    let tls_enabled = api_tls.is_some();
+    if let Some(tls) = &api_tls {
+        let acceptor = crate::proxy::build_tls_acceptor(tls)
+            .map_err(|e| anyhow::anyhow!("API TLS: {}", e))?;
+        *state.api_tls_acceptor.write().await = Some(acceptor);
+    }
+
     // Combine routes
     let app = Router::new()
         .merge(public_routes)
         .merge(protected_routes)
         .layer(TraceLayer::new_for_http())
         .layer(CorsLayer::permissive())
-        .with_state(state);
+        .with_state(state.clone());
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    tracing::info!("Management API listening on {}", addr);
-
-    let listener = tokio::net::TcpListener::bind(addr)
-        .await
-        .map_err(|e| anyhow::anyhow!("Failed to bind API server to {}: {}", addr, e))?;
-    axum::serve(listener, app)
-        .await
-        .map_err(|e| anyhow::anyhow!("API server error: {}", e))?;
+    // Same dual-stack story as the proxy listeners: `api.addresses` lists
+    // one socket per family, all serving the same router
+    let mut servers = Vec::new();
+    for address in &listen_addresses {
+        let listener = tokio::net::TcpListener::bind(address)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to bind API server to {}: {}", address, e))?;
+        // Spell out the security posture so a misconfiguration is obvious
+        // in the first lines of output
+        tracing::info!(
+            "Management API listening on {} (auth: {}, tls: {})",
+            listener.local_addr()?,
+            auth_mode,
+            if tls_enabled { "on" } else { "off" }
+        );
+        // ConnectInfo gives the rate limiter a per-IP key for
+        // unauthenticated callers
+        if tls_enabled {
+            servers.push(tokio::spawn(std::future::IntoFuture::into_future(
+                axum::serve(
+                    TlsListener {
+                        inner: listener,
+                        state: state.clone(),
+                    },
+                    app.clone().into_make_service_with_connect_info::<TlsClientAddr>(),
+                ),
+            )));
+        } else {
+            servers.push(tokio::spawn(std::future::IntoFuture::into_future(
+                axum::serve(
+                    listener,
+                    app.clone().into_make_service_with_connect_info::<SocketAddr>(),
+                ),
+            )));
+        }
+    }
+    for server in servers {
+        server
+            .await?
+            .map_err(|e| anyhow::anyhow!("API server error: {}", e))?;
+    }
     Ok(())
 }
 
+#[utoipa::path(get, path = "/health", tag = "health", responses(
+    (status = 200, description = "Proxy, upstream, and masking status", body = Value)
+))]
 async fn health_check(State(state): State<AppState>) -> impl IntoResponse {
     let health_status = state.health_status.read().await;
     let active_connections = state.active_connections.load(Ordering::Relaxed);
+    let (masking_enabled, health_config) = {
+        let config = state.config.read().await;
+        (
+            config.masking_enabled,
+            config.health_check.clone().unwrap_or_default(),
+        )
+    };
 
-    let response = json!({
+    let mut response = json!({
         "status": if health_status.healthy { "ok" } else { "degraded" },
+        // During a zero-downtime upgrade the old process reports
+        // "draining" while its replacement reports "active"
+        "role": if state.is_draining() { "draining" } else { "active" },
         "service": "ironveil",
         "version": env!("CARGO_PKG_VERSION"),
         "upstream": {
@@ -235,12 +711,33 @@ async fn health_check(State(state): State<AppState>) -> impl IntoResponse {
             "last_error": health_status.last_error,
             "latency_ms": health_status.latency_ms,
             "consecutive_failures": health_status.consecutive_failures,
-            "consecutive_successes": health_status.consecutive_successes
+            "consecutive_successes": health_status.consecutive_successes,
+            // The thresholds the counters are judged against, so operators
+            // can interpret them without reading the config
+            "thresholds": {
+                "unhealthy_after": health_config.unhealthy_threshold,
+                "healthy_after": health_config.healthy_threshold,
+                "check_interval_secs": health_config.interval_secs,
+                "check_timeout_secs": health_config.timeout_secs
+            }
         },
         "connections": {
             "active": active_connections
-        }
+        },
+        "masking_enabled": masking_enabled
     });
+    // With routing configured each named upstream is checked separately;
+    // report them all so one sick tenant cluster is attributable
+    let route_health = state.route_health.read().await;
+    if !route_health.is_empty() {
+        response["routes"] = json!(*route_health);
+    }
+
+    // Masking off means data is passing through in the clear; make that
+    // impossible to miss on the endpoint monitors already poll
+    if !masking_enabled {
+        response["warning"] = json!("masking is disabled; data is passing through unmasked");
+    }
 
     if health_status.healthy {
         (StatusCode::OK, Json(response))
@@ -249,20 +746,121 @@ async fn health_check(State(state): State<AppState>) -> impl IntoResponse {
     }
 }
 
+/// Liveness only: 200 as long as the process is up, regardless of
+/// upstream health, so Kubernetes can separate liveness from the
+/// readiness signal `/health` gives
+#[utoipa::path(get, path = "/health/live", tag = "health", responses(
+    (status = 200, description = "The process is up and serving", body = Value)
+))]
+async fn liveness_check() -> Json<Value> {
+    Json(json!({
+        "status": "ok",
+        "service": "ironveil",
+        "version": env!("CARGO_PKG_VERSION")
+    }))
+}
+
+#[utoipa::path(get, path = "/rules", tag = "rules", responses(
+    (status = 200, description = "Every configured rule with its id", body = Value)
+))]
 async fn get_rules(State(state): State<AppState>) -> Json<Value> {
     let config = state.config.read().await;
     Json(json!(*config))
 }
 
+/// The first rule whose plain (table, column) selector duplicates
+/// `candidate`'s, ignoring the rule with `skip_id` (the one being
+/// replaced). Two rules conflict when they name the same table scope and
+/// the same column selector; pattern and exact selectors never conflict
+/// with each other.
+fn conflicting_rule<'a>(
+    rules: &'a [MaskingRule],
+    candidate: &MaskingRule,
+    skip_id: Option<&str>,
+) -> Option<&'a MaskingRule> {
+    rules.iter().find(|other| {
+        skip_id.is_none_or(|id| other.id.as_deref() != Some(id))
+            && other.table == candidate.table
+            && other.column_regex.is_some() == candidate.column_regex.is_some()
+            && other
+                .column_label()
+                .eq_ignore_ascii_case(candidate.column_label())
+    })
+}
+
+/// 409 response naming the rule `candidate` collides with
+fn conflict_response(conflict: &MaskingRule) -> (StatusCode, Json<Value>) {
+    (
+        StatusCode::CONFLICT,
+        Json(json!({
+            "status": "error",
+            "error": format!(
+                "a rule for {}.{} already exists{}",
+                conflict.table.as_deref().unwrap_or("*"),
+                conflict.column_label(),
+                conflict
+                    .id
+                    .as_deref()
+                    .map(|id| format!(" (id {})", id))
+                    .unwrap_or_default()
+            )
+        })),
+    )
+}
+
+/// Creates a rule. Rules are consulted through the shared config on
+/// every result set, so new and existing connections both pick up the
+/// change immediately.
+#[utoipa::path(post, path = "/rules", tag = "rules", request_body = MaskingRule, responses(
+    (status = 200, description = "The stored rule with its assigned id", body = Value),
+    (status = 400, description = "Unknown strategy or broken selector regex"),
+    (status = 409, description = "Another rule already holds the selector")
+))]
 async fn add_rule(
     State(state): State<AppState>,
-    Json(rule): Json<MaskingRule>,
+    Json(mut rule): Json<MaskingRule>,
 ) -> impl IntoResponse {
+    // Reject unknown strategies and broken regex selectors before
+    // touching the config
+    if let Err(e) = rule
+        .strategy
+        .validate(&[])
+        .and_then(|()| rule.validate_regexes())
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "status": "error", "error": e.to_string() })),
+        );
+    }
+
     let mut config = state.config.write().await;
+
+    // Refuse a second rule for the same (table, column) selector
+    if let Some(conflict) = conflicting_rule(&config.rules, &rule, None) {
+        return conflict_response(conflict);
+    }
+
+    // Assign a fresh id unless the caller supplied one; reject collisions
+    // with existing rules either way
+    let rule_id = rule
+        .id
+        .get_or_insert_with(|| uuid::Uuid::new_v4().to_string())
+        .clone();
+    if config.rules.iter().any(|r| r.id.as_deref() == Some(&rule_id)) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "status": "error",
+                "error": format!("duplicate rule id '{}' (rule ids must be unique)", rule_id)
+            })),
+        );
+    }
+
     let rule_json = serde_json::to_value(&rule).unwrap_or_default();
     config.rules.push(rule);
     let rules_count = config.rules.len();
     drop(config);
+    state.bump_ruleset_generation();
 
     // Persist to file
     if let Err(e) = state.save_config().await {
@@ -280,69 +878,82 @@ async fn add_rule(
     // Log audit event
     state
         .audit_logger
-        .log(AuditLogger::rule_added(rule_json))
+        .log(AuditLogger::rule_added(rule_json.clone()))
         .await;
 
     (
         StatusCode::OK,
-        Json(json!({ "status": "success", "rules_count": rules_count })),
+        Json(json!({
+            "status": "success",
+            "id": rule_id,
+            "rule": rule_json,
+            "rules_count": rules_count
+        })),
     )
 }
 
-/// Delete rule request payload
-#[derive(Debug, Deserialize, Serialize)]
-struct DeleteRuleRequest {
-    /// Index of the rule to delete (0-based)
-    index: Option<usize>,
-    /// Or match by column name
-    column: Option<String>,
-    /// And optionally by table name
-    table: Option<String>,
-}
-
-async fn delete_rule(
+/// Replaces the rule with the given id wholesale. Like `add_rule`, the
+/// change is visible to new and existing connections on their next
+/// result set.
+#[utoipa::path(put, path = "/rules/{id}", tag = "rules",
+    params(("id" = String, Path, description = "Rule id")),
+    request_body = MaskingRule,
+    responses(
+        (status = 200, description = "The replacement rule under the same id", body = Value),
+        (status = 404, description = "No rule with this id")
+    )
+)]
+async fn replace_rule(
     State(state): State<AppState>,
-    Json(req): Json<DeleteRuleRequest>,
+    Path(id): Path<String>,
+    Json(mut rule): Json<MaskingRule>,
 ) -> impl IntoResponse {
-    let mut config = state.config.write().await;
-
-    let original_len = config.rules.len();
-    let delete_details = serde_json::to_value(&req).unwrap_or_default();
+    if let Err(e) = rule
+        .strategy
+        .validate(&[])
+        .and_then(|()| rule.validate_regexes())
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "status": "error", "error": e.to_string() })),
+        );
+    }
 
-    if let Some(index) = req.index {
-        if index >= config.rules.len() {
-            return (
-                StatusCode::NOT_FOUND,
-                Json(json!({
-                    "status": "error",
-                    "error": format!("Rule index {} out of bounds (have {} rules)", index, config.rules.len())
-                })),
-            );
-        }
-        config.rules.remove(index);
-    } else if let Some(ref column) = req.column {
-        config.rules.retain(|rule| {
-            let column_matches = &rule.column != column;
-            let table_matches = req
-                .table
-                .as_ref()
-                .map(|t| rule.table.as_ref() != Some(t))
-                .unwrap_or(true);
-            column_matches || !table_matches
-        });
-    } else {
+    // An id in the body must agree with the path; absent means keep it
+    if rule.id.as_deref().is_some_and(|body_id| body_id != id) {
         return (
             StatusCode::BAD_REQUEST,
             Json(json!({
                 "status": "error",
-                "error": "Must provide either 'index' or 'column' to identify rule to delete"
+                "error": "rule id in body does not match the path"
             })),
         );
     }
+    rule.id = Some(id.clone());
 
-    let deleted_count = original_len - config.rules.len();
-    let rules_count = config.rules.len();
+    let mut config = state.config.write().await;
+    let Some(pos) = config
+        .rules
+        .iter()
+        .position(|r| r.id.as_deref() == Some(id.as_str()))
+    else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "status": "error",
+                "error": format!("No rule with id '{}'", id)
+            })),
+        );
+    };
+    if let Some(conflict) = conflicting_rule(&config.rules, &rule, Some(&id)) {
+        return conflict_response(conflict);
+    }
+
+    let old = std::mem::replace(&mut config.rules[pos], rule);
+    let old_json = serde_json::to_value(&old).unwrap_or_default();
+    let new_json = serde_json::to_value(&config.rules[pos]).unwrap_or_default();
     drop(config);
+    state.bump_ruleset_generation();
 
     // Persist to file
     if let Err(e) = state.save_config().await {
@@ -351,60 +962,514 @@ async fn delete_rule(
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(json!({
                 "status": "error",
-                "error": format!("Failed to persist changes: {}", e)
+                "error": format!("Failed to persist rule: {}", e)
             })),
         );
     }
 
-    // Log audit event
+    // A replace is a delete of the old form plus an add of the new one
     state
         .audit_logger
-        .log(AuditLogger::rule_deleted(json!({
-            "request": delete_details,
-            "deleted_count": deleted_count
-        })))
+        .log(AuditLogger::rule_deleted(json!({ "replaced": old_json })))
+        .await;
+    state
+        .audit_logger
+        .log(AuditLogger::rule_added(new_json.clone()))
         .await;
 
     (
         StatusCode::OK,
-        Json(json!({
-            "status": "success",
-            "deleted": deleted_count,
-            "rules_count": rules_count
-        })),
+        Json(json!({ "status": "success", "rule": new_json })),
     )
 }
 
-/// Export rules as JSON
-async fn export_rules(State(state): State<AppState>) -> impl IntoResponse {
-    let config = state.config.read().await;
-    let rules_json =
-        serde_json::to_string_pretty(&config.rules).unwrap_or_else(|_| "[]".to_string());
-
-    (
-        StatusCode::OK,
-        [
-            ("content-type", "application/json"),
-            (
-                "content-disposition",
-                "attachment; filename=\"ironveil-rules.json\"",
-            ),
-        ],
-        rules_json,
+/// Deletes the rule with the given id: the REST form of
+/// `POST /rules/delete`, returning the removed rule
+#[utoipa::path(delete, path = "/rules/{id}", tag = "rules",
+    params(("id" = String, Path, description = "Rule id")),
+    responses(
+        (status = 200, description = "The removed rule", body = Value),
+        (status = 404, description = "No rule with this id")
     )
-}
-
-/// Import rules from JSON
-async fn import_rules(
-    State(state): State<AppState>,
-    Json(rules): Json<Vec<MaskingRule>>,
-) -> impl IntoResponse {
+)]
+async fn remove_rule(State(state): State<AppState>, Path(id): Path<String>) -> impl IntoResponse {
     let mut config = state.config.write().await;
-    let imported_count = rules.len();
-    config.rules.extend(rules);
-    let total_count = config.rules.len();
-    drop(config);
-
+    let Some(pos) = config
+        .rules
+        .iter()
+        .position(|r| r.id.as_deref() == Some(id.as_str()))
+    else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "status": "error",
+                "error": format!("No rule with id '{}'", id)
+            })),
+        );
+    };
+
+    let removed = config.rules.remove(pos);
+    let removed_json = serde_json::to_value(&removed).unwrap_or_default();
+    let rules_count = config.rules.len();
+    drop(config);
+    state.bump_ruleset_generation();
+
+    // Persist to file
+    if let Err(e) = state.save_config().await {
+        tracing::error!("Failed to save config: {}", e);
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "status": "error",
+                "error": format!("Failed to persist changes: {}", e)
+            })),
+        );
+    }
+
+    // Log audit event
+    state
+        .audit_logger
+        .log(AuditLogger::rule_deleted(removed_json.clone()))
+        .await;
+
+    (
+        StatusCode::OK,
+        Json(json!({
+            "status": "success",
+            "rule": removed_json,
+            "rules_count": rules_count
+        })),
+    )
+}
+
+/// Delete rule request payload
+#[derive(Debug, Deserialize, Serialize)]
+struct DeleteRuleRequest {
+    /// Stable id of the rule to delete (preferred)
+    id: Option<String>,
+    /// Or index of the rule to delete (0-based)
+    index: Option<usize>,
+    /// Or match by column name
+    column: Option<String>,
+    /// And optionally by table name
+    table: Option<String>,
+}
+
+async fn delete_rule(
+    State(state): State<AppState>,
+    Json(req): Json<DeleteRuleRequest>,
+) -> impl IntoResponse {
+    let mut config = state.config.write().await;
+
+    let original_len = config.rules.len();
+    let delete_details = serde_json::to_value(&req).unwrap_or_default();
+
+    if let Some(ref id) = req.id {
+        let Some(pos) = config
+            .rules
+            .iter()
+            .position(|rule| rule.id.as_deref() == Some(id.as_str()))
+        else {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "status": "error",
+                    "error": format!("No rule with id '{}'", id)
+                })),
+            );
+        };
+        config.rules.remove(pos);
+    } else if let Some(index) = req.index {
+        if index >= config.rules.len() {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "status": "error",
+                    "error": format!("Rule index {} out of bounds (have {} rules)", index, config.rules.len())
+                })),
+            );
+        }
+        config.rules.remove(index);
+    } else if let Some(ref column) = req.column {
+        config.rules.retain(|rule| {
+            let column_matches = &rule.column != column;
+            let table_matches = req
+                .table
+                .as_ref()
+                .map(|t| rule.table.as_ref() != Some(t))
+                .unwrap_or(true);
+            column_matches || !table_matches
+        });
+    } else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "status": "error",
+                "error": "Must provide 'id', 'index', or 'column' to identify rule to delete"
+            })),
+        );
+    }
+
+    let deleted_count = original_len - config.rules.len();
+    let rules_count = config.rules.len();
+    drop(config);
+    if deleted_count > 0 {
+        state.bump_ruleset_generation();
+    }
+
+    // Persist to file
+    if let Err(e) = state.save_config().await {
+        tracing::error!("Failed to save config: {}", e);
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "status": "error",
+                "error": format!("Failed to persist changes: {}", e)
+            })),
+        );
+    }
+
+    // Log audit event
+    state
+        .audit_logger
+        .log(AuditLogger::rule_deleted(json!({
+            "request": delete_details,
+            "deleted_count": deleted_count
+        })))
+        .await;
+
+    (
+        StatusCode::OK,
+        Json(json!({
+            "status": "success",
+            "deleted": deleted_count,
+            "rules_count": rules_count
+        })),
+    )
+}
+
+/// Export rules as JSON
+/// Query parameters for [`export_rules`]
+#[derive(Debug, Deserialize)]
+struct ExportQuery {
+    #[serde(default)]
+    format: RulesFormat,
+}
+
+/// Serialization of the rules array on export, and what import accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+enum RulesFormat {
+    #[default]
+    Json,
+    Yaml,
+}
+
+/// Exports the rules array, as JSON (default) or YAML, in exactly the
+/// shape `POST /rules/import` accepts
+async fn export_rules(
+    State(state): State<AppState>,
+    axum::extract::Query(query): axum::extract::Query<ExportQuery>,
+) -> impl IntoResponse {
+    let config = state.config.read().await;
+    let (content_type, disposition, body) = match query.format {
+        RulesFormat::Json => (
+            "application/json",
+            "attachment; filename=\"ironveil-rules.json\"",
+            serde_json::to_string_pretty(&config.rules).unwrap_or_else(|_| "[]".to_string()),
+        ),
+        RulesFormat::Yaml => (
+            "application/yaml",
+            "attachment; filename=\"ironveil-rules.yaml\"",
+            serde_yaml::to_string(&config.rules).unwrap_or_else(|_| "[]".to_string()),
+        ),
+    };
+
+    (
+        StatusCode::OK,
+        [
+            ("content-type", content_type),
+            ("content-disposition", disposition),
+        ],
+        body,
+    )
+}
+
+/// Request body for [`test_rule`]: either a sample value plus the strategy
+/// (or chain of strategies) to run it through, or an existing rule
+/// referenced by id applied to several sample values
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TestRuleRequest {
+    Inline {
+        value: String,
+        strategy: StrategyChain,
+        /// Tuning knobs in the same shape a rule body accepts
+        /// (`date_shift_days`, `noise_percent`, `ip_mode`, ...)
+        #[serde(default)]
+        options: Option<Value>,
+    },
+    Existing { rule_id: String, values: Vec<String> },
+}
+
+/// Dry-run a strategy chain against sample values. The response lists every
+/// stage with its output so chains can be debugged one stage at a time; the
+/// last stage's output is what a real result set would carry. The preview
+/// runs through the interceptor's own dispatch, so it cannot drift from what
+/// a live session produces. Nothing is persisted, no config is touched, and
+/// the submitted values never reach the event log or the audit trail.
+#[utoipa::path(post, path = "/rules/test", tag = "rules", request_body = Value, responses(
+    (status = 200, description = "The masked value(s) with per-stage outputs", body = Value),
+    (status = 400, description = "Unknown strategy or malformed options"),
+    (status = 404, description = "No rule with the referenced rule_id")
+))]
+async fn test_rule(
+    State(state): State<AppState>,
+    Json(req): Json<TestRuleRequest>,
+) -> impl IntoResponse {
+    let (chain, tuning, values) = match req {
+        TestRuleRequest::Inline {
+            value,
+            strategy,
+            options,
+        } => {
+            if let Err(e) = strategy.validate(&state.strategy_registry.names()) {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({ "status": "error", "error": e.to_string() })),
+                );
+            }
+            let tuning = match options {
+                // Options pass through the rule deserializer itself, so the
+                // accepted names and shapes are exactly what a rule takes
+                Some(Value::Object(mut fields)) => {
+                    fields.insert(
+                        "strategy".to_string(),
+                        serde_json::to_value(&strategy).unwrap_or_default(),
+                    );
+                    match serde_json::from_value::<MaskingRule>(Value::Object(fields)) {
+                        Ok(rule) => crate::interceptor::StrategyTuning::from_rule(&rule),
+                        Err(e) => {
+                            return (
+                                StatusCode::BAD_REQUEST,
+                                Json(json!({
+                                    "status": "error",
+                                    "error": format!("invalid options: {}", e)
+                                })),
+                            );
+                        }
+                    }
+                }
+                Some(_) => {
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        Json(json!({ "status": "error", "error": "options must be an object" })),
+                    );
+                }
+                None => crate::interceptor::StrategyTuning::default(),
+            };
+            (strategy, tuning, vec![value])
+        }
+        TestRuleRequest::Existing { rule_id, values } => {
+            let config = state.config.read().await;
+            let Some(rule) = config
+                .rules
+                .iter()
+                .find(|r| r.id.as_deref() == Some(rule_id.as_str()))
+            else {
+                return (
+                    StatusCode::NOT_FOUND,
+                    Json(json!({
+                        "status": "error",
+                        "error": format!("no rule with id '{}'", rule_id)
+                    })),
+                );
+            };
+            (
+                rule.strategy.clone(),
+                crate::interceptor::StrategyTuning::from_rule(rule),
+                values,
+            )
+        }
+    };
+
+    // The preview hashes with the live key, so the shown token matches
+    // what analysts will see in masked result sets
+    let hashing = crate::interceptor::HashSpec::from_config(&*state.config.read().await);
+    let results: Vec<Value> = values
+        .iter()
+        .map(|value| {
+            // Seeded the same way the interceptor seeds a cell, so the
+            // preview matches what a live session would see for this value
+            let seed = crate::interceptor::value_seed(value.as_bytes());
+            let outputs = crate::interceptor::mask_chain_stages(
+                &state.strategy_registry,
+                &hashing,
+                &tuning,
+                &chain,
+                value,
+                seed,
+            );
+            let stages: Vec<Value> = chain
+                .stages()
+                .iter()
+                .zip(&outputs)
+                .map(|(stage, output)| json!({ "strategy": stage.as_str(), "output": output }))
+                .collect();
+            json!({ "masked": outputs.last(), "stages": stages })
+        })
+        .collect();
+
+    // The single-value form keeps its flat shape; the batch form wraps each
+    // sample's result in order
+    let body = match results.as_slice() {
+        [only] => json!({
+            "status": "ok",
+            "masked": only["masked"],
+            "stages": only["stages"],
+        }),
+        _ => json!({ "status": "ok", "results": results }),
+    };
+    (StatusCode::OK, Json(body))
+}
+
+/// Query parameters for [`import_rules`]
+#[derive(Debug, Deserialize)]
+struct ImportQuery {
+    /// `merge` (default) appends the batch, skipping rules identical to
+    /// an existing one; `replace` swaps the whole ruleset
+    #[serde(default)]
+    mode: ImportMode,
+    /// Validate and report what would change without writing anything
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+enum ImportMode {
+    #[default]
+    Merge,
+    Replace,
+}
+
+/// A rule's serialized form with the id stripped, for exact-duplicate
+/// detection during a merge import
+fn rule_fingerprint(rule: &MaskingRule) -> Value {
+    let mut value = serde_json::to_value(rule).unwrap_or_default();
+    if let Some(obj) = value.as_object_mut() {
+        obj.remove("id");
+    }
+    value
+}
+
+/// Import rules from a JSON or YAML rules array (the `export_rules`
+/// shape). The whole batch is validated before anything is applied.
+async fn import_rules(
+    State(state): State<AppState>,
+    axum::extract::Query(query): axum::extract::Query<ImportQuery>,
+    body: String,
+) -> impl IntoResponse {
+    // Accept either serialization of the export shape: a JSON array
+    // starts with '[', anything else is parsed as YAML
+    let parsed: Result<Vec<MaskingRule>, String> = if body.trim_start().starts_with('[') {
+        serde_json::from_str(&body).map_err(|e| e.to_string())
+    } else {
+        serde_yaml::from_str(&body).map_err(|e| e.to_string())
+    };
+    let rules = match parsed {
+        Ok(rules) => rules,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "status": "error",
+                    "error": format!("body is not a JSON or YAML rules array: {}", e)
+                })),
+            );
+        }
+    };
+
+    // Reject the whole batch if any rule has an unknown strategy or a
+    // broken regex selector
+    for rule in &rules {
+        if let Err(e) = rule
+            .strategy
+            .validate(&[])
+            .and_then(|()| rule.validate_regexes())
+        {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "status": "error", "error": e.to_string() })),
+            );
+        }
+    }
+
+    let mut config = state.config.write().await;
+    let original_rules = config.rules.clone();
+    let original_len = original_rules.len();
+
+    // Merge skips rules identical (modulo id) to one already present, so
+    // re-importing an export is a no-op rather than a duplication
+    let mut skipped = Vec::new();
+    match query.mode {
+        ImportMode::Replace => {
+            config.rules = rules;
+        }
+        ImportMode::Merge => {
+            let mut fingerprints: Vec<Value> =
+                config.rules.iter().map(rule_fingerprint).collect();
+            for rule in rules {
+                let fingerprint = rule_fingerprint(&rule);
+                if fingerprints.contains(&fingerprint) {
+                    skipped.push(format!(
+                        "{}.{}",
+                        rule.table.as_deref().unwrap_or("*"),
+                        rule.column_label()
+                    ));
+                } else {
+                    fingerprints.push(fingerprint);
+                    config.rules.push(rule);
+                }
+            }
+        }
+    }
+
+    // Exported rules keep their ids, so a round-trip preserves references;
+    // rules without ids get fresh ones, and id collisions reject the batch
+    if let Err(e) = config.ensure_rule_ids() {
+        config.rules = original_rules;
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "status": "error", "error": e.to_string() })),
+        );
+    }
+
+    let total_count = config.rules.len();
+    let imported_count = match query.mode {
+        ImportMode::Replace => total_count,
+        ImportMode::Merge => total_count - original_len,
+    };
+
+    // A dry run stops here: report what would change, write nothing
+    if query.dry_run {
+        config.rules = original_rules;
+        return (
+            StatusCode::OK,
+            Json(json!({
+                "status": "success",
+                "dry_run": true,
+                "mode": format!("{:?}", query.mode).to_lowercase(),
+                "would_import": imported_count,
+                "skipped_duplicates": skipped,
+                "rules_count_after": total_count
+            })),
+        );
+    }
+
+    drop(config);
+    state.bump_ruleset_generation();
+
     // Persist to file
     if let Err(e) = state.save_config().await {
         tracing::error!("Failed to save config: {}", e);
@@ -427,17 +1492,92 @@ async fn import_rules(
         StatusCode::OK,
         Json(json!({
             "status": "success",
+            "mode": format!("{:?}", query.mode).to_lowercase(),
             "imported": imported_count,
+            "skipped_duplicates": skipped,
             "rules_count": total_count
         })),
     )
 }
 
+/// Body for `PUT /masking`
+#[derive(Debug, Deserialize)]
+struct MaskingToggle {
+    enabled: bool,
+}
+
+/// Query parameters for `PUT /masking`
+#[derive(Debug, Deserialize)]
+struct MaskingToggleQuery {
+    /// Also write the flipped flag to the config file
+    #[serde(default)]
+    persist: bool,
+}
+
+/// Current state of the global masking switch
+async fn get_masking(State(state): State<AppState>) -> Json<Value> {
+    let config = state.config.read().await;
+    Json(json!({ "masking_enabled": config.masking_enabled }))
+}
+
+/// Flip the global masking switch on the live config, for confirming
+/// during an incident whether the proxy is the source of odd data
+/// without a restart. `persist=true` also writes the flag to the config
+/// file; by default the change lasts until the next reload. Every toggle
+/// is audited as a ConfigChange with the actor that requested it.
+async fn set_masking(
+    State(state): State<AppState>,
+    identity: Option<axum::Extension<ApiIdentity>>,
+    axum::extract::Query(query): axum::extract::Query<MaskingToggleQuery>,
+    Json(body): Json<MaskingToggle>,
+) -> impl IntoResponse {
+    let old_value = {
+        let mut config = state.config.write().await;
+        std::mem::replace(&mut config.masking_enabled, body.enabled)
+    };
+
+    let mut entry = AuditLogger::config_change(json!({
+        "masking_enabled": { "old": old_value, "new": body.enabled },
+        "persist": query.persist
+    }));
+    if let Some(axum::Extension(ApiIdentity(user))) = identity {
+        entry = entry.with_user_id(user);
+    }
+    state.audit_logger.log(entry).await;
+
+    if query.persist
+        && let Err(e) = state.save_config().await
+    {
+        tracing::error!("Failed to save config: {}", e);
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "status": "error",
+                "error": format!(
+                    "masking_enabled changed on the live config but persisting failed: {}",
+                    e
+                ),
+                "masking_enabled": body.enabled
+            })),
+        );
+    }
+
+    (
+        StatusCode::OK,
+        Json(json!({
+            "status": "success",
+            "masking_enabled": body.enabled,
+            "persisted": query.persist
+        })),
+    )
+}
+
 async fn get_config(State(state): State<AppState>) -> Json<Value> {
     let config = state.config.read().await;
     Json(json!({
         "masking_enabled": config.masking_enabled,
-        "rules_count": config.rules.len()
+        "rules_count": config.rules.len(),
+        "ruleset_generation": state.current_ruleset_generation()
     }))
 }
 
@@ -470,71 +1610,507 @@ async fn update_config(State(state): State<AppState>, Json(payload): Json<Value>
     Json(json!({ "status": "success", "masking_enabled": config.masking_enabled }))
 }
 
-/// Reload configuration from disk
-async fn reload_config(State(state): State<AppState>) -> impl IntoResponse {
+/// Query parameters for `POST /reload`
+#[derive(Debug, Deserialize)]
+struct ReloadQuery {
+    /// Load and validate the on-disk file without swapping it in
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Rules keyed by their selector (`table.column`), for diffing two
+/// configs: the same selector with a different body counts as changed
+fn rules_by_selector(rules: &[MaskingRule]) -> std::collections::BTreeMap<String, Value> {
+    rules
+        .iter()
+        .map(|rule| {
+            let key = format!(
+                "{}.{}",
+                rule.table.as_deref().unwrap_or("*"),
+                rule.column_label()
+            );
+            (key, rule_fingerprint(rule))
+        })
+        .collect()
+}
+
+/// Which rule selectors a reload added, removed, or changed
+fn diff_rules(old: &[MaskingRule], new: &[MaskingRule]) -> Value {
+    let old = rules_by_selector(old);
+    let new = rules_by_selector(new);
+    let added: Vec<&String> = new.keys().filter(|k| !old.contains_key(*k)).collect();
+    let removed: Vec<&String> = old.keys().filter(|k| !new.contains_key(*k)).collect();
+    let changed: Vec<&String> = new
+        .iter()
+        .filter(|(key, fingerprint)| old.get(*key).is_some_and(|o| o != *fingerprint))
+        .map(|(key, _)| key)
+        .collect();
+    json!({ "added": added, "removed": removed, "changed": changed })
+}
+
+/// Reload configuration from disk, reporting the rule diff against the
+/// config it replaced. `dry_run=true` loads, validates, and stage-builds
+/// the file without swapping it in, so a deploy pipeline can vet a config
+/// change before flipping it live. A failed reload answers 400 and leaves
+/// the running config untouched, which the response states explicitly.
+async fn reload_config(
+    State(state): State<AppState>,
+    axum::extract::Query(query): axum::extract::Query<ReloadQuery>,
+) -> impl IntoResponse {
+    let old_rules = state.config.read().await.rules.clone();
+
+    if query.dry_run {
+        let path = state.config_path.as_ref();
+        let result = crate::config::AppConfig::load(path)
+            .map_err(|e| format!("Failed to load config from {}: {}", path, e))
+            .and_then(|config| {
+                crate::state::StagedArtifacts::build(&config)
+                    .map_err(|e| format!("Failed to apply config from {}: {}", path, e))?;
+                Ok(config)
+            });
+        return match result {
+            Ok(new_config) => (
+                StatusCode::OK,
+                Json(json!({
+                    "status": "success",
+                    "dry_run": true,
+                    "rules_count": new_config.rules.len(),
+                    "diff": diff_rules(&old_rules, &new_config.rules),
+                    "config_unchanged": true
+                })),
+            ),
+            Err(e) => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "status": "error",
+                    "dry_run": true,
+                    "error": e,
+                    "config_unchanged": true
+                })),
+            ),
+        };
+    }
+
     match state.reload_config().await {
         Ok(rules_count) => {
-            // Log audit event
+            let new_rules = state.config.read().await.rules.clone();
             state
                 .audit_logger
-                .log(AuditLogger::config_reload(rules_count))
+                .log(AuditLogger::config_reload(rules_count, "api"))
                 .await;
             (
                 StatusCode::OK,
                 Json(json!({
                     "status": "success",
                     "message": "Configuration reloaded successfully",
-                    "rules_count": rules_count
+                    "rules_count": rules_count,
+                    "diff": diff_rules(&old_rules, &new_rules)
+                })),
+            )
+        }
+        Err(e) => {
+            state
+                .audit_logger
+                .log(AuditLogger::config_reload_failed(&e, "api"))
+                .await;
+            (
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "status": "error",
+                    "error": e,
+                    "config_unchanged": true
                 })),
             )
         }
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({
-                "status": "error",
-                "error": e
-            })),
-        ),
     }
 }
 
-async fn scan_database(
+/// Starts a background scan of the upstream database and returns its id
+/// immediately; progress and findings are served by [`get_scan_status`].
+/// Only one scan runs at a time: a POST while one is in flight gets 409
+/// with the running scan's id.
+#[cfg(feature = "postgres")]
+#[utoipa::path(post, path = "/scan", tag = "scan", request_body = Value, responses(
+    (status = 202, description = "Scan accepted; poll /scan/{id}", body = Value),
+    (status = 409, description = "Another scan is already running")
+))]
+async fn start_scan(
     State(state): State<AppState>,
     Json(config): Json<ScanConfig>,
 ) -> impl IntoResponse {
+    let mut jobs = state.scan_jobs.write().await;
+    if let Some(running) = jobs
+        .values()
+        .find(|job| job.status == ScanJobStatus::Running)
+    {
+        return (
+            StatusCode::CONFLICT,
+            Json(json!({
+                "status": "error",
+                "error": "a scan is already running",
+                "scan_id": running.id
+            })),
+        );
+    }
+
+    let scanner_config = state.config.read().await.scanner.clone();
     let scanner = DbScanner::new(
         state.upstream_host.to_string(),
         state.upstream_port,
         state.db_protocol,
+    )
+    .with_version(state.get_upstream_version().await)
+    .with_scanner_config(scanner_config.as_ref());
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let progress = std::sync::Arc::new(ScanProgress::default());
+    jobs.insert(
+        id.clone(),
+        ScanJob {
+            id: id.clone(),
+            database: config.database.clone(),
+            started_at: chrono::Utc::now(),
+            status: ScanJobStatus::Running,
+            progress: progress.clone(),
+            result: None,
+            error: None,
+        },
     );
+    drop(jobs);
 
-    match scanner.scan(&config).await {
-        Ok(result) => {
-            // Log audit event
-            state
+    let task_state = state.clone();
+    let task_id = id.clone();
+    tokio::spawn(async move {
+        let outcome = scanner.scan_with_progress(&config, Some(&progress)).await;
+        if let Ok(result) = &outcome {
+            task_state
                 .audit_logger
                 .log(AuditLogger::database_scan(
                     &config.database,
                     result.findings.len(),
                 ))
                 .await;
-            (StatusCode::OK, Json(json!(result)))
         }
-        Err(e) => (
+        let mut jobs = task_state.scan_jobs.write().await;
+        if let Some(job) = jobs.get_mut(&task_id) {
+            match outcome {
+                Ok(result) => {
+                    job.status = ScanJobStatus::Completed;
+                    job.result = Some(result);
+                }
+                Err(e) => {
+                    job.status = ScanJobStatus::Failed;
+                    job.error = Some(e.to_string());
+                }
+            }
+        }
+    });
+
+    (
+        StatusCode::ACCEPTED,
+        Json(json!({ "status": "started", "scan_id": id })),
+    )
+}
+
+/// Reports a background scan: progress (tables done / total) while it
+/// runs, the full findings once it completes. Findings carry masked
+/// sample values only, never raw cell contents.
+#[cfg(feature = "postgres")]
+#[utoipa::path(get, path = "/scan/{id}", tag = "scan",
+    params(("id" = String, Path, description = "Scan job id")),
+    responses(
+        (status = 200, description = "Progress or findings of the scan", body = Value),
+        (status = 404, description = "No scan job with this id")
+    )
+)]
+async fn get_scan_status(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let jobs = state.scan_jobs.read().await;
+    let Some(job) = jobs.get(&id) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "status": "error",
+                "error": format!("no scan with id {}", id)
+            })),
+        );
+    };
+
+    let mut body = json!({
+        "scan_id": job.id,
+        "status": job.status,
+        "database": job.database,
+        "started_at": job.started_at,
+        "tables_total": job.progress.tables_total.load(Ordering::Relaxed),
+        "tables_done": job.progress.tables_done.load(Ordering::Relaxed),
+    });
+    if let Some(result) = &job.result {
+        body["result"] = json!(result);
+    }
+    if let Some(error) = &job.error {
+        body["error"] = json!(error);
+    }
+    (StatusCode::OK, Json(body))
+}
+
+/// Query parameters for `GET /scan/{id}/suggested-rules`
+#[cfg(feature = "postgres")]
+#[derive(Debug, Deserialize)]
+struct SuggestedRulesQuery {
+    /// Minimum detection rate (matches / sampled rows) for a finding to
+    /// become a suggestion
+    #[serde(default = "default_suggestion_threshold")]
+    threshold: f64,
+}
+
+#[cfg(feature = "postgres")]
+fn default_suggestion_threshold() -> f64 {
+    0.5
+}
+
+/// Turns a completed scan's findings into suggested masking rules, one
+/// per detected column not already covered by an existing rule. The
+/// response carries the rules both as JSON and as rules YAML ready to
+/// paste into the config file.
+#[cfg(feature = "postgres")]
+async fn get_suggested_rules(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    axum::extract::Query(query): axum::extract::Query<SuggestedRulesQuery>,
+) -> impl IntoResponse {
+    let jobs = state.scan_jobs.read().await;
+    let Some(job) = jobs.get(&id) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "status": "error",
+                "error": format!("no scan with id {}", id)
+            })),
+        );
+    };
+    let Some(result) = &job.result else {
+        return (
+            StatusCode::CONFLICT,
+            Json(json!({
+                "status": "error",
+                "error": format!("scan {} has not completed", id)
+            })),
+        );
+    };
+
+    let config = state.config.read().await;
+    let rules = result.suggested_rules(query.threshold, &config.rules);
+    drop(config);
+
+    let yaml = serde_yaml::to_string(&rules).unwrap_or_default();
+    (
+        StatusCode::OK,
+        Json(json!({
+            "scan_id": id,
+            "threshold": query.threshold,
+            "count": rules.len(),
+            "rules": rules,
+            "yaml": yaml
+        })),
+    )
+}
+
+/// Merges accepted rule suggestions into the live config and persists
+/// them, emitting a RuleAdded audit event for each
+#[cfg(feature = "postgres")]
+async fn apply_scan_rules(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(rules): Json<Vec<MaskingRule>>,
+) -> impl IntoResponse {
+    if !state.scan_jobs.read().await.contains_key(&id) {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "status": "error",
+                "error": format!("no scan with id {}", id)
+            })),
+        );
+    }
+
+    // Reject the whole batch if any rule has an unknown strategy or a
+    // broken regex selector
+    for rule in &rules {
+        if let Err(e) = rule
+            .strategy
+            .validate(&[])
+            .and_then(|()| rule.validate_regexes())
+        {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "status": "error", "error": e.to_string() })),
+            );
+        }
+    }
+
+    let mut config = state.config.write().await;
+    let applied_count = rules.len();
+    let original_len = config.rules.len();
+    config.rules.extend(rules);
+
+    // Suggestions come without ids; assign fresh ones, and reject the
+    // batch on a collision like an import would
+    if let Err(e) = config.ensure_rule_ids() {
+        config.rules.truncate(original_len);
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "status": "error", "error": e.to_string() })),
+        );
+    }
+
+    // Capture after id assignment so the audit trail has the final form
+    let applied: Vec<Value> = config.rules[original_len..]
+        .iter()
+        .map(|rule| serde_json::to_value(rule).unwrap_or_default())
+        .collect();
+    let total_count = config.rules.len();
+    drop(config);
+    state.bump_ruleset_generation();
+
+    // Persist to file
+    if let Err(e) = state.save_config().await {
+        tracing::error!("Failed to save config: {}", e);
+        return (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(json!({
                 "status": "error",
-                "error": e.to_string()
+                "error": format!("Failed to persist applied rules: {}", e)
+            })),
+        );
+    }
+
+    // Log an audit event per accepted rule
+    for rule_json in applied {
+        state
+            .audit_logger
+            .log(AuditLogger::rule_added(rule_json))
+            .await;
+    }
+
+    (
+        StatusCode::OK,
+        Json(json!({
+            "status": "success",
+            "applied": applied_count,
+            "rules_count": total_count
+        })),
+    )
+}
+
+/// One live session as `/connections` reports it, traffic counters
+/// included
+fn connection_json(id: usize, client: &crate::state::ClientInfo) -> Value {
+    json!({
+        "connection_id": id,
+        "client_addr": client.client_addr.to_string(),
+        "upstream": client.upstream,
+        "username": client.username,
+        "database": client.database,
+        "application_name": client.application_name,
+        "connected_at": client.connected_at,
+        "queries": client.queries,
+        "rows_masked": client.rows_masked,
+        "cells_masked": client.cells_masked,
+        "bytes_in": client.counters.bytes_in.load(Ordering::Relaxed),
+        "bytes_out": client.counters.bytes_out.load(Ordering::Relaxed),
+        "rows_forwarded": client.counters.rows_forwarded.load(Ordering::Relaxed),
+    })
+}
+
+#[utoipa::path(get, path = "/connections", tag = "connections", responses(
+    (status = 200, description = "Every live client connection", body = Value)
+))]
+async fn get_connections(State(state): State<AppState>) -> Json<Value> {
+    let count = state.active_connections.load(Ordering::Relaxed);
+    let sessions: Vec<Value> = state
+        .client_snapshot()
+        .await
+        .into_iter()
+        .map(|(id, client)| connection_json(id, &client))
+        .collect();
+    Json(json!({
+        "active_connections": count,
+        "sessions": sessions
+    }))
+}
+
+/// One live connection by id, for drilling into a specific session
+#[utoipa::path(get, path = "/connections/{id}", tag = "connections",
+    params(("id" = usize, Path, description = "Connection id")),
+    responses(
+        (status = 200, description = "One live connection with its traffic counters", body = Value),
+        (status = 404, description = "No live connection with this id")
+    )
+)]
+async fn get_connection(
+    State(state): State<AppState>,
+    Path(id): Path<usize>,
+) -> impl IntoResponse {
+    match state.clients.read().await.get(&id) {
+        Some(client) => (StatusCode::OK, Json(connection_json(id, client))),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "status": "error",
+                "error": format!("no live connection with id {}", id)
+            })),
+        ),
+    }
+}
+
+/// Forcibly close a proxied connection: cancelling its token makes the
+/// forwarding task return immediately, dropping both the client and
+/// upstream sockets. The kill is audited with who requested it.
+#[utoipa::path(delete, path = "/connections/{id}", tag = "connections",
+    params(("id" = usize, Path, description = "Connection id")),
+    responses(
+        (status = 200, description = "The connection was told to close", body = Value),
+        (status = 404, description = "No live connection with this id")
+    )
+)]
+async fn terminate_connection(
+    State(state): State<AppState>,
+    identity: Option<axum::Extension<ApiIdentity>>,
+    Path(id): Path<usize>,
+) -> impl IntoResponse {
+    let target = state
+        .clients
+        .read()
+        .await
+        .get(&id)
+        .map(|client| (client.cancel.clone(), client.client_addr.to_string()));
+    let Some((cancel, client_addr)) = target else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "status": "error",
+                "error": format!("no live connection with id {}", id)
             })),
-        ),
+        );
+    };
+    cancel.cancel();
+
+    let mut entry = AuditLogger::connection_terminated(id, &client_addr);
+    if let Some(axum::Extension(ApiIdentity(user))) = identity {
+        entry = entry.with_user_id(user);
     }
-}
+    state.audit_logger.log(entry).await;
 
-async fn get_connections(State(state): State<AppState>) -> Json<Value> {
-    let count = state.active_connections.load(Ordering::Relaxed);
-    Json(json!({
-        "active_connections": count
-    }))
+    (
+        StatusCode::OK,
+        Json(json!({
+            "status": "terminated",
+            "connection_id": id,
+            "client_addr": client_addr
+        })),
+    )
 }
 
 /// Get application statistics (queries, masking, connections)
@@ -577,19 +2153,165 @@ async fn get_stats(State(state): State<AppState>) -> Json<Value> {
     }))
 }
 
+/// Serve the prefetched catalog without touching the upstream: table and
+/// column lists from the schema cache, plus whether the cache is stale
+#[cfg(feature = "postgres")]
+#[utoipa::path(get, path = "/schema", tag = "schema", responses(
+    (status = 200, description = "The schema cached by the interceptor", body = Value)
+))]
+async fn get_cached_schema(State(state): State<AppState>) -> impl IntoResponse {
+    match state.oid_cache.as_ref() {
+        Some(cache) => {
+            let tables = cache.table_columns().await;
+            (
+                StatusCode::OK,
+                Json(json!({
+                    "stale": cache.is_stale(),
+                    "tables": tables,
+                })),
+            )
+        }
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "status": "error",
+                "error": "schema cache is not configured; set oid_resolution and schema_cache"
+            })),
+        ),
+    }
+}
+
+/// Which columns of the cached schema a rule's selectors would cover.
+/// Debugging a silently non-matching rule otherwise means replaying
+/// traffic; this answers it from the prefetched catalog instead.
+#[cfg(feature = "postgres")]
+async fn match_rule(
+    State(state): State<AppState>,
+    Json(rule): Json<MaskingRule>,
+) -> impl IntoResponse {
+    if let Err(e) = rule.validate_regexes() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "status": "error", "error": e.to_string() })),
+        );
+    }
+    let Some(cache) = state.oid_cache.as_ref() else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "status": "error",
+                "error": "schema cache is not configured; set oid_resolution and schema_cache"
+            })),
+        );
+    };
+
+    let tables = cache.table_columns().await;
+    let mut table_names: Vec<&String> = tables.keys().collect();
+    table_names.sort();
+    let matches: Vec<Value> = table_names
+        .iter()
+        .filter(|table| rule.table_matches(Some(table)))
+        .flat_map(|table| {
+            tables[table.as_str()]
+                .iter()
+                .filter(|column| rule.column_matches(column))
+                .map(move |column| json!({ "table": table, "column": column }))
+        })
+        .collect();
+
+    (
+        StatusCode::OK,
+        Json(json!({
+            "status": "ok",
+            "stale": cache.is_stale(),
+            "matches": matches,
+        })),
+    )
+}
+
+/// Seconds a live `/schema` result is served from the AppState snapshot
+/// before the upstream catalog is queried again
+#[cfg(feature = "postgres")]
+const SCHEMA_SNAPSHOT_TTL_SECS: i64 = 300;
+
+#[cfg(feature = "postgres")]
+#[derive(Deserialize, utoipa::IntoParams)]
+struct SchemaQuery {
+    /// Bypass the snapshot and re-query the upstream catalog
+    #[serde(default)]
+    refresh: bool,
+}
+
+/// The catalog tree plus, per column, which masking rule covers it. Rules
+/// are matched with the same selectors the proxy path uses, so "masked_by"
+/// here is exactly what a result set from that column would get.
+#[cfg(feature = "postgres")]
+fn schema_with_coverage(schema: &SchemaInfo, rules: &[MaskingRule]) -> Value {
+    json!({
+        "database": schema.database,
+        "schema": schema.schema,
+        "tables": schema.tables.iter().map(|table| json!({
+            "name": table.name,
+            "row_count": table.row_count,
+            "columns": table.columns.iter().map(|column| {
+                let masked_by = rules.iter().find(|rule| {
+                    rule.table_matches(Some(&table.name))
+                        && rule.column_matches(&column.column_name)
+                });
+                json!({
+                    "name": column.column_name,
+                    "data_type": column.data_type,
+                    "nullable": column.is_nullable,
+                    "masked_by": masked_by.map(|rule| json!({
+                        "rule_id": rule.id,
+                        "strategy": rule.strategy,
+                    })),
+                })
+            }).collect::<Vec<_>>(),
+        })).collect::<Vec<_>>(),
+    })
+}
+
+/// Live catalog view. Served from the AppState snapshot while it is
+/// fresh (`?refresh=true` forces a re-query); an actual upstream hit
+/// emits a SchemaQuery audit event and replaces the snapshot. When the
+/// upstream is unreachable this answers 503 with the last snapshot and
+/// its fetch time, so the dashboard degrades instead of going blank.
+#[cfg(feature = "postgres")]
+#[utoipa::path(post, path = "/schema", tag = "schema", params(SchemaQuery),
+    request_body = Value,
+    responses(
+        (status = 200, description = "The live schema with per-column rule coverage", body = Value),
+        (status = 503, description = "Upstream unreachable; stale snapshot attached when one exists")
+    )
+)]
 async fn get_schema(
     State(state): State<AppState>,
+    axum::extract::Query(query): axum::extract::Query<SchemaQuery>,
     Json(config): Json<ScanConfig>,
 ) -> impl IntoResponse {
+    if !query.refresh
+        && let Some(snapshot) = state.schema_snapshot.read().await.as_ref()
+        && snapshot.schema.database == config.database
+        && snapshot.schema.schema == config.schema
+        && (chrono::Utc::now() - snapshot.fetched_at).num_seconds() < SCHEMA_SNAPSHOT_TTL_SECS
+    {
+        let rules = state.config.read().await.rules.clone();
+        let mut body = schema_with_coverage(&snapshot.schema, &rules);
+        body["cached"] = json!(true);
+        body["fetched_at"] = json!(snapshot.fetched_at.to_rfc3339());
+        return (StatusCode::OK, Json(body));
+    }
+
     let scanner = DbScanner::new(
         state.upstream_host.to_string(),
         state.upstream_port,
         state.db_protocol,
-    );
+    )
+    .with_version(state.get_upstream_version().await);
 
     match scanner.get_schema(&config).await {
         Ok(schema) => {
-            // Log audit event
             state
                 .audit_logger
                 .log(AuditLogger::schema_query(
@@ -597,7 +2319,26 @@ async fn get_schema(
                     schema.tables.len(),
                 ))
                 .await;
-            (StatusCode::OK, Json(json!(schema)))
+            let fetched_at = chrono::Utc::now();
+            let rules = state.config.read().await.rules.clone();
+            let mut body = schema_with_coverage(&schema, &rules);
+            body["cached"] = json!(false);
+            body["fetched_at"] = json!(fetched_at.to_rfc3339());
+            *state.schema_snapshot.write().await = Some(SchemaSnapshot { schema, fetched_at });
+            (StatusCode::OK, Json(body))
+        }
+        Err(e @ ScanError::ConnectionFailed(_)) => {
+            let snapshot = state.schema_snapshot.read().await;
+            let mut body = json!({
+                "status": "error",
+                "error": e.to_string(),
+            });
+            if let Some(snapshot) = snapshot.as_ref() {
+                let rules = state.config.read().await.rules.clone();
+                body["stale_schema"] = schema_with_coverage(&snapshot.schema, &rules);
+                body["fetched_at"] = json!(snapshot.fetched_at.to_rfc3339());
+            }
+            (StatusCode::SERVICE_UNAVAILABLE, Json(body))
         }
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -609,87 +2350,326 @@ async fn get_schema(
     }
 }
 
-async fn get_logs(State(state): State<AppState>) -> Json<Value> {
+/// Firing/cleared state of every configured alert, plus the columns
+/// currently quarantined
+async fn get_alerts(State(state): State<AppState>) -> Json<Value> {
+    let alerts = state.alerts.write().await.states(std::time::Instant::now());
+    let mut quarantined: Vec<String> = state
+        .quarantined_columns
+        .read()
+        .await
+        .iter()
+        .cloned()
+        .collect();
+    quarantined.sort();
+    Json(json!({
+        "alerts": alerts,
+        "quarantined_columns": quarantined
+    }))
+}
+
+/// Request body for clearing a quarantined column
+#[derive(Debug, Deserialize)]
+struct ClearQuarantineRequest {
+    column: String,
+}
+
+/// Clear a quarantined column so it goes back to normal masking
+async fn clear_quarantine(
+    State(state): State<AppState>,
+    Json(req): Json<ClearQuarantineRequest>,
+) -> impl IntoResponse {
+    let removed = state
+        .quarantined_columns
+        .write()
+        .await
+        .remove(&req.column.to_lowercase());
+    if removed {
+        (
+            StatusCode::OK,
+            Json(json!({
+                "status": "cleared",
+                "column": req.column
+            })),
+        )
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "status": "error",
+                "error": format!("column '{}' is not quarantined", req.column)
+            })),
+        )
+    }
+}
+
+/// Query parameters for the in-memory event log
+#[derive(Debug, Deserialize, utoipa::IntoParams)]
+struct LogsQuery {
+    /// Maximum number of entries to return (default 50)
+    limit: Option<usize>,
+    /// Entries to skip from the newest end, for paging further back
+    #[serde(default)]
+    offset: usize,
+    /// Filter by event type
+    event_type: Option<String>,
+    /// Filter by connection id
+    connection_id: Option<usize>,
+    /// Only entries at or after this RFC 3339 timestamp
+    since: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// The in-memory event log, newest first. `total` counts every buffered
+/// entry that matched the filters, not just the returned page, and
+/// `evicted` says whether older history has already been dropped from
+/// the ring buffer.
+#[utoipa::path(get, path = "/logs", tag = "logs", params(LogsQuery), responses(
+    (status = 200, description = "Recent event log entries, newest first", body = Value)
+))]
+async fn get_logs(
+    State(state): State<AppState>,
+    axum::extract::Query(query): axum::extract::Query<LogsQuery>,
+) -> Json<Value> {
+    let limit = query.limit.unwrap_or(50);
     let logs = state.logs.read().await;
+
+    // push_front keeps the buffer newest-first, so front-to-back
+    // iteration is already the order we serve
+    let matching: Vec<&crate::state::LogEntry> = logs
+        .iter()
+        .filter(|entry| {
+            query
+                .event_type
+                .as_ref()
+                .is_none_or(|t| entry.event_type == *t)
+                && query.connection_id.is_none_or(|id| entry.connection_id == id)
+                && query.since.is_none_or(|since| entry.timestamp >= since)
+        })
+        .collect();
+    let total = matching.len();
+    let page: Vec<&crate::state::LogEntry> =
+        matching.into_iter().skip(query.offset).take(limit).collect();
+
     Json(json!({
-        "logs": *logs
+        "logs": page,
+        "total": total,
+        "limit": limit,
+        "offset": query.offset,
+        "evicted": state.logs_evicted.load(Ordering::Relaxed) > 0,
     }))
 }
 
+/// Query parameters for the live log stream
+#[derive(Debug, Deserialize)]
+struct LogStreamQuery {
+    /// Only push entries with this event type
+    event_type: Option<String>,
+}
+
+/// Events from a broadcast receiver as an SSE stream: each entry the
+/// filter accepts becomes a `log` event, falling behind the channel
+/// becomes a `lagged` event carrying the number of dropped entries, and
+/// the stream ends when the sender (AppState) goes away
+fn log_event_stream(
+    rx: tokio::sync::broadcast::Receiver<crate::state::LogEntry>,
+    event_type: Option<String>,
+) -> impl futures::Stream<Item = Result<axum::response::sse::Event, axum::Error>> {
+    use axum::response::sse::Event;
+    use tokio::sync::broadcast::error::RecvError;
+
+    futures::stream::unfold((rx, event_type), |(mut rx, filter)| async move {
+        loop {
+            let event = match rx.recv().await {
+                Ok(entry) => {
+                    if filter.as_ref().is_some_and(|t| entry.event_type != *t) {
+                        continue;
+                    }
+                    Event::default().event("log").json_data(&entry)
+                }
+                Err(RecvError::Lagged(n)) => Ok(Event::default()
+                    .event("lagged")
+                    .data(format!("lagged {} entries", n))),
+                Err(RecvError::Closed) => return None,
+            };
+            return Some((event, (rx, filter)));
+        }
+    })
+}
+
+/// Push each new log entry to the client as it happens, so the dashboard
+/// follows live traffic instead of polling `/logs`. Slow consumers lose
+/// entries (marked by a `lagged` event) rather than slowing the proxy.
+async fn stream_logs(
+    State(state): State<AppState>,
+    axum::extract::Query(query): axum::extract::Query<LogStreamQuery>,
+) -> axum::response::Sse<impl futures::Stream<Item = Result<axum::response::sse::Event, axum::Error>>>
+{
+    let rx = state.log_stream.subscribe();
+    axum::response::Sse::new(log_event_stream(rx, query.event_type))
+        .keep_alive(axum::response::sse::KeepAlive::default())
+}
+
 /// Query parameters for audit log retrieval
 #[derive(Debug, Deserialize)]
 struct AuditQuery {
-    /// Maximum number of entries to return
+    /// Maximum number of entries to return, capped by
+    /// `audit.query_max_results`
     limit: Option<usize>,
+    /// Matches to skip from the newest end, for pagination
+    #[serde(default)]
+    offset: usize,
     /// Filter by event type
     event_type: Option<String>,
     /// Filter by outcome
     outcome: Option<String>,
+    /// Filter by the entry's `user_id` (token name, JWT subject, ...)
+    actor: Option<String>,
+    /// Keep only entries at or after this timestamp
+    since: Option<chrono::DateTime<chrono::Utc>>,
+    /// Keep only entries at or before this timestamp
+    until: Option<chrono::DateTime<chrono::Utc>>,
+    /// Also scan the rotated `<file>.1` … `<file>.N` files
+    #[serde(default)]
+    include_rotated: bool,
 }
 
-/// Get audit logs with optional filtering
+/// The audit event types and outcomes are serde enums; queries name them
+/// by their wire spelling, so parse through serde rather than a hand
+/// match that could fall out of date
+fn parse_audit_enum<T: serde::de::DeserializeOwned>(name: &str) -> Option<T> {
+    serde_json::from_value(Value::String(name.to_string())).ok()
+}
+
+/// One page of the on-disk audit log, scanned line by line rather than
+/// loaded, so multi-gigabyte files stay cheap. Requires the admin role —
+/// the trail records who did what, which read tokens have no business
+/// seeing — and each query is itself recorded as ApiAccess.
 async fn get_audit_logs(
     State(state): State<AppState>,
+    identity: Option<axum::Extension<ApiIdentity>>,
+    role: Option<axum::Extension<ApiRole>>,
     axum::extract::Query(query): axum::extract::Query<AuditQuery>,
-) -> Json<Value> {
-    let limit = query.limit.unwrap_or(100);
-
-    let entries = if let Some(event_type) = query.event_type {
-        // Parse event type
-        let event = match event_type.as_str() {
-            "auth_attempt" => Some(AuditEventType::AuthAttempt),
-            "config_change" => Some(AuditEventType::ConfigChange),
-            "rule_added" => Some(AuditEventType::RuleAdded),
-            "rule_deleted" => Some(AuditEventType::RuleDeleted),
-            "rules_imported" => Some(AuditEventType::RulesImported),
-            "config_reload" => Some(AuditEventType::ConfigReload),
-            "database_scan" => Some(AuditEventType::DatabaseScan),
-            "schema_query" => Some(AuditEventType::SchemaQuery),
-            "api_access" => Some(AuditEventType::ApiAccess),
-            _ => None,
-        };
-        if let Some(e) = event {
-            state.audit_logger.get_entries_by_type(e, Some(limit)).await
-        } else {
-            state.audit_logger.get_entries(Some(limit)).await
-        }
-    } else if let Some(outcome) = query.outcome {
-        // Parse outcome
-        let out = match outcome.as_str() {
-            "success" => Some(AuditOutcome::Success),
-            "failure" => Some(AuditOutcome::Failure),
-            "denied" => Some(AuditOutcome::Denied),
-            _ => None,
-        };
-        if let Some(o) = out {
-            state
-                .audit_logger
-                .get_entries_by_outcome(o, Some(limit))
-                .await
-        } else {
-            state.audit_logger.get_entries(Some(limit)).await
-        }
-    } else {
-        state.audit_logger.get_entries(Some(limit)).await
+) -> impl IntoResponse {
+    let actor = identity.map(|axum::Extension(ApiIdentity(user))| user);
+
+    // Read tokens carry their role in the extensions; api-key and jwt
+    // identities are full-access and carry none
+    if matches!(role, Some(axum::Extension(ApiRole::Read))) {
+        state
+            .audit_logger
+            .log(
+                AuditLogger::api_access(actor.as_deref(), false)
+                    .with_endpoint("/audit")
+                    .with_method("GET"),
+            )
+            .await;
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": "querying the audit log requires the admin role" })),
+        );
+    }
+
+    let audit_config = state.config.read().await.audit.clone();
+    // Fresh installs without auditing get clean 404s, not scan errors
+    let Some(audit) = audit_config.filter(|audit| audit.enabled) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "audit logging is disabled" })),
+        );
     };
+    let Some(log_file) = audit.log_file.clone() else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "no audit log file is configured" })),
+        );
+    };
+    let path = std::path::Path::new(&log_file);
+    if !path.exists() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "no audit log file exists yet" })),
+        );
+    }
 
-    Json(json!({
-        "count": entries.len(),
-        "entries": entries
-    }))
+    let event_type = match &query.event_type {
+        Some(name) => match parse_audit_enum::<AuditEventType>(name) {
+            Some(event_type) => Some(event_type),
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({ "error": format!("unknown event type '{}'", name) })),
+                );
+            }
+        },
+        None => None,
+    };
+    let outcome = match &query.outcome {
+        Some(name) => match parse_audit_enum::<AuditOutcome>(name) {
+            Some(outcome) => Some(outcome),
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({ "error": format!("unknown outcome '{}'", name) })),
+                );
+            }
+        },
+        None => None,
+    };
+
+    let limit = query
+        .limit
+        .unwrap_or(100)
+        .min(audit.query_max_results.max(1));
+    let file_query = crate::audit::AuditFileQuery {
+        event_type,
+        outcome,
+        actor: query.actor.clone(),
+        since: query.since,
+        until: query.until,
+        include_rotated: query.include_rotated,
+        offset: query.offset,
+        limit,
+    };
+    let scan = AuditLogger::scan_file(path, &file_query, audit.max_rotated_files);
+
+    // Recorded after the scan so the query does not show up in its own
+    // results; reading the trail is itself sensitive access
+    state
+        .audit_logger
+        .log(
+            AuditLogger::api_access(actor.as_deref(), true)
+                .with_endpoint("/audit")
+                .with_method("GET"),
+        )
+        .await;
+
+    match scan {
+        Ok(scan) => (
+            StatusCode::OK,
+            Json(json!({
+                "count": scan.entries.len(),
+                "entries": scan.entries,
+                "matched": scan.matched,
+                "malformed": scan.malformed,
+                "limit": limit,
+                "offset": query.offset,
+            })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("failed to read audit log: {}", e) })),
+        ),
+    }
 }
 
-/// Prometheus metrics endpoint
+/// Prometheus metrics endpoint (only routed when that backend is selected)
 async fn get_metrics(State(state): State<AppState>) -> impl IntoResponse {
-    match &state.metrics_handle {
-        Some(handle) => {
-            let metrics = handle.render();
-            (
-                StatusCode::OK,
-                [("content-type", "text/plain; version=0.0.4; charset=utf-8")],
-                metrics,
-            )
-        }
+    match state.metrics.render() {
+        Some(metrics) => (
+            StatusCode::OK,
+            [("content-type", "text/plain; version=0.0.4; charset=utf-8")],
+            metrics,
+        ),
         None => (
             StatusCode::SERVICE_UNAVAILABLE,
             [("content-type", "text/plain; charset=utf-8")],
@@ -701,9 +2681,13 @@ async fn get_metrics(State(state): State<AppState>) -> impl IntoResponse {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{ApiConfig, AppConfig};
+    use crate::config::{ApiConfig, AppConfig, ApplicationNamePolicy, ExpressionHandling, Strategy, TypeMismatchPolicy};
     use axum::extract::State;
 
+    fn test_state() -> AppState {
+        AppState::new_for_test(AppConfig::default(), "proxy.yaml".to_string())
+    }
+
     #[tokio::test]
     async fn test_health_check() {
         let config = AppConfig::default();
@@ -715,6 +2699,42 @@ mod tests {
         assert_eq!(status.status, StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn test_alerts_endpoint_reports_quarantine_and_clears_it() {
+        let state = test_state();
+        state
+            .quarantined_columns
+            .write()
+            .await
+            .insert("email".to_string());
+
+        let Json(body) = get_alerts(State(state.clone())).await;
+        assert_eq!(body["quarantined_columns"], json!(["email"]));
+        assert_eq!(body["alerts"], json!([]));
+
+        // Clearing a quarantined column succeeds once, then 404s
+        let response = clear_quarantine(
+            State(state.clone()),
+            Json(ClearQuarantineRequest {
+                column: "EMAIL".to_string(),
+            }),
+        )
+        .await;
+        let (parts, _) = response.into_response().into_parts();
+        assert_eq!(parts.status, StatusCode::OK);
+        assert!(state.quarantined_columns.read().await.is_empty());
+
+        let response = clear_quarantine(
+            State(state),
+            Json(ClearQuarantineRequest {
+                column: "email".to_string(),
+            }),
+        )
+        .await;
+        let (parts, _) = response.into_response().into_parts();
+        assert_eq!(parts.status, StatusCode::NOT_FOUND);
+    }
+
     #[tokio::test]
     async fn test_api_key_config_parsing() {
         // Test that API key is correctly parsed from config
@@ -722,6 +2742,12 @@ mod tests {
             api: Some(ApiConfig {
                 api_key: Some("my-secret-key".to_string()),
                 jwt_secret: None,
+                addresses: None,
+                bind_address: None,
+                tls: None,
+                docs: false,
+                rate_limit: None,
+                auth: None,
             }),
             ..Default::default()
         };
@@ -823,6 +2849,12 @@ mod tests {
             api: Some(ApiConfig {
                 api_key: None,
                 jwt_secret: Some("my-jwt-secret".to_string()),
+                addresses: None,
+                bind_address: None,
+                tls: None,
+                docs: false,
+                rate_limit: None,
+                auth: None,
             }),
             ..Default::default()
         };
@@ -840,19 +2872,63 @@ mod tests {
     #[tokio::test]
     async fn test_get_config() {
         let config = AppConfig {
+            listen: None,
             masking_enabled: true,
+            verify_output: None,
             rules: vec![MaskingRule {
+                id: None,
                 table: Some("users".to_string()),
                 column: "email".to_string(),
-                strategy: "email".to_string(),
+                case_sensitive: false,
+                table_regex: None,
+                column_regex: None,
+                database: None,
+                compiled: Default::default(),
+                redact_text: None,
+                preserve_length: false,
+                date_shift_days: None,
+                seed_column: None,
+                noise_percent: None,
+                ip_mode: None,
+                preserve_domain: false,
+                domain_allowlist: Vec::new(),
+                json_paths: None,
+                pattern: None,
+                replacement: None,
+                wasm_module: None,
+                wasm_fuel: None,
+                deterministic: true,
+                strategy: Strategy::Email.into(),
+                composite_fields: None,
+                on_type_mismatch: TypeMismatchPolicy::Fallback,
+                when: None,
             }],
             tls: None,
             upstream_tls: false,
+            upstream_tls_options: None,
+            routing: None,
             telemetry: None,
+            metrics: None,
             api: None,
             limits: None,
+            policies_by_source: vec![],
+            application_name_policy: ApplicationNamePolicy::Pin,
+            expression_handling: ExpressionHandling::Heuristic,
+            scan_typed_columns: false,
+            memo_entries: 4096,
+            log_buffer_entries: 100,
+            watch: false,
+            upstream: None,
             health_check: None,
             audit: None,
+            alerts: None,
+            admin: None,
+            oid_resolution: None,
+            schema_cache: None,
+            masking: None,
+            scanner: None,
+            hashing: None,
+            custom_strategies: Default::default(),
         };
         let state = AppState::new_for_test(config, "proxy.yaml".to_string());
 
@@ -864,81 +2940,374 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_update_config() {
+    async fn test_update_config() {
+        let config = AppConfig {
+            masking_enabled: true,
+            verify_output: None,
+            rules: vec![],
+            listen: None,
+            tls: None,
+            upstream_tls: false,
+            upstream_tls_options: None,
+            routing: None,
+            telemetry: None,
+            metrics: None,
+            api: None,
+            limits: None,
+            policies_by_source: vec![],
+            application_name_policy: ApplicationNamePolicy::Pin,
+            expression_handling: ExpressionHandling::Heuristic,
+            scan_typed_columns: false,
+            memo_entries: 4096,
+            log_buffer_entries: 100,
+            watch: false,
+            upstream: None,
+            health_check: None,
+            audit: None,
+            alerts: None,
+            admin: None,
+            oid_resolution: None,
+            schema_cache: None,
+            masking: None,
+            scanner: None,
+            hashing: None,
+            custom_strategies: Default::default(),
+        };
+        let state = AppState::new_for_test(config, "proxy.yaml".to_string());
+
+        let payload = json!({ "masking_enabled": false });
+        let response = update_config(State(state.clone()), Json(payload)).await;
+        let json = response.0;
+
+        assert_eq!(json["status"], "success");
+        assert_eq!(json["masking_enabled"], false);
+
+        // Verify state was actually updated
+        let config = state.config.read().await;
+        assert!(!config.masking_enabled);
+    }
+
+    #[tokio::test]
+    async fn test_add_rule() {
+        let config = AppConfig {
+            masking_enabled: true,
+            verify_output: None,
+            rules: vec![],
+            listen: None,
+            tls: None,
+            upstream_tls: false,
+            upstream_tls_options: None,
+            routing: None,
+            telemetry: None,
+            metrics: None,
+            api: None,
+            limits: None,
+            policies_by_source: vec![],
+            application_name_policy: ApplicationNamePolicy::Pin,
+            expression_handling: ExpressionHandling::Heuristic,
+            scan_typed_columns: false,
+            memo_entries: 4096,
+            log_buffer_entries: 100,
+            watch: false,
+            upstream: None,
+            health_check: None,
+            audit: None,
+            alerts: None,
+            admin: None,
+            oid_resolution: None,
+            schema_cache: None,
+            masking: None,
+            scanner: None,
+            hashing: None,
+            custom_strategies: Default::default(),
+        };
+        let state = AppState::new_for_test(config, "/tmp/test_proxy.yaml".to_string());
+
+        // Create temp file so save works
+        std::fs::write("/tmp/test_proxy.yaml", "rules: []").ok();
+
+        let new_rule = MaskingRule {
+            id: None,
+            table: Some("users".to_string()),
+            column: "phone".to_string(),
+            case_sensitive: false,
+            table_regex: None,
+            column_regex: None,
+            database: None,
+            compiled: Default::default(),
+            redact_text: None,
+            preserve_length: false,
+            date_shift_days: None,
+            seed_column: None,
+            noise_percent: None,
+            ip_mode: None,
+            preserve_domain: false,
+            domain_allowlist: Vec::new(),
+            json_paths: None,
+            pattern: None,
+            replacement: None,
+            wasm_module: None,
+            wasm_fuel: None,
+            deterministic: true,
+            strategy: Strategy::Phone.into(),
+            composite_fields: None,
+            on_type_mismatch: TypeMismatchPolicy::Fallback,
+            when: None,
+        };
+
+        // Call add_rule and verify rule was added to state
+        let _ = add_rule(State(state.clone()), Json(new_rule)).await;
+
+        // Verify rule was added
+        let config = state.config.read().await;
+        assert_eq!(config.rules.len(), 1);
+        assert_eq!(config.rules[0].column, "phone");
+    }
+
+    #[tokio::test]
+    async fn test_add_rule_assigns_id_and_bumps_generation() {
         let config = AppConfig {
             masking_enabled: true,
+            verify_output: None,
             rules: vec![],
+            listen: None,
             tls: None,
             upstream_tls: false,
+            upstream_tls_options: None,
+            routing: None,
             telemetry: None,
+            metrics: None,
             api: None,
             limits: None,
+            policies_by_source: vec![],
+            application_name_policy: ApplicationNamePolicy::Pin,
+            expression_handling: ExpressionHandling::Heuristic,
+            scan_typed_columns: false,
+            memo_entries: 4096,
+            log_buffer_entries: 100,
+            watch: false,
+            upstream: None,
             health_check: None,
             audit: None,
+            alerts: None,
+            admin: None,
+            oid_resolution: None,
+            schema_cache: None,
+            masking: None,
+            scanner: None,
+            hashing: None,
+            custom_strategies: Default::default(),
         };
-        let state = AppState::new_for_test(config, "proxy.yaml".to_string());
+        let state = AppState::new_for_test(config, "/tmp/test_proxy_rule_id.yaml".to_string());
+        std::fs::write("/tmp/test_proxy_rule_id.yaml", "rules: []").ok();
 
-        let payload = json!({ "masking_enabled": false });
-        let response = update_config(State(state.clone()), Json(payload)).await;
-        let json = response.0;
+        assert_eq!(state.current_ruleset_generation(), 0);
 
-        assert_eq!(json["status"], "success");
-        assert_eq!(json["masking_enabled"], false);
+        let new_rule = MaskingRule {
+            id: None,
+            table: None,
+            column: "ssn".to_string(),
+            case_sensitive: false,
+            table_regex: None,
+            column_regex: None,
+            database: None,
+            compiled: Default::default(),
+            redact_text: None,
+            preserve_length: false,
+            date_shift_days: None,
+            seed_column: None,
+            noise_percent: None,
+            ip_mode: None,
+            preserve_domain: false,
+            domain_allowlist: Vec::new(),
+            json_paths: None,
+            pattern: None,
+            replacement: None,
+            wasm_module: None,
+            wasm_fuel: None,
+            deterministic: true,
+            strategy: Strategy::Ssn.into(),
+            composite_fields: None,
+            on_type_mismatch: TypeMismatchPolicy::Fallback,
+            when: None,
+        };
+        let _ = add_rule(State(state.clone()), Json(new_rule)).await;
 
-        // Verify state was actually updated
         let config = state.config.read().await;
-        assert!(!config.masking_enabled);
+        assert!(config.rules[0].id.is_some(), "add_rule should assign an id");
+        drop(config);
+        assert_eq!(state.current_ruleset_generation(), 1);
     }
 
     #[tokio::test]
-    async fn test_add_rule() {
+    async fn test_delete_rule_by_id() {
         let config = AppConfig {
+            listen: None,
             masking_enabled: true,
-            rules: vec![],
+            verify_output: None,
+            rules: vec![
+                MaskingRule {
+                    id: Some("rule-keep".to_string()),
+                    table: None,
+                    column: "email".to_string(),
+                    case_sensitive: false,
+                    table_regex: None,
+                    column_regex: None,
+                    database: None,
+                    compiled: Default::default(),
+                    redact_text: None,
+                    preserve_length: false,
+                    date_shift_days: None,
+                    seed_column: None,
+                    noise_percent: None,
+                    ip_mode: None,
+                    preserve_domain: false,
+                    domain_allowlist: Vec::new(),
+                    json_paths: None,
+                    pattern: None,
+                    replacement: None,
+                    wasm_module: None,
+                    wasm_fuel: None,
+                    deterministic: true,
+                    strategy: Strategy::Email.into(),
+                    composite_fields: None,
+                    on_type_mismatch: TypeMismatchPolicy::Fallback,
+                    when: None,
+                },
+                MaskingRule {
+                    id: Some("rule-drop".to_string()),
+                    table: None,
+                    column: "phone".to_string(),
+                    case_sensitive: false,
+                    table_regex: None,
+                    column_regex: None,
+                    database: None,
+                    compiled: Default::default(),
+                    redact_text: None,
+                    preserve_length: false,
+                    date_shift_days: None,
+                    seed_column: None,
+                    noise_percent: None,
+                    ip_mode: None,
+                    preserve_domain: false,
+                    domain_allowlist: Vec::new(),
+                    json_paths: None,
+                    pattern: None,
+                    replacement: None,
+                    wasm_module: None,
+                    wasm_fuel: None,
+                    deterministic: true,
+                    strategy: Strategy::Phone.into(),
+                    composite_fields: None,
+                    on_type_mismatch: TypeMismatchPolicy::Fallback,
+                    when: None,
+                },
+            ],
             tls: None,
             upstream_tls: false,
+            upstream_tls_options: None,
+            routing: None,
             telemetry: None,
+            metrics: None,
             api: None,
             limits: None,
+            policies_by_source: vec![],
+            application_name_policy: ApplicationNamePolicy::Pin,
+            expression_handling: ExpressionHandling::Heuristic,
+            scan_typed_columns: false,
+            memo_entries: 4096,
+            log_buffer_entries: 100,
+            watch: false,
+            upstream: None,
             health_check: None,
             audit: None,
+            alerts: None,
+            admin: None,
+            oid_resolution: None,
+            schema_cache: None,
+            masking: None,
+            scanner: None,
+            hashing: None,
+            custom_strategies: Default::default(),
         };
-        let state = AppState::new_for_test(config, "/tmp/test_proxy.yaml".to_string());
+        let state = AppState::new_for_test(config, "/tmp/test_proxy_delete_id.yaml".to_string());
+        std::fs::write("/tmp/test_proxy_delete_id.yaml", "rules: []").ok();
 
-        // Create temp file so save works
-        std::fs::write("/tmp/test_proxy.yaml", "rules: []").ok();
-
-        let new_rule = MaskingRule {
-            table: Some("users".to_string()),
-            column: "phone".to_string(),
-            strategy: "phone".to_string(),
+        let req = DeleteRuleRequest {
+            id: Some("rule-drop".to_string()),
+            index: None,
+            column: None,
+            table: None,
         };
+        let _ = delete_rule(State(state.clone()), Json(req)).await;
 
-        // Call add_rule and verify rule was added to state
-        let _ = add_rule(State(state.clone()), Json(new_rule)).await;
-
-        // Verify rule was added
         let config = state.config.read().await;
         assert_eq!(config.rules.len(), 1);
-        assert_eq!(config.rules[0].column, "phone");
+        assert_eq!(config.rules[0].id.as_deref(), Some("rule-keep"));
+        drop(config);
+        assert_eq!(state.current_ruleset_generation(), 1);
     }
 
     #[tokio::test]
     async fn test_get_rules() {
         let config = AppConfig {
+            listen: None,
             masking_enabled: true,
+            verify_output: None,
             rules: vec![MaskingRule {
+                id: None,
                 table: None,
                 column: "email".to_string(),
-                strategy: "email".to_string(),
+                case_sensitive: false,
+                table_regex: None,
+                column_regex: None,
+                database: None,
+                compiled: Default::default(),
+                redact_text: None,
+                preserve_length: false,
+                date_shift_days: None,
+                seed_column: None,
+                noise_percent: None,
+                ip_mode: None,
+                preserve_domain: false,
+                domain_allowlist: Vec::new(),
+                json_paths: None,
+                pattern: None,
+                replacement: None,
+                wasm_module: None,
+                wasm_fuel: None,
+                deterministic: true,
+                strategy: Strategy::Email.into(),
+                composite_fields: None,
+                on_type_mismatch: TypeMismatchPolicy::Fallback,
+                when: None,
             }],
             tls: None,
             upstream_tls: false,
+            upstream_tls_options: None,
+            routing: None,
             telemetry: None,
+            metrics: None,
             api: None,
             limits: None,
+            policies_by_source: vec![],
+            application_name_policy: ApplicationNamePolicy::Pin,
+            expression_handling: ExpressionHandling::Heuristic,
+            scan_typed_columns: false,
+            memo_entries: 4096,
+            log_buffer_entries: 100,
+            watch: false,
+            upstream: None,
             health_check: None,
             audit: None,
+            alerts: None,
+            admin: None,
+            oid_resolution: None,
+            schema_cache: None,
+            masking: None,
+            scanner: None,
+            hashing: None,
+            custom_strategies: Default::default(),
         };
         let state = AppState::new_for_test(config, "proxy.yaml".to_string());
 
@@ -953,14 +3322,35 @@ mod tests {
     async fn test_get_connections() {
         let config = AppConfig {
             masking_enabled: true,
+            verify_output: None,
             rules: vec![],
+            listen: None,
             tls: None,
             upstream_tls: false,
+            upstream_tls_options: None,
+            routing: None,
             telemetry: None,
+            metrics: None,
             api: None,
             limits: None,
+            policies_by_source: vec![],
+            application_name_policy: ApplicationNamePolicy::Pin,
+            expression_handling: ExpressionHandling::Heuristic,
+            scan_typed_columns: false,
+            memo_entries: 4096,
+            log_buffer_entries: 100,
+            watch: false,
+            upstream: None,
             health_check: None,
             audit: None,
+            alerts: None,
+            admin: None,
+            oid_resolution: None,
+            schema_cache: None,
+            masking: None,
+            scanner: None,
+            hashing: None,
+            custom_strategies: Default::default(),
         };
         let state = AppState::new_for_test(config, "proxy.yaml".to_string());
 
@@ -973,6 +3363,1013 @@ mod tests {
         assert_eq!(json["active_connections"], 3);
     }
 
-    // Note: scan_database and get_schema tests require a real database connection
-    // They are tested via E2E tests instead
+    #[tokio::test]
+    async fn test_openapi_document_covers_endpoints() {
+        let doc = serde_json::to_value(api_doc()).unwrap();
+        let paths = doc["paths"].as_object().unwrap();
+        for path in [
+            "/health",
+            "/health/live",
+            "/rules",
+            "/rules/{id}",
+            "/rules/test",
+            "/connections",
+            "/connections/{id}",
+            "/logs",
+        ] {
+            assert!(paths.contains_key(path), "missing path {}", path);
+        }
+        #[cfg(feature = "postgres")]
+        for path in ["/schema", "/scan", "/scan/{id}"] {
+            assert!(paths.contains_key(path), "missing path {}", path);
+        }
+
+        // The serde types are the source of truth for the schemas: what a
+        // client deserializes is what the document describes
+        let schemas = doc["components"]["schemas"].as_object().unwrap();
+        for schema in ["MaskingRule", "LogEntry", "HealthStatus"] {
+            assert!(schemas.contains_key(schema), "missing schema {}", schema);
+        }
+        assert!(schemas["MaskingRule"]["properties"]["strategy"].is_object());
+        assert!(schemas["LogEntry"]["properties"]["event_type"].is_object());
+        assert!(schemas["HealthStatus"]["properties"]["consecutive_failures"].is_object());
+
+        // /docs stays dark until api.docs enables it
+        let response = api_docs(State(test_state())).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let state = AppState::new_for_test(
+            AppConfig {
+                api: Some(ApiConfig {
+                    api_key: None,
+                    jwt_secret: None,
+                    addresses: None,
+                    bind_address: None,
+                    tls: None,
+                    docs: true,
+                    rate_limit: None,
+                    auth: None,
+                }),
+                ..Default::default()
+            },
+            "/tmp/test_api_docs.yaml".to_string(),
+        );
+        let response = api_docs(State(state)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter_buckets_per_caller() {
+        let state = test_state();
+        let limit = crate::config::ApiRateLimitConfig {
+            requests_per_second: 1.0,
+            burst: Some(2),
+        };
+
+        // A caller gets its burst, then a wait measured in whole seconds
+        let key = || "ip:10.0.0.1".to_string();
+        assert_eq!(take_rate_token(&state, &limit, key()).await, Ok(()));
+        assert_eq!(take_rate_token(&state, &limit, key()).await, Ok(()));
+        let wait = take_rate_token(&state, &limit, key()).await.unwrap_err();
+        assert!(wait >= 1);
+
+        // Each caller draws from its own bucket
+        assert_eq!(
+            take_rate_token(&state, &limit, "token:ci".to_string()).await,
+            Ok(())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rule_test_endpoint_shows_stage_outputs() {
+        let chain: StrategyChain = serde_yaml::from_str("[dob, date_shift]").unwrap();
+        let response = test_rule(
+            State(test_state()),
+            Json(TestRuleRequest::Inline {
+                value: "1987-06-05".to_string(),
+                strategy: chain,
+                options: None,
+            }),
+        )
+        .await
+        .into_response();
+        let (parts, body) = response.into_parts();
+        assert_eq!(parts.status, StatusCode::OK);
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+
+        let stages = json["stages"].as_array().unwrap();
+        assert_eq!(stages.len(), 2);
+        assert_eq!(stages[0]["strategy"], "dob");
+        assert_eq!(stages[0]["output"], "1900-01-01");
+        assert_eq!(stages[1]["strategy"], "date_shift");
+        // The final stage's output is the overall masked value
+        assert_eq!(json["masked"], stages[1]["output"]);
+        assert_ne!(json["masked"], "1987-06-05");
+
+        // An invalid chain is rejected, not silently truncated
+        let bad: StrategyChain = serde_yaml::from_str("[hash, numeric_noise]").unwrap();
+        let response = test_rule(
+            State(test_state()),
+            Json(TestRuleRequest::Inline {
+                value: "5".to_string(),
+                strategy: bad,
+                options: None,
+            }),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_rule_test_options_and_existing_rule_forms() {
+        // A zero-width date_shift is a no-op, which only holds if the
+        // options actually reached the strategy dispatch
+        let chain: StrategyChain = serde_yaml::from_str("date_shift").unwrap();
+        let response = test_rule(
+            State(test_state()),
+            Json(TestRuleRequest::Inline {
+                value: "1987-06-05".to_string(),
+                strategy: chain,
+                options: Some(json!({ "date_shift_days": 0 })),
+            }),
+        )
+        .await
+        .into_response();
+        let (parts, body) = response.into_parts();
+        assert_eq!(parts.status, StatusCode::OK);
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["masked"], "1987-06-05");
+
+        // Options that a rule body would refuse are refused here too
+        let chain: StrategyChain = serde_yaml::from_str("date_shift").unwrap();
+        let response = test_rule(
+            State(test_state()),
+            Json(TestRuleRequest::Inline {
+                value: "1987-06-05".to_string(),
+                strategy: chain,
+                options: Some(json!({ "date_shift_days": "a lot" })),
+            }),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // The rule_id form previews an existing rule with its own tuning
+        let mut rule = MaskingRule::basic(
+            Some("users".to_string()),
+            "dob".to_string(),
+            Strategy::Dob.into(),
+        );
+        rule.id = Some("r-1".to_string());
+        let state = AppState::new_for_test(
+            AppConfig {
+                rules: vec![rule],
+                ..Default::default()
+            },
+            "/tmp/test_rule_test_forms.yaml".to_string(),
+        );
+        let response = test_rule(
+            State(state.clone()),
+            Json(TestRuleRequest::Existing {
+                rule_id: "r-1".to_string(),
+                values: vec!["1987-06-05".to_string(), "2001-02-03".to_string()],
+            }),
+        )
+        .await
+        .into_response();
+        let (parts, body) = response.into_parts();
+        assert_eq!(parts.status, StatusCode::OK);
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        let results = json["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["masked"], "1900-01-01");
+        assert_eq!(results[1]["masked"], "1900-01-01");
+
+        // An unknown rule id 404s
+        let response = test_rule(
+            State(state),
+            Json(TestRuleRequest::Existing {
+                rule_id: "nope".to_string(),
+                values: vec!["x".to_string()],
+            }),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[cfg(feature = "postgres")]
+    #[tokio::test]
+    async fn test_scan_job_tracking() {
+        let state = test_state();
+
+        // Unknown ids 404
+        let response = get_scan_status(State(state.clone()), Path("nope".to_string()))
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Seed a running job the way start_scan would
+        let progress = std::sync::Arc::new(ScanProgress::default());
+        progress.tables_total.store(8, Ordering::Relaxed);
+        progress.tables_done.store(3, Ordering::Relaxed);
+        state.scan_jobs.write().await.insert(
+            "job-1".to_string(),
+            ScanJob {
+                id: "job-1".to_string(),
+                database: "appdb".to_string(),
+                started_at: chrono::Utc::now(),
+                status: ScanJobStatus::Running,
+                progress,
+                result: None,
+                error: None,
+            },
+        );
+
+        // Progress is reported while the scan runs, without findings
+        let response = get_scan_status(State(state.clone()), Path("job-1".to_string()))
+            .await
+            .into_response();
+        let (parts, body) = response.into_parts();
+        assert_eq!(parts.status, StatusCode::OK);
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["status"], "running");
+        assert_eq!(json["tables_total"], 8);
+        assert_eq!(json["tables_done"], 3);
+        assert!(json.get("result").is_none());
+
+        // A second POST while one runs is refused with the running id
+        let config: ScanConfig = serde_json::from_value(json!({
+            "username": "scanner",
+            "password": "secret",
+            "database": "appdb"
+        }))
+        .unwrap();
+        let response = start_scan(State(state.clone()), Json(config))
+            .await
+            .into_response();
+        let (parts, body) = response.into_parts();
+        assert_eq!(parts.status, StatusCode::CONFLICT);
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["scan_id"], "job-1");
+    }
+
+    #[cfg(feature = "postgres")]
+    #[tokio::test]
+    async fn test_scan_rule_suggestions_and_apply() {
+        use crate::db_scanner::{PiiFinding, ScanResult};
+
+        let state =
+            AppState::new_for_test(AppConfig::default(), "/tmp/test_scan_apply.yaml".to_string());
+        std::fs::write("/tmp/test_scan_apply.yaml", "rules: []").ok();
+
+        state.scan_jobs.write().await.insert(
+            "job-2".to_string(),
+            ScanJob {
+                id: "job-2".to_string(),
+                database: "appdb".to_string(),
+                started_at: chrono::Utc::now(),
+                status: ScanJobStatus::Completed,
+                progress: std::sync::Arc::new(ScanProgress::default()),
+                result: Some(ScanResult {
+                    status: "completed".to_string(),
+                    tables_scanned: 1,
+                    columns_scanned: 3,
+                    findings: vec![PiiFinding {
+                        table: "users".to_string(),
+                        column: "email".to_string(),
+                        pii_type: "Email".to_string(),
+                        confidence: 0.98,
+                        sample: None,
+                        row_count: 100,
+                        match_count: 98,
+                        data_type: "text".to_string(),
+                    }],
+                    schema: "public".to_string(),
+                    database: "appdb".to_string(),
+                    scan_duration_ms: 5,
+                }),
+                error: None,
+            },
+        );
+
+        // Suggestions come back as both JSON rules and pasteable YAML
+        let response = get_suggested_rules(
+            State(state.clone()),
+            Path("job-2".to_string()),
+            axum::extract::Query(SuggestedRulesQuery { threshold: 0.5 }),
+        )
+        .await
+        .into_response();
+        let (parts, body) = response.into_parts();
+        assert_eq!(parts.status, StatusCode::OK);
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["count"], 1);
+        assert_eq!(json["rules"][0]["table"], "users");
+        assert_eq!(json["rules"][0]["column"], "email");
+        assert_eq!(json["rules"][0]["strategy"], "email");
+        assert!(json["yaml"].as_str().unwrap().contains("strategy: email"));
+
+        // Accepting the suggestions merges them into the live config
+        let rules: Vec<MaskingRule> = serde_json::from_value(json["rules"].clone()).unwrap();
+        let response = apply_scan_rules(State(state.clone()), Path("job-2".to_string()), Json(rules))
+            .await
+            .into_response();
+        let (parts, body) = response.into_parts();
+        assert_eq!(parts.status, StatusCode::OK);
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["applied"], 1);
+        assert_eq!(json["rules_count"], 1);
+        assert!(state.config.read().await.rules[0].id.is_some());
+
+        // The applied rule now covers the column, so it is not re-suggested
+        let response = get_suggested_rules(
+            State(state.clone()),
+            Path("job-2".to_string()),
+            axum::extract::Query(SuggestedRulesQuery { threshold: 0.5 }),
+        )
+        .await
+        .into_response();
+        let (_, body) = response.into_parts();
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["count"], 0);
+
+        // Unknown scan ids 404 on both endpoints
+        let response = apply_scan_rules(State(state.clone()), Path("nope".to_string()), Json(vec![]))
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_rule_crud_rest_endpoints() {
+        let state =
+            AppState::new_for_test(AppConfig::default(), "/tmp/test_rule_crud.yaml".to_string());
+        std::fs::write("/tmp/test_rule_crud.yaml", "rules: []").ok();
+
+        // Create returns the rule with its assigned id
+        let rule = MaskingRule::basic(
+            Some("users".to_string()),
+            "email".to_string(),
+            Strategy::Email.into(),
+        );
+        let response = add_rule(State(state.clone()), Json(rule))
+            .await
+            .into_response();
+        let (parts, body) = response.into_parts();
+        assert_eq!(parts.status, StatusCode::OK);
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        let id = json["rule"]["id"].as_str().unwrap().to_string();
+        assert_eq!(json["rule"]["column"], "email");
+
+        // A second rule for the same (table, column) is refused with 409,
+        // naming the rule that holds the selector
+        let duplicate = MaskingRule::basic(
+            Some("users".to_string()),
+            "EMAIL".to_string(),
+            Strategy::Redact.into(),
+        );
+        let response = add_rule(State(state.clone()), Json(duplicate))
+            .await
+            .into_response();
+        let (parts, body) = response.into_parts();
+        assert_eq!(parts.status, StatusCode::CONFLICT);
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert!(json["error"].as_str().unwrap().contains(&id));
+
+        // Replace swaps the strategy; the id survives
+        let replacement = MaskingRule::basic(
+            Some("users".to_string()),
+            "email".to_string(),
+            Strategy::Hash.into(),
+        );
+        let response = replace_rule(
+            State(state.clone()),
+            Path(id.clone()),
+            Json(replacement.clone()),
+        )
+        .await
+        .into_response();
+        let (parts, body) = response.into_parts();
+        assert_eq!(parts.status, StatusCode::OK);
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["rule"]["id"], id.as_str());
+        assert_eq!(json["rule"]["strategy"], "hash");
+        assert_eq!(
+            state.config.read().await.rules[0].strategy,
+            StrategyChain::from(Strategy::Hash)
+        );
+
+        // Replacing an unknown id 404s
+        let response = replace_rule(
+            State(state.clone()),
+            Path("missing".to_string()),
+            Json(replacement),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Delete returns the removed rule; a second delete 404s
+        let response = remove_rule(State(state.clone()), Path(id.clone()))
+            .await
+            .into_response();
+        let (parts, body) = response.into_parts();
+        assert_eq!(parts.status, StatusCode::OK);
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["rule"]["column"], "email");
+        assert_eq!(json["rules_count"], 0);
+        assert!(state.config.read().await.rules.is_empty());
+
+        let response = remove_rule(State(state.clone()), Path(id))
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_rules_import_modes_and_dry_run() {
+        let state = AppState::new_for_test(
+            AppConfig::default(),
+            "/tmp/test_rules_import.yaml".to_string(),
+        );
+        std::fs::write("/tmp/test_rules_import.yaml", "rules: []").ok();
+
+        {
+            let mut config = state.config.write().await;
+            config.rules.push(MaskingRule::basic(
+                Some("users".to_string()),
+                "email".to_string(),
+                Strategy::Email.into(),
+            ));
+            config.ensure_rule_ids().unwrap();
+        }
+
+        // The YAML export is a bare rules array
+        let response = export_rules(
+            State(state.clone()),
+            axum::extract::Query(ExportQuery {
+                format: RulesFormat::Yaml,
+            }),
+        )
+        .await
+        .into_response();
+        let (parts, body) = response.into_parts();
+        assert_eq!(parts.status, StatusCode::OK);
+        assert_eq!(parts.headers["content-type"], "application/yaml");
+        let yaml = String::from_utf8(
+            axum::body::to_bytes(body, usize::MAX).await.unwrap().to_vec(),
+        )
+        .unwrap();
+        assert!(yaml.contains("strategy: email"));
+
+        // Re-importing the export under merge is a no-op: the duplicate
+        // is skipped and reported
+        let response = import_rules(
+            State(state.clone()),
+            axum::extract::Query(ImportQuery {
+                mode: ImportMode::Merge,
+                dry_run: false,
+            }),
+            yaml.clone(),
+        )
+        .await
+        .into_response();
+        let (parts, body) = response.into_parts();
+        assert_eq!(parts.status, StatusCode::OK);
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["imported"], 0);
+        assert_eq!(json["skipped_duplicates"], json!(["users.email"]));
+        assert_eq!(state.config.read().await.rules.len(), 1);
+
+        // A dry run reports the new rule without applying anything
+        let batch = format!("{}- table: users\n  column: phone\n  strategy: phone\n", yaml);
+        let response = import_rules(
+            State(state.clone()),
+            axum::extract::Query(ImportQuery {
+                mode: ImportMode::Merge,
+                dry_run: true,
+            }),
+            batch.clone(),
+        )
+        .await
+        .into_response();
+        let (_, body) = response.into_parts();
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["dry_run"], true);
+        assert_eq!(json["would_import"], 1);
+        assert_eq!(json["rules_count_after"], 2);
+        assert_eq!(state.config.read().await.rules.len(), 1);
+
+        // The same batch for real merges the new rule and assigns it an id
+        let response = import_rules(
+            State(state.clone()),
+            axum::extract::Query(ImportQuery {
+                mode: ImportMode::Merge,
+                dry_run: false,
+            }),
+            batch,
+        )
+        .await
+        .into_response();
+        let (_, body) = response.into_parts();
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["imported"], 1);
+        let config = state.config.read().await;
+        assert_eq!(config.rules.len(), 2);
+        assert!(config.rules[1].id.is_some());
+        drop(config);
+
+        // Replace swaps the whole ruleset
+        let response = import_rules(
+            State(state.clone()),
+            axum::extract::Query(ImportQuery {
+                mode: ImportMode::Replace,
+                dry_run: false,
+            }),
+            "- column: ssn\n  strategy: ssn\n".to_string(),
+        )
+        .await
+        .into_response();
+        let (_, body) = response.into_parts();
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["imported"], 1);
+        assert_eq!(json["rules_count"], 1);
+        assert_eq!(state.config.read().await.rules[0].column, "ssn");
+
+        // A body that is neither a JSON nor a YAML rules array 400s
+        let response = import_rules(
+            State(state.clone()),
+            axum::extract::Query(ImportQuery {
+                mode: ImportMode::Merge,
+                dry_run: false,
+            }),
+            "[{\"column\": }]".to_string(),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[cfg(feature = "postgres")]
+    fn sample_schema() -> SchemaInfo {
+        use crate::db_scanner::{ColumnInfo, TableInfo};
+        SchemaInfo {
+            database: "appdb".to_string(),
+            schema: "public".to_string(),
+            tables: vec![TableInfo {
+                name: "users".to_string(),
+                row_count: Some(42),
+                columns: vec![
+                    ColumnInfo {
+                        table_name: "users".to_string(),
+                        column_name: "email".to_string(),
+                        data_type: "text".to_string(),
+                        is_nullable: false,
+                        character_maximum_length: None,
+                    },
+                    ColumnInfo {
+                        table_name: "users".to_string(),
+                        column_name: "signup_date".to_string(),
+                        data_type: "date".to_string(),
+                        is_nullable: true,
+                        character_maximum_length: None,
+                    },
+                ],
+            }],
+        }
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn test_schema_with_coverage_marks_masked_columns() {
+        let rules = vec![MaskingRule::basic(
+            Some("users".to_string()),
+            "email".to_string(),
+            crate::config::Strategy::Email.into(),
+        )];
+        let body = schema_with_coverage(&sample_schema(), &rules);
+
+        let columns = &body["tables"][0]["columns"];
+        assert_eq!(columns[0]["name"], "email");
+        assert_eq!(columns[0]["nullable"], false);
+        assert_eq!(columns[0]["masked_by"]["strategy"], "email");
+        assert_eq!(columns[1]["name"], "signup_date");
+        assert_eq!(columns[1]["nullable"], true);
+        assert!(columns[1]["masked_by"].is_null());
+    }
+
+    #[cfg(feature = "postgres")]
+    #[tokio::test]
+    async fn test_schema_served_from_fresh_snapshot() {
+        let mut config = AppConfig::default();
+        config.rules = vec![MaskingRule::basic(
+            Some("users".to_string()),
+            "email".to_string(),
+            crate::config::Strategy::Email.into(),
+        )];
+        let state = AppState::new_for_test(config, "proxy.yaml".to_string());
+        let fetched_at = chrono::Utc::now();
+        *state.schema_snapshot.write().await = Some(SchemaSnapshot {
+            schema: sample_schema(),
+            fetched_at,
+        });
+
+        // A fresh snapshot for the same database and schema answers the
+        // request without touching the upstream
+        let response = get_schema(
+            State(state.clone()),
+            axum::extract::Query(SchemaQuery { refresh: false }),
+            Json(ScanConfig {
+                username: "scanner".to_string(),
+                password: String::new(),
+                database: "appdb".to_string(),
+                sample_size: 100,
+                schema: "public".to_string(),
+                exclude_tables: Vec::new(),
+                confidence_threshold: 0.5,
+            }),
+        )
+        .await
+        .into_response();
+        let (parts, body) = response.into_parts();
+        assert_eq!(parts.status, StatusCode::OK);
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["cached"], true);
+        assert_eq!(json["fetched_at"], fetched_at.to_rfc3339());
+        assert_eq!(
+            json["tables"][0]["columns"][0]["masked_by"]["strategy"],
+            "email"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_logs_pagination_and_filters() {
+        use crate::state::LogEntry;
+
+        let state = AppState::new_for_test(AppConfig::default(), "proxy.yaml".to_string());
+        let start = chrono::Utc::now();
+        for i in 1..=6 {
+            state
+                .add_log(LogEntry {
+                    id: i.to_string(),
+                    timestamp: start + chrono::Duration::seconds(i),
+                    connection_id: (i % 2) as usize,
+                    event_type: if i % 3 == 0 { "masking" } else { "query" }.to_string(),
+                    content: format!("entry {}", i),
+                    details: None,
+                })
+                .await;
+        }
+
+        // Newest-first with limit/offset paging, total counting all matches
+        let body = get_logs(
+            State(state.clone()),
+            axum::extract::Query(LogsQuery {
+                limit: Some(2),
+                offset: 1,
+                event_type: None,
+                connection_id: None,
+                since: None,
+            }),
+        )
+        .await
+        .0;
+        assert_eq!(body["total"], 6);
+        assert_eq!(body["evicted"], false);
+        assert_eq!(body["logs"][0]["id"], "5");
+        assert_eq!(body["logs"][1]["id"], "4");
+
+        // Filters compose: event type, connection id, and a since bound
+        let body = get_logs(
+            State(state.clone()),
+            axum::extract::Query(LogsQuery {
+                limit: None,
+                offset: 0,
+                event_type: Some("masking".to_string()),
+                connection_id: Some(0),
+                since: Some(start + chrono::Duration::seconds(4)),
+            }),
+        )
+        .await
+        .0;
+        assert_eq!(body["total"], 1);
+        assert_eq!(body["logs"][0]["id"], "6");
+    }
+
+    #[tokio::test]
+    async fn test_log_event_stream_filters_and_marks_lag() {
+        use crate::state::LogEntry;
+        use futures::StreamExt;
+
+        let entry = |id: &str, event_type: &str| LogEntry {
+            id: id.to_string(),
+            timestamp: chrono::Utc::now(),
+            connection_id: 0,
+            event_type: event_type.to_string(),
+            content: String::new(),
+            details: None,
+        };
+
+        // Filtered entries are skipped and the stream ends with the sender
+        let (tx, rx) = tokio::sync::broadcast::channel(8);
+        let mut stream = Box::pin(log_event_stream(rx, Some("masking".to_string())));
+        tx.send(entry("1", "query")).unwrap();
+        tx.send(entry("2", "masking")).unwrap();
+        drop(tx);
+        assert!(stream.next().await.unwrap().is_ok());
+        assert!(stream.next().await.is_none());
+
+        // Overrunning a slow consumer yields a lagged marker, then resumes
+        let (tx, rx) = tokio::sync::broadcast::channel(1);
+        let mut stream = Box::pin(log_event_stream(rx, None));
+        tx.send(entry("1", "query")).unwrap();
+        tx.send(entry("2", "query")).unwrap();
+        tx.send(entry("3", "query")).unwrap();
+        drop(tx);
+        let lagged = stream.next().await.unwrap().unwrap();
+        assert!(format!("{:?}", lagged).contains("lagged"), "{:?}", lagged);
+        assert!(stream.next().await.unwrap().is_ok());
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_connection_detail_endpoint() {
+        let state = AppState::new_for_test(AppConfig::default(), "proxy.yaml".to_string());
+        state
+            .register_client(3, "10.0.0.1:5000".parse().unwrap())
+            .await;
+        state
+            .set_client_session(3, Some("alice".to_string()), Some("app".to_string()), None)
+            .await;
+        state
+            .set_client_upstream(3, "db.internal:5432".to_string())
+            .await;
+        state.record_client_masked_row(3, 2).await;
+        let counters = state.client_counters(3).await.unwrap();
+        counters.bytes_in.fetch_add(120, Ordering::Relaxed);
+        counters.bytes_out.fetch_add(4096, Ordering::Relaxed);
+        counters.rows_forwarded.fetch_add(10, Ordering::Relaxed);
+
+        let response = get_connection(State(state.clone()), Path(3))
+            .await
+            .into_response();
+        let (parts, body) = response.into_parts();
+        assert_eq!(parts.status, StatusCode::OK);
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["connection_id"], 3);
+        assert_eq!(json["upstream"], "db.internal:5432");
+        assert_eq!(json["username"], "alice");
+        assert_eq!(json["rows_masked"], 1);
+        assert_eq!(json["cells_masked"], 2);
+        assert_eq!(json["bytes_in"], 120);
+        assert_eq!(json["bytes_out"], 4096);
+        assert_eq!(json["rows_forwarded"], 10);
+
+        // Unknown and already-closed connections 404
+        state.clear_client(3).await;
+        let response = get_connection(State(state.clone()), Path(3))
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_terminate_connection_cancels_token() {
+        let state = AppState::new_for_test(AppConfig::default(), "proxy.yaml".to_string());
+        state
+            .register_client(11, "10.0.0.1:5000".parse().unwrap())
+            .await;
+        let cancel = state.client_cancel(11).await.unwrap();
+        assert!(!cancel.is_cancelled());
+
+        let response = terminate_connection(
+            State(state.clone()),
+            Some(axum::Extension(ApiIdentity("ci".to_string()))),
+            Path(11),
+        )
+        .await
+        .into_response();
+        let (parts, body) = response.into_parts();
+        assert_eq!(parts.status, StatusCode::OK);
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["status"], "terminated");
+        assert!(cancel.is_cancelled());
+
+        // A connection that is already gone 404s
+        let response = terminate_connection(State(state.clone()), None, Path(99))
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_reload_endpoint_diff_and_dry_run() {
+        let path = "/tmp/test_api_reload.yaml";
+        std::fs::write(
+            path,
+            "rules:\n  - table: users\n    column: email\n    strategy: phone\n\
+             \x20 - table: users\n    column: ssn\n    strategy: ssn\n",
+        )
+        .unwrap();
+        let config = AppConfig {
+            rules: vec![MaskingRule::basic(
+                Some("users".to_string()),
+                "email".to_string(),
+                crate::config::Strategy::Email.into(),
+            )],
+            ..Default::default()
+        };
+        let state = AppState::new_for_test(config, path.to_string());
+
+        // Dry run reports the would-be diff without swapping configs
+        let response = reload_config(
+            State(state.clone()),
+            axum::extract::Query(ReloadQuery { dry_run: true }),
+        )
+        .await
+        .into_response();
+        let (parts, body) = response.into_parts();
+        assert_eq!(parts.status, StatusCode::OK);
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["dry_run"], true);
+        assert_eq!(json["rules_count"], 2);
+        assert_eq!(json["diff"]["added"][0], "users.ssn");
+        assert_eq!(json["diff"]["changed"][0], "users.email");
+        assert_eq!(state.config.read().await.rules.len(), 1);
+
+        // A real reload swaps the config and reports the same diff
+        let response = reload_config(
+            State(state.clone()),
+            axum::extract::Query(ReloadQuery { dry_run: false }),
+        )
+        .await
+        .into_response();
+        let (parts, body) = response.into_parts();
+        assert_eq!(parts.status, StatusCode::OK);
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["rules_count"], 2);
+        assert_eq!(json["diff"]["added"][0], "users.ssn");
+        assert_eq!(state.config.read().await.rules.len(), 2);
+
+        // A broken file 400s and the running config stays as it was
+        std::fs::write(path, "rules: [").unwrap();
+        let response = reload_config(
+            State(state.clone()),
+            axum::extract::Query(ReloadQuery { dry_run: false }),
+        )
+        .await
+        .into_response();
+        let (parts, body) = response.into_parts();
+        assert_eq!(parts.status, StatusCode::BAD_REQUEST);
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["config_unchanged"], true);
+        assert_eq!(state.config.read().await.rules.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_masking_toggle_endpoint() {
+        let path = "/tmp/test_masking_toggle.yaml";
+        std::fs::write(path, "rules: []\n").unwrap();
+        let state = AppState::new_for_test(AppConfig::default(), path.to_string());
+
+        let body = get_masking(State(state.clone())).await.0;
+        assert_eq!(body["masking_enabled"], true);
+
+        // Toggling off with persist writes the flag through to disk and
+        // surfaces a warning on /health
+        let response = set_masking(
+            State(state.clone()),
+            Some(axum::Extension(ApiIdentity("ci".to_string()))),
+            axum::extract::Query(MaskingToggleQuery { persist: true }),
+            Json(MaskingToggle { enabled: false }),
+        )
+        .await
+        .into_response();
+        let (parts, body) = response.into_parts();
+        assert_eq!(parts.status, StatusCode::OK);
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["masking_enabled"], false);
+        assert_eq!(json["persisted"], true);
+        assert!(!state.config.read().await.masking_enabled);
+        let saved = std::fs::read_to_string(path).unwrap();
+        assert!(saved.contains("masking_enabled: false"), "{}", saved);
+
+        let response = health_check(State(state.clone())).await.into_response();
+        let (_, body) = response.into_parts();
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["masking_enabled"], false);
+        assert!(
+            json["warning"].as_str().unwrap().contains("disabled"),
+            "{}",
+            json
+        );
+
+        // Back on, without persisting: live config flips, file keeps false
+        let response = set_masking(
+            State(state.clone()),
+            None,
+            axum::extract::Query(MaskingToggleQuery { persist: false }),
+            Json(MaskingToggle { enabled: true }),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(state.config.read().await.masking_enabled);
+        let saved = std::fs::read_to_string(path).unwrap();
+        assert!(saved.contains("masking_enabled: false"), "{}", saved);
+    }
+
+    #[tokio::test]
+    async fn test_health_reports_upstream_detail_and_liveness_stays_up() {
+        let state = AppState::new_for_test(AppConfig::default(), "proxy.yaml".to_string());
+
+        // Healthy: 200 with the snapshot and the configured thresholds
+        let response = health_check(State(state.clone())).await.into_response();
+        let (parts, body) = response.into_parts();
+        assert_eq!(parts.status, StatusCode::OK);
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["upstream"]["thresholds"]["unhealthy_after"], 3);
+        assert_eq!(json["upstream"]["thresholds"]["healthy_after"], 1);
+
+        // An unhealthy upstream flips /health to 503 with the error, while
+        // /health/live keeps answering 200 for liveness probes
+        for _ in 0..3 {
+            state
+                .update_health_status(false, None, Some("connection refused".to_string()))
+                .await;
+        }
+        let response = health_check(State(state.clone())).await.into_response();
+        let (parts, body) = response.into_parts();
+        assert_eq!(parts.status, StatusCode::SERVICE_UNAVAILABLE);
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["status"], "degraded");
+        assert_eq!(json["upstream"]["healthy"], false);
+        assert_eq!(json["upstream"]["last_error"], "connection refused");
+        assert_eq!(json["upstream"]["consecutive_failures"], 3);
+
+        let body = liveness_check().await.0;
+        assert_eq!(body["status"], "ok");
+    }
+
+    #[test]
+    fn test_bearer_token_matching() {
+        let auth = ApiAuthConfig {
+            tokens: vec![
+                ApiTokenConfig {
+                    name: "dashboard".to_string(),
+                    token_sha256: sha256_hex("read-only-token"),
+                    role: ApiRole::Read,
+                },
+                ApiTokenConfig {
+                    name: "ci".to_string(),
+                    token_sha256: sha256_hex("admin-token").to_uppercase(),
+                    role: ApiRole::Admin,
+                },
+            ],
+        };
+
+        let matched = match_bearer_token(&auth, "read-only-token").unwrap();
+        assert_eq!(matched.name, "dashboard");
+        assert_eq!(matched.role, ApiRole::Read);
+
+        // Hash comparison is case-insensitive so hand-edited configs work
+        let matched = match_bearer_token(&auth, "admin-token").unwrap();
+        assert_eq!(matched.name, "ci");
+        assert_eq!(matched.role, ApiRole::Admin);
+
+        // Neither a wrong value nor the stored hash itself authenticates
+        assert!(match_bearer_token(&auth, "wrong-token").is_none());
+        let stored = auth.tokens[0].token_sha256.clone();
+        assert!(match_bearer_token(&auth, &stored).is_none());
+    }
+
+    // Note: a full start_scan run and get_schema require a real database
+    // connection; they are tested via E2E tests instead
 }
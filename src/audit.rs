@@ -48,6 +48,20 @@ pub enum AuditEventType {
     SchemaQuery,
     /// API access (general)
     ApiAccess,
+    /// Result set truncated by the proxy's size limits
+    ResultTruncated,
+    /// Source policy resolved for a connection
+    ConnectionPolicy,
+    /// A client tried to change a session parameter that policy pinned at
+    /// connect time
+    PinnedParameterChange,
+    /// The output verifier caught a masked cell still holding the original
+    /// PII
+    MaskingVerificationFailed,
+    /// A PII detection alert crossed its threshold
+    Alert,
+    /// A live connection was forcibly closed through the management API
+    ConnectionTerminated,
 }
 
 /// Outcome of an audit event
@@ -65,6 +79,9 @@ pub enum AuditOutcome {
 pub enum AuthMethod {
     ApiKey,
     Jwt,
+    /// A static bearer token from `api.auth.tokens`
+    BearerToken,
+    Password,
     None,
 }
 
@@ -194,6 +211,11 @@ pub struct AuditConfig {
     /// Events to log (if empty, logs all events)
     #[serde(default)]
     pub events: Vec<AuditEventType>,
+
+    /// Most entries one `GET /audit` query may return, bounding how much
+    /// of a large file a single request pulls into memory (default: 1000)
+    #[serde(default = "default_query_max_results")]
+    pub query_max_results: usize,
 }
 
 fn default_audit_enabled() -> bool {
@@ -212,6 +234,10 @@ fn default_max_rotated_files() -> usize {
     MAX_ROTATED_FILES
 }
 
+fn default_query_max_results() -> usize {
+    1000
+}
+
 impl Default for AuditConfig {
     fn default() -> Self {
         Self {
@@ -222,10 +248,39 @@ impl Default for AuditConfig {
             max_file_size_bytes: MAX_LOG_FILE_SIZE,
             max_rotated_files: MAX_ROTATED_FILES,
             events: vec![],
+            query_max_results: default_query_max_results(),
         }
     }
 }
 
+/// Filters for [`AuditLogger::scan_file`]; an entry must pass every filter
+/// that is set to count as a match
+#[derive(Debug, Default)]
+pub struct AuditFileQuery {
+    pub event_type: Option<AuditEventType>,
+    pub outcome: Option<AuditOutcome>,
+    /// Matches the entry's `user_id` exactly
+    pub actor: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// Also scan the rotated `<file>.1` … `<file>.N` files
+    pub include_rotated: bool,
+    /// Matches to skip from the newest end before taking `limit`
+    pub offset: usize,
+    pub limit: usize,
+}
+
+/// One page of matches from the on-disk audit log
+#[derive(Debug)]
+pub struct AuditFileScan {
+    /// The requested page, most recent entry first
+    pub entries: Vec<AuditEntry>,
+    /// Matches seen across the whole scan, beyond this page
+    pub matched: usize,
+    /// Lines that did not parse as audit entries and were skipped
+    pub malformed: usize,
+}
+
 /// The audit logger
 #[derive(Clone)]
 pub struct AuditLogger {
@@ -368,6 +423,93 @@ impl AuditLogger {
         Ok(())
     }
 
+    /// Scan the on-disk audit log for entries matching `query`, one line
+    /// at a time so a multi-gigabyte file never loads into memory: only
+    /// the newest `offset + limit` matches are retained while scanning.
+    /// With `include_rotated`, rotated files are read oldest-first ahead
+    /// of the live file so the retained window stays the newest overall.
+    pub fn scan_file(
+        path: &Path,
+        query: &AuditFileQuery,
+        max_rotated_files: usize,
+    ) -> std::io::Result<AuditFileScan> {
+        use std::io::BufRead;
+
+        let keep = query.offset.saturating_add(query.limit);
+        let mut recent: VecDeque<AuditEntry> = VecDeque::new();
+        let mut matched = 0usize;
+        let mut malformed = 0usize;
+
+        let mut files: Vec<PathBuf> = Vec::new();
+        if query.include_rotated {
+            for i in (1..=max_rotated_files).rev() {
+                let rotated = PathBuf::from(format!("{}.{}", path.display(), i));
+                if rotated.exists() {
+                    files.push(rotated);
+                }
+            }
+        }
+        files.push(path.to_path_buf());
+
+        for file in files {
+            let reader = std::io::BufReader::new(std::fs::File::open(&file)?);
+            for line in reader.lines() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let Ok(entry) = serde_json::from_str::<AuditEntry>(&line) else {
+                    malformed += 1;
+                    continue;
+                };
+                if let Some(event_type) = &query.event_type
+                    && entry.event_type != *event_type
+                {
+                    continue;
+                }
+                if let Some(outcome) = &query.outcome
+                    && entry.outcome != *outcome
+                {
+                    continue;
+                }
+                if let Some(actor) = &query.actor
+                    && entry.user_id.as_deref() != Some(actor.as_str())
+                {
+                    continue;
+                }
+                if let Some(since) = query.since
+                    && entry.timestamp < since
+                {
+                    continue;
+                }
+                if let Some(until) = query.until
+                    && entry.timestamp > until
+                {
+                    continue;
+                }
+                matched += 1;
+                if keep > 0 {
+                    if recent.len() == keep {
+                        recent.pop_front();
+                    }
+                    recent.push_back(entry);
+                }
+            }
+        }
+
+        let entries: Vec<AuditEntry> = recent
+            .into_iter()
+            .rev()
+            .skip(query.offset)
+            .take(query.limit)
+            .collect();
+        Ok(AuditFileScan {
+            entries,
+            matched,
+            malformed,
+        })
+    }
+
     /// Get recent audit entries
     pub async fn get_entries(&self, limit: Option<usize>) -> Vec<AuditEntry> {
         let entries = self.entries.read().await;
@@ -424,6 +566,46 @@ impl AuditLogger {
             .with_error(error)
     }
 
+    /// Create an entry for a proxied session's authentication outcome, as
+    /// observed from the upstream's side of the exchange. The mechanism is
+    /// whatever the upstream advertised (e.g. `SCRAM-SHA-256`), if any.
+    pub fn session_auth(
+        user: Option<&str>,
+        mechanism: Option<&str>,
+        success: bool,
+    ) -> AuditEntry {
+        let outcome = if success {
+            AuditOutcome::Success
+        } else {
+            AuditOutcome::Failure
+        };
+        let mut entry = AuditEntry::new(AuditEventType::AuthAttempt, outcome);
+        if let Some(user) = user {
+            entry = entry.with_user_id(user);
+        }
+        if let Some(mechanism) = mechanism {
+            entry = entry.with_details(serde_json::json!({ "mechanism": mechanism }));
+        }
+        entry
+    }
+
+    /// Create an API access entry for a static bearer-token request. Only
+    /// the token's configured name is recorded, never its value.
+    pub fn api_access(token_name: Option<&str>, success: bool) -> AuditEntry {
+        let outcome = if success {
+            AuditOutcome::Success
+        } else {
+            AuditOutcome::Failure
+        };
+        let mut entry = AuditEntry::new(AuditEventType::ApiAccess, outcome)
+            .with_auth_method(AuthMethod::BearerToken);
+        match token_name {
+            Some(name) => entry = entry.with_user_id(name),
+            None => entry = entry.with_error("unknown bearer token"),
+        }
+        entry
+    }
+
     /// Create an authentication denied entry (no credentials provided)
     pub fn auth_denied() -> AuditEntry {
         AuditEntry::new(AuditEventType::AuthAttempt, AuditOutcome::Denied)
@@ -452,10 +634,19 @@ impl AuditLogger {
             .with_details(serde_json::json!({ "rules_count": count }))
     }
 
-    /// Create a config reload entry
-    pub fn config_reload(rules_count: usize) -> AuditEntry {
-        AuditEntry::new(AuditEventType::ConfigReload, AuditOutcome::Success)
-            .with_details(serde_json::json!({ "rules_count": rules_count }))
+    /// Create a config reload entry; `trigger` records what initiated the
+    /// reload ("api" or "file_watcher")
+    pub fn config_reload(rules_count: usize, trigger: &str) -> AuditEntry {
+        AuditEntry::new(AuditEventType::ConfigReload, AuditOutcome::Success).with_details(
+            serde_json::json!({ "rules_count": rules_count, "trigger": trigger }),
+        )
+    }
+
+    /// Create an entry for a reload that was rejected at staging, naming the
+    /// failing component; the previous config kept serving
+    pub fn config_reload_failed(error: &str, trigger: &str) -> AuditEntry {
+        AuditEntry::new(AuditEventType::ConfigReload, AuditOutcome::Failure)
+            .with_details(serde_json::json!({ "error": error, "trigger": trigger }))
     }
 
     /// Create a database scan entry
@@ -468,6 +659,16 @@ impl AuditLogger {
         )
     }
 
+    /// Create an entry for a connection killed via `DELETE /connections/{id}`
+    pub fn connection_terminated(connection_id: usize, client_addr: &str) -> AuditEntry {
+        AuditEntry::new(AuditEventType::ConnectionTerminated, AuditOutcome::Success).with_details(
+            serde_json::json!({
+                "connection_id": connection_id,
+                "client_addr": client_addr
+            }),
+        )
+    }
+
     /// Create a schema query entry
     pub fn schema_query(database: &str, tables_count: usize) -> AuditEntry {
         AuditEntry::new(AuditEventType::SchemaQuery, AuditOutcome::Success).with_details(
@@ -477,6 +678,105 @@ impl AuditLogger {
             }),
         )
     }
+
+    /// Create a result truncation entry
+    pub fn result_truncated(
+        username: Option<&str>,
+        rows_forwarded: u64,
+        bytes_forwarded: u64,
+        limit: &str,
+    ) -> AuditEntry {
+        let entry = AuditEntry::new(AuditEventType::ResultTruncated, AuditOutcome::Success)
+            .with_details(serde_json::json!({
+                "rows_forwarded": rows_forwarded,
+                "bytes_forwarded": bytes_forwarded,
+                "limit": limit
+            }));
+        match username {
+            Some(user) => entry.with_user_id(user),
+            None => entry,
+        }
+    }
+
+    /// Create a source policy resolution entry
+    pub fn source_policy(
+        client_ip: &str,
+        connection_id: usize,
+        action: crate::config::PolicyAction,
+    ) -> AuditEntry {
+        let outcome = if action == crate::config::PolicyAction::Reject {
+            AuditOutcome::Denied
+        } else {
+            AuditOutcome::Success
+        };
+        AuditEntry::new(AuditEventType::ConnectionPolicy, outcome)
+            .with_client_ip(client_ip)
+            .with_details(serde_json::json!({
+                "connection_id": connection_id,
+                "action": action
+            }))
+    }
+
+    /// Create an entry for a session whose user is on
+    /// `masking.bypass_users` and therefore gets raw rows
+    pub fn masking_bypassed(user: &str, connection_id: usize) -> AuditEntry {
+        AuditEntry::new(AuditEventType::ConnectionPolicy, AuditOutcome::Success)
+            .with_user_id(user)
+            .with_details(serde_json::json!({
+                "connection_id": connection_id,
+                "action": "masking_bypassed"
+            }))
+    }
+
+    /// Create an entry for the output verifier catching a masked cell that
+    /// still held the original PII
+    pub fn masking_verification_failed(
+        connection_id: usize,
+        column_idx: usize,
+        pii_type: &str,
+    ) -> AuditEntry {
+        AuditEntry::new(
+            AuditEventType::MaskingVerificationFailed,
+            AuditOutcome::Failure,
+        )
+        .with_details(serde_json::json!({
+            "connection_id": connection_id,
+            "column_idx": column_idx,
+            "pii_type": pii_type
+        }))
+    }
+
+    /// Create an entry for a PII detection alert crossing its threshold
+    pub fn alert_fired(
+        name: &str,
+        pii_type: Option<&str>,
+        detections_in_window: u64,
+        quarantined_column: Option<&str>,
+    ) -> AuditEntry {
+        AuditEntry::new(AuditEventType::Alert, AuditOutcome::Failure).with_details(
+            serde_json::json!({
+                "alert": name,
+                "pii_type": pii_type,
+                "detections_in_window": detections_in_window,
+                "quarantined_column": quarantined_column
+            }),
+        )
+    }
+
+    /// Create an entry for an attempt to change a pinned session parameter
+    pub fn pinned_parameter_change(
+        connection_id: usize,
+        parameter: &str,
+        attempted_value: &str,
+    ) -> AuditEntry {
+        AuditEntry::new(AuditEventType::PinnedParameterChange, AuditOutcome::Success).with_details(
+            serde_json::json!({
+                "connection_id": connection_id,
+                "parameter": parameter,
+                "attempted_value": attempted_value
+            }),
+        )
+    }
 }
 
 #[cfg(test)]
@@ -658,8 +958,9 @@ mod tests {
         let rules_imported = AuditLogger::rules_imported(5);
         assert_eq!(rules_imported.event_type, AuditEventType::RulesImported);
 
-        let config_reload = AuditLogger::config_reload(10);
+        let config_reload = AuditLogger::config_reload(10, "api");
         assert_eq!(config_reload.event_type, AuditEventType::ConfigReload);
+        assert_eq!(config_reload.details.unwrap()["trigger"], "api");
 
         let db_scan = AuditLogger::database_scan("testdb", 3);
         assert_eq!(db_scan.event_type, AuditEventType::DatabaseScan);
@@ -668,6 +969,66 @@ mod tests {
         assert_eq!(schema_query.event_type, AuditEventType::SchemaQuery);
     }
 
+    #[test]
+    fn test_scan_file_filters_and_pagination() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+
+        // Rotated file holds the oldest entries; the live file the newest,
+        // plus one line of garbage that must be counted, not fatal.
+        let mut lines = Vec::new();
+        for i in 0..3 {
+            let mut entry = AuditEntry::new(AuditEventType::AuthAttempt, AuditOutcome::Success);
+            entry.id = format!("old-{}", i);
+            entry.user_id = Some("alice".to_string());
+            lines.push(serde_json::to_string(&entry).unwrap());
+        }
+        std::fs::write(format!("{}.1", path.display()), lines.join("\n") + "\n").unwrap();
+
+        let mut lines = Vec::new();
+        for i in 0..3 {
+            let mut entry = AuditEntry::new(AuditEventType::ConfigChange, AuditOutcome::Failure);
+            entry.id = format!("new-{}", i);
+            entry.user_id = Some("bob".to_string());
+            lines.push(serde_json::to_string(&entry).unwrap());
+        }
+        lines.push("not json".to_string());
+        std::fs::write(&path, lines.join("\n") + "\n").unwrap();
+
+        // Unfiltered scan of the live file only: newest first, garbage counted
+        let query = AuditFileQuery {
+            limit: 10,
+            ..Default::default()
+        };
+        let scan = AuditLogger::scan_file(&path, &query, 5).unwrap();
+        assert_eq!(scan.matched, 3);
+        assert_eq!(scan.malformed, 1);
+        assert_eq!(scan.entries[0].id, "new-2");
+
+        // Rotated files included, filtered to the older event type
+        let query = AuditFileQuery {
+            event_type: Some(AuditEventType::AuthAttempt),
+            include_rotated: true,
+            limit: 10,
+            ..Default::default()
+        };
+        let scan = AuditLogger::scan_file(&path, &query, 5).unwrap();
+        assert_eq!(scan.matched, 3);
+        assert!(scan.entries.iter().all(|e| e.id.starts_with("old-")));
+
+        // Actor filter plus pagination: skip the newest match
+        let query = AuditFileQuery {
+            actor: Some("bob".to_string()),
+            offset: 1,
+            limit: 1,
+            ..Default::default()
+        };
+        let scan = AuditLogger::scan_file(&path, &query, 5).unwrap();
+        assert_eq!(scan.matched, 3);
+        assert_eq!(scan.entries.len(), 1);
+        assert_eq!(scan.entries[0].id, "new-1");
+    }
+
     #[tokio::test]
     async fn test_memory_limit() {
         let logger = AuditLogger::new(AuditConfig::default());
@@ -1,26 +1,153 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::fs;
+use std::str::FromStr;
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct AppConfig {
     #[serde(default = "default_masking_enabled")]
     pub masking_enabled: bool,
+    /// Re-scan outgoing masked values for PII the strategy claimed to
+    /// remove (default: off)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verify_output: Option<VerifyOutputConfig>,
     pub rules: Vec<MaskingRule>,
+    /// Proxy listener sockets (default: one `0.0.0.0` listener on the
+    /// builder/CLI port)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub listen: Option<ListenConfig>,
+    /// Upstream database target, the config-file counterpart of the
+    /// `--upstream-host`, `--upstream-port`, and `--protocol` flags. A flag
+    /// passed explicitly on the command line pins its field; otherwise a
+    /// reload applies host/port changes to new connections while existing
+    /// ones keep their current target (default: the CLI/builder values)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstream: Option<UpstreamConfig>,
     #[serde(default)]
     pub tls: Option<TlsConfig>,
     #[serde(default)]
     pub upstream_tls: bool,
+    /// How the upstream server certificate is verified when `upstream_tls`
+    /// is enabled (default: the platform trust store with hostname
+    /// checking)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstream_tls_options: Option<UpstreamTlsConfig>,
+    /// Route PostgreSQL sessions to different upstreams by the database
+    /// named in the startup message (default: every session goes to the
+    /// builder upstream)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub routing: Option<RoutingConfig>,
     #[serde(default)]
     pub telemetry: Option<TelemetryConfig>,
     #[serde(default)]
+    pub metrics: Option<MetricsConfig>,
+    #[serde(default)]
     pub api: Option<ApiConfig>,
     #[serde(default)]
     pub limits: Option<LimitsConfig>,
+    /// Masking policies keyed on the client's source network, resolved at
+    /// connection setup (default: none, every source gets full masking)
+    #[serde(default)]
+    pub policies_by_source: Vec<SourcePolicy>,
+    /// Whether a mid-session `SET application_name` re-resolves source
+    /// policies or is pinned to the connect-time value
+    #[serde(default)]
+    pub application_name_policy: ApplicationNamePolicy,
+    /// How SELECT expressions over rule-matched columns are masked
+    #[serde(default)]
+    pub expression_handling: ExpressionHandling,
+    /// Run content heuristics on non-text columns too. Off by default:
+    /// integers and timestamps cannot contain free-form PII, so scanning
+    /// them costs cycles for nothing
+    #[serde(default)]
+    pub scan_typed_columns: bool,
+    /// Entries in the per-connection memo of masked values, so values
+    /// repeated across rows are masked once (0 disables; default 4096)
+    #[serde(default = "default_memo_entries")]
+    pub memo_entries: usize,
+    /// Entries kept in the in-memory event log served by `GET /logs`;
+    /// the oldest entry is evicted once the buffer is full (default 100)
+    #[serde(default = "default_log_buffer_entries")]
+    pub log_buffer_entries: usize,
+    /// Watch the config file and reload it automatically when it changes.
+    /// Off by default: reloads happen through the API or a restart. Read
+    /// once at startup, so toggling it requires a restart
+    #[serde(default)]
+    pub watch: bool,
     #[serde(default)]
     pub health_check: Option<HealthCheckConfig>,
     #[serde(default)]
     pub audit: Option<AuditConfig>,
+    /// Alerting on heuristic PII detections (default: no alerts)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alerts: Option<AlertsConfig>,
+    /// Admin console pseudo-database (default: disabled)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin: Option<AdminConfig>,
+    /// Resolving RowDescription table OIDs to table names over a
+    /// maintenance connection, so rules scoped with `table:` fire only for
+    /// columns that actually originate from that table (default: off)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oid_resolution: Option<OidResolutionConfig>,
+    /// Prefetching the upstream catalog at startup and on a refresh
+    /// cadence, so the first query of a fresh session is already
+    /// table-aware (default: lazy per-OID resolution only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema_cache: Option<SchemaCacheConfig>,
+    /// Engine-wide masking settings, currently the seed-derivation key
+    /// that keeps deterministic masking stable across restarts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub masking: Option<MaskingConfig>,
+    /// Tuning for the content heuristics (default: scan everything)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scanner: Option<ScannerConfig>,
+    /// Keyed hashing for the `hash` strategy, so the same input maps to
+    /// the same token across connections and restarts (required whenever
+    /// a rule uses `hash`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hashing: Option<HashingConfig>,
+    /// Named regex-replace strategies rules can reference by bare name
+    /// (e.g. `strategy: scrub_order`), keeping the rules list readable when
+    /// several columns share a pattern. Registered alongside the embedder's
+    /// custom strategies when the proxy starts, so a reload that introduces
+    /// a new name needs a restart to take effect (default: none)
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub custom_strategies: std::collections::BTreeMap<String, RegexStrategyDef>,
+}
+
+/// The `upstream` config section; every field is optional so a partial
+/// section only overrides what it names
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct UpstreamConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// Read once at startup; changing it requires a restart
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<UpstreamProtocol>,
+    /// Seconds allowed for the upstream TCP connect, taking precedence
+    /// over `limits.connect_timeout` when both are set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_timeout: Option<u64>,
+}
+
+/// Database protocol named by the `upstream` config section
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UpstreamProtocol {
+    Postgres,
+    Mysql,
+}
+
+/// A named strategy from the `custom_strategies` section: `pattern` is
+/// applied to the whole value with replace-all, and `replacement` may
+/// reference capture groups as `$1`, `${name}`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RegexStrategyDef {
+    pub pattern: String,
+    pub replacement: String,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -40,12 +167,763 @@ pub struct LimitsConfig {
     /// Idle timeout in seconds - close connection after no activity (default: 300)
     #[serde(default = "default_idle_timeout")]
     pub idle_timeout_secs: u64,
+
+    /// Maximum rows forwarded to a client per statement (default: unlimited)
+    #[serde(default)]
+    pub max_result_rows: Option<u64>,
+
+    /// Maximum result bytes forwarded to a client per statement (default: unlimited)
+    #[serde(default)]
+    pub max_result_bytes: Option<u64>,
+
+    /// Session usernames exempt from the result size caps, e.g. service
+    /// accounts that legitimately pull whole tables (default: none)
+    #[serde(default)]
+    pub result_limit_exempt_users: Vec<String>,
+
+    /// Maximum size in bytes of a single client-origin protocol frame
+    /// (default: 16 MiB). A frame claiming more is rejected before any
+    /// buffer is allocated for it.
+    #[serde(default = "default_max_client_message_bytes")]
+    pub max_client_message_bytes: usize,
+
+    /// Maximum size in bytes of a single upstream-origin frame (default:
+    /// 1 GiB, the hard limit Postgres itself enforces). Separate from the
+    /// client cap because legitimate result rows can be large.
+    #[serde(default = "default_max_upstream_message_bytes")]
+    pub max_upstream_message_bytes: usize,
+
+    /// How long a partially received frame may sit incomplete before the
+    /// connection is closed as a protocol violation (default: 30). Much
+    /// shorter than the idle timeout: a half-sent frame holds a buffer,
+    /// a quiet connection does not.
+    #[serde(default = "default_frame_timeout")]
+    pub frame_timeout_secs: u64,
+
+    /// Refuse new connections from a source address for `greylist_secs`
+    /// after it commits this many protocol violations (default: disabled)
+    #[serde(default)]
+    pub greylist_after_violations: Option<u32>,
+
+    /// How long a greylisted source address is refused, in seconds
+    /// (default: 60)
+    #[serde(default = "default_greylist_secs")]
+    pub greylist_secs: u64,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: None,
+            connections_per_second: None,
+            connect_timeout_secs: default_connect_timeout(),
+            idle_timeout_secs: default_idle_timeout(),
+            max_result_rows: None,
+            max_result_bytes: None,
+            result_limit_exempt_users: Vec::new(),
+            max_client_message_bytes: default_max_client_message_bytes(),
+            max_upstream_message_bytes: default_max_upstream_message_bytes(),
+            frame_timeout_secs: default_frame_timeout(),
+            greylist_after_violations: None,
+            greylist_secs: default_greylist_secs(),
+        }
+    }
+}
+
+/// How rule binding treats a SELECT expression computed over a column that
+/// matches a masking rule (e.g. `upper(email)`).
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpressionHandling {
+    /// Mask the expression output with the matched rule's strategy
+    Sensitive,
+    /// Leave expression outputs to heuristic PII detection (the default)
+    #[default]
+    Heuristic,
+}
+
+/// What a resolved access policy lets a session see.
+///
+/// Variants are ordered from least to most restrictive, so "most restrictive
+/// wins" is a plain `max`.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyAction {
+    /// Forward rows unmasked
+    Unmasked,
+    /// Apply explicit masking rules only, skipping heuristic PII detection
+    Partial,
+    /// Apply explicit rules and heuristic PII detection (the default)
+    Mask,
+    /// Refuse the connection
+    Reject,
+}
+
+/// A masking policy keyed on where and what the client connects from. All
+/// present matchers must hold for the policy to apply.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SourcePolicy {
+    /// Network in CIDR notation, IPv4 or IPv6 (e.g. `10.8.0.0/16` or
+    /// `fd00:1::/32`; default: any source)
+    #[serde(default)]
+    pub cidr: Option<String>,
+
+    /// Glob matched against the `application_name` startup parameter
+    /// (default: any; a connection without the parameter never matches)
+    #[serde(default)]
+    pub application_name: Option<String>,
+
+    /// Additional startup parameters that must all match, parameter name to
+    /// glob (default: none)
+    #[serde(default)]
+    pub parameters: std::collections::BTreeMap<String, String>,
+
+    pub action: PolicyAction,
+
+    /// Precedence among matching entries: the highest priority wins, and
+    /// entries tied on priority resolve to the most restrictive action
+    /// (default: 0)
+    #[serde(default)]
+    pub priority: i32,
+
+    /// Row sampling applied to sessions under this policy, for carving
+    /// reduced development datasets out of live traffic. Strictly opt-in
+    /// per policy: never set it on a production policy, since dropped rows
+    /// are silently thinned from every result (default: none)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sampling: Option<SamplingConfig>,
+}
+
+/// The admin console pseudo-database, in the spirit of PgBouncer's
+/// "pgbouncer" database: a client whose StartupMessage names
+/// `database_name` is served an in-process SHOW console instead of being
+/// proxied upstream.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AdminConfig {
+    /// Database name that selects the console
+    #[serde(default = "default_admin_database")]
+    pub database_name: String,
+    /// Cleartext password required at startup. Optional when `allow_cidr`
+    /// restricts access by source network instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// Source network admitted without a password
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow_cidr: Option<String>,
+}
+
+/// Credentials and cache policy for the maintenance connection that
+/// resolves table OIDs against `pg_class`/`pg_namespace`
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct OidResolutionConfig {
+    pub username: String,
+    pub password: String,
+    /// Database to resolve against; OIDs are database-local
+    pub database: String,
+    /// Seconds a cached mapping (including misses) stays valid
+    #[serde(default = "default_oid_ttl_secs")]
+    pub ttl_secs: u64,
+}
+
+fn default_oid_ttl_secs() -> u64 {
+    300
+}
+
+/// Catalog prefetch policy. The prefetch connection reuses the
+/// `oid_resolution` maintenance credentials, so that section must be
+/// configured too.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SchemaCacheConfig {
+    /// Prefetch the full OID-to-table map and column lists at startup
+    #[serde(default = "default_schema_prefetch")]
+    pub prefetch: bool,
+    /// Seconds between background refreshes
+    #[serde(default = "default_schema_refresh_secs")]
+    pub refresh_interval_secs: u64,
+}
+
+/// Configuration for the `hash` strategy: HMAC-SHA256 over the value with
+/// a stable key, so masked datasets stay joinable on hashed identifiers.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct HashingConfig {
+    /// HMAC key. The `IRONVEIL_HASH_KEY` environment variable overrides
+    /// it, keeping the key itself out of config files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// Encoding of the digest (default hex)
+    #[serde(default)]
+    pub encoding: HashEncoding,
+    /// Keep only the first N characters of the encoded digest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub truncate: Option<usize>,
+}
+
+impl HashingConfig {
+    /// The effective key: the `IRONVEIL_HASH_KEY` environment variable
+    /// when set, else the config's
+    pub fn resolved_key(&self) -> Option<String> {
+        std::env::var("IRONVEIL_HASH_KEY")
+            .ok()
+            .or_else(|| self.key.clone())
+    }
+}
+
+/// Tuning for the content heuristics that scan unruled columns.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ScannerConfig {
+    /// Skip private, loopback, and link-local addresses when detecting
+    /// IPs (default false): 127.0.0.1 in a config table is not PII
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub ignore_private_ips: bool,
+    /// Scan free text for embedded PII and mask just the matched spans,
+    /// leaving the prose around them (default false): substring scanning
+    /// runs every detector over every position of every unruled value, a
+    /// real cost next to the whole-value checks
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub scan_substrings: bool,
+    /// Minimum confidence (0-1, default 0.8) a heuristic detection needs
+    /// before the row path masks it. Detector scores reflect how specific
+    /// the matched shape is — separators, checksums, and a column name
+    /// hinting at the type all push a candidate up, so a bare ten-digit
+    /// number masks in a `phone` column but not in an order-id one.
+    #[serde(
+        default = "default_min_confidence",
+        skip_serializing_if = "is_default_min_confidence"
+    )]
+    pub min_confidence: f32,
+    /// Countries whose national identifier patterns the scanner checks
+    /// (default `["US"]`). Only the listed locales are compiled in, so a
+    /// German deployment is not flagging eleven-digit order numbers as
+    /// SSNs and vice versa.
+    #[serde(
+        default = "default_locales",
+        skip_serializing_if = "is_default_locales"
+    )]
+    pub locales: Vec<Locale>,
+    /// Column-name fragments (case-insensitive) that mark a date value as
+    /// a birth date (default `["dob", "birth"]`). A date only masks
+    /// heuristically when the column name contains one of these, so
+    /// `created_at` timestamps are never touched; teams with their own
+    /// naming conventions extend the list.
+    #[serde(
+        default = "dob_column_hints_default",
+        skip_serializing_if = "is_default_dob_column_hints"
+    )]
+    pub dob_column_hints: Vec<String>,
+    /// Operator-defined patterns for identifiers the built-in detectors
+    /// cannot know about (employee IDs, patient MRNs, ticket numbers).
+    /// Detections report under the entry's name and, absent an explicit
+    /// rule, mask with its strategy (default: none)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub custom_patterns: Vec<CustomPatternConfig>,
+}
+
+/// One `scanner.custom_patterns` entry: a named regex the scanner checks
+/// before the built-in detectors, so company identifiers that happen to
+/// look like a passport number report under their own name.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CustomPatternConfig {
+    /// Name detections report under (e.g. `mrn`)
+    pub name: String,
+    /// Regex a value must match in full; validation rejects patterns that
+    /// do not compile
+    pub pattern: String,
+    /// Strategy applied when a match has no explicit rule covering its
+    /// column (default `redact`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<Strategy>,
+    /// Reporting severity attached to detections of this pattern
+    #[serde(default, skip_serializing_if = "is_default_severity")]
+    pub severity: PatternSeverity,
+}
+
+/// Reporting severity of a custom pattern detection, carried into alert
+/// logs and webhook payloads.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PatternSeverity {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+impl PatternSeverity {
+    /// The config-file name, used in logs and webhook payloads
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PatternSeverity::Low => "low",
+            PatternSeverity::Medium => "medium",
+            PatternSeverity::High => "high",
+        }
+    }
+}
+
+fn is_default_severity(severity: &PatternSeverity) -> bool {
+    *severity == PatternSeverity::default()
+}
+
+/// The `scanner.min_confidence` applied when the section or field is
+/// absent, shared with the interceptors
+pub(crate) const DEFAULT_MIN_CONFIDENCE: f32 = 0.8;
+
+fn default_min_confidence() -> f32 {
+    DEFAULT_MIN_CONFIDENCE
+}
+
+fn is_default_min_confidence(value: &f32) -> bool {
+    *value == DEFAULT_MIN_CONFIDENCE
+}
+
+/// A country whose national identifier format the scanner knows, named
+/// by its `scanner.locales` country code.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Locale {
+    /// US Social Security numbers
+    Us,
+    /// UK National Insurance numbers
+    Uk,
+    /// German Steuer-IDs
+    De,
+}
+
+/// The `scanner.locales` applied when the section or field is absent,
+/// matching the scanner's historical US-only behavior
+pub(crate) const DEFAULT_LOCALES: &[Locale] = &[Locale::Us];
+
+fn default_locales() -> Vec<Locale> {
+    DEFAULT_LOCALES.to_vec()
+}
+
+fn is_default_locales(locales: &[Locale]) -> bool {
+    locales == DEFAULT_LOCALES
+}
+
+/// The `scanner.dob_column_hints` applied when the section or field is
+/// absent, shared with the interceptors
+pub(crate) fn default_dob_column_hints() -> &'static [String] {
+    static DEFAULTS: std::sync::OnceLock<Vec<String>> = std::sync::OnceLock::new();
+    DEFAULTS.get_or_init(|| vec!["dob".to_string(), "birth".to_string()])
+}
+
+fn dob_column_hints_default() -> Vec<String> {
+    default_dob_column_hints().to_vec()
+}
+
+fn is_default_dob_column_hints(hints: &[String]) -> bool {
+    hints == default_dob_column_hints()
+}
+
+/// Settings for the masking engine as a whole, as opposed to per-rule
+/// options.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct MaskingConfig {
+    /// Secret key for deriving per-cell seeds. Seeds are
+    /// HMAC-SHA256(value, key), so without the key an outsider cannot
+    /// brute-force small value spaces (phone numbers, dates) by replaying
+    /// the derivation. The `IRONVEIL_DETERMINISM_KEY` environment variable
+    /// overrides it, keeping the key itself out of config files. When
+    /// neither is set, a random per-process key is generated and masked
+    /// values are not stable across restarts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub determinism_key: Option<String>,
+    /// Apply the content heuristics to columns no rule names (default
+    /// true). Off, the Anonymizer masks only what explicit rules cover —
+    /// for deployments where machine tokens that merely look like an
+    /// email kept getting mangled
+    #[serde(default = "default_heuristics_enabled", skip_serializing_if = "is_true")]
+    pub heuristics_enabled: bool,
+    /// Database users whose sessions bypass masking entirely, matched
+    /// against the `user` the client introduces itself with — for service
+    /// accounts like ETL that need raw rows. Every bypassed session is
+    /// logged, audited, and counted; the list is read per connection, so
+    /// a reload applies to new sessions (default: nobody bypasses)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bypass_users: Vec<String>,
+    /// Strategy overrides for the heuristic path, by detector name (the
+    /// names rule conditions' `or_detected` accept): e.g. `credit_card:
+    /// format_preserving` keeps card shapes instead of substituting a
+    /// synthetic number. Detectors not listed keep their built-in
+    /// default, explicit rules always apply their own strategy, and
+    /// unknown names fail validation
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub defaults: std::collections::BTreeMap<String, Strategy>,
+}
+
+fn default_heuristics_enabled() -> bool {
+    true
+}
+
+impl MaskingConfig {
+    /// The effective key: the `IRONVEIL_DETERMINISM_KEY` environment
+    /// variable when set, else the config's
+    pub fn resolved_determinism_key(&self) -> Option<String> {
+        std::env::var("IRONVEIL_DETERMINISM_KEY")
+            .ok()
+            .or_else(|| self.determinism_key.clone())
+    }
+}
+
+/// Encoding of the `hash` strategy's digest.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum HashEncoding {
+    #[default]
+    Hex,
+    Base64,
+}
+
+/// How the `ip` strategy anonymizes an address.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "api", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum IpMode {
+    /// Zero the host part: the last octet of an IPv4 address, the last 80
+    /// bits of an IPv6 address
+    #[default]
+    Truncate,
+    /// Deterministically remap into a reserved range — 198.18.0.0/15
+    /// (RFC 2544 benchmarking) for IPv4, 2001:db8::/32 (RFC 3849
+    /// documentation) for IPv6 — so distinct clients stay distinct without
+    /// colliding with real addresses
+    Pseudonymize,
+}
+
+fn default_schema_prefetch() -> bool {
+    true
+}
+
+fn default_schema_refresh_secs() -> u64 {
+    300
+}
+
+fn default_admin_database() -> String {
+    "iron_veil".to_string()
+}
+
+impl AdminConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.database_name.is_empty() {
+            anyhow::bail!("admin database_name must not be empty");
+        }
+        if self.password.is_none() && self.allow_cidr.is_none() {
+            anyhow::bail!(
+                "admin console must be restricted by a password or an allow_cidr"
+            );
+        }
+        if let Some(cidr) = &self.allow_cidr {
+            cidr.parse::<crate::hooks::Cidr>().map_err(|e| {
+                anyhow::anyhow!("invalid admin allow_cidr '{}': {}", cidr, e)
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Alerting on heuristic PII detections flowing to clients.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AlertsConfig {
+    pub rules: Vec<AlertRuleConfig>,
+}
+
+/// One alert condition: a detector seen more than `threshold` times within
+/// a sliding `window_secs` window fires the configured actions.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AlertRuleConfig {
+    /// Name identifying the alert in audit entries and `GET /alerts`
+    pub name: String,
+
+    /// Detector to count, by its config-file name (e.g. `email`, `ssn`;
+    /// default: every detector counts)
+    #[serde(default)]
+    pub pii_type: Option<String>,
+
+    /// Fire when strictly more than this many detections land in the window
+    pub threshold: u64,
+
+    /// Length of the sliding window in seconds
+    pub window_secs: u64,
+
+    /// Only count detections on connections under this policy action, for
+    /// alerting on low-trust sources specifically (default: every
+    /// connection counts)
+    #[serde(default)]
+    pub policy: Option<PolicyAction>,
+
+    /// URL POSTed a JSON payload when the alert fires. Plain `http://`
+    /// only: webhooks are notifications to an endpoint inside the
+    /// operator's network (default: none)
+    #[serde(default)]
+    pub webhook: Option<String>,
+
+    /// Quarantine the column the tipping detection arrived in: subsequent
+    /// result sets carry a hard redaction for it until an operator clears
+    /// it via the API (default: off)
+    #[serde(default)]
+    pub quarantine: bool,
+}
+
+impl AlertRuleConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.name.is_empty() {
+            anyhow::bail!("alert rules must have a name");
+        }
+        if let Some(pii_type) = &self.pii_type
+            && crate::scanner::PiiType::parse(pii_type).is_none()
+        {
+            anyhow::bail!(
+                "alert '{}' names unknown pii_type '{}' (known: {})",
+                self.name,
+                pii_type,
+                crate::scanner::PiiType::NAMES.join(", ")
+            );
+        }
+        if self.window_secs == 0 {
+            anyhow::bail!("alert '{}' must have a non-zero window_secs", self.name);
+        }
+        if let Some(webhook) = &self.webhook
+            && !webhook.starts_with("http://")
+        {
+            anyhow::bail!(
+                "alert '{}' webhook must be a plain http:// URL, got '{}'",
+                self.name,
+                webhook
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Defense-in-depth re-scan of outgoing masked cells.
+///
+/// After a strategy replaces a cell, the guard re-scans the output with the
+/// PII detectors: output identical to the original that still scans as PII
+/// means the strategy leaked it (a bug, a broken plugin, a memo mix-up),
+/// and the cell drops to a hard redaction before the bytes leave the proxy.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+pub struct VerifyOutputConfig {
+    #[serde(default)]
+    pub mode: VerifyOutputMode,
+
+    /// Fraction of masked cells re-scanned in sample mode, 1-100
+    /// (default 10; full mode checks every cell regardless)
+    #[serde(default = "default_verify_sample_percent")]
+    pub sample_percent: u8,
+}
+
+fn default_verify_sample_percent() -> u8 {
+    10
+}
+
+impl VerifyOutputConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=100).contains(&self.sample_percent) {
+            anyhow::bail!(
+                "verify_output sample_percent must be between 1 and 100, got {}",
+                self.sample_percent
+            );
+        }
+        Ok(())
+    }
+}
+
+/// How much of the masked output the verifier re-scans.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum VerifyOutputMode {
+    /// No re-scanning (the default)
+    #[default]
+    Off,
+    /// Re-scan a deterministic fraction of masked cells
+    Sample,
+    /// Re-scan every masked cell, accepting the cost
+    Full,
+}
+
+/// Deterministic row thinning for building reduced development datasets.
+///
+/// `percent` keeps a stable fraction of rows decided by a hash of
+/// `key_column`'s value, so result sets from different tables keyed on the
+/// same foreign key keep the same entities; `max_rows_per_result` caps what
+/// remains per result set.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SamplingConfig {
+    /// Percentage of rows to keep, 1-100 (default: no percentage thinning)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub percent: Option<u8>,
+
+    /// Column whose value drives the keep/drop decision. When the column is
+    /// not part of a result set the whole row is hashed instead, which still
+    /// thins deterministically but without cross-table affinity (default:
+    /// hash the whole row)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_column: Option<String>,
+
+    /// Hard cap on rows forwarded per result set (default: no cap)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_rows_per_result: Option<u64>,
+}
+
+impl SamplingConfig {
+    /// Checks the knobs make sense: at least one of them set, and a percent
+    /// within 1-100 (0 would drop everything; use a reject policy for that).
+    pub fn validate(&self) -> Result<()> {
+        if self.percent.is_none() && self.max_rows_per_result.is_none() {
+            anyhow::bail!("sampling must set percent or max_rows_per_result");
+        }
+        if let Some(percent) = self.percent
+            && !(1..=100).contains(&percent)
+        {
+            anyhow::bail!("sampling percent must be between 1 and 100, got {}", percent);
+        }
+        Ok(())
+    }
+}
+
+/// Routes sessions from one listener to different upstream clusters by the
+/// database the client asks for.
+///
+/// PostgreSQL only: the database name arrives in the startup message, before
+/// the proxy dials upstream. MySQL clients name their database only after
+/// the server has spoken, so MySQL connections always use the builder
+/// upstream.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RoutingConfig {
+    /// Named upstream definitions that `by_database` entries refer to
+    pub upstreams: std::collections::BTreeMap<String, UpstreamTarget>,
+
+    /// Ordered routes; the first entry whose glob matches the requested
+    /// database wins
+    pub by_database: Vec<DatabaseRoute>,
+
+    /// Named upstream that catches sessions whose database matches no
+    /// route, so the catch-all can be a listed cluster instead of the
+    /// builder upstream; takes precedence over `unmatched` when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_upstream: Option<String>,
+
+    /// What happens to a session whose database matches no route
+    /// (default: it goes to the builder upstream)
+    #[serde(default)]
+    pub unmatched: UnmatchedDatabase,
+}
+
+/// One upstream cluster a route can send sessions to.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct UpstreamTarget {
+    pub host: String,
+    pub port: u16,
+
+    /// Service account substituted for the client's `user` in the forwarded
+    /// startup message, for upstreams that authenticate the proxy rather
+    /// than end users (default: forward the client's user unchanged)
+    #[serde(default)]
+    pub user: Option<String>,
+
+    /// Protocol spoken by this upstream. Routing decides by the database
+    /// named in the PostgreSQL startup message, so only `postgres` is
+    /// accepted; the field exists so a mistaken MySQL entry fails at load
+    /// instead of on the first connection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<UpstreamProtocol>,
+}
+
+/// Maps a database-name glob to a named upstream.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DatabaseRoute {
+    /// Glob matched against the `database` startup parameter
+    pub database: String,
+    /// Key into [`RoutingConfig::upstreams`]
+    pub upstream: String,
+}
+
+/// Where sessions land when no `by_database` entry matches.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum UnmatchedDatabase {
+    /// Use the upstream the proxy was built with (the default)
+    #[default]
+    DefaultUpstream,
+    /// Refuse the session with a protocol error
+    Reject,
+}
+
+/// Outcome of routing one session. Borrowed from the [`RoutingConfig`] it
+/// was resolved against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteDecision<'a> {
+    /// A route matched; the session goes to this named upstream
+    Upstream(&'a str, &'a UpstreamTarget),
+    /// No route matched and the config falls back to the builder upstream
+    Default,
+    /// No route matched and the config rejects unmatched databases
+    Reject,
+}
+
+impl RoutingConfig {
+    /// Resolves the upstream for a session asking for `database`.
+    ///
+    /// A session that named no database matches no route and falls through
+    /// to the `unmatched` behavior. Cancel requests never reach this: the
+    /// proxy exempts them before route resolution, since a 16-byte
+    /// CancelRequest is not a session and rejecting it would swallow the
+    /// cancel.
+    pub fn resolve(&self, database: Option<&str>) -> RouteDecision<'_> {
+        if let Some(database) = database {
+            for route in &self.by_database {
+                if crate::hooks::glob_match(&route.database, database)
+                    && let Some(target) = self.upstreams.get(&route.upstream)
+                {
+                    return RouteDecision::Upstream(&route.upstream, target);
+                }
+            }
+        }
+        if let Some(name) = &self.default_upstream
+            && let Some(target) = self.upstreams.get(name)
+        {
+            return RouteDecision::Upstream(name, target);
+        }
+        match self.unmatched {
+            UnmatchedDatabase::DefaultUpstream => RouteDecision::Default,
+            UnmatchedDatabase::Reject => RouteDecision::Reject,
+        }
+    }
+}
+
+/// How a mid-session `SET application_name` interacts with source policies
+/// that were resolved from the connect-time value.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ApplicationNamePolicy {
+    /// Keep the connect-time value for policy purposes; a change attempt is
+    /// forwarded but audited and ignored by policy resolution (the default)
+    #[default]
+    Pin,
+    /// Re-resolve source policies against the new value
+    Reevaluate,
 }
 
 fn default_connect_timeout() -> u64 {
     30
 }
 
+fn default_max_client_message_bytes() -> usize {
+    16 * 1024 * 1024
+}
+
+fn default_max_upstream_message_bytes() -> usize {
+    1024 * 1024 * 1024
+}
+
+fn default_frame_timeout() -> u64 {
+    30
+}
+
+fn default_greylist_secs() -> u64 {
+    60
+}
+
 fn default_idle_timeout() -> u64 {
     300 // 5 minutes
 }
@@ -117,6 +995,101 @@ pub struct ApiConfig {
     /// If set, endpoints also accept `Authorization: Bearer <token>` header.
     #[serde(default)]
     pub jwt_secret: Option<String>,
+
+    /// Socket addresses the API binds, e.g. `["127.0.0.1:8080", "[::1]:8080"]`
+    /// (default: one listener on `bind_address` and the CLI port)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub addresses: Option<Vec<String>>,
+
+    /// IP the API listener binds when `addresses` is not set: a specific
+    /// interface address, or `::1`/`::` for IPv6 (default: `127.0.0.1`,
+    /// so exposure beyond the host is opt-in). `--api-bind` overrides it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bind_address: Option<String>,
+
+    /// TLS on the API listener, in the same shape as the data-plane `tls`
+    /// block (default: plaintext). The control plane carries bearer tokens
+    /// and rule contents, so production deployments should enable this.
+    /// The cert and key are re-read on config reload, so short-lived
+    /// certificates rotate through the normal reload path
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+
+    /// Serve interactive API docs (Swagger UI) at `/docs` (default: off;
+    /// the machine-readable spec at `/openapi.json` is always served)
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub docs: bool,
+
+    /// Per-caller request rate limit on the management API (default: no
+    /// limit). `/health` is always exempt so orchestrator probes are
+    /// unaffected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<ApiRateLimitConfig>,
+
+    /// Static bearer-token authentication. When set, every route except
+    /// `/health` requires `Authorization: Bearer <token>` matching one of
+    /// the configured token hashes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<ApiAuthConfig>,
+}
+
+/// `api.rate_limit`: a token bucket per caller — keyed by token name once
+/// a request is authenticated, by client IP otherwise
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ApiRateLimitConfig {
+    /// Sustained requests per second allowed per caller
+    pub requests_per_second: f64,
+    /// How many requests a caller may burst above the sustained rate
+    /// (the bucket size; default: the per-second rate, at least 1)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub burst: Option<u32>,
+}
+
+impl ApiRateLimitConfig {
+    /// The bucket size the limiter fills toward
+    pub fn burst_size(&self) -> f64 {
+        self.burst
+            .map(f64::from)
+            .unwrap_or_else(|| self.requests_per_second.ceil())
+            .max(1.0)
+    }
+}
+
+/// Static bearer tokens accepted by the management API
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ApiAuthConfig {
+    /// The accepted tokens, each stored as a hash so the config file
+    /// never contains a usable credential
+    pub tokens: Vec<ApiTokenConfig>,
+}
+
+/// One accepted API token
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ApiTokenConfig {
+    /// Name identifying the token in audit events (never the value)
+    pub name: String,
+    /// Lowercase hex SHA-256 of the token value, e.g. from
+    /// `echo -n "$TOKEN" | sha256sum`
+    pub token_sha256: String,
+    /// What the token may do (default: read)
+    #[serde(default, skip_serializing_if = "ApiRole::is_default")]
+    pub role: ApiRole,
+}
+
+/// What an API token is allowed to do: `read` tokens may only issue GET
+/// requests, mutation requires `admin`
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiRole {
+    #[default]
+    Read,
+    Admin,
+}
+
+impl ApiRole {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
 }
 
 /// Audit event types to log
@@ -132,6 +1105,9 @@ pub enum AuditEventType {
     DatabaseScan,
     SchemaQuery,
     ApiAccess,
+    ResultTruncated,
+    ConnectionPolicy,
+    PinnedParameterChange,
 }
 
 /// Configuration for audit logging
@@ -164,6 +1140,15 @@ pub struct AuditConfig {
     /// Events to log (if empty, logs all events)
     #[serde(default)]
     pub events: Vec<AuditEventType>,
+
+    /// Most entries one `GET /audit` query may return, bounding how much
+    /// of a large file a single request pulls into memory (default: 1000)
+    #[serde(default = "default_audit_query_max_results")]
+    pub query_max_results: usize,
+}
+
+fn default_audit_query_max_results() -> usize {
+    1000
 }
 
 fn default_audit_enabled() -> bool {
@@ -192,10 +1177,37 @@ impl Default for AuditConfig {
             max_file_size_bytes: default_audit_max_size(),
             max_rotated_files: default_audit_max_files(),
             events: vec![],
+            query_max_results: default_audit_query_max_results(),
         }
     }
 }
 
+/// Listener sockets for the proxy itself.
+///
+/// Dual-stack hosts need one socket per address family: a lone `0.0.0.0`
+/// listener misses IPv6 clients on platforms without v4-mapped addresses,
+/// so list both (e.g. `["0.0.0.0:6543", "[::]:6543"]`). Every listener
+/// feeds the same connection handler.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ListenConfig {
+    /// Socket addresses to bind, e.g. `0.0.0.0:6543` or `[::]:6543`
+    pub addresses: Vec<String>,
+
+    /// Whether startup fails when any address fails to bind. When off, the
+    /// proxy continues with the subset that bound (default: true)
+    #[serde(default = "default_require_all")]
+    pub require_all: bool,
+
+    /// Bind with SO_REUSEPORT so a replacement process can bind the same
+    /// addresses during a zero-downtime upgrade (default: false)
+    #[serde(default)]
+    pub reuse_port: bool,
+}
+
+fn default_require_all() -> bool {
+    true
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct TlsConfig {
     pub enabled: bool,
@@ -203,84 +1215,3486 @@ pub struct TlsConfig {
     pub key_path: String,
 }
 
+/// Verification options for the proxy-to-upstream TLS hop
 #[derive(Debug, Deserialize, Serialize, Clone)]
-pub struct TelemetryConfig {
+pub struct UpstreamTlsConfig {
+    /// PEM bundle of CA certificates to trust instead of the platform
+    /// trust store, e.g. the RDS CA bundle
     #[serde(default)]
-    pub enabled: bool,
-    #[serde(default = "default_otlp_endpoint")]
-    pub otlp_endpoint: String,
-    #[serde(default = "default_service_name")]
+    pub ca_cert_path: Option<String>,
+
+    /// Whether the certificate must be valid for the upstream hostname
+    /// (default: true). Turning this off still verifies the chain, so a
+    /// private CA's certificate issued for a different name is accepted
+    /// without trusting arbitrary certificates.
+    #[serde(default = "default_verify_hostname")]
+    pub verify_hostname: bool,
+
+    /// Skip certificate verification entirely. Development only: the hop
+    /// is encrypted but the upstream is not authenticated.
+    #[serde(default)]
+    pub insecure_skip_verify: bool,
+}
+
+impl Default for UpstreamTlsConfig {
+    fn default() -> Self {
+        Self {
+            ca_cert_path: None,
+            verify_hostname: default_verify_hostname(),
+            insecure_skip_verify: false,
+        }
+    }
+}
+
+fn default_verify_hostname() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TelemetryConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_otlp_endpoint")]
+    pub otlp_endpoint: String,
+    #[serde(default = "default_service_name")]
     pub service_name: String,
 }
 
-fn default_otlp_endpoint() -> String {
-    "http://localhost:4317".to_string()
-}
+/// Which metrics exporter to install behind the `metrics` facade
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MetricsBackendKind {
+    /// Pull-based: metrics are rendered on the `/metrics` endpoint
+    #[default]
+    Prometheus,
+    /// Push-based: metrics are sent as dogstatsd datagrams over UDP
+    Statsd,
+    /// No recorder installed; all emission sites become no-ops
+    None,
+}
+
+/// Metrics exporter configuration
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct MetricsConfig {
+    /// Exporter to use (default: prometheus)
+    #[serde(default)]
+    pub backend: MetricsBackendKind,
+
+    /// statsd/dogstatsd settings, used when `backend: statsd`
+    #[serde(default)]
+    pub statsd: Option<StatsdConfig>,
+}
+
+/// Destination and formatting for the statsd backend
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct StatsdConfig {
+    /// Agent host to send datagrams to (default: 127.0.0.1)
+    #[serde(default = "default_statsd_host")]
+    pub host: String,
+
+    /// Agent port (default: 8125)
+    #[serde(default = "default_statsd_port")]
+    pub port: u16,
+
+    /// Prefix prepended to every metric name (default: ironveil)
+    #[serde(default = "default_statsd_prefix")]
+    pub prefix: String,
+
+    /// Constant tags attached to every datagram, as `key:value` strings
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl Default for StatsdConfig {
+    fn default() -> Self {
+        Self {
+            host: default_statsd_host(),
+            port: default_statsd_port(),
+            prefix: default_statsd_prefix(),
+            tags: vec![],
+        }
+    }
+}
+
+fn default_statsd_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_statsd_port() -> u16 {
+    8125
+}
+
+fn default_statsd_prefix() -> String {
+    "ironveil".to_string()
+}
+
+fn default_otlp_endpoint() -> String {
+    "http://localhost:4317".to_string()
+}
+
+fn default_service_name() -> String {
+    "iron-veil".to_string()
+}
+
+fn default_masking_enabled() -> bool {
+    true
+}
+
+fn default_memo_entries() -> usize {
+    4096
+}
+
+fn default_log_buffer_entries() -> usize {
+    100
+}
+
+/// A masking strategy, parsed from its lowercase name.
+///
+/// Unknown names deserialize as [`Strategy::Custom`] so existing YAML keeps
+/// loading; [`Strategy::validate`] rejects custom strategies that are not
+/// registered, with a did-you-mean suggestion.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum Strategy {
+    Email,
+    Phone,
+    Address,
+    FirstName,
+    LastName,
+    /// A synthetic "First Last" pair, both parts deterministic in the value
+    FullName,
+    CreditCard,
+    Ssn,
+    Ip,
+    Dob,
+    Passport,
+    /// A synthetic, checksum-valid IBAN, deterministic in the value
+    Iban,
+    Hash,
+    Json,
+    /// Deterministic noise applied to a numeric value, keeping it numeric
+    NumericNoise,
+    /// Deterministic shift of a date or timestamp, keeping it parseable
+    DateShift,
+    /// Deterministic per-character replacement: digits map to digits and
+    /// letters to letters of the same case, everything else stays in place,
+    /// so the output keeps the input's shape (an SSN stays `NNN-NN-NNNN`)
+    FormatPreserving,
+    /// Regex find-and-replace over the whole value: the rule's `pattern`
+    /// and `replacement` configure it, applied with replace-all and
+    /// supporting capture-group references like `$1`
+    RegexReplace,
+    /// The `mask` export of the rule's `wasm_module`, invoked per cell
+    /// under a fuel limit. Requires the `wasm-plugins` feature; the
+    /// `wasm` module documents the guest ABI
+    Wasm,
+    /// Withhold the value entirely: the cell is rewritten to a wire-level
+    /// SQL NULL rather than a masked replacement. Quote the name in YAML
+    /// (`strategy: "null"`) — bare `null` is the YAML null value
+    Null,
+    /// Replace the value with a fixed placeholder; `redact_text` and
+    /// `preserve_length` on the rule configure it (default `[REDACTED]`)
+    Redact,
+    /// A strategy provided by a registered plugin
+    Custom(String),
+}
+
+impl Strategy {
+    /// Names of all built-in strategies, as they appear in config files
+    pub const BUILTIN: &'static [&'static str] = &[
+        "email",
+        "phone",
+        "address",
+        "first_name",
+        "last_name",
+        "full_name",
+        "credit_card",
+        "ssn",
+        "ip",
+        "dob",
+        "passport",
+        "iban",
+        "hash",
+        "json",
+        "numeric_noise",
+        "date_shift",
+        "format_preserving",
+        "regex_replace",
+        "wasm",
+        "null",
+        "redact",
+    ];
+
+    /// The lowercase name used in config files, stats, and audit labels
+    pub fn as_str(&self) -> &str {
+        match self {
+            Strategy::Email => "email",
+            Strategy::Phone => "phone",
+            Strategy::Address => "address",
+            Strategy::FirstName => "first_name",
+            Strategy::LastName => "last_name",
+            Strategy::FullName => "full_name",
+            Strategy::CreditCard => "credit_card",
+            Strategy::Ssn => "ssn",
+            Strategy::Ip => "ip",
+            Strategy::Dob => "dob",
+            Strategy::Passport => "passport",
+            Strategy::Iban => "iban",
+            Strategy::Hash => "hash",
+            Strategy::Json => "json",
+            Strategy::NumericNoise => "numeric_noise",
+            Strategy::DateShift => "date_shift",
+            Strategy::FormatPreserving => "format_preserving",
+            Strategy::RegexReplace => "regex_replace",
+            Strategy::Wasm => "wasm",
+            Strategy::Null => "null",
+            Strategy::Redact => "redact",
+            Strategy::Custom(name) => name,
+        }
+    }
+
+    /// Checks that this strategy is either built-in or a registered custom
+    /// strategy. On failure the error lists the valid strategies and, when a
+    /// built-in name is close enough, a did-you-mean suggestion.
+    pub fn validate(&self, registered: &[String]) -> Result<()> {
+        match self {
+            Strategy::Custom(name) if !registered.iter().any(|r| r == name) => {
+                let mut msg = format!(
+                    "unknown masking strategy '{}' (valid strategies: {}",
+                    name,
+                    Self::BUILTIN.join(", ")
+                );
+                if registered.is_empty() {
+                    msg.push(')');
+                } else {
+                    msg.push_str(&format!("; registered custom: {})", registered.join(", ")));
+                }
+                let candidates = Self::BUILTIN
+                    .iter()
+                    .copied()
+                    .chain(registered.iter().map(|s| s.as_str()));
+                if let Some(suggestion) = candidates
+                    .min_by_key(|candidate| levenshtein(name, candidate))
+                    .filter(|candidate| levenshtein(name, candidate) <= 2)
+                {
+                    msg.push_str(&format!(". Did you mean '{}'?", suggestion));
+                }
+                Err(anyhow::anyhow!(msg))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<String> for Strategy {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "email" => Strategy::Email,
+            "phone" => Strategy::Phone,
+            "address" => Strategy::Address,
+            "first_name" => Strategy::FirstName,
+            "last_name" => Strategy::LastName,
+            "full_name" => Strategy::FullName,
+            "credit_card" => Strategy::CreditCard,
+            "ssn" => Strategy::Ssn,
+            "ip" => Strategy::Ip,
+            "dob" => Strategy::Dob,
+            "passport" => Strategy::Passport,
+            "iban" => Strategy::Iban,
+            "hash" => Strategy::Hash,
+            "json" => Strategy::Json,
+            "numeric_noise" => Strategy::NumericNoise,
+            "date_shift" => Strategy::DateShift,
+            "format_preserving" => Strategy::FormatPreserving,
+            "regex_replace" => Strategy::RegexReplace,
+            "wasm" => Strategy::Wasm,
+            "null" => Strategy::Null,
+            "redact" => Strategy::Redact,
+            _ => Strategy::Custom(s),
+        }
+    }
+}
+
+impl From<Strategy> for String {
+    fn from(s: Strategy) -> Self {
+        s.as_str().to_string()
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Strategy::from(s.to_string()))
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One or more strategies applied in order, each stage's output feeding the
+/// next — `strategy: [phone, hash]` replaces a value with a synthetic phone
+/// number and then hashes that.
+///
+/// Deserializes from a bare strategy name (the common case) or a list of
+/// names; single-stage chains serialize back to the bare form so existing
+/// YAML round-trips unchanged.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "StrategyChainRepr", into = "StrategyChainRepr")]
+pub struct StrategyChain(Vec<Strategy>);
+
+/// Wire shape for [`StrategyChain`]: a bare name or a list of names
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum StrategyChainRepr {
+    Single(Strategy),
+    Chain(Vec<Strategy>),
+}
+
+impl From<StrategyChainRepr> for StrategyChain {
+    fn from(repr: StrategyChainRepr) -> Self {
+        match repr {
+            StrategyChainRepr::Single(strategy) => StrategyChain(vec![strategy]),
+            StrategyChainRepr::Chain(stages) => StrategyChain(stages),
+        }
+    }
+}
+
+impl From<StrategyChain> for StrategyChainRepr {
+    fn from(chain: StrategyChain) -> Self {
+        let mut stages = chain.0;
+        if stages.len() == 1 {
+            StrategyChainRepr::Single(stages.remove(0))
+        } else {
+            StrategyChainRepr::Chain(stages)
+        }
+    }
+}
+
+impl From<Strategy> for StrategyChain {
+    fn from(strategy: Strategy) -> Self {
+        StrategyChain(vec![strategy])
+    }
+}
+
+impl StrategyChain {
+    /// The stages in application order; [`validate`](Self::validate)
+    /// guarantees at least one
+    pub fn stages(&self) -> &[Strategy] {
+        &self.0
+    }
+
+    /// The sole strategy of a single-stage chain, `None` for longer ones
+    pub fn as_single(&self) -> Option<&Strategy> {
+        match self.0.as_slice() {
+            [only] => Some(only),
+            _ => None,
+        }
+    }
+
+    /// The stage whose output reaches the wire, and therefore the one that
+    /// must fit the column's type. Only an unvalidated empty chain has none.
+    pub fn terminal(&self) -> Option<&Strategy> {
+        self.0.last()
+    }
+
+    /// The label used for stats and audit attribution: stage names joined
+    /// with `+`, so a chained cell counts once against the whole chain
+    /// rather than once per stage
+    pub fn label(&self) -> String {
+        self.0
+            .iter()
+            .map(Strategy::as_str)
+            .collect::<Vec<_>>()
+            .join("+")
+    }
+
+    /// Validates every stage and the chain's shape: at least one stage,
+    /// `json` and `null` only on their own (`json` rewrites structure in
+    /// place rather than mapping a value; `null` leaves nothing for a later
+    /// stage to consume), and each stage able to consume what the previous
+    /// one emits — `numeric_noise` needs numeric input and `date_shift` a
+    /// date, which only their own kind of stage produces.
+    pub fn validate(&self, registered: &[String]) -> Result<()> {
+        if self.0.is_empty() {
+            anyhow::bail!("strategy chain must contain at least one stage");
+        }
+        for stage in &self.0 {
+            stage.validate(registered)?;
+        }
+        if self.0.len() > 1 && self.0.contains(&Strategy::Json) {
+            anyhow::bail!("'json' cannot be combined with other strategies in a chain");
+        }
+        if self.0.len() > 1 && self.0.contains(&Strategy::Null) {
+            anyhow::bail!("'null' cannot be combined with other strategies in a chain");
+        }
+        if self.0.len() > 1 && self.0.contains(&Strategy::Redact) {
+            anyhow::bail!("'redact' cannot be combined with other strategies in a chain");
+        }
+        for pair in self.0.windows(2) {
+            let compatible = match &pair[1] {
+                Strategy::NumericNoise => pair[0] == Strategy::NumericNoise,
+                Strategy::DateShift => matches!(pair[0], Strategy::DateShift | Strategy::Dob),
+                _ => true,
+            };
+            if !compatible {
+                anyhow::bail!(
+                    "strategy chain stage '{}' cannot consume the output of '{}'",
+                    pair[1].as_str(),
+                    pair[0].as_str()
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for StrategyChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.label())
+    }
+}
+
+/// Edit distance used for did-you-mean suggestions
+fn levenshtein(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+
+    for (i, ca) in a.iter().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+/// Gates a [`MaskingRule`] on the row being masked. A condition tests one
+/// thing: either the cell's own value (`self_matches` and/or `or_detected`,
+/// satisfied when any of them holds) or a sibling column's value in the same
+/// row (`column` plus `equals`). Cells where the condition does not hold are
+/// left unmasked, and heuristics do not reclaim them — the condition deciding
+/// "don't mask" is as deliberate as the rule itself.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RuleCondition {
+    /// Regex the cell's own value must match (default: not tested)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub self_matches: Option<String>,
+
+    /// PII detectors, any of which firing on the cell's own value satisfies
+    /// the condition (default: none)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub or_detected: Vec<String>,
+
+    /// Sibling column in the same result set whose value is tested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<String>,
+
+    /// Value the sibling column must equal, compared as text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub equals: Option<String>,
+
+    /// What happens when the referenced sibling column is not part of the
+    /// result set (default: fail closed and mask)
+    #[serde(default)]
+    pub on_missing_column: MissingColumnPolicy,
+}
+
+/// Resolution for a sibling condition whose column the query did not select.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingColumnPolicy {
+    /// Fail closed: mask the cell as if the condition held
+    #[default]
+    Mask,
+    /// Fail open: leave the cell unmasked
+    Skip,
+}
+
+impl RuleCondition {
+    /// Checks that the condition tests exactly one thing and that its parts
+    /// are well-formed: a compilable regex, known detector names, and
+    /// `column`/`equals` only as a pair.
+    pub fn validate(&self) -> Result<()> {
+        let tests_own_value = self.self_matches.is_some() || !self.or_detected.is_empty();
+        let tests_sibling = self.column.is_some() || self.equals.is_some();
+        if tests_own_value && tests_sibling {
+            anyhow::bail!(
+                "a condition tests either the cell's own value or a sibling column, not both"
+            );
+        }
+        if !tests_own_value && !tests_sibling {
+            anyhow::bail!(
+                "condition must test something: self_matches, or_detected, or column/equals"
+            );
+        }
+        if self.column.is_some() != self.equals.is_some() {
+            anyhow::bail!("'column' and 'equals' must be given together");
+        }
+        if let Some(pattern) = &self.self_matches {
+            regex::Regex::new(pattern)
+                .map_err(|e| anyhow::anyhow!("invalid self_matches regex: {}", e))?;
+        }
+        for name in &self.or_detected {
+            if crate::scanner::PiiType::parse(name).is_none() {
+                anyhow::bail!(
+                    "unknown detector '{}' (valid detectors: {})",
+                    name,
+                    crate::scanner::PiiType::NAMES.join(", ")
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[cfg_attr(feature = "api", derive(utoipa::ToSchema))]
+pub struct MaskingRule {
+    /// Stable identifier for this rule, generated at load when absent.
+    ///
+    /// The id survives edits and renames and is preserved across
+    /// export/import, so policies, audit diffs, and API calls can reference
+    /// the rule without relying on its position or contents. Deleting a rule
+    /// and re-adding it produces a new id.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub table: Option<String>,
+    /// Column name, or a glob pattern over column names: `*` matches any
+    /// run of characters and `?` exactly one, so `*_email` covers
+    /// `billing_email` and `contact_email`. Exact-name rules take
+    /// precedence over pattern rules when both match a column. May be
+    /// omitted only when `column_regex` is set instead.
+    #[serde(default)]
+    pub column: String,
+    /// Match `column` case-sensitively (default: matching ignores case).
+    /// Does not apply to regexes, which use their own `(?i)` flag
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub case_sensitive: bool,
+    /// Regex the resolved table name must match, as an alternative to
+    /// `table`; mutually exclusive with it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub table_regex: Option<String>,
+    /// Regex the column name must match, as an alternative to `column`;
+    /// mutually exclusive with it. Like glob patterns, regex rules yield
+    /// to exact-name rules when both cover a column
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column_regex: Option<String>,
+    /// Database this rule is scoped to: an exact name or a glob over
+    /// names (`*`/`?`), matched case-insensitively against the database
+    /// the client named at startup. Unset, the rule applies in every
+    /// database; like `table`, a scoped rule still applies when the
+    /// connection's database is unknown, failing closed toward masking
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub database: Option<String>,
+    /// Compiled forms of `table_regex`/`column_regex`, primed once by
+    /// [`AppConfig::validate`] at config load
+    #[serde(skip)]
+    pub compiled: RuleRegexes,
+    /// The strategy to apply, or a list of strategies applied in order with
+    /// each stage's output feeding the next
+    #[cfg_attr(feature = "api", schema(value_type = Value))]
+    pub strategy: StrategyChain,
+    /// Replacement text for the `redact` strategy (default `[REDACTED]`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redact_text: Option<String>,
+    /// Cycle or cut the `redact` placeholder to the original value's
+    /// character count, for UIs that rely on column widths
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub preserve_length: bool,
+    /// Half-width in days of the `date_shift` strategy's offset range: a
+    /// derived shift lands in `[-N, +N]` (default 30)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date_shift_days: Option<u32>,
+    /// Sibling column whose value seeds this rule's `date_shift` offset
+    /// instead of the date itself, so every date of one subject moves by
+    /// the same amount and intervals between events survive masking (e.g.
+    /// `seed_column: customer_id`). Falls back to the cell's own value when
+    /// the sibling is absent from the result set or NULL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed_column: Option<String>,
+    /// Half-width in percent of the `numeric_noise` strategy's
+    /// multiplicative range: a masked value lands within ±N% of the
+    /// original (default 10)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub noise_percent: Option<f64>,
+    /// How the `ip` strategy anonymizes addresses: zero the host part
+    /// (`truncate`, the default) or deterministically remap into a
+    /// reserved range (`pseudonymize`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip_mode: Option<IpMode>,
+    /// Fake only the local part of the `email` strategy's output and keep
+    /// everything after the `@`, for environments that route mail or gate
+    /// features by domain. Values without an `@` get a fully fake address
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub preserve_domain: bool,
+    /// Domains whose addresses the `email` strategy leaves entirely
+    /// unmasked (e.g. `ourcompany.com`, or `*@ourcompany.com` — the
+    /// prefix is tolerated); matched case-insensitively against the part
+    /// after the `@`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub domain_allowlist: Vec<String>,
+    /// JSON pointer paths the `json` strategy masks, each with its own
+    /// strategy or chain (e.g. `/email: email`, `/address/city: address`).
+    /// A `*` segment fans out over every array element or object member
+    /// (`/contacts/*/email`). When set, only the listed paths are masked
+    /// instead of heuristically scanning every string, and values that do
+    /// not parse as JSON pass through untouched with a warning
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "api", schema(value_type = Option<Object>))]
+    pub json_paths: Option<std::collections::BTreeMap<String, StrategyChain>>,
+    /// Regex the `regex_replace` strategy applies to the whole value with
+    /// replace-all, compiled at config load
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    /// Replacement text for `regex_replace`; capture-group references like
+    /// `$1` refer to groups of `pattern` (default: matches are removed)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replacement: Option<String>,
+    /// Path of the `.wasm` (or `.wat`) module implementing the `wasm`
+    /// strategy, compiled once at config load; the `wasm` module's docs
+    /// describe the guest ABI
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wasm_module: Option<String>,
+    /// Fuel budget per `mask` invocation of the `wasm` strategy, bounding
+    /// how long one cell may compute (default 1,000,000)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wasm_fuel: Option<u64>,
+    /// Derive fakes from the cell value (default), so one input maps to
+    /// one fake and masked datasets stay joinable. Set false to draw a
+    /// fresh random fake per occurrence instead, making frequency analysis
+    /// of a one-off export impossible at the cost of determinism
+    #[serde(default = "default_deterministic", skip_serializing_if = "is_true")]
+    pub deterministic: bool,
+    /// Per-field strategies for a composite (row-typed) column, by field
+    /// position. When set, values are parsed as composite row literals and
+    /// each field is masked by its entry; `null` entries leave that field to
+    /// the content heuristics. Values that do not parse as a composite — or
+    /// that nest another composite — resolve per `on_type_mismatch`
+    /// (default: none, the whole value is masked as a scalar)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "api", schema(value_type = Option<Vec<Value>>))]
+    pub composite_fields: Option<Vec<Option<StrategyChain>>>,
+    /// What to do when this rule binds to a column whose wire type its
+    /// strategy cannot produce, e.g. an email strategy on an int8 column
+    #[serde(default, skip_serializing_if = "TypeMismatchPolicy::is_default")]
+    pub on_type_mismatch: TypeMismatchPolicy,
+    /// Row-level condition gating this rule (default: the rule always
+    /// applies)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "api", schema(value_type = Option<Object>))]
+    pub when: Option<RuleCondition>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+fn default_deterministic() -> bool {
+    true
+}
+
+/// A rule's regexes in compiled form. Validation compiles and caches them
+/// at config load, so match time never parses a pattern and an invalid one
+/// cannot reach a running config.
+#[derive(Debug, Clone, Default)]
+pub struct RuleRegexes {
+    column: std::sync::OnceLock<Option<regex::Regex>>,
+    table: std::sync::OnceLock<Option<regex::Regex>>,
+    value: std::sync::OnceLock<Option<regex::Regex>>,
+}
+
+impl MaskingRule {
+    /// A minimal rule masking `table`.`column` with `strategy`, every
+    /// optional knob at its default. Scan-driven rule suggestions build
+    /// on this.
+    pub fn basic(table: Option<String>, column: String, strategy: StrategyChain) -> Self {
+        Self {
+            id: None,
+            table,
+            column,
+            case_sensitive: false,
+            table_regex: None,
+            column_regex: None,
+            database: None,
+            compiled: RuleRegexes::default(),
+            strategy,
+            redact_text: None,
+            preserve_length: false,
+            date_shift_days: None,
+            seed_column: None,
+            noise_percent: None,
+            ip_mode: None,
+            preserve_domain: false,
+            domain_allowlist: Vec::new(),
+            json_paths: None,
+            pattern: None,
+            replacement: None,
+            wasm_module: None,
+            wasm_fuel: None,
+            deterministic: true,
+            composite_fields: None,
+            on_type_mismatch: TypeMismatchPolicy::default(),
+            when: None,
+        }
+    }
+
+    fn column_re(&self) -> Option<&regex::Regex> {
+        self.compiled
+            .column
+            .get_or_init(|| {
+                self.column_regex
+                    .as_deref()
+                    .and_then(|p| regex::Regex::new(p).ok())
+            })
+            .as_ref()
+    }
+
+    fn table_re(&self) -> Option<&regex::Regex> {
+        self.compiled
+            .table
+            .get_or_init(|| {
+                self.table_regex
+                    .as_deref()
+                    .and_then(|p| regex::Regex::new(p).ok())
+            })
+            .as_ref()
+    }
+
+    /// The compiled `pattern` of a `regex_replace` rule, primed by
+    /// validation like the selector regexes
+    pub(crate) fn value_re(&self) -> Option<&regex::Regex> {
+        self.compiled
+            .value
+            .get_or_init(|| {
+                self.pattern
+                    .as_deref()
+                    .and_then(|p| regex::Regex::new(p).ok())
+            })
+            .as_ref()
+    }
+
+    /// Whether this rule's column selector (name, glob pattern, or regex)
+    /// covers `column`
+    pub fn column_matches(&self, column: &str) -> bool {
+        if self.column_regex.is_some() {
+            return self.column_re().is_some_and(|re| re.is_match(column));
+        }
+        if self.case_sensitive {
+            glob_match(&self.column, column)
+        } else {
+            glob_match(&self.column.to_lowercase(), &column.to_lowercase())
+        }
+    }
+
+    /// Whether this rule's table scope (name or regex) covers the resolved
+    /// table. A rule scoped to a table still applies when the table could
+    /// not be resolved, failing closed toward masking.
+    pub fn table_matches(&self, table: Option<&str>) -> bool {
+        if self.table_regex.is_some() {
+            return self
+                .table_re()
+                .is_some_and(|re| table.is_none_or(|resolved| re.is_match(resolved)));
+        }
+        self.table
+            .as_ref()
+            .is_none_or(|t| table.is_none_or(|resolved| t.as_str() == resolved))
+    }
+
+    /// Whether this rule's database scope (name or glob) covers the
+    /// database the connection announced. A scoped rule still applies when
+    /// the database is unknown, failing closed toward masking.
+    pub fn database_matches(&self, database: Option<&str>) -> bool {
+        self.database.as_ref().is_none_or(|pattern| {
+            database.is_none_or(|db| glob_match(&pattern.to_lowercase(), &db.to_lowercase()))
+        })
+    }
+
+    /// Whether the rule's column selector is a glob pattern or regex rather
+    /// than an exact name
+    pub fn column_is_pattern(&self) -> bool {
+        self.column_regex.is_some() || self.column.contains(['*', '?'])
+    }
+
+    /// The rule's column selector for error messages: the name or glob, or
+    /// the regex when that is how the rule selects columns
+    pub fn column_label(&self) -> &str {
+        self.column_regex.as_deref().unwrap_or(&self.column)
+    }
+
+    /// Checks the regex selectors: each is exclusive with its plain
+    /// counterpart, and both must compile. Compiling here also primes the
+    /// cached forms, so a validated rule never parses a pattern at match
+    /// time.
+    pub fn validate_regexes(&self) -> Result<()> {
+        if self.column.is_empty() && self.column_regex.is_none() {
+            anyhow::bail!("rule must set either column or column_regex");
+        }
+        if !self.column.is_empty() && self.column_regex.is_some() {
+            anyhow::bail!("column and column_regex are mutually exclusive");
+        }
+        if self.table.is_some() && self.table_regex.is_some() {
+            anyhow::bail!("table and table_regex are mutually exclusive");
+        }
+        if let Some(pattern) = &self.column_regex {
+            let re = regex::Regex::new(pattern)
+                .map_err(|e| anyhow::anyhow!("invalid column_regex: {}", e))?;
+            let _ = self.compiled.column.set(Some(re));
+        }
+        if let Some(pattern) = &self.table_regex {
+            let re = regex::Regex::new(pattern)
+                .map_err(|e| anyhow::anyhow!("invalid table_regex: {}", e))?;
+            let _ = self.compiled.table.set(Some(re));
+        }
+        Ok(())
+    }
+
+    /// Reject glob syntax the matcher does not support: a character class
+    /// would be taken literally and silently never match anything
+    pub fn validate_column_pattern(&self) -> Result<()> {
+        if self.column_regex.is_some() {
+            return Ok(());
+        }
+        if self.column.is_empty() {
+            anyhow::bail!("column must not be empty");
+        }
+        if self.column.contains(['[', ']']) {
+            anyhow::bail!(
+                "column pattern '{}' uses unsupported glob syntax; only '*' and '?'                  wildcards are supported",
+                self.column
+            );
+        }
+        if let Some(database) = &self.database {
+            if database.is_empty() {
+                anyhow::bail!("database must not be empty when set");
+            }
+            if database.contains(['[', ']']) {
+                anyhow::bail!(
+                    "database pattern '{}' uses unsupported glob syntax; only '*' and '?'                  wildcards are supported",
+                    database
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Iterative glob match over `*` (any run, including empty) and `?`
+/// (exactly one character); everything else is literal
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            // Backtrack: let the last `*` swallow one more character
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Resolution for a rule whose strategy does not fit the column's type.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "api", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum TypeMismatchPolicy {
+    /// Replace values with a type-valid placeholder (zero for numbers, an
+    /// epoch-shifted date for dates) instead of the strategy's output
+    #[default]
+    Fallback,
+    /// Leave the column unmasked and log a warning at bind time
+    Skip,
+    /// Apply the strategy anyway, even if clients may fail to parse the
+    /// result
+    Apply,
+}
+
+impl TypeMismatchPolicy {
+    fn is_default(&self) -> bool {
+        *self == TypeMismatchPolicy::Fallback
+    }
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            masking_enabled: true,
+            verify_output: None,
+            rules: vec![],
+            listen: None,
+            upstream: None,
+            tls: None,
+            upstream_tls: false,
+            upstream_tls_options: None,
+            routing: None,
+            telemetry: None,
+            metrics: None,
+            api: None,
+            limits: None,
+            policies_by_source: vec![],
+            application_name_policy: ApplicationNamePolicy::Pin,
+            expression_handling: ExpressionHandling::Heuristic,
+            scan_typed_columns: false,
+            memo_entries: default_memo_entries(),
+            log_buffer_entries: default_log_buffer_entries(),
+            watch: false,
+            health_check: None,
+            audit: None,
+            alerts: None,
+            admin: None,
+            oid_resolution: None,
+            schema_cache: None,
+            masking: None,
+            scanner: None,
+            hashing: None,
+            custom_strategies: std::collections::BTreeMap::new(),
+        }
+    }
+}
+
+/// Path of the last-known-good snapshot kept beside a config file, written
+/// after each successful staged apply
+pub fn last_known_good_path(config_path: &str) -> String {
+    format!("{}.last-good", config_path)
+}
+
+/// Severity of a [`ValidationFinding`]: `Error` findings fail
+/// `validate-config`, `Warning` findings are advisory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FindingSeverity {
+    Error,
+    Warning,
+}
+
+impl std::fmt::Display for FindingSeverity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FindingSeverity::Error => write!(f, "error"),
+            FindingSeverity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// One finding from [`AppConfig::check`], naming the config component it
+/// concerns so CI annotations can point at the right section
+#[derive(Debug, Serialize)]
+pub struct ValidationFinding {
+    pub severity: FindingSeverity,
+    pub component: String,
+    pub message: String,
+}
+
+/// Replaces `${VAR}` and `${VAR:-default}` references inside every string
+/// value of a parsed config. Substitution runs after YAML parsing, so an
+/// environment value can never inject structure — it only ever lands inside
+/// the string that referenced it. Returns the name of the first referenced
+/// variable that is unset and has no default.
+fn substitute_env_vars(value: &mut serde_yaml::Value) -> std::result::Result<(), String> {
+    match value {
+        serde_yaml::Value::String(s) => *s = expand_env_refs(s)?,
+        serde_yaml::Value::Sequence(items) => {
+            for item in items {
+                substitute_env_vars(item)?;
+            }
+        }
+        serde_yaml::Value::Mapping(map) => {
+            for (_, item) in map.iter_mut() {
+                substitute_env_vars(item)?;
+            }
+        }
+        serde_yaml::Value::Tagged(tagged) => substitute_env_vars(&mut tagged.value)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Expands the `${...}` references in one string. Anything that does not
+/// look like a reference — no closing brace, or an empty name — is kept
+/// literally.
+fn expand_env_refs(input: &str) -> std::result::Result<String, String> {
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find('}') else {
+            out.push_str(&rest[start..]);
+            return Ok(out);
+        };
+        let reference = &after[..end];
+        let (name, default) = match reference.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (reference, None),
+        };
+        if name.is_empty() {
+            out.push_str(&rest[start..start + 2 + end + 1]);
+        } else {
+            match std::env::var(name) {
+                Ok(value) => out.push_str(&value),
+                Err(_) => match default {
+                    Some(default) => out.push_str(default),
+                    None => return Err(name.to_string()),
+                },
+            }
+        }
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+impl AppConfig {
+    pub fn load(path: &str) -> Result<Self> {
+        let mut config = Self::parse(path)?;
+        config.validate(&[])?;
+        config.ensure_rule_ids()?;
+        Ok(config)
+    }
+
+    /// Reads and parses the file, expanding environment references, but
+    /// defers validation; `validate-config` uses this so a single run can
+    /// report every finding instead of stopping at the first
+    pub fn parse(path: &str) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let mut value: serde_yaml::Value = serde_yaml::from_str(&content)?;
+        substitute_env_vars(&mut value).map_err(|var| {
+            anyhow::anyhow!(
+                "environment variable '{}' referenced by {} is not set and \
+                 has no default",
+                var,
+                path
+            )
+        })?;
+        Ok(serde_yaml::from_value(value)?)
+    }
+
+    /// Like [`load`](Self::load), but a config that fails to load falls back
+    /// to the last-known-good snapshot written after the previous successful
+    /// apply. Returns whether the fallback was used; when the snapshot is
+    /// missing or broken too, the original error is surfaced.
+    pub fn load_or_last_known_good(path: &str) -> Result<(Self, bool)> {
+        match Self::load(path) {
+            Ok(config) => Ok((config, false)),
+            Err(e) => match Self::load(&last_known_good_path(path)) {
+                Ok(config) => Ok((config, true)),
+                Err(_) => Err(e),
+            },
+        }
+    }
+
+    /// Runs every check a staged apply would run, without applying
+    /// anything: [`validate`](Self::validate) and the duplicate-id check,
+    /// plus the artifact builds (TLS acceptors) and sink probes (audit file
+    /// writability) that only a live apply exercises otherwise. Collects
+    /// findings instead of bailing at the first problem, so
+    /// `validate-config` can report them all in one run.
+    pub fn check(&self) -> Vec<ValidationFinding> {
+        fn error(component: &str, message: String) -> ValidationFinding {
+            ValidationFinding {
+                severity: FindingSeverity::Error,
+                component: component.to_string(),
+                message,
+            }
+        }
+
+        let mut findings = Vec::new();
+        if let Err(e) = self.validate(&[]) {
+            findings.push(error("config", e.to_string()));
+        }
+        if let Err(e) = self.clone().ensure_rule_ids() {
+            findings.push(error("rules", e.to_string()));
+        }
+
+        // Build the TLS acceptors the same way a staged apply would, so a
+        // missing or unparseable cert fails CI instead of the next reload
+        if let Some(tls) = self.tls.as_ref()
+            && tls.enabled
+            && let Err(e) = crate::proxy::build_tls_acceptor(tls)
+        {
+            findings.push(error("tls", e.to_string()));
+        }
+        #[cfg(feature = "api")]
+        if let Some(tls) = self.api.as_ref().and_then(|api| api.tls.as_ref())
+            && tls.enabled
+            && let Err(e) = crate::proxy::build_tls_acceptor(tls)
+        {
+            findings.push(error("api.tls", e.to_string()));
+        }
+
+        if let Some(audit) = self.audit.as_ref()
+            && audit.enabled
+        {
+            if let Some(log_file) = audit.log_file.as_ref() {
+                if let Err(e) = fs::OpenOptions::new()
+                    .append(true)
+                    .create(true)
+                    .open(log_file)
+                {
+                    findings.push(error(
+                        "audit",
+                        format!("audit log file {} is not writable: {}", log_file, e),
+                    ));
+                }
+            } else if !audit.log_to_stdout {
+                findings.push(ValidationFinding {
+                    severity: FindingSeverity::Warning,
+                    component: "audit".to_string(),
+                    message: "audit is enabled but has no stdout or file sink; \
+                              entries only reach the in-memory buffer"
+                        .to_string(),
+                });
+            }
+        }
+
+        findings
+    }
+
+    /// Assigns a fresh id to every rule that lacks one and rejects duplicate
+    /// ids. Called at load so every rule in a running config has a stable,
+    /// unique identifier; [`crate::state::AppState::save_config`] persists
+    /// the assignments.
+    pub fn ensure_rule_ids(&mut self) -> Result<()> {
+        let mut seen = std::collections::HashSet::new();
+        for rule in &mut self.rules {
+            let id = rule
+                .id
+                .get_or_insert_with(|| uuid::Uuid::new_v4().to_string());
+            if !seen.insert(id.clone()) {
+                anyhow::bail!("duplicate rule id '{}' (rule ids must be unique)", id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Looks up a rule by its stable id
+    pub fn rule_by_id(&self, id: &str) -> Option<&MaskingRule> {
+        self.rules
+            .iter()
+            .find(|rule| rule.id.as_deref() == Some(id))
+    }
+
+    /// Checks that every id in `refs` resolves to a current rule. Used by
+    /// anything that stores rule references (policies, preset overrides) to
+    /// fail fast instead of silently skipping a rule.
+    pub fn validate_rule_refs(&self, refs: &[String]) -> Result<()> {
+        for id in refs {
+            if self.rule_by_id(id).is_none() {
+                anyhow::bail!(
+                    "reference to unknown rule id '{}'; the rule may have been \
+                     deleted (re-adding a rule assigns a new id, so stale \
+                     references must be updated)",
+                    id
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Validates all masking rules against the built-in strategies plus the
+    /// given registered custom strategies, and rejects sections that need a
+    /// feature this binary was compiled without.
+    pub fn validate(&self, registered_strategies: &[String]) -> Result<()> {
+        // A config that references a compiled-out feature is an error, not a
+        // silent ignore: the operator asked for something this binary cannot
+        // do, so fail at startup instead of quietly running without it.
+        if self.api.is_some() && !cfg!(feature = "api") {
+            anyhow::bail!(
+                "config has an 'api' section, but this binary was compiled \
+                 without the 'api' feature"
+            );
+        }
+        if let Some(auth) = self.api.as_ref().and_then(|api| api.auth.as_ref()) {
+            if auth.tokens.is_empty() {
+                anyhow::bail!("api.auth.tokens must list at least one token");
+            }
+            let mut names = std::collections::HashSet::new();
+            for token in &auth.tokens {
+                if token.name.is_empty() {
+                    anyhow::bail!("api.auth tokens must have a non-empty name");
+                }
+                if !names.insert(token.name.as_str()) {
+                    anyhow::bail!("duplicate api.auth token name '{}'", token.name);
+                }
+                if token.token_sha256.len() != 64
+                    || !token.token_sha256.bytes().all(|b| b.is_ascii_hexdigit())
+                {
+                    anyhow::bail!(
+                        "api.auth token '{}': token_sha256 must be 64 hex characters \
+                         (the SHA-256 of the token value)",
+                        token.name
+                    );
+                }
+            }
+        }
+        if let Some(limit) = self.api.as_ref().and_then(|api| api.rate_limit.as_ref()) {
+            if !limit.requests_per_second.is_finite() || limit.requests_per_second <= 0.0 {
+                anyhow::bail!(
+                    "api.rate_limit.requests_per_second must be a positive number, got {}",
+                    limit.requests_per_second
+                );
+            }
+            if limit.burst == Some(0) {
+                anyhow::bail!("api.rate_limit.burst must be at least 1");
+            }
+        }
+        if let Some(listen) = &self.listen {
+            if listen.addresses.is_empty() {
+                anyhow::bail!("listen.addresses must list at least one address");
+            }
+            for addr in &listen.addresses {
+                addr.parse::<std::net::SocketAddr>().map_err(|e| {
+                    anyhow::anyhow!("invalid listen address '{}': {}", addr, e)
+                })?;
+            }
+        }
+        for addr in self.api.iter().flat_map(|api| api.addresses.iter().flatten()) {
+            addr.parse::<std::net::SocketAddr>()
+                .map_err(|e| anyhow::anyhow!("invalid api address '{}': {}", addr, e))?;
+        }
+        if let Some(bind) = self.api.as_ref().and_then(|api| api.bind_address.as_ref()) {
+            bind.parse::<std::net::IpAddr>()
+                .map_err(|e| anyhow::anyhow!("invalid api bind_address '{}': {}", bind, e))?;
+        }
+        if let Some(verify) = &self.verify_output {
+            verify.validate()?;
+        }
+        for alert in self.alerts.iter().flat_map(|a| a.rules.iter()) {
+            alert.validate()?;
+        }
+        if let Some(admin) = &self.admin {
+            admin.validate()?;
+        }
+        if let Some(options) = &self.upstream_tls_options {
+            if !self.upstream_tls {
+                anyhow::bail!(
+                    "config has upstream_tls_options, but upstream_tls is not enabled"
+                );
+            }
+            if options.insecure_skip_verify && options.ca_cert_path.is_some() {
+                anyhow::bail!(
+                    "upstream_tls_options.insecure_skip_verify skips verification                      entirely and conflicts with ca_cert_path"
+                );
+            }
+        }
+        if let Some(limits) = &self.limits {
+            if limits.max_client_message_bytes < 1024 || limits.max_upstream_message_bytes < 1024 {
+                anyhow::bail!("limits message size caps must be at least 1024 bytes");
+            }
+            if limits.frame_timeout_secs == 0 {
+                anyhow::bail!("limits.frame_timeout_secs must be greater than zero");
+            }
+        }
+        if self.telemetry.as_ref().is_some_and(|t| t.enabled) && !cfg!(feature = "otel") {
+            anyhow::bail!(
+                "config enables telemetry, but this binary was compiled \
+                 without the 'otel' feature"
+            );
+        }
+        if self
+            .metrics
+            .as_ref()
+            .is_some_and(|m| m.backend == MetricsBackendKind::Prometheus)
+            && !cfg!(feature = "metrics-prometheus")
+        {
+            anyhow::bail!(
+                "config selects the 'prometheus' metrics backend, but this \
+                 binary was compiled without the 'metrics-prometheus' feature"
+            );
+        }
+
+        for (name, def) in &self.custom_strategies {
+            if Strategy::BUILTIN.contains(&name.as_str()) {
+                anyhow::bail!(
+                    "custom strategy '{}' shadows a built-in strategy name",
+                    name
+                );
+            }
+            regex::Regex::new(&def.pattern).map_err(|e| {
+                anyhow::anyhow!("invalid pattern in custom strategy '{}': {}", name, e)
+            })?;
+        }
+        // Rules may reference the config's named strategies like the
+        // embedder's registered ones
+        let registered_strategies: Vec<String> = registered_strategies
+            .iter()
+            .cloned()
+            .chain(self.custom_strategies.keys().cloned())
+            .collect();
+        let registered_strategies = registered_strategies.as_slice();
+
+        if let Some(scanner) = &self.scanner
+            && !(0.0..=1.0).contains(&scanner.min_confidence)
+        {
+            anyhow::bail!("scanner.min_confidence must be between 0 and 1");
+        }
+        if let Some(scanner) = &self.scanner
+            && scanner.dob_column_hints.iter().any(|h| h.trim().is_empty())
+        {
+            // An empty fragment is contained in every column name, which
+            // would flag every date in the database as a birth date
+            anyhow::bail!("scanner.dob_column_hints entries must not be empty");
+        }
+        for pattern in self.scanner.iter().flat_map(|s| s.custom_patterns.iter()) {
+            if pattern.name.trim().is_empty() {
+                anyhow::bail!("scanner.custom_patterns entries must have a name");
+            }
+            if crate::scanner::PiiType::parse(&pattern.name).is_some() {
+                anyhow::bail!(
+                    "custom pattern '{}' shadows a built-in detector name",
+                    pattern.name
+                );
+            }
+            regex::Regex::new(&pattern.pattern).map_err(|e| {
+                anyhow::anyhow!("invalid regex in custom pattern '{}': {}", pattern.name, e)
+            })?;
+            if let Some(strategy) = &pattern.strategy {
+                strategy.validate(registered_strategies).map_err(|e| {
+                    anyhow::anyhow!("invalid strategy on custom pattern '{}': {}", pattern.name, e)
+                })?;
+            }
+        }
+        for (name, strategy) in self.masking.iter().flat_map(|m| m.defaults.iter()) {
+            if crate::scanner::PiiType::parse(name).is_none() {
+                anyhow::bail!(
+                    "unknown detector '{}' in masking.defaults (valid detectors: {})",
+                    name,
+                    crate::scanner::PiiType::NAMES.join(", ")
+                );
+            }
+            strategy.validate(registered_strategies).map_err(|e| {
+                anyhow::anyhow!("invalid strategy for masking.defaults '{}': {}", name, e)
+            })?;
+        }
+
+        // Every rule is checked and every problem collected before failing,
+        // so a load with three typos reports all three instead of one per
+        // attempt
+        let mut problems: Vec<String> = Vec::new();
+        for (i, rule) in self.rules.iter().enumerate() {
+            let mut problem = |msg: String| problems.push(format!("rule at index {}: {}", i, msg));
+            if let Err(e) = rule.validate_regexes() {
+                problem(e.to_string());
+            }
+            if let Err(e) = rule.validate_column_pattern() {
+                problem(format!(
+                    "invalid rule for column '{}': {}",
+                    rule.column_label(),
+                    e
+                ));
+            }
+            if let Err(e) = rule.strategy.validate(registered_strategies) {
+                problem(format!(
+                    "invalid rule for column '{}': {}",
+                    rule.column_label(),
+                    e
+                ));
+            }
+            if (rule.redact_text.is_some() || rule.preserve_length)
+                && rule.strategy.as_single() != Some(&Strategy::Redact)
+            {
+                problem(format!(
+                    "invalid rule for column '{}': redact_text and preserve_length \
+                     apply only to the 'redact' strategy",
+                    rule.column_label()
+                ));
+            }
+            let uses_date_shift = rule.strategy.stages().contains(&Strategy::DateShift)
+                || rule
+                    .composite_fields
+                    .iter()
+                    .flatten()
+                    .flatten()
+                    .any(|chain| chain.stages().contains(&Strategy::DateShift));
+            if (rule.date_shift_days.is_some() || rule.seed_column.is_some()) && !uses_date_shift {
+                problem(format!(
+                    "invalid rule for column '{}': date_shift_days and seed_column \
+                     apply only to the 'date_shift' strategy",
+                    rule.column_label()
+                ));
+            }
+            if rule.date_shift_days == Some(0) {
+                problem(format!(
+                    "invalid rule for column '{}': date_shift_days must be at least 1",
+                    rule.column_label()
+                ));
+            }
+            let uses_numeric_noise = rule.strategy.stages().contains(&Strategy::NumericNoise)
+                || rule
+                    .composite_fields
+                    .iter()
+                    .flatten()
+                    .flatten()
+                    .any(|chain| chain.stages().contains(&Strategy::NumericNoise));
+            if rule.noise_percent.is_some() && !uses_numeric_noise {
+                problem(format!(
+                    "invalid rule for column '{}': noise_percent applies only to the \
+                     'numeric_noise' strategy",
+                    rule.column_label()
+                ));
+            }
+            if let Some(percent) = rule.noise_percent
+                && !(percent > 0.0 && percent <= 100.0)
+            {
+                problem(format!(
+                    "invalid rule for column '{}': noise_percent must be greater than 0 \
+                     and at most 100",
+                    rule.column_label()
+                ));
+            }
+            let uses_ip = rule.strategy.stages().contains(&Strategy::Ip)
+                || rule
+                    .composite_fields
+                    .iter()
+                    .flatten()
+                    .flatten()
+                    .any(|chain| chain.stages().contains(&Strategy::Ip));
+            if rule.ip_mode.is_some() && !uses_ip {
+                problem(format!(
+                    "invalid rule for column '{}': ip_mode applies only to the 'ip' strategy",
+                    rule.column_label()
+                ));
+            }
+            let uses_email = rule.strategy.stages().contains(&Strategy::Email)
+                || rule
+                    .composite_fields
+                    .iter()
+                    .flatten()
+                    .flatten()
+                    .any(|chain| chain.stages().contains(&Strategy::Email));
+            if (rule.preserve_domain || !rule.domain_allowlist.is_empty()) && !uses_email {
+                problem(format!(
+                    "invalid rule for column '{}': preserve_domain and domain_allowlist \
+                     apply only to the 'email' strategy",
+                    rule.column_label()
+                ));
+            }
+            if rule.domain_allowlist.iter().any(|domain| {
+                domain.trim_start_matches("*@").is_empty()
+            }) {
+                problem(format!(
+                    "invalid rule for column '{}': domain_allowlist entries must name a \
+                     domain",
+                    rule.column_label()
+                ));
+            }
+            let uses_regex_replace = rule.strategy.stages().contains(&Strategy::RegexReplace)
+                || rule
+                    .composite_fields
+                    .iter()
+                    .flatten()
+                    .flatten()
+                    .chain(rule.json_paths.iter().flat_map(|paths| paths.values()))
+                    .any(|chain| chain.stages().contains(&Strategy::RegexReplace));
+            if (rule.pattern.is_some() || rule.replacement.is_some()) && !uses_regex_replace {
+                problem(format!(
+                    "invalid rule for column '{}': pattern and replacement apply only to \
+                     the 'regex_replace' strategy",
+                    rule.column_label()
+                ));
+            }
+            if uses_regex_replace {
+                match &rule.pattern {
+                    None => problem(format!(
+                        "invalid rule for column '{}': the 'regex_replace' strategy \
+                         requires a pattern",
+                        rule.column_label()
+                    )),
+                    Some(pattern) => match regex::Regex::new(pattern) {
+                        Ok(re) => {
+                            let _ = rule.compiled.value.set(Some(re));
+                        }
+                        Err(e) => problem(format!(
+                            "invalid pattern on rule for column '{}': {}",
+                            rule.column_label(),
+                            e
+                        )),
+                    },
+                }
+            }
+            let uses_wasm = rule.strategy.stages().contains(&Strategy::Wasm)
+                || rule
+                    .composite_fields
+                    .iter()
+                    .flatten()
+                    .flatten()
+                    .chain(rule.json_paths.iter().flat_map(|paths| paths.values()))
+                    .any(|chain| chain.stages().contains(&Strategy::Wasm));
+            if (rule.wasm_module.is_some() || rule.wasm_fuel.is_some()) && !uses_wasm {
+                problem(format!(
+                    "invalid rule for column '{}': wasm_module and wasm_fuel apply only \
+                     to the 'wasm' strategy",
+                    rule.column_label()
+                ));
+            }
+            if uses_wasm {
+                match &rule.wasm_module {
+                    None => problem(format!(
+                        "invalid rule for column '{}': the 'wasm' strategy requires \
+                         wasm_module",
+                        rule.column_label()
+                    )),
+                    Some(path) => {
+                        if rule.wasm_fuel == Some(0) {
+                            problem(format!(
+                                "invalid rule for column '{}': wasm_fuel must be at \
+                                 least 1",
+                                rule.column_label()
+                            ));
+                        }
+                        #[cfg(feature = "wasm-plugins")]
+                        if let Err(e) = crate::wasm::load_module(path) {
+                            problem(format!(
+                                "invalid wasm_module on rule for column '{}': {:#}",
+                                rule.column_label(),
+                                e
+                            ));
+                        }
+                        #[cfg(not(feature = "wasm-plugins"))]
+                        {
+                            let _ = path;
+                            problem(
+                                "a rule uses the 'wasm' strategy, but this binary was \
+                                 compiled without the 'wasm-plugins' feature"
+                                    .to_string(),
+                            );
+                        }
+                    }
+                }
+            }
+            if let Some(paths) = &rule.json_paths {
+                if rule.strategy.as_single() != Some(&Strategy::Json) {
+                    problem(format!(
+                        "invalid rule for column '{}': json_paths applies only to the \
+                         'json' strategy",
+                        rule.column_label()
+                    ));
+                }
+                if paths.is_empty() {
+                    problem(format!(
+                        "invalid rule for column '{}': json_paths must list at least one \
+                         path",
+                        rule.column_label()
+                    ));
+                }
+                for (path, chain) in paths {
+                    if !path.starts_with('/') {
+                        problem(format!(
+                            "invalid json_paths entry '{}' on rule for column '{}': paths \
+                             are JSON pointers and must start with '/'",
+                            path,
+                            rule.column_label()
+                        ));
+                    }
+                    if chain.stages().contains(&Strategy::Json) {
+                        problem(format!(
+                            "invalid json_paths entry '{}' on rule for column '{}': \
+                             entries cannot nest the 'json' strategy",
+                            path,
+                            rule.column_label()
+                        ));
+                    }
+                    if let Err(e) = chain.validate(registered_strategies) {
+                        problem(format!(
+                            "invalid json_paths entry '{}' on rule for column '{}': {}",
+                            path,
+                            rule.column_label(),
+                            e
+                        ));
+                    }
+                }
+            }
+            if let Some(when) = &rule.when
+                && let Err(e) = when.validate()
+            {
+                problem(format!(
+                    "invalid condition on rule for column '{}': {}",
+                    rule.column_label(),
+                    e
+                ));
+            }
+            if let Some(fields) = &rule.composite_fields {
+                if fields.is_empty() {
+                    problem(format!(
+                        "invalid rule for column '{}': composite_fields must list at least \
+                         one field",
+                        rule.column_label()
+                    ));
+                }
+                for chain in fields.iter().flatten() {
+                    if let Err(e) = chain.validate(registered_strategies) {
+                        problem(format!(
+                            "invalid composite_fields entry on rule for column '{}': {}",
+                            rule.column_label(),
+                            e
+                        ));
+                    }
+                }
+            }
+        }
+        if !problems.is_empty() {
+            anyhow::bail!(
+                "config has {} invalid rule(s): {}",
+                problems.len(),
+                problems.join("; ")
+            );
+        }
+
+        // Two rules naming the same exact table and column are legal (the
+        // first match wins) but usually a merge accident, so flag them
+        // without failing the load
+        let mut seen_targets = std::collections::HashSet::new();
+        for rule in &self.rules {
+            if rule.column_is_pattern() || rule.table_regex.is_some() {
+                continue;
+            }
+            let table = rule
+                .table
+                .as_deref()
+                .map(str::to_lowercase)
+                .unwrap_or_default();
+            let column = if rule.case_sensitive {
+                rule.column.clone()
+            } else {
+                rule.column.to_lowercase()
+            };
+            if !seen_targets.insert((table, column)) {
+                tracing::warn!(
+                    table = rule.table.as_deref().unwrap_or("<any>"),
+                    column = %rule.column,
+                    "Duplicate masking rules target the same table and column; the \
+                     first match wins"
+                );
+            }
+        }
+
+        let hash_keyed = self
+            .hashing
+            .as_ref()
+            .is_some_and(|h| h.resolved_key().is_some());
+        if !hash_keyed {
+            let uses_hash = self.rules.iter().any(|rule| {
+                rule.strategy.stages().contains(&Strategy::Hash)
+                    || rule.composite_fields.iter().flatten().flatten().any(|chain| {
+                        chain.stages().contains(&Strategy::Hash)
+                    })
+                    || rule.json_paths.iter().flat_map(|paths| paths.values()).any(|chain| {
+                        chain.stages().contains(&Strategy::Hash)
+                    })
+            });
+            if uses_hash {
+                anyhow::bail!(
+                    "a rule uses the 'hash' strategy but no key is configured; set \
+                     hashing.key or the IRONVEIL_HASH_KEY environment variable (a \
+                     random per-process key would silently break joins)"
+                );
+            }
+        }
+
+        for policy in &self.policies_by_source {
+            if let Some(cidr) = &policy.cidr {
+                cidr.parse::<crate::hooks::Cidr>().map_err(|e| {
+                    anyhow::anyhow!("invalid CIDR '{}' in policies_by_source: {}", cidr, e)
+                })?;
+            }
+            if policy.cidr.is_none()
+                && policy.application_name.is_none()
+                && policy.parameters.is_empty()
+            {
+                anyhow::bail!(
+                    "a policies_by_source entry must set at least one of cidr, \
+                     application_name, or parameters"
+                );
+            }
+            if let Some(sampling) = &policy.sampling {
+                sampling
+                    .validate()
+                    .map_err(|e| anyhow::anyhow!("invalid policies_by_source entry: {}", e))?;
+            }
+        }
+
+        if let Some(routing) = &self.routing {
+            for route in &routing.by_database {
+                if !routing.upstreams.contains_key(&route.upstream) {
+                    anyhow::bail!(
+                        "route for database '{}' names undefined upstream '{}'",
+                        route.database,
+                        route.upstream
+                    );
+                }
+            }
+            if let Some(name) = &routing.default_upstream
+                && !routing.upstreams.contains_key(name)
+            {
+                anyhow::bail!("routing.default_upstream names undefined upstream '{}'", name);
+            }
+            for (name, target) in &routing.upstreams {
+                if target.protocol == Some(UpstreamProtocol::Mysql) {
+                    anyhow::bail!(
+                        "routing upstream '{}' is marked mysql, but routing decides \
+                         by the database in the PostgreSQL startup message and only \
+                         supports postgres upstreams",
+                        name
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_load_valid_yaml() {
+        let yaml = r#"
+masking_enabled: true
+upstream_tls: false
+rules:
+  - table: "users"
+    column: "email"
+    strategy: "email"
+  - column: "phone"
+    strategy: "phone"
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+
+        assert!(config.masking_enabled);
+        assert!(!config.upstream_tls);
+        assert_eq!(config.rules.len(), 2);
+        assert_eq!(config.rules[0].table, Some("users".to_string()));
+        assert_eq!(config.rules[0].column, "email");
+        assert_eq!(config.rules[0].strategy, Strategy::Email.into());
+        assert_eq!(config.rules[1].table, None);
+    }
+
+    #[test]
+    fn test_oid_resolution_parses_with_default_ttl() {
+        let yaml = r#"
+masking_enabled: true
+rules: []
+oid_resolution:
+  username: maintenance
+  password: secret
+  database: app
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let resolution = config.oid_resolution.unwrap();
+        assert_eq!(resolution.username, "maintenance");
+        assert_eq!(resolution.database, "app");
+        assert_eq!(resolution.ttl_secs, 300);
+    }
+
+    #[test]
+    fn test_schema_cache_parses_with_defaults() {
+        let yaml = r#"
+masking_enabled: true
+rules: []
+schema_cache: {}
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let schema_cache = config.schema_cache.unwrap();
+        assert!(schema_cache.prefetch);
+        assert_eq!(schema_cache.refresh_interval_secs, 300);
+    }
+
+    #[test]
+    fn test_validate_source_policy_cidrs() {
+        let yaml = r#"
+rules: []
+policies_by_source:
+  - cidr: "10.8.0.0/16"
+    action: partial
+    priority: 10
+  - cidr: "fd00:1::/32"
+    action: reject
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        config.validate(&[]).unwrap();
+
+        let yaml = r#"
+rules: []
+policies_by_source:
+  - cidr: "10.8.0.0/33"
+    action: mask
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("invalid CIDR '10.8.0.0/33'"), "{}", err);
+    }
+
+    #[test]
+    fn test_validate_routing_upstream_references() {
+        let yaml = r#"
+rules: []
+routing:
+  upstreams:
+    analytics:
+      host: "10.1.0.5"
+      port: 5432
+  by_database:
+    - database: "analytics*"
+      upstream: "analytics"
+  unmatched: reject
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        config.validate(&[]).unwrap();
+
+        let yaml = r#"
+rules: []
+routing:
+  upstreams: {}
+  by_database:
+    - database: "app"
+      upstream: "missing"
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("undefined upstream 'missing'"), "{}", err);
+
+        let yaml = r#"
+rules: []
+routing:
+  upstreams:
+    analytics:
+      host: "10.1.0.5"
+      port: 5432
+  by_database: []
+  default_upstream: "nope"
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("undefined upstream 'nope'"), "{}", err);
+
+        // Routing parses the PostgreSQL startup message, so a mysql
+        // upstream entry is a config mistake caught at load
+        let yaml = r#"
+rules: []
+routing:
+  upstreams:
+    legacy:
+      host: "10.3.0.5"
+      port: 3306
+      protocol: mysql
+  by_database: []
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("only supports postgres upstreams"), "{}", err);
+    }
+
+    #[test]
+    fn test_routing_resolution() {
+        let yaml = r#"
+upstreams:
+  analytics:
+    host: "10.1.0.5"
+    port: 5432
+    user: "svc_analytics"
+  app:
+    host: "10.2.0.5"
+    port: 5432
+by_database:
+  - database: "analytics*"
+    upstream: "analytics"
+  - database: "app"
+    upstream: "app"
+"#;
+        let routing: RoutingConfig = serde_yaml::from_str(yaml).unwrap();
+
+        match routing.resolve(Some("analytics_eu")) {
+            RouteDecision::Upstream(name, target) => {
+                assert_eq!(name, "analytics");
+                assert_eq!(target.user.as_deref(), Some("svc_analytics"));
+            }
+            other => panic!("expected the analytics route, got {:?}", other),
+        }
+        assert!(matches!(
+            routing.resolve(Some("app")),
+            RouteDecision::Upstream("app", _)
+        ));
+        // Unmatched databases (and sessions naming none) use the default
+        assert_eq!(routing.resolve(Some("payments")), RouteDecision::Default);
+        assert_eq!(routing.resolve(None), RouteDecision::Default);
+
+        let rejecting = RoutingConfig {
+            unmatched: UnmatchedDatabase::Reject,
+            ..routing.clone()
+        };
+        assert_eq!(rejecting.resolve(Some("payments")), RouteDecision::Reject);
+
+        // A named catch-all wins over the unmatched behavior
+        let catch_all = RoutingConfig {
+            default_upstream: Some("app".to_string()),
+            unmatched: UnmatchedDatabase::Reject,
+            ..routing
+        };
+        assert!(matches!(
+            catch_all.resolve(Some("payments")),
+            RouteDecision::Upstream("app", _)
+        ));
+        assert!(matches!(
+            catch_all.resolve(None),
+            RouteDecision::Upstream("app", _)
+        ));
+    }
+
+    #[test]
+    fn test_column_glob_validation() {
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: "*_email"
+    strategy: email
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.validate(&[]).is_ok());
+
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: "email[0-9]"
+    strategy: email
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("email[0-9]"), "error does not name the rule: {}", err);
+        assert!(err.contains("unsupported glob syntax"), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn test_database_scope_matching_and_validation() {
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: email
+    database: "app*"
+    strategy: email
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.validate(&[]).is_ok());
+
+        let rule = &config.rules[0];
+        assert!(rule.database_matches(Some("app_prod")));
+        assert!(rule.database_matches(Some("APP_STAGING")), "matching is case-insensitive");
+        assert!(!rule.database_matches(Some("analytics")));
+        // An unknown database fails closed toward masking
+        assert!(rule.database_matches(None));
+        // An unscoped rule is indifferent to the database
+        let unscoped = MaskingRule::basic(None, "email".to_string(), Strategy::Email.into());
+        assert!(unscoped.database_matches(Some("anything")));
+
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: email
+    database: "app[0-9]"
+    strategy: email
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("unsupported glob syntax"), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn test_masking_defaults_validation() {
+        let yaml = r#"
+masking_enabled: true
+rules: []
+masking:
+  defaults:
+    credit_card: format_preserving
+    email: redact
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.validate(&[]).is_ok());
+
+        let yaml = r#"
+masking_enabled: true
+rules: []
+masking:
+  defaults:
+    national_insurance: redact
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("national_insurance"), "error does not name the key: {}", err);
+        assert!(err.contains("valid detectors"), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn test_regex_rule_validation() {
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column_regex: "^(ssn|social_security|national_id)$"
+    table_regex: "^(hr|payroll)_"
+    strategy: ssn
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.validate(&[]).is_ok());
+
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: "email"
+    strategy: email
+  - column_regex: "^(ssn|"
+    strategy: ssn
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("rule at index 1"), "error does not give the index: {}", err);
+        assert!(err.contains("invalid column_regex"), "unexpected error: {}", err);
+
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: "email"
+    column_regex: "email"
+    strategy: email
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("mutually exclusive"), "unexpected error: {}", err);
+
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - strategy: email
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(
+            err.contains("column or column_regex"),
+            "unexpected error: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_regex_rule_matching() {
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column_regex: "^(ssn|social_security|national_id)$"
+    table_regex: "^(hr|payroll)_"
+    strategy: ssn
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        config.validate(&[]).unwrap();
+        let rule = &config.rules[0];
+
+        assert!(rule.column_is_pattern());
+        assert!(rule.column_matches("ssn"));
+        assert!(rule.column_matches("national_id"));
+        assert!(!rule.column_matches("ssn_last_four"));
+        // Regexes are as-written: no implicit case folding
+        assert!(!rule.column_matches("SSN"));
+
+        assert!(rule.table_matches(Some("hr_employees")));
+        assert!(rule.table_matches(Some("payroll_2024")));
+        assert!(!rule.table_matches(Some("audit_hr_employees")));
+        // An unresolved table fails closed, same as a plain table scope
+        assert!(rule.table_matches(None));
+    }
+
+    #[test]
+    fn test_glob_match_semantics() {
+        let rule = |column: &str, case_sensitive: bool| MaskingRule {
+            id: None,
+            table: None,
+            column: column.to_string(),
+            case_sensitive,
+            table_regex: None,
+            column_regex: None,
+            database: None,
+            compiled: Default::default(),
+            redact_text: None,
+            preserve_length: false,
+            date_shift_days: None,
+            seed_column: None,
+            noise_percent: None,
+            ip_mode: None,
+            preserve_domain: false,
+            domain_allowlist: Vec::new(),
+            json_paths: None,
+            pattern: None,
+            replacement: None,
+            wasm_module: None,
+            wasm_fuel: None,
+            deterministic: true,
+            strategy: Strategy::Email.into(),
+            composite_fields: None,
+            on_type_mismatch: TypeMismatchPolicy::Fallback,
+            when: None,
+        };
+
+        assert!(rule("*_email", false).column_matches("billing_email"));
+        assert!(rule("*_email", false).column_matches("Recovery_EMAIL"));
+        assert!(!rule("*_email", false).column_matches("email_count"));
+        assert!(rule("email*", false).column_matches("email_primary"));
+        assert!(rule("e?ail", false).column_matches("email"));
+        assert!(!rule("e?ail", false).column_matches("eml"));
+        assert!(rule("*", false).column_matches("anything"));
+        assert!(!rule("*_email", true).column_matches("Billing_EMAIL"));
+        assert!(rule("email", false).column_matches("EMAIL"));
+    }
+
+    #[test]
+    fn test_strategy_roundtrip() {
+        for name in Strategy::BUILTIN {
+            let strategy: Strategy = name.parse().unwrap();
+            assert!(!matches!(strategy, Strategy::Custom(_)));
+            assert_eq!(strategy.to_string(), *name);
+        }
+    }
+
+    #[test]
+    fn test_unknown_strategy_parses_as_custom() {
+        let strategy: Strategy = "redact_v2".parse().unwrap();
+        assert_eq!(strategy, Strategy::Custom("redact_v2".to_string()));
+    }
+
+    #[test]
+    fn test_hash_strategy_requires_key() {
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: customer_id
+    strategy: hash
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("'hash'"), "unexpected error: {}", err);
+        assert!(err.contains("IRONVEIL_HASH_KEY"), "unexpected error: {}", err);
+
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: customer_id
+    strategy: hash
+hashing:
+  key: "test-key"
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        config.validate(&[]).unwrap();
+        let hashing = config.hashing.unwrap();
+        assert_eq!(hashing.encoding, HashEncoding::Hex);
+        assert_eq!(hashing.truncate, None);
+    }
+
+    #[test]
+    fn test_deterministic_flag_round_trips() {
+        let yaml = r#"
+rules:
+  - column: "email"
+    strategy: "email"
+  - column: "phone"
+    strategy: "phone"
+    deterministic: false
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        config.validate(&[]).unwrap();
+        assert!(config.rules[0].deterministic);
+        assert!(!config.rules[1].deterministic);
+
+        // save_config serializes the same way: the default stays implicit,
+        // the opt-out survives
+        let saved = serde_yaml::to_string(&config).unwrap();
+        assert!(!saved.contains("deterministic: true"), "{}", saved);
+        let reloaded: AppConfig = serde_yaml::from_str(&saved).unwrap();
+        assert!(reloaded.rules[0].deterministic);
+        assert!(!reloaded.rules[1].deterministic);
+    }
+
+    #[test]
+    fn test_scanner_config_parses() {
+        let yaml = r#"
+rules: []
+scanner:
+  ignore_private_ips: true
+  scan_substrings: true
+  locales: ["DE", "UK", "US"]
+  dob_column_hints: ["dob", "geburtsdatum"]
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        config.validate(&[]).unwrap();
+        let scanner = config.scanner.unwrap();
+        assert!(scanner.ignore_private_ips);
+        assert!(scanner.scan_substrings);
+        assert_eq!(scanner.locales, vec![Locale::De, Locale::Uk, Locale::Us]);
+        assert_eq!(scanner.dob_column_hints, vec!["dob", "geburtsdatum"]);
+
+        // An empty hint fragment would match every column name
+        let config: AppConfig =
+            serde_yaml::from_str("rules: []\nscanner:\n  dob_column_hints: [\"dob\", \"\"]\n")
+                .unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(
+            err.contains("dob_column_hints entries must not be empty"),
+            "{}",
+            err
+        );
+
+        // An unknown country code fails to parse at all
+        let err = serde_yaml::from_str::<AppConfig>("rules: []\nscanner:\n  locales: [\"FR\"]\n")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("unknown variant"), "{}", err);
+    }
+
+    #[test]
+    fn test_min_confidence_parses_and_validates() {
+        let yaml = r#"
+rules: []
+scanner:
+  min_confidence: 0.6
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        config.validate(&[]).unwrap();
+        assert_eq!(config.scanner.as_ref().unwrap().min_confidence, 0.6);
+        // The default is left out when saving back
+        let config: AppConfig =
+            serde_yaml::from_str("rules: []\nscanner:\n  ignore_private_ips: true\n").unwrap();
+        assert_eq!(
+            config.scanner.as_ref().unwrap().min_confidence,
+            DEFAULT_MIN_CONFIDENCE
+        );
+        let saved = serde_yaml::to_string(&config).unwrap();
+        assert!(!saved.contains("min_confidence"), "{}", saved);
+        // The default US-only locale set stays implicit too
+        assert_eq!(config.scanner.as_ref().unwrap().locales, DEFAULT_LOCALES);
+        assert!(!saved.contains("locales"), "{}", saved);
+
+        let out_of_range = r#"
+rules: []
+scanner:
+  min_confidence: 1.5
+"#;
+        let config: AppConfig = serde_yaml::from_str(out_of_range).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("must be between 0 and 1"), "{}", err);
+    }
+
+    #[test]
+    fn test_custom_patterns_parse_and_round_trip() {
+        let yaml = r#"
+rules: []
+scanner:
+  custom_patterns:
+    - name: "mrn"
+      pattern: 'MRN-\d{8}'
+      strategy: "redact"
+      severity: high
+    - name: "ticket"
+      pattern: 'TKT-\d+'
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        config.validate(&[]).unwrap();
+        let scanner = config.scanner.as_ref().unwrap();
+        assert_eq!(scanner.custom_patterns.len(), 2);
+        assert_eq!(scanner.custom_patterns[0].severity, PatternSeverity::High);
+        assert_eq!(scanner.custom_patterns[0].strategy, Some(Strategy::Redact));
+        // Defaults: medium severity, no strategy, and neither serialized back
+        assert_eq!(scanner.custom_patterns[1].severity, PatternSeverity::Medium);
+        assert_eq!(scanner.custom_patterns[1].strategy, None);
+        let saved = serde_yaml::to_string(&config).unwrap();
+        assert!(!saved.contains("severity: medium"), "{}", saved);
+    }
+
+    #[test]
+    fn test_custom_pattern_validation_failures() {
+        let broken_regex = r#"
+rules: []
+scanner:
+  custom_patterns:
+    - name: "mrn"
+      pattern: 'MRN-[\d{8}'
+"#;
+        let config: AppConfig = serde_yaml::from_str(broken_regex).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(
+            err.contains("invalid regex in custom pattern 'mrn'"),
+            "{}",
+            err
+        );
+
+        let shadowing = r#"
+rules: []
+scanner:
+  custom_patterns:
+    - name: "email"
+      pattern: '.*'
+"#;
+        let config: AppConfig = serde_yaml::from_str(shadowing).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("shadows a built-in detector"), "{}", err);
+
+        let unknown_strategy = r#"
+rules: []
+scanner:
+  custom_patterns:
+    - name: "mrn"
+      pattern: 'MRN-\d{8}'
+      strategy: "redactt"
+"#;
+        let config: AppConfig = serde_yaml::from_str(unknown_strategy).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(
+            err.contains("invalid strategy on custom pattern 'mrn'"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_masking_determinism_key_parses() {
+        let yaml = r#"
+rules: []
+masking:
+  determinism_key: "super-secret"
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        config.validate(&[]).unwrap();
+        assert_eq!(
+            config
+                .masking
+                .unwrap()
+                .resolved_determinism_key()
+                .as_deref(),
+            Some("super-secret")
+        );
+    }
+
+    #[test]
+    fn test_masking_heuristics_flag_parses() {
+        let yaml = "rules: []\nmasking:\n  heuristics_enabled: false\n";
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        config.validate(&[]).unwrap();
+        assert!(!config.masking.as_ref().unwrap().heuristics_enabled);
+
+        // On by default, and left implicit when saving back
+        let yaml = "rules: []\nmasking:\n  determinism_key: \"k\"\n";
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.masking.as_ref().unwrap().heuristics_enabled);
+        let saved = serde_yaml::to_string(&config).unwrap();
+        assert!(!saved.contains("heuristics_enabled"), "{}", saved);
+    }
+
+    #[cfg(feature = "api")]
+    #[test]
+    fn test_api_auth_config_parses_and_validates() {
+        let hash_a = "a".repeat(64);
+        let hash_b = "b".repeat(64);
+        let yaml = format!(
+            "rules: []\napi:\n  auth:\n    tokens:\n\
+             \x20     - name: dashboard\n        token_sha256: \"{hash_a}\"\n\
+             \x20     - name: ci\n        token_sha256: \"{hash_b}\"\n        role: admin\n"
+        );
+        let config: AppConfig = serde_yaml::from_str(&yaml).unwrap();
+        config.validate(&[]).unwrap();
+        let auth = config.api.as_ref().unwrap().auth.as_ref().unwrap();
+        assert_eq!(auth.tokens[0].role, ApiRole::Read);
+        assert_eq!(auth.tokens[1].role, ApiRole::Admin);
+
+        // The default read role stays implicit when saving back
+        let saved = serde_yaml::to_string(&config).unwrap();
+        assert_eq!(saved.matches("role:").count(), 1, "{}", saved);
+
+        // A value that is not a 64-hex-char digest is refused by name
+        let yaml = "rules: []\napi:\n  auth:\n    tokens:\n\
+                    \x20     - name: dashboard\n        token_sha256: \"hunter2\"\n";
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("64 hex"), "unexpected error: {}", err);
+        assert!(err.contains("dashboard"), "unexpected error: {}", err);
+
+        // Token names must be unique, and the list non-empty
+        let yaml = format!(
+            "rules: []\napi:\n  auth:\n    tokens:\n\
+             \x20     - name: ci\n        token_sha256: \"{hash_a}\"\n\
+             \x20     - name: ci\n        token_sha256: \"{hash_b}\"\n"
+        );
+        let config: AppConfig = serde_yaml::from_str(&yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("duplicate"), "unexpected error: {}", err);
+
+        let yaml = "rules: []\napi:\n  auth:\n    tokens: []\n";
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("at least one token"), "unexpected error: {}", err);
+    }
+
+    #[cfg(feature = "api")]
+    #[test]
+    fn test_api_bind_address_validation() {
+        // Interface IPs and IPv6 forms are all accepted
+        for bind in ["127.0.0.1", "10.1.2.3", "::1", "::"] {
+            let yaml = format!("rules: []\napi:\n  bind_address: \"{}\"\n", bind);
+            let config: AppConfig = serde_yaml::from_str(&yaml).unwrap();
+            config.validate(&[]).unwrap();
+        }
+
+        // A hostname or garbage is refused at load, not at bind time
+        let yaml = "rules: []\napi:\n  bind_address: \"eth0\"\n";
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("bind_address"), "unexpected error: {}", err);
+    }
+
+    #[cfg(feature = "api")]
+    #[test]
+    fn test_api_rate_limit_config_validation() {
+        let yaml = "rules: []\napi:\n  rate_limit:\n    requests_per_second: 5\n";
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        config.validate(&[]).unwrap();
+        let limit = config.api.as_ref().unwrap().rate_limit.as_ref().unwrap();
+        // An unset burst defaults to one second's worth of requests
+        assert_eq!(limit.burst_size(), 5.0);
+
+        let yaml = "rules: []\napi:\n  rate_limit:\n    requests_per_second: 0\n";
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("positive"), "unexpected error: {}", err);
+
+        let yaml = "rules: []\napi:\n  rate_limit:\n    requests_per_second: 5\n    burst: 0\n";
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("at least 1"), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn test_date_shift_rule_options() {
+        // The options only make sense alongside the strategy they tune
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: email
+    strategy: email
+    seed_column: customer_id
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("'date_shift'"), "unexpected error: {}", err);
+
+        // A zero-width range would shift every date by nothing
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: admitted_on
+    strategy: date_shift
+    date_shift_days: 0
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("at least 1"), "unexpected error: {}", err);
+
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: admitted_on
+    strategy: date_shift
+    date_shift_days: 10
+    seed_column: customer_id
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        config.validate(&[]).unwrap();
+        assert_eq!(config.rules[0].date_shift_days, Some(10));
+        assert_eq!(config.rules[0].seed_column.as_deref(), Some("customer_id"));
+    }
+
+    #[test]
+    fn test_numeric_noise_rule_options() {
+        // The option only makes sense alongside the strategy it tunes
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: email
+    strategy: email
+    noise_percent: 5
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("'numeric_noise'"), "unexpected error: {}", err);
+
+        // Zero noise masks nothing; more than ±100% flips signs
+        for bad in ["0", "-5", "250"] {
+            let yaml = format!(
+                "masking_enabled: true\nrules:\n  - column: salary\n    \
+                 strategy: numeric_noise\n    noise_percent: {}\n",
+                bad
+            );
+            let config: AppConfig = serde_yaml::from_str(&yaml).unwrap();
+            let err = config.validate(&[]).unwrap_err().to_string();
+            assert!(err.contains("greater than 0"), "unexpected error: {}", err);
+        }
+
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: salary
+    strategy: numeric_noise
+    noise_percent: 5
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        config.validate(&[]).unwrap();
+        assert_eq!(config.rules[0].noise_percent, Some(5.0));
+    }
+
+    #[test]
+    fn test_ip_mode_rule_option() {
+        // The option only makes sense alongside the strategy it tunes
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: email
+    strategy: email
+    ip_mode: pseudonymize
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("'ip'"), "unexpected error: {}", err);
+
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: client_ip
+    strategy: ip
+    ip_mode: pseudonymize
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        config.validate(&[]).unwrap();
+        assert_eq!(config.rules[0].ip_mode, Some(IpMode::Pseudonymize));
+    }
+
+    #[test]
+    fn test_email_domain_rule_options() {
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: phone
+    strategy: phone
+    preserve_domain: true
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("'email'"), "unexpected error: {}", err);
+
+        // An allowlist entry that is only the '*@' spelling names no domain
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: email
+    strategy: email
+    domain_allowlist: ["*@"]
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("must name a domain"), "unexpected error: {}", err);
+
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: email
+    strategy: email
+    preserve_domain: true
+    domain_allowlist: ["*@ourcompany.com", "partner.example"]
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        config.validate(&[]).unwrap();
+        assert!(config.rules[0].preserve_domain);
+        assert_eq!(config.rules[0].domain_allowlist.len(), 2);
+    }
+
+    #[test]
+    fn test_json_paths_rule_options() {
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: email
+    strategy: email
+    json_paths:
+      /email: email
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("'json'"), "unexpected error: {}", err);
+
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: properties
+    strategy: json
+    json_paths:
+      email: email
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("must start with '/'"), "unexpected error: {}", err);
+
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: properties
+    strategy: json
+    json_paths:
+      /nested: json
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("cannot nest"), "unexpected error: {}", err);
+
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: properties
+    strategy: json
+    json_paths:
+      /email: email
+      /contacts/*/email: email
+      /address/city: [address, hash]
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        // Valid paths, but the hash stage still needs a key configured
+        assert!(err.contains("hash"), "unexpected error: {}", err);
+
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: properties
+    strategy: json
+    json_paths:
+      /email: email
+      /contacts/*/email: email
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        config.validate(&[]).unwrap();
+        assert_eq!(config.rules[0].json_paths.as_ref().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_regex_replace_rule_options() {
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: email
+    strategy: email
+    pattern: "ORD-\\d+"
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("'regex_replace'"), "unexpected error: {}", err);
+
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: order_ref
+    strategy: regex_replace
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("requires a pattern"), "unexpected error: {}", err);
+
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: order_ref
+    strategy: regex_replace
+    pattern: "ORD-("
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(
+            err.contains("invalid pattern on rule for column 'order_ref'"),
+            "unexpected error: {}",
+            err
+        );
+
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: order_ref
+    strategy: regex_replace
+    pattern: "ORD-(\\d{4})-\\d{6}"
+    replacement: "ORD-$1-XXXXXX"
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        config.validate(&[]).unwrap();
+    }
+
+    #[test]
+    fn test_custom_strategies_section() {
+        // A defined name is referenceable without embedder registration
+        let yaml = r#"
+masking_enabled: true
+custom_strategies:
+  scrub_order:
+    pattern: "ORD-\\d{4}-\\d{6}"
+    replacement: "ORD-XXXX-XXXXXX"
+rules:
+  - column: note
+    strategy: scrub_order
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        config.validate(&[]).unwrap();
+
+        // Without the definition the reference is an unknown strategy
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: note
+    strategy: scrub_order
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("unknown masking strategy"), "unexpected error: {}", err);
+
+        let yaml = r#"
+masking_enabled: true
+custom_strategies:
+  scrub_order:
+    pattern: "ORD-("
+    replacement: ""
+rules: []
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(
+            err.contains("invalid pattern in custom strategy 'scrub_order'"),
+            "unexpected error: {}",
+            err
+        );
+
+        let yaml = r#"
+masking_enabled: true
+custom_strategies:
+  email:
+    pattern: ".*"
+    replacement: ""
+rules: []
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("shadows a built-in"), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn test_wasm_rule_options() {
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: email
+    strategy: email
+    wasm_module: tokenizer.wasm
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("'wasm'"), "unexpected error: {}", err);
+
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: token
+    strategy: wasm
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("requires wasm_module"), "unexpected error: {}", err);
+
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: token
+    strategy: wasm
+    wasm_module: tokenizer.wasm
+    wasm_fuel: 0
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("at least 1"), "unexpected error: {}", err);
+
+        // A module that does not load rejects the config up front; in
+        // builds without the runtime the feature itself is refused
+        let yaml = r#"
+masking_enabled: true
+rules:
+  - column: token
+    strategy: wasm
+    wasm_module: /no/such/module.wasm
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        #[cfg(feature = "wasm-plugins")]
+        assert!(err.contains("invalid wasm_module"), "unexpected error: {}", err);
+        #[cfg(not(feature = "wasm-plugins"))]
+        assert!(err.contains("'wasm-plugins' feature"), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn test_strategy_chain_parsing_and_roundtrip() {
+        // A bare name loads as a single-stage chain, as before
+        let single: StrategyChain = serde_yaml::from_str("email").unwrap();
+        assert_eq!(single, Strategy::Email.into());
+        assert_eq!(single.as_single(), Some(&Strategy::Email));
+
+        // A list loads as a multi-stage chain with order preserved
+        let chain: StrategyChain = serde_yaml::from_str("[phone, hash]").unwrap();
+        assert_eq!(chain.stages(), [Strategy::Phone, Strategy::Hash]);
+        assert_eq!(chain.as_single(), None);
+        assert_eq!(chain.terminal(), Some(&Strategy::Hash));
+        assert_eq!(chain.label(), "phone+hash");
+
+        // Single-stage chains serialize back to the bare form, so loading
+        // and saving a config does not rewrite its rules
+        assert_eq!(serde_yaml::to_string(&single).unwrap().trim(), "email");
+        assert_eq!(
+            serde_yaml::to_string(&chain).unwrap().trim(),
+            "- phone\n- hash"
+        );
+    }
+
+    #[test]
+    fn test_strategy_chain_validation() {
+        let valid: StrategyChain = serde_yaml::from_str("[phone, hash]").unwrap();
+        valid.validate(&[]).unwrap();
+
+        // A chain must have at least one stage
+        let empty: StrategyChain = serde_yaml::from_str("[]").unwrap();
+        assert!(empty.validate(&[]).unwrap_err().to_string().contains("at least one stage"));
+
+        // numeric_noise needs numeric input, which hash does not produce
+        let mismatched: StrategyChain = serde_yaml::from_str("[hash, numeric_noise]").unwrap();
+        let err = mismatched.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("'numeric_noise' cannot consume the output of 'hash'"), "{err}");
+
+        // json rewrites structure in place, so it only works on its own
+        let json_chain: StrategyChain = serde_yaml::from_str("[json, hash]").unwrap();
+        assert!(json_chain.validate(&[]).unwrap_err().to_string().contains("'json'"));
+
+        // null leaves nothing for a later stage, so it only works on its own
+        let null_chain: StrategyChain = serde_yaml::from_str(r#"["null", hash]"#).unwrap();
+        assert!(null_chain.validate(&[]).unwrap_err().to_string().contains("'null'"));
+
+        // redact is configured on the rule, so it only works on its own
+        let redact_chain: StrategyChain = serde_yaml::from_str("[hash, redact]").unwrap();
+        assert!(redact_chain.validate(&[]).unwrap_err().to_string().contains("'redact'"));
+
+        // Unknown stages are rejected just like single strategies
+        let unknown: StrategyChain = serde_yaml::from_str("[phone, redact_v2]").unwrap();
+        assert!(unknown.validate(&[]).is_err());
+        unknown.validate(&["redact_v2".to_string()]).unwrap();
+
+        // dob produces a date, which date_shift accepts
+        let dated: StrategyChain = serde_yaml::from_str("[dob, date_shift]").unwrap();
+        dated.validate(&[]).unwrap();
+    }
+
+    #[test]
+    fn test_validate_rule_conditions() {
+        let base = RuleCondition {
+            self_matches: None,
+            or_detected: vec![],
+            column: None,
+            equals: None,
+            on_missing_column: MissingColumnPolicy::Mask,
+        };
+
+        // Each kind of test is valid on its own
+        RuleCondition {
+            self_matches: Some(".*@.*".to_string()),
+            or_detected: vec!["email".to_string()],
+            ..base.clone()
+        }
+        .validate()
+        .unwrap();
+        RuleCondition {
+            column: Some("account_type".to_string()),
+            equals: Some("personal".to_string()),
+            ..base.clone()
+        }
+        .validate()
+        .unwrap();
+
+        // Mixing both kinds, or testing nothing, is rejected
+        let err = RuleCondition {
+            self_matches: Some(".*".to_string()),
+            column: Some("account_type".to_string()),
+            equals: Some("personal".to_string()),
+            ..base.clone()
+        }
+        .validate()
+        .unwrap_err();
+        assert!(err.to_string().contains("not both"), "{err}");
+        assert!(base.validate().unwrap_err().to_string().contains("must test something"));
+
+        // column and equals only come as a pair
+        let err = RuleCondition {
+            column: Some("account_type".to_string()),
+            ..base.clone()
+        }
+        .validate()
+        .unwrap_err();
+        assert!(err.to_string().contains("together"), "{err}");
+
+        // Regexes must compile and detector names must exist
+        let err = RuleCondition {
+            self_matches: Some("(".to_string()),
+            ..base.clone()
+        }
+        .validate()
+        .unwrap_err();
+        assert!(err.to_string().contains("invalid self_matches"), "{err}");
+        let err = RuleCondition {
+            or_detected: vec!["sin".to_string()],
+            ..base.clone()
+        }
+        .validate()
+        .unwrap_err();
+        assert!(err.to_string().contains("unknown detector 'sin'"), "{err}");
+    }
+
+    #[test]
+    fn test_composite_fields_parse_and_validate() {
+        let yaml = r#"
+rules:
+  - column: "contact"
+    strategy: "email"
+    composite_fields: ["email", null, ["phone", "ssn"]]
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        config.validate(&[]).unwrap();
+        let fields = config.rules[0].composite_fields.as_ref().unwrap();
+        assert_eq!(fields[0], Some(Strategy::Email.into()));
+        assert_eq!(fields[1], None);
+        assert_eq!(
+            fields[2],
+            Some(StrategyChain(vec![Strategy::Phone, Strategy::Ssn]))
+        );
 
-fn default_service_name() -> String {
-    "iron-veil".to_string()
-}
+        // Entries are validated like any rule strategy, and an empty list
+        // configures nothing
+        let yaml = r#"
+rules:
+  - column: "contact"
+    strategy: "email"
+    composite_fields: ["emial"]
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("invalid composite_fields entry"), "{}", err);
 
-fn default_masking_enabled() -> bool {
-    true
-}
+        let yaml = r#"
+rules:
+  - column: "contact"
+    strategy: "email"
+    composite_fields: []
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("at least"), "{}", err);
+    }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
-pub struct MaskingRule {
-    pub table: Option<String>,
-    pub column: String,
-    pub strategy: String,
-}
+    #[test]
+    fn test_load_or_last_known_good() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("proxy.yaml");
+        let path_str = path.to_str().unwrap();
 
-impl Default for AppConfig {
-    fn default() -> Self {
-        Self {
-            masking_enabled: true,
-            rules: vec![],
-            tls: None,
-            upstream_tls: false,
-            telemetry: None,
-            api: None,
-            limits: None,
-            health_check: None,
-            audit: None,
-        }
+        // A broken config with no snapshot surfaces its own error
+        fs::write(&path, "rules: [").unwrap();
+        assert!(AppConfig::load_or_last_known_good(path_str).is_err());
+
+        // With a snapshot beside it, the fallback loads and is reported
+        fs::write(last_known_good_path(path_str), "rules: []\nmasking_enabled: false\n")
+            .unwrap();
+        let (config, from_snapshot) = AppConfig::load_or_last_known_good(path_str).unwrap();
+        assert!(from_snapshot);
+        assert!(!config.masking_enabled);
+
+        // A loadable config wins over the snapshot
+        fs::write(&path, "rules: []\n").unwrap();
+        let (config, from_snapshot) = AppConfig::load_or_last_known_good(path_str).unwrap();
+        assert!(!from_snapshot);
+        assert!(config.masking_enabled);
     }
-}
 
-impl AppConfig {
-    pub fn load(path: &str) -> Result<Self> {
-        let content = fs::read_to_string(path)?;
-        let config: AppConfig = serde_yaml::from_str(&content)?;
-        Ok(config)
+    #[test]
+    fn test_validate_listen_addresses() {
+        let yaml = r#"
+masking_enabled: true
+rules: []
+listen:
+  addresses: ["0.0.0.0:6543", "[::]:6543"]
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        config.validate(&[]).unwrap();
+        assert!(config.listen.as_ref().unwrap().require_all);
+
+        let yaml = r#"
+masking_enabled: true
+rules: []
+listen:
+  addresses: ["not-an-address"]
+  require_all: false
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("invalid listen address 'not-an-address'"), "{}", err);
+
+        let yaml = r#"
+masking_enabled: true
+rules: []
+listen:
+  addresses: []
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.validate(&[]).is_err());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_validate_alert_rules() {
+        let yaml = r#"
+masking_enabled: true
+rules: []
+alerts:
+  rules:
+    - name: email-burst
+      pii_type: email
+      threshold: 10
+      window_secs: 300
+      policy: partial
+      webhook: "http://hooks.internal/alerts"
+      quarantine: true
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        config.validate(&[]).unwrap();
+
+        let yaml = r#"
+masking_enabled: true
+rules: []
+alerts:
+  rules:
+    - name: bad-type
+      pii_type: emial
+      threshold: 1
+      window_secs: 60
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("unknown pii_type 'emial'"), "{}", err);
+
+        let yaml = r#"
+masking_enabled: true
+rules: []
+alerts:
+  rules:
+    - name: zero-window
+      threshold: 1
+      window_secs: 0
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("non-zero window_secs"), "{}", err);
+
+        let yaml = r#"
+masking_enabled: true
+rules: []
+alerts:
+  rules:
+    - name: tls-hook
+      threshold: 1
+      window_secs: 60
+      webhook: "https://hooks.internal/alerts"
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("plain http:// URL"), "{}", err);
+    }
 
     #[test]
-    fn test_config_load_valid_yaml() {
+    fn test_validate_admin_config() {
         let yaml = r#"
 masking_enabled: true
-upstream_tls: false
+rules: []
+admin:
+  password: "sekret"
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        config.validate(&[]).unwrap();
+        assert_eq!(config.admin.as_ref().unwrap().database_name, "iron_veil");
+
+        // No restriction at all leaves the console wide open
+        let yaml = r#"
+masking_enabled: true
+rules: []
+admin:
+  database_name: console
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("password or an allow_cidr"), "{}", err);
+
+        let yaml = r#"
+masking_enabled: true
+rules: []
+admin:
+  allow_cidr: "not-a-network"
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("invalid admin allow_cidr"), "{}", err);
+    }
+
+    #[test]
+    fn test_validate_upstream_tls_options() {
+        let yaml = r#"
+masking_enabled: true
+rules: []
+upstream_tls: true
+upstream_tls_options:
+  ca_cert_path: "certs/rds-bundle.pem"
+  verify_hostname: false
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        config.validate(&[]).unwrap();
+        let options = config.upstream_tls_options.as_ref().unwrap();
+        assert_eq!(options.ca_cert_path.as_deref(), Some("certs/rds-bundle.pem"));
+        assert!(!options.verify_hostname);
+        assert!(!options.insecure_skip_verify);
+
+        // Options without the upstream_tls switch would silently do nothing
+        let yaml = r#"
+masking_enabled: true
+rules: []
+upstream_tls_options:
+  insecure_skip_verify: true
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("upstream_tls is not enabled"), "{}", err);
+
+        // Skipping verification makes a pinned CA meaningless
+        let yaml = r#"
+masking_enabled: true
+rules: []
+upstream_tls: true
+upstream_tls_options:
+  ca_cert_path: "certs/rds-bundle.pem"
+  insecure_skip_verify: true
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("conflicts with ca_cert_path"), "{}", err);
+    }
+
+    #[test]
+    fn test_validate_sampling_config() {
+        // At least one knob must be set
+        let err = SamplingConfig {
+            percent: None,
+            key_column: None,
+            max_rows_per_result: None,
+        }
+        .validate()
+        .unwrap_err();
+        assert!(err.to_string().contains("percent or max_rows_per_result"), "{err}");
+
+        // percent 0 would drop everything; 101 is nonsense
+        for bad in [0, 101] {
+            let err = SamplingConfig {
+                percent: Some(bad),
+                key_column: None,
+                max_rows_per_result: None,
+            }
+            .validate()
+            .unwrap_err();
+            assert!(err.to_string().contains("between 1 and 100"), "{err}");
+        }
+
+        // Either knob alone is enough; key_column rides along with percent
+        assert!(
+            SamplingConfig {
+                percent: Some(10),
+                key_column: Some("user_id".to_string()),
+                max_rows_per_result: None,
+            }
+            .validate()
+            .is_ok()
+        );
+        assert!(
+            SamplingConfig {
+                percent: None,
+                key_column: None,
+                max_rows_per_result: Some(500),
+            }
+            .validate()
+            .is_ok()
+        );
+
+        // Policies carry the validation through AppConfig::validate
+        let yaml = r#"
+rules: []
+policies_by_source:
+  - cidr: "10.0.0.0/8"
+    action: "mask"
+    sampling:
+      percent: 0
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("invalid policies_by_source entry"), "{}", err);
+    }
+
+    #[test]
+    fn test_validate_rejects_unknown_strategy_with_suggestion() {
+        let yaml = r#"
+rules:
+  - column: "email"
+    strategy: "emial"
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("unknown masking strategy 'emial'"), "{}", err);
+        assert!(err.contains("Did you mean 'email'?"), "{}", err);
+    }
+
+    #[test]
+    fn test_validate_accepts_registered_custom_strategy() {
+        let strategy = Strategy::Custom("redact_v2".to_string());
+        assert!(strategy.validate(&["redact_v2".to_string()]).is_ok());
+        assert!(strategy.validate(&[]).is_err());
+    }
+
+    #[test]
+    fn test_validate_reports_every_problem_at_once() {
+        let yaml = r#"
+rules:
+  - column: "email"
+    strategy: "emial"
+  - column: "notes"
+    strategy: "redact"
+    noise_percent: 10
+  - column: "created_at"
+    strategy: "date_shift"
+    date_shift_days: 0
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("3 invalid rule(s)"), "{}", err);
+        assert!(
+            err.contains("rule at index 0: invalid rule for column 'email'"),
+            "{}",
+            err
+        );
+        assert!(err.contains("unknown masking strategy 'emial'"), "{}", err);
+        assert!(err.contains("rule at index 1:"), "{}", err);
+        assert!(err.contains("noise_percent applies only"), "{}", err);
+        assert!(err.contains("rule at index 2:"), "{}", err);
+        assert!(err.contains("date_shift_days must be at least 1"), "{}", err);
+    }
+
+    #[test]
+    fn test_validate_allows_duplicate_rule_targets() {
+        // Duplicate (table, column) pairs warn but stay valid: the first
+        // matching rule wins at runtime
+        let yaml = r#"
 rules:
   - table: "users"
     column: "email"
     strategy: "email"
-  - column: "phone"
-    strategy: "phone"
+  - table: "users"
+    column: "EMAIL"
+    strategy: "redact"
 "#;
         let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.validate(&[]).is_ok());
+    }
 
-        assert!(config.masking_enabled);
-        assert!(!config.upstream_tls);
-        assert_eq!(config.rules.len(), 2);
-        assert_eq!(config.rules[0].table, Some("users".to_string()));
-        assert_eq!(config.rules[0].column, "email");
-        assert_eq!(config.rules[0].strategy, "email");
-        assert_eq!(config.rules[1].table, None);
+    #[test]
+    fn test_rule_ids_generated_and_stable_across_save_load() {
+        let yaml = r#"
+rules:
+  - column: "email"
+    strategy: "email"
+"#;
+        let mut config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        config.ensure_rule_ids().unwrap();
+        let id = config.rules[0].id.clone().expect("id should be generated");
+
+        // Serialize (as save_config does) and load again: the id round-trips
+        let saved = serde_yaml::to_string(&config).unwrap();
+        let mut reloaded: AppConfig = serde_yaml::from_str(&saved).unwrap();
+        reloaded.ensure_rule_ids().unwrap();
+        assert_eq!(reloaded.rules[0].id.as_ref(), Some(&id));
+
+        // Edits keep the id
+        reloaded.rules[0].column = "email_address".to_string();
+        reloaded.ensure_rule_ids().unwrap();
+        assert_eq!(reloaded.rules[0].id.as_ref(), Some(&id));
+    }
+
+    #[test]
+    fn test_rule_ids_preserved_across_export_import() {
+        let mut config = AppConfig {
+            rules: vec![MaskingRule {
+                id: None,
+                table: None,
+                column: "ssn".to_string(),
+                case_sensitive: false,
+                table_regex: None,
+                column_regex: None,
+                database: None,
+                compiled: Default::default(),
+                redact_text: None,
+                preserve_length: false,
+                date_shift_days: None,
+                seed_column: None,
+                noise_percent: None,
+                ip_mode: None,
+                preserve_domain: false,
+                domain_allowlist: Vec::new(),
+                json_paths: None,
+                pattern: None,
+                replacement: None,
+                wasm_module: None,
+                wasm_fuel: None,
+                deterministic: true,
+                strategy: Strategy::Ssn.into(),
+                composite_fields: None,
+                on_type_mismatch: TypeMismatchPolicy::Fallback,
+                when: None,
+            }],
+            ..Default::default()
+        };
+        config.ensure_rule_ids().unwrap();
+        let id = config.rules[0].id.clone().unwrap();
+
+        // Export/import use JSON; the id must survive the round-trip
+        let exported = serde_json::to_string(&config.rules).unwrap();
+        let imported: Vec<MaskingRule> = serde_json::from_str(&exported).unwrap();
+        assert_eq!(imported[0].id.as_ref(), Some(&id));
+    }
+
+    #[test]
+    fn test_duplicate_rule_ids_rejected() {
+        let yaml = r#"
+rules:
+  - id: "rule-1"
+    column: "email"
+    strategy: "email"
+  - id: "rule-1"
+    column: "phone"
+    strategy: "phone"
+"#;
+        let mut config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.ensure_rule_ids().unwrap_err().to_string();
+        assert!(err.contains("duplicate rule id 'rule-1'"), "{}", err);
+    }
+
+    #[test]
+    fn test_dangling_rule_reference_fails_validation() {
+        let mut config = AppConfig {
+            rules: vec![MaskingRule {
+                id: Some("rule-1".to_string()),
+                table: None,
+                column: "email".to_string(),
+                case_sensitive: false,
+                table_regex: None,
+                column_regex: None,
+                database: None,
+                compiled: Default::default(),
+                redact_text: None,
+                preserve_length: false,
+                date_shift_days: None,
+                seed_column: None,
+                noise_percent: None,
+                ip_mode: None,
+                preserve_domain: false,
+                domain_allowlist: Vec::new(),
+                json_paths: None,
+                pattern: None,
+                replacement: None,
+                wasm_module: None,
+                wasm_fuel: None,
+                deterministic: true,
+                strategy: Strategy::Email.into(),
+                composite_fields: None,
+                on_type_mismatch: TypeMismatchPolicy::Fallback,
+                when: None,
+            }],
+            ..Default::default()
+        };
+        config.ensure_rule_ids().unwrap();
+
+        assert!(config.validate_rule_refs(&["rule-1".to_string()]).is_ok());
+
+        // Delete and re-add: the re-added rule gets a new id, so the old
+        // reference is dangling and must fail with a clear message
+        config.rules.clear();
+        config.rules.push(MaskingRule {
+            id: None,
+            table: None,
+            column: "email".to_string(),
+            case_sensitive: false,
+            table_regex: None,
+            column_regex: None,
+            database: None,
+            compiled: Default::default(),
+            redact_text: None,
+            preserve_length: false,
+            date_shift_days: None,
+            seed_column: None,
+            noise_percent: None,
+            ip_mode: None,
+            preserve_domain: false,
+            domain_allowlist: Vec::new(),
+            json_paths: None,
+            pattern: None,
+            replacement: None,
+            wasm_module: None,
+            wasm_fuel: None,
+            deterministic: true,
+            strategy: Strategy::Email.into(),
+            composite_fields: None,
+            on_type_mismatch: TypeMismatchPolicy::Fallback,
+            when: None,
+        });
+        config.ensure_rule_ids().unwrap();
+        assert_ne!(config.rules[0].id.as_deref(), Some("rule-1"));
+
+        let err = config
+            .validate_rule_refs(&["rule-1".to_string()])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("unknown rule id 'rule-1'"), "{}", err);
+        assert!(err.contains("new id"), "{}", err);
     }
 
     #[test]
@@ -317,6 +4731,63 @@ rules: []
         assert_eq!(tls.key_path, "certs/server.key");
     }
 
+    #[test]
+    fn test_check_collects_findings() {
+        let config: AppConfig = serde_yaml::from_str("rules: []").unwrap();
+        assert!(config.check().is_empty());
+
+        // An unloadable cert and a sink-less audit section surface in one
+        // pass, with severities CI can split on
+        let yaml = r#"
+rules: []
+tls:
+  enabled: true
+  cert_path: /nonexistent/server.crt
+  key_path: /nonexistent/server.key
+audit:
+  enabled: true
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let findings = config.check();
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0].severity, FindingSeverity::Error);
+        assert_eq!(findings[0].component, "tls");
+        assert_eq!(findings[1].severity, FindingSeverity::Warning);
+        assert_eq!(findings[1].component, "audit");
+    }
+
+    #[test]
+    fn test_env_var_substitution() {
+        // Unique names so parallel tests cannot race on the same variable
+        unsafe { std::env::set_var("IRONVEIL_TEST_SUBST_HOST", "db.internal") };
+
+        // Set variable, default taken when unset, literal non-references
+        assert_eq!(
+            expand_env_refs("host=${IRONVEIL_TEST_SUBST_HOST}").unwrap(),
+            "host=db.internal"
+        );
+        assert_eq!(
+            expand_env_refs("${IRONVEIL_TEST_SUBST_MISSING:-fallback}").unwrap(),
+            "fallback"
+        );
+        assert_eq!(expand_env_refs("no refs $HOME ${").unwrap(), "no refs $HOME ${");
+        assert_eq!(expand_env_refs("${}").unwrap(), "${}");
+
+        // Unset without a default fails with the variable name
+        assert_eq!(
+            expand_env_refs("${IRONVEIL_TEST_SUBST_MISSING}").unwrap_err(),
+            "IRONVEIL_TEST_SUBST_MISSING"
+        );
+
+        // Substitution is per string value: an env value full of YAML
+        // syntax stays one string instead of becoming structure
+        unsafe { std::env::set_var("IRONVEIL_TEST_SUBST_YAML", "a: [b, c]") };
+        let mut value: serde_yaml::Value =
+            serde_yaml::from_str("description: ${IRONVEIL_TEST_SUBST_YAML}").unwrap();
+        substitute_env_vars(&mut value).unwrap();
+        assert_eq!(value["description"], serde_yaml::Value::from("a: [b, c]"));
+    }
+
     #[test]
     fn test_invalid_yaml_fails() {
         let yaml = r#"
@@ -334,4 +4805,74 @@ masking_enabled: true
         let result: Result<AppConfig, _> = serde_yaml::from_str(yaml);
         assert!(result.is_err()); // Should fail because 'rules' is missing
     }
+
+    // The compiled-without-X checks are no-ops under default features, so
+    // these tests only exist in the slimmer builds CI exercises.
+    #[cfg(not(feature = "api"))]
+    #[test]
+    fn test_api_section_rejected_without_api_feature() {
+        let config = AppConfig {
+            api: Some(ApiConfig {
+                api_key: None,
+                jwt_secret: None,
+                addresses: None,
+                bind_address: None,
+                tls: None,
+                docs: false,
+                rate_limit: None,
+                auth: None,
+            }),
+            ..Default::default()
+        };
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("without the 'api' feature"), "{}", err);
+    }
+
+    #[cfg(not(feature = "otel"))]
+    #[test]
+    fn test_enabled_telemetry_rejected_without_otel_feature() {
+        let yaml = r#"
+rules: []
+telemetry:
+  enabled: true
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(err.contains("without the 'otel' feature"), "{}", err);
+
+        // A present-but-disabled section is fine
+        let yaml = r#"
+rules: []
+telemetry:
+  enabled: false
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.validate(&[]).is_ok());
+    }
+
+    #[cfg(not(feature = "metrics-prometheus"))]
+    #[test]
+    fn test_prometheus_backend_rejected_without_feature() {
+        let yaml = r#"
+rules: []
+metrics:
+  backend: prometheus
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        let err = config.validate(&[]).unwrap_err().to_string();
+        assert!(
+            err.contains("without the 'metrics-prometheus' feature"),
+            "{}",
+            err
+        );
+
+        // statsd needs no compile-time support
+        let yaml = r#"
+rules: []
+metrics:
+  backend: statsd
+"#;
+        let config: AppConfig = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.validate(&[]).is_ok());
+    }
 }
@@ -3,10 +3,14 @@
 //! Provides real database introspection capabilities for PII detection.
 //! Queries `information_schema` for column metadata and samples actual data.
 
+use crate::config::{MaskingRule, Strategy, StrategyChain};
 use crate::scanner::{PiiScanner, PiiType};
 use crate::state::DbProtocol;
+use crate::version::{ServerVersion, VersionQuirks};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use thiserror::Error;
 use tokio_postgres::{Client, NoTls};
 use tracing::{debug, info, instrument, warn};
@@ -60,6 +64,41 @@ fn default_confidence_threshold() -> f64 {
     0.5
 }
 
+/// Shared progress counters for a running scan. The scan loop updates
+/// them without taking a lock, so status reads never contend with the
+/// scan itself.
+#[derive(Debug, Default)]
+pub struct ScanProgress {
+    /// Tables selected for scanning, known once the catalog is read
+    pub tables_total: AtomicUsize,
+    /// Tables fully scanned so far
+    pub tables_done: AtomicUsize,
+}
+
+/// Status of a background scan started via `POST /scan`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanJobStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+/// A background scan tracked in [`AppState`](crate::state::AppState)'s
+/// job table: progress while running, the full result (or error) once
+/// the task finishes. Findings carry masked sample values only, so the
+/// job can be echoed to API clients without exposing raw cell contents.
+#[derive(Debug)]
+pub struct ScanJob {
+    pub id: String,
+    pub database: String,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub status: ScanJobStatus,
+    pub progress: Arc<ScanProgress>,
+    pub result: Option<ScanResult>,
+    pub error: Option<String>,
+}
+
 /// Represents column metadata from information_schema
 #[derive(Debug, Clone, Serialize)]
 pub struct ColumnInfo {
@@ -95,6 +134,227 @@ pub struct ScanResult {
     pub scan_duration_ms: u64,
 }
 
+/// One PII column in a discovery report, with its detection rate and
+/// whether a masking rule already covers it
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportColumn {
+    pub schema: String,
+    pub table: String,
+    pub column: String,
+    pub data_type: String,
+    pub pii_type: String,
+    /// Fraction of sampled values the scanner matched (0.0 - 1.0)
+    pub detection_rate: f64,
+    pub match_count: usize,
+    pub rows_sampled: usize,
+    pub sample: Option<String>,
+    /// Whether a masking rule in the loaded config covers this column
+    pub rule_covered: bool,
+}
+
+/// A PII discovery report across one or more schemas: every detected
+/// column ranked by detection rate and cross-referenced against the
+/// masking rules, so uncovered columns stand out. Produced by
+/// [`DbScanner::discover`].
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscoveryReport {
+    pub database: String,
+    pub schemas: Vec<String>,
+    pub tables_scanned: usize,
+    pub columns_scanned: usize,
+    pub columns: Vec<ReportColumn>,
+    /// Detected PII columns that no masking rule covers
+    pub uncovered: usize,
+    pub scan_duration_ms: u64,
+}
+
+impl DiscoveryReport {
+    /// Assemble a report from per-schema scan results, ranking columns by
+    /// detection rate and marking which ones `rules` already cover
+    pub fn assemble(
+        database: &str,
+        schemas: Vec<String>,
+        results: Vec<ScanResult>,
+        rules: &[MaskingRule],
+    ) -> Self {
+        let mut tables_scanned = 0;
+        let mut columns_scanned = 0;
+        let mut scan_duration_ms = 0;
+        let mut columns = Vec::new();
+
+        for result in results {
+            tables_scanned += result.tables_scanned;
+            columns_scanned += result.columns_scanned;
+            scan_duration_ms += result.scan_duration_ms;
+            for finding in result.findings {
+                let rule_covered = rules.iter().any(|rule| {
+                    rule.table_matches(Some(&finding.table)) && rule.column_matches(&finding.column)
+                });
+                let detection_rate = if finding.row_count > 0 {
+                    finding.match_count as f64 / finding.row_count as f64
+                } else {
+                    0.0
+                };
+                columns.push(ReportColumn {
+                    schema: result.schema.clone(),
+                    table: finding.table,
+                    column: finding.column,
+                    data_type: finding.data_type,
+                    pii_type: finding.pii_type,
+                    detection_rate,
+                    match_count: finding.match_count,
+                    rows_sampled: finding.row_count,
+                    sample: finding.sample,
+                    rule_covered,
+                });
+            }
+        }
+
+        // Highest detection rate first; ties in catalog order so the
+        // report is stable across runs
+        columns.sort_by(|a, b| {
+            b.detection_rate
+                .partial_cmp(&a.detection_rate)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| {
+                    (&a.schema, &a.table, &a.column).cmp(&(&b.schema, &b.table, &b.column))
+                })
+        });
+
+        let uncovered = columns.iter().filter(|c| !c.rule_covered).count();
+
+        Self {
+            database: database.to_string(),
+            schemas,
+            tables_scanned,
+            columns_scanned,
+            columns,
+            uncovered,
+            scan_duration_ms,
+        }
+    }
+
+    /// Render the report as a human-readable table
+    pub fn render_table(&self) -> String {
+        use std::fmt::Write;
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "PII discovery report for {} (schemas: {})",
+            self.database,
+            self.schemas.join(", ")
+        );
+        let _ = writeln!(
+            out,
+            "{} tables, {} columns scanned in {}ms; {} PII columns found, {} without a masking rule",
+            self.tables_scanned,
+            self.columns_scanned,
+            self.scan_duration_ms,
+            self.columns.len(),
+            self.uncovered
+        );
+        if self.columns.is_empty() {
+            return out;
+        }
+        let _ = writeln!(out);
+
+        const HEADER: [&str; 5] = ["COLUMN", "PII TYPE", "RATE", "MATCHES", "RULE"];
+        let rows: Vec<[String; 5]> = self
+            .columns
+            .iter()
+            .map(|c| {
+                [
+                    format!("{}.{}.{}", c.schema, c.table, c.column),
+                    c.pii_type.clone(),
+                    format!("{:.0}%", c.detection_rate * 100.0),
+                    format!("{}/{}", c.match_count, c.rows_sampled),
+                    if c.rule_covered {
+                        "covered".to_string()
+                    } else {
+                        "UNCOVERED".to_string()
+                    },
+                ]
+            })
+            .collect();
+
+        let mut widths = HEADER.map(str::len);
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len());
+            }
+        }
+
+        let mut line = String::new();
+        for (cell, width) in HEADER.iter().zip(widths) {
+            let _ = write!(line, "{:<width$}  ", cell);
+        }
+        let _ = writeln!(out, "{}", line.trim_end());
+        for row in &rows {
+            line.clear();
+            for (cell, width) in row.iter().zip(widths) {
+                let _ = write!(line, "{:<width$}  ", cell);
+            }
+            let _ = writeln!(out, "{}", line.trim_end());
+        }
+
+        out
+    }
+}
+
+/// Default strategy for a finding's PII type, used when a scan finding
+/// is turned into a suggested rule. PII types without a fake generator
+/// (foreign national IDs, custom patterns) fall back to redaction.
+fn suggested_strategy(pii_type: &str) -> Strategy {
+    match pii_type {
+        "Email" => Strategy::Email,
+        "Phone" => Strategy::Phone,
+        "CreditCard" => Strategy::CreditCard,
+        "Ssn" => Strategy::Ssn,
+        "IpAddress" => Strategy::Ip,
+        "DateOfBirth" => Strategy::Dob,
+        "Passport" => Strategy::Passport,
+        "Iban" => Strategy::Iban,
+        _ => Strategy::Redact,
+    }
+}
+
+impl ScanResult {
+    /// Turn the findings into ready-to-apply [`MaskingRule`]s: one rule
+    /// per (table, column) whose detection rate (matches / sampled rows)
+    /// reaches `min_detection_rate`, with a default strategy for the
+    /// detected PII type. Columns a rule in `existing` already covers
+    /// are skipped.
+    pub fn suggested_rules(
+        &self,
+        min_detection_rate: f64,
+        existing: &[MaskingRule],
+    ) -> Vec<MaskingRule> {
+        let mut rules = Vec::new();
+        for finding in &self.findings {
+            let detection_rate = if finding.row_count > 0 {
+                finding.match_count as f64 / finding.row_count as f64
+            } else {
+                0.0
+            };
+            if detection_rate < min_detection_rate {
+                continue;
+            }
+            if existing.iter().any(|rule| {
+                rule.table_matches(Some(&finding.table)) && rule.column_matches(&finding.column)
+            }) {
+                continue;
+            }
+            rules.push(MaskingRule::basic(
+                Some(finding.table.clone()),
+                finding.column.clone(),
+                StrategyChain::from(suggested_strategy(&finding.pii_type)),
+            ));
+        }
+        rules
+    }
+}
+
 /// Represents schema information
 #[derive(Debug, Clone, Serialize)]
 pub struct SchemaInfo {
@@ -111,12 +371,21 @@ pub struct TableInfo {
     pub row_count: Option<i64>,
 }
 
+/// A cached `/schema` fetch: what came back and when, so the API can
+/// serve it within a TTL and fall back to it when the upstream is down
+#[derive(Debug, Clone)]
+pub struct SchemaSnapshot {
+    pub schema: SchemaInfo,
+    pub fetched_at: chrono::DateTime<chrono::Utc>,
+}
+
 /// Database scanner for PII detection
 pub struct DbScanner {
     host: String,
     port: u16,
     protocol: DbProtocol,
     pii_scanner: PiiScanner,
+    version: Option<ServerVersion>,
 }
 
 impl DbScanner {
@@ -127,16 +396,55 @@ impl DbScanner {
             port,
             protocol,
             pii_scanner: PiiScanner::new(),
+            version: None,
         }
     }
 
+    /// Set the detected upstream server version so queries can pick
+    /// version-appropriate behavior
+    pub fn with_version(mut self, version: Option<ServerVersion>) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Apply the `scanner` config section, so on-demand scans honor
+    /// `ignore_private_ips` and detect `custom_patterns` like the row path
+    pub fn with_scanner_config(mut self, config: Option<&crate::config::ScannerConfig>) -> Self {
+        if let Some(config) = config {
+            self.pii_scanner
+                .set_ignore_private_ips(config.ignore_private_ips);
+            self.pii_scanner.set_locales(&config.locales);
+            self.pii_scanner.set_custom_patterns(&config.custom_patterns);
+        }
+        self
+    }
+
+    /// Quirk table for the detected version (conservative defaults if unknown)
+    fn quirks(&self) -> VersionQuirks {
+        self.version
+            .as_ref()
+            .map(|v| v.quirks())
+            .unwrap_or_default()
+    }
+
     /// Scan the database for PII
     #[instrument(skip(self, config), fields(host = %self.host, port = %self.port, db = %config.database))]
     pub async fn scan(&self, config: &ScanConfig) -> Result<ScanResult, ScanError> {
+        self.scan_with_progress(config, None).await
+    }
+
+    /// Scan the database for PII, updating `progress` as tables complete
+    /// so a concurrent status endpoint can report tables done / total
+    #[instrument(skip(self, config, progress), fields(host = %self.host, port = %self.port, db = %config.database))]
+    pub async fn scan_with_progress(
+        &self,
+        config: &ScanConfig,
+        progress: Option<&ScanProgress>,
+    ) -> Result<ScanResult, ScanError> {
         let start = std::time::Instant::now();
 
         match self.protocol {
-            DbProtocol::Postgres => self.scan_postgres(config, start).await,
+            DbProtocol::Postgres => self.scan_postgres(config, start, progress).await,
             DbProtocol::MySql => {
                 // MySQL support coming in future
                 Err(ScanError::UnsupportedProtocol(DbProtocol::MySql))
@@ -144,6 +452,48 @@ impl DbScanner {
         }
     }
 
+    /// Scan one or more schemas and assemble a discovery report: every
+    /// detected PII column ranked by detection rate and cross-referenced
+    /// against `rules`. An empty `schemas` list scans every non-system
+    /// schema in the database; `exclude_schemas` entries are skipped either
+    /// way. The schema list takes the place of `config.schema`.
+    #[instrument(skip_all, fields(host = %self.host, port = %self.port, db = %config.database))]
+    pub async fn discover(
+        &self,
+        config: &ScanConfig,
+        schemas: &[String],
+        exclude_schemas: &[String],
+        rules: &[MaskingRule],
+    ) -> Result<DiscoveryReport, ScanError> {
+        if self.protocol != DbProtocol::Postgres {
+            return Err(ScanError::UnsupportedProtocol(self.protocol));
+        }
+
+        let mut schemas = if schemas.is_empty() {
+            let client = self.connect_postgres(config).await?;
+            self.list_postgres_schemas(&client).await?
+        } else {
+            schemas.to_vec()
+        };
+        schemas.retain(|schema| !exclude_schemas.contains(schema));
+
+        let mut results = Vec::with_capacity(schemas.len());
+        for schema in &schemas {
+            let schema_config = ScanConfig {
+                schema: schema.clone(),
+                ..config.clone()
+            };
+            results.push(self.scan(&schema_config).await?);
+        }
+
+        Ok(DiscoveryReport::assemble(
+            &config.database,
+            schemas,
+            results,
+            rules,
+        ))
+    }
+
     /// Get schema information from the database
     #[instrument(skip(self, config), fields(host = %self.host, port = %self.port, db = %config.database))]
     pub async fn get_schema(&self, config: &ScanConfig) -> Result<SchemaInfo, ScanError> {
@@ -158,6 +508,7 @@ impl DbScanner {
         &self,
         config: &ScanConfig,
         start: std::time::Instant,
+        progress: Option<&ScanProgress>,
     ) -> Result<ScanResult, ScanError> {
         let client = self.connect_postgres(config).await?;
 
@@ -189,6 +540,9 @@ impl DbScanner {
             tables.len(),
             config.exclude_tables
         );
+        if let Some(progress) = progress {
+            progress.tables_total.store(tables.len(), Ordering::Relaxed);
+        }
 
         let mut findings = Vec::new();
         let mut columns_scanned = 0;
@@ -261,6 +615,10 @@ impl DbScanner {
                     });
                 }
             }
+
+            if let Some(progress) = progress {
+                progress.tables_done.fetch_add(1, Ordering::Relaxed);
+            }
         }
 
         let duration = start.elapsed();
@@ -316,8 +674,18 @@ impl DbScanner {
         client: &Client,
         schema: &str,
     ) -> Result<Vec<ColumnInfo>, ScanError> {
-        let query = r#"
-            SELECT 
+        // information_schema.columns only has is_generated on Pg 12+;
+        // on those versions skip generated columns (masking the source
+        // columns covers them).
+        let generated_filter = if self.quirks().has_is_generated_column {
+            "AND is_generated = 'NEVER'"
+        } else {
+            ""
+        };
+
+        let query = format!(
+            r#"
+            SELECT
                 table_name,
                 column_name,
                 data_type,
@@ -327,11 +695,14 @@ impl DbScanner {
             WHERE table_schema = $1
             AND table_name NOT LIKE 'pg_%'
             AND table_name NOT LIKE 'sql_%'
+            {}
             ORDER BY table_name, ordinal_position
-        "#;
+        "#,
+            generated_filter
+        );
 
         let rows = client
-            .query(query, &[&schema])
+            .query(&query, &[&schema])
             .await
             .map_err(|e| ScanError::QueryFailed(e.to_string()))?;
 
@@ -349,6 +720,24 @@ impl DbScanner {
         Ok(columns)
     }
 
+    /// List user schemas (everything but the system catalogs)
+    async fn list_postgres_schemas(&self, client: &Client) -> Result<Vec<String>, ScanError> {
+        let query = r#"
+            SELECT schema_name
+            FROM information_schema.schemata
+            WHERE schema_name <> 'information_schema'
+            AND schema_name NOT LIKE 'pg_%'
+            ORDER BY schema_name
+        "#;
+
+        let rows = client
+            .query(query, &[])
+            .await
+            .map_err(|e| ScanError::QueryFailed(e.to_string()))?;
+
+        Ok(rows.iter().map(|row| row.get("schema_name")).collect())
+    }
+
     /// Get PostgreSQL schema information
     async fn get_postgres_schema(&self, config: &ScanConfig) -> Result<SchemaInfo, ScanError> {
         let client = self.connect_postgres(config).await?;
@@ -426,12 +815,27 @@ impl DbScanner {
         table: &str,
         limit: usize,
     ) -> Result<Vec<HashMap<String, Option<String>>>, ScanError> {
-        // Use TABLESAMPLE for large tables, or LIMIT for smaller ones
-        let query = format!(r#"SELECT * FROM "{}"."{}" LIMIT {}"#, schema, table, limit);
-
-        let rows = client.query(&query, &[]).await.map_err(|e| {
-            ScanError::QueryFailed(format!("Failed to sample {}.{}: {}", schema, table, e))
-        })?;
+        // Use TABLESAMPLE on versions that support it (keeps sampling cheap on
+        // large tables), falling back to a plain LIMIT when the sampled pages
+        // come back empty (small tables) or on older versions.
+        let plain_query = format!(r#"SELECT * FROM "{}"."{}" LIMIT {}"#, schema, table, limit);
+
+        let rows = if self.quirks().supports_tablesample {
+            let sampled_query = format!(
+                r#"SELECT * FROM "{}"."{}" TABLESAMPLE SYSTEM (5) LIMIT {}"#,
+                schema, table, limit
+            );
+            match client.query(&sampled_query, &[]).await {
+                Ok(rows) if !rows.is_empty() => rows,
+                _ => client.query(&plain_query, &[]).await.map_err(|e| {
+                    ScanError::QueryFailed(format!("Failed to sample {}.{}: {}", schema, table, e))
+                })?,
+            }
+        } else {
+            client.query(&plain_query, &[]).await.map_err(|e| {
+                ScanError::QueryFailed(format!("Failed to sample {}.{}: {}", schema, table, e))
+            })?
+        };
 
         let result: Vec<HashMap<String, Option<String>>> = rows
             .iter()
@@ -672,6 +1076,138 @@ mod tests {
         assert!(!scanner.is_scannable_type("timestamp"));
     }
 
+    fn finding(table: &str, column: &str, pii_type: &str, matches: usize, rows: usize) -> PiiFinding {
+        PiiFinding {
+            table: table.to_string(),
+            column: column.to_string(),
+            pii_type: pii_type.to_string(),
+            confidence: matches as f64 / rows as f64,
+            sample: None,
+            row_count: rows,
+            match_count: matches,
+            data_type: "text".to_string(),
+        }
+    }
+
+    fn schema_result(schema: &str, findings: Vec<PiiFinding>) -> ScanResult {
+        ScanResult {
+            status: "completed".to_string(),
+            tables_scanned: 1,
+            columns_scanned: findings.len() + 2,
+            findings,
+            schema: schema.to_string(),
+            database: "appdb".to_string(),
+            scan_duration_ms: 10,
+        }
+    }
+
+    #[test]
+    fn test_discovery_report_ranks_and_marks_coverage() {
+        let rules: Vec<MaskingRule> = serde_yaml::from_str(
+            r#"
+            - table: users
+              column: email
+              strategy: email
+            - column: "*_ssn"
+              strategy: redact
+            "#,
+        )
+        .unwrap();
+
+        let results = vec![
+            schema_result(
+                "public",
+                vec![
+                    finding("users", "email", "Email", 98, 100),
+                    finding("users", "phone", "Phone", 60, 100),
+                ],
+            ),
+            schema_result(
+                "billing",
+                vec![finding("invoices", "customer_ssn", "Ssn", 100, 100)],
+            ),
+        ];
+
+        let report = DiscoveryReport::assemble(
+            "appdb",
+            vec!["public".to_string(), "billing".to_string()],
+            results,
+            &rules,
+        );
+
+        // Ranked by detection rate, highest first
+        let order: Vec<_> = report.columns.iter().map(|c| c.column.as_str()).collect();
+        assert_eq!(order, ["customer_ssn", "email", "phone"]);
+
+        // The *_ssn glob and the users.email rule cover two of the three
+        assert!(report.columns[0].rule_covered);
+        assert!(report.columns[1].rule_covered);
+        assert!(!report.columns[2].rule_covered);
+        assert_eq!(report.uncovered, 1);
+
+        assert_eq!(report.tables_scanned, 2);
+        assert_eq!(report.columns_scanned, 7);
+        assert!((report.columns[1].detection_rate - 0.98).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_suggested_rules() {
+        let result = schema_result(
+            "public",
+            vec![
+                finding("users", "email", "Email", 98, 100),
+                finding("users", "notes", "Phone", 10, 100),
+                finding("users", "tax_id", "NationalId(De)", 90, 100),
+                finding("users", "ssn", "Ssn", 95, 100),
+            ],
+        );
+        let existing: Vec<MaskingRule> =
+            serde_yaml::from_str("- table: users\n  column: ssn\n  strategy: ssn\n").unwrap();
+
+        // Below-threshold and already-covered columns are skipped
+        let rules = result.suggested_rules(0.5, &existing);
+        let summary: Vec<_> = rules
+            .iter()
+            .map(|r| (r.table.as_deref().unwrap(), r.column.as_str()))
+            .collect();
+        assert_eq!(summary, [("users", "email"), ("users", "tax_id")]);
+
+        assert_eq!(rules[0].strategy, StrategyChain::from(Strategy::Email));
+        // No fake generator for foreign national IDs: fall back to redact
+        assert_eq!(rules[1].strategy, StrategyChain::from(Strategy::Redact));
+
+        // Suggestions serialize to minimal rules YAML, defaults omitted
+        let yaml = serde_yaml::to_string(&rules).unwrap();
+        assert!(yaml.contains("strategy: email"));
+        assert!(!yaml.contains("case_sensitive"));
+    }
+
+    #[test]
+    fn test_discovery_report_render_table() {
+        let results = vec![schema_result(
+            "public",
+            vec![
+                finding("users", "email", "Email", 98, 100),
+                finding("users", "phone", "Phone", 60, 100),
+            ],
+        )];
+        let report =
+            DiscoveryReport::assemble("appdb", vec!["public".to_string()], results, &[]);
+
+        let text = report.render_table();
+        assert!(text.contains("PII discovery report for appdb (schemas: public)"));
+        assert!(text.contains("2 PII columns found, 2 without a masking rule"));
+        assert!(text.lines().any(|line| line.starts_with("COLUMN")));
+        assert!(text.contains("public.users.email"));
+        assert!(text.contains("98%"));
+        assert!(text.contains("60/100"));
+        assert!(text.contains("UNCOVERED"));
+
+        // An empty report is just the summary, no table
+        let empty = DiscoveryReport::assemble("appdb", vec!["public".to_string()], vec![], &[]);
+        assert!(!empty.render_table().contains("COLUMN"));
+    }
+
     #[test]
     fn test_mask_sample() {
         let scanner = DbScanner::new("localhost".to_string(), 5432, DbProtocol::Postgres);
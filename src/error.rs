@@ -0,0 +1,195 @@
+//! Typed errors for the protocol and interceptor layers.
+//!
+//! The proxy paths return [`ProxyError`] so embedders can distinguish a
+//! malformed frame from a dropped upstream from a failed masking strategy.
+//! `anyhow` remains at the binary boundary; the accept loop converts these
+//! into `anyhow::Error` when logging.
+
+use thiserror::Error;
+
+/// Errors produced while framing or parsing wire-protocol messages
+#[derive(Debug, Error)]
+pub enum ProtocolError {
+    /// The frame structure itself is broken (bad length, missing terminator)
+    #[error("malformed frame{}: {details}", message_type.map(|t| format!(" (message type '{}')", t as char)).unwrap_or_default())]
+    Framing {
+        message_type: Option<u8>,
+        details: String,
+    },
+
+    /// A frame larger than the configured maximum
+    #[error("frame of {size} bytes exceeds maximum of {max} bytes")]
+    Oversize { size: usize, max: usize },
+
+    /// The frame is well-formed but its contents are not a valid message
+    #[error("invalid {message_type} message: {details}")]
+    InvalidMessage {
+        message_type: String,
+        details: String,
+    },
+
+    /// The peer requested a protocol version we do not speak
+    #[error("unsupported protocol version {version}")]
+    UnsupportedVersion { version: u32 },
+}
+
+impl ProtocolError {
+    /// Short label for the `ironveil_protocol_violations_total` metric
+    pub fn violation_kind(&self) -> &'static str {
+        match self {
+            ProtocolError::Framing { .. } => "framing",
+            ProtocolError::Oversize { .. } => "oversize",
+            ProtocolError::InvalidMessage { .. } => "invalid_message",
+            ProtocolError::UnsupportedVersion { .. } => "unsupported_version",
+        }
+    }
+}
+
+/// Errors produced while applying masking strategies to result data
+#[derive(Debug, Error)]
+pub enum MaskingError {
+    /// A rule references a strategy that is not built-in or registered
+    #[error("unknown strategy '{strategy}' bound to column '{column}'")]
+    UnknownStrategy { strategy: String, column: String },
+
+    /// The value in a column could not be parsed for its strategy
+    #[error("failed to process value in column {column_idx}: {details}")]
+    ValueParse { column_idx: usize, details: String },
+
+    /// A plugin-provided strategy returned an error
+    #[error("plugin strategy '{plugin}' failed: {details}")]
+    Plugin { plugin: String, details: String },
+}
+
+/// Top-level error for a proxied connection
+#[derive(Debug, Error)]
+pub enum ProxyError {
+    #[error(transparent)]
+    Protocol(#[from] ProtocolError),
+
+    #[error(transparent)]
+    Masking(#[from] MaskingError),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("failed to connect to upstream {host}:{port}: {details}")]
+    UpstreamConnect {
+        host: String,
+        port: u16,
+        details: String,
+    },
+
+    #[error("upstream closed the connection unexpectedly")]
+    UpstreamClosed,
+}
+
+/// Client-facing behavior for an error class: which SQLSTATE to report and
+/// whether the connection should be closed afterwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorDisposition {
+    pub sqlstate: &'static str,
+    pub close_connection: bool,
+}
+
+impl ProxyError {
+    /// The single table mapping error classes to client-facing behavior.
+    ///
+    /// | class                | SQLSTATE | closes connection |
+    /// |----------------------|----------|-------------------|
+    /// | protocol (any)       | `08P01`  | yes               |
+    /// | masking (any)        | `XX000`  | no                |
+    /// | upstream connect     | `08001`  | yes               |
+    /// | upstream closed / IO | `08006`  | yes               |
+    pub fn disposition(&self) -> ErrorDisposition {
+        match self {
+            // protocol_violation: the stream is unrecoverable once framing is lost
+            ProxyError::Protocol(_) => ErrorDisposition {
+                sqlstate: "08P01",
+                close_connection: true,
+            },
+            // internal_error: the offending row is dropped but the session survives
+            ProxyError::Masking(_) => ErrorDisposition {
+                sqlstate: "XX000",
+                close_connection: false,
+            },
+            // sqlclient_unable_to_establish_sqlconnection
+            ProxyError::UpstreamConnect { .. } => ErrorDisposition {
+                sqlstate: "08001",
+                close_connection: true,
+            },
+            // connection_failure
+            ProxyError::Io(_) | ProxyError::UpstreamClosed => ErrorDisposition {
+                sqlstate: "08006",
+                close_connection: true,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protocol_errors_close_with_protocol_violation() {
+        let err = ProxyError::from(ProtocolError::Framing {
+            message_type: Some(b'D'),
+            details: "missing null terminator".to_string(),
+        });
+        assert!(matches!(err, ProxyError::Protocol(_)));
+        let d = err.disposition();
+        assert_eq!(d.sqlstate, "08P01");
+        assert!(d.close_connection);
+
+        let oversize = ProxyError::from(ProtocolError::Oversize { size: 99, max: 10 });
+        assert_eq!(oversize.disposition(), d);
+    }
+
+    #[test]
+    fn test_masking_errors_keep_connection_open() {
+        let err = ProxyError::from(MaskingError::Plugin {
+            plugin: "redact_v2".to_string(),
+            details: "wasm trap".to_string(),
+        });
+        assert!(matches!(err, ProxyError::Masking(_)));
+        let d = err.disposition();
+        assert_eq!(d.sqlstate, "XX000");
+        assert!(!d.close_connection);
+    }
+
+    #[test]
+    fn test_upstream_connect_maps_to_08001() {
+        let err = ProxyError::UpstreamConnect {
+            host: "db.internal".to_string(),
+            port: 5432,
+            details: "timed out".to_string(),
+        };
+        let d = err.disposition();
+        assert_eq!(d.sqlstate, "08001");
+        assert!(d.close_connection);
+    }
+
+    #[test]
+    fn test_io_and_upstream_closed_map_to_08006() {
+        let io = ProxyError::Io(std::io::Error::other("broken pipe"));
+        assert_eq!(io.disposition().sqlstate, "08006");
+        assert!(io.disposition().close_connection);
+        assert_eq!(ProxyError::UpstreamClosed.disposition().sqlstate, "08006");
+    }
+
+    #[test]
+    fn test_error_messages_carry_context() {
+        let err = ProtocolError::InvalidMessage {
+            message_type: "RowDescription".to_string(),
+            details: "field count mismatch".to_string(),
+        };
+        assert!(err.to_string().contains("RowDescription"));
+
+        let err = MaskingError::ValueParse {
+            column_idx: 3,
+            details: "not valid UTF-8".to_string(),
+        };
+        assert!(err.to_string().contains("column 3"));
+    }
+}
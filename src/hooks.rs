@@ -0,0 +1,817 @@
+//! Connection lifecycle hooks for embedders and extensions.
+//!
+//! Where [`crate::interceptor`] rewrites result rows, hooks observe and gate
+//! the connection itself: accept or refuse clients by address, authorize
+//! sessions at startup, watch statements on their way upstream, and react to
+//! disconnects. Hooks are registered on the
+//! [`ProxyServerBuilder`](crate::proxy::ProxyServerBuilder) and run in
+//! registration order; for the gating callbacks the first rejection wins and
+//! later hooks are not consulted.
+//!
+//! The built-in [`IpAllowlist`], [`UserPolicy`], and [`ConnectionRegistry`]
+//! are themselves ordinary hooks, so anything they do is available to
+//! embedder-provided implementations too.
+
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::sync::RwLock;
+
+use crate::config::{PolicyAction, SourcePolicy};
+use crate::state::DbProtocol;
+
+/// Outcome of a gating hook callback
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    /// Let the connection proceed
+    Allow,
+    /// Refuse the connection. For [`ConnectionHooks::on_startup`] the message
+    /// is sent to the client as a protocol-level error before closing; for
+    /// [`ConnectionHooks::on_accept`] the socket is closed without traffic.
+    Reject { message: String },
+}
+
+impl Decision {
+    /// Shorthand for a rejection carrying the given message
+    pub fn reject(message: impl Into<String>) -> Self {
+        Decision::Reject {
+            message: message.into(),
+        }
+    }
+
+    pub fn is_reject(&self) -> bool {
+        matches!(self, Decision::Reject { .. })
+    }
+}
+
+/// What the proxy knows about a session once the client has introduced
+/// itself (the Postgres startup message or the MySQL handshake response)
+#[derive(Debug, Clone)]
+pub struct SessionInfo {
+    pub connection_id: usize,
+    pub client_addr: SocketAddr,
+    /// Local address of the listener that accepted the connection
+    pub listener_addr: Option<SocketAddr>,
+    pub protocol: DbProtocol,
+    /// Username from the startup/handshake, when the client sent one
+    pub username: Option<String>,
+    /// Database the client asked for, when the client sent one
+    pub database: Option<String>,
+    /// Source policy resolved at connection setup, when a
+    /// `policies_by_source` entry matched the client address
+    pub source_policy: Option<PolicyAction>,
+    /// Named routing upstream this session was dispatched to, when a
+    /// `routing.by_database` entry matched
+    pub route: Option<String>,
+}
+
+/// A statement observed on its way to the upstream
+#[derive(Debug, Clone)]
+pub struct QueryContext {
+    pub connection_id: usize,
+    pub query: String,
+    /// Leading keyword, uppercased (`SELECT`, `INSERT`, ...)
+    pub query_type: String,
+}
+
+/// Summary of a finished connection, handed to [`ConnectionHooks::on_close`]
+#[derive(Debug, Clone)]
+pub struct ConnectionSummary {
+    pub connection_id: usize,
+    pub client_addr: SocketAddr,
+    pub duration: Duration,
+    /// The error that ended the connection, if it did not close cleanly
+    pub error: Option<String>,
+}
+
+/// Lifecycle callbacks for proxied connections.
+///
+/// All methods default to no-ops, so implementations only override what they
+/// care about. Hooks run on the connection task: keep the callbacks cheap or
+/// the connection stalls.
+#[async_trait]
+pub trait ConnectionHooks: Send + Sync + 'static {
+    /// Called as soon as a TCP connection is accepted, before any protocol
+    /// traffic. A rejection closes the socket silently.
+    async fn on_accept(&self, _client_addr: SocketAddr) -> Decision {
+        Decision::Allow
+    }
+
+    /// Called once the client has identified itself. A rejection is reported
+    /// to the client as a protocol error before the connection closes.
+    async fn on_startup(&self, _session: &SessionInfo) -> Decision {
+        Decision::Allow
+    }
+
+    /// Called for every statement forwarded to the upstream
+    async fn on_statement(&self, _query: &QueryContext) {}
+
+    /// Called when the connection ends, cleanly or not
+    async fn on_close(&self, _summary: &ConnectionSummary) {}
+}
+
+// Allow registering a shared hook (e.g. a ConnectionRegistry the embedder
+// keeps a handle to) without a wrapper type.
+#[async_trait]
+impl<T: ConnectionHooks + ?Sized> ConnectionHooks for Arc<T> {
+    async fn on_accept(&self, client_addr: SocketAddr) -> Decision {
+        (**self).on_accept(client_addr).await
+    }
+
+    async fn on_startup(&self, session: &SessionInfo) -> Decision {
+        (**self).on_startup(session).await
+    }
+
+    async fn on_statement(&self, query: &QueryContext) {
+        (**self).on_statement(query).await
+    }
+
+    async fn on_close(&self, summary: &ConnectionSummary) {
+        (**self).on_close(summary).await
+    }
+}
+
+/// An ordered chain of hooks, cloned into every connection task.
+///
+/// Gating callbacks short-circuit on the first rejection; observational
+/// callbacks always run for every hook.
+#[derive(Clone, Default)]
+pub struct HookChain {
+    hooks: Arc<[Arc<dyn ConnectionHooks>]>,
+}
+
+impl HookChain {
+    pub fn new(hooks: Vec<Arc<dyn ConnectionHooks>>) -> Self {
+        Self {
+            hooks: hooks.into(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Runs every hook's `on_accept` in order; first rejection wins
+    pub async fn accept(&self, client_addr: SocketAddr) -> Decision {
+        for hook in self.hooks.iter() {
+            let decision = hook.on_accept(client_addr).await;
+            if decision.is_reject() {
+                return decision;
+            }
+        }
+        Decision::Allow
+    }
+
+    /// Runs every hook's `on_startup` in order; first rejection wins
+    pub async fn startup(&self, session: &SessionInfo) -> Decision {
+        for hook in self.hooks.iter() {
+            let decision = hook.on_startup(session).await;
+            if decision.is_reject() {
+                return decision;
+            }
+        }
+        Decision::Allow
+    }
+
+    /// Runs every hook's `on_statement` in order
+    pub async fn statement(&self, query: &QueryContext) {
+        for hook in self.hooks.iter() {
+            hook.on_statement(query).await;
+        }
+    }
+
+    /// Runs every hook's `on_close` in order
+    pub async fn close(&self, summary: &ConnectionSummary) {
+        for hook in self.hooks.iter() {
+            hook.on_close(summary).await;
+        }
+    }
+}
+
+/// A network in CIDR notation, IPv4 or IPv6, matched against client
+/// addresses.
+///
+/// Address families must match: an IPv4 client never falls inside an IPv6
+/// CIDR or vice versa.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    network: u128,
+    prefix_len: u8,
+    v6: bool,
+}
+
+impl std::str::FromStr for Cidr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = s
+            .split_once('/')
+            .ok_or_else(|| anyhow::anyhow!("expected <address>/<prefix-length>"))?;
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| anyhow::anyhow!("invalid network address '{}'", addr))?;
+        let prefix_len: u8 = prefix
+            .parse()
+            .map_err(|_| anyhow::anyhow!("invalid prefix length '{}'", prefix))?;
+        let (bits, v6) = match addr {
+            IpAddr::V4(_) => (32, false),
+            IpAddr::V6(_) => (128, true),
+        };
+        if u32::from(prefix_len) > bits {
+            anyhow::bail!(
+                "prefix length /{} is too long for the address family",
+                prefix_len
+            );
+        }
+        let mut cidr = Self {
+            network: ip_bits(addr),
+            prefix_len,
+            v6,
+        };
+        cidr.network &= cidr.mask();
+        Ok(cidr)
+    }
+}
+
+impl Cidr {
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        if matches!(addr, IpAddr::V6(_)) != self.v6 {
+            return false;
+        }
+        ip_bits(addr) & self.mask() == self.network
+    }
+
+    fn mask(&self) -> u128 {
+        let bits: u32 = if self.v6 { 128 } else { 32 };
+        if self.prefix_len == 0 {
+            0
+        } else {
+            (!0u128 >> (128 - bits)) & (!0u128 << (bits - u32::from(self.prefix_len)))
+        }
+    }
+}
+
+fn ip_bits(addr: IpAddr) -> u128 {
+    match addr {
+        IpAddr::V4(v4) => u128::from(u32::from(v4)),
+        IpAddr::V6(v6) => u128::from(v6),
+    }
+}
+
+/// Minimal glob matching for policy parameter patterns: `*` matches any
+/// run of characters, `?` exactly one. Matching is case-sensitive.
+pub fn glob_match(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let value: Vec<char> = value.chars().collect();
+    let (mut p, mut v) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while v < value.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == value[v]) {
+            p += 1;
+            v += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            // Tentatively match nothing; retry with one more character on
+            // the next mismatch
+            backtrack = Some((p, v));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            v = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Resolves the effective source policy for a client address and its
+/// startup parameters.
+///
+/// Among the entries whose matchers all hold — CIDR contains the address,
+/// `application_name` and any other parameter globs match the startup
+/// parameters — the highest priority wins; entries tied on priority resolve
+/// to the most restrictive action. A policy naming a parameter the client
+/// did not send never matches. `None` means no entry matched, which leaves
+/// the default behavior (full masking) in place. Entries with CIDRs that
+/// fail to parse are skipped;
+/// [`AppConfig::validate`](crate::config::AppConfig::validate) rejects them
+/// at load time.
+pub fn resolve_source_policy<'a>(
+    policies: &'a [SourcePolicy],
+    addr: IpAddr,
+    params: &[(String, String)],
+) -> Option<&'a SourcePolicy> {
+    let lookup = |name: &str| {
+        params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    };
+    policies
+        .iter()
+        .filter(|p| {
+            let cidr_matches = p
+                .cidr
+                .as_ref()
+                .is_none_or(|cidr| cidr.parse::<Cidr>().is_ok_and(|c| c.contains(addr)));
+            let app_matches = p.application_name.as_ref().is_none_or(|pattern| {
+                lookup("application_name").is_some_and(|value| glob_match(pattern, value))
+            });
+            let params_match = p.parameters.iter().all(|(name, pattern)| {
+                lookup(name).is_some_and(|value| glob_match(pattern, value))
+            });
+            cidr_matches && app_matches && params_match
+        })
+        .max_by_key(|p| (p.priority, p.action))
+}
+
+/// Built-in hook that refuses clients whose IP is not on the allowlist.
+///
+/// Runs at accept time, so rejected clients never see any protocol traffic.
+pub struct IpAllowlist {
+    allowed: HashSet<IpAddr>,
+}
+
+impl IpAllowlist {
+    pub fn new(allowed: impl IntoIterator<Item = IpAddr>) -> Self {
+        Self {
+            allowed: allowed.into_iter().collect(),
+        }
+    }
+}
+
+#[async_trait]
+impl ConnectionHooks for IpAllowlist {
+    async fn on_accept(&self, client_addr: SocketAddr) -> Decision {
+        if self.allowed.contains(&client_addr.ip()) {
+            Decision::Allow
+        } else {
+            Decision::reject(format!("client address {} is not allowed", client_addr.ip()))
+        }
+    }
+}
+
+/// Built-in hook enforcing per-user policies at startup.
+///
+/// A denylist always wins; when an allowlist is set, only listed users (and
+/// clients that did not send a username at all, which the upstream will
+/// reject itself) may proceed.
+#[derive(Default)]
+pub struct UserPolicy {
+    denied: HashSet<String>,
+    allowed: Option<HashSet<String>>,
+}
+
+impl UserPolicy {
+    /// Policy that rejects the given users and allows everyone else
+    pub fn deny_users(users: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            denied: users.into_iter().map(Into::into).collect(),
+            allowed: None,
+        }
+    }
+
+    /// Policy that allows only the given users
+    pub fn allow_only(users: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            denied: HashSet::new(),
+            allowed: Some(users.into_iter().map(Into::into).collect()),
+        }
+    }
+}
+
+#[async_trait]
+impl ConnectionHooks for UserPolicy {
+    async fn on_startup(&self, session: &SessionInfo) -> Decision {
+        let Some(username) = session.username.as_deref() else {
+            return Decision::Allow;
+        };
+        if self.denied.contains(username) {
+            return Decision::reject(format!("user '{}' is denied by policy", username));
+        }
+        if let Some(allowed) = &self.allowed
+            && !allowed.contains(username)
+        {
+            return Decision::reject(format!("user '{}' is not in the allowlist", username));
+        }
+        Decision::Allow
+    }
+}
+
+/// Built-in hook maintaining a registry of live sessions.
+///
+/// Register a shared handle (`Arc<ConnectionRegistry>`) as a hook and keep a
+/// clone to query [`active_sessions`](ConnectionRegistry::active_sessions)
+/// from elsewhere.
+#[derive(Default)]
+pub struct ConnectionRegistry {
+    sessions: RwLock<HashMap<usize, SessionInfo>>,
+}
+
+impl ConnectionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// All sessions that have completed startup and not yet closed
+    pub async fn active_sessions(&self) -> Vec<SessionInfo> {
+        self.sessions.read().await.values().cloned().collect()
+    }
+
+    /// Look up a live session by connection id
+    pub async fn get(&self, connection_id: usize) -> Option<SessionInfo> {
+        self.sessions.read().await.get(&connection_id).cloned()
+    }
+}
+
+#[async_trait]
+impl ConnectionHooks for ConnectionRegistry {
+    async fn on_startup(&self, session: &SessionInfo) -> Decision {
+        self.sessions
+            .write()
+            .await
+            .insert(session.connection_id, session.clone());
+        Decision::Allow
+    }
+
+    async fn on_close(&self, summary: &ConnectionSummary) {
+        self.sessions.write().await.remove(&summary.connection_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn test_session(connection_id: usize, username: &str) -> SessionInfo {
+        SessionInfo {
+            connection_id,
+            client_addr: "127.0.0.1:5000".parse().unwrap(),
+            listener_addr: None,
+            protocol: DbProtocol::Postgres,
+            username: Some(username.to_string()),
+            database: None,
+            source_policy: None,
+            route: None,
+        }
+    }
+
+    /// Records every callback it receives, in order
+    struct RecordingHook {
+        name: &'static str,
+        events: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl ConnectionHooks for RecordingHook {
+        async fn on_accept(&self, _client_addr: SocketAddr) -> Decision {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("{}:accept", self.name));
+            Decision::Allow
+        }
+
+        async fn on_startup(&self, _session: &SessionInfo) -> Decision {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("{}:startup", self.name));
+            Decision::Allow
+        }
+
+        async fn on_statement(&self, query: &QueryContext) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("{}:statement:{}", self.name, query.query_type));
+        }
+
+        async fn on_close(&self, _summary: &ConnectionSummary) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("{}:close", self.name));
+        }
+    }
+
+    /// Rejects a single username at startup
+    struct RejectUser(&'static str);
+
+    #[async_trait]
+    impl ConnectionHooks for RejectUser {
+        async fn on_startup(&self, session: &SessionInfo) -> Decision {
+            if session.username.as_deref() == Some(self.0) {
+                Decision::reject(format!("user '{}' is not welcome here", self.0))
+            } else {
+                Decision::Allow
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hooks_run_in_registration_order() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let chain = HookChain::new(vec![
+            Arc::new(RecordingHook {
+                name: "first",
+                events: events.clone(),
+            }),
+            Arc::new(RecordingHook {
+                name: "second",
+                events: events.clone(),
+            }),
+        ]);
+
+        let addr: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        assert_eq!(chain.accept(addr).await, Decision::Allow);
+        assert_eq!(chain.startup(&test_session(1, "alice")).await, Decision::Allow);
+        chain
+            .statement(&QueryContext {
+                connection_id: 1,
+                query: "SELECT 1".to_string(),
+                query_type: "SELECT".to_string(),
+            })
+            .await;
+        chain
+            .close(&ConnectionSummary {
+                connection_id: 1,
+                client_addr: addr,
+                duration: Duration::from_secs(1),
+                error: None,
+            })
+            .await;
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                "first:accept",
+                "second:accept",
+                "first:startup",
+                "second:startup",
+                "first:statement:SELECT",
+                "second:statement:SELECT",
+                "first:close",
+                "second:close",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_first_rejection_wins_and_skips_later_hooks() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let chain = HookChain::new(vec![
+            Arc::new(RejectUser("mallory")),
+            Arc::new(RecordingHook {
+                name: "after",
+                events: events.clone(),
+            }),
+        ]);
+
+        let decision = chain.startup(&test_session(1, "mallory")).await;
+        assert_eq!(
+            decision,
+            Decision::reject("user 'mallory' is not welcome here")
+        );
+        assert!(
+            events.lock().unwrap().is_empty(),
+            "hooks after the rejecting one must not run"
+        );
+
+        // Other users pass straight through
+        assert_eq!(chain.startup(&test_session(2, "alice")).await, Decision::Allow);
+    }
+
+    #[tokio::test]
+    async fn test_ip_allowlist() {
+        let allowlist = IpAllowlist::new(["127.0.0.1".parse::<IpAddr>().unwrap()]);
+
+        let local: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        assert_eq!(allowlist.on_accept(local).await, Decision::Allow);
+
+        let remote: SocketAddr = "10.0.0.9:5000".parse().unwrap();
+        assert!(allowlist.on_accept(remote).await.is_reject());
+    }
+
+    #[test]
+    fn test_cidr_matching() {
+        let office: Cidr = "10.8.0.0/16".parse().unwrap();
+        assert!(office.contains("10.8.3.4".parse().unwrap()));
+        assert!(!office.contains("10.9.0.1".parse().unwrap()));
+        // Address families never cross-match
+        assert!(!office.contains("::ffff:10.8.3.4".parse().unwrap()));
+
+        let lab: Cidr = "fd00:1::/32".parse().unwrap();
+        assert!(lab.contains("fd00:1::42".parse().unwrap()));
+        assert!(!lab.contains("fd00:2::42".parse().unwrap()));
+
+        let everything: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(everything.contains("203.0.113.9".parse().unwrap()));
+
+        assert!("10.8.0.0".parse::<Cidr>().is_err());
+        assert!("10.8.0.0/33".parse::<Cidr>().is_err());
+        assert!("fd00:1::/129".parse::<Cidr>().is_err());
+        assert!("not-an-address/8".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn test_resolve_source_policy_precedence() {
+        let policies = vec![
+            SourcePolicy {
+                cidr: Some("10.8.0.0/16".to_string()),
+                application_name: None,
+                parameters: Default::default(),
+                action: PolicyAction::Partial,
+                priority: 10,
+                sampling: None,
+            },
+            SourcePolicy {
+                cidr: Some("0.0.0.0/0".to_string()),
+                application_name: None,
+                parameters: Default::default(),
+                action: PolicyAction::Reject,
+                priority: 0,
+                sampling: None,
+            },
+            SourcePolicy {
+                cidr: Some("::/0".to_string()),
+                application_name: None,
+                parameters: Default::default(),
+                action: PolicyAction::Reject,
+                priority: 0,
+                sampling: None,
+            },
+        ];
+
+        // The more specific, higher-priority entry beats the catch-all
+        assert_eq!(
+            resolve_source_policy(&policies, "10.8.3.4".parse().unwrap(), &[]).map(|p| p.action),
+            Some(PolicyAction::Partial)
+        );
+        // Anything else lands on the catch-all, IPv6 included
+        assert_eq!(
+            resolve_source_policy(&policies, "203.0.113.9".parse().unwrap(), &[]).map(|p| p.action),
+            Some(PolicyAction::Reject)
+        );
+        assert_eq!(
+            resolve_source_policy(&policies, "2001:db8::1".parse().unwrap(), &[]).map(|p| p.action),
+            Some(PolicyAction::Reject)
+        );
+        // No match at all leaves the default in place
+        assert!(resolve_source_policy(&[], "10.8.3.4".parse().unwrap(), &[]).is_none());
+
+        // Entries tied on priority resolve to the most restrictive action
+        let tied = vec![
+            SourcePolicy {
+                cidr: Some("10.8.0.0/16".to_string()),
+                application_name: None,
+                parameters: Default::default(),
+                action: PolicyAction::Unmasked,
+                priority: 5,
+                sampling: None,
+            },
+            SourcePolicy {
+                cidr: Some("10.8.0.0/24".to_string()),
+                application_name: None,
+                parameters: Default::default(),
+                action: PolicyAction::Mask,
+                priority: 5,
+                sampling: None,
+            },
+        ];
+        assert_eq!(
+            resolve_source_policy(&tied, "10.8.0.7".parse().unwrap(), &[]).map(|p| p.action),
+            Some(PolicyAction::Mask)
+        );
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("reporting-suite", "reporting-suite"));
+        assert!(glob_match("report*", "reporting-suite"));
+        assert!(glob_match("*suite", "reporting-suite"));
+        assert!(glob_match("*port*", "reporting-suite"));
+        assert!(glob_match("repor?ing-suite", "reporting-suite"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*", "anything"));
+
+        assert!(!glob_match("report", "reporting-suite"));
+        assert!(!glob_match("Report*", "reporting-suite"));
+        assert!(!glob_match("?", ""));
+        assert!(!glob_match("", "x"));
+    }
+
+    #[test]
+    fn test_resolve_source_policy_by_startup_parameters() {
+        fn param(name: &str, value: &str) -> (String, String) {
+            (name.to_string(), value.to_string())
+        }
+
+        let policies = vec![
+            SourcePolicy {
+                cidr: None,
+                application_name: Some("report*".to_string()),
+                parameters: Default::default(),
+                action: PolicyAction::Unmasked,
+                priority: 10,
+                sampling: None,
+            },
+            SourcePolicy {
+                cidr: Some("0.0.0.0/0".to_string()),
+                application_name: None,
+                parameters: Default::default(),
+                action: PolicyAction::Mask,
+                priority: 0,
+                sampling: None,
+            },
+        ];
+        let addr: IpAddr = "10.0.0.1".parse().unwrap();
+
+        // Two clients differing only in application_name land on different
+        // policies
+        assert_eq!(
+            resolve_source_policy(&policies, addr, &[param("application_name", "reporting-suite")])
+                .map(|p| p.action),
+            Some(PolicyAction::Unmasked)
+        );
+        assert_eq!(
+            resolve_source_policy(&policies, addr, &[param("application_name", "webapp")]).map(|p| p.action),
+            Some(PolicyAction::Mask)
+        );
+        // A client that never sent the parameter cannot match the scoped
+        // entry
+        assert_eq!(
+            resolve_source_policy(&policies, addr, &[]).map(|p| p.action),
+            Some(PolicyAction::Mask)
+        );
+
+        // Arbitrary parameters match with globs, and combine with CIDR
+        let custom = vec![SourcePolicy {
+            cidr: Some("10.0.0.0/8".to_string()),
+            application_name: None,
+            parameters: [("options".to_string(), "*read_only*".to_string())]
+                .into_iter()
+                .collect(),
+            action: PolicyAction::Partial,
+            priority: 0,
+            sampling: None,
+        }];
+        assert_eq!(
+            resolve_source_policy(&custom, addr, &[param("options", "-c read_only=on")]).map(|p| p.action),
+            Some(PolicyAction::Partial)
+        );
+        assert!(
+            resolve_source_policy(&custom, addr, &[param("options", "-c work_mem=1MB")]).is_none()
+        );
+        assert!(
+            resolve_source_policy(
+                &custom,
+                "192.168.1.1".parse().unwrap(),
+                &[param("options", "-c read_only=on")]
+            )
+            .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_user_policy_deny_and_allow_only() {
+        let deny = UserPolicy::deny_users(["mallory"]);
+        assert!(deny.on_startup(&test_session(1, "mallory")).await.is_reject());
+        assert_eq!(deny.on_startup(&test_session(2, "alice")).await, Decision::Allow);
+
+        let allow_only = UserPolicy::allow_only(["alice"]);
+        assert_eq!(
+            allow_only.on_startup(&test_session(3, "alice")).await,
+            Decision::Allow
+        );
+        assert!(allow_only.on_startup(&test_session(4, "bob")).await.is_reject());
+    }
+
+    #[tokio::test]
+    async fn test_connection_registry_tracks_sessions() {
+        let registry = Arc::new(ConnectionRegistry::new());
+        let chain = HookChain::new(vec![Arc::new(registry.clone())]);
+
+        chain.startup(&test_session(7, "alice")).await;
+        assert_eq!(registry.active_sessions().await.len(), 1);
+        assert_eq!(
+            registry.get(7).await.and_then(|s| s.username),
+            Some("alice".to_string())
+        );
+
+        chain
+            .close(&ConnectionSummary {
+                connection_id: 7,
+                client_addr: "127.0.0.1:5000".parse().unwrap(),
+                duration: Duration::from_millis(10),
+                error: None,
+            })
+            .await;
+        assert!(registry.active_sessions().await.is_empty());
+    }
+}
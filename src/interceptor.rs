@@ -1,57 +1,798 @@
-use crate::protocol::mysql::{ColumnDefinition, ResultRow};
-use crate::protocol::postgres::{DataRow, RowDescription};
+use crate::audit::AuditLogger;
+use crate::config::{
+    AppConfig, HashEncoding, IpMode, MaskingRule, MissingColumnPolicy, PolicyAction, RuleCondition,
+    Strategy, StrategyChain, VerifyOutputConfig, VerifyOutputMode,
+};
+#[cfg(feature = "mysql")]
+use crate::protocol::mysql::{BinaryCell, BinaryRow, ColumnDefinition, ResultRow};
+#[cfg(feature = "postgres")]
+use crate::protocol::postgres::{CopyOutStatement, DataRow, FieldDescription, RowDescription};
 use crate::scanner::{PiiScanner, PiiType};
-use anyhow::Result;
+use crate::error::MaskingError;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
 use fake::Fake;
 use fake::faker::address::en::CityName;
 use fake::faker::creditcard::en::CreditCardNumber;
-use fake::faker::internet::en::SafeEmail;
+use fake::faker::internet::en::{SafeEmail, Username};
+use fake::faker::name::en::{FirstName, LastName};
 use fake::faker::phone_number::en::PhoneNumber;
-use rand::SeedableRng;
+use hmac::{Hmac, Mac};
+use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha8Rng;
+use sha2::Sha256;
+use std::collections::{BTreeMap, HashMap};
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 
-fn generate_fake_data(strategy: &str, seed: u64) -> String {
+fn generate_fake_data(strategy: &Strategy, seed: u64) -> String {
     let mut rng = ChaCha8Rng::seed_from_u64(seed);
     match strategy {
-        "email" => SafeEmail().fake_with_rng(&mut rng),
-        "phone" => PhoneNumber().fake_with_rng(&mut rng),
-        "address" => CityName().fake_with_rng(&mut rng),
-        "credit_card" => CreditCardNumber().fake_with_rng(&mut rng),
-        "ssn" => format!("XXX-XX-{:04}", (seed % 10000)),
-        "ip" => "0.0.0.0".to_string(),
-        "dob" => "1900-01-01".to_string(),
-        "passport" => "XXXXXXXX".to_string(),
+        Strategy::Email => SafeEmail().fake_with_rng(&mut rng),
+        Strategy::Phone => PhoneNumber().fake_with_rng(&mut rng),
+        Strategy::Address => CityName().fake_with_rng(&mut rng),
+        Strategy::FirstName => FirstName().fake_with_rng(&mut rng),
+        Strategy::LastName => LastName().fake_with_rng(&mut rng),
+        // Both parts drawn from the same seeded generator, so the pair is
+        // deterministic in the value
+        Strategy::FullName => format!(
+            "{} {}",
+            FirstName().fake_with_rng::<String, _>(&mut rng),
+            LastName().fake_with_rng::<String, _>(&mut rng)
+        ),
+        Strategy::CreditCard => CreditCardNumber().fake_with_rng(&mut rng),
+        Strategy::Ssn => format!("XXX-XX-{:04}", (seed % 10000)),
+        // Contexts without the original value in hand (JSON fields, array
+        // elements) cannot truncate it; degrade to the unspecified address
+        Strategy::Ip => "0.0.0.0".to_string(),
+        Strategy::Dob => "1900-01-01".to_string(),
+        Strategy::Passport => "XXXXXXXX".to_string(),
+        // Country drawn from the scanner's length table and the BBAN from
+        // the seeded generator, with check digits computed so the output
+        // still passes downstream mod-97 validators
+        Strategy::Iban => {
+            let (country, len) =
+                crate::scanner::IBAN_LENGTHS[rng.random_range(0..crate::scanner::IBAN_LENGTHS.len())];
+            let bban: String = (0..len - 4)
+                .map(|_| char::from(b'0' + rng.random_range(0..10)))
+                .collect();
+            let rem = crate::scanner::iban_mod97(&format!("{}{}00", bban, country))
+                .expect("digits and uppercase letters only");
+            format!("{}{:02}{}", country, 98 - rem, bban)
+        }
+        // The row paths rewrite `null` cells to wire-level NULLs before
+        // reaching here; contexts that cannot express one (composite
+        // fields, range bounds, previews) degrade to an empty string
+        Strategy::Null => String::new(),
+        // Without its rule's configuration (previews, composite fields)
+        // redact falls back to the default placeholder
+        Strategy::Redact => REDACT_PLACEHOLDER.to_string(),
         _ => "MASKED".to_string(),
     }
 }
 
-/// Convert PiiType to masking strategy string
-fn pii_type_to_strategy(pii_type: PiiType) -> &'static str {
+/// Masking function supplied by the embedder for one [`Strategy::Custom`]
+/// name: original value and cell seed in, masked value out
+pub type CustomStrategyFn = Arc<dyn Fn(&str, u64) -> String + Send + Sync>;
+
+/// Embedder-registered custom strategies, looked up by the name `custom:`
+/// rule stages use.
+///
+/// Registered on [`crate::proxy::ProxyServerBuilder::strategy`] before the
+/// proxy starts; a custom stage whose name is not registered masks to the
+/// static placeholder.
+#[derive(Clone, Default)]
+pub struct StrategyRegistry {
+    strategies: HashMap<String, CustomStrategyFn>,
+}
+
+impl StrategyRegistry {
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        f: impl Fn(&str, u64) -> String + Send + Sync + 'static,
+    ) {
+        self.strategies.insert(name.into(), Arc::new(f));
+    }
+
+    /// Registered names, for [`crate::config::Strategy::validate`]
+    pub fn names(&self) -> Vec<String> {
+        self.strategies.keys().cloned().collect()
+    }
+
+    fn get(&self, name: &str) -> Option<&CustomStrategyFn> {
+        self.strategies.get(name)
+    }
+}
+
+/// Default replacement text for the `redact` strategy, used when a rule
+/// does not set `redact_text` and in contexts without rule configuration
+/// (previews, composite fields)
+const REDACT_PLACEHOLDER: &str = "[REDACTED]";
+
+/// A single-stage `redact` rule resolved for masking: the rule's
+/// replacement text and whether to fit it to the original's length
+#[derive(Debug, Clone)]
+struct RedactSpec {
+    text: String,
+    preserve_length: bool,
+}
+
+impl RedactSpec {
+    /// The spec for a rule whose whole strategy is `redact`, with the
+    /// default placeholder filled in
+    fn from_rule(rule: &MaskingRule) -> Option<Self> {
+        (rule.strategy.as_single() == Some(&Strategy::Redact)).then(|| Self {
+            text: rule
+                .redact_text
+                .clone()
+                .unwrap_or_else(|| REDACT_PLACEHOLDER.to_string()),
+            preserve_length: rule.preserve_length,
+        })
+    }
+
+    /// The replacement for `original`: the text as-is, or cycled and cut
+    /// to the original's character count when preserving length
+    fn apply(&self, original: &str) -> String {
+        if self.preserve_length {
+            let len = original.chars().count();
+            self.text.chars().cycle().take(len).collect()
+        } else {
+            self.text.clone()
+        }
+    }
+}
+
+/// Default half-width of the `date_shift` offset range, in days
+const DEFAULT_DATE_SHIFT_DAYS: u32 = 30;
+
+/// Default half-width of the `numeric_noise` range, in percent
+const DEFAULT_NOISE_PERCENT: f64 = 10.0;
+
+/// A rule's tuning for the value-derived strategies, resolved for masking:
+/// the `date_shift` offset range, the `numeric_noise` percentage, and the
+/// `email` domain options. The sibling-seeding option (`seed_column`)
+/// lives with the row paths instead — it changes where the seed comes
+/// from, not how a stage maps a value.
+#[derive(Debug, Clone)]
+pub(crate) struct StrategyTuning {
+    shift_days: u32,
+    noise_percent: f64,
+    ip_mode: IpMode,
+    preserve_domain: bool,
+    domain_allowlist: Vec<String>,
+    /// Seed fakes from the cell value (default); a rule that opts out gets
+    /// a fresh random seed per occurrence instead
+    deterministic: bool,
+    /// Shared rather than cloned per row: the map can carry many chains
+    json_paths: Option<Arc<BTreeMap<String, StrategyChain>>>,
+    /// The `regex_replace` rule's compiled pattern and replacement text
+    regex_replace: Option<Arc<(regex::Regex, String)>>,
+    /// The `wasm` rule's compiled module and per-call fuel budget
+    #[cfg(feature = "wasm-plugins")]
+    wasm: Option<Arc<(crate::wasm::WasmStrategy, u64)>>,
+}
+
+impl Default for StrategyTuning {
+    fn default() -> Self {
+        Self {
+            shift_days: DEFAULT_DATE_SHIFT_DAYS,
+            noise_percent: DEFAULT_NOISE_PERCENT,
+            ip_mode: IpMode::default(),
+            preserve_domain: false,
+            domain_allowlist: Vec::new(),
+            deterministic: true,
+            json_paths: None,
+            regex_replace: None,
+            #[cfg(feature = "wasm-plugins")]
+            wasm: None,
+        }
+    }
+}
+
+impl StrategyTuning {
+    /// Feeds every option that changes a stage's output into `hasher`, so
+    /// [`MaskMemo`] entries from two same-chain rules with different
+    /// tuning never collide (e.g. one `email` rule preserving domains and
+    /// one masking them). The `wasm` options are absent because chains
+    /// with a wasm stage bypass the memo entirely.
+    fn hash_options(&self, hasher: &mut DefaultHasher) {
+        self.shift_days.hash(hasher);
+        self.noise_percent.to_bits().hash(hasher);
+        self.ip_mode.hash(hasher);
+        self.preserve_domain.hash(hasher);
+        self.domain_allowlist.hash(hasher);
+        self.deterministic.hash(hasher);
+        for (path, chain) in self.json_paths.iter().flat_map(|paths| paths.iter()) {
+            path.hash(hasher);
+            for stage in chain.stages() {
+                stage.as_str().hash(hasher);
+            }
+        }
+        if let Some(replace) = &self.regex_replace {
+            replace.0.as_str().hash(hasher);
+            replace.1.hash(hasher);
+        }
+    }
+
+    pub(crate) fn from_rule(rule: &MaskingRule) -> Self {
+        Self {
+            shift_days: rule.date_shift_days.unwrap_or(DEFAULT_DATE_SHIFT_DAYS),
+            noise_percent: rule.noise_percent.unwrap_or(DEFAULT_NOISE_PERCENT),
+            ip_mode: rule.ip_mode.unwrap_or_default(),
+            preserve_domain: rule.preserve_domain,
+            domain_allowlist: rule.domain_allowlist.clone(),
+            deterministic: rule.deterministic,
+            json_paths: rule.json_paths.as_ref().map(|paths| Arc::new(paths.clone())),
+            regex_replace: rule.value_re().map(|re| {
+                Arc::new((re.clone(), rule.replacement.clone().unwrap_or_default()))
+            }),
+            // Cached since validation; a miss here means the rule was
+            // built by hand around a module that does not load
+            #[cfg(feature = "wasm-plugins")]
+            wasm: rule.wasm_module.as_deref().and_then(|path| {
+                crate::wasm::load_module(path).ok().map(|module| {
+                    Arc::new((
+                        module,
+                        rule.wasm_fuel.unwrap_or(crate::wasm::DEFAULT_WASM_FUEL),
+                    ))
+                })
+            }),
+        }
+    }
+}
+
+/// The `hash` strategy resolved for masking: HMAC-SHA256 with the config's
+/// key, encoded and truncated per config. The digest depends only on the
+/// key and the value, so the same input maps to the same token across
+/// connections and proxy restarts.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HashSpec {
+    key: Option<String>,
+    encoding: HashEncoding,
+    truncate: Option<usize>,
+}
+
+impl HashSpec {
+    pub(crate) fn from_config(config: &AppConfig) -> Self {
+        match &config.hashing {
+            Some(hashing) => Self {
+                key: hashing.resolved_key(),
+                encoding: hashing.encoding,
+                truncate: hashing.truncate,
+            },
+            None => Self::default(),
+        }
+    }
+
+    /// The token for `original`. Validation refuses configs that use the
+    /// strategy without a key; contexts that skip validation (previews)
+    /// degrade to the static placeholder rather than minting unjoinable
+    /// tokens from a random key.
+    fn apply(&self, original: &str) -> String {
+        let Some(key) = &self.key else {
+            return "MASKED".to_string();
+        };
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(original.as_bytes());
+        let digest = mac.finalize().into_bytes();
+        let mut encoded = match self.encoding {
+            HashEncoding::Hex => digest.iter().map(|b| format!("{:02x}", b)).collect(),
+            HashEncoding::Base64 => BASE64.encode(digest),
+        };
+        if let Some(limit) = self.truncate {
+            encoded.truncate(limit);
+        }
+        encoded
+    }
+}
+
+/// Process-wide key for deriving per-cell seeds, installed from
+/// `masking.determinism_key` at startup. Absent a configured key, a random
+/// one is generated on first use, so masking stays deterministic within
+/// the process but not across restarts.
+static DETERMINISM_KEY: std::sync::OnceLock<Vec<u8>> = std::sync::OnceLock::new();
+
+/// Installs the configured seed-derivation key, or surfaces the missing-key
+/// warning at startup when there is none. Called once when the proxy
+/// starts; later calls are ignored, since swapping the key mid-flight
+/// would remap every fake already issued to live sessions.
+pub(crate) fn init_determinism_key(key: Option<String>) {
+    match key {
+        Some(key) => {
+            let _ = DETERMINISM_KEY.set(key.into_bytes());
+        }
+        None => {
+            determinism_key();
+        }
+    }
+}
+
+fn determinism_key() -> &'static [u8] {
+    DETERMINISM_KEY.get_or_init(|| {
+        // Unit tests pin the key instead: golden fixtures and cross-test
+        // expectations need run-to-run stability
+        #[cfg(test)]
+        {
+            b"ironveil-test-determinism-key".to_vec()
+        }
+        #[cfg(not(test))]
+        {
+            tracing::warn!(
+                "No masking.determinism_key or IRONVEIL_DETERMINISM_KEY is set; \
+                 using a random per-process key, so masked values will not be \
+                 stable across restarts"
+            );
+            let key: [u8; 32] = rand::rng().random();
+            key.to_vec()
+        }
+    })
+}
+
+/// The deterministic seed for a value: the first eight bytes of
+/// HMAC-SHA256(value, determinism key). Keyed so small value spaces (phone
+/// numbers, dates) cannot be reversed by replaying the derivation, and
+/// stable across Rust releases, which `DefaultHasher` is not.
+pub(crate) fn value_seed(value: &[u8]) -> u64 {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(determinism_key())
+        .expect("HMAC accepts keys of any length");
+    mac.update(value);
+    let digest = mac.finalize().into_bytes();
+    u64::from_be_bytes(digest[..8].try_into().expect("digest is 32 bytes"))
+}
+
+/// The seed for a rule-bound cell: value-derived for deterministic rules
+/// (the default), drawn fresh from the thread RNG when the rule opts out,
+/// so repeated values mask to unrelated fakes
+fn cell_seed(tuning: &StrategyTuning, value: &[u8]) -> u64 {
+    if tuning.deterministic {
+        value_seed(value)
+    } else {
+        rand::rng().random()
+    }
+}
+
+/// Generate the masked replacement for a value. Most strategies synthesize
+/// output from the seed alone; `numeric_noise`, `date_shift`,
+/// `format_preserving`, `ip`, and `email` (when its domain options are
+/// set) derive it from the original so the result stays valid for the
+/// column's type.
+fn mask_value(
+    custom: &StrategyRegistry,
+    hashing: &HashSpec,
+    tuning: &StrategyTuning,
+    strategy: &Strategy,
+    original: &str,
+    seed: u64,
+) -> String {
+    match strategy {
+        Strategy::Email => mask_email(original, seed, tuning),
+        Strategy::NumericNoise => numeric_noise(original, seed, tuning.noise_percent),
+        Strategy::DateShift => date_shift(original, seed, tuning.shift_days),
+        Strategy::FormatPreserving => format_preserving(original, seed),
+        Strategy::Ip => mask_ip(original, seed, tuning.ip_mode),
+        // Contexts without the rule's pattern in hand (previews, heuristic
+        // matches) have nothing to apply and degrade to the placeholder,
+        // like an unregistered custom strategy
+        Strategy::RegexReplace => match &tuning.regex_replace {
+            Some(spec) => spec.0.replace_all(original, spec.1.as_str()).into_owned(),
+            None => "MASKED".to_string(),
+        },
+        Strategy::Hash => hashing.apply(original),
+        // A trapped, fuel-exhausted, or misbehaving module must not leak
+        // the original value; degrade to the redact placeholder and log
+        #[cfg(feature = "wasm-plugins")]
+        Strategy::Wasm => match &tuning.wasm {
+            Some(spec) => match spec.0.apply(original, seed, spec.1) {
+                Ok(masked) => masked,
+                Err(e) => {
+                    tracing::warn!(
+                        error = %e,
+                        "WASM strategy failed; substituting the redact placeholder"
+                    );
+                    REDACT_PLACEHOLDER.to_string()
+                }
+            },
+            None => REDACT_PLACEHOLDER.to_string(),
+        },
+        // Validation refuses wasm rules in builds without the runtime;
+        // contexts that skip it still must not leak the value
+        #[cfg(not(feature = "wasm-plugins"))]
+        Strategy::Wasm => REDACT_PLACEHOLDER.to_string(),
+        Strategy::Custom(name) => match custom.get(name) {
+            Some(f) => f(original, seed),
+            None => "MASKED".to_string(),
+        },
+        _ => generate_fake_data(strategy, seed),
+    }
+}
+
+/// Apply every stage of a chain in order, each output feeding the next.
+/// Stages are total functions (unparseable input degrades, it never fails
+/// mid-chain), and the seed is shared so the composite stays deterministic
+/// in the original value.
+fn mask_chain(
+    custom: &StrategyRegistry,
+    hashing: &HashSpec,
+    tuning: &StrategyTuning,
+    chain: &StrategyChain,
+    original: &str,
+    seed: u64,
+) -> String {
+    chain
+        .stages()
+        .iter()
+        .fold(original.to_string(), |value, stage| {
+            mask_value(custom, hashing, tuning, stage, &value, seed)
+        })
+}
+
+/// Like [`mask_chain`] but collects each stage's output in order (the last
+/// element is the final masked value), so the rule-test API endpoint can show
+/// what every stage of a chain did.
+#[cfg(feature = "api")]
+pub(crate) fn mask_chain_stages(
+    custom: &StrategyRegistry,
+    hashing: &HashSpec,
+    tuning: &StrategyTuning,
+    chain: &StrategyChain,
+    original: &str,
+    seed: u64,
+) -> Vec<String> {
+    let mut outputs = Vec::with_capacity(chain.stages().len());
+    let mut value = original.to_string();
+    for stage in chain.stages() {
+        value = mask_value(custom, hashing, tuning, stage, &value, seed);
+        outputs.push(value.clone());
+    }
+    outputs
+}
+
+/// Deterministic multiplicative noise of up to ±N% (the rule's
+/// `noise_percent`, default ±10) on a numeric value. The output still
+/// parses as the column's declared numeric type: integers stay integers,
+/// decimals keep their number of decimal places, scientific notation stays
+/// scientific, and unparseable input degrades to "0".
+fn numeric_noise(original: &str, seed: u64, percent: f64) -> String {
+    let spread = percent / 100.0;
+    let factor = 1.0 - spread + (seed % 2001) as f64 / 2000.0 * 2.0 * spread;
+    let trimmed = original.trim();
+    if let Ok(n) = trimmed.parse::<i64>() {
+        ((n as f64 * factor).round() as i64).to_string()
+    } else if trimmed.contains(['e', 'E'])
+        && let Ok(f) = trimmed.parse::<f64>()
+    {
+        format!("{:e}", f * factor)
+    } else if let Ok(f) = trimmed.parse::<f64>() {
+        let decimals = trimmed.split('.').nth(1).map_or(0, str::len);
+        format!("{:.*}", decimals, f * factor)
+    } else {
+        "0".to_string()
+    }
+}
+
+/// Shift the date part of a date or timestamp by a deterministic offset in
+/// [-N, +N] days (the rule's `date_shift_days`, default ±30), leaving any
+/// time-of-day and timezone suffix intact. Input that does not parse as a
+/// date passes through unchanged with a debug log rather than being
+/// corrupted — the output verifier is the safety net if it was PII after
+/// all.
+fn date_shift(original: &str, seed: u64, max_days: u32) -> String {
+    let span = u64::from(max_days) * 2 + 1;
+    let offset = chrono::Duration::days((seed % span) as i64 - i64::from(max_days));
+    let trimmed = original.trim();
+    if let (Some(head), Some(tail)) = (trimmed.get(..10), trimmed.get(10..))
+        && let Ok(date) = chrono::NaiveDate::parse_from_str(head, "%Y-%m-%d")
+    {
+        format!("{}{}", date + offset, tail)
+    } else {
+        tracing::debug!("date_shift input did not parse as a date; leaving it unchanged");
+        original.to_string()
+    }
+}
+
+/// The `email` strategy's value-aware path. With no options set this is
+/// the plain synthetic address; `preserve_domain` fakes only the local
+/// part and keeps everything after the `@`, and addresses on a
+/// `domain_allowlist` domain pass through entirely unmasked. Values
+/// without an `@` (or with nothing after it) always get a fully fake
+/// address, so malformed input cannot leak through the domain options.
+fn mask_email(original: &str, seed: u64, tuning: &StrategyTuning) -> String {
+    if let Some((_, domain)) = original.rsplit_once('@')
+        && !domain.is_empty()
+    {
+        let allowed = tuning.domain_allowlist.iter().any(|entry| {
+            entry.trim_start_matches("*@").eq_ignore_ascii_case(domain)
+        });
+        if allowed {
+            return original.to_string();
+        }
+        if tuning.preserve_domain {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            let local: String = Username().fake_with_rng(&mut rng);
+            return format!("{}@{}", local, domain);
+        }
+    }
+    generate_fake_data(&Strategy::Email, seed)
+}
+
+/// Replace every ASCII digit with a digit and every ASCII letter with a
+/// letter of the same case, leaving punctuation, spacing, and non-ASCII
+/// characters where they are, so `123-45-6789` still scans as an SSN and a
+/// formatted phone number keeps its punctuation and country code. The seed
+/// drives the same ChaCha8 generator as the synthetic strategies, so the
+/// replacement is deterministic in the original value. Values with nothing
+/// to replace (empty, all punctuation) pass through unchanged.
+fn format_preserving(original: &str, seed: u64) -> String {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    original
+        .chars()
+        .map(|c| match c {
+            '0'..='9' => char::from(b'0' + rng.random_range(0..10)),
+            'a'..='z' => char::from(b'a' + rng.random_range(0..26)),
+            'A'..='Z' => char::from(b'A' + rng.random_range(0..26)),
+            other => other,
+        })
+        .collect()
+}
+
+/// Anonymize an IP address per the rule's `ip_mode`, detecting v4 vs v6
+/// from the text. A numeric `/len` CIDR suffix is preserved as-is, and
+/// input that does not parse as an address passes through untouched —
+/// `inet` columns never see a value they cannot parse.
+fn mask_ip(original: &str, seed: u64, mode: IpMode) -> String {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    let trimmed = original.trim();
+    let (addr_part, prefix_len) = match trimmed.split_once('/') {
+        Some((addr, len)) if !len.is_empty() && len.bytes().all(|b| b.is_ascii_digit()) => {
+            (addr, Some(len))
+        }
+        Some(_) => return original.to_string(),
+        None => (trimmed, None),
+    };
+    let masked = if let Ok(v4) = addr_part.parse::<Ipv4Addr>() {
+        match mode {
+            IpMode::Truncate => {
+                let [a, b, c, _] = v4.octets();
+                Ipv4Addr::new(a, b, c, 0).to_string()
+            }
+            // 198.18.0.0/15 holds 2^17 addresses; the seed picks one
+            IpMode::Pseudonymize => {
+                let base = u32::from(Ipv4Addr::new(198, 18, 0, 0));
+                Ipv4Addr::from(base | (seed as u32 & 0x0001_ffff)).to_string()
+            }
+        }
+    } else if let Ok(v6) = addr_part.parse::<Ipv6Addr>() {
+        match mode {
+            // Keep the top 48 bits, zero the last 80 (nginx's anonymizer)
+            IpMode::Truncate => Ipv6Addr::from(u128::from(v6) & !((1u128 << 80) - 1)).to_string(),
+            IpMode::Pseudonymize => {
+                let base = u128::from(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0));
+                Ipv6Addr::from(base | u128::from(seed)).to_string()
+            }
+        }
+    } else {
+        return original.to_string();
+    };
+    match prefix_len {
+        Some(len) => format!("{}/{}", masked, len),
+        None => masked,
+    }
+}
+
+/// Bounded per-connection memo of masked values, keyed by strategy chain,
+/// the rule's tuning, and original-value hash. Result sets repeat values
+/// constantly (denormalized columns across join rows, enum-like fields) and
+/// every builtin strategy is deterministic in (strategy, tuning, value), so
+/// replaying a stored result is indistinguishable from recomputing it. When
+/// full the memo is cleared wholesale, the same tradeoff the statement
+/// cache in [`crate::sql_resolver`] makes. Chains with a custom or wasm
+/// stage bypass it: a plugin's determinism is unknown.
+struct MaskMemo {
+    entries: HashMap<(u64, u64), String>,
+    capacity: usize,
+    /// Ruleset generation the entries were computed under
+    generation: u64,
+}
+
+impl MaskMemo {
+    fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            capacity: 0,
+            generation: 0,
+        }
+    }
+
+    /// Adopts the configured capacity and current ruleset generation,
+    /// dropping every entry when either changed since the last sync
+    fn sync(&mut self, capacity: usize, generation: u64) {
+        if self.capacity != capacity || self.generation != generation {
+            self.entries.clear();
+            self.capacity = capacity;
+            self.generation = generation;
+        }
+    }
+
+    /// Returns the memoized masked value for `(chain, tuning, seed)`,
+    /// computing and storing it on a miss. The second element reports
+    /// whether the lookup hit. A capacity of zero disables memoization
+    /// entirely.
+    fn get_or_compute(
+        &mut self,
+        chain: &StrategyChain,
+        tuning: &StrategyTuning,
+        seed: u64,
+        compute: impl FnOnce() -> String,
+    ) -> (String, bool) {
+        // A wasm module's determinism is just as unknown as a registry
+        // plugin's, so both kinds of stage skip the memo
+        if self.capacity == 0
+            || chain
+                .stages()
+                .iter()
+                .any(|stage| matches!(stage, Strategy::Custom(_) | Strategy::Wasm))
+        {
+            return (compute(), false);
+        }
+        let mut hasher = DefaultHasher::new();
+        for stage in chain.stages() {
+            stage.as_str().hash(&mut hasher);
+        }
+        tuning.hash_options(&mut hasher);
+        let key = (hasher.finish(), seed);
+        if let Some(stored) = self.entries.get(&key) {
+            return (stored.clone(), true);
+        }
+        if self.entries.len() >= self.capacity {
+            self.entries.clear();
+        }
+        let value = compute();
+        self.entries.insert(key, value.clone());
+        (value, false)
+    }
+}
+
+/// A rule's `when` clause compiled for the current result set: the regex
+/// built once, detector names resolved, and a sibling reference turned into
+/// a column index (or into the rule's missing-column policy when the query
+/// did not select the sibling).
+#[derive(Debug, Clone)]
+enum BoundCondition {
+    /// Test the cell's own value: a regex match or any listed detector
+    /// firing satisfies the condition
+    OwnValue {
+        regex: Option<regex::Regex>,
+        detectors: Vec<PiiType>,
+    },
+    /// Compare the sibling column at this index against a literal
+    Sibling { column_idx: usize, equals: String },
+    /// The referenced sibling is not in the result set; the rule's
+    /// missing-column policy decides
+    MissingSibling { mask: bool },
+}
+
+/// Compiles a condition against the current result set, locating a sibling
+/// reference through `sibling_lookup` (column name to output index).
+fn bind_condition(
+    cond: &RuleCondition,
+    sibling_lookup: impl Fn(&str) -> Option<usize>,
+) -> BoundCondition {
+    if let (Some(column), Some(equals)) = (&cond.column, &cond.equals) {
+        match sibling_lookup(column) {
+            Some(column_idx) => BoundCondition::Sibling {
+                column_idx,
+                equals: equals.clone(),
+            },
+            None => BoundCondition::MissingSibling {
+                mask: cond.on_missing_column == MissingColumnPolicy::Mask,
+            },
+        }
+    } else {
+        BoundCondition::OwnValue {
+            // Validation compiled the pattern once already, so an invalid
+            // one cannot reach a running config
+            regex: cond
+                .self_matches
+                .as_deref()
+                .and_then(|p| regex::Regex::new(p).ok()),
+            detectors: cond
+                .or_detected
+                .iter()
+                .filter_map(|name| PiiType::parse(name))
+                .collect(),
+        }
+    }
+}
+
+impl BoundCondition {
+    /// Whether the condition holds for the cell `own` in `row`. Callers
+    /// evaluate against the row as it arrived from upstream, before any cell
+    /// is masked, so a sibling test is unaffected by the sibling's own
+    /// masking.
+    fn holds<B: AsRef<[u8]>>(&self, own: &[u8], row: &[Option<B>], scanner: &PiiScanner) -> bool {
+        match self {
+            BoundCondition::OwnValue { regex, detectors } => {
+                let Ok(s) = std::str::from_utf8(own) else {
+                    return false;
+                };
+                regex.as_ref().is_some_and(|r| r.is_match(s))
+                    || (!detectors.is_empty()
+                        && scanner.scan(s).is_some_and(|t| detectors.contains(&t)))
+            }
+            BoundCondition::Sibling { column_idx, equals } => row
+                .get(*column_idx)
+                .and_then(|v| v.as_ref())
+                .is_some_and(|v| v.as_ref() == equals.as_bytes()),
+            BoundCondition::MissingSibling { mask } => *mask,
+        }
+    }
+}
+
+/// Convert PiiType to masking strategy: the operator's `masking.defaults`
+/// override when one names the detector, else the built-in mapping
+fn pii_type_to_strategy(pii_type: PiiType, scanner: &PiiScanner) -> Strategy {
+    if let Some(strategy) = scanner.default_strategy(&pii_type) {
+        return strategy.clone();
+    }
     match pii_type {
-        PiiType::Email => "email",
-        PiiType::CreditCard => "credit_card",
-        PiiType::Ssn => "ssn",
-        PiiType::Phone => "phone",
-        PiiType::IpAddress => "ip",
-        PiiType::DateOfBirth => "dob",
-        PiiType::Passport => "passport",
+        PiiType::Email => Strategy::Email,
+        PiiType::CreditCard => Strategy::CreditCard,
+        PiiType::Ssn => Strategy::Ssn,
+        PiiType::Phone => Strategy::Phone,
+        PiiType::IpAddress => Strategy::Ip,
+        PiiType::DateOfBirth => Strategy::Dob,
+        PiiType::Passport => Strategy::Passport,
+        PiiType::Iban => Strategy::Iban,
+        // No fake generators for the foreign ID formats yet, so these
+        // redact rather than substituting a US-shaped lookalike
+        PiiType::NationalId(_) => Strategy::Redact,
+        // A custom pattern masks with its configured strategy, falling
+        // back to redaction: the operator named the shape but not how to
+        // fake it
+        PiiType::Custom(name) => scanner
+            .custom_strategy(&name)
+            .cloned()
+            .unwrap_or(Strategy::Redact),
+    }
+}
+
+/// Rewrites only the spans the scanner flagged inside a free-text value,
+/// each replaced by a fake from its own detector and seeded from the span
+/// bytes, so the prose around them survives and repeated values map to the
+/// same fake. Returns the rewritten text and the detected types, `None`
+/// when nothing matched.
+fn mask_pii_substrings(text: &str, scanner: &PiiScanner) -> Option<(String, Vec<PiiType>)> {
+    let matches = scanner.scan_all(text);
+    if matches.is_empty() {
+        return None;
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut types = Vec::with_capacity(matches.len());
+    let mut last = 0;
+    for (pii_type, range) in matches {
+        out.push_str(&text[last..range.start]);
+        let strategy = pii_type_to_strategy(pii_type.clone(), scanner);
+        let seed = value_seed(text[range.clone()].as_bytes());
+        out.push_str(&generate_fake_data(&strategy, seed));
+        last = range.end;
+        types.push(pii_type);
     }
+    out.push_str(&text[last..]);
+    Some((out, types))
 }
 
 fn mask_json_recursively(val: &mut serde_json::Value, scanner: &PiiScanner) {
     match val {
         serde_json::Value::String(s) => {
             if let Some(pii_type) = scanner.scan(s) {
-                let strategy = pii_type_to_strategy(pii_type);
+                let strategy = pii_type_to_strategy(pii_type, scanner);
 
-                // Deterministic seed based on the string value
-                let mut hasher = DefaultHasher::new();
-                s.hash(&mut hasher);
-                let seed = hasher.finish();
+                let seed = value_seed(s.as_bytes());
 
-                *s = generate_fake_data(strategy, seed);
+                *s = generate_fake_data(&strategy, seed);
             }
         }
         serde_json::Value::Array(arr) => {
@@ -68,6 +809,105 @@ fn mask_json_recursively(val: &mut serde_json::Value, scanner: &PiiScanner) {
     }
 }
 
+/// Mask only the configured JSON pointer paths of a parsed document, each
+/// with its own chain. A `*` segment fans out over every array element or
+/// object member; an index or member segment that the document does not
+/// have resolves to nothing, matching JSON pointer semantics. Pointer
+/// escapes (`~1` for `/`, `~0` for `~`) are honoured in member names.
+fn mask_json_paths(
+    root: &mut serde_json::Value,
+    paths: &BTreeMap<String, StrategyChain>,
+    custom: &StrategyRegistry,
+    hashing: &HashSpec,
+    tuning: &StrategyTuning,
+) {
+    for (path, chain) in paths {
+        let segments: Vec<String> = path
+            .split('/')
+            .skip(1)
+            .map(|s| s.replace("~1", "/").replace("~0", "~"))
+            .collect();
+        mask_json_path(root, &segments, chain, custom, hashing, tuning);
+    }
+}
+
+fn mask_json_path(
+    node: &mut serde_json::Value,
+    segments: &[String],
+    chain: &StrategyChain,
+    custom: &StrategyRegistry,
+    hashing: &HashSpec,
+    tuning: &StrategyTuning,
+) {
+    let Some((head, rest)) = segments.split_first() else {
+        mask_json_node(node, chain, custom, hashing, tuning);
+        return;
+    };
+    match node {
+        serde_json::Value::Object(map) => {
+            if head == "*" {
+                for (_, v) in map {
+                    mask_json_path(v, rest, chain, custom, hashing, tuning);
+                }
+            } else if let Some(v) = map.get_mut(head.as_str()) {
+                mask_json_path(v, rest, chain, custom, hashing, tuning);
+            }
+        }
+        serde_json::Value::Array(arr) => {
+            if head == "*" {
+                for v in arr {
+                    mask_json_path(v, rest, chain, custom, hashing, tuning);
+                }
+            } else if let Ok(idx) = head.parse::<usize>()
+                && let Some(v) = arr.get_mut(idx)
+            {
+                mask_json_path(v, rest, chain, custom, hashing, tuning);
+            }
+        }
+        // A scalar mid-path: the pointer does not resolve
+        _ => {}
+    }
+}
+
+/// Mask the node a pointer resolved to. Strings are masked in place;
+/// numbers stay numbers when the chain's output still parses as one (as
+/// `numeric_noise` output does); nulls and booleans carry no PII and pass
+/// through; a container target masks every scalar beneath it.
+fn mask_json_node(
+    node: &mut serde_json::Value,
+    chain: &StrategyChain,
+    custom: &StrategyRegistry,
+    hashing: &HashSpec,
+    tuning: &StrategyTuning,
+) {
+    let seed_of = |value: &str| cell_seed(tuning, value.as_bytes());
+    match node {
+        serde_json::Value::String(s) => {
+            *s = mask_chain(custom, hashing, tuning, chain, s, seed_of(s));
+        }
+        serde_json::Value::Number(n) => {
+            let original = n.to_string();
+            let masked = mask_chain(custom, hashing, tuning, chain, &original, seed_of(&original));
+            *node = match masked.parse::<serde_json::Number>() {
+                Ok(number) => serde_json::Value::Number(number),
+                Err(_) => serde_json::Value::String(masked),
+            };
+        }
+        serde_json::Value::Null | serde_json::Value::Bool(_) => {}
+        serde_json::Value::Array(arr) => {
+            for v in arr {
+                mask_json_node(v, chain, custom, hashing, tuning);
+            }
+        }
+        serde_json::Value::Object(map) => {
+            for (_, v) in map {
+                mask_json_node(v, chain, custom, hashing, tuning);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "postgres")]
 fn mask_postgres_array(raw: &str, scanner: &PiiScanner) -> Option<String> {
     if !raw.starts_with('{') || !raw.ends_with('}') {
         return None;
@@ -116,13 +956,11 @@ fn mask_postgres_array(raw: &str, scanner: &PiiScanner) -> Option<String> {
         let clean_val = val.replace("\\\"", "\"").replace("\\\\", "\\");
 
         if let Some(pii_type) = scanner.scan(&clean_val) {
-            let strategy = pii_type_to_strategy(pii_type);
+            let strategy = pii_type_to_strategy(pii_type, scanner);
 
-            let mut hasher = DefaultHasher::new();
-            clean_val.hash(&mut hasher);
-            let seed = hasher.finish();
+            let seed = value_seed(clean_val.as_bytes());
 
-            let fake = generate_fake_data(strategy, seed);
+            let fake = generate_fake_data(&strategy, seed);
             // Always quote masked values to be safe
             new_elements.push(format!("\"{}\"", fake));
             changed = true;
@@ -138,12 +976,231 @@ fn mask_postgres_array(raw: &str, scanner: &PiiScanner) -> Option<String> {
     }
 }
 
+/// Splits the interior of a composite row or range literal into fields,
+/// respecting double-quoting (with `""` and backslash escapes). Fields come
+/// back unescaped, `None` standing for an empty unquoted field — NULL in a
+/// composite, an unbounded end in a range. Returns `None` overall when the
+/// text is not well-formed (e.g. an unterminated quote).
+#[cfg(feature = "postgres")]
+fn split_structured_fields(content: &str) -> Option<Vec<Option<String>>> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut in_quotes = false;
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    current.push('"');
+                }
+                '"' => in_quotes = false,
+                '\\' => current.push(chars.next()?),
+                _ => current.push(c),
+            }
+        } else {
+            match c {
+                '"' => {
+                    in_quotes = true;
+                    quoted = true;
+                }
+                '\\' => current.push(chars.next()?),
+                ',' => {
+                    fields.push(
+                        (!current.is_empty() || quoted).then(|| std::mem::take(&mut current)),
+                    );
+                    quoted = false;
+                }
+                _ => current.push(c),
+            }
+        }
+    }
+    if in_quotes {
+        return None;
+    }
+    fields.push((!current.is_empty() || quoted).then_some(current));
+    Some(fields)
+}
+
+/// Quotes a composite or range field value when the literal syntax requires
+/// it, escaping embedded quotes and backslashes
+#[cfg(feature = "postgres")]
+fn quote_structured_field(value: &str) -> String {
+    if value.is_empty() || value.contains([',', '(', ')', '[', ']', '"', '\\']) {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Masks a composite row literal field by field — per-position strategies
+/// where configured, content heuristics on the rest — and reassembles a
+/// literal the column's type still accepts. NULL fields stay NULL. Returns
+/// `None`, letting the rule's mismatch policy decide, when the value is not
+/// a well-formed composite or nests another composite.
+#[cfg(feature = "postgres")]
+fn mask_composite_literal(
+    raw: &str,
+    fields: &[Option<StrategyChain>],
+    scanner: &PiiScanner,
+    custom: &StrategyRegistry,
+    hashing: &HashSpec,
+    tuning: &StrategyTuning,
+    seed: u64,
+) -> Option<String> {
+    let content = raw.trim().strip_prefix('(')?.strip_suffix(')')?;
+    let parsed = split_structured_fields(content)?;
+    let mut out = Vec::with_capacity(parsed.len());
+    for (idx, field) in parsed.into_iter().enumerate() {
+        let Some(value) = field else {
+            out.push(String::new());
+            continue;
+        };
+        if value.starts_with('(') && value.ends_with(')') {
+            return None; // nested composite: not worth guessing at
+        }
+        let masked = match fields.get(idx).and_then(Option::as_ref) {
+            Some(chain) => mask_chain(custom, hashing, tuning, chain, &value, seed),
+            None => match scanner.scan(&value) {
+                Some(pii_type) => {
+                    mask_value(
+                        custom,
+                        hashing,
+                        tuning,
+                        &pii_type_to_strategy(pii_type, scanner),
+                        &value,
+                        value_seed(value.as_bytes()),
+                    )
+                }
+                None => value,
+            },
+        };
+        out.push(quote_structured_field(&masked));
+    }
+    Some(format!("({})", out.join(",")))
+}
+
+/// Masks a range literal by applying the chain to each bound, keeping the
+/// brackets and their inclusivity. The strategies that fit the range class
+/// keep the bounds ordered — `numeric_noise` scales both by the same factor
+/// and `date_shift` moves both by the same offset — and unbounded ends stay
+/// unbounded. Unparseable input degrades to the empty range, the same
+/// total-function contract the scalar strategies follow.
+#[cfg(feature = "postgres")]
+fn mask_range_literal(
+    raw: &str,
+    chain: &StrategyChain,
+    custom: &StrategyRegistry,
+    hashing: &HashSpec,
+    tuning: &StrategyTuning,
+    seed: u64,
+) -> String {
+    let trimmed = raw.trim();
+    if trimmed.eq_ignore_ascii_case("empty") {
+        return "empty".to_string();
+    }
+    let (Some(open @ ('[' | '(')), Some(close @ (']' | ')'))) =
+        (trimmed.chars().next(), trimmed.chars().last())
+    else {
+        return "empty".to_string();
+    };
+    let bounds = split_structured_fields(&trimmed[1..trimmed.len() - 1]);
+    let Some([lower, upper]) = bounds.as_deref() else {
+        return "empty".to_string();
+    };
+    let mask_bound = |bound: &Option<String>| match bound {
+        Some(value) => {
+            quote_structured_field(&mask_chain(custom, hashing, tuning, chain, value, seed))
+        }
+        None => String::new(),
+    };
+    format!("{}{},{}{}", open, mask_bound(lower), mask_bound(upper), close)
+}
+
+/// Redaction the output verifier substitutes when it catches a leak:
+/// deliberately inert so a caught failure is visible rather than plausible
+const VERIFY_REDACTION: &str = "[REDACTED]";
+
+/// Defense-in-depth check on one masked cell (see `verify_output` in the
+/// config). The strategy claimed to remove PII; output that is the original
+/// value still scanning as PII means it leaked — a broken plugin, a memo
+/// mix-up — so the cell drops to a hard redaction, with a failure audit
+/// entry and a metric raising the alarm.
+#[allow(clippy::too_many_arguments)]
+async fn verify_masked_output(
+    state: &AppState,
+    scanner: &PiiScanner,
+    verify: Option<VerifyOutputConfig>,
+    connection_id: usize,
+    column_idx: usize,
+    original: &str,
+    masked: String,
+    seed: u64,
+) -> String {
+    let Some(verify) = verify else {
+        return masked;
+    };
+    match verify.mode {
+        VerifyOutputMode::Off => return masked,
+        // The seed is a hash of the cell, so sampling is deterministic per
+        // value rather than per row position
+        VerifyOutputMode::Sample if seed % 100 >= u64::from(verify.sample_percent) => {
+            return masked;
+        }
+        _ => {}
+    }
+    if masked != original {
+        return masked;
+    }
+    let Some(pii_type) = scanner.scan(original) else {
+        return masked;
+    };
+    let pii_type = pii_type.name();
+    tracing::error!(
+        column_idx,
+        pii_type,
+        "Masked output still held the original PII; redacting"
+    );
+    state
+        .audit_logger
+        .log(AuditLogger::masking_verification_failed(
+            connection_id,
+            column_idx,
+            pii_type,
+        ))
+        .await;
+    crate::metrics::record_verification_failure(pii_type);
+    VERIFY_REDACTION.to_string()
+}
+
 use crate::state::{AppState, LogEntry};
 use chrono::Utc;
 use serde_json::json;
 use tracing::instrument;
 
+#[cfg(feature = "postgres")]
+use crate::config::{ExpressionHandling, TypeMismatchPolicy};
+#[cfg(feature = "postgres")]
+use crate::protocol::postgres::PgTypeClass;
+#[cfg(feature = "postgres")]
+use crate::sql_resolver::{ColumnOrigin, OutputColumn, QueryResolver};
+use std::sync::Arc;
+
+#[cfg(feature = "postgres")]
 pub trait PacketInterceptor {
+    /// Called with the SQL text of each client statement before it is
+    /// forwarded upstream, so implementations can prepare for the result
+    /// set that follows. The default implementation does nothing.
+    fn on_query(&mut self, _sql: &str) -> impl std::future::Future<Output = ()> + Send {
+        async {}
+    }
+    /// Called once with the database the client named in its startup
+    /// message, before any queries flow, so implementations can scope
+    /// behaviour per database. The default implementation does nothing.
+    fn on_startup(&mut self, _database: Option<&str>) -> impl std::future::Future<Output = ()> + Send {
+        async {}
+    }
     fn on_row_description(
         &mut self,
         msg: &RowDescription,
@@ -151,70 +1208,571 @@ pub trait PacketInterceptor {
     fn on_data_row(
         &mut self,
         msg: DataRow,
-    ) -> impl std::future::Future<Output = Result<DataRow>> + Send;
+    ) -> impl std::future::Future<Output = Result<DataRow, MaskingError>> + Send;
+    /// Called when a `COPY ... TO STDOUT` begins, with whatever the
+    /// statement revealed about the columns, so implementations can bind
+    /// rules for the COPY rows that will be fed through
+    /// [`Self::on_data_row`]. The default implementation does nothing.
+    fn on_copy_out(
+        &mut self,
+        _stmt: &CopyOutStatement,
+    ) -> impl std::future::Future<Output = ()> + Send {
+        async {}
+    }
 }
 
+/// What binding decided for a masked column: a strategy from a rule or
+/// heuristic, or a type-valid placeholder when the rule's strategy did not
+/// fit the column type and its mismatch policy asked for a fallback.
+#[cfg(feature = "postgres")]
+#[derive(Clone)]
+enum ColumnMask {
+    /// A rule applying its strategy chain, with the rule's `date_shift`
+    /// options resolved: the shift range, and the output index of the
+    /// sibling column seeding the offset (`None` when the rule names no
+    /// `seed_column` or the query did not select the sibling)
+    Strategy {
+        chain: StrategyChain,
+        tuning: StrategyTuning,
+        seed_index: Option<usize>,
+        condition: Option<BoundCondition>,
+    },
+    TypedFallback(PgTypeClass, Option<BoundCondition>),
+    /// A rule redacting to a fixed placeholder, with the rule's text and
+    /// length handling resolved
+    Redact {
+        spec: RedactSpec,
+        condition: Option<BoundCondition>,
+    },
+    /// A rule with per-field strategies for a composite literal; the whole
+    /// chain and the mismatch policy decide what happens when a value does
+    /// not parse as one
+    Composite {
+        fields: Vec<Option<StrategyChain>>,
+        whole: StrategyChain,
+        on_parse_failure: TypeMismatchPolicy,
+        class: PgTypeClass,
+        tuning: StrategyTuning,
+        condition: Option<BoundCondition>,
+    },
+}
+
+#[cfg(feature = "postgres")]
+impl ColumnMask {
+    fn condition(&self) -> Option<&BoundCondition> {
+        match self {
+            ColumnMask::TypedFallback(_, cond) => cond.as_ref(),
+            ColumnMask::Strategy { condition, .. }
+            | ColumnMask::Redact { condition, .. }
+            | ColumnMask::Composite { condition, .. } => condition.as_ref(),
+        }
+    }
+}
+
+#[cfg(feature = "postgres")]
 pub struct Anonymizer {
     state: AppState,
     scanner: PiiScanner,
-    target_cols: Vec<(usize, String)>,
+    target_cols: Vec<(usize, ColumnMask)>,
+    col_classes: Vec<PgTypeClass>,
+    col_names: Vec<String>,
     connection_id: usize,
+    resolver: QueryResolver,
+    query_origins: Option<Arc<Vec<OutputColumn>>>,
+    memo: MaskMemo,
+    /// The database the client named at startup, for `database`-scoped
+    /// rules; `None` until [`PacketInterceptor::on_startup`] runs
+    database: Option<String>,
 }
 
+#[cfg(feature = "postgres")]
 impl Anonymizer {
     pub fn new(state: AppState, connection_id: usize) -> Self {
         Self {
             state,
             scanner: PiiScanner::new(),
             target_cols: Vec::new(),
+            col_classes: Vec::new(),
+            col_names: Vec::new(),
             connection_id,
+            resolver: QueryResolver::new(),
+            query_origins: None,
+            memo: MaskMemo::new(),
+            database: None,
         }
     }
 }
 
+#[cfg(feature = "postgres")]
+impl Anonymizer {
+    /// The table a field originates from, as the upstream's catalogs know
+    /// it, consulted when the statement itself did not say
+    async fn resolve_table_oid(&self, oid: u32) -> Option<String> {
+        self.state.oid_cache.as_ref()?.resolve(oid).await
+    }
+}
+
+/// Find the first rule matching a column, optionally constrained by the
+/// resolved table name and the connection's database. A rule without a
+/// table matches any table; a rule with a table also matches when the
+/// table could not be resolved, which preserves the pre-resolver
+/// behaviour of matching on column name alone. Database scopes fail
+/// closed the same way when the connection named no database.
+/// Exact-name rules win over glob-pattern rules when both cover a column.
+/// The mask a bound rule applies: its placeholder spec for a single-stage
+/// `redact` rule, otherwise its strategy chain
+#[cfg(feature = "postgres")]
+fn rule_mask(
+    rule: &MaskingRule,
+    seed_index: Option<usize>,
+    condition: Option<BoundCondition>,
+) -> ColumnMask {
+    match RedactSpec::from_rule(rule) {
+        Some(spec) => ColumnMask::Redact { spec, condition },
+        None => ColumnMask::Strategy {
+            chain: rule.strategy.clone(),
+            tuning: StrategyTuning::from_rule(rule),
+            seed_index,
+            condition,
+        },
+    }
+}
+
+fn find_rule<'a>(
+    rules: &'a [MaskingRule],
+    database: Option<&str>,
+    table: Option<&str>,
+    column: &str,
+) -> Option<&'a MaskingRule> {
+    let applies = |rule: &MaskingRule| {
+        rule.column_matches(column)
+            && rule.table_matches(table)
+            && rule.database_matches(database)
+    };
+    rules
+        .iter()
+        .find(|rule| !rule.column_is_pattern() && applies(rule))
+        .or_else(|| rules.iter().find(|rule| rule.column_is_pattern() && applies(rule)))
+}
+
+/// Whether a strategy's output fits a column's type class. Unrecognised
+/// OIDs classify as `Other` and accept anything, preserving the behaviour
+/// from before column types were consulted.
+#[cfg(feature = "postgres")]
+fn strategy_fits_type(strategy: &Strategy, class: PgTypeClass) -> bool {
+    match strategy {
+        // The range arms apply the strategy to each bound (see
+        // `mask_range_literal`), so the literal keeps its structure
+        Strategy::NumericNoise => matches!(
+            class,
+            PgTypeClass::Integer
+                | PgTypeClass::Float
+                | PgTypeClass::Numeric
+                | PgTypeClass::Range
+                | PgTypeClass::Other
+        ),
+        Strategy::DateShift => matches!(
+            class,
+            PgTypeClass::Date | PgTypeClass::Timestamp | PgTypeClass::Range | PgTypeClass::Other
+        ),
+        // dob emits a plain date, which date and timestamp columns accept too
+        Strategy::Dob => matches!(
+            class,
+            PgTypeClass::Text | PgTypeClass::Date | PgTypeClass::Timestamp | PgTypeClass::Other
+        ),
+        Strategy::Json => matches!(
+            class,
+            PgTypeClass::Json | PgTypeClass::Text | PgTypeClass::Other
+        ),
+        // Digits map to digits and letters to letters, so numeric shapes
+        // survive; a digit-shuffled date is not a valid date, so date and
+        // timestamp columns fall to the mismatch policy
+        Strategy::FormatPreserving => matches!(
+            class,
+            PgTypeClass::Text
+                | PgTypeClass::Integer
+                | PgTypeClass::Float
+                | PgTypeClass::Numeric
+                | PgTypeClass::Range
+                | PgTypeClass::Other
+        ),
+        // Substitution leaves non-matching text in place, so the output's
+        // format is whatever the pattern author chose to preserve
+        Strategy::RegexReplace => true,
+        // NULL is valid for every column type
+        Strategy::Null => true,
+        // Every other strategy produces free-form text
+        _ => class.is_textual(),
+    }
+}
+
+/// A type-valid placeholder for a column whose bound strategy does not fit
+/// its wire type: zero for numbers, an epoch-shifted date for dates.
+#[cfg(feature = "postgres")]
+fn typed_fallback_value(class: PgTypeClass, seed: u64) -> String {
+    let epoch_shifted = || {
+        chrono::NaiveDate::from_ymd_opt(1970, 1, 1).unwrap()
+            + chrono::Duration::days((seed % 365) as i64)
+    };
+    match class {
+        PgTypeClass::Boolean => "f".to_string(),
+        PgTypeClass::Integer | PgTypeClass::Numeric => "0".to_string(),
+        PgTypeClass::Float => "0.0".to_string(),
+        PgTypeClass::Date => epoch_shifted().to_string(),
+        PgTypeClass::Timestamp => format!("{} 00:00:00", epoch_shifted()),
+        PgTypeClass::Uuid => "00000000-0000-0000-0000-000000000000".to_string(),
+        // Every range type accepts the empty range
+        PgTypeClass::Range => "empty".to_string(),
+        PgTypeClass::Text | PgTypeClass::Json | PgTypeClass::Other => "MASKED".to_string(),
+    }
+}
+
+/// Index of the result-set column that carries `name`, preferring resolved
+/// origin columns (so aliasing a sibling does not hide it) and falling back
+/// to the wire display names.
+#[cfg(feature = "postgres")]
+fn sibling_index(
+    msg: &RowDescription,
+    origins: Option<&[OutputColumn]>,
+    name: &str,
+) -> Option<usize> {
+    if let Some(origins) = origins
+        && let Some(idx) = origins.iter().position(|output| {
+            matches!(&output.origin, ColumnOrigin::Column { column, .. } if column == name)
+        })
+    {
+        return Some(idx);
+    }
+    msg.fields
+        .iter()
+        .position(|f| f.name.as_ref() == name.as_bytes())
+}
+
+#[cfg(feature = "postgres")]
 impl PacketInterceptor for Anonymizer {
+    async fn on_startup(&mut self, database: Option<&str>) {
+        self.database = database.map(str::to_string);
+    }
+
+    #[instrument(skip(self, sql))]
+    async fn on_query(&mut self, sql: &str) {
+        // Best-effort: unparseable or opaque statements resolve to None and
+        // rule binding falls back to the display names in RowDescription.
+        self.query_origins = self.resolver.resolve(sql);
+    }
+
     #[instrument(skip(self, msg), fields(num_fields = msg.fields.len()))]
     async fn on_row_description(&mut self, msg: &RowDescription) {
         self.target_cols.clear();
+        self.col_classes.clear();
+        self.col_names.clear();
 
         let config = self.state.config.read().await;
+        self.memo
+            .sync(config.memo_entries, self.state.current_ruleset_generation());
+        let origins = self.query_origins.clone();
         for (i, field) in msg.fields.iter().enumerate() {
-            for rule in &config.rules {
-                // Check if rule applies to this column
-                let table_match = rule.table.as_ref().is_none_or(|_t| {
-                    // TODO: In a real app, we'd need to resolve table OID to name.
-                    // For now, we assume the rule matches if table is None (global)
-                    // or if we could somehow know the table name (which we don't easily from RowDescription alone without a cache).
-                    // So for MVP, we'll ignore table name matching in RowDescription and just match on column name.
-                    // A proper implementation would query pg_class to map OID -> Name.
-                    true
-                });
-
-                // Convert Bytes field name to str for comparison
-                let field_name = std::str::from_utf8(&field.name).unwrap_or("");
-                if table_match && rule.column == field_name {
-                    self.target_cols.push((i, rule.strategy.clone()));
-                    break; // Apply first matching rule
+            let class = PgTypeClass::from_oid(field.type_oid);
+            self.col_classes.push(class);
+
+            // Display name from the wire, used when resolution has nothing
+            // better to offer
+            let field_name = std::str::from_utf8(&field.name).unwrap_or("");
+            self.col_names.push(field_name.to_string());
+
+            let origin = self
+                .query_origins
+                .as_ref()
+                .and_then(|origins| origins.get(i))
+                .map(|output| &output.origin);
+
+            let rule = match origin {
+                // The resolver traced this output back through aliases to a
+                // real column; bind rules against that, not the display name,
+                // so `SELECT email AS contact` is still masked and
+                // `SELECT phone AS email` is not mistaken for an email column.
+                Some(ColumnOrigin::Column { table, column }) => {
+                    let table = match table {
+                        Some(table) => Some(table.clone()),
+                        None => self.resolve_table_oid(field.table_oid).await,
+                    };
+                    find_rule(&config.rules, self.database.as_deref(), table.as_deref(), column)
+                }
+                // An expression over real columns: treat its output as
+                // sensitive when configured to, otherwise leave it to the
+                // per-value heuristics in on_data_row.
+                Some(ColumnOrigin::Expression { sources }) => {
+                    if config.expression_handling == ExpressionHandling::Sensitive {
+                        sources.iter().find_map(|(table, column)| {
+                            find_rule(&config.rules, self.database.as_deref(), table.as_deref(), column)
+                        })
+                    } else {
+                        None
+                    }
+                }
+                // No resolution available: the catalogs can still say which
+                // table the field came from, so rules scoped to a table fire
+                // only for its columns; without that, the display name alone
+                Some(ColumnOrigin::Unknown) | None => {
+                    let table = self.resolve_table_oid(field.table_oid).await;
+                    find_rule(&config.rules, self.database.as_deref(), table.as_deref(), field_name)
+                }
+            };
+
+            let Some(rule) = rule else { continue };
+            // The last stage's output is what reaches the wire, so it alone
+            // decides whether the chain fits the column's type
+            let Some(terminal) = rule.strategy.terminal() else {
+                continue;
+            };
+            let condition = rule.when.as_ref().map(|when| {
+                bind_condition(when, |name| {
+                    sibling_index(msg, origins.as_deref().map(|v| &v[..]), name)
+                })
+            });
+            // A seed column the query did not select resolves to None and
+            // the seed falls back to the cell's own value
+            let seed_index = rule
+                .seed_column
+                .as_deref()
+                .and_then(|name| sibling_index(msg, origins.as_deref().map(|v| &v[..]), name));
+            // Per-field composite masking skips the scalar fit check: the
+            // value is rewritten structurally, not replaced by the chain's
+            // output
+            if let Some(fields) = &rule.composite_fields {
+                self.target_cols.push((
+                    i,
+                    ColumnMask::Composite {
+                        fields: fields.clone(),
+                        whole: rule.strategy.clone(),
+                        on_parse_failure: rule.on_type_mismatch,
+                        class,
+                        tuning: StrategyTuning::from_rule(rule),
+                        condition,
+                    },
+                ));
+                continue;
+            }
+            if strategy_fits_type(terminal, class) {
+                self.target_cols.push((i, rule_mask(rule, seed_index, condition)));
+            } else {
+                match rule.on_type_mismatch {
+                    TypeMismatchPolicy::Apply => {
+                        self.target_cols.push((i, rule_mask(rule, seed_index, condition)));
+                    }
+                    TypeMismatchPolicy::Skip => {
+                        tracing::warn!(
+                            column = %field_name,
+                            strategy = %rule.strategy,
+                            ?class,
+                            "Rule strategy does not fit the column type; leaving column unmasked"
+                        );
+                    }
+                    TypeMismatchPolicy::Fallback => {
+                        tracing::warn!(
+                            column = %field_name,
+                            strategy = %rule.strategy,
+                            ?class,
+                            "Rule strategy does not fit the column type; masking with a type-valid fallback"
+                        );
+                        self.target_cols
+                            .push((i, ColumnMask::TypedFallback(class, condition)));
+                    }
                 }
             }
         }
     }
 
+    /// COPY produces no RowDescription, so rule binding works from what the
+    /// statement itself revealed: an explicit column list binds against the
+    /// table, a `COPY (SELECT ...)` form goes through the resolver like any
+    /// query, and a bare `COPY table` leaves only the content heuristics.
+    /// Binding happens through a synthetic RowDescription of text columns,
+    /// so conditions, quarantine, and type handling behave exactly as they
+    /// do for SELECT results.
+    #[instrument(skip(self, stmt))]
+    async fn on_copy_out(&mut self, stmt: &CopyOutStatement) {
+        let columns: Vec<String> = if !stmt.columns.is_empty() {
+            self.query_origins = Some(Arc::new(
+                stmt.columns
+                    .iter()
+                    .map(|column| OutputColumn {
+                        name: Some(column.clone()),
+                        origin: ColumnOrigin::Column {
+                            table: stmt.table.clone(),
+                            column: column.clone(),
+                        },
+                    })
+                    .collect(),
+            ));
+            stmt.columns.clone()
+        } else if let Some(query) = &stmt.query {
+            self.query_origins = self.resolver.resolve(query);
+            self.query_origins
+                .as_deref()
+                .map(|origins| {
+                    origins
+                        .iter()
+                        .map(|output| output.name.clone().unwrap_or_default())
+                        .collect()
+                })
+                .unwrap_or_default()
+        } else {
+            self.query_origins = None;
+            Vec::new()
+        };
+
+        let fields = columns
+            .iter()
+            .map(|name| FieldDescription {
+                name: bytes::Bytes::copy_from_slice(name.as_bytes()),
+                table_oid: 0,
+                column_index: 0,
+                type_oid: 25, // text: COPY text/csv fields are free-form
+                type_len: -1,
+                type_modifier: -1,
+                format_code: 0,
+            })
+            .collect();
+        self.on_row_description(&RowDescription { fields }).await;
+    }
+
     #[instrument(skip(self, msg), fields(num_values = msg.values.len(), connection_id = self.connection_id))]
-    async fn on_data_row(&mut self, mut msg: DataRow) -> Result<DataRow> {
+    async fn on_data_row(&mut self, mut msg: DataRow) -> Result<DataRow, MaskingError> {
         // Check if masking is globally enabled
-        {
+        let (scan_typed_columns, scan_substrings, min_confidence, heuristics_configured, verify_output, hashing) = {
             let config = self.state.config.read().await;
             if !config.masking_enabled {
                 return Ok(msg);
             }
+            self.scanner.set_ignore_private_ips(
+                config.scanner.as_ref().is_some_and(|s| s.ignore_private_ips),
+            );
+            self.scanner.set_locales(
+                config
+                    .scanner
+                    .as_ref()
+                    .map(|s| s.locales.as_slice())
+                    .unwrap_or(crate::config::DEFAULT_LOCALES),
+            );
+            self.scanner.set_dob_column_hints(
+                config
+                    .scanner
+                    .as_ref()
+                    .map(|s| s.dob_column_hints.as_slice())
+                    .unwrap_or_else(|| crate::config::default_dob_column_hints()),
+            );
+            self.scanner.sync_custom_patterns(
+                config
+                    .scanner
+                    .as_ref()
+                    .map(|s| s.custom_patterns.as_slice())
+                    .unwrap_or_default(),
+                self.state.current_ruleset_generation(),
+            );
+            self.scanner.sync_default_strategies(
+                config.masking.as_ref().map(|m| &m.defaults),
+                self.state.current_ruleset_generation(),
+            );
+            (
+                config.scan_typed_columns,
+                config.scanner.as_ref().is_some_and(|s| s.scan_substrings),
+                config
+                    .scanner
+                    .as_ref()
+                    .map_or(crate::config::DEFAULT_MIN_CONFIDENCE, |s| s.min_confidence),
+                config.masking.as_ref().is_none_or(|m| m.heuristics_enabled),
+                config.verify_output,
+                HashSpec::from_config(&config),
+            )
+        };
+
+        // Source policy resolved at connection setup: Unmasked skips masking
+        // entirely, Partial applies explicit rules only
+        let policy = self
+            .state
+            .policy_action(self.connection_id)
+            .await
+            .unwrap_or(PolicyAction::Mask);
+        if policy == PolicyAction::Unmasked {
+            return Ok(msg);
         }
+        let heuristics_enabled = heuristics_configured && policy == PolicyAction::Mask;
+
+        // Columns an alert quarantined: the alerting layer decided they are
+        // leaking, so every value drops to a hard redaction until cleared
+        let quarantined = {
+            let quarantined = self.state.quarantined_columns.read().await;
+            if quarantined.is_empty() {
+                None
+            } else {
+                Some(quarantined.clone())
+            }
+        };
+
+        // Row-level rule conditions are evaluated up front, against the row
+        // as it arrived, so a sibling test sees original values even when
+        // the sibling itself is about to be masked
+        let condition_failed: Vec<usize> = self
+            .target_cols
+            .iter()
+            .filter_map(|(i, mask)| {
+                let condition = mask.condition()?;
+                let own = msg.values.get(*i)?.as_ref()?;
+                (!condition.holds(own, &msg.values, &self.scanner)).then_some(*i)
+            })
+            .collect();
+
+        // Sibling-seeded rules (`seed_column`) hash the sibling's value up
+        // front, against the row as it arrived, so the derived offset is
+        // keyed on the subject rather than on the date being shifted. A
+        // NULL sibling falls back to the cell's own value.
+        let seed_overrides: HashMap<usize, u64> = self
+            .target_cols
+            .iter()
+            .filter_map(|(i, mask)| {
+                let ColumnMask::Strategy {
+                    seed_index: Some(idx),
+                    ..
+                } = mask
+                else {
+                    return None;
+                };
+                let sibling = msg.values.get(*idx)?.as_ref()?;
+                Some((*i, value_seed(sibling)))
+            })
+            .collect();
 
         let mut changes_log = Vec::new();
         let mut changed_any = false;
 
         for (i, val_opt) in msg.values.iter_mut().enumerate() {
             if let Some(val) = val_opt {
+                if let Some(quarantined) = &quarantined
+                    && self
+                        .col_names
+                        .get(i)
+                        .is_some_and(|name| quarantined.contains(&name.to_lowercase()))
+                {
+                    val.clear();
+                    val.extend_from_slice(VERIFY_REDACTION.as_bytes());
+                    changed_any = true;
+                    self.state.record_masking("quarantine").await;
+                    changes_log.push(json!({
+                        "column_idx": i,
+                        "strategy": "quarantine",
+                        "masked": VERIFY_REDACTION
+                    }));
+                    continue;
+                }
+                // A bound rule whose condition does not hold leaves the cell
+                // exactly as it arrived; heuristics do not reclaim it — the
+                // condition deciding "don't mask" is as deliberate as the
+                // rule itself
+                if condition_failed.contains(&i) {
+                    self.state.record_condition_skip().await;
+                    continue;
+                }
                 let original_val_preview = if val.len() > 50 {
                     format!("{}...", String::from_utf8_lossy(&val[..50]))
                 } else {
@@ -222,39 +1780,206 @@ impl PacketInterceptor for Anonymizer {
                 };
 
                 // 1. Check for explicit rule
-                let explicit_strategy = self
+                let bound = self
                     .target_cols
                     .iter()
                     .find(|(col_idx, _)| *col_idx == i)
-                    .map(|(_, strategy)| strategy.as_str());
+                    .map(|(_, mask)| mask.clone());
 
-                // Handle explicit JSON strategy
-                if let Some("json") = explicit_strategy
-                    && let Ok(s) = std::str::from_utf8(val)
-                    && let Ok(mut json_val) = serde_json::from_str::<serde_json::Value>(s)
+                // A rule binding the `null` strategy withholds the cell
+                // entirely: the value becomes a wire-level NULL (length -1,
+                // `\N` in a COPY stream) rather than an empty string, so
+                // clients see a real SQL NULL
+                if let Some(ColumnMask::Strategy { ref chain, .. }) = bound
+                    && chain.as_single() == Some(&Strategy::Null)
+                {
+                    changed_any = true;
+                    self.state.record_masking("null").await;
+                    changes_log.push(json!({
+                        "column_idx": i,
+                        "strategy": "null",
+                        "original": original_val_preview,
+                        "masked": null
+                    }));
+                    *val_opt = None;
+                    continue;
+                }
+
+                // A redact rule replaces the value with its fixed
+                // placeholder, cycled or cut to the original's character
+                // count when the rule preserves length
+                if let Some(ColumnMask::Redact { ref spec, .. }) = bound {
+                    let original = String::from_utf8_lossy(val).to_string();
+                    let fake_val = spec.apply(&original);
+                    val.clear();
+                    val.extend_from_slice(fake_val.as_bytes());
+                    changed_any = true;
+                    self.state.record_masking("redact").await;
+                    changes_log.push(json!({
+                        "column_idx": i,
+                        "strategy": "redact",
+                        "original": original_val_preview,
+                        "masked": fake_val
+                    }));
+                    continue;
+                }
+
+                // Type-mismatch fallback: the bound strategy could not
+                // produce output this column's type accepts, so emit a
+                // type-valid placeholder instead
+                if let Some(ColumnMask::TypedFallback(class, _)) = bound {
+                    let seed = value_seed(val);
+
+                    let fake_val = typed_fallback_value(class, seed);
+                    val.clear();
+                    val.extend_from_slice(fake_val.as_bytes());
+                    changed_any = true;
+                    self.state.record_masking("other").await;
+                    changes_log.push(json!({
+                        "column_idx": i,
+                        "strategy": "typed_fallback",
+                        "original": original_val_preview,
+                        "masked": fake_val
+                    }));
+                    continue;
+                }
+
+                // Composite rule: mask field by field and reassemble the
+                // literal; a value that does not parse as one resolves per
+                // the rule's mismatch policy
+                if let Some(ColumnMask::Composite {
+                    fields,
+                    whole,
+                    on_parse_failure,
+                    class,
+                    tuning,
+                    ..
+                }) = &bound
                 {
-                    mask_json_recursively(&mut json_val, &self.scanner);
-                    let new_json = serde_json::to_string(&json_val)?;
+                    let seed = cell_seed(tuning, val);
+                    let original = String::from_utf8_lossy(val).to_string();
 
-                    if new_json.as_bytes() != &val[..] {
+                    let masked = match mask_composite_literal(
+                        &original,
+                        fields,
+                        &self.scanner,
+                        &self.state.strategy_registry,
+                        &hashing,
+                        tuning,
+                        seed,
+                    ) {
+                        Some(masked) => Some(masked),
+                        None => match on_parse_failure {
+                            TypeMismatchPolicy::Apply => Some(mask_chain(
+                                &self.state.strategy_registry,
+                                &hashing,
+                                tuning,
+                                whole,
+                                &original,
+                                seed,
+                            )),
+                            TypeMismatchPolicy::Fallback => {
+                                Some(typed_fallback_value(*class, seed))
+                            }
+                            TypeMismatchPolicy::Skip => {
+                                tracing::warn!(
+                                    column_idx = i,
+                                    "Value bound to a composite rule did not parse; leaving it unmasked"
+                                );
+                                None
+                            }
+                        },
+                    };
+                    if let Some(masked) = masked {
                         val.clear();
-                        val.extend_from_slice(new_json.as_bytes());
+                        val.extend_from_slice(masked.as_bytes());
                         changed_any = true;
-                        // Record masking stats for JSON
-                        self.state.record_masking("json").await;
+                        self.state.record_masking("composite").await;
                         changes_log.push(json!({
                             "column_idx": i,
-                            "strategy": "json",
+                            "strategy": "composite",
                             "original": original_val_preview,
-                            "masked": "(JSON Masked)"
+                            "masked": masked
                         }));
                     }
                     continue;
                 }
 
+                let explicit_strategy = match bound {
+                    Some(ColumnMask::Strategy { chain, tuning, .. }) => Some((chain, tuning)),
+                    _ => None,
+                };
+
+                // Handle explicit JSON strategy (always a single-stage
+                // chain: validation rejects json combined with other stages)
+                if let Some((chain, tuning)) = &explicit_strategy
+                    && chain.as_single() == Some(&Strategy::Json)
+                    && let Ok(s) = std::str::from_utf8(val)
+                {
+                    match serde_json::from_str::<serde_json::Value>(s) {
+                        Ok(mut json_val) => {
+                            // With configured pointer paths only those are
+                            // masked; without, every string is scanned
+                            match &tuning.json_paths {
+                                Some(paths) => mask_json_paths(
+                                    &mut json_val,
+                                    paths.as_ref(),
+                                    &self.state.strategy_registry,
+                                    &hashing,
+                                    tuning,
+                                ),
+                                None => mask_json_recursively(&mut json_val, &self.scanner),
+                            }
+                            let new_json = serde_json::to_string(&json_val).map_err(|e| {
+                                MaskingError::ValueParse {
+                                    column_idx: i,
+                                    details: e.to_string(),
+                                }
+                            })?;
+
+                            if new_json.as_bytes() != &val[..] {
+                                val.clear();
+                                val.extend_from_slice(new_json.as_bytes());
+                                changed_any = true;
+                                // Record masking stats for JSON
+                                self.state.record_masking("json").await;
+                                changes_log.push(json!({
+                                    "column_idx": i,
+                                    "strategy": "json",
+                                    "original": original_val_preview,
+                                    "masked": "(JSON Masked)"
+                                }));
+                            }
+                            continue;
+                        }
+                        // A path-targeted rule leaves a malformed document
+                        // alone rather than flattening it to a placeholder
+                        Err(e) if tuning.json_paths.is_some() => {
+                            tracing::warn!(
+                                column_idx = i,
+                                error = %e,
+                                "Value bound to a json rule with json_paths is not valid JSON; leaving it unmasked"
+                            );
+                            continue;
+                        }
+                        // A plain json rule on a non-JSON value falls
+                        // through to generic masking, as before
+                        Err(_) => {}
+                    }
+                }
+
+                // Heuristics only make sense on columns that can hold
+                // free-form text; ints and timestamps are skipped unless
+                // scanning typed columns is explicitly enabled
+                let scannable = scan_typed_columns
+                    || self
+                        .col_classes
+                        .get(i)
+                        .is_none_or(|class| class.is_textual());
+
                 let strategy = if let Some(s) = explicit_strategy {
                     Some(s)
-                } else {
+                } else if heuristics_enabled && scannable {
                     // 2. Heuristic scan
                     if let Ok(s) = std::str::from_utf8(val) {
                         // Try JSON heuristic first if it looks like JSON
@@ -307,30 +2032,141 @@ impl PacketInterceptor for Anonymizer {
                             }
                         }
 
-                        self.scanner.scan(s).map(pii_type_to_strategy)
+                        let candidates = self
+                            .scanner
+                            .scan_scored(s, self.col_names.get(i).map(String::as_str));
+                        if !candidates.is_empty() {
+                            // The full scored list, so an operator can tune
+                            // scanner.min_confidence from real traffic
+                            tracing::debug!(
+                                column = ?self.col_names.get(i),
+                                candidates = ?candidates,
+                                "Heuristic PII candidates"
+                            );
+                        }
+                        match candidates.into_iter().next() {
+                            Some((pii_type, confidence)) if confidence >= min_confidence => {
+                                // A detection on a column no rule covers is
+                                // what the alerting layer wants to hear about
+                                self.state.report_detection(crate::alerts::Detection {
+                                    pii_type: pii_type.clone(),
+                                    column: self.col_names.get(i).cloned(),
+                                    policy,
+                                    severity: self.scanner.custom_severity(pii_type.name()),
+                                    confidence: Some(confidence),
+                                });
+                                Some((
+                                    StrategyChain::from(pii_type_to_strategy(
+                                        pii_type,
+                                        &self.scanner,
+                                    )),
+                                    StrategyTuning::default(),
+                                ))
+                            }
+                            // Below the threshold: leave the value, the
+                            // debug line above records what was close
+                            Some(_) => None,
+                            // Whole-value miss: substring mode can still
+                            // find PII embedded in free text and rewrite
+                            // just those spans
+                            None if scan_substrings => {
+                                if let Some((masked, types)) =
+                                    mask_pii_substrings(s, &self.scanner)
+                                {
+                                    for pii_type in types {
+                                        self.state.report_detection(
+                                            crate::alerts::Detection {
+                                                pii_type: pii_type.clone(),
+                                                column: self.col_names.get(i).cloned(),
+                                                policy,
+                                                severity: self
+                                                    .scanner
+                                                    .custom_severity(pii_type.name()),
+                                                confidence: None,
+                                            },
+                                        );
+                                    }
+                                    val.clear();
+                                    val.extend_from_slice(masked.as_bytes());
+                                    changed_any = true;
+                                    self.state.record_masking("other").await;
+                                    changes_log.push(json!({
+                                        "column_idx": i,
+                                        "strategy": "substring (heuristic)",
+                                        "original": original_val_preview,
+                                        "masked": masked
+                                    }));
+                                    continue;
+                                }
+                                None
+                            }
+                            None => None,
+                        }
                     } else {
                         None
                     }
+                } else {
+                    None
                 };
 
-                if let Some(strat) = strategy {
-                    // Apply masking
-                    let mut hasher = DefaultHasher::new();
-                    val.hash(&mut hasher);
-                    let seed = hasher.finish();
+                if let Some((strat, tuning)) = strategy {
+                    // Apply masking. A sibling-seeded rule takes its seed
+                    // from the seed column's value instead of the cell's own.
+                    let seed = match seed_overrides.get(&i) {
+                        Some(seed) => *seed,
+                        None => cell_seed(&tuning, val),
+                    };
 
-                    let fake_val = generate_fake_data(strat, seed);
+                    let original = String::from_utf8_lossy(val).to_string();
+                    // Range literals keep their structure: the chain lands on
+                    // each bound rather than on the literal as a whole. The
+                    // memo is bypassed — its entries are keyed on (chain,
+                    // value) alone, while a range output also depends on the
+                    // literal's brackets and a sibling-seeded output on
+                    // another column entirely
+                    let registry = &self.state.strategy_registry;
+                    let (fake_val, memo_hit) =
+                        if self.col_classes.get(i) == Some(&PgTypeClass::Range) {
+                            (
+                                mask_range_literal(
+                                    &original, &strat, registry, &hashing, &tuning, seed,
+                                ),
+                                false,
+                            )
+                        } else if seed_overrides.contains_key(&i) {
+                            (
+                                mask_chain(registry, &hashing, &tuning, &strat, &original, seed),
+                                false,
+                            )
+                        } else {
+                            self.memo.get_or_compute(&strat, &tuning, seed, || {
+                                mask_chain(registry, &hashing, &tuning, &strat, &original, seed)
+                            })
+                        };
+                    crate::metrics::record_memo_lookup(memo_hit);
+                    let fake_val = verify_masked_output(
+                        &self.state,
+                        &self.scanner,
+                        verify_output,
+                        self.connection_id,
+                        i,
+                        &original,
+                        fake_val,
+                        seed,
+                    )
+                    .await;
 
                     val.clear();
                     val.extend_from_slice(fake_val.as_bytes());
                     changed_any = true;
 
-                    // Record masking stats
-                    self.state.record_masking(strat).await;
+                    // Record masking stats; a chained cell is attributed to
+                    // the whole chain, not to each stage
+                    self.state.record_masking(&strat.label()).await;
 
                     changes_log.push(json!({
                         "column_idx": i,
-                        "strategy": strat,
+                        "strategy": strat.to_string(),
                         "original": original_val_preview,
                         "masked": fake_val
                     }));
@@ -339,6 +2175,9 @@ impl PacketInterceptor for Anonymizer {
         }
 
         if changed_any {
+            self.state
+                .record_client_masked_row(self.connection_id, changes_log.len() as u64)
+                .await;
             // Log the change
             let id = format!("{:x}", rand::random::<u128>());
             self.state
@@ -362,7 +2201,14 @@ impl PacketInterceptor for Anonymizer {
 // ============================================================================
 
 /// Trait for intercepting MySQL packets
+#[cfg(feature = "mysql")]
 pub trait MySqlPacketInterceptor {
+    /// Called once with the database the client selected in its handshake
+    /// response, before any queries flow. The default implementation does
+    /// nothing.
+    fn on_startup(&mut self, _database: Option<&str>) -> impl std::future::Future<Output = ()> + Send {
+        async {}
+    }
     fn on_column_definition(
         &mut self,
         col: &ColumnDefinition,
@@ -370,37 +2216,81 @@ pub trait MySqlPacketInterceptor {
     fn on_result_row(
         &mut self,
         row: ResultRow,
-    ) -> impl std::future::Future<Output = Result<ResultRow>> + Send;
+    ) -> impl std::future::Future<Output = Result<ResultRow, MaskingError>> + Send;
+    /// Binary-protocol counterpart of [`Self::on_result_row`], for rows of a
+    /// COM_STMT_EXECUTE result set
+    fn on_binary_row(
+        &mut self,
+        row: BinaryRow,
+    ) -> impl std::future::Future<Output = Result<BinaryRow, MaskingError>> + Send;
+    /// Reset column tracking for a new result set
+    fn reset_columns(&mut self);
+}
+
+/// A rule bound to one MySQL result-set column, with its per-rule options
+/// resolved
+#[cfg(feature = "mysql")]
+struct BoundRule {
+    col_idx: usize,
+    chain: StrategyChain,
+    when: Option<RuleCondition>,
+    redact: Option<RedactSpec>,
+    tuning: StrategyTuning,
+    seed_column: Option<String>,
 }
 
 /// MySQL-specific anonymizer that reuses the core masking logic
+#[cfg(feature = "mysql")]
 pub struct MySqlAnonymizer {
     state: AppState,
     scanner: PiiScanner,
-    target_cols: Vec<(usize, String)>,
+    target_cols: Vec<BoundRule>,
+    /// Conditions from `target_cols` compiled against the full column list;
+    /// bound lazily on the first row, once every column definition is in
+    bound_conditions: Option<HashMap<usize, BoundCondition>>,
+    /// `seed_column` references resolved to column indexes, bound lazily
+    /// alongside the conditions
+    bound_seed_cols: Option<HashMap<usize, usize>>,
     column_names: Vec<String>,
     connection_id: usize,
+    memo: MaskMemo,
+    /// The database the client selected in its handshake response, for
+    /// `database`-scoped rules; `None` until
+    /// [`MySqlPacketInterceptor::on_startup`] runs
+    database: Option<String>,
 }
 
+#[cfg(feature = "mysql")]
 impl MySqlAnonymizer {
     pub fn new(state: AppState, connection_id: usize) -> Self {
         Self {
             state,
             scanner: PiiScanner::new(),
             target_cols: Vec::new(),
+            bound_conditions: None,
+            bound_seed_cols: None,
             column_names: Vec::new(),
             connection_id,
+            memo: MaskMemo::new(),
+            database: None,
         }
     }
+}
+
+#[cfg(feature = "mysql")]
+impl MySqlPacketInterceptor for MySqlAnonymizer {
+    async fn on_startup(&mut self, database: Option<&str>) {
+        self.database = database.map(str::to_string);
+    }
 
     /// Reset column tracking for a new result set
-    pub fn reset_columns(&mut self) {
+    fn reset_columns(&mut self) {
         self.target_cols.clear();
+        self.bound_conditions = None;
+        self.bound_seed_cols = None;
         self.column_names.clear();
     }
-}
 
-impl MySqlPacketInterceptor for MySqlAnonymizer {
     #[instrument(skip(self, col), fields(column_name = %String::from_utf8_lossy(&col.name)))]
     async fn on_column_definition(&mut self, col: &ColumnDefinition) {
         let col_name = String::from_utf8_lossy(&col.name).to_string();
@@ -408,34 +2298,221 @@ impl MySqlPacketInterceptor for MySqlAnonymizer {
         self.column_names.push(col_name.clone());
 
         let config = self.state.config.read().await;
-        for rule in &config.rules {
-            // Table match (MySQL provides table name in column def)
-            let table_name = String::from_utf8_lossy(&col.table);
-            let table_match = rule.table.as_ref().is_none_or(|t| t == &*table_name);
-
-            if table_match && rule.column == col_name {
-                self.target_cols.push((col_idx, rule.strategy.clone()));
-                tracing::debug!(column = %col_name, strategy = %rule.strategy, "MySQL column matched rule");
-                break;
+        // MySQL provides the table name in the column definition itself
+        let table_name = String::from_utf8_lossy(&col.table).to_string();
+        if let Some(rule) = find_rule(&config.rules, self.database.as_deref(), Some(&table_name), &col_name) {
+            self.target_cols.push(BoundRule {
+                col_idx,
+                chain: rule.strategy.clone(),
+                when: rule.when.clone(),
+                redact: RedactSpec::from_rule(rule),
+                tuning: StrategyTuning::from_rule(rule),
+                seed_column: rule.seed_column.clone(),
+            });
+            tracing::debug!(column = %col_name, strategy = %rule.strategy, "MySQL column matched rule");
+        }
+    }
+
+    /// Binary rows go through the same masking core as text rows: string-
+    /// typed cells are lifted into a [`ResultRow`], masked, and written
+    /// back. Numeric, temporal, and other non-string cells carry their raw
+    /// wire encoding and are never touched — they appear to the masking
+    /// pass (and to rule conditions on sibling columns) as NULL.
+    #[instrument(skip(self, row), fields(num_values = row.values.len(), connection_id = self.connection_id))]
+    async fn on_binary_row(&mut self, mut row: BinaryRow) -> Result<BinaryRow, MaskingError> {
+        let values: Vec<Option<bytes::BytesMut>> = row
+            .values
+            .iter()
+            .map(|cell| match cell {
+                BinaryCell::Text(v) => Some(v.clone()),
+                _ => None,
+            })
+            .collect();
+        let masked = self
+            .on_result_row(ResultRow {
+                sequence_id: row.sequence_id,
+                values,
+            })
+            .await?;
+        for (cell, masked_val) in row.values.iter_mut().zip(masked.values) {
+            if let BinaryCell::Text(v) = cell {
+                match masked_val {
+                    Some(masked_val) => *v = masked_val,
+                    // The `null` strategy dropped the value; the encoder
+                    // rebuilds the NULL bitmap from the cell
+                    None => *cell = BinaryCell::Null,
+                }
             }
         }
+        Ok(row)
     }
 
     #[instrument(skip(self, row), fields(num_values = row.values.len(), connection_id = self.connection_id))]
-    async fn on_result_row(&mut self, mut row: ResultRow) -> Result<ResultRow> {
+    async fn on_result_row(&mut self, mut row: ResultRow) -> Result<ResultRow, MaskingError> {
         // Check if masking is globally enabled
-        {
+        let (scan_substrings, min_confidence, heuristics_configured, verify_output, hashing) = {
             let config = self.state.config.read().await;
             if !config.masking_enabled {
                 return Ok(row);
             }
+            self.memo
+                .sync(config.memo_entries, self.state.current_ruleset_generation());
+            self.scanner.set_ignore_private_ips(
+                config.scanner.as_ref().is_some_and(|s| s.ignore_private_ips),
+            );
+            self.scanner.set_locales(
+                config
+                    .scanner
+                    .as_ref()
+                    .map(|s| s.locales.as_slice())
+                    .unwrap_or(crate::config::DEFAULT_LOCALES),
+            );
+            self.scanner.set_dob_column_hints(
+                config
+                    .scanner
+                    .as_ref()
+                    .map(|s| s.dob_column_hints.as_slice())
+                    .unwrap_or_else(|| crate::config::default_dob_column_hints()),
+            );
+            self.scanner.sync_custom_patterns(
+                config
+                    .scanner
+                    .as_ref()
+                    .map(|s| s.custom_patterns.as_slice())
+                    .unwrap_or_default(),
+                self.state.current_ruleset_generation(),
+            );
+            self.scanner.sync_default_strategies(
+                config.masking.as_ref().map(|m| &m.defaults),
+                self.state.current_ruleset_generation(),
+            );
+            (
+                config.scanner.as_ref().is_some_and(|s| s.scan_substrings),
+                config
+                    .scanner
+                    .as_ref()
+                    .map_or(crate::config::DEFAULT_MIN_CONFIDENCE, |s| s.min_confidence),
+                config.masking.as_ref().is_none_or(|m| m.heuristics_enabled),
+                config.verify_output,
+                HashSpec::from_config(&config),
+            )
+        };
+
+        // Source policy resolved at connection setup: Unmasked skips masking
+        // entirely, Partial applies explicit rules only
+        let policy = self
+            .state
+            .policy_action(self.connection_id)
+            .await
+            .unwrap_or(PolicyAction::Mask);
+        if policy == PolicyAction::Unmasked {
+            return Ok(row);
+        }
+        let heuristics_enabled = heuristics_configured && policy == PolicyAction::Mask;
+
+        // Columns an alert quarantined: hard redaction until cleared, same
+        // as the Postgres path
+        let quarantined = {
+            let quarantined = self.state.quarantined_columns.read().await;
+            if quarantined.is_empty() {
+                None
+            } else {
+                Some(quarantined.clone())
+            }
+        };
+
+        // Conditions bind lazily on the first row: only then are all column
+        // definitions in, so a sibling defined after the rule's own column
+        // is still found
+        if self.bound_conditions.is_none() {
+            let names = &self.column_names;
+            let bound: HashMap<usize, BoundCondition> = self
+                .target_cols
+                .iter()
+                .filter_map(|target| {
+                    let when = target.when.as_ref()?;
+                    let condition =
+                        bind_condition(when, |name| names.iter().position(|n| n == name));
+                    Some((target.col_idx, condition))
+                })
+                .collect();
+            self.bound_conditions = Some(bound);
+            // A seed column the result set does not carry resolves to
+            // nothing and the seed falls back to the cell's own value
+            let seed_cols: HashMap<usize, usize> = self
+                .target_cols
+                .iter()
+                .filter_map(|target| {
+                    let name = target.seed_column.as_deref()?;
+                    let idx = names.iter().position(|n| n == name)?;
+                    Some((target.col_idx, idx))
+                })
+                .collect();
+            self.bound_seed_cols = Some(seed_cols);
         }
 
+        // Evaluated up front, against the row as it arrived, so a sibling
+        // test sees original values even when the sibling itself is about
+        // to be masked
+        let condition_failed: Vec<usize> = self
+            .bound_conditions
+            .as_ref()
+            .map(|bound| {
+                bound
+                    .iter()
+                    .filter_map(|(i, condition)| {
+                        let own = row.values.get(*i)?.as_ref()?;
+                        (!condition.holds(own, &row.values, &self.scanner)).then_some(*i)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // Sibling-seeded rules (`seed_column`) hash the sibling's value up
+        // front, against the row as it arrived, so the derived offset is
+        // keyed on the subject rather than on the date being shifted. A
+        // NULL sibling falls back to the cell's own value.
+        let seed_overrides: HashMap<usize, u64> = self
+            .bound_seed_cols
+            .iter()
+            .flatten()
+            .filter_map(|(i, idx)| {
+                let sibling = row.values.get(*idx)?.as_ref()?;
+                Some((*i, value_seed(sibling)))
+            })
+            .collect();
+
         let mut changes_log = Vec::new();
         let mut changed_any = false;
 
         for (i, val_opt) in row.values.iter_mut().enumerate() {
             if let Some(val) = val_opt {
+                if let Some(quarantined) = &quarantined
+                    && self
+                        .column_names
+                        .get(i)
+                        .is_some_and(|name| quarantined.contains(&name.to_lowercase()))
+                {
+                    val.clear();
+                    val.extend_from_slice(VERIFY_REDACTION.as_bytes());
+                    changed_any = true;
+                    self.state.record_masking("quarantine").await;
+                    changes_log.push(json!({
+                        "column_idx": i,
+                        "column_name": self.column_names.get(i).unwrap_or(&"?".to_string()),
+                        "strategy": "quarantine",
+                        "masked": VERIFY_REDACTION
+                    }));
+                    continue;
+                }
+                // A bound rule whose condition does not hold leaves the cell
+                // exactly as it arrived; heuristics do not reclaim it — the
+                // condition deciding "don't mask" is as deliberate as the
+                // rule itself
+                if condition_failed.contains(&i) {
+                    self.state.record_condition_skip().await;
+                    continue;
+                }
                 let original_val_preview = if val.len() > 50 {
                     format!("{}...", String::from_utf8_lossy(&val[..50]))
                 } else {
@@ -443,69 +2520,231 @@ impl MySqlPacketInterceptor for MySqlAnonymizer {
                 };
 
                 // Check for explicit rule
-                let explicit_strategy = self
-                    .target_cols
-                    .iter()
-                    .find(|(col_idx, _)| *col_idx == i)
-                    .map(|(_, strategy)| strategy.as_str());
+                let target = self.target_cols.iter().find(|t| t.col_idx == i);
+
+                // A redact rule replaces the value with its fixed
+                // placeholder, cycled or cut to the original's character
+                // count when the rule preserves length
+                if let Some(spec) = target.and_then(|t| t.redact.as_ref()) {
+                    let original = String::from_utf8_lossy(val).to_string();
+                    let fake_val = spec.apply(&original);
+                    val.clear();
+                    val.extend_from_slice(fake_val.as_bytes());
+                    changed_any = true;
+                    self.state.record_masking("redact").await;
+                    changes_log.push(json!({
+                        "column_idx": i,
+                        "column_name": self.column_names.get(i).unwrap_or(&"?".to_string()),
+                        "strategy": "redact",
+                        "original": original_val_preview,
+                        "masked": fake_val
+                    }));
+                    continue;
+                }
+
+                let explicit_strategy = target.map(|t| (t.chain.clone(), t.tuning.clone()));
+
+                // A rule binding the `null` strategy withholds the cell
+                // entirely; the text protocol carries it as the NULL byte
+                // and a binary row flips its NULL-bitmap bit
+                if explicit_strategy.as_ref().and_then(|(chain, _)| chain.as_single())
+                    == Some(&Strategy::Null)
+                {
+                    changed_any = true;
+                    self.state.record_masking("null").await;
+                    changes_log.push(json!({
+                        "column_idx": i,
+                        "column_name": self.column_names.get(i).unwrap_or(&"?".to_string()),
+                        "strategy": "null",
+                        "original": original_val_preview,
+                        "masked": null
+                    }));
+                    *val_opt = None;
+                    continue;
+                }
 
-                // Handle explicit JSON strategy
-                if let Some("json") = explicit_strategy
+                // Handle explicit JSON strategy (always a single-stage
+                // chain: validation rejects json combined with other stages)
+                if let Some((chain, tuning)) = &explicit_strategy
+                    && chain.as_single() == Some(&Strategy::Json)
                     && let Ok(s) = std::str::from_utf8(val)
-                    && let Ok(mut json_val) = serde_json::from_str::<serde_json::Value>(s)
                 {
-                    mask_json_recursively(&mut json_val, &self.scanner);
-                    if let Ok(new_json) = serde_json::to_string(&json_val)
-                        && new_json.as_bytes() != &val[..]
-                    {
-                        val.clear();
-                        val.extend_from_slice(new_json.as_bytes());
-                        changed_any = true;
-                        // Record masking stats for JSON
-                        self.state.record_masking("json").await;
-                        changes_log.push(json!({
-                            "column_idx": i,
-                            "column_name": self.column_names.get(i).unwrap_or(&"?".to_string()),
-                            "strategy": "json",
-                            "original": original_val_preview,
-                            "masked": "(JSON Masked)"
-                        }));
+                    match serde_json::from_str::<serde_json::Value>(s) {
+                        Ok(mut json_val) => {
+                            // With configured pointer paths only those are
+                            // masked; without, every string is scanned
+                            match &tuning.json_paths {
+                                Some(paths) => mask_json_paths(
+                                    &mut json_val,
+                                    paths.as_ref(),
+                                    &self.state.strategy_registry,
+                                    &hashing,
+                                    tuning,
+                                ),
+                                None => mask_json_recursively(&mut json_val, &self.scanner),
+                            }
+                            if let Ok(new_json) = serde_json::to_string(&json_val)
+                                && new_json.as_bytes() != &val[..]
+                            {
+                                val.clear();
+                                val.extend_from_slice(new_json.as_bytes());
+                                changed_any = true;
+                                // Record masking stats for JSON
+                                self.state.record_masking("json").await;
+                                changes_log.push(json!({
+                                    "column_idx": i,
+                                    "column_name": self.column_names.get(i).unwrap_or(&"?".to_string()),
+                                    "strategy": "json",
+                                    "original": original_val_preview,
+                                    "masked": "(JSON Masked)"
+                                }));
+                            }
+                            continue;
+                        }
+                        // A path-targeted rule leaves a malformed document
+                        // alone rather than flattening it to a placeholder
+                        Err(e) if tuning.json_paths.is_some() => {
+                            tracing::warn!(
+                                column_idx = i,
+                                error = %e,
+                                "Value bound to a json rule with json_paths is not valid JSON; leaving it unmasked"
+                            );
+                            continue;
+                        }
+                        // A plain json rule on a non-JSON value falls
+                        // through to generic masking, as before
+                        Err(_) => {}
                     }
-                    continue;
                 }
 
                 let strategy = if let Some(s) = explicit_strategy {
                     Some(s)
-                } else {
+                } else if heuristics_enabled {
                     // Heuristic scan
                     if let Ok(s) = std::str::from_utf8(val) {
-                        self.scanner.scan(s).map(pii_type_to_strategy)
+                        let candidates = self
+                            .scanner
+                            .scan_scored(s, self.column_names.get(i).map(String::as_str));
+                        if !candidates.is_empty() {
+                            // The full scored list, so an operator can tune
+                            // scanner.min_confidence from real traffic
+                            tracing::debug!(
+                                column = ?self.column_names.get(i),
+                                candidates = ?candidates,
+                                "Heuristic PII candidates"
+                            );
+                        }
+                        match candidates.into_iter().next() {
+                            Some((pii_type, confidence)) if confidence >= min_confidence => {
+                                self.state.report_detection(crate::alerts::Detection {
+                                    pii_type: pii_type.clone(),
+                                    column: self.column_names.get(i).cloned(),
+                                    policy,
+                                    severity: self.scanner.custom_severity(pii_type.name()),
+                                    confidence: Some(confidence),
+                                });
+                                Some((
+                                    StrategyChain::from(pii_type_to_strategy(
+                                        pii_type,
+                                        &self.scanner,
+                                    )),
+                                    StrategyTuning::default(),
+                                ))
+                            }
+                            // Below the threshold: leave the value, the
+                            // debug line above records what was close
+                            Some(_) => None,
+                            // Whole-value miss: substring mode can still
+                            // find PII embedded in free text and rewrite
+                            // just those spans
+                            None if scan_substrings => {
+                                if let Some((masked, types)) =
+                                    mask_pii_substrings(s, &self.scanner)
+                                {
+                                    for pii_type in types {
+                                        self.state.report_detection(
+                                            crate::alerts::Detection {
+                                                pii_type: pii_type.clone(),
+                                                column: self.column_names.get(i).cloned(),
+                                                policy,
+                                                severity: self
+                                                    .scanner
+                                                    .custom_severity(pii_type.name()),
+                                                confidence: None,
+                                            },
+                                        );
+                                    }
+                                    val.clear();
+                                    val.extend_from_slice(masked.as_bytes());
+                                    changed_any = true;
+                                    self.state.record_masking("other").await;
+                                    changes_log.push(json!({
+                                        "column_idx": i,
+                                        "column_name": self.column_names.get(i).unwrap_or(&"?".to_string()),
+                                        "strategy": "substring (heuristic)",
+                                        "original": original_val_preview,
+                                        "masked": masked
+                                    }));
+                                    continue;
+                                }
+                                None
+                            }
+                            None => None,
+                        }
                     } else {
                         None
                     }
+                } else {
+                    None
                 };
 
-                if let Some(strat) = strategy {
-                    use std::collections::hash_map::DefaultHasher;
-                    use std::hash::{Hash, Hasher};
-
-                    let mut hasher = DefaultHasher::new();
-                    val.hash(&mut hasher);
-                    let seed = hasher.finish();
+                if let Some((strat, tuning)) = strategy {
+                    // A sibling-seeded rule takes its seed from the seed
+                    // column's value instead of the cell's own
+                    let seed = match seed_overrides.get(&i) {
+                        Some(seed) => *seed,
+                        None => cell_seed(&tuning, val),
+                    };
 
-                    let fake_val = generate_fake_data(strat, seed);
+                    let original = String::from_utf8_lossy(val).to_string();
+                    let registry = &self.state.strategy_registry;
+                    // A sibling-seeded output depends on another column, so
+                    // the (chain, seed) memo key no longer identifies it
+                    let (fake_val, memo_hit) = if seed_overrides.contains_key(&i) {
+                        (
+                            mask_chain(registry, &hashing, &tuning, &strat, &original, seed),
+                            false,
+                        )
+                    } else {
+                        self.memo.get_or_compute(&strat, &tuning, seed, || {
+                            mask_chain(registry, &hashing, &tuning, &strat, &original, seed)
+                        })
+                    };
+                    crate::metrics::record_memo_lookup(memo_hit);
+                    let fake_val = verify_masked_output(
+                        &self.state,
+                        &self.scanner,
+                        verify_output,
+                        self.connection_id,
+                        i,
+                        &original,
+                        fake_val,
+                        seed,
+                    )
+                    .await;
 
                     val.clear();
                     val.extend_from_slice(fake_val.as_bytes());
                     changed_any = true;
 
-                    // Record masking stats
-                    self.state.record_masking(strat).await;
+                    // Record masking stats; a chained cell is attributed to
+                    // the whole chain, not to each stage
+                    self.state.record_masking(&strat.label()).await;
 
                     changes_log.push(json!({
                         "column_idx": i,
                         "column_name": self.column_names.get(i).unwrap_or(&"?".to_string()),
-                        "strategy": strat,
+                        "strategy": strat.to_string(),
                         "original": original_val_preview,
                         "masked": fake_val
                     }));
@@ -514,6 +2753,9 @@ impl MySqlPacketInterceptor for MySqlAnonymizer {
         }
 
         if changed_any {
+            self.state
+                .record_client_masked_row(self.connection_id, changes_log.len() as u64)
+                .await;
             let id = format!("{:x}", rand::random::<u128>());
             self.state
                 .add_log(LogEntry {
@@ -531,113 +2773,986 @@ impl MySqlPacketInterceptor for MySqlAnonymizer {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "postgres"))]
 mod tests {
     use super::*;
-    use crate::config::{AppConfig, MaskingRule};
-    use crate::protocol::postgres::{FieldDescription, RowDescription};
+
+    fn no_custom() -> StrategyRegistry {
+        StrategyRegistry::default()
+    }
+
+    fn default_tuning() -> StrategyTuning {
+        StrategyTuning::default()
+    }
+
+    fn no_hash() -> HashSpec {
+        HashSpec::default()
+    }
+    use crate::config::{AppConfig, ApplicationNamePolicy, MaskingRule};
+    use crate::protocol::testing::ResultSetFixture;
     use crate::state::AppState;
     use bytes::BytesMut;
+    use std::fs;
+    use std::path::{Path, PathBuf};
 
-    #[tokio::test]
-    async fn test_heuristic_detection() {
+    /// A golden masking scenario: input result set + rules → expected masked
+    /// result set. Covering a new strategy means adding a fixture file, not a
+    /// test; regenerate expectations with `IRONVEIL_UPDATE_FIXTURES=1 cargo
+    /// test` after an intentional behavior change.
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct MaskingFixture {
+        description: String,
+        #[serde(default = "fixture_masking_enabled")]
+        masking_enabled: bool,
+        #[serde(default)]
+        rules: Vec<MaskingRule>,
+        input: ResultSetFixture,
+        expected: ResultSetFixture,
+    }
+
+    fn fixture_masking_enabled() -> bool {
+        true
+    }
+
+    fn fixture_state(fixture: &MaskingFixture) -> AppState {
         let config = AppConfig {
-            masking_enabled: true,
-            rules: vec![],
-            tls: None,
-            upstream_tls: false,
-            telemetry: None,
-            api: None,
-            limits: None,
-            health_check: None,
-            audit: None,
+            masking_enabled: fixture.masking_enabled,
+            rules: fixture.rules.clone(),
+            ..Default::default()
         };
-        let state = AppState::new_for_test(config, "proxy.yaml".to_string());
-        let mut anonymizer = Anonymizer::new(state, 1);
+        AppState::new_for_test(config, "proxy.yaml".to_string())
+    }
 
-        // Create a DataRow with an email
-        let email = "test@example.com";
-        let other = "some data";
-        let mut row = DataRow {
-            values: vec![
-                Some(BytesMut::from(email.as_bytes())),
-                Some(BytesMut::from(other.as_bytes())),
-            ],
-        };
+    async fn run_fixture_postgres(fixture: &MaskingFixture) -> ResultSetFixture {
+        let mut anonymizer = Anonymizer::new(fixture_state(fixture), 1);
+        let (description, rows) = fixture.input.to_postgres();
+        anonymizer.on_row_description(&description).await;
+        let mut masked = Vec::new();
+        for row in rows {
+            masked.push(anonymizer.on_data_row(row).await.unwrap());
+        }
+        ResultSetFixture::from_postgres(&description, &masked)
+    }
 
-        // Process the row
-        row = anonymizer.on_data_row(row).await.unwrap();
+    #[cfg(feature = "mysql")]
+    async fn run_fixture_mysql(fixture: &MaskingFixture) -> ResultSetFixture {
+        let mut anonymizer = MySqlAnonymizer::new(fixture_state(fixture), 1);
+        let (columns, rows) = fixture.input.to_mysql("users");
+        anonymizer.reset_columns();
+        for column in &columns {
+            anonymizer.on_column_definition(column).await;
+        }
+        let mut masked = Vec::new();
+        for row in rows {
+            masked.push(anonymizer.on_result_row(row).await.unwrap());
+        }
+        ResultSetFixture::from_mysql(&columns, &masked)
+    }
+
+    #[tokio::test]
+    async fn test_masking_golden_fixtures() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/masking");
+        let update = std::env::var("IRONVEIL_UPDATE_FIXTURES").is_ok();
+        let mut paths: Vec<PathBuf> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        paths.sort();
+        assert!(!paths.is_empty(), "no fixtures found in {:?}", dir);
+
+        for path in paths {
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            let mut fixture: MaskingFixture =
+                serde_yaml::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
 
-        // Check results
-        let val0 = std::str::from_utf8(row.values[0].as_ref().unwrap()).unwrap();
-        let val1 = std::str::from_utf8(row.values[1].as_ref().unwrap()).unwrap();
+            let actual = run_fixture_postgres(&fixture).await;
+            if update {
+                fixture.expected = actual;
+                fs::write(&path, serde_yaml::to_string(&fixture).unwrap()).unwrap();
+                continue;
+            }
+            assert_eq!(
+                actual, fixture.expected,
+                "{}: postgres output diverged ({})",
+                name, fixture.description
+            );
 
-        assert_ne!(val0, email, "Email should be masked");
-        assert!(val0.contains("@"), "Masked value should still be an email");
-        assert_eq!(val1, other, "Non-PII data should be unchanged");
+            // Both paths share the masking core, so the same fixture must
+            // mask identically through the MySQL interceptor
+            #[cfg(feature = "mysql")]
+            {
+                let actual = run_fixture_mysql(&fixture).await;
+                assert_eq!(
+                    actual, fixture.expected,
+                    "{}: mysql output diverged ({})",
+                    name, fixture.description
+                );
+            }
+        }
     }
 
+    /// Binary-protocol rows reuse the text masking core: string-typed cells
+    /// are masked in place, everything else keeps its raw wire bytes.
+    #[cfg(feature = "mysql")]
     #[tokio::test]
-    async fn test_explicit_rule_overrides_heuristic() {
+    async fn test_mysql_binary_rows_mask_text_cells_only() {
+        use crate::config::TypeMismatchPolicy;
+        use crate::protocol::mysql::{BinaryCell, BinaryRow};
+
         let config = AppConfig {
-            masking_enabled: true,
             rules: vec![MaskingRule {
+                id: None,
                 table: None,
-                column: "email_col".to_string(),
-                strategy: "address".to_string(), // Intentionally wrong strategy to prove override
+                column: "email".to_string(),
+                case_sensitive: false,
+                table_regex: None,
+                column_regex: None,
+                database: None,
+                compiled: Default::default(),
+                redact_text: None,
+                preserve_length: false,
+                date_shift_days: None,
+                seed_column: None,
+                noise_percent: None,
+                ip_mode: None,
+                preserve_domain: false,
+                domain_allowlist: Vec::new(),
+                json_paths: None,
+                pattern: None,
+                replacement: None,
+                wasm_module: None,
+                wasm_fuel: None,
+                deterministic: true,
+                strategy: Strategy::Email.into(),
+                composite_fields: None,
+                on_type_mismatch: TypeMismatchPolicy::Fallback,
+                when: None,
             }],
-            tls: None,
-            upstream_tls: false,
-            telemetry: None,
-            api: None,
-            limits: None,
-            health_check: None,
-            audit: None,
+            ..Default::default()
         };
-        let state = AppState::new_for_test(config, "proxy.yaml".to_string());
-        let mut anonymizer = Anonymizer::new(state, 1);
+        let mut anonymizer =
+            MySqlAnonymizer::new(AppState::new_for_test(config, "proxy.yaml".to_string()), 1);
 
-        let desc = RowDescription {
-            fields: vec![FieldDescription {
-                name: bytes::Bytes::from_static(b"email_col"),
-                table_oid: 0,
-                column_index: 0,
-                type_oid: 0,
-                type_len: 0,
-                type_modifier: 0,
-                format_code: 0,
-            }],
+        let fixture: ResultSetFixture =
+            serde_yaml::from_str("columns: [id, email]\nrows: []").unwrap();
+        let (columns, _) = fixture.to_mysql("users");
+        anonymizer.reset_columns();
+        for column in &columns {
+            anonymizer.on_column_definition(column).await;
+        }
+
+        let row = BinaryRow {
+            sequence_id: 5,
+            values: vec![
+                BinaryCell::Other(BytesMut::from(&[42u8, 0, 0, 0][..])),
+                BinaryCell::Text(BytesMut::from(&b"user@example.com"[..])),
+            ],
         };
+        let masked = anonymizer.on_binary_row(row).await.unwrap();
+        assert!(matches!(&masked.values[0], BinaryCell::Other(v) if v[..] == [42, 0, 0, 0]));
+        match &masked.values[1] {
+            BinaryCell::Text(v) => assert_ne!(&v[..], &b"user@example.com"[..]),
+            other => panic!("text cell changed kind: {:?}", other),
+        }
+    }
 
-        anonymizer.on_row_description(&desc).await;
+    /// Three clients from different source ranges, same user: the office VPN
+    /// sees partially masked data, the application subnet gets full masking,
+    /// and everywhere else is rejected before any result flows.
+    #[tokio::test]
+    async fn test_source_policies_drive_masking_per_connection() {
+        use crate::config::{PolicyAction, SourcePolicy};
+        use crate::hooks::resolve_source_policy;
 
-        let email = "test@example.com";
-        let mut row = DataRow {
-            values: vec![Some(BytesMut::from(email.as_bytes()))],
+        let policies = vec![
+            // Office VPN
+            SourcePolicy {
+                cidr: Some("10.8.0.0/16".to_string()),
+                application_name: None,
+                parameters: Default::default(),
+                action: PolicyAction::Partial,
+                priority: 10,
+                sampling: None,
+            },
+            // Application subnet (IPv6)
+            SourcePolicy {
+                cidr: Some("fd00:a::/32".to_string()),
+                application_name: None,
+                parameters: Default::default(),
+                action: PolicyAction::Mask,
+                priority: 10,
+                sampling: None,
+            },
+            // Everything else
+            SourcePolicy {
+                cidr: Some("0.0.0.0/0".to_string()),
+                application_name: None,
+                parameters: Default::default(),
+                action: PolicyAction::Reject,
+                priority: 0,
+                sampling: None,
+            },
+            SourcePolicy {
+                cidr: Some("::/0".to_string()),
+                application_name: None,
+                parameters: Default::default(),
+                action: PolicyAction::Reject,
+                priority: 0,
+                sampling: None,
+            },
+        ];
+
+        let config = AppConfig {
+            rules: vec![MaskingRule {
+                id: None,
+                table: None,
+                column: "comment".to_string(),
+                case_sensitive: false,
+                table_regex: None,
+                column_regex: None,
+                database: None,
+                compiled: Default::default(),
+                redact_text: None,
+                preserve_length: false,
+                date_shift_days: None,
+                seed_column: None,
+                noise_percent: None,
+                ip_mode: None,
+                preserve_domain: false,
+                domain_allowlist: Vec::new(),
+                json_paths: None,
+                pattern: None,
+                replacement: None,
+                wasm_module: None,
+                wasm_fuel: None,
+                deterministic: true,
+                strategy: Strategy::Address.into(),
+                composite_fields: None,
+                on_type_mismatch: TypeMismatchPolicy::Fallback,
+                when: None,
+            }],
+            policies_by_source: policies.clone(),
+            ..Default::default()
         };
+        let state = AppState::new_for_test(config, "proxy.yaml".to_string());
 
-        row = anonymizer.on_data_row(row).await.unwrap();
-        let val0 = std::str::from_utf8(row.values[0].as_ref().unwrap()).unwrap();
+        let input = ResultSetFixture {
+            columns: vec!["comment".to_string(), "contact".to_string()],
+            rows: vec![vec![
+                Some("call me".to_string()),
+                Some("alice@example.com".to_string()),
+            ]],
+        };
+        let (description, rows) = input.to_postgres();
 
-        // Should look like a city, not an email
-        assert!(
-            !val0.contains("@"),
-            "Should be masked as address, not email"
+        let office: std::net::IpAddr = "10.8.3.4".parse().unwrap();
+        let app: std::net::IpAddr = "fd00:a::17".parse().unwrap();
+        let elsewhere: std::net::IpAddr = "203.0.113.9".parse().unwrap();
+
+        // Anywhere outside the known ranges is rejected outright
+        assert_eq!(
+            resolve_source_policy(&policies, elsewhere, &[]).map(|p| p.action),
+            Some(PolicyAction::Reject)
+        );
+
+        // Office VPN: the explicit rule applies, but the heuristically
+        // detected email survives
+        let action = resolve_source_policy(&policies, office, &[]).unwrap().action;
+        state.set_policy_action(1, action).await;
+        let mut anonymizer = Anonymizer::new(state.clone(), 1);
+        anonymizer.on_row_description(&description).await;
+        let masked = anonymizer.on_data_row(rows[0].clone()).await.unwrap();
+        let partial = ResultSetFixture::from_postgres(&description, &[masked]);
+        assert_ne!(partial.rows[0][0].as_deref(), Some("call me"));
+        assert_eq!(partial.rows[0][1].as_deref(), Some("alice@example.com"));
+
+        // Application subnet: full masking also rewrites the detected email
+        let action = resolve_source_policy(&policies, app, &[]).unwrap().action;
+        state.set_policy_action(2, action).await;
+        let mut anonymizer = Anonymizer::new(state.clone(), 2);
+        anonymizer.on_row_description(&description).await;
+        let masked = anonymizer.on_data_row(rows[0].clone()).await.unwrap();
+        let full = ResultSetFixture::from_postgres(&description, &[masked]);
+        assert_ne!(full.rows[0][0].as_deref(), Some("call me"));
+        assert_ne!(full.rows[0][1].as_deref(), Some("alice@example.com"));
+    }
+
+    fn resolver_state(rules: Vec<MaskingRule>, expression_handling: ExpressionHandling) -> AppState {
+        let config = AppConfig {
+            rules,
+            expression_handling,
+            ..Default::default()
+        };
+        AppState::new_for_test(config, "proxy.yaml".to_string())
+    }
+
+    fn rule_on(table: Option<&str>, column: &str) -> MaskingRule {
+        MaskingRule {
+            id: None,
+            table: table.map(str::to_string),
+            column: column.to_string(),
+            case_sensitive: false,
+            table_regex: None,
+            column_regex: None,
+            database: None,
+            compiled: Default::default(),
+            redact_text: None,
+            preserve_length: false,
+            date_shift_days: None,
+            seed_column: None,
+            noise_percent: None,
+            ip_mode: None,
+            preserve_domain: false,
+            domain_allowlist: Vec::new(),
+            json_paths: None,
+            pattern: None,
+            replacement: None,
+            wasm_module: None,
+            wasm_fuel: None,
+            deterministic: true,
+            strategy: Strategy::Address.into(),
+            composite_fields: None,
+            on_type_mismatch: TypeMismatchPolicy::Fallback,
+            when: None,
+        }
+    }
+
+    async fn mask_one(
+        state: &AppState,
+        sql: Option<&str>,
+        input: &ResultSetFixture,
+    ) -> ResultSetFixture {
+        let mut anonymizer = Anonymizer::new(state.clone(), 1);
+        if let Some(sql) = sql {
+            anonymizer.on_query(sql).await;
+        }
+        let (description, rows) = input.to_postgres();
+        anonymizer.on_row_description(&description).await;
+        let masked = anonymizer.on_data_row(rows[0].clone()).await.unwrap();
+        ResultSetFixture::from_postgres(&description, &[masked])
+    }
+
+    /// Like [`mask_one`] but feeds every row through a single anonymizer, so
+    /// the per-connection memo sees the repeats.
+    async fn mask_all(state: &AppState, input: &ResultSetFixture) -> ResultSetFixture {
+        let mut anonymizer = Anonymizer::new(state.clone(), 1);
+        let (description, rows) = input.to_postgres();
+        anonymizer.on_row_description(&description).await;
+        let mut masked = Vec::with_capacity(rows.len());
+        for row in rows {
+            masked.push(anonymizer.on_data_row(row).await.unwrap());
+        }
+        ResultSetFixture::from_postgres(&description, &masked)
+    }
+
+    /// Aliasing a sensitive column must not bypass its rule, and aliasing a
+    /// harmless column AS a sensitive name must not pick the rule up.
+    #[tokio::test]
+    async fn test_alias_resolution_during_rule_binding() {
+        let state = resolver_state(vec![rule_on(None, "notes")], ExpressionHandling::Heuristic);
+
+        // `notes AS remarks`: display name misses the rule, origin hits it
+        let input = ResultSetFixture {
+            columns: vec!["remarks".to_string()],
+            rows: vec![vec![Some("internal memo".to_string())]],
+        };
+        let sql = "SELECT notes AS remarks FROM users";
+        let masked = mask_one(&state, Some(sql), &input).await;
+        assert_ne!(masked.rows[0][0].as_deref(), Some("internal memo"));
+
+        // Without the query text the display name is all we have
+        let unmasked = mask_one(&state, None, &input).await;
+        assert_eq!(unmasked.rows[0][0].as_deref(), Some("internal memo"));
+
+        // `status AS notes`: the display name matches the rule but the
+        // underlying column does not, so the value passes through
+        let input = ResultSetFixture {
+            columns: vec!["notes".to_string()],
+            rows: vec![vec![Some("active".to_string())]],
+        };
+        let sql = "SELECT status AS notes FROM users";
+        let masked = mask_one(&state, Some(sql), &input).await;
+        assert_eq!(masked.rows[0][0].as_deref(), Some("active"));
+    }
+
+    /// Glob rules cover families of column names; an exact-name rule wins
+    /// over a glob when both apply, and matching ignores case unless the
+    /// rule opts into sensitivity.
+    #[tokio::test]
+    async fn test_glob_column_rules_and_precedence() {
+        // Values the content heuristics would never flag, so any change
+        // is attributable to the glob rule alone
+        let glob = rule_on(None, "*_email");
+        let state = resolver_state(vec![glob], ExpressionHandling::Heuristic);
+
+        let input = ResultSetFixture {
+            columns: vec![
+                "billing_email".to_string(),
+                "Contact_Email".to_string(),
+                "email_count".to_string(),
+            ],
+            rows: vec![vec![
+                Some("HQ front desk".to_string()),
+                Some("ask reception".to_string()),
+                Some("7".to_string()),
+            ]],
+        };
+        let masked = mask_one(&state, None, &input).await;
+        assert_ne!(masked.rows[0][0].as_deref(), Some("HQ front desk"));
+        // Case-insensitive by default
+        assert_ne!(masked.rows[0][1].as_deref(), Some("ask reception"));
+        // The suffix pattern does not cover a prefix use of the word
+        assert_eq!(masked.rows[0][2].as_deref(), Some("7"));
+
+        // An exact-name rule takes precedence over a glob listed first:
+        // the exact rule's email strategy keeps the value email-shaped,
+        // the glob's hash strategy would not
+        let mut glob = rule_on(None, "*_email");
+        glob.strategy = Strategy::Hash.into();
+        let mut exact = rule_on(None, "billing_email");
+        exact.strategy = Strategy::Email.into();
+        let state = resolver_state(vec![glob, exact], ExpressionHandling::Heuristic);
+        let input = ResultSetFixture {
+            columns: vec!["billing_email".to_string()],
+            rows: vec![vec![Some("bill@example.com".to_string())]],
+        };
+        let masked = mask_one(&state, None, &input).await;
+        let value = masked.rows[0][0].as_deref().unwrap();
+        assert_ne!(value, "bill@example.com");
+        assert!(value.contains('@'), "glob rule shadowed the exact rule: {}", value);
+
+        // Opting into case sensitivity stops the cross-case match
+        let mut sensitive = rule_on(None, "*_email");
+        sensitive.case_sensitive = true;
+        let state = resolver_state(vec![sensitive], ExpressionHandling::Heuristic);
+        let input = ResultSetFixture {
+            columns: vec!["Contact_Email".to_string()],
+            rows: vec![vec![Some("ask reception".to_string())]],
+        };
+        let masked = mask_one(&state, None, &input).await;
+        assert_eq!(masked.rows[0][0].as_deref(), Some("ask reception"));
+    }
+
+    /// A table-scoped rule only fires for the table it names, even when a
+    /// join exposes the same column name from two relations.
+    #[tokio::test]
+    async fn test_table_scoped_rule_in_a_join() {
+        let state = resolver_state(
+            vec![rule_on(Some("users"), "code")],
+            ExpressionHandling::Heuristic,
         );
+
+        let input = ResultSetFixture {
+            columns: vec!["ucode".to_string(), "ocode".to_string()],
+            rows: vec![vec![
+                Some("u-1234".to_string()),
+                Some("o-5678".to_string()),
+            ]],
+        };
+        let sql = "SELECT u.code AS ucode, o.code AS ocode \
+                   FROM users u JOIN orders o ON u.id = o.user_id";
+        let masked = mask_one(&state, Some(sql), &input).await;
+        assert_ne!(masked.rows[0][0].as_deref(), Some("u-1234"));
+        assert_eq!(masked.rows[0][1].as_deref(), Some("o-5678"));
+    }
+
+    /// A `database`-scoped rule fires only for connections to a matching
+    /// database, and fails closed when the client named none.
+    #[tokio::test]
+    async fn test_database_scoped_rule() {
+        async fn mask_in(
+            state: &AppState,
+            database: Option<&str>,
+            input: &ResultSetFixture,
+        ) -> ResultSetFixture {
+            let mut anonymizer = Anonymizer::new(state.clone(), 1);
+            anonymizer.on_startup(database).await;
+            let (description, rows) = input.to_postgres();
+            anonymizer.on_row_description(&description).await;
+            let masked = anonymizer.on_data_row(rows[0].clone()).await.unwrap();
+            ResultSetFixture::from_postgres(&description, &[masked])
+        }
+
+        let mut scoped = rule_on(None, "notes");
+        scoped.database = Some("app*".to_string());
+        let state = resolver_state(vec![scoped], ExpressionHandling::Heuristic);
+
+        let input = ResultSetFixture {
+            columns: vec!["notes".to_string()],
+            rows: vec![vec![Some("internal memo".to_string())]],
+        };
+
+        let masked = mask_in(&state, Some("app_prod"), &input).await;
+        assert_ne!(masked.rows[0][0].as_deref(), Some("internal memo"));
+
+        // The same `users` column in an unrelated database passes through
+        let other = mask_in(&state, Some("analytics"), &input).await;
+        assert_eq!(other.rows[0][0].as_deref(), Some("internal memo"));
+
+        // No database in the startup message: fail closed toward masking
+        let unknown = mask_in(&state, None, &input).await;
+        assert_ne!(unknown.rows[0][0].as_deref(), Some("internal memo"));
+    }
+
+    /// A CTE that renames a sensitive column still traces back to its source.
+    #[tokio::test]
+    async fn test_cte_rename_still_binds_rule() {
+        let state = resolver_state(vec![rule_on(None, "notes")], ExpressionHandling::Heuristic);
+
+        let input = ResultSetFixture {
+            columns: vec!["remarks".to_string()],
+            rows: vec![vec![Some("internal memo".to_string())]],
+        };
+        let sql = "WITH t AS (SELECT notes AS remarks FROM users) SELECT remarks FROM t";
+        let masked = mask_one(&state, Some(sql), &input).await;
+        assert_ne!(masked.rows[0][0].as_deref(), Some("internal memo"));
+    }
+
+    /// Expressions over a sensitive column are masked only when the operator
+    /// opted into `expression_handling: sensitive`.
+    #[tokio::test]
+    async fn test_expression_handling_modes() {
+        let input = ResultSetFixture {
+            columns: vec!["n".to_string()],
+            rows: vec![vec![Some("INTERNAL MEMO".to_string())]],
+        };
+        let sql = "SELECT upper(notes) AS n FROM users";
+
+        let state = resolver_state(vec![rule_on(None, "notes")], ExpressionHandling::Sensitive);
+        let masked = mask_one(&state, Some(sql), &input).await;
+        assert_ne!(masked.rows[0][0].as_deref(), Some("INTERNAL MEMO"));
+
+        let state = resolver_state(vec![rule_on(None, "notes")], ExpressionHandling::Heuristic);
+        let masked = mask_one(&state, Some(sql), &input).await;
+        assert_eq!(masked.rows[0][0].as_deref(), Some("INTERNAL MEMO"));
+    }
+
+    fn typed_description(cols: &[(&str, u32)]) -> RowDescription {
+        RowDescription {
+            fields: cols
+                .iter()
+                .map(|(name, oid)| crate::protocol::postgres::FieldDescription {
+                    name: bytes::Bytes::copy_from_slice(name.as_bytes()),
+                    table_oid: 0,
+                    column_index: 0,
+                    type_oid: *oid,
+                    type_len: -1,
+                    type_modifier: -1,
+                    format_code: 0,
+                })
+                .collect(),
+        }
+    }
+
+    fn typed_row(values: &[&str]) -> DataRow {
+        DataRow {
+            values: values
+                .iter()
+                .map(|v| Some(BytesMut::from(v.as_bytes())))
+                .collect(),
+        }
+    }
+
+    fn row_strings(row: &DataRow) -> Vec<String> {
+        row.values
+            .iter()
+            .map(|v| String::from_utf8_lossy(v.as_ref().unwrap()).to_string())
+            .collect()
+    }
+
+    /// Heuristics skip non-text columns: a credit-card-shaped int8 value is
+    /// left alone unless `scan_typed_columns` opts typed columns back in.
+    #[tokio::test]
+    async fn test_heuristics_skip_typed_columns() {
+        let description = typed_description(&[("id", 20), ("card", 20), ("contact", 25)]);
+        let row = typed_row(&["7", "4111111111111111", "alice@example.com"]);
+
+        let state = resolver_state(vec![], ExpressionHandling::Heuristic);
+        let mut anonymizer = Anonymizer::new(state, 1);
+        anonymizer.on_row_description(&description).await;
+        let masked = row_strings(&anonymizer.on_data_row(row.clone()).await.unwrap());
+        assert_eq!(masked[0], "7");
+        assert_eq!(masked[1], "4111111111111111");
+        assert_ne!(masked[2], "alice@example.com");
+
+        let config = AppConfig {
+            scan_typed_columns: true,
+            memo_entries: 4096,
+            log_buffer_entries: 100,
+            watch: false,
+            upstream: None,
+            ..Default::default()
+        };
+        let state = AppState::new_for_test(config, "proxy.yaml".to_string());
+        let mut anonymizer = Anonymizer::new(state, 1);
+        anonymizer.on_row_description(&description).await;
+        let masked = row_strings(&anonymizer.on_data_row(row).await.unwrap());
+        assert_ne!(masked[1], "4111111111111111");
+    }
+
+    /// Sibling of [`test_heuristics_skip_typed_columns`]:
+    /// `masking.heuristics_enabled: false` turns the content heuristics
+    /// off wholesale, so only explicit rules mask.
+    #[tokio::test]
+    async fn test_heuristics_disabled_masks_rules_only() {
+        let config = AppConfig {
+            rules: vec![rule_on(None, "contact")],
+            masking: Some(crate::config::MaskingConfig {
+                determinism_key: None,
+                heuristics_enabled: false,
+                bypass_users: vec![],
+                defaults: Default::default(),
+            }),
+            ..Default::default()
+        };
+        let state = AppState::new_for_test(config, "proxy.yaml".to_string());
+        let input = ResultSetFixture {
+            columns: vec!["contact".to_string(), "token".to_string()],
+            rows: vec![vec![
+                Some("alice@example.com".to_string()),
+                Some("ci-bot@build-7f3a.internal".to_string()),
+            ]],
+        };
+        let masked = mask_one(&state, None, &input).await;
+        // The ruled column still masks
+        assert_ne!(masked.rows[0][0].as_deref(), Some("alice@example.com"));
+        // The email-shaped machine token survives with heuristics off
+        assert_eq!(
+            masked.rows[0][1].as_deref(),
+            Some("ci-bot@build-7f3a.internal")
+        );
+    }
+
+    /// A text strategy bound to an int8 column resolves per the rule's
+    /// mismatch policy: fallback emits a type-valid zero, skip leaves the
+    /// value alone, apply masks regardless.
+    #[tokio::test]
+    async fn test_type_mismatch_policies() {
+        let description = typed_description(&[("id", 20)]);
+
+        for (policy, check) in [
+            (
+                TypeMismatchPolicy::Fallback,
+                Box::new(|v: &str| v == "0") as Box<dyn Fn(&str) -> bool>,
+            ),
+            (TypeMismatchPolicy::Skip, Box::new(|v: &str| v == "42")),
+            (TypeMismatchPolicy::Apply, Box::new(|v: &str| v.contains('@'))),
+        ] {
+            let mut rule = rule_on(None, "id");
+            rule.strategy = Strategy::Email.into();
+            rule.on_type_mismatch = policy;
+            let state = resolver_state(vec![rule], ExpressionHandling::Heuristic);
+            let mut anonymizer = Anonymizer::new(state, 1);
+            anonymizer.on_row_description(&description).await;
+            let masked = row_strings(&anonymizer.on_data_row(typed_row(&["42"])).await.unwrap());
+            assert!(check(&masked[0]), "policy {:?} produced {:?}", policy, masked[0]);
+        }
+    }
+
+    /// The typed strategies produce output the column's type still accepts:
+    /// noisy numbers stay numeric, shifted timestamps keep their time and
+    /// zone suffix.
+    #[tokio::test]
+    async fn test_typed_strategies_emit_type_valid_output() {
+        let mut amount_rule = rule_on(None, "amount");
+        amount_rule.strategy = Strategy::NumericNoise.into();
+        let mut created_rule = rule_on(None, "created");
+        created_rule.strategy = Strategy::DateShift.into();
+
+        let description = typed_description(&[("amount", 1700), ("created", 1184)]);
+        let row = typed_row(&["125000", "2024-06-15 10:30:00+00"]);
+
+        let state = resolver_state(vec![amount_rule, created_rule], ExpressionHandling::Heuristic);
+        let mut anonymizer = Anonymizer::new(state, 1);
+        anonymizer.on_row_description(&description).await;
+        let masked = row_strings(&anonymizer.on_data_row(row).await.unwrap());
+
+        let amount: i64 = masked[0].parse().expect("noisy amount is still an integer");
+        assert!((112_500..=137_500).contains(&amount));
+        assert!(masked[1].ends_with(" 10:30:00+00"));
+        chrono::NaiveDate::parse_from_str(&masked[1][..10], "%Y-%m-%d")
+            .expect("shifted timestamp still starts with a date");
+    }
+
+    /// A composite rule masks field by field and reassembles the literal:
+    /// embedded commas and doubled quotes survive the round trip, and the
+    /// per-position strategy lands on its field.
+    #[tokio::test]
+    async fn test_composite_rule_masks_fields_in_place() {
+        let mut rule = rule_on(None, "contact");
+        rule.composite_fields = Some(vec![None, Some(Strategy::Ssn.into())]);
+
+        // A user-defined composite type: the OID classifies as Other
+        let description = typed_description(&[("contact", 16384)]);
+        let original = r#"("doe, jane ""jd""",123-45-6789)"#;
+        let row = typed_row(&[original]);
+
+        let state = resolver_state(vec![rule], ExpressionHandling::Heuristic);
+        let mut anonymizer = Anonymizer::new(state, 1);
+        anonymizer.on_row_description(&description).await;
+        let masked = row_strings(&anonymizer.on_data_row(row).await.unwrap());
+
+        let seed = value_seed(original.as_bytes());
+        let expected_ssn = mask_value(&no_custom(), &no_hash(), &default_tuning(), &Strategy::Ssn, "123-45-6789", seed);
+        assert_eq!(masked[0], format!(r#"("doe, jane ""jd""",{})"#, expected_ssn));
+    }
+
+    /// Fields without a configured strategy get the content heuristics, and
+    /// NULL fields stay NULL.
+    #[tokio::test]
+    async fn test_composite_rule_heuristic_fields_and_nulls() {
+        let mut rule = rule_on(None, "contact");
+        rule.composite_fields = Some(vec![None, None, None]);
+
+        let description = typed_description(&[("contact", 16384)]);
+        let row = typed_row(&["(alice@example.com,,42)"]);
+
+        let state = resolver_state(vec![rule], ExpressionHandling::Heuristic);
+        let mut anonymizer = Anonymizer::new(state, 1);
+        anonymizer.on_row_description(&description).await;
+        let masked = row_strings(&anonymizer.on_data_row(row).await.unwrap());
+
+        // The heuristic path seeds from the field value, like a scalar scan
+        let expected_email = mask_value(&no_custom(), &no_hash(), &default_tuning(), &Strategy::Email, "alice@example.com", value_seed(b"alice@example.com"));
+        assert_eq!(masked[0], format!("({},,42)", expected_email));
+    }
+
+    /// A value that is not a well-formed composite — or that nests another
+    /// composite — resolves per the rule's mismatch policy.
+    #[tokio::test]
+    async fn test_composite_parse_failure_uses_mismatch_policy() {
+        for (policy, check) in [
+            (
+                TypeMismatchPolicy::Fallback,
+                Box::new(|v: &str| v == "MASKED") as Box<dyn Fn(&str) -> bool>,
+            ),
+            (TypeMismatchPolicy::Skip, Box::new(|v: &str| v == "not a composite")),
+            (TypeMismatchPolicy::Apply, Box::new(|v: &str| v.contains('@'))),
+        ] {
+            let mut rule = rule_on(None, "contact");
+            rule.strategy = Strategy::Email.into();
+            rule.composite_fields = Some(vec![Some(Strategy::Email.into())]);
+            rule.on_type_mismatch = policy;
+            let state = resolver_state(vec![rule], ExpressionHandling::Heuristic);
+            let mut anonymizer = Anonymizer::new(state, 1);
+            anonymizer
+                .on_row_description(&typed_description(&[("contact", 16384)]))
+                .await;
+
+            let row = typed_row(&["not a composite"]);
+            let masked = row_strings(&anonymizer.on_data_row(row).await.unwrap());
+            assert!(check(&masked[0]), "policy {:?} produced {:?}", policy, masked[0]);
+
+            let nested = typed_row(&[r#"("(1,2)",x)"#]);
+            let masked = row_strings(&anonymizer.on_data_row(nested).await.unwrap());
+            assert!(
+                check(&masked[0]) || policy == TypeMismatchPolicy::Skip,
+                "policy {:?} produced {:?} for a nested composite",
+                policy,
+                masked[0]
+            );
+        }
+    }
+
+    /// Range columns are never treated as text: the typed strategies land on
+    /// each bound with the brackets intact, and a text strategy resolves per
+    /// the mismatch policy instead of clobbering the literal.
+    #[test]
+    fn test_custom_strategy_registry_applies() {
+        let mut registry = StrategyRegistry::default();
+        registry.register("upper", |value: &str, _seed| value.to_uppercase());
+        let chain = StrategyChain::from(Strategy::Custom("upper".to_string()));
+
+        assert_eq!(mask_chain(&registry, &no_hash(), &default_tuning(), &chain, "alice", 7), "ALICE");
+        // An unregistered name keeps the static placeholder
+        let unknown = StrategyChain::from(Strategy::Custom("missing".to_string()));
+        assert_eq!(mask_chain(&registry, &no_hash(), &default_tuning(), &unknown, "alice", 7), "MASKED");
+    }
+
+    #[tokio::test]
+    async fn test_verify_output_redacts_leaked_values() {
+        let state = AppState::new_for_test(AppConfig::default(), "proxy.yaml".to_string());
+        let scanner = PiiScanner::new();
+        let verify = Some(VerifyOutputConfig {
+            mode: VerifyOutputMode::Full,
+            sample_percent: 10,
+        });
+
+        // A strategy emitting the original PII is caught and redacted
+        let out = verify_masked_output(
+            &state,
+            &scanner,
+            verify,
+            1,
+            0,
+            "alice@example.com",
+            "alice@example.com".to_string(),
+            7,
+        )
+        .await;
+        assert_eq!(out, VERIFY_REDACTION);
+        let alarms = state
+            .audit_logger
+            .get_entries_by_type(crate::audit::AuditEventType::MaskingVerificationFailed, None)
+            .await;
+        assert_eq!(alarms.len(), 1);
+
+        // Properly masked output passes through untouched
+        let out = verify_masked_output(
+            &state,
+            &scanner,
+            verify,
+            1,
+            0,
+            "alice@example.com",
+            "fake@example.org".to_string(),
+            7,
+        )
+        .await;
+        assert_eq!(out, "fake@example.org");
+
+        // Identity output that is not PII (numeric noise on zero) raises no
+        // alarm
+        let out =
+            verify_masked_output(&state, &scanner, verify, 1, 0, "0", "0".to_string(), 7).await;
+        assert_eq!(out, "0");
+
+        // Off mode never re-scans
+        let off = Some(VerifyOutputConfig {
+            mode: VerifyOutputMode::Off,
+            sample_percent: 10,
+        });
+        let out = verify_masked_output(
+            &state,
+            &scanner,
+            off,
+            1,
+            0,
+            "alice@example.com",
+            "alice@example.com".to_string(),
+            7,
+        )
+        .await;
+        assert_eq!(out, "alice@example.com");
+    }
+
+    /// Quarantine overrides everything: a column the alerting layer flagged
+    /// drops to a hard redaction regardless of rules or types, and clearing
+    /// it restores normal masking.
+    #[tokio::test]
+    async fn test_quarantined_column_hard_redacts_until_cleared() {
+        let state = AppState::new_for_test(AppConfig::default(), "proxy.yaml".to_string());
+        state
+            .quarantined_columns
+            .write()
+            .await
+            .insert("email".to_string());
+
+        let description = typed_description(&[("id", 20), ("email", 25)]);
+        let row = typed_row(&["7", "alice@example.com"]);
+
+        let mut anonymizer = Anonymizer::new(state.clone(), 1);
+        anonymizer.on_row_description(&description).await;
+        let masked = row_strings(&anonymizer.on_data_row(row.clone()).await.unwrap());
+        assert_eq!(masked[0], "7");
+        assert_eq!(masked[1], VERIFY_REDACTION);
+
+        // Clearing the quarantine puts the column back on the normal path:
+        // the heuristic scan masks it instead of redacting
+        state.quarantined_columns.write().await.remove("email");
+        let mut anonymizer = Anonymizer::new(state.clone(), 2);
+        anonymizer.on_row_description(&description).await;
+        let masked = row_strings(&anonymizer.on_data_row(row).await.unwrap());
+        assert_ne!(masked[1], "alice@example.com");
+        assert_ne!(masked[1], VERIFY_REDACTION);
+
+        // The heuristic hit was reported to the alerting channel with the
+        // column name attached
+        let mut rx = state.detection_rx.lock().unwrap().take().unwrap();
+        let detection = rx.try_recv().unwrap();
+        assert_eq!(detection.pii_type, crate::scanner::PiiType::Email);
+        assert_eq!(detection.column.as_deref(), Some("email"));
+    }
+
+    #[tokio::test]
+    async fn test_range_rules_keep_bounds() {
+        let mut span_rule = rule_on(None, "span");
+        span_rule.strategy = Strategy::NumericNoise.into();
+        let mut active_rule = rule_on(None, "active");
+        active_rule.strategy = Strategy::DateShift.into();
+        // An email strategy cannot fit int4range; the default fallback policy
+        // emits the empty range
+        let mut ids_rule = rule_on(None, "ids");
+        ids_rule.strategy = Strategy::Email.into();
+
+        // int4range, daterange, int4range
+        let description =
+            typed_description(&[("span", 3904), ("active", 3912), ("ids", 3904)]);
+        let row = typed_row(&["[100,200)", "[2024-01-10,2024-02-10)", "[1,10)"]);
+
+        let state = resolver_state(
+            vec![span_rule, active_rule, ids_rule],
+            ExpressionHandling::Heuristic,
+        );
+        let mut anonymizer = Anonymizer::new(state, 1);
+        anonymizer.on_row_description(&description).await;
+        let masked = row_strings(&anonymizer.on_data_row(row).await.unwrap());
+
+        let seed_of = |v: &str| value_seed(v.as_bytes());
+        let seed = seed_of("[100,200)");
+        assert_eq!(
+            masked[0],
+            format!(
+                "[{},{})",
+                mask_value(&no_custom(), &no_hash(), &default_tuning(), &Strategy::NumericNoise, "100", seed),
+                mask_value(&no_custom(), &no_hash(), &default_tuning(), &Strategy::NumericNoise, "200", seed)
+            )
+        );
+        let seed = seed_of("[2024-01-10,2024-02-10)");
+        assert_eq!(
+            masked[1],
+            format!(
+                "[{},{})",
+                mask_value(&no_custom(), &no_hash(), &default_tuning(), &Strategy::DateShift, "2024-01-10", seed),
+                mask_value(&no_custom(), &no_hash(), &default_tuning(), &Strategy::DateShift, "2024-02-10", seed)
+            )
+        );
+        assert_eq!(masked[2], "empty");
     }
 
     #[tokio::test]
     async fn test_json_masking() {
         let config = AppConfig {
             masking_enabled: true,
+            verify_output: None,
             rules: vec![],
+            listen: None,
             tls: None,
             upstream_tls: false,
+            upstream_tls_options: None,
+            routing: None,
             telemetry: None,
+            metrics: None,
             api: None,
             limits: None,
+            policies_by_source: vec![],
+            application_name_policy: ApplicationNamePolicy::Pin,
+            expression_handling: ExpressionHandling::Heuristic,
+            scan_typed_columns: false,
+            memo_entries: 4096,
+            log_buffer_entries: 100,
+            watch: false,
+            upstream: None,
             health_check: None,
             audit: None,
+            alerts: None,
+            admin: None,
+            oid_resolution: None,
+            schema_cache: None,
+            masking: None,
+            scanner: None,
+            hashing: None,
+            custom_strategies: Default::default(),
         };
         let state = AppState::new_for_test(config, "proxy.yaml".to_string());
         let mut anonymizer = Anonymizer::new(state, 1);
@@ -685,14 +3800,35 @@ mod tests {
     async fn test_array_masking() {
         let config = AppConfig {
             masking_enabled: true,
+            verify_output: None,
             rules: vec![],
+            listen: None,
             tls: None,
             upstream_tls: false,
+            upstream_tls_options: None,
+            routing: None,
             telemetry: None,
+            metrics: None,
             api: None,
             limits: None,
+            policies_by_source: vec![],
+            application_name_policy: ApplicationNamePolicy::Pin,
+            expression_handling: ExpressionHandling::Heuristic,
+            scan_typed_columns: false,
+            memo_entries: 4096,
+            log_buffer_entries: 100,
+            watch: false,
+            upstream: None,
             health_check: None,
             audit: None,
+            alerts: None,
+            admin: None,
+            oid_resolution: None,
+            schema_cache: None,
+            masking: None,
+            scanner: None,
+            hashing: None,
+            custom_strategies: Default::default(),
         };
         let state = AppState::new_for_test(config, "proxy.yaml".to_string());
         let mut anonymizer = Anonymizer::new(state, 1);
@@ -733,14 +3869,35 @@ mod tests {
     async fn test_deterministic_masking() {
         let config = AppConfig {
             masking_enabled: true,
+            verify_output: None,
             rules: vec![],
+            listen: None,
             tls: None,
             upstream_tls: false,
+            upstream_tls_options: None,
+            routing: None,
             telemetry: None,
+            metrics: None,
             api: None,
             limits: None,
+            policies_by_source: vec![],
+            application_name_policy: ApplicationNamePolicy::Pin,
+            expression_handling: ExpressionHandling::Heuristic,
+            scan_typed_columns: false,
+            memo_entries: 4096,
+            log_buffer_entries: 100,
+            watch: false,
+            upstream: None,
             health_check: None,
             audit: None,
+            alerts: None,
+            admin: None,
+            oid_resolution: None,
+            schema_cache: None,
+            masking: None,
+            scanner: None,
+            hashing: None,
+            custom_strategies: Default::default(),
         };
         let state = AppState::new_for_test(config, "proxy.yaml".to_string());
         let mut anonymizer = Anonymizer::new(state, 1);
@@ -766,61 +3923,1435 @@ mod tests {
         assert_ne!(val1, email, "Output should be different from input");
     }
 
+    /// The name strategies map a value to the same fake name within and
+    /// across queries, and `full_name` yields a "First Last" pair with both
+    /// parts deterministic.
+    #[tokio::test]
+    async fn test_name_strategies_deterministic() {
+        let seed = 7;
+        for strategy in [Strategy::FirstName, Strategy::LastName] {
+            let masked = mask_value(&no_custom(), &no_hash(), &default_tuning(), &strategy, "Alice", seed);
+            assert!(!masked.is_empty() && !masked.contains(' '), "{masked}");
+            assert_eq!(
+                masked,
+                mask_value(&no_custom(), &no_hash(), &default_tuning(), &strategy, "Alice", seed)
+            );
+        }
+
+        let mut rule = rule_on(None, "full_name");
+        rule.strategy = Strategy::FullName.into();
+        let state = resolver_state(vec![rule], ExpressionHandling::Heuristic);
+        let input = ResultSetFixture {
+            columns: vec!["full_name".to_string()],
+            rows: vec![vec![Some("Alice Smith".to_string())]],
+        };
+        // Two anonymizers stand in for two queries
+        let first = mask_one(&state, None, &input).await;
+        let second = mask_one(&state, None, &input).await;
+        assert_eq!(first.rows[0][0], second.rows[0][0]);
+
+        let full = first.rows[0][0].as_deref().unwrap();
+        assert_ne!(full, "Alice Smith");
+        let parts: Vec<&str> = full.split(' ').collect();
+        assert_eq!(parts.len(), 2, "{full}");
+        assert!(parts.iter().all(|part| !part.is_empty()), "{full}");
+    }
+
+    /// The memo is a pure cache: masking a result set full of repeats must
+    /// produce byte-identical output whether it is enabled or disabled.
+    #[tokio::test]
+    async fn test_memo_is_transparent_to_output() {
+        let input = ResultSetFixture {
+            columns: vec!["city".to_string()],
+            rows: vec![
+                vec![Some("Springfield".to_string())],
+                vec![Some("Springfield".to_string())],
+                vec![Some("Shelbyville".to_string())],
+                vec![Some("Springfield".to_string())],
+            ],
+        };
+
+        let with_memo = resolver_state(vec![rule_on(None, "city")], ExpressionHandling::Heuristic);
+        let without_memo =
+            resolver_state(vec![rule_on(None, "city")], ExpressionHandling::Heuristic);
+        without_memo.config.write().await.memo_entries = 0;
+
+        let memoized = mask_all(&with_memo, &input).await;
+        let recomputed = mask_all(&without_memo, &input).await;
+
+        assert_eq!(memoized, recomputed);
+        assert_eq!(
+            memoized.rows[0], memoized.rows[1],
+            "repeated values should mask identically"
+        );
+        assert_ne!(memoized.rows[0][0].as_deref(), Some("Springfield"));
+    }
+
+    /// Two rules with the same chain but different tuning must not serve
+    /// each other's memoized output: the same address in both columns once
+    /// replayed the domain-preserving result for the rule that demanded
+    /// full masking.
+    #[tokio::test]
+    async fn test_memo_keeps_same_chain_rules_with_different_tuning_apart() {
+        let mut preserving = rule_on(None, "audit_email");
+        preserving.strategy = Strategy::Email.into();
+        preserving.preserve_domain = true;
+        let mut full = rule_on(None, "personal_email");
+        full.strategy = Strategy::Email.into();
+
+        let state = resolver_state(vec![preserving, full], ExpressionHandling::Heuristic);
+        let input = ResultSetFixture {
+            columns: vec!["audit_email".to_string(), "personal_email".to_string()],
+            rows: vec![vec![
+                Some("bob@corp-internal.example".to_string()),
+                Some("bob@corp-internal.example".to_string()),
+            ]],
+        };
+        let masked = mask_one(&state, None, &input).await;
+
+        let kept = masked.rows[0][0].as_deref().unwrap();
+        assert_ne!(kept, "bob@corp-internal.example");
+        assert!(
+            kept.ends_with("@corp-internal.example"),
+            "preserve_domain rule lost the domain: {}",
+            kept
+        );
+
+        let replaced = masked.rows[0][1].as_deref().unwrap();
+        assert_ne!(replaced, "bob@corp-internal.example");
+        assert!(
+            !replaced.contains("corp-internal.example"),
+            "full-masking rule replayed the domain-preserving output: {}",
+            replaced
+        );
+    }
+
+    #[test]
+    fn test_memo_eviction_and_bypass() {
+        let mut memo = MaskMemo::new();
+        memo.sync(2, 1);
+        let strat = StrategyChain::from(Strategy::Email);
+        let tuning = StrategyTuning::default();
+
+        let (first, hit) = memo.get_or_compute(&strat, &tuning, 7, || "a".to_string());
+        assert_eq!((first.as_str(), hit), ("a", false));
+
+        // A hit replays the stored value without running the closure
+        let (again, hit) = memo.get_or_compute(&strat, &tuning, 7, || unreachable!());
+        assert_eq!((again.as_str(), hit), ("a", true));
+
+        // A full memo is cleared wholesale before the next insert
+        memo.get_or_compute(&strat, &tuning, 8, || "b".to_string());
+        memo.get_or_compute(&strat, &tuning, 9, || "c".to_string());
+        let (_, hit) = memo.get_or_compute(&strat, &tuning, 7, || "a".to_string());
+        assert!(!hit, "clearing on overflow should have dropped the entry");
+
+        // A ruleset generation bump invalidates everything
+        memo.sync(2, 2);
+        let (_, hit) = memo.get_or_compute(&strat, &tuning, 7, || "a".to_string());
+        assert!(!hit);
+
+        // Custom strategies are never memoized: the plugin may not be
+        // deterministic
+        let custom = StrategyChain::from(Strategy::Custom("redact_v2".to_string()));
+        memo.get_or_compute(&custom, &tuning, 7, || "x".to_string());
+        let (_, hit) = memo.get_or_compute(&custom, &tuning, 7, || "x".to_string());
+        assert!(!hit);
+
+        // Wasm stages too: the module's determinism is equally unknown,
+        // and two rules may point at different modules
+        let wasm = StrategyChain::from(Strategy::Wasm);
+        memo.get_or_compute(&wasm, &tuning, 7, || "y".to_string());
+        let (_, hit) = memo.get_or_compute(&wasm, &tuning, 7, || "y".to_string());
+        assert!(!hit);
+
+        // Capacity zero disables memoization outright
+        memo.sync(0, 2);
+        memo.get_or_compute(&strat, &tuning, 7, || "a".to_string());
+        let (_, hit) = memo.get_or_compute(&strat, &tuning, 7, || "a".to_string());
+        assert!(!hit);
+    }
+
+    /// A two-stage chain runs its stages in order — dob replaces the value
+    /// with its fixed date and date_shift then shifts that — and the
+    /// composite is deterministic in the input value.
     #[tokio::test]
-    async fn test_masking_can_be_disabled() {
+    async fn test_strategy_chain_masks_through_each_stage() {
+        let chain: StrategyChain = serde_yaml::from_str("[dob, date_shift]").unwrap();
+        chain.validate(&[]).unwrap();
+        let rule = MaskingRule {
+            id: None,
+            table: None,
+            column: "birthday".to_string(),
+            case_sensitive: false,
+            table_regex: None,
+            column_regex: None,
+            database: None,
+            compiled: Default::default(),
+            redact_text: None,
+            preserve_length: false,
+            date_shift_days: None,
+            seed_column: None,
+            noise_percent: None,
+            ip_mode: None,
+            preserve_domain: false,
+            domain_allowlist: Vec::new(),
+            json_paths: None,
+            pattern: None,
+            replacement: None,
+            wasm_module: None,
+            wasm_fuel: None,
+            deterministic: true,
+            strategy: chain.clone(),
+            // Fixture columns are text on the wire; apply the chain anyway
+            // rather than falling back to a placeholder
+            composite_fields: None,
+            on_type_mismatch: TypeMismatchPolicy::Apply,
+            when: None,
+        };
+        let state = resolver_state(vec![rule], ExpressionHandling::Heuristic);
+
+        let input = ResultSetFixture {
+            columns: vec!["birthday".to_string()],
+            rows: vec![
+                vec![Some("1987-06-05".to_string())],
+                vec![Some("1987-06-05".to_string())],
+            ],
+        };
+        let masked = mask_all(&state, &input).await;
+
+        // Deterministic: the repeated value masks identically
+        assert_eq!(masked.rows[0], masked.rows[1]);
+
+        // And the output is exactly the stage-by-stage composition
+        let seed = value_seed("1987-06-05".as_bytes());
+        let expected = mask_chain(&no_custom(), &no_hash(), &default_tuning(), &chain, "1987-06-05", seed);
+        assert_eq!(masked.rows[0][0].as_deref(), Some(expected.as_str()));
+        assert_eq!(
+            expected,
+            mask_value(&no_custom(), &no_hash(), &default_tuning(), &Strategy::DateShift, "1900-01-01", seed),
+            "second stage should consume the first stage's output"
+        );
+        assert_ne!(masked.rows[0][0].as_deref(), Some("1987-06-05"));
+    }
+
+    /// A `"null"` rule withholds the cell entirely: the masked row carries
+    /// a wire-level NULL, not an empty string, and untargeted columns are
+    /// untouched. The MySQL path must agree, including binary rows, where
+    /// the NULL lands in the bitmap.
+    #[tokio::test]
+    async fn test_null_strategy_emits_wire_null() {
+        let mut rule = rule_on(None, "ssn");
+        rule.strategy = Strategy::Null.into();
+        let state = resolver_state(vec![rule], ExpressionHandling::Heuristic);
+
+        let input = ResultSetFixture {
+            columns: vec!["ssn".to_string(), "city".to_string()],
+            rows: vec![vec![
+                Some("123-45-6789".to_string()),
+                Some("HQ front desk".to_string()),
+            ]],
+        };
+        let masked = mask_one(&state, None, &input).await;
+        assert_eq!(masked.rows[0][0], None);
+        assert_eq!(masked.rows[0][1].as_deref(), Some("HQ front desk"));
+
+        #[cfg(feature = "mysql")]
+        {
+            let mut anonymizer = MySqlAnonymizer::new(state.clone(), 1);
+            let (columns, rows) = input.to_mysql("users");
+            anonymizer.reset_columns();
+            for column in &columns {
+                anonymizer.on_column_definition(column).await;
+            }
+            let masked = anonymizer.on_result_row(rows[0].clone()).await.unwrap();
+            let masked = ResultSetFixture::from_mysql(&columns, &[masked]);
+            assert_eq!(masked.rows[0][0], None);
+
+            let mut anonymizer = MySqlAnonymizer::new(state, 1);
+            anonymizer.reset_columns();
+            for column in &columns {
+                anonymizer.on_column_definition(column).await;
+            }
+            let row = BinaryRow {
+                sequence_id: 2,
+                values: vec![
+                    BinaryCell::Text(bytes::BytesMut::from("123-45-6789".as_bytes())),
+                    BinaryCell::Text(bytes::BytesMut::from("HQ front desk".as_bytes())),
+                ],
+            };
+            let masked = anonymizer.on_binary_row(row).await.unwrap();
+            assert!(matches!(masked.values[0], BinaryCell::Null));
+            assert!(matches!(&masked.values[1], BinaryCell::Text(v) if v == "HQ front desk"));
+        }
+    }
+
+    /// `redact` writes the rule's placeholder (default `[REDACTED]`), and
+    /// `preserve_length: true` fits it to the original's character count.
+    #[tokio::test]
+    async fn test_redact_strategy_text_and_length() {
+        let mut custom = rule_on(None, "ssn");
+        custom.strategy = Strategy::Redact.into();
+        custom.redact_text = Some("(hidden)".to_string());
+        let mut sized = rule_on(None, "phone");
+        sized.strategy = Strategy::Redact.into();
+        sized.preserve_length = true;
+        let mut plain = rule_on(None, "notes");
+        plain.strategy = Strategy::Redact.into();
+        let state = resolver_state(vec![custom, sized, plain], ExpressionHandling::Heuristic);
+
+        let input = ResultSetFixture {
+            columns: vec!["ssn".to_string(), "phone".to_string(), "notes".to_string()],
+            rows: vec![vec![
+                Some("123-45-6789".to_string()),
+                Some("555-0100".to_string()),
+                Some("ask reception".to_string()),
+            ]],
+        };
+        let masked = mask_one(&state, None, &input).await;
+
+        assert_eq!(masked.rows[0][0].as_deref(), Some("(hidden)"));
+        // "555-0100" is 8 characters: the default placeholder cut to fit
+        assert_eq!(masked.rows[0][1].as_deref(), Some("[REDACTE"));
+        assert_eq!(masked.rows[0][2].as_deref(), Some("[REDACTED]"));
+    }
+
+    /// `hash` is HMAC-SHA256 under the configured key: the same value maps
+    /// to the same token across connections, and the token is exactly the
+    /// primitive's output — auditable, not derived from the row hasher.
+    #[tokio::test]
+    async fn test_hash_strategy_is_keyed_and_stable() {
+        use crate::config::HashingConfig;
+
+        let mut rule = rule_on(None, "customer_id");
+        rule.strategy = Strategy::Hash.into();
         let config = AppConfig {
-            masking_enabled: false, // Disabled
-            rules: vec![],
-            tls: None,
-            upstream_tls: false,
-            telemetry: None,
-            api: None,
-            limits: None,
-            health_check: None,
-            audit: None,
+            rules: vec![rule],
+            masking: None,
+            scanner: None,
+            hashing: Some(HashingConfig {
+                key: Some("test-key".to_string()),
+                encoding: HashEncoding::Hex,
+                truncate: None,
+            }),
+            ..Default::default()
         };
         let state = AppState::new_for_test(config, "proxy.yaml".to_string());
-        let mut anonymizer = Anonymizer::new(state, 1);
 
-        let email = "test@example.com";
-        let mut row = DataRow {
-            values: vec![Some(BytesMut::from(email.as_bytes()))],
+        let input = ResultSetFixture {
+            columns: vec!["customer_id".to_string()],
+            rows: vec![vec![Some("cust-42".to_string())]],
         };
+        // Two anonymizers stand in for two connections
+        let first = mask_one(&state, None, &input).await;
+        let second = mask_one(&state, None, &input).await;
+        assert_eq!(first.rows[0][0], second.rows[0][0]);
 
-        row = anonymizer.on_data_row(row).await.unwrap();
-        let val = std::str::from_utf8(row.values[0].as_ref().unwrap()).unwrap();
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(b"test-key").unwrap();
+        mac.update(b"cust-42");
+        let expected: String = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        assert_eq!(first.rows[0][0].as_deref(), Some(expected.as_str()));
+
+        // Base64 encoding and truncation apply to the encoded digest
+        let spec = HashSpec {
+            key: Some("test-key".to_string()),
+            encoding: HashEncoding::Base64,
+            truncate: Some(12),
+        };
+        let token = spec.apply("cust-42");
+        assert_eq!(token.len(), 12);
+        assert_ne!(token, spec.apply("cust-43"));
+
+        // Without a key (an unvalidated context) nothing is minted from a
+        // random key that would silently break joins
+        assert_eq!(no_hash().apply("cust-42"), "MASKED");
+    }
+
+    /// `format_preserving` keeps every non-alphanumeric character in place,
+    /// maps digits to digits and letters to letters of the same case, and is
+    /// deterministic in the value — so the output still passes downstream
+    /// format validation.
+    #[tokio::test]
+    async fn test_format_preserving_keeps_shape() {
+        let fp = |value: &str, seed: u64| {
+            mask_value(&no_custom(), &no_hash(), &default_tuning(), &Strategy::FormatPreserving, value, seed)
+        };
+        let same_shape = |original: &str, masked: &str| {
+            original.chars().count() == masked.chars().count()
+                && original.chars().zip(masked.chars()).all(|(o, m)| {
+                    if o.is_ascii_digit() {
+                        m.is_ascii_digit()
+                    } else if o.is_ascii_lowercase() {
+                        m.is_ascii_lowercase()
+                    } else if o.is_ascii_uppercase() {
+                        m.is_ascii_uppercase()
+                    } else {
+                        o == m
+                    }
+                })
+        };
+
+        let ssn = fp("123-45-6789", 7);
+        assert!(same_shape("123-45-6789", &ssn), "{ssn}");
+        assert_ne!(ssn, "123-45-6789");
+        assert_eq!(ssn, fp("123-45-6789", 7));
+
+        let phone = fp("+1 (415) 555-0133", 7);
+        assert!(same_shape("+1 (415) 555-0133", &phone), "{phone}");
+
+        let plate = fp("Ab-12-cd", 3);
+        assert!(same_shape("Ab-12-cd", &plate), "{plate}");
+
+        // Nothing to replace: the value passes through unchanged
+        assert_eq!(fp("", 1), "");
+        assert_eq!(fp("--- / ---", 1), "--- / ---");
+        let long = "9".repeat(100_000);
+        assert_eq!(fp(&long, 1).chars().count(), 100_000);
+
+        // Bound to a column, the replacement is stable across connections
+        let mut rule = rule_on(None, "ssn");
+        rule.strategy = Strategy::FormatPreserving.into();
+        let state = resolver_state(vec![rule], ExpressionHandling::Heuristic);
+        let input = ResultSetFixture {
+            columns: vec!["ssn".to_string()],
+            rows: vec![vec![Some("123-45-6789".to_string())]],
+        };
+        let first = mask_one(&state, None, &input).await;
+        let second = mask_one(&state, None, &input).await;
+        assert_eq!(first.rows[0][0], second.rows[0][0]);
+        assert!(same_shape("123-45-6789", first.rows[0][0].as_deref().unwrap()));
+    }
+
+    /// With `seed_column`, `date_shift` keys its offset on a sibling value,
+    /// so every date of one subject moves by the same amount and intervals
+    /// between events survive; `date_shift_days` bounds the offset, and
+    /// values that do not parse as dates pass through unchanged.
+    #[tokio::test]
+    async fn test_date_shift_seed_column_and_range() {
+        let mut rule = rule_on(None, "admitted_on");
+        rule.strategy = Strategy::DateShift.into();
+        rule.date_shift_days = Some(10);
+        rule.seed_column = Some("customer_id".to_string());
+        // Fixture columns are text on the wire; apply the strategy anyway
+        // rather than falling back to a placeholder
+        rule.on_type_mismatch = TypeMismatchPolicy::Apply;
+        let state = resolver_state(vec![rule], ExpressionHandling::Heuristic);
+
+        let day = |s: &str| chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+        let input = ResultSetFixture {
+            columns: vec!["customer_id".to_string(), "admitted_on".to_string()],
+            rows: vec![
+                vec![Some("cust-7".to_string()), Some("2024-03-01".to_string())],
+                vec![Some("cust-7".to_string()), Some("2024-04-15".to_string())],
+                vec![Some("cust-11".to_string()), Some("2024-03-01".to_string())],
+            ],
+        };
+        // One anonymizer for all three rows, so a (chain, seed) memo hit
+        // cannot replay the first date for the second
+        let masked = mask_all(&state, &input).await;
+        let shifted: Vec<chrono::NaiveDate> = masked
+            .rows
+            .iter()
+            .map(|row| day(row[1].as_deref().unwrap()))
+            .collect();
 
-        // Should NOT be masked when disabled
+        // Same subject: both dates move by one offset within ±10 days, so
+        // the 45-day interval between them is preserved
+        let offset = shifted[0] - day("2024-03-01");
+        assert!(offset.num_days().abs() <= 10, "offset {:?}", offset);
+        assert_eq!(shifted[1] - shifted[0], day("2024-04-15") - day("2024-03-01"));
+        // Another subject lands on another offset
+        assert_ne!(shifted[2], shifted[0]);
+
+        // A timestamp keeps its time-of-day and timezone suffix, shifted by
+        // the same per-subject offset
+        let ts_input = ResultSetFixture {
+            columns: vec!["customer_id".to_string(), "admitted_on".to_string()],
+            rows: vec![vec![
+                Some("cust-7".to_string()),
+                Some("2024-03-01 08:30:00+02".to_string()),
+            ]],
+        };
+        let ts = mask_one(&state, None, &ts_input).await;
         assert_eq!(
-            val, email,
-            "Data should not be masked when masking is disabled"
+            ts.rows[0][1].as_deref(),
+            Some(format!("{} 08:30:00+02", shifted[0]).as_str())
         );
+
+        // A NULL seed column falls back to the cell's own value, and input
+        // that is not a date passes through rather than being corrupted
+        let odd_input = ResultSetFixture {
+            columns: vec!["customer_id".to_string(), "admitted_on".to_string()],
+            rows: vec![vec![None, Some("unknown".to_string())]],
+        };
+        let odd = mask_one(&state, None, &odd_input).await;
+        assert_eq!(odd.rows[0][1].as_deref(), Some("unknown"));
+
+        #[cfg(feature = "mysql")]
+        {
+            let mut anonymizer = MySqlAnonymizer::new(state, 1);
+            let (columns, rows) = input.to_mysql("admissions");
+            anonymizer.reset_columns();
+            for column in &columns {
+                anonymizer.on_column_definition(column).await;
+            }
+            let mut out = Vec::new();
+            for row in rows {
+                out.push(anonymizer.on_result_row(row).await.unwrap());
+            }
+            let masked = ResultSetFixture::from_mysql(&columns, &out);
+            let shifted: Vec<chrono::NaiveDate> = masked
+                .rows
+                .iter()
+                .map(|row| day(row[1].as_deref().unwrap()))
+                .collect();
+            assert_eq!(shifted[1] - shifted[0], day("2024-04-15") - day("2024-03-01"));
+            assert_ne!(shifted[2], shifted[0]);
+        }
     }
 
+    /// `numeric_noise` output always parses back into the column's numeric
+    /// type: integers stay integers, decimals keep their decimal places,
+    /// scientific notation stays scientific, and the rule's `noise_percent`
+    /// bounds the relative change.
     #[tokio::test]
-    async fn test_null_values_handled() {
+    async fn test_numeric_noise_precision_and_bounds() {
+        let noise = |value: &str, seed: u64| {
+            mask_value(
+                &no_custom(),
+                &no_hash(),
+                &default_tuning(),
+                &Strategy::NumericNoise,
+                value,
+                seed,
+            )
+        };
+        for seed in [0, 7, 1000, 65_535] {
+            let masked = noise("68500.25", seed);
+            assert_eq!(masked.split('.').nth(1).map(str::len), Some(2), "{masked}");
+            let parsed: f64 = masked.parse().unwrap();
+            assert!((parsed / 68500.25 - 1.0).abs() <= 0.1001, "{masked}");
+
+            // Multiplicative noise keeps the sign
+            let negative: i64 = noise("-1200", seed).parse().unwrap();
+            assert!((-1321..=-1079).contains(&negative), "{negative}");
+
+            let sci = noise("1.5e4", seed);
+            assert!(sci.contains(['e', 'E']), "{sci}");
+            let parsed: f64 = sci.parse().unwrap();
+            assert!((parsed / 1.5e4 - 1.0).abs() <= 0.1001, "{sci}");
+        }
+
+        // A rule caps the jitter at its own percentage, deterministically
+        let mut rule = rule_on(None, "salary");
+        rule.strategy = Strategy::NumericNoise.into();
+        rule.noise_percent = Some(5.0);
+        // Fixture columns are text on the wire; apply the strategy anyway
+        rule.on_type_mismatch = TypeMismatchPolicy::Apply;
+        let state = resolver_state(vec![rule], ExpressionHandling::Heuristic);
+        let input = ResultSetFixture {
+            columns: vec!["salary".to_string()],
+            rows: vec![vec![Some("90000".to_string())]],
+        };
+        let first = mask_one(&state, None, &input).await;
+        let second = mask_one(&state, None, &input).await;
+        assert_eq!(first.rows[0][0], second.rows[0][0]);
+        let masked: f64 = first.rows[0][0].as_deref().unwrap().parse().unwrap();
+        assert!((masked / 90000.0 - 1.0).abs() <= 0.0501, "{masked}");
+    }
+
+    /// `ip` zeroes the host part by default (last IPv4 octet, last 80 IPv6
+    /// bits), keeps CIDR prefix lengths, leaves non-IP garbage untouched,
+    /// and in `pseudonymize` mode remaps deterministically into a reserved
+    /// range.
+    #[tokio::test]
+    async fn test_ip_strategy_truncates_and_pseudonymizes() {
+        let truncate = |value: &str| {
+            mask_value(&no_custom(), &no_hash(), &default_tuning(), &Strategy::Ip, value, 7)
+        };
+        assert_eq!(truncate("203.0.113.77"), "203.0.113.0");
+        assert_eq!(truncate("10.1.2.3/24"), "10.1.2.0/24");
+        assert_eq!(truncate("2001:4860:4860::8888"), "2001:4860:4860::");
+        assert_eq!(truncate("not-an-ip"), "not-an-ip");
+        assert_eq!(truncate("203.0.113.77/abc"), "203.0.113.77/abc");
+
+        let mut rule = rule_on(None, "client_ip");
+        rule.strategy = Strategy::Ip.into();
+        rule.ip_mode = Some(IpMode::Pseudonymize);
+        let state = resolver_state(vec![rule], ExpressionHandling::Heuristic);
+        let fixture = |value: &str| ResultSetFixture {
+            columns: vec!["client_ip".to_string()],
+            rows: vec![vec![Some(value.to_string())]],
+        };
+
+        // Deterministic across connections and inside the reserved range
+        let first = mask_one(&state, None, &fixture("203.0.113.77")).await;
+        let second = mask_one(&state, None, &fixture("203.0.113.77")).await;
+        assert_eq!(first.rows[0][0], second.rows[0][0]);
+        let masked: std::net::Ipv4Addr = first.rows[0][0].as_deref().unwrap().parse().unwrap();
+        let octets = masked.octets();
+        assert!(octets[0] == 198 && (octets[1] == 18 || octets[1] == 19), "{masked}");
+
+        // Distinct clients stay distinct
+        let other = mask_one(&state, None, &fixture("203.0.113.78")).await;
+        assert_ne!(other.rows[0][0], first.rows[0][0]);
+
+        // IPv6 lands in the documentation range
+        let v6 = mask_one(&state, None, &fixture("2001:4860:4860::8888")).await;
+        assert!(
+            v6.rows[0][0].as_deref().unwrap().starts_with("2001:db8:"),
+            "{:?}",
+            v6.rows[0][0]
+        );
+    }
+
+    /// `preserve_domain` fakes only the local part, `domain_allowlist`
+    /// passes whole addresses through, and values without an `@` still get
+    /// a fully fake address.
+    #[tokio::test]
+    async fn test_email_domain_options() {
+        let preserve = StrategyTuning {
+            preserve_domain: true,
+            ..StrategyTuning::default()
+        };
+        let mask = |tuning: &StrategyTuning, value: &str, seed: u64| {
+            mask_value(&no_custom(), &no_hash(), tuning, &Strategy::Email, value, seed)
+        };
+
+        let masked = mask(&preserve, "alice@bigcustomer.com", 42);
+        assert!(masked.ends_with("@bigcustomer.com"), "{masked}");
+        assert_ne!(masked, "alice@bigcustomer.com");
+        assert_eq!(masked, mask(&preserve, "alice@bigcustomer.com", 42));
+        assert_ne!(masked, mask(&preserve, "bob@bigcustomer.com", 7));
+
+        // No '@' (or nothing after it): full fake email, not a leak
+        assert!(mask(&preserve, "not-an-address", 42).contains('@'));
+        assert!(!mask(&preserve, "dangling@", 42).ends_with('@'));
+
+        // Allowlisted domains pass through untouched, case-insensitively,
+        // with or without the '*@' spelling
+        let allow = StrategyTuning {
+            domain_allowlist: vec!["*@ourcompany.com".to_string()],
+            ..StrategyTuning::default()
+        };
+        assert_eq!(mask(&allow, "bob@OurCompany.com", 42), "bob@OurCompany.com");
+        assert_ne!(mask(&allow, "bob@elsewhere.com", 42), "bob@elsewhere.com");
+
+        // End to end: an explicit rule's options survive the row path, and
+        // an allowlisted address is not re-masked by the content heuristics
+        let mut rule = rule_on(None, "email");
+        rule.strategy = Strategy::Email.into();
+        rule.preserve_domain = true;
+        rule.domain_allowlist = vec!["ourcompany.com".to_string()];
+        let state = resolver_state(vec![rule], ExpressionHandling::Heuristic);
+        let fixture = |value: &str| ResultSetFixture {
+            columns: vec!["email".to_string()],
+            rows: vec![vec![Some(value.to_string())]],
+        };
+        let masked = mask_one(&state, None, &fixture("carol@bigcustomer.com")).await;
+        assert!(
+            masked.rows[0][0].as_deref().unwrap().ends_with("@bigcustomer.com"),
+            "{:?}",
+            masked.rows[0][0]
+        );
+        let internal = mask_one(&state, None, &fixture("dave@ourcompany.com")).await;
+        assert_eq!(internal.rows[0][0].as_deref(), Some("dave@ourcompany.com"));
+    }
+
+    /// A `json` rule with `json_paths` masks only the listed pointer
+    /// paths (wildcards fanning out over arrays), keeps numbers numeric,
+    /// and passes malformed documents through untouched.
+    #[tokio::test]
+    async fn test_json_paths_mask_only_listed_paths() {
+        let mut rule = rule_on(None, "properties");
+        rule.strategy = Strategy::Json.into();
+        rule.json_paths = Some(
+            [
+                ("/email".to_string(), StrategyChain::from(Strategy::Email)),
+                ("/address/city".to_string(), StrategyChain::from(Strategy::Address)),
+                ("/contacts/*/email".to_string(), StrategyChain::from(Strategy::Email)),
+                ("/age".to_string(), StrategyChain::from(Strategy::NumericNoise)),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let state = resolver_state(vec![rule], ExpressionHandling::Heuristic);
+        let fixture = |value: &str| ResultSetFixture {
+            columns: vec!["properties".to_string()],
+            rows: vec![vec![Some(value.to_string())]],
+        };
+
+        let doc = r#"{"email":"a@b.com","age":68500,"note":"keep me",
+            "address":{"city":"Oslo","zip":"0150"},
+            "contacts":[{"email":"c@d.com"},{"email":"e@f.com"}]}"#;
+        let masked = mask_one(&state, None, &fixture(doc)).await;
+        let parsed: serde_json::Value =
+            serde_json::from_str(masked.rows[0][0].as_deref().unwrap()).unwrap();
+
+        assert_ne!(parsed["email"], "a@b.com");
+        assert!(parsed["email"].as_str().unwrap().contains('@'));
+        assert_ne!(parsed["address"]["city"], "Oslo");
+        // Paths not listed survive verbatim
+        assert_eq!(parsed["address"]["zip"], "0150");
+        assert_eq!(parsed["note"], "keep me");
+        // The wildcard reached every array element
+        assert_ne!(parsed["contacts"][0]["email"], "c@d.com");
+        assert_ne!(parsed["contacts"][1]["email"], "e@f.com");
+        // numeric_noise output still parses as a number, so the field
+        // stays a JSON number within the default ±10%
+        let age = parsed["age"].as_f64().unwrap();
+        assert!((61650.0..=75350.0).contains(&age), "{age}");
+
+        // Malformed documents pass through rather than flattening
+        let masked = mask_one(&state, None, &fixture("not json {")).await;
+        assert_eq!(masked.rows[0][0].as_deref(), Some("not json {"));
+    }
+
+    /// `regex_replace` applies the rule's pattern to the whole value with
+    /// replace-all, honours capture-group references, and leaves
+    /// non-matching values unchanged.
+    #[tokio::test]
+    async fn test_regex_replace_strategy() {
+        let mut rule = rule_on(None, "order_ref");
+        rule.strategy = Strategy::RegexReplace.into();
+        rule.pattern = Some(r"ORD-(\d{4})-\d{6}".to_string());
+        rule.replacement = Some("ORD-$1-XXXXXX".to_string());
+        let state = resolver_state(vec![rule], ExpressionHandling::Heuristic);
+        let fixture = |value: &str| ResultSetFixture {
+            columns: vec!["order_ref".to_string()],
+            rows: vec![vec![Some(value.to_string())]],
+        };
+
+        let masked = mask_one(&state, None, &fixture("ORD-2024-123456, ORD-2025-654321")).await;
+        assert_eq!(
+            masked.rows[0][0].as_deref(),
+            Some("ORD-2024-XXXXXX, ORD-2025-XXXXXX")
+        );
+
+        let masked = mask_one(&state, None, &fixture("no order reference")).await;
+        assert_eq!(masked.rows[0][0].as_deref(), Some("no order reference"));
+    }
+
+    /// A `wasm` rule drives the module's `mask` export; traps and fuel
+    /// exhaustion degrade to the redact placeholder instead of leaking
+    /// the original value.
+    #[cfg(feature = "wasm-plugins")]
+    #[tokio::test]
+    async fn test_wasm_strategy_modules() {
+        let dir = tempfile::tempdir().unwrap();
+        let token = dir.path().join("token.wat");
+        std::fs::write(
+            &token,
+            r#"(module
+  (memory (export "memory") 1)
+  (data (i32.const 0) "WASMTOKEN")
+  (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+  (func (export "mask") (param i32 i32 i64) (result i64) (i64.const 9)))"#,
+        )
+        .unwrap();
+        let spin = dir.path().join("spin.wat");
+        std::fs::write(
+            &spin,
+            r#"(module
+  (memory (export "memory") 1)
+  (func (export "alloc") (param i32) (result i32) (i32.const 0))
+  (func (export "mask") (param i32 i32 i64) (result i64)
+    (loop $l (br $l)) (i64.const 0)))"#,
+        )
+        .unwrap();
+
+        let fixture = |value: &str| ResultSetFixture {
+            columns: vec!["token".to_string()],
+            rows: vec![vec![Some(value.to_string())]],
+        };
+        let wasm_rule = |path: &std::path::Path, fuel: Option<u64>| {
+            let mut rule = rule_on(None, "token");
+            rule.strategy = Strategy::Wasm.into();
+            rule.wasm_module = Some(path.to_str().unwrap().to_string());
+            rule.wasm_fuel = fuel;
+            rule
+        };
+
+        let state = resolver_state(vec![wasm_rule(&token, None)], ExpressionHandling::Heuristic);
+        let masked = mask_one(&state, None, &fixture("secret-value")).await;
+        assert_eq!(masked.rows[0][0].as_deref(), Some("WASMTOKEN"));
+
+        // An endless loop runs out of fuel and masks to the placeholder
+        let state = resolver_state(
+            vec![wasm_rule(&spin, Some(10_000))],
+            ExpressionHandling::Heuristic,
+        );
+        let masked = mask_one(&state, None, &fixture("secret-value")).await;
+        assert_eq!(masked.rows[0][0].as_deref(), Some(REDACT_PLACEHOLDER));
+    }
+
+    /// Seeds come from keyed HMAC over the value, shared process-wide: the
+    /// same value masks identically across separate Anonymizer instances,
+    /// and the pipeline's seed is exactly `value_seed`.
+    #[tokio::test]
+    async fn test_determinism_stable_across_anonymizer_instances() {
+        let fixture = ResultSetFixture {
+            columns: vec!["email".to_string()],
+            rows: vec![vec![Some("carol@example.com".to_string())]],
+        };
+        let email_rule = || {
+            let mut rule = rule_on(None, "email");
+            rule.strategy = Strategy::Email.into();
+            rule
+        };
+
+        let first_state = resolver_state(vec![email_rule()], ExpressionHandling::Heuristic);
+        let second_state = resolver_state(vec![email_rule()], ExpressionHandling::Heuristic);
+        let first = mask_one(&first_state, None, &fixture).await;
+        let second = mask_one(&second_state, None, &fixture).await;
+        assert_eq!(first.rows, second.rows);
+
+        let expected = mask_value(
+            &no_custom(),
+            &no_hash(),
+            &default_tuning(),
+            &Strategy::Email,
+            "carol@example.com",
+            value_seed(b"carol@example.com"),
+        );
+        assert_eq!(first.rows[0][0].as_deref(), Some(expected.as_str()));
+    }
+
+    /// The `iban` strategy mints checksum-valid fakes: the scanner that
+    /// detects real IBANs must accept the replacement too, and the same
+    /// seed reproduces it.
+    #[test]
+    fn test_iban_strategy_produces_valid_ibans() {
+        let scanner = PiiScanner::new();
+        for seed in [1u64, 42, 31337] {
+            let fake = mask_value(
+                &no_custom(),
+                &no_hash(),
+                &default_tuning(),
+                &Strategy::Iban,
+                "DE89370400440532013000",
+                seed,
+            );
+            assert_eq!(
+                scanner.scan(&fake),
+                Some(PiiType::Iban),
+                "not a valid IBAN: {}",
+                fake
+            );
+            assert_ne!(fake, "DE89370400440532013000");
+            assert_eq!(
+                fake,
+                mask_value(
+                    &no_custom(),
+                    &no_hash(),
+                    &default_tuning(),
+                    &Strategy::Iban,
+                    "DE89370400440532013000",
+                    seed,
+                )
+            );
+        }
+    }
+
+    /// With `deterministic: false` the rule draws a fresh seed per
+    /// occurrence, so repeated values stop masking identically and
+    /// frequency analysis of an export finds nothing to count.
+    #[tokio::test]
+    async fn test_non_deterministic_rule_freshens_fakes() {
+        let input = ResultSetFixture {
+            columns: vec!["email".to_string()],
+            rows: vec![
+                vec![Some("carol@example.com".to_string())],
+                vec![Some("carol@example.com".to_string())],
+            ],
+        };
+        let email_rule = |deterministic: bool| {
+            let mut rule = rule_on(None, "email");
+            rule.strategy = Strategy::Email.into();
+            rule.deterministic = deterministic;
+            rule
+        };
+
+        // Default: the repeated value masks identically
+        let state = resolver_state(vec![email_rule(true)], ExpressionHandling::Heuristic);
+        let masked = mask_all(&state, &input).await;
+        assert_eq!(masked.rows[0], masked.rows[1]);
+
+        // Opted out: each occurrence gets its own fake
+        let state = resolver_state(vec![email_rule(false)], ExpressionHandling::Heuristic);
+        let masked = mask_all(&state, &input).await;
+        assert_ne!(masked.rows[0][0], Some("carol@example.com".to_string()));
+        assert_ne!(masked.rows[0], masked.rows[1]);
+    }
+
+    /// With `scanner.scan_substrings` on, heuristics mask just the PII
+    /// spans inside free text instead of passing the value through because
+    /// the whole cell matched nothing.
+    #[tokio::test]
+    async fn test_substring_scan_masks_spans_in_free_text() {
+        let text = "customer said email is bob@example.com call back";
+        let input = ResultSetFixture {
+            columns: vec!["notes".to_string()],
+            rows: vec![vec![Some(text.to_string())]],
+        };
+
+        // Off by default: a cell that fails the whole-value scan is left
+        // untouched
+        let state = resolver_state(Vec::new(), ExpressionHandling::Heuristic);
+        let untouched = mask_one(&state, None, &input).await;
+        assert_eq!(untouched.rows[0][0].as_deref(), Some(text));
+
         let config = AppConfig {
-            masking_enabled: true,
-            rules: vec![],
-            tls: None,
-            upstream_tls: false,
-            telemetry: None,
-            api: None,
-            limits: None,
-            health_check: None,
-            audit: None,
+            scanner: Some(crate::config::ScannerConfig {
+                ignore_private_ips: false,
+                min_confidence: crate::config::DEFAULT_MIN_CONFIDENCE,
+                locales: crate::config::DEFAULT_LOCALES.to_vec(),
+                dob_column_hints: crate::config::default_dob_column_hints().to_vec(),
+                scan_substrings: true,
+                custom_patterns: Vec::new(),
+            }),
+            ..Default::default()
         };
         let state = AppState::new_for_test(config, "proxy.yaml".to_string());
-        let mut anonymizer = Anonymizer::new(state, 1);
+        let masked = mask_one(&state, None, &input).await;
+        let out = masked.rows[0][0].as_deref().unwrap();
+        // The prose survives; the embedded address is swapped for the same
+        // fake the whole-value path would mint for it
+        let fake = generate_fake_data(&Strategy::Email, value_seed(b"bob@example.com"));
+        assert_eq!(out, format!("customer said email is {} call back", fake));
 
-        let mut row = DataRow {
-            values: vec![None, Some(BytesMut::from("data".as_bytes())), None],
+        // The span was reported to the alerting channel like any other
+        // heuristic detection
+        let mut rx = state.detection_rx.lock().unwrap().take().unwrap();
+        let detection = rx.try_recv().unwrap();
+        assert_eq!(detection.pii_type, crate::scanner::PiiType::Email);
+        assert_eq!(detection.column.as_deref(), Some("notes"));
+    }
+
+    /// A `scanner.custom_patterns` entry catches identifiers no built-in
+    /// detector knows about and masks them with no rule configured:
+    /// redaction by default, the entry's strategy when it names one.
+    #[tokio::test]
+    async fn test_custom_pattern_masks_heuristically() {
+        let config = AppConfig {
+            scanner: Some(crate::config::ScannerConfig {
+                ignore_private_ips: false,
+                min_confidence: crate::config::DEFAULT_MIN_CONFIDENCE,
+                locales: crate::config::DEFAULT_LOCALES.to_vec(),
+                dob_column_hints: crate::config::default_dob_column_hints().to_vec(),
+                scan_substrings: false,
+                custom_patterns: vec![
+                    crate::config::CustomPatternConfig {
+                        name: "mrn".to_string(),
+                        pattern: r"MRN-\d{8}".to_string(),
+                        strategy: None,
+                        severity: crate::config::PatternSeverity::High,
+                    },
+                    crate::config::CustomPatternConfig {
+                        name: "employee_id".to_string(),
+                        pattern: r"EMP-\d{4}".to_string(),
+                        strategy: Some(Strategy::Phone),
+                        severity: Default::default(),
+                    },
+                ],
+            }),
+            ..Default::default()
         };
+        let state = AppState::new_for_test(config, "proxy.yaml".to_string());
 
-        row = anonymizer.on_data_row(row).await.unwrap();
+        let input = ResultSetFixture {
+            columns: vec!["chart".to_string(), "badge".to_string()],
+            rows: vec![vec![
+                Some("MRN-12345678".to_string()),
+                Some("EMP-0042".to_string()),
+            ]],
+        };
+        let masked = mask_one(&state, None, &input).await;
+        // No strategy on the entry: the match redacts
+        assert_eq!(masked.rows[0][0].as_deref(), Some(REDACT_PLACEHOLDER));
+        // A configured strategy substitutes a fake of that shape instead
+        let badge = masked.rows[0][1].as_deref().unwrap();
+        assert_ne!(badge, "EMP-0042");
+        assert_ne!(badge, REDACT_PLACEHOLDER);
+
+        // The detection reported under the pattern's name, severity attached
+        let mut rx = state.detection_rx.lock().unwrap().take().unwrap();
+        let detection = rx.try_recv().unwrap();
+        assert_eq!(
+            detection.pii_type,
+            crate::scanner::PiiType::Custom("mrn".to_string())
+        );
+        assert_eq!(
+            detection.severity,
+            Some(crate::config::PatternSeverity::High)
+        );
+    }
+
+    /// `masking.defaults` swaps the strategy a heuristic detection applies;
+    /// explicit rules keep their own strategy, and detectors the map does
+    /// not name keep their built-in default.
+    #[tokio::test]
+    async fn test_masking_defaults_override_heuristic_strategy() {
+        let mut rule = rule_on(None, "work_email");
+        rule.strategy = Strategy::Email.into();
+        let config = AppConfig {
+            rules: vec![rule],
+            masking: Some(crate::config::MaskingConfig {
+                determinism_key: None,
+                heuristics_enabled: true,
+                bypass_users: vec![],
+                defaults: [("email".to_string(), Strategy::Redact)].into(),
+            }),
+            ..Default::default()
+        };
+        let state = AppState::new_for_test(config, "proxy.yaml".to_string());
+
+        let input = ResultSetFixture {
+            columns: vec![
+                "contact".to_string(),
+                "work_email".to_string(),
+                "card".to_string(),
+            ],
+            rows: vec![vec![
+                Some("alice@example.com".to_string()),
+                Some("bob@example.com".to_string()),
+                Some("4111111111111111".to_string()),
+            ]],
+        };
+        let masked = mask_one(&state, None, &input).await;
+
+        // The heuristic detection redacts instead of substituting a fake
+        // address
+        assert_eq!(masked.rows[0][0].as_deref(), Some(REDACT_PLACEHOLDER));
+        // The explicit rule still applies its own strategy
+        let ruled = masked.rows[0][1].as_deref().unwrap();
+        assert_ne!(ruled, "bob@example.com");
+        assert!(ruled.contains('@'), "rule strategy was overridden: {}", ruled);
+        // A detector the map does not name keeps its built-in default
+        let card = masked.rows[0][2].as_deref().unwrap();
+        assert_ne!(card, "4111111111111111");
+        assert_ne!(card, REDACT_PLACEHOLDER);
+    }
+
+    /// The heuristic path only masks when the top-scored candidate clears
+    /// `scanner.min_confidence`; the column name tips an ambiguous value
+    /// over the line.
+    #[tokio::test]
+    async fn test_min_confidence_gates_heuristic_masking() {
+        // Ten bare digits score 0.6 as a phone number: under the default
+        // threshold, so a generically named column passes through
+        let state = resolver_state(Vec::new(), ExpressionHandling::Heuristic);
+        let input = ResultSetFixture {
+            columns: vec!["ref_code".to_string()],
+            rows: vec![vec![Some("4155550133".to_string())]],
+        };
+        let untouched = mask_one(&state, None, &input).await;
+        assert_eq!(untouched.rows[0][0].as_deref(), Some("4155550133"));
+
+        // The same value in a phone-named column gets the hint boost and
+        // clears the bar
+        let input = ResultSetFixture {
+            columns: vec!["phone_number".to_string()],
+            rows: vec![vec![Some("4155550133".to_string())]],
+        };
+        let masked = mask_one(&state, None, &input).await;
+        let fake = generate_fake_data(&Strategy::Phone, value_seed(b"4155550133"));
+        assert_eq!(masked.rows[0][0].as_deref(), Some(fake.as_str()));
+
+        // The winning score travels with the detection for the audit trail
+        let mut rx = state.detection_rx.lock().unwrap().take().unwrap();
+        let detection = rx.try_recv().unwrap();
+        assert_eq!(detection.pii_type, crate::scanner::PiiType::Phone);
+        assert_eq!(detection.confidence, Some(0.85));
+
+        // Lowering the threshold lets the unhinted match through as well
+        let config = AppConfig {
+            scanner: Some(crate::config::ScannerConfig {
+                ignore_private_ips: false,
+                min_confidence: 0.5,
+                locales: crate::config::DEFAULT_LOCALES.to_vec(),
+                dob_column_hints: crate::config::default_dob_column_hints().to_vec(),
+                scan_substrings: false,
+                custom_patterns: Vec::new(),
+            }),
+            ..Default::default()
+        };
+        let state = AppState::new_for_test(config, "proxy.yaml".to_string());
+        let input = ResultSetFixture {
+            columns: vec!["ref_code".to_string()],
+            rows: vec![vec![Some("4155550133".to_string())]],
+        };
+        let masked = mask_one(&state, None, &input).await;
+        assert_eq!(masked.rows[0][0].as_deref(), Some(fake.as_str()));
+    }
+
+    /// `scanner.locales` swaps which countries' national IDs the
+    /// heuristic path recognizes; detections carry the country.
+    #[tokio::test]
+    async fn test_locale_national_ids_masked_heuristically() {
+        let config = AppConfig {
+            scanner: Some(crate::config::ScannerConfig {
+                ignore_private_ips: false,
+                min_confidence: crate::config::DEFAULT_MIN_CONFIDENCE,
+                locales: vec![crate::config::Locale::De, crate::config::Locale::Uk],
+                dob_column_hints: crate::config::default_dob_column_hints().to_vec(),
+                scan_substrings: false,
+                custom_patterns: Vec::new(),
+            }),
+            ..Default::default()
+        };
+        let state = AppState::new_for_test(config, "proxy.yaml".to_string());
+
+        let input = ResultSetFixture {
+            columns: vec![
+                "tax_id".to_string(),
+                "ni_number".to_string(),
+                "legacy_ssn".to_string(),
+            ],
+            rows: vec![vec![
+                Some("12345678903".to_string()),
+                Some("AB123456C".to_string()),
+                Some("123-45-6789".to_string()),
+            ]],
+        };
+        let masked = mask_one(&state, None, &input).await;
+        // No fake generator for foreign formats: both redact
+        assert_eq!(masked.rows[0][0].as_deref(), Some(REDACT_PLACEHOLDER));
+        assert_eq!(masked.rows[0][1].as_deref(), Some(REDACT_PLACEHOLDER));
+        // The US detector went out with its locale
+        assert_eq!(masked.rows[0][2].as_deref(), Some("123-45-6789"));
+
+        let mut rx = state.detection_rx.lock().unwrap().take().unwrap();
+        let detection = rx.try_recv().unwrap();
+        assert_eq!(
+            detection.pii_type,
+            crate::scanner::PiiType::NationalId(crate::config::Locale::De)
+        );
+        assert_eq!(
+            rx.try_recv().unwrap().pii_type,
+            crate::scanner::PiiType::NationalId(crate::config::Locale::Uk)
+        );
+    }
+
+    /// A date only masks heuristically when the column name carries a
+    /// `scanner.dob_column_hints` fragment and the value sits in a
+    /// plausible birth range, so timestamp columns keep their dates.
+    #[tokio::test]
+    async fn test_dob_column_hint_masks_birth_dates() {
+        let state = resolver_state(Vec::new(), ExpressionHandling::Heuristic);
+        let input = ResultSetFixture {
+            columns: vec!["birth_date".to_string(), "created_at".to_string()],
+            rows: vec![vec![
+                Some("1985-06-15".to_string()),
+                Some("1985-06-15".to_string()),
+            ]],
+        };
+        let masked = mask_one(&state, None, &input).await;
+        let fake = generate_fake_data(&Strategy::Dob, value_seed(b"1985-06-15"));
+        assert_eq!(masked.rows[0][0].as_deref(), Some(fake.as_str()));
+        // The identical date in a non-birth column is left alone
+        assert_eq!(masked.rows[0][1].as_deref(), Some("1985-06-15"));
+
+        // A custom hint list swaps in the team's naming convention
+        let config = AppConfig {
+            scanner: Some(crate::config::ScannerConfig {
+                ignore_private_ips: false,
+                min_confidence: crate::config::DEFAULT_MIN_CONFIDENCE,
+                locales: crate::config::DEFAULT_LOCALES.to_vec(),
+                dob_column_hints: vec!["geburtsdatum".to_string()],
+                scan_substrings: false,
+                custom_patterns: Vec::new(),
+            }),
+            ..Default::default()
+        };
+        let state = AppState::new_for_test(config, "proxy.yaml".to_string());
+        let input = ResultSetFixture {
+            columns: vec!["geburtsdatum".to_string(), "birth_date".to_string()],
+            rows: vec![vec![
+                Some("1985-06-15".to_string()),
+                Some("1985-06-15".to_string()),
+            ]],
+        };
+        let masked = mask_one(&state, None, &input).await;
+        assert_eq!(masked.rows[0][0].as_deref(), Some(fake.as_str()));
+        assert_eq!(masked.rows[0][1].as_deref(), Some("1985-06-15"));
+    }
+
+    fn sibling_condition(on_missing: MissingColumnPolicy) -> RuleCondition {
+        RuleCondition {
+            self_matches: None,
+            or_detected: vec![],
+            column: Some("account_type".to_string()),
+            equals: Some("personal".to_string()),
+            on_missing_column: on_missing,
+        }
+    }
+
+    /// `when: {column, equals}` gates the rule on a sibling's value per row,
+    /// and a cell the condition releases is not reclaimed by heuristics even
+    /// when it plainly contains PII.
+    #[tokio::test]
+    async fn test_sibling_condition_gates_rule_per_row() {
+        let rule = MaskingRule {
+            id: None,
+            table: None,
+            column: "email".to_string(),
+            case_sensitive: false,
+            table_regex: None,
+            column_regex: None,
+            database: None,
+            compiled: Default::default(),
+            redact_text: None,
+            preserve_length: false,
+            date_shift_days: None,
+            seed_column: None,
+            noise_percent: None,
+            ip_mode: None,
+            preserve_domain: false,
+            domain_allowlist: Vec::new(),
+            json_paths: None,
+            pattern: None,
+            replacement: None,
+            wasm_module: None,
+            wasm_fuel: None,
+            deterministic: true,
+            strategy: Strategy::Email.into(),
+            composite_fields: None,
+            on_type_mismatch: TypeMismatchPolicy::Fallback,
+            when: Some(sibling_condition(MissingColumnPolicy::Mask)),
+        };
+        let state = resolver_state(vec![rule], ExpressionHandling::Heuristic);
+        let input = ResultSetFixture {
+            columns: vec!["email".to_string(), "account_type".to_string()],
+            rows: vec![
+                vec![
+                    Some("alice@example.com".to_string()),
+                    Some("personal".to_string()),
+                ],
+                vec![
+                    Some("sales@example.com".to_string()),
+                    Some("business".to_string()),
+                ],
+            ],
+        };
+        let masked = mask_all(&state, &input).await;
+
+        assert_ne!(masked.rows[0][0].as_deref(), Some("alice@example.com"));
+        // The business row is released by the condition, and heuristics do
+        // not reclaim it even though the value is detectable PII
+        assert_eq!(masked.rows[1][0].as_deref(), Some("sales@example.com"));
+        // The sibling itself is not the rule's target
+        assert_eq!(masked.rows[0][1].as_deref(), Some("personal"));
+        assert_eq!(state.stats.read().await.masking.condition_skipped, 1);
+    }
+
+    /// A sibling condition whose column the query did not select fails
+    /// closed by default (mask anyway) and fails open when the rule says so.
+    #[tokio::test]
+    async fn test_sibling_condition_missing_column_policy() {
+        let rule = |on_missing| MaskingRule {
+            id: None,
+            table: None,
+            column: "email".to_string(),
+            case_sensitive: false,
+            table_regex: None,
+            column_regex: None,
+            database: None,
+            compiled: Default::default(),
+            redact_text: None,
+            preserve_length: false,
+            date_shift_days: None,
+            seed_column: None,
+            noise_percent: None,
+            ip_mode: None,
+            preserve_domain: false,
+            domain_allowlist: Vec::new(),
+            json_paths: None,
+            pattern: None,
+            replacement: None,
+            wasm_module: None,
+            wasm_fuel: None,
+            deterministic: true,
+            strategy: Strategy::Email.into(),
+            composite_fields: None,
+            on_type_mismatch: TypeMismatchPolicy::Fallback,
+            when: Some(sibling_condition(on_missing)),
+        };
+        let input = ResultSetFixture {
+            columns: vec!["email".to_string()],
+            rows: vec![vec![Some("alice@example.com".to_string())]],
+        };
+
+        let closed = resolver_state(
+            vec![rule(MissingColumnPolicy::Mask)],
+            ExpressionHandling::Heuristic,
+        );
+        let masked = mask_all(&closed, &input).await;
+        assert_ne!(masked.rows[0][0].as_deref(), Some("alice@example.com"));
+
+        let open = resolver_state(
+            vec![rule(MissingColumnPolicy::Skip)],
+            ExpressionHandling::Heuristic,
+        );
+        let masked = mask_all(&open, &input).await;
+        assert_eq!(masked.rows[0][0].as_deref(), Some("alice@example.com"));
+    }
+
+    /// A self-value condition masks only cells whose own value matches the
+    /// regex or trips one of the listed detectors.
+    #[tokio::test]
+    async fn test_self_value_condition() {
+        let rule = MaskingRule {
+            id: None,
+            table: None,
+            column: "notes".to_string(),
+            case_sensitive: false,
+            table_regex: None,
+            column_regex: None,
+            database: None,
+            compiled: Default::default(),
+            redact_text: None,
+            preserve_length: false,
+            date_shift_days: None,
+            seed_column: None,
+            noise_percent: None,
+            ip_mode: None,
+            preserve_domain: false,
+            domain_allowlist: Vec::new(),
+            json_paths: None,
+            pattern: None,
+            replacement: None,
+            wasm_module: None,
+            wasm_fuel: None,
+            deterministic: true,
+            strategy: Strategy::Address.into(),
+            composite_fields: None,
+            on_type_mismatch: TypeMismatchPolicy::Fallback,
+            when: Some(RuleCondition {
+                self_matches: Some("(?i)confidential".to_string()),
+                or_detected: vec!["email".to_string()],
+                column: None,
+                equals: None,
+                on_missing_column: MissingColumnPolicy::Mask,
+            }),
+        };
+        let state = resolver_state(vec![rule], ExpressionHandling::Heuristic);
+        let input = ResultSetFixture {
+            columns: vec!["notes".to_string()],
+            rows: vec![
+                vec![Some("CONFIDENTIAL: pending deal".to_string())],
+                vec![Some("alice@example.com".to_string())],
+                vec![Some("routine follow-up call".to_string())],
+            ],
+        };
+        let masked = mask_all(&state, &input).await;
+
+        assert_ne!(masked.rows[0][0], input.rows[0][0], "regex match masks");
+        assert_ne!(masked.rows[1][0], input.rows[1][0], "detector hit masks");
+        assert_eq!(masked.rows[2][0], input.rows[2][0], "neither test held");
+        assert_eq!(state.stats.read().await.masking.condition_skipped, 1);
+    }
+
+    /// The MySQL path evaluates sibling conditions too, binding them once
+    /// all column definitions have arrived.
+    #[cfg(feature = "mysql")]
+    #[tokio::test]
+    async fn test_sibling_condition_mysql() {
+        let rule = MaskingRule {
+            id: None,
+            table: None,
+            column: "email".to_string(),
+            case_sensitive: false,
+            table_regex: None,
+            column_regex: None,
+            database: None,
+            compiled: Default::default(),
+            redact_text: None,
+            preserve_length: false,
+            date_shift_days: None,
+            seed_column: None,
+            noise_percent: None,
+            ip_mode: None,
+            preserve_domain: false,
+            domain_allowlist: Vec::new(),
+            json_paths: None,
+            pattern: None,
+            replacement: None,
+            wasm_module: None,
+            wasm_fuel: None,
+            deterministic: true,
+            strategy: Strategy::Email.into(),
+            composite_fields: None,
+            on_type_mismatch: TypeMismatchPolicy::Fallback,
+            when: Some(sibling_condition(MissingColumnPolicy::Mask)),
+        };
+        let state = resolver_state(vec![rule], ExpressionHandling::Heuristic);
+        let mut anonymizer = MySqlAnonymizer::new(state, 1);
+
+        let input = ResultSetFixture {
+            columns: vec!["email".to_string(), "account_type".to_string()],
+            rows: vec![
+                vec![
+                    Some("alice@example.com".to_string()),
+                    Some("personal".to_string()),
+                ],
+                vec![
+                    Some("sales@example.com".to_string()),
+                    Some("business".to_string()),
+                ],
+            ],
+        };
+        let (columns, rows) = input.to_mysql("users");
+        anonymizer.reset_columns();
+        for column in &columns {
+            anonymizer.on_column_definition(column).await;
+        }
+        let mut masked = Vec::new();
+        for row in rows {
+            masked.push(anonymizer.on_result_row(row).await.unwrap());
+        }
+        let masked = ResultSetFixture::from_mysql(&columns, &masked);
+
+        assert_ne!(masked.rows[0][0].as_deref(), Some("alice@example.com"));
+        assert_eq!(masked.rows[1][0].as_deref(), Some("sales@example.com"));
+    }
+
+    /// Not a correctness test: masks a join-shaped result where 90% of the
+    /// values repeat, with the memo on and off, and prints both timings.
+    /// Run with `cargo test bench_memo -- --ignored --nocapture`.
+    #[tokio::test]
+    #[ignore = "micro-benchmark"]
+    async fn bench_memo_on_repeated_values() {
+        let rows = (0..10_000)
+            .map(|i| {
+                let value = if i % 10 == 0 {
+                    format!("{}@example.com", i)
+                } else {
+                    "shared@example.com".to_string()
+                };
+                vec![Some(value)]
+            })
+            .collect();
+        let input = ResultSetFixture {
+            columns: vec!["city".to_string()],
+            rows,
+        };
+
+        let state = resolver_state(vec![rule_on(None, "city")], ExpressionHandling::Heuristic);
+        let start = std::time::Instant::now();
+        mask_all(&state, &input).await;
+        let memo_on = start.elapsed();
+
+        state.config.write().await.memo_entries = 0;
+        let start = std::time::Instant::now();
+        mask_all(&state, &input).await;
+        let memo_off = start.elapsed();
 
-        assert!(row.values[0].is_none(), "NULL should remain NULL");
-        assert!(row.values[1].is_some(), "Non-NULL should remain Some");
-        assert!(row.values[2].is_none(), "NULL should remain NULL");
+        println!("memo on: {memo_on:?}, memo off: {memo_off:?}");
     }
 }
@@ -0,0 +1,54 @@
+//! IronVeil — a PII-masking database proxy for PostgreSQL and MySQL.
+//!
+//! This crate can be used in two ways:
+//!
+//! - as the `iron-veil` binary, which wires everything together from CLI
+//!   arguments and a YAML config file, or
+//! - as a library, embedding the proxy inside another service via
+//!   [`proxy::ProxyServer`] with custom interceptors, metrics, and shutdown
+//!   handling.
+//!
+//! ```no_run
+//! use iron_veil::config::AppConfig;
+//! use iron_veil::proxy::ProxyServer;
+//! use iron_veil::state::DbProtocol;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let handle = ProxyServer::builder(AppConfig::default())
+//!     .listen_port(6543)
+//!     .upstream("db.internal", 5432)
+//!     .protocol(DbProtocol::Postgres)
+//!     .serve()
+//!     .await?;
+//! handle.join().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+#[cfg(not(any(feature = "postgres", feature = "mysql")))]
+compile_error!("at least one of the 'postgres' or 'mysql' features must be enabled");
+
+#[cfg(feature = "postgres")]
+pub mod admin;
+#[cfg(feature = "api")]
+pub mod api;
+pub mod alerts;
+pub mod audit;
+pub mod config;
+#[cfg(feature = "postgres")]
+pub mod db_scanner;
+pub mod error;
+pub mod hooks;
+pub mod interceptor;
+pub mod metrics;
+#[cfg(feature = "postgres")]
+pub mod oid_cache;
+pub mod protocol;
+pub mod proxy;
+pub mod scanner;
+pub mod sql_resolver;
+pub mod state;
+pub mod telemetry;
+pub mod version;
+#[cfg(feature = "wasm-plugins")]
+pub mod wasm;
@@ -1,44 +1,14 @@
 use anyhow::Result;
-use clap::{Parser, ValueEnum};
-use notify::{Config as NotifyConfig, Event, RecommendedWatcher, RecursiveMode, Watcher};
-use std::time::{Duration, Instant};
-use tokio::sync::Semaphore;
-use tokio_util::sync::CancellationToken;
-use tracing::{Instrument, info, info_span, warn};
-
-mod api;
-mod audit;
-mod config;
-mod db_scanner;
-mod interceptor;
-mod metrics;
-mod protocol;
-mod scanner;
-mod state;
-mod telemetry;
-
-use crate::config::AppConfig;
-use crate::interceptor::{Anonymizer, MySqlAnonymizer, MySqlPacketInterceptor, PacketInterceptor};
-use crate::protocol::mysql::{MySqlCodec, MySqlMessage};
-use crate::protocol::postgres::{PgMessage, PostgresCodec};
-use crate::state::{AppState, DbProtocol as StateDbProtocol, LogEntry};
-use bytes::BufMut;
-use chrono::Utc;
-use futures::{SinkExt, StreamExt};
-use rustls_platform_verifier::Verifier;
-use std::fs::File;
-use std::io::BufReader;
-use std::sync::Arc;
-use std::sync::atomic::Ordering;
-use tokio::io::AsyncReadExt;
-use tokio::io::AsyncWriteExt;
-use tokio_rustls::TlsAcceptor;
-use tokio_rustls::TlsConnector;
-use tokio_rustls::rustls::ClientConfig;
-use tokio_rustls::rustls::crypto::aws_lc_rs::default_provider;
-use tokio_rustls::rustls::pki_types::ServerName;
-use tokio_rustls::rustls::{ServerConfig, pki_types::CertificateDer, pki_types::PrivateKeyDer};
-use tokio_util::codec::Framed;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::time::Duration;
+use tracing::info;
+
+use iron_veil::config::AppConfig;
+use iron_veil::proxy::{ProxyServer, run_config_watcher, run_sighup_reloader};
+use iron_veil::state::DbProtocol as StateDbProtocol;
+#[cfg(feature = "api")]
+use iron_veil::api;
+use iron_veil::{metrics, telemetry};
 
 #[derive(Debug, Clone, Copy, ValueEnum, Default)]
 pub enum DbProtocol {
@@ -50,922 +20,530 @@ pub enum DbProtocol {
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    /// Subcommand to run instead of the proxy
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Port to listen on
     #[arg(short, long, default_value_t = 6543)]
     port: u16,
 
-    /// Upstream database host
-    #[arg(long, default_value = "127.0.0.1")]
-    upstream_host: String,
+    /// Upstream database host, overriding `upstream.host` from the config
+    /// (default: 127.0.0.1)
+    #[arg(long)]
+    upstream_host: Option<String>,
 
-    /// Upstream database port
-    #[arg(long, default_value_t = 5432)]
-    upstream_port: u16,
+    /// Upstream database port, overriding `upstream.port` from the config
+    /// (default: 5432)
+    #[arg(long)]
+    upstream_port: Option<u16>,
 
     /// Path to configuration file
     #[arg(long, default_value = "proxy.yaml")]
     config: String,
 
     /// Management API port
+    #[cfg(feature = "api")]
     #[arg(long, default_value_t = 3001)]
     api_port: u16,
 
-    /// Database protocol to proxy
-    #[arg(long, value_enum, default_value_t = DbProtocol::Postgres)]
-    protocol: DbProtocol,
+    /// IP the management API binds, overriding `api.bind_address` from the
+    /// config (default: 127.0.0.1 — exposing the control plane is opt-in)
+    #[cfg(feature = "api")]
+    #[arg(long = "api-bind")]
+    api_bind: Option<std::net::IpAddr>,
+
+    /// Database protocol to proxy, overriding `upstream.protocol` from the
+    /// config (default: postgres)
+    #[arg(long, value_enum)]
+    protocol: Option<DbProtocol>,
 
     /// Graceful shutdown timeout in seconds
     #[arg(long, default_value_t = 30)]
     shutdown_timeout: u64,
-}
 
-/// Waits for a shutdown signal (SIGTERM, SIGINT, or Ctrl+C)
-async fn shutdown_signal() {
-    let ctrl_c = async {
-        tokio::signal::ctrl_c()
-            .await
-            .expect("Failed to install Ctrl+C handler");
-    };
+    /// Fall back to the snapshot written after the last successful config
+    /// apply when the config file fails to load
+    #[arg(long, default_value_t = false)]
+    last_known_good: bool,
 
+    /// PID of a running iron-veil process to take over from. Listeners
+    /// bind with SO_REUSEPORT alongside the old process, which is then
+    /// signalled (SIGUSR2) to stop accepting and drain its sessions.
     #[cfg(unix)]
-    let terminate = async {
-        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
-            .expect("Failed to install SIGTERM handler")
-            .recv()
-            .await;
-    };
-
-    #[cfg(not(unix))]
-    let terminate = std::future::pending::<()>();
+    #[arg(long)]
+    upgrade_from: Option<i32>,
+}
 
-    tokio::select! {
-        _ = ctrl_c => info!("Received Ctrl+C, initiating shutdown..."),
-        _ = terminate => info!("Received SIGTERM, initiating shutdown..."),
-    }
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Connect to the upstream database, sample rows through the PII
+    /// scanner, and report which columns contain PII and whether the
+    /// masking rules cover them
+    #[cfg(feature = "postgres")]
+    Scan(Box<ScanArgs>),
+
+    /// Load and fully validate a config file, then exit 0 or 1 — for CI
+    /// to reject a broken rules file before deploy
+    ValidateConfig(ValidateConfigArgs),
 }
 
-/// Background task that periodically checks upstream database connectivity
-async fn run_health_check_task(
-    state: AppState,
+#[cfg(feature = "postgres")]
+#[derive(clap::Args, Debug)]
+struct ScanArgs {
+    /// Path to configuration file, consulted for scanner settings and to
+    /// mark which findings existing rules already cover. A missing file
+    /// is fine: scanning is how a rules file gets bootstrapped.
+    #[arg(long, default_value = "proxy.yaml")]
+    config: String,
+
+    /// Upstream database host
+    #[arg(long, default_value = "127.0.0.1")]
     upstream_host: String,
+
+    /// Upstream database port
+    #[arg(long, default_value_t = 5432)]
     upstream_port: u16,
-    config: Option<crate::config::HealthCheckConfig>,
-) {
-    let config = config.unwrap_or_default();
-    let interval = Duration::from_secs(config.interval_secs);
-    let timeout = Duration::from_secs(config.timeout_secs);
 
-    info!(
-        "Starting upstream health check task (interval: {}s, timeout: {}s)",
-        config.interval_secs, config.timeout_secs
-    );
+    /// Database protocol to scan
+    #[arg(long, value_enum, default_value_t = DbProtocol::Postgres)]
+    protocol: DbProtocol,
 
-    loop {
-        let start = Instant::now();
+    /// Database user to connect as
+    #[arg(long)]
+    username: String,
 
-        // Try to connect to upstream
-        let connect_result = tokio::time::timeout(
-            timeout,
-            tokio::net::TcpStream::connect(format!("{}:{}", upstream_host, upstream_port)),
-        )
-        .await;
+    /// Database password; falls back to $IRON_VEIL_DB_PASSWORD
+    #[arg(long)]
+    password: Option<String>,
 
-        let latency = start.elapsed().as_millis() as u64;
-
-        match connect_result {
-            Ok(Ok(_stream)) => {
-                // Connection successful
-                state.update_health_status(true, Some(latency), None).await;
-                tracing::debug!(
-                    "Health check passed: upstream {}:{} ({}ms)",
-                    upstream_host,
-                    upstream_port,
-                    latency
-                );
-            }
-            Ok(Err(e)) => {
-                // Connection failed
-                let error = format!("Connection failed: {}", e);
-                state
-                    .update_health_status(false, None, Some(error.clone()))
-                    .await;
-                warn!(
-                    "Health check failed: upstream {}:{} - {}",
-                    upstream_host, upstream_port, error
-                );
-            }
-            Err(_) => {
-                // Timeout
-                let error = format!("Connection timeout after {}s", config.timeout_secs);
-                state
-                    .update_health_status(false, None, Some(error.clone()))
-                    .await;
-                warn!(
-                    "Health check timeout: upstream {}:{} - {}",
-                    upstream_host, upstream_port, error
-                );
-            }
-        }
+    /// Database to scan
+    #[arg(long)]
+    database: String,
 
-        tokio::time::sleep(interval).await;
-    }
+    /// Rows to sample per table
+    #[arg(long, default_value_t = 1000)]
+    sample_rows: usize,
+
+    /// Schema to scan; repeatable (default: every non-system schema)
+    #[arg(long = "schema")]
+    schemas: Vec<String>,
+
+    /// Schema to skip; repeatable
+    #[arg(long = "exclude-schema")]
+    exclude_schemas: Vec<String>,
+
+    /// Table to skip; repeatable
+    #[arg(long = "exclude-table")]
+    exclude_tables: Vec<String>,
+
+    /// Minimum detection confidence for a column to appear in the report
+    #[arg(long, default_value_t = 0.5)]
+    confidence_threshold: f64,
+
+    /// Write the report to this file instead of stdout
+    #[arg(long)]
+    output: Option<std::path::PathBuf>,
+
+    /// Report format (default: json when --output is set, table otherwise)
+    #[arg(long, value_enum)]
+    format: Option<ReportFormat>,
 }
 
-/// Background task that watches the config file for changes and reloads
-async fn run_config_watcher(state: AppState, config_path: String) {
-    use std::path::Path;
-    use std::sync::mpsc::channel;
+/// Output format for the `scan` and `validate-config` reports
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ReportFormat {
+    /// Human-readable table
+    Table,
+    /// Machine-readable JSON
+    Json,
+}
 
-    let path = Path::new(&config_path);
-    let parent = path.parent().unwrap_or(Path::new("."));
+#[derive(clap::Args, Debug)]
+struct ValidateConfigArgs {
+    /// Path to configuration file
+    #[arg(long, default_value = "proxy.yaml")]
+    config: String,
 
-    // Create a channel to receive events
-    let (tx, rx) = channel();
+    /// Report format
+    #[arg(long, value_enum, default_value = "table")]
+    format: ReportFormat,
+}
 
-    // Create a watcher with debounce
-    let mut watcher: RecommendedWatcher = match Watcher::new(
-        move |res: Result<Event, notify::Error>| {
-            if let Ok(event) = res {
-                let _ = tx.send(event);
+/// Runs the `validate-config` subcommand: loads the config, runs every
+/// check a staged apply would run, prints a summary plus the findings,
+/// and exits non-zero when any finding is an error
+fn run_validate_config(args: ValidateConfigArgs) -> Result<()> {
+    use iron_veil::config::{FindingSeverity, ValidationFinding};
+
+    let (config, mut findings) = match AppConfig::parse(&args.config) {
+        Ok(config) => (Some(config), vec![]),
+        Err(e) => (
+            None,
+            vec![ValidationFinding {
+                severity: FindingSeverity::Error,
+                component: "config".to_string(),
+                message: e.to_string(),
+            }],
+        ),
+    };
+    if let Some(config) = config.as_ref() {
+        findings.extend(config.check());
+    }
+    let valid = findings
+        .iter()
+        .all(|f| f.severity != FindingSeverity::Error);
+
+    let audit_sinks = |config: &AppConfig| -> Vec<String> {
+        let mut sinks = Vec::new();
+        if let Some(audit) = config.audit.as_ref()
+            && audit.enabled
+        {
+            if audit.log_to_stdout {
+                sinks.push("stdout".to_string());
+            }
+            if let Some(log_file) = audit.log_file.as_ref() {
+                sinks.push(log_file.clone());
             }
-        },
-        NotifyConfig::default().with_poll_interval(Duration::from_secs(2)),
-    ) {
-        Ok(w) => w,
-        Err(e) => {
-            warn!(
-                "Failed to create config file watcher: {}. Hot reload disabled.",
-                e
-            );
-            return;
         }
+        sinks
     };
 
-    // Watch the config file's parent directory
-    if let Err(e) = watcher.watch(parent, RecursiveMode::NonRecursive) {
-        warn!(
-            "Failed to watch config directory: {}. Hot reload disabled.",
-            e
-        );
-        return;
-    }
-
-    info!("Config file watcher started for {}", config_path);
-
-    let filename = path
-        .file_name()
-        .and_then(|f| f.to_str())
-        .unwrap_or("proxy.yaml");
-    let mut last_reload = Instant::now();
-    let debounce_duration = Duration::from_secs(1);
-
-    loop {
-        // Check for events with a timeout
-        match rx.recv_timeout(Duration::from_secs(5)) {
-            Ok(event) => {
-                // Check if this event is for our config file
-                let is_config_file = event.paths.iter().any(|p| {
-                    p.file_name()
-                        .and_then(|f| f.to_str())
-                        .map(|f| f == filename)
-                        .unwrap_or(false)
-                });
-
-                if is_config_file && last_reload.elapsed() > debounce_duration {
-                    info!("Config file changed, reloading...");
-                    match state.reload_config().await {
-                        Ok(rules_count) => {
-                            info!("Configuration reloaded: {} rules", rules_count);
-                        }
-                        Err(e) => {
-                            warn!("Failed to reload configuration: {}", e);
-                        }
+    match args.format {
+        ReportFormat::Json => {
+            let summary = config.as_ref().map(|c| {
+                serde_json::json!({
+                    "rules": c.rules.len(),
+                    "masking_enabled": c.masking_enabled,
+                    "tls": c.tls.as_ref().is_some_and(|tls| tls.enabled),
+                    "audit_sinks": audit_sinks(c),
+                })
+            });
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&serde_json::json!({
+                    "config": args.config,
+                    "valid": valid,
+                    "summary": summary,
+                    "findings": findings,
+                }))?
+            );
+        }
+        ReportFormat::Table => {
+            println!("Config: {}", args.config);
+            if let Some(c) = config.as_ref() {
+                println!("  rules: {}", c.rules.len());
+                println!(
+                    "  masking: {}",
+                    if c.masking_enabled { "enabled" } else { "disabled" }
+                );
+                println!(
+                    "  tls: {}",
+                    if c.tls.as_ref().is_some_and(|tls| tls.enabled) {
+                        "on"
+                    } else {
+                        "off"
                     }
-                    last_reload = Instant::now();
-                }
-            }
-            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
-                // No events, continue watching
+                );
+                let sinks = audit_sinks(c);
+                println!(
+                    "  audit sinks: {}",
+                    if sinks.is_empty() {
+                        "none".to_string()
+                    } else {
+                        sinks.join(", ")
+                    }
+                );
             }
-            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
-                warn!("Config watcher channel disconnected, stopping watcher");
-                break;
+            for finding in &findings {
+                println!("{}: [{}] {}", finding.severity, finding.component, finding.message);
             }
+            println!(
+                "Configuration is {}",
+                if valid { "valid" } else { "invalid" }
+            );
         }
     }
-}
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let args = Args::parse();
+    if !valid {
+        std::process::exit(1);
+    }
+    Ok(())
+}
 
-    // Load configuration
-    let config = AppConfig::load(&args.config)?;
+/// Runs the `scan` subcommand: samples the upstream database and emits a
+/// PII discovery report
+#[cfg(feature = "postgres")]
+async fn run_scan(args: ScanArgs) -> Result<()> {
+    use iron_veil::db_scanner::{DbScanner, ScanConfig};
 
-    // Initialize telemetry (must be done before any tracing calls)
+    let config = if std::path::Path::new(&args.config).exists() {
+        AppConfig::load(&args.config)?
+    } else {
+        eprintln!(
+            "No config at {}; scanning with default settings and no rule coverage",
+            args.config
+        );
+        AppConfig::default()
+    };
     let _telemetry_guard = telemetry::init_telemetry(config.telemetry.as_ref())?;
 
-    info!(
-        "Loaded {} masking rules from {}",
-        config.rules.len(),
-        args.config
-    );
-
-    // Initialize Prometheus metrics
-    let metrics_handle = metrics::init_metrics();
-    info!("Prometheus metrics initialized");
-
-    // Load TLS config if enabled
-    let tls_acceptor = if let Some(tls_config) = &config.tls {
-        if tls_config.enabled {
-            info!("TLS enabled. Loading certs from {}", tls_config.cert_path);
-            let certs = load_certs(&tls_config.cert_path)?;
-            let key = load_keys(&tls_config.key_path)?;
-            let config = ServerConfig::builder()
-                .with_no_client_auth()
-                .with_single_cert(certs, key)?;
-            Some(TlsAcceptor::from(Arc::new(config)))
-        } else {
-            info!("TLS disabled in config.");
-            None
-        }
-    } else {
-        info!("TLS not configured.");
-        None
+    let password = args
+        .password
+        .or_else(|| std::env::var("IRON_VEIL_DB_PASSWORD").ok())
+        .unwrap_or_default();
+
+    let scan_config = ScanConfig {
+        username: args.username,
+        password,
+        database: args.database,
+        sample_size: args.sample_rows,
+        // discover() works from the schema list, not this field
+        schema: String::new(),
+        exclude_tables: args.exclude_tables,
+        confidence_threshold: args.confidence_threshold,
     };
 
-    // Initialize shared state
     let db_protocol = match args.protocol {
         DbProtocol::Postgres => StateDbProtocol::Postgres,
         DbProtocol::Mysql => StateDbProtocol::MySql,
     };
-    let state = AppState::new(
-        config.clone(),
-        args.config.clone(),
-        args.upstream_host.clone(),
-        args.upstream_port,
-        db_protocol,
-    )
-    .with_metrics(metrics_handle);
-
-    // Start Management API in a separate task
-    let api_port = args.api_port;
-    let api_state = state.clone();
-    tokio::spawn(async move {
-        if let Err(e) = api::start_api_server(api_port, api_state).await {
-            tracing::error!("API server error: {}", e);
-        }
-    });
-
-    // Start upstream health check task
-    let health_check_enabled = config
-        .health_check
-        .as_ref()
-        .map(|h| h.enabled)
-        .unwrap_or(true);
-
-    if health_check_enabled {
-        let health_state = state.clone();
-        let health_host = args.upstream_host.clone();
-        let health_port = args.upstream_port;
-        let health_config = config.health_check.clone();
-        tokio::spawn(async move {
-            run_health_check_task(health_state, health_host, health_port, health_config).await;
-        });
-    }
-
-    // Start config file watcher for hot reload
-    let watch_state = state.clone();
-    let config_path = args.config.clone();
-    tokio::spawn(async move {
-        run_config_watcher(watch_state, config_path).await;
-    });
 
-    // Start stats history recorder (every 5 seconds)
-    let stats_state = state.clone();
-    tokio::spawn(async move {
-        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(5));
-        loop {
-            interval.tick().await;
-            stats_state.record_history_snapshot().await;
-        }
-    });
-
-    info!("Starting DB Proxy on port {}", args.port);
-    info!(
-        "Forwarding to upstream at {}:{}",
-        args.upstream_host, args.upstream_port
-    );
-    info!("Protocol: {:?}", args.protocol);
-
-    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", args.port)).await?;
-    let protocol = args.protocol;
-
-    // Create cancellation token for graceful shutdown
-    let cancel_token = CancellationToken::new();
-    let shutdown_timeout = args.shutdown_timeout;
+    let scanner = DbScanner::new(args.upstream_host, args.upstream_port, db_protocol)
+        .with_scanner_config(config.scanner.as_ref());
+    let report = scanner
+        .discover(
+            &scan_config,
+            &args.schemas,
+            &args.exclude_schemas,
+            &config.rules,
+        )
+        .await?;
 
-    // Connection limiting
-    let max_connections = config.limits.as_ref().and_then(|l| l.max_connections);
-    let connection_semaphore = max_connections.map(|max| {
-        info!("Connection limit set to {}", max);
-        Arc::new(Semaphore::new(max))
+    let format = args.format.unwrap_or(if args.output.is_some() {
+        ReportFormat::Json
+    } else {
+        ReportFormat::Table
     });
+    let rendered = match format {
+        ReportFormat::Table => report.render_table(),
+        ReportFormat::Json => serde_json::to_string_pretty(&report)? + "\n",
+    };
 
-    // Rate limiting state
-    let rate_limit = config
-        .limits
-        .as_ref()
-        .and_then(|l| l.connections_per_second);
-    if let Some(rate) = rate_limit {
-        info!("Rate limit set to {} connections/second", rate);
-    }
-    let mut rate_limit_tokens: u32 = rate_limit.unwrap_or(0);
-    let mut last_refill = Instant::now();
-
-    // Accept connections until shutdown signal
-    loop {
-        tokio::select! {
-            // Wait for new connection
-            accept_result = listener.accept() => {
-                let (client_socket, client_addr) = accept_result?;
-
-                // Rate limiting check
-                if let Some(max_rate) = rate_limit {
-                    // Refill tokens based on elapsed time
-                    let elapsed = last_refill.elapsed();
-                    if elapsed >= Duration::from_secs(1) {
-                        rate_limit_tokens = max_rate;
-                        last_refill = Instant::now();
-                    }
-
-                    if rate_limit_tokens == 0 {
-                        warn!("Rate limit exceeded, rejecting connection from {}", client_addr);
-                        drop(client_socket);
-                        continue;
-                    }
-                    rate_limit_tokens = rate_limit_tokens.saturating_sub(1);
-                }
-
-                // Connection limit check
-                let permit = if let Some(ref sem) = connection_semaphore {
-                    match sem.clone().try_acquire_owned() {
-                        Ok(permit) => Some(permit),
-                        Err(_) => {
-                            warn!("Connection limit reached, rejecting connection from {}", client_addr);
-                            drop(client_socket);
-                            continue;
-                        }
-                    }
-                } else {
-                    None
-                };
-
-                info!("Accepted connection from {}", client_addr);
-
-                let upstream_host = args.upstream_host.clone();
-                let upstream_port = args.upstream_port;
-                let state = state.clone();
-                let tls_acceptor = tls_acceptor.clone();
-
-                tokio::spawn(async move {
-                    // Hold the permit for the duration of the connection
-                    let _permit = permit;
-
-                    let span = info_span!(
-                        "connection",
-                        client.addr = %client_addr,
-                        upstream.host = %upstream_host,
-                        upstream.port = %upstream_port,
-                        protocol = ?protocol
-                    );
-
-                    async {
-                        state.active_connections.fetch_add(1, Ordering::Relaxed);
-                        state.record_connection().await;
-                        let result = match protocol {
-                            DbProtocol::Postgres => {
-                                process_postgres_connection(
-                                    client_socket,
-                                    upstream_host,
-                                    upstream_port,
-                                    state.clone(),
-                                    tls_acceptor,
-                                )
-                                .await
-                            }
-                            DbProtocol::Mysql => {
-                                process_mysql_connection(
-                                    client_socket,
-                                    upstream_host,
-                                    upstream_port,
-                                    state.clone(),
-                                )
-                                .await
-                            }
-                        };
-                        state.active_connections.fetch_sub(1, Ordering::Relaxed);
-
-                        if let Err(e) = result {
-                            tracing::error!(error = %e, "Connection error");
-                        }
-                    }
-                    .instrument(span)
-                    .await
-                });
-            }
-
-            // Wait for shutdown signal
-            _ = shutdown_signal() => {
-                info!("Shutdown signal received, stopping accept loop...");
-                break;
-            }
-        }
-    }
-
-    // Graceful shutdown: wait for active connections to drain
-    info!(
-        "Waiting for {} active connections to close (timeout: {}s)...",
-        state.active_connections.load(Ordering::Relaxed),
-        shutdown_timeout
-    );
-
-    // Signal all connections to shutdown
-    cancel_token.cancel();
-
-    // Wait for connections to drain with timeout
-    let drain_start = std::time::Instant::now();
-    let timeout_duration = std::time::Duration::from_secs(shutdown_timeout);
-
-    while state.active_connections.load(Ordering::Relaxed) > 0 {
-        if drain_start.elapsed() >= timeout_duration {
-            warn!(
-                "Shutdown timeout reached, {} connections still active",
-                state.active_connections.load(Ordering::Relaxed)
+    match args.output {
+        Some(path) => {
+            std::fs::write(&path, rendered)?;
+            println!(
+                "Scan report written to {} ({} PII columns, {} uncovered)",
+                path.display(),
+                report.columns.len(),
+                report.uncovered
             );
-            break;
         }
-        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        None => print!("{}", rendered),
     }
 
-    info!("Shutdown complete.");
     Ok(())
 }
 
-// ============================================================================
-// PostgreSQL Connection Handling
-// ============================================================================
-
-async fn process_postgres_connection(
-    mut client_socket: tokio::net::TcpStream,
-    upstream_host: String,
-    upstream_port: u16,
-    state: AppState,
-    tls_acceptor: Option<TlsAcceptor>,
-) -> Result<()> {
-    let mut buffer = [0u8; 8];
-    let n = client_socket.peek(&mut buffer).await?;
-    if n >= 8 {
-        let len = u32::from_be_bytes(
-            buffer[0..4]
-                .try_into()
-                .map_err(|_| anyhow::anyhow!("Invalid startup message length"))?,
-        );
-        let code = u32::from_be_bytes(
-            buffer[4..8]
-                .try_into()
-                .map_err(|_| anyhow::anyhow!("Invalid startup message code"))?,
-        );
-
-        if len == 8 && code == 80877103 {
-            // It is an SSLRequest
-            let mut trash = [0u8; 8];
-            client_socket.read_exact(&mut trash).await?;
-
-            if let Some(acceptor) = tls_acceptor {
-                info!("Received SSLRequest, accepting...");
-                client_socket.write_all(b"S").await?;
-
-                let tls_stream = acceptor.accept(client_socket).await?;
-                return handle_postgres_protocol(tls_stream, upstream_host, upstream_port, state)
-                    .await;
-            } else {
-                info!("Received SSLRequest, denying (TLS not configured)...");
-                client_socket.write_all(b"N").await?;
-            }
-        }
-    }
-
-    handle_postgres_protocol(client_socket, upstream_host, upstream_port, state).await
+/// Waits for the SIGUSR2 upgrade convention: a replacement process has
+/// bound the listeners and wants this one to stop accepting and drain
+#[cfg(unix)]
+async fn upgrade_signal() {
+    tokio::signal::unix::signal(tokio::signal::unix::SignalKind::user_defined2())
+        .expect("Failed to install SIGUSR2 handler")
+        .recv()
+        .await;
 }
 
-/// Creates a TLS ClientConfig that uses the OS native certificate verifier.
-pub fn create_upstream_tls_config() -> ClientConfig {
-    // Initialize the platform-specific verifier
-    let provider = Arc::new(default_provider());
-    let verifier = Arc::new(Verifier::new(provider).expect("Failed to create platform verifier"));
-
-    ClientConfig::builder()
-        // .dangerous() is required because we are overriding the default
-        // WebPki verifier with a custom one (the platform verifier).
-        .dangerous()
-        .with_custom_certificate_verifier(verifier)
-        .with_no_client_auth()
+#[cfg(not(unix))]
+async fn upgrade_signal() {
+    std::future::pending::<()>().await
 }
 
-async fn handle_postgres_protocol<S>(
-    client_socket: S,
-    upstream_host: String,
-    upstream_port: u16,
-    state: AppState,
-) -> Result<()>
-where
-    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
-{
-    // Get timeout configuration
-    let (connect_timeout, idle_timeout) = {
-        let config = state.config.read().await;
-        let limits = config.limits.as_ref();
-        (
-            Duration::from_secs(limits.map(|l| l.connect_timeout_secs).unwrap_or(30)),
-            Duration::from_secs(limits.map(|l| l.idle_timeout_secs).unwrap_or(300)),
-        )
+/// Waits for a shutdown signal (SIGTERM, SIGINT, or Ctrl+C)
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
     };
 
-    // Create upstream connection with timeout
-    let mut upstream_socket = tokio::time::timeout(
-        connect_timeout,
-        tokio::net::TcpStream::connect(format!("{}:{}", upstream_host, upstream_port)),
-    )
-    .await
-    .map_err(|_| anyhow::anyhow!("Upstream connection timeout after {:?}", connect_timeout))??;
-
-    // Check if upstream TLS is enabled
-    let upstream_tls_enabled = {
-        let config = state.config.read().await;
-        config.upstream_tls
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
     };
 
-    if upstream_tls_enabled {
-        info!(
-            "Upstream TLS enabled. Attempting handshake with {}:{}",
-            upstream_host, upstream_port
-        );
-
-        // 1. Send SSLRequest to upstream
-        let mut ssl_request = bytes::BytesMut::with_capacity(8);
-        ssl_request.put_u32(8); // Length
-        ssl_request.put_u32(80877103); // SSLRequest code
-        upstream_socket.write_all(&ssl_request).await?;
-
-        // 2. Read response (1 byte)
-        let mut response = [0u8; 1];
-        upstream_socket.read_exact(&mut response).await?;
-
-        if response[0] == b'S' {
-            info!("Upstream accepted SSLRequest. Upgrading connection...");
-
-            // 3. Upgrade to TLS
-            let client_config = Arc::new(create_upstream_tls_config());
-            let connector = TlsConnector::from(client_config);
-
-            let domain = ServerName::try_from(upstream_host.as_str())
-                .map_err(|_| anyhow::anyhow!("Invalid DNS name for upstream host"))?
-                .to_owned();
-
-            let upstream_tls_stream = connector.connect(domain, upstream_socket).await?;
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
 
-            // 4. Continue with TLS stream
-            return handle_postgres_protocol_inner(
-                client_socket,
-                upstream_tls_stream,
-                state,
-                idle_timeout,
-            )
-            .await;
-        } else {
-            tracing::warn!(
-                "Upstream denied SSLRequest. Falling back to cleartext (or aborting if strict)."
-            );
-            // For now, we fall back to cleartext as per standard behavior, but you might want to enforce it.
-        }
+    tokio::select! {
+        _ = ctrl_c => info!("Received Ctrl+C, initiating shutdown..."),
+        _ = terminate => info!("Received SIGTERM, initiating shutdown..."),
     }
-
-    // Cleartext connection
-    handle_postgres_protocol_inner(client_socket, upstream_socket, state, idle_timeout).await
 }
 
-async fn handle_postgres_protocol_inner<S, U>(
-    client_socket: S,
-    upstream_socket: U,
-    state: AppState,
-    idle_timeout: Duration,
-) -> Result<()>
-where
-    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
-    U: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
-{
-    let mut client_framed = Framed::new(client_socket, PostgresCodec::new());
-    let mut upstream_framed = Framed::new(upstream_socket, PostgresCodec::new_upstream());
-
-    let connection_id = rand::random::<u64>() as usize;
-    let mut interceptor = Anonymizer::new(state.clone(), connection_id);
-
-    loop {
-        tokio::select! {
-            // Client -> Upstream
-            msg = client_framed.next() => {
-                match msg {
-                    Some(Ok(msg)) => {
-                        match msg {
-                            PgMessage::SSLRequest => {
-                                info!("Received SSLRequest, denying...");
-                                // Deny SSL, force cleartext
-                                client_framed.get_mut().write_all(b"N").await?;
-                            }
-                            PgMessage::Query(ref q) => {
-                                let query_str = String::from_utf8_lossy(&q.query).to_string();
-                                let id = format!("{:x}", rand::random::<u128>());
-                                state.add_log(LogEntry {
-                                    id,
-                                    timestamp: Utc::now(),
-                                    connection_id,
-                                    event_type: "Query".to_string(),
-                                    content: query_str.clone(),
-                                    details: None,
-                                }).await;
-
-                                // Record query type stats
-                                let query_type = query_str
-                                    .split_whitespace()
-                                    .next()
-                                    .unwrap_or("OTHER")
-                                    .to_uppercase();
-                                state.record_query(&query_type).await;
-
-                                upstream_framed.send(msg).await?;
-                            }
-                            PgMessage::Parse(ref p) => {
-                                let query_str = String::from_utf8_lossy(&p.query).to_string();
-                                let id = format!("{:x}", rand::random::<u128>());
-                                state.add_log(LogEntry {
-                                    id,
-                                    timestamp: Utc::now(),
-                                    connection_id,
-                                    event_type: "Parse".to_string(),
-                                    content: query_str.clone(),
-                                    details: None,
-                                }).await;
-
-                                // Record query type stats for prepared statements
-                                let query_type = query_str
-                                    .split_whitespace()
-                                    .next()
-                                    .unwrap_or("OTHER")
-                                    .to_uppercase();
-                                state.record_query(&query_type).await;
-
-                                upstream_framed.send(msg).await?;
-                            }
-                            _ => {
-                                // Forward other messages (Startup, Query, etc.)
-                                upstream_framed.send(msg).await?;
-                            }
-                        }
-                    }
-                    Some(Err(e)) => return Err(e),
-                    None => return Ok(()), // Client disconnected
-                }
-            }
-            // Upstream -> Client
-            msg = upstream_framed.next() => {
-                match msg {
-                    Some(Ok(msg)) => {
-                        let msg_to_send = match msg {
-                            PgMessage::RowDescription(ref rd) => {
-                                interceptor.on_row_description(rd).await;
-                                PgMessage::RowDescription(rd.clone())
-                            }
-                            PgMessage::DataRow(dr) => {
-                                let new_dr = interceptor.on_data_row(dr).await?;
-                                PgMessage::DataRow(new_dr)
-                            }
-                            _ => msg,
-                        };
-                        client_framed.send(msg_to_send).await?;
-                    }
-                    Some(Err(e)) => return Err(e),
-                    None => return Ok(()), // Upstream disconnected
-                }
-            }
-            // Idle timeout
-            _ = tokio::time::sleep(idle_timeout) => {
-                info!("Connection idle timeout after {:?}", idle_timeout);
-                return Ok(());
-            }
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+
+    match args.command {
+        #[cfg(feature = "postgres")]
+        Some(Command::Scan(scan_args)) => return run_scan(*scan_args).await,
+        Some(Command::ValidateConfig(validate_args)) => {
+            return run_validate_config(validate_args);
         }
+        None => {}
     }
-}
-
-// ============================================================================
-// MySQL Connection Handling
-// ============================================================================
 
-async fn process_mysql_connection(
-    client_socket: tokio::net::TcpStream,
-    upstream_host: String,
-    upstream_port: u16,
-    state: AppState,
-) -> Result<()> {
-    // Get timeout configuration
-    let (connect_timeout, idle_timeout) = {
-        let config = state.config.read().await;
-        let limits = config.limits.as_ref();
-        (
-            Duration::from_secs(limits.map(|l| l.connect_timeout_secs).unwrap_or(30)),
-            Duration::from_secs(limits.map(|l| l.idle_timeout_secs).unwrap_or(300)),
-        )
+    // Load configuration
+    let (config, from_snapshot) = if args.last_known_good {
+        AppConfig::load_or_last_known_good(&args.config)?
+    } else {
+        (AppConfig::load(&args.config)?, false)
     };
 
-    // Connect to upstream MySQL server with timeout
-    let upstream_socket = tokio::time::timeout(
-        connect_timeout,
-        tokio::net::TcpStream::connect(format!("{}:{}", upstream_host, upstream_port)),
-    )
-    .await
-    .map_err(|_| anyhow::anyhow!("Upstream connection timeout after {:?}", connect_timeout))??;
+    // Initialize telemetry (must be done before any tracing calls)
+    let _telemetry_guard = telemetry::init_telemetry(config.telemetry.as_ref())?;
 
-    handle_mysql_protocol(client_socket, upstream_socket, state, idle_timeout).await
-}
+    if from_snapshot {
+        tracing::warn!(
+            "Config at {} failed to load; running on the last-known-good snapshot",
+            args.config
+        );
+    }
+    info!(
+        "Loaded {} masking rules from {}",
+        config.rules.len(),
+        args.config
+    );
 
-async fn handle_mysql_protocol<S, U>(
-    client_socket: S,
-    upstream_socket: U,
-    state: AppState,
-    idle_timeout: Duration,
-) -> Result<()>
-where
-    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
-    U: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
-{
-    let mut client_framed = Framed::new(client_socket, MySqlCodec::new_server());
-    let mut upstream_framed = Framed::new(upstream_socket, MySqlCodec::new_client());
-
-    let connection_id = rand::random::<u64>() as usize;
-    let mut interceptor = MySqlAnonymizer::new(state.clone(), connection_id);
-
-    // Phase 1: Forward handshake from upstream to client
-    let handshake = match upstream_framed.next().await {
-        Some(Ok(MySqlMessage::Handshake(h))) => {
-            info!(server_version = %h.server_version, "Received MySQL handshake from upstream");
-            // Forward the handshake to the client
-            client_framed
-                .send(MySqlMessage::Handshake(h.clone()))
-                .await?;
-            h
-        }
-        Some(Ok(other)) => {
-            tracing::warn!("Expected handshake, got {:?}", other);
-            return Err(anyhow::anyhow!("Protocol error: expected handshake"));
-        }
-        Some(Err(e)) => return Err(e),
-        None => return Ok(()),
+    // Install the configured metrics backend (default: prometheus)
+    let metrics_backend = metrics::init_metrics(config.metrics.as_ref());
+
+    // Resolve the upstream target: explicitly passed flags win, then the
+    // config's `upstream` section, then the built-in defaults. The log
+    // names the winning source per field so nobody has to guess.
+    let upstream = config.upstream.clone();
+    let (upstream_host, host_source) = match args.upstream_host.clone() {
+        Some(host) => (host, "flag"),
+        None => match upstream.as_ref().and_then(|u| u.host.clone()) {
+            Some(host) => (host, "config"),
+            None => ("127.0.0.1".to_string(), "default"),
+        },
+    };
+    let (upstream_port, port_source) = match args.upstream_port {
+        Some(port) => (port, "flag"),
+        None => match upstream.as_ref().and_then(|u| u.port) {
+            Some(port) => (port, "config"),
+            None => (5432, "default"),
+        },
+    };
+    let (protocol, protocol_source) = match args.protocol {
+        Some(protocol) => (protocol, "flag"),
+        None => match upstream.as_ref().and_then(|u| u.protocol) {
+            Some(iron_veil::config::UpstreamProtocol::Postgres) => {
+                (DbProtocol::Postgres, "config")
+            }
+            Some(iron_veil::config::UpstreamProtocol::Mysql) => (DbProtocol::Mysql, "config"),
+            None => (DbProtocol::Postgres, "default"),
+        },
+    };
+    let db_protocol = match protocol {
+        DbProtocol::Postgres => StateDbProtocol::Postgres,
+        DbProtocol::Mysql => StateDbProtocol::MySql,
     };
 
-    // Update codec capability flags
-    client_framed
-        .codec_mut()
-        .set_capability_flags(handshake.capability_flags);
-    upstream_framed
-        .codec_mut()
-        .set_capability_flags(handshake.capability_flags);
-
-    // Phase 2: Forward client handshake response to upstream
-    match client_framed.next().await {
-        Some(Ok(MySqlMessage::HandshakeResponse(r))) => {
-            info!(username = %r.username, database = ?r.database, "Received client handshake response");
-            // Update capability flags based on what client actually supports
-            client_framed
-                .codec_mut()
-                .set_capability_flags(r.capability_flags);
-            upstream_framed
-                .codec_mut()
-                .set_capability_flags(r.capability_flags);
-            upstream_framed
-                .send(MySqlMessage::HandshakeResponse(r))
-                .await?;
-        }
-        Some(Ok(other)) => {
-            tracing::warn!("Expected handshake response, got {:?}", other);
-            return Err(anyhow::anyhow!(
-                "Protocol error: expected handshake response"
-            ));
-        }
-        Some(Err(e)) => return Err(e),
-        None => return Ok(()),
-    }
+    info!("Starting DB Proxy on port {}", args.port);
+    info!(
+        "Upstream target {}:{} via {:?} (host from {}, port from {}, protocol from {})",
+        upstream_host, upstream_port, protocol, host_source, port_source, protocol_source
+    );
 
-    // Phase 3: Forward auth result
-    match upstream_framed.next().await {
-        Some(Ok(msg @ MySqlMessage::Ok(_))) => {
-            info!("MySQL authentication successful");
-            client_framed.send(msg).await?;
-        }
-        Some(Ok(MySqlMessage::Err(e))) => {
-            tracing::warn!(error_code = e.error_code, "MySQL authentication failed");
-            client_framed.send(MySqlMessage::Err(e)).await?;
-            return Ok(());
-        }
-        Some(Ok(other)) => {
-            // Could be auth switch request or other auth packets - forward as-is
-            client_framed.send(other).await?;
+    #[cfg(unix)]
+    let upgrade_from = args.upgrade_from;
+    #[cfg(not(unix))]
+    let upgrade_from: Option<i32> = None;
+
+    let watch_config = config.watch;
+
+    let handle = ProxyServer::builder(config)
+        .config_path(args.config.clone())
+        .listen_port(args.port)
+        .upstream(upstream_host, upstream_port)
+        .upstream_override(iron_veil::state::UpstreamOverride {
+            host: args.upstream_host.clone(),
+            port: args.upstream_port,
+        })
+        .protocol(db_protocol)
+        .metrics(metrics_backend)
+        .reuse_port(upgrade_from.is_some())
+        .serve()
+        .await?;
+
+    // Handover: our listeners are bound, so tell the old process to stop
+    // accepting and drain. New connections land here from now on.
+    #[cfg(unix)]
+    if let Some(pid) = upgrade_from {
+        info!("Listeners bound; asking pid {} to drain (SIGUSR2)", pid);
+        if unsafe { libc::kill(pid, libc::SIGUSR2) } != 0 {
+            anyhow::bail!(
+                "failed to signal pid {}: {}",
+                pid,
+                std::io::Error::last_os_error()
+            );
         }
-        Some(Err(e)) => return Err(e),
-        None => return Ok(()),
     }
 
-    // Phase 4: Command phase - bidirectional proxy with interception
-    loop {
-        tokio::select! {
-            // Client -> Upstream
-            msg = client_framed.next() => {
-                match msg {
-                    Some(Ok(msg)) => {
-                        if let MySqlMessage::Query(q) = &msg {
-                            let query_str = String::from_utf8_lossy(&q.query).to_string();
-                            let id = format!("{:x}", rand::random::<u128>());
-                            state.add_log(LogEntry {
-                                id,
-                                timestamp: Utc::now(),
-                                connection_id,
-                                event_type: "MySqlQuery".to_string(),
-                                content: query_str.clone(),
-                                details: None,
-                            }).await;
-
-                            // Record query type stats
-                            let query_type = query_str
-                                .split_whitespace()
-                                .next()
-                                .unwrap_or("OTHER")
-                                .to_uppercase();
-                            state.record_query(&query_type).await;
-
-                            // Reset interceptor for new result set
-                            interceptor.reset_columns();
-                        }
-                        upstream_framed.send(msg).await?;
-                    }
-                    Some(Err(e)) => return Err(e),
-                    None => return Ok(()),
-                }
-            }
-            // Upstream -> Client
-            msg = upstream_framed.next() => {
-                match msg {
-                    Some(Ok(msg)) => {
-                        let msg_to_send = match msg {
-                            MySqlMessage::ColumnDefinition(ref col) => {
-                                interceptor.on_column_definition(col).await;
-                                msg
-                            }
-                            MySqlMessage::ResultRow(row) => {
-                                let new_row = interceptor.on_result_row(row).await?;
-                                MySqlMessage::ResultRow(new_row)
-                            }
-                            MySqlMessage::Eof(_) => {
-                                // EOF after columns means we're about to get rows
-                                // EOF after rows means result set is done
-                                msg
-                            }
-                            _ => msg,
-                        };
-                        client_framed.send(msg_to_send).await?;
-                    }
-                    Some(Err(e)) => return Err(e),
-                    None => return Ok(()),
-                }
-            }
-            // Idle timeout
-            _ = tokio::time::sleep(idle_timeout) => {
-                info!("MySQL connection idle timeout after {:?}", idle_timeout);
-                return Ok(());
+    // Start Management API in a separate task
+    #[cfg(feature = "api")]
+    {
+        let api_port = args.api_port;
+        let api_bind = args.api_bind;
+        let api_state = handle.state().clone();
+        tokio::spawn(async move {
+            if let Err(e) = api::start_api_server(api_port, api_bind, api_state).await {
+                tracing::error!("API server error: {}", e);
             }
-        }
+        });
     }
-}
 
-fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
-    let certfile = File::open(path)?;
-    let mut reader = BufReader::new(certfile);
-    let certs = rustls_pemfile::certs(&mut reader).collect::<Result<Vec<_>, _>>()?;
-    Ok(certs)
-}
+    // Reload on SIGHUP, the convention deployment tooling expects after
+    // pushing a new config file (no-op on Windows)
+    let sighup_state = handle.state().clone();
+    tokio::spawn(async move {
+        run_sighup_reloader(sighup_state).await;
+    });
+
+    // Start the config file watcher for hot reload (opt-in via `watch: true`)
+    if watch_config {
+        let watch_state = handle.state().clone();
+        let config_path = args.config.clone();
+        tokio::spawn(async move {
+            run_config_watcher(watch_state, config_path).await;
+        });
+    }
 
-fn load_keys(path: &str) -> Result<PrivateKeyDer<'static>> {
-    let keyfile = File::open(path)?;
-    let mut reader = BufReader::new(keyfile);
-    let key = rustls_pemfile::private_key(&mut reader)?
-        .ok_or_else(|| anyhow::anyhow!("No private key found"))?;
-    Ok(key)
+    // Wait for a shutdown signal (or the SIGUSR2 handover convention),
+    // then drain active connections
+    tokio::select! {
+        _ = shutdown_signal() => {}
+        _ = upgrade_signal() => {
+            info!("Received SIGUSR2, draining for upgrade...");
+            handle.begin_drain();
+        }
+    }
+    info!(
+        "Waiting for active connections to close (timeout: {}s)...",
+        args.shutdown_timeout
+    );
+    handle
+        .shutdown_and_wait(Duration::from_secs(args.shutdown_timeout))
+        .await?;
+
+    info!("Shutdown complete.");
+    Ok(())
 }
@@ -1,21 +1,222 @@
-//! Prometheus metrics collection and exposition.
+//! Metrics collection behind the `metrics` facade.
 //!
-//! This module provides application metrics for monitoring:
+//! Emission sites call the free functions below (or the `metrics` macros
+//! directly) and never know which exporter is installed. [`init_metrics`]
+//! picks the exporter once at startup from `metrics.backend` in the config:
+//! `prometheus` (pull-based, rendered on the `/metrics` endpoint), `statsd`
+//! (dogstatsd datagrams pushed over UDP), or `none`.
+//!
+//! Metrics cover:
 //! - Connection counts (active, total)
 //! - Query processing metrics (count, latency)
 //! - Masking operations (fields masked, errors)
 //! - Upstream health check latency
 
-use metrics::{counter, gauge, histogram};
+use std::net::UdpSocket;
+use std::sync::Arc;
+
+use metrics::{
+    Counter, CounterFn, Gauge, GaugeFn, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
+    SharedString, Unit, counter, gauge, histogram,
+};
+#[cfg(feature = "metrics-prometheus")]
 use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
 
-/// Initialize the Prometheus metrics recorder.
-/// Returns a handle that can be used to render metrics.
-pub fn init_metrics() -> PrometheusHandle {
-    let builder = PrometheusBuilder::new();
-    builder
-        .install_recorder()
-        .expect("Failed to install Prometheus recorder")
+use crate::config::{MetricsBackendKind, MetricsConfig, StatsdConfig};
+
+/// The installed metrics backend.
+///
+/// Hides the exporter behind one type so [`crate::state::AppState`] carries
+/// no Prometheus-specific handle; only the `/metrics` endpoint cares which
+/// variant this is.
+#[derive(Clone, Default)]
+pub enum MetricsBackend {
+    /// Pull-based: [`MetricsBackend::render`] produces the exposition text
+    #[cfg(feature = "metrics-prometheus")]
+    Prometheus(Arc<PrometheusHandle>),
+    /// Push-based: datagrams go straight to the agent, nothing to render
+    Statsd,
+    /// No recorder installed; all emission sites are no-ops
+    #[default]
+    None,
+}
+
+impl MetricsBackend {
+    /// Renders the Prometheus exposition text. `None` unless the prometheus
+    /// backend is installed.
+    pub fn render(&self) -> Option<String> {
+        #[cfg(feature = "metrics-prometheus")]
+        if let MetricsBackend::Prometheus(handle) = self {
+            return Some(handle.render());
+        }
+        None
+    }
+
+    pub fn is_prometheus(&self) -> bool {
+        #[cfg(feature = "metrics-prometheus")]
+        {
+            matches!(self, MetricsBackend::Prometheus(_))
+        }
+        #[cfg(not(feature = "metrics-prometheus"))]
+        {
+            false
+        }
+    }
+}
+
+/// Install the metrics recorder selected by the config (default: prometheus).
+pub fn init_metrics(config: Option<&MetricsConfig>) -> MetricsBackend {
+    match config.map(|c| c.backend).unwrap_or_default() {
+        MetricsBackendKind::Prometheus => {
+            #[cfg(feature = "metrics-prometheus")]
+            {
+                let handle = PrometheusBuilder::new()
+                    .install_recorder()
+                    .expect("Failed to install Prometheus recorder");
+                MetricsBackend::Prometheus(Arc::new(handle))
+            }
+            // An explicit `backend: prometheus` is rejected by config
+            // validation; this branch only covers the implicit default.
+            #[cfg(not(feature = "metrics-prometheus"))]
+            {
+                tracing::debug!(
+                    "compiled without the 'metrics-prometheus' feature; no metrics backend installed"
+                );
+                MetricsBackend::None
+            }
+        }
+        MetricsBackendKind::Statsd => {
+            let statsd = config.and_then(|c| c.statsd.clone()).unwrap_or_default();
+            let recorder =
+                StatsdRecorder::new(&statsd).expect("Failed to create statsd exporter socket");
+            metrics::set_global_recorder(recorder).expect("Failed to install statsd recorder");
+            MetricsBackend::Statsd
+        }
+        MetricsBackendKind::None => MetricsBackend::None,
+    }
+}
+
+/// A minimal dogstatsd exporter.
+///
+/// Every update becomes one UDP datagram of the form
+/// `prefix.name:value|type|#tags`, where the tags combine the constant tags
+/// from the config with the metric's own labels. Sends are fire-and-forget:
+/// a missing agent drops datagrams instead of failing the proxy.
+pub struct StatsdRecorder {
+    sink: Arc<StatsdSink>,
+}
+
+impl StatsdRecorder {
+    pub fn new(config: &StatsdConfig) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(format!("{}:{}", config.host, config.port))?;
+        Ok(Self {
+            sink: Arc::new(StatsdSink {
+                socket,
+                prefix: config.prefix.clone(),
+                constant_tags: config.tags.clone(),
+            }),
+        })
+    }
+}
+
+struct StatsdSink {
+    socket: UdpSocket,
+    prefix: String,
+    constant_tags: Vec<String>,
+}
+
+impl StatsdSink {
+    fn send(&self, key: &Key, value: impl std::fmt::Display, kind: &str) {
+        let mut datagram = format!("{}.{}:{}|{}", self.prefix, key.name(), value, kind);
+
+        let mut tags = self.constant_tags.clone();
+        tags.extend(
+            key.labels()
+                .map(|label| format!("{}:{}", label.key(), label.value())),
+        );
+        if !tags.is_empty() {
+            datagram.push_str("|#");
+            datagram.push_str(&tags.join(","));
+        }
+
+        let _ = self.socket.send(datagram.as_bytes());
+    }
+}
+
+struct StatsdCounter {
+    sink: Arc<StatsdSink>,
+    key: Key,
+}
+
+impl CounterFn for StatsdCounter {
+    fn increment(&self, value: u64) {
+        self.sink.send(&self.key, value, "c");
+    }
+
+    fn absolute(&self, value: u64) {
+        // statsd counters are deltas; report absolute values as a gauge
+        self.sink.send(&self.key, value, "g");
+    }
+}
+
+struct StatsdGauge {
+    sink: Arc<StatsdSink>,
+    key: Key,
+}
+
+impl GaugeFn for StatsdGauge {
+    fn increment(&self, value: f64) {
+        self.sink.send(&self.key, format_args!("+{}", value), "g");
+    }
+
+    fn decrement(&self, value: f64) {
+        self.sink.send(&self.key, format_args!("-{}", value), "g");
+    }
+
+    fn set(&self, value: f64) {
+        self.sink.send(&self.key, value, "g");
+    }
+}
+
+struct StatsdHistogram {
+    sink: Arc<StatsdSink>,
+    key: Key,
+}
+
+impl HistogramFn for StatsdHistogram {
+    fn record(&self, value: f64) {
+        self.sink.send(&self.key, value, "h");
+    }
+}
+
+impl Recorder for StatsdRecorder {
+    fn describe_counter(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn describe_gauge(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn describe_histogram(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn register_counter(&self, key: &Key, _metadata: &Metadata<'_>) -> Counter {
+        Counter::from_arc(Arc::new(StatsdCounter {
+            sink: self.sink.clone(),
+            key: key.clone(),
+        }))
+    }
+
+    fn register_gauge(&self, key: &Key, _metadata: &Metadata<'_>) -> Gauge {
+        Gauge::from_arc(Arc::new(StatsdGauge {
+            sink: self.sink.clone(),
+            key: key.clone(),
+        }))
+    }
+
+    fn register_histogram(&self, key: &Key, _metadata: &Metadata<'_>) -> Histogram {
+        Histogram::from_arc(Arc::new(StatsdHistogram {
+            sink: self.sink.clone(),
+            key: key.clone(),
+        }))
+    }
 }
 
 /// Record a new connection
@@ -31,12 +232,48 @@ pub fn record_connection_closed() {
     gauge!("ironveil_connections_active").decrement(1.0);
 }
 
+/// Record a connection accepted, labeled by the listener that took it
+#[allow(dead_code)]
+pub fn record_connection_accepted(listener: &str) {
+    counter!("ironveil_connections_accepted_total", "listener" => listener.to_string())
+        .increment(1);
+}
+
+/// Record a session at startup resolution by whether a
+/// `masking.bypass_users` entry exempted it from masking, so a growing
+/// bypassed share is alertable
+#[allow(dead_code)]
+pub fn record_session_masking(bypassed: bool) {
+    let masking = if bypassed { "bypassed" } else { "masked" };
+    counter!("ironveil_sessions_masking_total", "masking" => masking).increment(1);
+}
+
 /// Record a connection rejected (rate limit or max connections)
 #[allow(dead_code)]
 pub fn record_connection_rejected(reason: &str) {
     counter!("ironveil_connections_rejected_total", "reason" => reason.to_string()).increment(1);
 }
 
+/// Record the output verifier catching a masked cell that still held PII
+#[allow(dead_code)]
+pub fn record_verification_failure(pii_type: &str) {
+    counter!("ironveil_verify_output_failures_total", "pii_type" => pii_type.to_string())
+        .increment(1);
+}
+
+/// Record a PII detection alert firing
+#[allow(dead_code)]
+pub fn record_alert_fired(alert: &str) {
+    counter!("ironveil_alerts_fired_total", "alert" => alert.to_string()).increment(1);
+}
+
+/// Record a protocol violation (oversized claim, broken framing, stalled
+/// frame) that closed a connection
+#[allow(dead_code)]
+pub fn record_protocol_violation(kind: &str) {
+    counter!("ironveil_protocol_violations_total", "kind" => kind.to_string()).increment(1);
+}
+
 /// Record query processed
 #[allow(dead_code)]
 pub fn record_query_processed(protocol: &str, duration_secs: f64) {
@@ -45,6 +282,19 @@ pub fn record_query_processed(protocol: &str, duration_secs: f64) {
         .record(duration_secs);
 }
 
+/// Record a management API request rejected by the rate limiter
+#[allow(dead_code)]
+pub fn record_api_rate_limited() {
+    counter!("ironveil_api_rate_limited_total").increment(1);
+}
+
+/// Record traffic through the proxy, labeled by direction relative to
+/// the client ("in" = received from the client, "out" = sent back to it)
+#[allow(dead_code)]
+pub fn record_bytes_proxied(direction: &'static str, count: u64) {
+    counter!("ironveil_bytes_proxied_total", "direction" => direction).increment(count);
+}
+
 /// Record fields masked
 #[allow(dead_code)]
 pub fn record_fields_masked(count: u64) {
@@ -70,6 +320,28 @@ pub fn record_health_check(healthy: bool, latency_ms: Option<u64>) {
     }
 }
 
+/// Record a masked-value memo lookup, for hit-rate monitoring
+#[allow(dead_code)]
+pub fn record_memo_lookup(hit: bool) {
+    if hit {
+        counter!("ironveil_mask_memo_hits_total").increment(1);
+    } else {
+        counter!("ironveil_mask_memo_misses_total").increment(1);
+    }
+}
+
+/// Record a health check result for a named routing upstream
+#[allow(dead_code)]
+pub fn record_route_health_check(route: &str, healthy: bool, latency_ms: Option<u64>) {
+    let upstream = route.to_string();
+    if let Some(latency) = latency_ms {
+        histogram!("ironveil_upstream_health_check_latency_ms", "upstream" => upstream.clone())
+            .record(latency as f64);
+    }
+    gauge!("ironveil_upstream_healthy", "upstream" => upstream)
+        .set(if healthy { 1.0 } else { 0.0 });
+}
+
 /// Record upstream connection timeout
 #[allow(dead_code)]
 pub fn record_upstream_timeout() {
@@ -84,10 +356,166 @@ pub fn record_idle_timeout() {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    /// Captures every update as a `name:value|kind` string
+    #[derive(Default)]
+    struct RecordingRecorder {
+        events: Arc<Mutex<Vec<String>>>,
+    }
+
+    struct RecordingHandle {
+        name: String,
+        events: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl RecordingHandle {
+        fn push(&self, value: impl std::fmt::Display, kind: &str) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("{}:{}|{}", self.name, value, kind));
+        }
+    }
+
+    impl CounterFn for RecordingHandle {
+        fn increment(&self, value: u64) {
+            self.push(value, "c");
+        }
+
+        fn absolute(&self, value: u64) {
+            self.push(value, "c");
+        }
+    }
+
+    impl GaugeFn for RecordingHandle {
+        fn increment(&self, value: f64) {
+            self.push(value, "g");
+        }
+
+        fn decrement(&self, value: f64) {
+            self.push(-value, "g");
+        }
+
+        fn set(&self, value: f64) {
+            self.push(value, "g");
+        }
+    }
+
+    impl HistogramFn for RecordingHandle {
+        fn record(&self, value: f64) {
+            self.push(value, "h");
+        }
+    }
+
+    impl RecordingRecorder {
+        fn handle(&self, key: &Key) -> Arc<RecordingHandle> {
+            Arc::new(RecordingHandle {
+                name: key.name().to_string(),
+                events: self.events.clone(),
+            })
+        }
+    }
+
+    impl Recorder for RecordingRecorder {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            Counter::from_arc(self.handle(key))
+        }
+
+        fn register_gauge(&self, key: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::from_arc(self.handle(key))
+        }
+
+        fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+            Histogram::from_arc(self.handle(key))
+        }
+    }
+
+    #[test]
+    fn test_emission_sites_reach_the_recorder() {
+        let recorder = RecordingRecorder::default();
+        let events = recorder.events.clone();
+
+        metrics::with_local_recorder(&recorder, || {
+            record_connection_opened();
+            record_fields_masked(3);
+            record_health_check(true, Some(12));
+        });
+
+        let events = events.lock().unwrap();
+        assert!(
+            events.contains(&"ironveil_connections_total:1|c".to_string()),
+            "{:?}",
+            events
+        );
+        assert!(events.contains(&"ironveil_fields_masked_total:3|c".to_string()));
+        assert!(events.contains(&"ironveil_upstream_healthy:1|g".to_string()));
+        assert!(events.contains(&"ironveil_upstream_health_check_latency_ms:12|h".to_string()));
+    }
+
     #[test]
-    fn test_metrics_can_be_initialized() {
-        // Just test that metrics can be called without panicking
-        // (actual initialization requires a recorder)
-        // These will be no-ops without a recorder installed
+    fn test_statsd_datagram_format() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        listener
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let config = StatsdConfig {
+            host: addr.ip().to_string(),
+            port: addr.port(),
+            prefix: "test".to_string(),
+            tags: vec!["env:ci".to_string()],
+        };
+        let recorder = StatsdRecorder::new(&config).unwrap();
+
+        metrics::with_local_recorder(&recorder, || {
+            counter!("queries_total", "protocol" => "postgres").increment(2);
+        });
+
+        let mut buf = [0u8; 512];
+        let n = listener.recv(&mut buf).unwrap();
+        let datagram = std::str::from_utf8(&buf[..n]).unwrap();
+        assert_eq!(datagram, "test.queries_total:2|c|#env:ci,protocol:postgres");
+    }
+
+    #[test]
+    fn test_statsd_gauge_and_histogram_datagrams() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        listener
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let config = StatsdConfig {
+            host: addr.ip().to_string(),
+            port: addr.port(),
+            prefix: "test".to_string(),
+            tags: vec![],
+        };
+        let recorder = StatsdRecorder::new(&config).unwrap();
+
+        metrics::with_local_recorder(&recorder, || {
+            gauge!("connections_active").increment(1.0);
+            gauge!("upstream_healthy").set(0.0);
+            histogram!("latency_ms").record(7.5);
+        });
+
+        let read = || {
+            let mut buf = [0u8; 512];
+            let n = listener.recv(&mut buf).unwrap();
+            std::str::from_utf8(&buf[..n]).unwrap().to_string()
+        };
+        assert_eq!(read(), "test.connections_active:+1|g");
+        assert_eq!(read(), "test.upstream_healthy:0|g");
+        assert_eq!(read(), "test.latency_ms:7.5|h");
     }
 }
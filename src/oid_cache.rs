@@ -0,0 +1,172 @@
+//! Resolving RowDescription table OIDs to table names.
+//!
+//! RowDescription identifies a column's source table only by OID, so a
+//! rule scoped with `table:` has nothing to match against when the query
+//! resolver cannot trace an output column back to its table (views,
+//! functions, SQL the parser does not handle). This cache resolves OIDs
+//! against the upstream's `pg_class`/`pg_namespace` catalogs over a
+//! separate maintenance connection and remembers the answers, so the
+//! catalogs are consulted once per OID rather than once per result set.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use tokio::sync::RwLock;
+use tokio_postgres::{Client, NoTls};
+use tracing::{debug, warn};
+
+use crate::config::OidResolutionConfig;
+
+/// One resolved OID. `table: None` records a miss (a dropped table, or an
+/// OID from another database), so unknown OIDs are not re-queried on every
+/// result set that mentions them.
+struct CachedOid {
+    table: Option<String>,
+    resolved_at: Instant,
+}
+
+/// An OID-to-table-name cache for one upstream, shared by every
+/// connection proxied to it.
+pub struct OidCache {
+    host: String,
+    port: u16,
+    config: OidResolutionConfig,
+    ttl: Duration,
+    entries: RwLock<HashMap<u32, CachedOid>>,
+    /// Column lists per table from the last prefetch, for the schema API
+    tables: RwLock<HashMap<String, Vec<String>>>,
+    /// True until a prefetch has succeeded; lazy per-OID resolution still
+    /// works while stale
+    stale: AtomicBool,
+}
+
+impl OidCache {
+    pub fn new(host: String, port: u16, config: OidResolutionConfig) -> Self {
+        let ttl = Duration::from_secs(config.ttl_secs);
+        Self {
+            host,
+            port,
+            config,
+            ttl,
+            entries: RwLock::new(HashMap::new()),
+            tables: RwLock::new(HashMap::new()),
+            stale: AtomicBool::new(true),
+        }
+    }
+
+    /// Whether no prefetch has succeeded yet (or prefetch is not in use)
+    pub fn is_stale(&self) -> bool {
+        self.stale.load(Ordering::Relaxed)
+    }
+
+    /// Column lists per table from the last prefetch, for the schema API
+    pub async fn table_columns(&self) -> HashMap<String, Vec<String>> {
+        self.tables.read().await.clone()
+    }
+
+    /// Load the full OID-to-table map and column lists in one pass, so
+    /// the first query of a fresh session is already table-aware.
+    /// Returns the number of tables seen.
+    pub async fn prefetch(&self) -> Result<usize, tokio_postgres::Error> {
+        let client = self.connect().await?;
+        let rows = client
+            .query(
+                "SELECT c.oid, c.relname, a.attname
+                 FROM pg_catalog.pg_class c
+                 JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+                 LEFT JOIN pg_catalog.pg_attribute a
+                   ON a.attrelid = c.oid AND a.attnum > 0 AND NOT a.attisdropped
+                 WHERE c.relkind IN ('r', 'p', 'v', 'm')
+                   AND n.nspname NOT IN ('pg_catalog', 'information_schema')
+                 ORDER BY c.relname, a.attnum",
+                &[],
+            )
+            .await?;
+
+        let now = Instant::now();
+        let mut entries = HashMap::new();
+        let mut tables: HashMap<String, Vec<String>> = HashMap::new();
+        for row in rows {
+            let oid: u32 = row.get(0);
+            let table: String = row.get(1);
+            entries.entry(oid).or_insert_with(|| CachedOid {
+                table: Some(table.clone()),
+                resolved_at: now,
+            });
+            let columns = tables.entry(table).or_default();
+            if let Some(column) = row.get::<_, Option<String>>(2) {
+                columns.push(column);
+            }
+        }
+        let count = tables.len();
+
+        *self.entries.write().await = entries;
+        *self.tables.write().await = tables;
+        self.stale.store(false, Ordering::Relaxed);
+        Ok(count)
+    }
+
+    /// The unqualified table name behind an OID, from the cache or the
+    /// upstream's catalogs. OID 0 marks an expression output and never
+    /// resolves; lookup failures are cached as misses for the TTL so a
+    /// broken maintenance connection cannot stall every result set.
+    pub async fn resolve(&self, oid: u32) -> Option<String> {
+        if oid == 0 {
+            return None;
+        }
+
+        if let Some(entry) = self.entries.read().await.get(&oid)
+            && entry.resolved_at.elapsed() < self.ttl
+        {
+            return entry.table.clone();
+        }
+
+        let table = match self.lookup(oid).await {
+            Ok(resolved) => {
+                debug!(oid, resolved = ?resolved, "Resolved table OID");
+                resolved.map(|(_, table)| table)
+            }
+            Err(e) => {
+                warn!(oid, error = %e, "Table OID lookup failed; caching as a miss");
+                None
+            }
+        };
+        self.entries.write().await.insert(
+            oid,
+            CachedOid {
+                table: table.clone(),
+                resolved_at: Instant::now(),
+            },
+        );
+        table
+    }
+
+    async fn connect(&self) -> Result<Client, tokio_postgres::Error> {
+        let conn_str = format!(
+            "host={} port={} user={} password={} dbname={} sslmode=prefer connect_timeout=10",
+            self.host, self.port, self.config.username, self.config.password, self.config.database
+        );
+        let (client, connection) = tokio_postgres::connect(&conn_str, NoTls).await?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                warn!("OID resolution connection error: {}", e);
+            }
+        });
+        Ok(client)
+    }
+
+    async fn lookup(&self, oid: u32) -> Result<Option<(String, String)>, tokio_postgres::Error> {
+        let client = self.connect().await?;
+        let row = client
+            .query_opt(
+                "SELECT c.relname, n.nspname
+                 FROM pg_catalog.pg_class c
+                 JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+                 WHERE c.oid = $1",
+                &[&oid],
+            )
+            .await?;
+        Ok(row.map(|row| (row.get::<_, String>(1), row.get::<_, String>(0))))
+    }
+}
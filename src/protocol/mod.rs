@@ -1,2 +1,6 @@
+#[cfg(feature = "mysql")]
 pub mod mysql;
+#[cfg(feature = "postgres")]
 pub mod postgres;
+pub mod testing;
+pub mod wire_serde;
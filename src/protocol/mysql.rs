@@ -3,12 +3,15 @@
 //! This module implements the MySQL client/server protocol for proxying MySQL connections.
 //! Reference: https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_basics.html
 
-use anyhow::Result;
+use crate::error::{ProtocolError, ProxyError};
+use crate::protocol::wire_serde;
 use bytes::{Buf, BufMut, Bytes, BytesMut};
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use tokio_util::codec::{Decoder, Encoder};
 
 /// MySQL packet types and messages
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum MySqlMessage {
     /// Initial handshake from server
     Handshake(HandshakeV10),
@@ -22,6 +25,8 @@ pub enum MySqlMessage {
     ColumnDefinition(ColumnDefinition),
     /// Result set row (text protocol)
     ResultRow(ResultRow),
+    /// Result set row (binary protocol, from a prepared statement)
+    BinaryRow(BinaryRow),
     /// OK packet
     Ok(OkPacket),
     /// ERR packet
@@ -31,7 +36,7 @@ pub enum MySqlMessage {
 }
 
 /// MySQL Handshake V10 packet (server -> client)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HandshakeV10 {
     pub protocol_version: u8,
     pub server_version: String,
@@ -45,7 +50,7 @@ pub struct HandshakeV10 {
 }
 
 /// Client handshake response
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HandshakeResponse {
     pub capability_flags: u32,
     pub max_packet_size: u32,
@@ -57,28 +62,36 @@ pub struct HandshakeResponse {
 }
 
 /// Generic packet for passthrough
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GenericPacket {
     pub sequence_id: u8,
+    #[serde(with = "wire_serde::bytes_mut")]
     pub payload: BytesMut,
 }
 
 /// COM_QUERY packet
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueryPacket {
     pub sequence_id: u8,
+    #[serde(with = "wire_serde::bytes")]
     pub query: Bytes,
 }
 
 /// Column definition packet (part of result set)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ColumnDefinition {
     pub sequence_id: u8,
+    #[serde(with = "wire_serde::bytes")]
     pub catalog: Bytes,
+    #[serde(with = "wire_serde::bytes")]
     pub schema: Bytes,
+    #[serde(with = "wire_serde::bytes")]
     pub table: Bytes,
+    #[serde(with = "wire_serde::bytes")]
     pub org_table: Bytes,
+    #[serde(with = "wire_serde::bytes")]
     pub name: Bytes,
+    #[serde(with = "wire_serde::bytes")]
     pub org_name: Bytes,
     pub character_set: u16,
     pub column_length: u32,
@@ -88,25 +101,90 @@ pub struct ColumnDefinition {
 }
 
 /// Result row packet (text protocol)
-#[derive(Debug, Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ResultRow {
     pub sequence_id: u8,
+    #[serde(with = "wire_serde::row_values")]
     pub values: Vec<Option<BytesMut>>,
 }
 
+/// Renders values as lossy UTF-8 (NULLs bare) so row dumps in logs and
+/// test failures stay readable.
+impl fmt::Debug for ResultRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ResultRow(seq={})[", self.sequence_id)?;
+        for (i, value) in self.values.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            match value {
+                Some(v) => write!(f, "{:?}", String::from_utf8_lossy(v))?,
+                None => write!(f, "NULL")?,
+            }
+        }
+        write!(f, "]")
+    }
+}
+
+/// Result row packet (binary protocol). Produced for the rows of a
+/// COM_STMT_EXECUTE result set, where values are encoded by column type
+/// rather than as length-encoded strings.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BinaryRow {
+    pub sequence_id: u8,
+    pub values: Vec<BinaryCell>,
+}
+
+/// One value in a binary-protocol row. String-typed columns (VARCHAR, the
+/// BLOB family, ENUM, SET) decode to `Text` so the interceptor can rewrite
+/// them; every other type keeps its exact wire bytes — length prefix
+/// included — and is re-emitted untouched.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum BinaryCell {
+    Null,
+    Text(#[serde(with = "wire_serde::bytes_mut")] BytesMut),
+    Other(#[serde(with = "wire_serde::bytes_mut")] BytesMut),
+}
+
+/// Text cells as lossy UTF-8, opaque cells as byte counts, so binary row
+/// dumps in logs and test failures stay readable.
+impl fmt::Debug for BinaryCell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BinaryCell::Null => write!(f, "NULL"),
+            BinaryCell::Text(v) => write!(f, "{:?}", String::from_utf8_lossy(v)),
+            BinaryCell::Other(v) => write!(f, "<{} bytes>", v.len()),
+        }
+    }
+}
+
+impl fmt::Debug for BinaryRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "BinaryRow(seq={})[", self.sequence_id)?;
+        for (i, value) in self.values.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{:?}", value)?;
+        }
+        write!(f, "]")
+    }
+}
+
 /// OK packet
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OkPacket {
     pub sequence_id: u8,
     pub affected_rows: u64,
     pub last_insert_id: u64,
     pub status_flags: u16,
     pub warnings: u16,
+    #[serde(with = "wire_serde::bytes")]
     pub info: Bytes,
 }
 
 /// ERR packet
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ErrPacket {
     pub sequence_id: u8,
     pub error_code: u16,
@@ -115,7 +193,7 @@ pub struct ErrPacket {
 }
 
 /// EOF packet
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EofPacket {
     pub sequence_id: u8,
     pub warnings: u16,
@@ -130,6 +208,11 @@ pub const CLIENT_SECURE_CONNECTION: u32 = 1 << 15;
 pub const CLIENT_PLUGIN_AUTH: u32 = 1 << 19;
 pub const CLIENT_DEPRECATE_EOF: u32 = 1 << 24;
 
+// Command bytes
+pub const COM_QUERY: u8 = 0x03;
+pub const COM_STMT_PREPARE: u8 = 0x16;
+pub const COM_STMT_EXECUTE: u8 = 0x17;
+
 /// State machine for MySQL codec
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum MySqlState {
@@ -143,6 +226,11 @@ pub enum MySqlState {
     ReadingColumns { remaining: usize },
     /// Reading rows in result set
     ReadingRows,
+    /// Reading binary-protocol rows from a COM_STMT_EXECUTE result set
+    ReadingBinaryRows,
+    /// Passing through the parameter and column definitions that follow a
+    /// COM_STMT_PREPARE_OK header
+    ReadingPrepareResponse { remaining: usize },
 }
 
 /// MySQL codec for framing and parsing packets
@@ -151,6 +239,13 @@ pub struct MySqlCodec {
     capability_flags: u32,
     is_client_side: bool,
     column_count: usize,
+    /// Column types of the current result set, in order; binary rows can
+    /// only be decoded with them in hand
+    column_types: Vec<u8>,
+    /// Command byte of the request the next response answers (0 = none)
+    pending_command: u8,
+    /// Whether the current result set carries binary-protocol rows
+    binary_resultset: bool,
 }
 
 impl MySqlCodec {
@@ -161,6 +256,9 @@ impl MySqlCodec {
             capability_flags: 0,
             is_client_side: false,
             column_count: 0,
+            column_types: Vec::new(),
+            pending_command: 0,
+            binary_resultset: false,
         }
     }
 
@@ -171,6 +269,9 @@ impl MySqlCodec {
             capability_flags: 0,
             is_client_side: true,
             column_count: 0,
+            column_types: Vec::new(),
+            pending_command: 0,
+            binary_resultset: false,
         }
     }
 
@@ -179,6 +280,13 @@ impl MySqlCodec {
         self.capability_flags = flags;
     }
 
+    /// Records the command byte the proxy just forwarded upstream, so the
+    /// next response is framed correctly: COM_STMT_PREPARE answers with its
+    /// own header and COM_STMT_EXECUTE with binary-protocol rows.
+    pub fn note_client_command(&mut self, command: u8) {
+        self.pending_command = command;
+    }
+
     fn uses_deprecate_eof(&self) -> bool {
         self.capability_flags & CLIENT_DEPRECATE_EOF != 0
     }
@@ -186,9 +294,9 @@ impl MySqlCodec {
 
 impl Decoder for MySqlCodec {
     type Item = MySqlMessage;
-    type Error = anyhow::Error;
+    type Error = ProxyError;
 
-    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>> {
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, ProxyError> {
         // MySQL packet header: 3 bytes length + 1 byte sequence id
         if src.len() < 4 {
             return Ok(None);
@@ -265,6 +373,14 @@ impl Decoder for MySqlCodec {
 
                 let first_byte = packet[0];
 
+                // The first packet of a response consumes the pending
+                // command: it alone decides how the response is framed
+                let pending_command = if self.is_client_side {
+                    std::mem::replace(&mut self.pending_command, 0)
+                } else {
+                    0
+                };
+
                 // Check for COM_QUERY from client
                 if !self.is_client_side && first_byte == 0x03 {
                     packet.advance(1);
@@ -275,6 +391,24 @@ impl Decoder for MySqlCodec {
                     })));
                 }
 
+                // COM_STMT_PREPARE_OK: its 0x00 header would otherwise be
+                // taken for an OK packet, and the parameter and column
+                // definitions after it must ride through unparsed
+                if self.is_client_side
+                    && first_byte == 0x00
+                    && pending_command == COM_STMT_PREPARE
+                {
+                    let remaining =
+                        prepare_response_packets(&packet, self.uses_deprecate_eof())?;
+                    if remaining > 0 {
+                        self.state = MySqlState::ReadingPrepareResponse { remaining };
+                    }
+                    return Ok(Some(MySqlMessage::Generic(GenericPacket {
+                        sequence_id,
+                        payload: packet,
+                    })));
+                }
+
                 // Check for result set header (column count) from server
                 if self.is_client_side
                     && first_byte != 0x00
@@ -285,6 +419,8 @@ impl Decoder for MySqlCodec {
                     let (col_count, _) = read_lenenc_int(&packet)?;
                     if col_count > 0 && col_count < 1000 {
                         self.column_count = col_count as usize;
+                        self.column_types.clear();
+                        self.binary_resultset = pending_command == COM_STMT_EXECUTE;
                         self.state = MySqlState::ReadingColumns {
                             remaining: col_count as usize,
                         };
@@ -324,18 +460,27 @@ impl Decoder for MySqlCodec {
                 // EOF packet marks end of column definitions
                 if first_byte == 0xfe && packet.len() < 9 && !self.uses_deprecate_eof() {
                     let eof = parse_eof_packet(&mut packet, sequence_id)?;
-                    self.state = MySqlState::ReadingRows;
+                    self.state = if self.binary_resultset {
+                        MySqlState::ReadingBinaryRows
+                    } else {
+                        MySqlState::ReadingRows
+                    };
                     return Ok(Some(MySqlMessage::Eof(eof)));
                 }
 
                 // Parse column definition
                 let col_def = parse_column_definition(&mut packet, sequence_id)?;
+                self.column_types.push(col_def.column_type);
                 let new_remaining = remaining.saturating_sub(1);
 
                 if new_remaining == 0 {
                     if self.uses_deprecate_eof() {
                         // No EOF packet, go straight to rows
-                        self.state = MySqlState::ReadingRows;
+                        self.state = if self.binary_resultset {
+                            MySqlState::ReadingBinaryRows
+                        } else {
+                            MySqlState::ReadingRows
+                        };
                     }
                     // Otherwise wait for EOF packet
                 } else {
@@ -374,14 +519,62 @@ impl Decoder for MySqlCodec {
                 let row = parse_result_row(&mut packet, sequence_id, self.column_count)?;
                 Ok(Some(MySqlMessage::ResultRow(row)))
             }
+            MySqlState::ReadingBinaryRows => {
+                let first_byte = packet[0];
+
+                // EOF packet marks end of rows; binary rows always start
+                // with a 0x00 header, so 0xfe is unambiguous here
+                if first_byte == 0xfe && packet.len() < 9 {
+                    let eof = parse_eof_packet(&mut packet, sequence_id)?;
+                    self.state = MySqlState::Command;
+                    return Ok(Some(MySqlMessage::Eof(eof)));
+                }
+
+                // With CLIENT_DEPRECATE_EOF the terminator is an OK packet
+                // that reuses the 0xfe header; pass it through byte-exact
+                if first_byte == 0xfe {
+                    self.state = MySqlState::Command;
+                    return Ok(Some(MySqlMessage::Generic(GenericPacket {
+                        sequence_id,
+                        payload: packet,
+                    })));
+                }
+
+                // ERR packet
+                if first_byte == 0xff {
+                    let err = parse_err_packet(&mut packet, sequence_id, self.capability_flags)?;
+                    self.state = MySqlState::Command;
+                    return Ok(Some(MySqlMessage::Err(err)));
+                }
+
+                // Parse binary result row
+                let row = parse_binary_row(&mut packet, sequence_id, &self.column_types)?;
+                Ok(Some(MySqlMessage::BinaryRow(row)))
+            }
+            MySqlState::ReadingPrepareResponse { remaining } => {
+                // Parameter and column definitions after COM_STMT_PREPARE_OK
+                // carry no row data; nothing in them is maskable
+                let new_remaining = remaining.saturating_sub(1);
+                self.state = if new_remaining == 0 {
+                    MySqlState::Command
+                } else {
+                    MySqlState::ReadingPrepareResponse {
+                        remaining: new_remaining,
+                    }
+                };
+                Ok(Some(MySqlMessage::Generic(GenericPacket {
+                    sequence_id,
+                    payload: packet,
+                })))
+            }
         }
     }
 }
 
 impl Encoder<MySqlMessage> for MySqlCodec {
-    type Error = anyhow::Error;
+    type Error = ProxyError;
 
-    fn encode(&mut self, item: MySqlMessage, dst: &mut BytesMut) -> Result<()> {
+    fn encode(&mut self, item: MySqlMessage, dst: &mut BytesMut) -> Result<(), ProxyError> {
         match item {
             MySqlMessage::Handshake(h) => encode_handshake_v10(&h, dst),
             MySqlMessage::HandshakeResponse(r) => encode_handshake_response(&r, dst),
@@ -389,6 +582,7 @@ impl Encoder<MySqlMessage> for MySqlCodec {
             MySqlMessage::Query(q) => encode_query(&q, dst),
             MySqlMessage::ColumnDefinition(c) => encode_column_definition(&c, dst),
             MySqlMessage::ResultRow(r) => encode_result_row(&r, dst),
+            MySqlMessage::BinaryRow(r) => encode_binary_row(&r, dst),
             MySqlMessage::Ok(o) => encode_ok(&o, dst, self.capability_flags),
             MySqlMessage::Err(e) => encode_err(&e, dst, self.capability_flags),
             MySqlMessage::Eof(e) => encode_eof(&e, dst),
@@ -401,9 +595,17 @@ impl Encoder<MySqlMessage> for MySqlCodec {
 // Parsing helpers
 // ============================================================================
 
-fn read_lenenc_int(buf: &[u8]) -> Result<(u64, usize)> {
+/// Shorthand for a framing error with no specific message type
+fn framing(details: impl Into<String>) -> ProtocolError {
+    ProtocolError::Framing {
+        message_type: None,
+        details: details.into(),
+    }
+}
+
+fn read_lenenc_int(buf: &[u8]) -> Result<(u64, usize), ProtocolError> {
     if buf.is_empty() {
-        anyhow::bail!("Empty buffer for lenenc int");
+        return Err(framing("empty buffer for lenenc int"));
     }
 
     let first = buf[0];
@@ -411,13 +613,13 @@ fn read_lenenc_int(buf: &[u8]) -> Result<(u64, usize)> {
         0..=0xfa => Ok((first as u64, 1)),
         0xfc => {
             if buf.len() < 3 {
-                anyhow::bail!("Not enough bytes for 2-byte lenenc int");
+                return Err(framing("not enough bytes for 2-byte lenenc int"));
             }
             Ok(((buf[1] as u64) | ((buf[2] as u64) << 8), 3))
         }
         0xfd => {
             if buf.len() < 4 {
-                anyhow::bail!("Not enough bytes for 3-byte lenenc int");
+                return Err(framing("not enough bytes for 3-byte lenenc int"));
             }
             Ok((
                 (buf[1] as u64) | ((buf[2] as u64) << 8) | ((buf[3] as u64) << 16),
@@ -426,7 +628,7 @@ fn read_lenenc_int(buf: &[u8]) -> Result<(u64, usize)> {
         }
         0xfe => {
             if buf.len() < 9 {
-                anyhow::bail!("Not enough bytes for 8-byte lenenc int");
+                return Err(framing("not enough bytes for 8-byte lenenc int"));
             }
             let val = (buf[1] as u64)
                 | ((buf[2] as u64) << 8)
@@ -439,35 +641,36 @@ fn read_lenenc_int(buf: &[u8]) -> Result<(u64, usize)> {
             Ok((val, 9))
         }
         0xfb => Ok((0, 1)), // NULL in row data
-        0xff => anyhow::bail!("Invalid lenenc int marker 0xff"),
+        0xff => Err(framing("invalid lenenc int marker 0xff")),
     }
 }
 
-fn read_lenenc_int_from_buf(buf: &mut BytesMut) -> Result<u64> {
+fn read_lenenc_int_from_buf(buf: &mut BytesMut) -> Result<u64, ProtocolError> {
     let (val, consumed) = read_lenenc_int(buf)?;
     buf.advance(consumed);
     Ok(val)
 }
 
-fn read_lenenc_string(buf: &mut BytesMut) -> Result<Bytes> {
+fn read_lenenc_string(buf: &mut BytesMut) -> Result<Bytes, ProtocolError> {
     let len = read_lenenc_int_from_buf(buf)? as usize;
     if buf.len() < len {
-        anyhow::bail!("Not enough bytes for lenenc string");
+        return Err(framing("not enough bytes for lenenc string"));
     }
     Ok(buf.split_to(len).freeze())
 }
 
-fn read_null_terminated_string(buf: &mut BytesMut) -> Result<String> {
+fn read_null_terminated_string(buf: &mut BytesMut) -> Result<String, ProtocolError> {
     let pos = buf
         .iter()
         .position(|&b| b == 0)
-        .ok_or_else(|| anyhow::anyhow!("Missing null terminator"))?;
-    let s = String::from_utf8(buf.split_to(pos).to_vec())?;
+        .ok_or_else(|| framing("missing null terminator"))?;
+    let s = String::from_utf8(buf.split_to(pos).to_vec())
+        .map_err(|e| framing(format!("string is not valid UTF-8: {}", e)))?;
     buf.advance(1); // Skip null
     Ok(s)
 }
 
-fn parse_handshake_v10(buf: &mut BytesMut) -> Result<HandshakeV10> {
+fn parse_handshake_v10(buf: &mut BytesMut) -> Result<HandshakeV10, ProtocolError> {
     let protocol_version = buf.get_u8();
     let server_version = read_null_terminated_string(buf)?;
     let connection_id = buf.get_u32_le();
@@ -518,7 +721,10 @@ fn parse_handshake_v10(buf: &mut BytesMut) -> Result<HandshakeV10> {
     })
 }
 
-fn parse_handshake_response(buf: &mut BytesMut, _server_caps: u32) -> Result<HandshakeResponse> {
+fn parse_handshake_response(
+    buf: &mut BytesMut,
+    _server_caps: u32,
+) -> Result<HandshakeResponse, ProtocolError> {
     let capability_flags = buf.get_u32_le();
     let max_packet_size = buf.get_u32_le();
     let character_set = buf.get_u8();
@@ -561,7 +767,11 @@ fn parse_handshake_response(buf: &mut BytesMut, _server_caps: u32) -> Result<Han
     })
 }
 
-fn parse_ok_packet(buf: &mut BytesMut, sequence_id: u8, capability_flags: u32) -> Result<OkPacket> {
+fn parse_ok_packet(
+    buf: &mut BytesMut,
+    sequence_id: u8,
+    capability_flags: u32,
+) -> Result<OkPacket, ProtocolError> {
     buf.advance(1); // header 0x00
     let affected_rows = read_lenenc_int_from_buf(buf)?;
     let last_insert_id = read_lenenc_int_from_buf(buf)?;
@@ -588,7 +798,7 @@ fn parse_err_packet(
     buf: &mut BytesMut,
     sequence_id: u8,
     capability_flags: u32,
-) -> Result<ErrPacket> {
+) -> Result<ErrPacket, ProtocolError> {
     buf.advance(1); // header 0xff
     let error_code = buf.get_u16_le();
 
@@ -611,7 +821,7 @@ fn parse_err_packet(
     })
 }
 
-fn parse_eof_packet(buf: &mut BytesMut, sequence_id: u8) -> Result<EofPacket> {
+fn parse_eof_packet(buf: &mut BytesMut, sequence_id: u8) -> Result<EofPacket, ProtocolError> {
     buf.advance(1); // header 0xfe
     let warnings = if buf.len() >= 2 { buf.get_u16_le() } else { 0 };
     let status_flags = if buf.len() >= 2 { buf.get_u16_le() } else { 0 };
@@ -623,7 +833,10 @@ fn parse_eof_packet(buf: &mut BytesMut, sequence_id: u8) -> Result<EofPacket> {
     })
 }
 
-fn parse_column_definition(buf: &mut BytesMut, sequence_id: u8) -> Result<ColumnDefinition> {
+fn parse_column_definition(
+    buf: &mut BytesMut,
+    sequence_id: u8,
+) -> Result<ColumnDefinition, ProtocolError> {
     let catalog = read_lenenc_string(buf)?;
     let schema = read_lenenc_string(buf)?;
     let table = read_lenenc_string(buf)?;
@@ -654,7 +867,11 @@ fn parse_column_definition(buf: &mut BytesMut, sequence_id: u8) -> Result<Column
     })
 }
 
-fn parse_result_row(buf: &mut BytesMut, sequence_id: u8, column_count: usize) -> Result<ResultRow> {
+fn parse_result_row(
+    buf: &mut BytesMut,
+    sequence_id: u8,
+    column_count: usize,
+) -> Result<ResultRow, ProtocolError> {
     let mut values = Vec::with_capacity(column_count);
 
     for _ in 0..column_count {
@@ -683,6 +900,97 @@ fn parse_result_row(buf: &mut BytesMut, sequence_id: u8, column_count: usize) ->
     })
 }
 
+/// Number of packets following a COM_STMT_PREPARE_OK header: one column
+/// definition per parameter and per result column, each non-empty group
+/// closed by an EOF unless the session negotiated CLIENT_DEPRECATE_EOF.
+fn prepare_response_packets(packet: &[u8], deprecate_eof: bool) -> Result<usize, ProtocolError> {
+    if packet.len() < 9 {
+        return Err(framing("COM_STMT_PREPARE_OK shorter than its fixed header"));
+    }
+    // Layout: status (1), statement id (4), num_columns (2), num_params (2)
+    let num_columns = (packet[5] as usize) | ((packet[6] as usize) << 8);
+    let num_params = (packet[7] as usize) | ((packet[8] as usize) << 8);
+    let mut remaining = num_columns + num_params;
+    if !deprecate_eof {
+        remaining += usize::from(num_params > 0) + usize::from(num_columns > 0);
+    }
+    Ok(remaining)
+}
+
+fn take_raw(buf: &mut BytesMut, len: usize) -> Result<BinaryCell, ProtocolError> {
+    if buf.len() < len {
+        return Err(framing("not enough bytes for binary value"));
+    }
+    Ok(BinaryCell::Other(buf.split_to(len)))
+}
+
+/// Splits the next binary-protocol value off `buf` according to its column
+/// type. String-like types come back as `Text` so they can be masked;
+/// everything else keeps its exact wire bytes, length prefix included.
+fn read_binary_value(buf: &mut BytesMut, column_type: u8) -> Result<BinaryCell, ProtocolError> {
+    match column_type {
+        // VARCHAR, ENUM, SET, the BLOB/TEXT family, VAR_STRING, STRING —
+        // all length-encoded, all candidates for masking
+        15 | 247..=254 => {
+            let (len, prefix) = read_lenenc_int(buf)?;
+            let len = len as usize;
+            if buf.len() < prefix + len {
+                return Err(framing("not enough bytes for binary string value"));
+            }
+            buf.advance(prefix);
+            Ok(BinaryCell::Text(buf.split_to(len)))
+        }
+        // Fixed-width numerics
+        1 => take_raw(buf, 1),         // TINY
+        2 | 13 => take_raw(buf, 2),    // SHORT, YEAR
+        3 | 4 | 9 => take_raw(buf, 4), // LONG, FLOAT, INT24
+        5 | 8 => take_raw(buf, 8),     // DOUBLE, LONGLONG
+        // Temporal types carry a one-byte length prefix
+        7 | 10 | 11 | 12 => {
+            if buf.is_empty() {
+                return Err(framing("missing length byte for temporal value"));
+            }
+            let len = buf[0] as usize;
+            take_raw(buf, 1 + len)
+        }
+        // Everything else (DECIMAL, BIT, JSON, GEOMETRY, ...) is
+        // length-encoded; kept opaque, prefix and all
+        _ => {
+            let (len, prefix) = read_lenenc_int(buf)?;
+            take_raw(buf, prefix + len as usize)
+        }
+    }
+}
+
+fn parse_binary_row(
+    buf: &mut BytesMut,
+    sequence_id: u8,
+    column_types: &[u8],
+) -> Result<BinaryRow, ProtocolError> {
+    buf.advance(1); // header 0x00
+    // NULL bitmap: one bit per column, offset by 2 bits
+    let bitmap_len = (column_types.len() + 2).div_ceil(8);
+    if buf.len() < bitmap_len {
+        return Err(framing("binary row shorter than its NULL bitmap"));
+    }
+    let bitmap = buf.split_to(bitmap_len);
+
+    let mut values = Vec::with_capacity(column_types.len());
+    for (i, &column_type) in column_types.iter().enumerate() {
+        let bit = i + 2;
+        if bitmap[bit / 8] & (1 << (bit % 8)) != 0 {
+            values.push(BinaryCell::Null);
+        } else {
+            values.push(read_binary_value(buf, column_type)?);
+        }
+    }
+
+    Ok(BinaryRow {
+        sequence_id,
+        values,
+    })
+}
+
 // ============================================================================
 // Encoding helpers
 // ============================================================================
@@ -817,6 +1125,35 @@ fn encode_result_row(r: &ResultRow, dst: &mut BytesMut) {
     dst.put_slice(&payload);
 }
 
+fn encode_binary_row(r: &BinaryRow, dst: &mut BytesMut) {
+    let mut payload = BytesMut::new();
+    payload.put_u8(0x00);
+
+    // The NULL bitmap is rebuilt from the cells, so a strategy that nulls
+    // a value out stays consistent with the bitmap
+    let mut bitmap = vec![0u8; (r.values.len() + 2).div_ceil(8)];
+    for (i, cell) in r.values.iter().enumerate() {
+        if matches!(cell, BinaryCell::Null) {
+            let bit = i + 2;
+            bitmap[bit / 8] |= 1 << (bit % 8);
+        }
+    }
+    payload.put_slice(&bitmap);
+
+    for cell in &r.values {
+        match cell {
+            BinaryCell::Null => {}
+            // Re-emitted with a fresh length prefix: masking may have
+            // changed the value's length
+            BinaryCell::Text(v) => write_lenenc_string(&mut payload, v),
+            BinaryCell::Other(v) => payload.put_slice(v),
+        }
+    }
+
+    write_packet_header(dst, payload.len(), r.sequence_id);
+    dst.put_slice(&payload);
+}
+
 fn encode_ok(o: &OkPacket, dst: &mut BytesMut, capability_flags: u32) {
     let mut payload = BytesMut::new();
     payload.put_u8(0x00);
@@ -909,4 +1246,201 @@ mod tests {
             assert_eq!(decoded, val);
         }
     }
+
+    /// Upstream-facing codec already past the handshake, as it is when the
+    /// command phase starts
+    fn client_codec_in_command(capability_flags: u32) -> MySqlCodec {
+        let mut codec = MySqlCodec::new_client();
+        codec.state = MySqlState::Command;
+        codec.set_capability_flags(capability_flags);
+        codec
+    }
+
+    fn packet(sequence_id: u8, payload: &[u8]) -> BytesMut {
+        let mut buf = BytesMut::new();
+        write_packet_header(&mut buf, payload.len(), sequence_id);
+        buf.put_slice(payload);
+        buf
+    }
+
+    fn column_definition(name: &str, column_type: u8, sequence_id: u8) -> ColumnDefinition {
+        ColumnDefinition {
+            sequence_id,
+            catalog: Bytes::from_static(b"def"),
+            schema: Bytes::new(),
+            table: Bytes::from_static(b"users"),
+            org_table: Bytes::from_static(b"users"),
+            name: Bytes::copy_from_slice(name.as_bytes()),
+            org_name: Bytes::copy_from_slice(name.as_bytes()),
+            character_set: 0x21,
+            column_length: 255,
+            column_type,
+            flags: 0,
+            decimals: 0,
+        }
+    }
+
+    #[test]
+    fn test_prepare_response_passes_through_unparsed() {
+        let mut codec = client_codec_in_command(CLIENT_PROTOCOL_41);
+        codec.note_client_command(COM_STMT_PREPARE);
+
+        // COM_STMT_PREPARE_OK: stmt_id 1, one column, one parameter. Its
+        // 0x00 header must not be mistaken for an OK packet.
+        let mut src = packet(1, &[0x00, 1, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0]);
+        assert!(matches!(
+            codec.decode(&mut src).unwrap(),
+            Some(MySqlMessage::Generic(_))
+        ));
+
+        // Parameter definition, EOF, column definition, EOF: four packets
+        // that all ride through as Generic, whatever their leading byte
+        for (seq, payload) in [
+            (2u8, &[0x03, 0x64, 0x65, 0x66][..]),
+            (3, &[0xfe, 0, 0, 2, 0]),
+            (4, &[0x03, 0x64, 0x65, 0x66]),
+            (5, &[0xfe, 0, 0, 2, 0]),
+        ] {
+            let mut src = packet(seq, payload);
+            assert!(
+                matches!(codec.decode(&mut src).unwrap(), Some(MySqlMessage::Generic(_))),
+                "prepare response packet {} was parsed instead of passed through",
+                seq
+            );
+        }
+
+        // Back in the command phase: a real OK parses as one again
+        let mut src = packet(1, &[0x00, 0, 0, 2, 0, 0, 0]);
+        assert!(matches!(
+            codec.decode(&mut src).unwrap(),
+            Some(MySqlMessage::Ok(_))
+        ));
+    }
+
+    #[test]
+    fn test_execute_binary_resultset_roundtrip() {
+        let mut codec = client_codec_in_command(CLIENT_PROTOCOL_41);
+        codec.note_client_command(COM_STMT_EXECUTE);
+
+        // Result set header: two columns
+        let mut src = packet(1, &[0x02]);
+        assert!(matches!(
+            codec.decode(&mut src).unwrap(),
+            Some(MySqlMessage::Generic(_))
+        ));
+
+        // Column definitions: id LONG, email VAR_STRING
+        let mut encoder = MySqlCodec::new_server();
+        let mut src = BytesMut::new();
+        encoder
+            .encode(
+                MySqlMessage::ColumnDefinition(column_definition("id", 3, 2)),
+                &mut src,
+            )
+            .unwrap();
+        encoder
+            .encode(
+                MySqlMessage::ColumnDefinition(column_definition("email", 253, 3)),
+                &mut src,
+            )
+            .unwrap();
+        for _ in 0..2 {
+            assert!(matches!(
+                codec.decode(&mut src).unwrap(),
+                Some(MySqlMessage::ColumnDefinition(_))
+            ));
+        }
+        let mut src = packet(4, &[0xfe, 0, 0, 2, 0]);
+        assert!(matches!(
+            codec.decode(&mut src).unwrap(),
+            Some(MySqlMessage::Eof(_))
+        ));
+
+        // Binary row: header, NULL bitmap, id = 42 (4 raw bytes), then the
+        // email as a length-encoded string
+        let mut payload = vec![0x00, 0x00, 42, 0, 0, 0, 16];
+        payload.extend_from_slice(b"user@example.com");
+        let wire = packet(5, &payload);
+        let mut src = wire.clone();
+        let row = match codec.decode(&mut src).unwrap() {
+            Some(MySqlMessage::BinaryRow(row)) => row,
+            other => panic!("expected binary row, got {:?}", other),
+        };
+        assert!(matches!(&row.values[0], BinaryCell::Other(v) if v[..] == [42, 0, 0, 0]));
+        assert!(matches!(&row.values[1], BinaryCell::Text(v) if v[..] == b"user@example.com"[..]));
+
+        // Untouched, the row re-encodes byte-for-byte
+        let mut out = BytesMut::new();
+        encoder
+            .encode(MySqlMessage::BinaryRow(row.clone()), &mut out)
+            .unwrap();
+        assert_eq!(out, wire);
+
+        // A masked value of a different length gets a corrected prefix
+        let mut masked = row;
+        masked.values[1] = BinaryCell::Text(BytesMut::from(&b"masked@example.org"[..]));
+        let mut out = BytesMut::new();
+        encoder
+            .encode(MySqlMessage::BinaryRow(masked), &mut out)
+            .unwrap();
+        // Lenenc prefix sits after the packet header, row header, bitmap,
+        // and the 4 raw id bytes
+        assert_eq!(out[10], 18);
+        assert!(out.ends_with(b"masked@example.org"));
+
+        // A row with a NULL id exercises the bitmap on both directions
+        let mut payload = vec![0x00, 0b0000_0100, 16];
+        payload.extend_from_slice(b"user@example.com");
+        let wire = packet(6, &payload);
+        let mut src = wire.clone();
+        let row = match codec.decode(&mut src).unwrap() {
+            Some(MySqlMessage::BinaryRow(row)) => row,
+            other => panic!("expected binary row, got {:?}", other),
+        };
+        assert!(matches!(row.values[0], BinaryCell::Null));
+        let mut out = BytesMut::new();
+        encoder
+            .encode(MySqlMessage::BinaryRow(row), &mut out)
+            .unwrap();
+        assert_eq!(out, wire);
+    }
+
+    #[test]
+    fn test_binary_resultset_with_deprecate_eof() {
+        let mut codec = client_codec_in_command(CLIENT_PROTOCOL_41 | CLIENT_DEPRECATE_EOF);
+        codec.note_client_command(COM_STMT_EXECUTE);
+
+        let mut src = packet(1, &[0x01]);
+        codec.decode(&mut src).unwrap();
+
+        // Single column, no EOF after it: rows follow directly
+        let mut encoder = MySqlCodec::new_server();
+        let mut src = BytesMut::new();
+        encoder
+            .encode(
+                MySqlMessage::ColumnDefinition(column_definition("email", 253, 2)),
+                &mut src,
+            )
+            .unwrap();
+        codec.decode(&mut src).unwrap();
+
+        let mut payload = vec![0x00, 0x00, 3];
+        payload.extend_from_slice(b"a@b");
+        let mut src = packet(3, &payload);
+        assert!(matches!(
+            codec.decode(&mut src).unwrap(),
+            Some(MySqlMessage::BinaryRow(_))
+        ));
+
+        // The terminating OK reuses the 0xfe header; it must end the result
+        // set and pass through byte-exact
+        let mut src = packet(4, &[0xfe, 0, 0, 2, 0, 0, 0, 0, 0, 0]);
+        match codec.decode(&mut src).unwrap() {
+            Some(MySqlMessage::Generic(g)) => {
+                assert_eq!(g.payload[0], 0xfe);
+            }
+            other => panic!("expected passthrough terminator, got {:?}", other),
+        }
+        assert_eq!(codec.state, MySqlState::Command);
+    }
 }
@@ -1,8 +1,21 @@
-use anyhow::Result;
+use crate::error::{ProtocolError, ProxyError};
+use crate::protocol::wire_serde;
 use bytes::{Buf, BufMut, Bytes, BytesMut};
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use tokio_util::codec::{Decoder, Encoder};
 
-#[derive(Debug, Clone)]
+/// Largest frame we will buffer before rejecting the stream as broken,
+/// absent a configured cap. Matches the 1 GiB hard limit Postgres itself
+/// enforces on message sizes.
+const MAX_MESSAGE_SIZE: usize = 1024 * 1024 * 1024;
+
+/// Largest startup-phase packet we will buffer. Postgres caps startup
+/// packets at 10000 bytes; a megabyte-sized length claim before
+/// authentication is an attack, not a client.
+pub const MAX_STARTUP_SIZE: usize = 10_240;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum PgMessage {
     Startup(StartupMessage),
     Regular(RegularMessage),
@@ -11,39 +24,53 @@ pub enum PgMessage {
     Query(QueryMessage),
     Parse(ParseMessage),
     SSLRequest,
+    CancelRequest(CancelRequestMessage),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StartupMessage {
     pub protocol_version: u32,
     pub parameters: Vec<(String, String)>,
 }
 
-#[derive(Debug, Clone)]
+/// The body of a CancelRequest: the BackendKeyData a session received,
+/// echoed back on a fresh connection to interrupt that session's query.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancelRequestMessage {
+    pub process_id: u32,
+    pub secret_key: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueryMessage {
+    #[serde(with = "wire_serde::bytes")]
     pub query: Bytes,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ParseMessage {
+    #[serde(with = "wire_serde::bytes")]
     pub statement: Bytes,
+    #[serde(with = "wire_serde::bytes")]
     pub query: Bytes,
     pub param_types: Vec<u32>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RegularMessage {
     pub message_type: u8,
+    #[serde(with = "wire_serde::bytes_mut")]
     pub payload: BytesMut,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RowDescription {
     pub fields: Vec<FieldDescription>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FieldDescription {
+    #[serde(with = "wire_serde::bytes")]
     pub name: Bytes,
     pub table_oid: u32,
     pub column_index: u16,
@@ -53,32 +80,129 @@ pub struct FieldDescription {
     pub format_code: i16,
 }
 
-#[derive(Debug, Clone)]
+/// Broad classification of a column's wire type, derived from the type OID
+/// in a [`RowDescription`]. Only common built-in types are recognised;
+/// user-defined types, extensions, and everything else map to
+/// [`PgTypeClass::Other`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PgTypeClass {
+    Boolean,
+    Integer,
+    Float,
+    Numeric,
+    Date,
+    Timestamp,
+    Text,
+    Json,
+    Uuid,
+    /// A range type (`int4range`, `daterange`, ...): a structured literal
+    /// that must not be treated as free-form text
+    Range,
+    Other,
+}
+
+impl PgTypeClass {
+    /// Classifies a type OID from `pg_type`. Covers the built-in scalar
+    /// types a proxy is likely to see in result sets.
+    pub fn from_oid(oid: u32) -> Self {
+        match oid {
+            16 => PgTypeClass::Boolean,              // bool
+            20 | 21 | 23 | 26 => PgTypeClass::Integer, // int8, int2, int4, oid
+            700 | 701 => PgTypeClass::Float,         // float4, float8
+            1700 => PgTypeClass::Numeric,            // numeric
+            1082 => PgTypeClass::Date,               // date
+            1114 | 1184 => PgTypeClass::Timestamp,   // timestamp, timestamptz
+            18 | 19 | 25 | 1042 | 1043 => PgTypeClass::Text, // char, name, text, bpchar, varchar
+            114 | 3802 => PgTypeClass::Json,         // json, jsonb
+            2950 => PgTypeClass::Uuid,               // uuid
+            // int4range, numrange, tsrange, tstzrange, daterange, int8range
+            3904 | 3906 | 3908 | 3910 | 3912 | 3926 => PgTypeClass::Range,
+            _ => PgTypeClass::Other,
+        }
+    }
+
+    /// Whether values of this class carry free-form text worth running
+    /// content heuristics over. Unrecognised types count as textual so the
+    /// proxy errs on the side of scanning.
+    pub fn is_textual(self) -> bool {
+        matches!(self, PgTypeClass::Text | PgTypeClass::Json | PgTypeClass::Other)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct DataRow {
+    #[serde(with = "wire_serde::row_values")]
     pub values: Vec<Option<BytesMut>>,
 }
 
+/// Renders values as lossy UTF-8 (NULLs bare) so row dumps in logs and
+/// test failures stay readable.
+impl fmt::Debug for DataRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DataRow[")?;
+        for (i, value) in self.values.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            match value {
+                Some(v) => write!(f, "{:?}", String::from_utf8_lossy(v))?,
+                None => write!(f, "NULL")?,
+            }
+        }
+        write!(f, "]")
+    }
+}
+
 pub struct PostgresCodec {
     // State to track if we are expecting a startup message (first message)
     // or regular messages.
     is_startup: bool,
+    // Which side this codec frames. Type bytes are direction-dependent:
+    // 'D' is DataRow from the backend but Describe from the client, so a
+    // client-side codec must not parse it as a row.
+    upstream: bool,
+    // Frames claiming more than this are rejected before any buffer is
+    // reserved for them, so a forged length header cannot force an
+    // allocation.
+    max_message_size: usize,
 }
 
 impl PostgresCodec {
     pub fn new() -> Self {
-        Self { is_startup: true }
+        Self {
+            is_startup: true,
+            upstream: false,
+            max_message_size: MAX_MESSAGE_SIZE,
+        }
     }
 
     pub fn new_upstream() -> Self {
-        Self { is_startup: false }
+        Self {
+            is_startup: false,
+            upstream: true,
+            max_message_size: MAX_MESSAGE_SIZE,
+        }
+    }
+
+    /// Caps the size of a single frame, from `limits.max_client_message_bytes`
+    /// or `limits.max_upstream_message_bytes` depending on the side
+    pub fn with_max_message_size(mut self, max: usize) -> Self {
+        self.max_message_size = max;
+        self
+    }
+}
+
+impl Default for PostgresCodec {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Decoder for PostgresCodec {
     type Item = PgMessage;
-    type Error = anyhow::Error;
+    type Error = ProxyError;
 
-    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>> {
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, ProxyError> {
         if src.len() < 4 {
             return Ok(None);
         }
@@ -89,6 +213,13 @@ impl Decoder for PostgresCodec {
         let length = u32::from_be_bytes(length_bytes) as usize;
 
         if self.is_startup {
+            if !(8..=MAX_STARTUP_SIZE).contains(&length) {
+                return Err(ProtocolError::Framing {
+                    message_type: None,
+                    details: format!("startup message with invalid length {}", length),
+                }
+                .into());
+            }
             // Startup packet: [Length (4 bytes)] [Protocol Version (4 bytes)] [Params...]
             // OR SSLRequest: [Length (4 bytes)] [1234 in high 16 bits] [5679 in low 16 bits]
 
@@ -109,6 +240,38 @@ impl Decoder for PostgresCodec {
                 return Ok(Some(PgMessage::SSLRequest));
             }
 
+            if protocol_version == 80877102 {
+                // CancelRequest (1234.5678): sent on its own connection,
+                // which carries nothing else before closing
+                if data.remaining() < 8 {
+                    return Err(ProtocolError::InvalidMessage {
+                        message_type: "CancelRequest".to_string(),
+                        details: "packet too short for a process id and secret key"
+                            .to_string(),
+                    }
+                    .into());
+                }
+                return Ok(Some(PgMessage::CancelRequest(CancelRequestMessage {
+                    process_id: data.get_u32(),
+                    secret_key: data.get_u32(),
+                })));
+            }
+
+            // Only 3.x startup packets carry null-terminated key/value
+            // pairs; reading a 2.0 packet as one would garble its
+            // fixed-width fields, so reject anything else cleanly
+            if protocol_version >> 16 != 3 {
+                return Err(ProtocolError::InvalidMessage {
+                    message_type: "StartupMessage".to_string(),
+                    details: format!(
+                        "unsupported protocol version {}.{}; only 3.x is supported",
+                        protocol_version >> 16,
+                        protocol_version & 0xffff
+                    ),
+                }
+                .into());
+            }
+
             // Parse Startup Message
             let mut parameters = Vec::new();
             while data.has_remaining() {
@@ -144,6 +307,21 @@ impl Decoder for PostgresCodec {
             length_bytes.copy_from_slice(&src[1..5]);
             let length = u32::from_be_bytes(length_bytes) as usize;
 
+            if length < 4 {
+                return Err(ProtocolError::Framing {
+                    message_type: Some(message_type),
+                    details: format!("length field {} is smaller than itself", length),
+                }
+                .into());
+            }
+            if length > self.max_message_size {
+                return Err(ProtocolError::Oversize {
+                    size: length,
+                    max: self.max_message_size,
+                }
+                .into());
+            }
+
             // Total frame size = 1 (type) + length
             let frame_len = 1 + length;
 
@@ -156,7 +334,7 @@ impl Decoder for PostgresCodec {
             data.advance(5); // Skip Type (1) + Length (4)
 
             match message_type {
-                b'T' => {
+                b'T' if self.upstream => {
                     // RowDescription
                     let num_fields = data.get_u16();
                     let mut fields = Vec::with_capacity(num_fields as usize);
@@ -181,26 +359,44 @@ impl Decoder for PostgresCodec {
                     }
                     Ok(Some(PgMessage::RowDescription(RowDescription { fields })))
                 }
-                b'D' => {
+                b'D' if self.upstream => {
                     // DataRow
                     let num_cols = data.get_u16();
                     let mut values = Vec::with_capacity(num_cols as usize);
                     for _ in 0..num_cols {
+                        if data.remaining() < 4 {
+                            return Err(ProtocolError::InvalidMessage {
+                                message_type: "DataRow".to_string(),
+                                details: "column count exceeds the payload".to_string(),
+                            }
+                            .into());
+                        }
                         let len = data.get_i32();
                         if len == -1 {
                             values.push(None);
                         } else {
+                            if len < 0 || len as usize > data.len() {
+                                return Err(ProtocolError::InvalidMessage {
+                                    message_type: "DataRow".to_string(),
+                                    details: format!(
+                                        "column length {} exceeds remaining payload of {} bytes",
+                                        len,
+                                        data.len()
+                                    ),
+                                }
+                                .into());
+                            }
                             let val = data.split_to(len as usize);
                             values.push(Some(val));
                         }
                     }
                     Ok(Some(PgMessage::DataRow(DataRow { values })))
                 }
-                b'Q' => {
+                b'Q' if !self.upstream => {
                     let query = read_cstring_bytes(&mut data)?;
                     Ok(Some(PgMessage::Query(QueryMessage { query })))
                 }
-                b'P' => {
+                b'P' if !self.upstream => {
                     let statement = read_cstring_bytes(&mut data)?;
                     let query = read_cstring_bytes(&mut data)?;
                     let num_params = data.get_u16();
@@ -224,9 +420,9 @@ impl Decoder for PostgresCodec {
 }
 
 impl Encoder<PgMessage> for PostgresCodec {
-    type Error = anyhow::Error;
+    type Error = ProxyError;
 
-    fn encode(&mut self, item: PgMessage, dst: &mut BytesMut) -> Result<()> {
+    fn encode(&mut self, item: PgMessage, dst: &mut BytesMut) -> Result<(), ProxyError> {
         match item {
             PgMessage::Startup(msg) => {
                 // Calculate length
@@ -252,6 +448,12 @@ impl Encoder<PgMessage> for PostgresCodec {
                 dst.put_u32(8);
                 dst.put_u32(80877103);
             }
+            PgMessage::CancelRequest(msg) => {
+                dst.put_u32(16);
+                dst.put_u32(80877102);
+                dst.put_u32(msg.process_id);
+                dst.put_u32(msg.secret_key);
+            }
             PgMessage::RowDescription(msg) => {
                 dst.put_u8(b'T');
 
@@ -336,21 +538,407 @@ impl Encoder<PgMessage> for PostgresCodec {
     }
 }
 
+/// Parse a ParameterStatus ('S') payload into its (name, value) pair.
+///
+/// Returns None if the payload is not two null-terminated strings.
+pub fn parse_parameter_status(payload: &[u8]) -> Option<(String, String)> {
+    let mut parts = payload.split(|&b| b == 0);
+    let name = std::str::from_utf8(parts.next()?).ok()?;
+    let value = std::str::from_utf8(parts.next()?).ok()?;
+    Some((name.to_string(), value.to_string()))
+}
+
+// ============================================================================
+// COPY TO STDOUT support
+// ============================================================================
+
+/// Field layout of a `COPY ... TO STDOUT` data stream the proxy can mask.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyFormat {
+    /// COPY text format: tab-delimited by default, backslash escapes,
+    /// `\N` for NULL
+    Text { delimiter: u8 },
+    /// COPY CSV format: comma-delimited by default, double-quote quoting,
+    /// unquoted empty string for NULL
+    Csv { delimiter: u8 },
+}
+
+/// What a `COPY ... TO STDOUT` statement declared about the stream it is
+/// about to produce. Parsed best-effort from the SQL text: statements the
+/// parser does not understand fall back to text-format defaults with no
+/// column metadata, which still leaves the content heuristics in force.
+#[derive(Debug, Clone)]
+pub struct CopyOutStatement {
+    /// Table being copied, when the source is a table rather than a query
+    pub table: Option<String>,
+    /// Explicit column list, when one was given
+    pub columns: Vec<String>,
+    /// Inner query for the `COPY (SELECT ...) TO STDOUT` form
+    pub query: Option<String>,
+    pub format: CopyFormat,
+    /// `FORMAT binary` was requested; the stream cannot be masked
+    pub binary: bool,
+    /// `CSV HEADER`: the first row is column names, not data
+    pub header: bool,
+}
+
+impl Default for CopyOutStatement {
+    fn default() -> Self {
+        Self {
+            table: None,
+            columns: Vec::new(),
+            query: None,
+            format: CopyFormat::Text { delimiter: b'\t' },
+            binary: false,
+            header: false,
+        }
+    }
+}
+
+/// Parse a CopyOutResponse ('H') payload into its overall format code
+/// (0 = text/csv, 1 = binary) and column count.
+pub fn parse_copy_out_response(payload: &[u8]) -> Option<(u8, u16)> {
+    if payload.len() < 3 {
+        return None;
+    }
+    Some((payload[0], u16::from_be_bytes([payload[1], payload[2]])))
+}
+
+/// Strip surrounding double quotes from an identifier, if present.
+fn unquote_ident(ident: &str) -> String {
+    let ident = ident.trim();
+    ident
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .map(|s| s.replace("\"\"", "\""))
+        .unwrap_or_else(|| ident.to_string())
+}
+
+/// Parse a `COPY ... TO STDOUT` statement, extracting the source (table
+/// plus optional column list, or inner query) and the options that shape
+/// the data stream. Returns None for anything that is not a COPY TO
+/// STDOUT — including COPY FROM, which flows the other way and carries no
+/// data to mask.
+pub fn parse_copy_to_stdout(sql: &str) -> Option<CopyOutStatement> {
+    let sql = sql.trim().trim_end_matches(';').trim();
+    let rest = sql
+        .get(..4)
+        .filter(|head| head.eq_ignore_ascii_case("copy"))
+        .map(|_| sql[4..].trim_start())?;
+
+    let mut stmt = CopyOutStatement::default();
+    let after_target;
+
+    if let Some(inner) = rest.strip_prefix('(') {
+        // COPY (query) TO STDOUT: find the matching close paren, skipping
+        // string literals
+        let mut depth = 1usize;
+        let mut in_string = false;
+        let mut end = None;
+        for (i, c) in inner.char_indices() {
+            match c {
+                '\'' => in_string = !in_string,
+                '(' if !in_string => depth += 1,
+                ')' if !in_string => {
+                    depth -= 1;
+                    if depth == 0 {
+                        end = Some(i);
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        let end = end?;
+        stmt.query = Some(inner[..end].trim().to_string());
+        after_target = inner[end + 1..].trim_start();
+    } else {
+        // COPY table [(col, ...)] TO STDOUT
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || c == '(')
+            .unwrap_or(rest.len());
+        let qualified = &rest[..name_end];
+        stmt.table = Some(unquote_ident(
+            qualified.rsplit('.').next().unwrap_or(qualified),
+        ));
+        let rest = rest[name_end..].trim_start();
+        if let Some(list) = rest.strip_prefix('(') {
+            let end = list.find(')')?;
+            stmt.columns = list[..end].split(',').map(unquote_ident).collect();
+            after_target = list[end + 1..].trim_start();
+        } else {
+            after_target = rest;
+        }
+    }
+
+    // TO STDOUT, then options in either the old keyword or the new
+    // parenthesized syntax
+    let mut words = after_target.split_whitespace();
+    if !words.next()?.eq_ignore_ascii_case("to") || !words.next()?.eq_ignore_ascii_case("stdout") {
+        return None;
+    }
+    let options = after_target.to_ascii_lowercase();
+    stmt.binary = options.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .any(|word| word == "binary");
+    let csv = options
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .any(|word| word == "csv");
+    stmt.header = options
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .any(|word| word == "header");
+
+    // DELIMITER 'x' (or DELIMITER ',' inside a parenthesized option list)
+    let delimiter = options.find("delimiter").and_then(|pos| {
+        let tail = &after_target[pos + "delimiter".len()..];
+        let quote = tail.find('\'')?;
+        tail.as_bytes().get(quote + 1).copied()
+    });
+    stmt.format = if csv {
+        CopyFormat::Csv {
+            delimiter: delimiter.unwrap_or(b','),
+        }
+    } else {
+        CopyFormat::Text {
+            delimiter: delimiter.unwrap_or(b'\t'),
+        }
+    };
+    Some(stmt)
+}
+
+/// Re-frames a COPY TO STDOUT data stream into logical rows. CopyData
+/// frame boundaries carry no meaning — a row can span frames and a frame
+/// can carry many rows — so bytes are buffered until a row-terminating
+/// newline arrives. In CSV format a newline inside a quoted field does not
+/// terminate the row, and quote state survives frame boundaries.
+pub struct CopyRowBuffer {
+    format: CopyFormat,
+    buf: BytesMut,
+    scan_pos: usize,
+    in_quotes: bool,
+}
+
+impl CopyRowBuffer {
+    pub fn new(format: CopyFormat) -> Self {
+        Self {
+            format,
+            buf: BytesMut::new(),
+            scan_pos: 0,
+            in_quotes: false,
+        }
+    }
+
+    /// Append the payload of one CopyData frame.
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Take the next complete row off the buffer, without its trailing
+    /// newline.
+    pub fn next_row(&mut self) -> Option<BytesMut> {
+        while self.scan_pos < self.buf.len() {
+            let b = self.buf[self.scan_pos];
+            if matches!(self.format, CopyFormat::Csv { .. }) && b == b'"' {
+                self.in_quotes = !self.in_quotes;
+            } else if b == b'\n' && !self.in_quotes {
+                let mut row = self.buf.split_to(self.scan_pos + 1);
+                row.truncate(row.len() - 1);
+                self.scan_pos = 0;
+                return Some(row);
+            }
+            self.scan_pos += 1;
+        }
+        None
+    }
+
+    /// Take whatever is left after the last newline. A well-formed COPY
+    /// stream ends every row with one, so this only fires on truncated
+    /// streams.
+    pub fn take_partial(&mut self) -> Option<BytesMut> {
+        self.scan_pos = 0;
+        (!self.buf.is_empty()).then(|| self.buf.split())
+    }
+
+    /// Split one row into field values, NULLs distinguished per the format.
+    pub fn decode_row(&self, row: &[u8]) -> Vec<Option<BytesMut>> {
+        match self.format {
+            CopyFormat::Text { delimiter } => decode_copy_text_row(row, delimiter),
+            CopyFormat::Csv { delimiter } => decode_copy_csv_row(row, delimiter),
+        }
+    }
+
+    /// Re-emit one row of field values, trailing newline included.
+    pub fn encode_row(&self, values: &[Option<BytesMut>], dst: &mut BytesMut) {
+        match self.format {
+            CopyFormat::Text { delimiter } => encode_copy_text_row(values, delimiter, dst),
+            CopyFormat::Csv { delimiter } => encode_copy_csv_row(values, delimiter, dst),
+        }
+    }
+}
+
+fn decode_copy_text_row(row: &[u8], delimiter: u8) -> Vec<Option<BytesMut>> {
+    let mut values = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    // A backslash escapes the next byte, so a raw delimiter is always a
+    // field boundary
+    while i < row.len() {
+        if row[i] == b'\\' {
+            i += 2;
+            continue;
+        }
+        if row[i] == delimiter {
+            values.push(decode_copy_text_field(&row[start..i]));
+            start = i + 1;
+        }
+        i += 1;
+    }
+    values.push(decode_copy_text_field(&row[start..]));
+    values
+}
+
+fn decode_copy_text_field(raw: &[u8]) -> Option<BytesMut> {
+    if raw == b"\\N" {
+        return None;
+    }
+    let mut field = BytesMut::with_capacity(raw.len());
+    let mut i = 0;
+    while i < raw.len() {
+        if raw[i] == b'\\' && i + 1 < raw.len() {
+            field.put_u8(match raw[i + 1] {
+                b'b' => 0x08,
+                b'f' => 0x0c,
+                b'n' => b'\n',
+                b'r' => b'\r',
+                b't' => b'\t',
+                b'v' => 0x0b,
+                other => other,
+            });
+            i += 2;
+        } else {
+            field.put_u8(raw[i]);
+            i += 1;
+        }
+    }
+    Some(field)
+}
+
+fn encode_copy_text_row(values: &[Option<BytesMut>], delimiter: u8, dst: &mut BytesMut) {
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            dst.put_u8(delimiter);
+        }
+        match value {
+            None => dst.put_slice(b"\\N"),
+            Some(value) => {
+                for &b in value.iter() {
+                    match b {
+                        0x08 => dst.put_slice(b"\\b"),
+                        0x0c => dst.put_slice(b"\\f"),
+                        b'\n' => dst.put_slice(b"\\n"),
+                        b'\r' => dst.put_slice(b"\\r"),
+                        b'\t' => dst.put_slice(b"\\t"),
+                        0x0b => dst.put_slice(b"\\v"),
+                        b'\\' => dst.put_slice(b"\\\\"),
+                        b if b == delimiter => {
+                            dst.put_u8(b'\\');
+                            dst.put_u8(b);
+                        }
+                        b => dst.put_u8(b),
+                    }
+                }
+            }
+        }
+    }
+    dst.put_u8(b'\n');
+}
+
+fn decode_copy_csv_row(row: &[u8], delimiter: u8) -> Vec<Option<BytesMut>> {
+    let mut values = Vec::new();
+    let mut field = BytesMut::new();
+    let mut quoted = false;
+    let mut in_quotes = false;
+    let mut i = 0;
+    while i < row.len() {
+        let b = row[i];
+        if in_quotes {
+            if b == b'"' {
+                if row.get(i + 1) == Some(&b'"') {
+                    field.put_u8(b'"');
+                    i += 2;
+                    continue;
+                }
+                in_quotes = false;
+            } else {
+                field.put_u8(b);
+            }
+        } else if b == b'"' {
+            in_quotes = true;
+            quoted = true;
+        } else if b == delimiter {
+            values.push(finish_csv_field(&mut field, &mut quoted));
+        } else {
+            field.put_u8(b);
+        }
+        i += 1;
+    }
+    values.push(finish_csv_field(&mut field, &mut quoted));
+    values
+}
+
+/// An unquoted empty field is NULL in CSV; a quoted one is an empty string.
+fn finish_csv_field(field: &mut BytesMut, quoted: &mut bool) -> Option<BytesMut> {
+    let value = field.split();
+    let was_quoted = std::mem::take(quoted);
+    (was_quoted || !value.is_empty()).then_some(value)
+}
+
+fn encode_copy_csv_row(values: &[Option<BytesMut>], delimiter: u8, dst: &mut BytesMut) {
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            dst.put_u8(delimiter);
+        }
+        let Some(value) = value else { continue };
+        let needs_quotes = value.is_empty()
+            || value
+                .iter()
+                .any(|&b| b == delimiter || b == b'"' || b == b'\n' || b == b'\r');
+        if needs_quotes {
+            dst.put_u8(b'"');
+            for &b in value.iter() {
+                if b == b'"' {
+                    dst.put_u8(b'"');
+                }
+                dst.put_u8(b);
+            }
+            dst.put_u8(b'"');
+        } else {
+            dst.put_slice(value);
+        }
+    }
+    dst.put_u8(b'\n');
+}
+
 /// Read a null-terminated C-string from the buffer, returning a zero-copy Bytes slice.
-fn read_cstring_bytes(buf: &mut BytesMut) -> Result<Bytes> {
+fn read_cstring_bytes(buf: &mut BytesMut) -> Result<Bytes, ProtocolError> {
     let pos = buf
         .iter()
         .position(|&b| b == 0)
-        .ok_or_else(|| anyhow::anyhow!("Missing null terminator in C-string"))?;
+        .ok_or_else(|| ProtocolError::Framing {
+            message_type: None,
+            details: "missing null terminator in C-string".to_string(),
+        })?;
     let bytes = buf.split_to(pos).freeze();
     buf.advance(1); // Skip the null terminator
     Ok(bytes)
 }
 
 /// Read a null-terminated C-string as a String (for startup parameters)
-fn read_cstring(buf: &mut BytesMut) -> Result<String> {
+fn read_cstring(buf: &mut BytesMut) -> Result<String, ProtocolError> {
     let bytes = read_cstring_bytes(buf)?;
-    Ok(String::from_utf8(bytes.to_vec())?)
+    String::from_utf8(bytes.to_vec()).map_err(|e| ProtocolError::Framing {
+        message_type: None,
+        details: format!("C-string is not valid UTF-8: {}", e),
+    })
 }
 
 #[cfg(test)]
@@ -412,8 +1000,7 @@ mod tests {
 
     #[test]
     fn test_decode_row_description() {
-        let mut codec = PostgresCodec::new();
-        codec.is_startup = false;
+        let mut codec = PostgresCodec::new_upstream();
         let mut buf = BytesMut::new();
 
         // 'T' (RowDescription)
@@ -449,8 +1036,7 @@ mod tests {
 
     #[test]
     fn test_decode_data_row() {
-        let mut codec = PostgresCodec::new();
-        codec.is_startup = false;
+        let mut codec = PostgresCodec::new_upstream();
         let mut buf = BytesMut::new();
 
         // 'D' (DataRow)
@@ -496,6 +1082,64 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_decode_v2_startup_is_rejected() {
+        let mut codec = PostgresCodec::new();
+        let mut buf = BytesMut::new();
+
+        // Protocol 2.0 startup: fixed-width fields, not key/value pairs
+        buf.put_u32(296);
+        buf.put_u32(131072);
+        buf.extend_from_slice(&[0u8; 288]);
+
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert!(
+            err.to_string().contains("unsupported protocol version 2.0"),
+            "unexpected error: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_cancel_request_roundtrip() {
+        let mut codec = PostgresCodec::new();
+        let mut buf = BytesMut::new();
+
+        // CancelRequest: Length (16) + Code (80877102) + PID + Key
+        buf.put_u32(16);
+        buf.put_u32(80877102);
+        buf.put_u32(4242);
+        buf.put_u32(0xdead_beef);
+
+        let result = codec.decode(&mut buf).unwrap().unwrap();
+        let PgMessage::CancelRequest(ref cancel) = result else {
+            panic!("expected CancelRequest, got {:?}", result);
+        };
+        assert_eq!(cancel.process_id, 4242);
+        assert_eq!(cancel.secret_key, 0xdead_beef);
+
+        let mut encoded = BytesMut::new();
+        codec.encode(result, &mut encoded).unwrap();
+        let mut expected = BytesMut::new();
+        expected.put_u32(16);
+        expected.put_u32(80877102);
+        expected.put_u32(4242);
+        expected.put_u32(0xdead_beef);
+        assert_eq!(encoded, expected);
+    }
+
+    #[test]
+    fn test_cancel_request_too_short_is_rejected() {
+        let mut codec = PostgresCodec::new();
+        let mut buf = BytesMut::new();
+
+        buf.put_u32(12);
+        buf.put_u32(80877102);
+        buf.put_u32(4242); // no secret key
+
+        assert!(codec.decode(&mut buf).is_err());
+    }
+
     #[test]
     fn test_decode_parse_message() {
         let mut codec = PostgresCodec::new();
@@ -530,6 +1174,16 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_parameter_status() {
+        let payload = b"server_version\x0016.1\x00";
+        let (name, value) = parse_parameter_status(payload).unwrap();
+        assert_eq!(name, "server_version");
+        assert_eq!(value, "16.1");
+
+        assert!(parse_parameter_status(b"no-terminator").is_none());
+    }
+
     #[test]
     fn test_decode_incomplete_message() {
         let mut codec = PostgresCodec::new();
@@ -550,8 +1204,7 @@ mod tests {
 
     #[test]
     fn test_decode_data_row_with_null() {
-        let mut codec = PostgresCodec::new();
-        codec.is_startup = false;
+        let mut codec = PostgresCodec::new_upstream();
         let mut buf = BytesMut::new();
 
         // DataRow with 2 cols: NULL and "data"
@@ -601,12 +1254,204 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_decode_oversize_frame_is_rejected() {
+        let mut codec = PostgresCodec::new_upstream();
+        let mut buf = BytesMut::new();
+
+        buf.put_u8(b'D');
+        buf.put_u32(u32::MAX); // Absurd length
+
+        let err = codec.decode(&mut buf).unwrap_err();
+        match err {
+            ProxyError::Protocol(ProtocolError::Oversize { size, max }) => {
+                assert_eq!(size, u32::MAX as usize);
+                assert_eq!(max, MAX_MESSAGE_SIZE);
+            }
+            other => panic!("Expected Oversize error, got {:?}", other),
+        }
+        assert!(err.disposition().close_connection);
+        assert_eq!(err.disposition().sqlstate, "08P01");
+    }
+
+    /// Feeds `bytes` into a fresh decode loop in `chunk`-sized pieces,
+    /// returning the rendered message sequence. With `chunk == 1` this
+    /// exercises every possible split point in the stream.
+    fn decode_stream(mut codec: PostgresCodec, bytes: &[u8], chunk: usize) -> Vec<String> {
+        let mut buf = BytesMut::new();
+        let mut messages = Vec::new();
+        for piece in bytes.chunks(chunk) {
+            buf.extend_from_slice(piece);
+            while let Some(msg) = codec.decode(&mut buf).unwrap() {
+                messages.push(format!("{:?}", msg));
+            }
+        }
+        assert!(buf.is_empty(), "stream left {} undecoded bytes", buf.len());
+        messages
+    }
+
+    #[test]
+    fn test_decode_client_stream_byte_at_a_time_matches_one_shot() {
+        // Captured client stream: startup (no type byte), a simple query,
+        // then Terminate
+        let mut stream = Vec::new();
+        let params = b"user alice database app  ";
+        stream.extend_from_slice(&((4 + 4 + params.len()) as u32).to_be_bytes());
+        stream.extend_from_slice(&196608u32.to_be_bytes());
+        stream.extend_from_slice(params);
+        let query = b"SELECT email FROM users ";
+        stream.push(b'Q');
+        stream.extend_from_slice(&((4 + query.len()) as u32).to_be_bytes());
+        stream.extend_from_slice(query);
+        stream.push(b'X');
+        stream.extend_from_slice(&4u32.to_be_bytes());
+
+        let one_shot = decode_stream(PostgresCodec::new(), &stream, stream.len());
+        let trickled = decode_stream(PostgresCodec::new(), &stream, 1);
+
+        assert_eq!(one_shot.len(), 3);
+        assert_eq!(one_shot, trickled);
+    }
+
+    #[test]
+    fn test_decode_upstream_stream_byte_at_a_time_matches_one_shot() {
+        // Captured upstream stream: a one-column RowDescription, a DataRow
+        // far larger than any single read, CommandComplete, ReadyForQuery
+        let mut stream = Vec::new();
+        let name = b"payload ";
+        stream.push(b'T');
+        stream.extend_from_slice(&((4 + 2 + name.len() + 18) as u32).to_be_bytes());
+        stream.extend_from_slice(&1u16.to_be_bytes());
+        stream.extend_from_slice(name);
+        stream.extend_from_slice(&0u32.to_be_bytes()); // table OID
+        stream.extend_from_slice(&1u16.to_be_bytes()); // column index
+        stream.extend_from_slice(&25u32.to_be_bytes()); // type OID (text)
+        stream.extend_from_slice(&(-1i16).to_be_bytes());
+        stream.extend_from_slice(&(-1i32).to_be_bytes());
+        stream.extend_from_slice(&0i16.to_be_bytes());
+        let value = vec![b'x'; 70_000];
+        stream.push(b'D');
+        stream.extend_from_slice(&((4 + 2 + 4 + value.len()) as u32).to_be_bytes());
+        stream.extend_from_slice(&1u16.to_be_bytes());
+        stream.extend_from_slice(&(value.len() as i32).to_be_bytes());
+        stream.extend_from_slice(&value);
+        let tag = b"SELECT 1 ";
+        stream.push(b'C');
+        stream.extend_from_slice(&((4 + tag.len()) as u32).to_be_bytes());
+        stream.extend_from_slice(tag);
+        stream.push(b'Z');
+        stream.extend_from_slice(&5u32.to_be_bytes());
+        stream.push(b'I');
+
+        let one_shot = decode_stream(PostgresCodec::new_upstream(), &stream, stream.len());
+        let trickled = decode_stream(PostgresCodec::new_upstream(), &stream, 1);
+
+        assert_eq!(one_shot.len(), 4);
+        assert_eq!(one_shot, trickled);
+    }
+
+    #[test]
+    fn test_decode_startup_length_over_cap_is_rejected() {
+        let mut codec = PostgresCodec::new();
+        let mut buf = BytesMut::new();
+
+        // Startup packet claiming a megabyte: no client sends this before
+        // authenticating, so it is rejected on the length alone
+        buf.put_u32(1024 * 1024);
+        buf.put_u32(196608);
+
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert!(matches!(
+            err,
+            ProxyError::Protocol(ProtocolError::Framing { .. })
+        ));
+    }
+
+    #[test]
+    fn test_decode_configured_client_cap_rejects_claim_without_payload() {
+        let mut codec = PostgresCodec::new().with_max_message_size(1024);
+        codec.is_startup = false;
+        let mut buf = BytesMut::new();
+
+        // A 'Q' header claiming 10 KB with no payload behind it: the claim
+        // alone must trip the cap, before any buffer is reserved
+        buf.put_u8(b'Q');
+        buf.put_u32(10_000);
+
+        let err = codec.decode(&mut buf).unwrap_err();
+        match err {
+            ProxyError::Protocol(ProtocolError::Oversize { size, max }) => {
+                assert_eq!(size, 10_000);
+                assert_eq!(max, 1024);
+            }
+            other => panic!("Expected Oversize error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_upstream_row_just_under_cap_passes() {
+        let mut codec = PostgresCodec::new_upstream().with_max_message_size(8192);
+        let mut buf = BytesMut::new();
+
+        // One wide column filling the frame to just under the cap
+        let val = vec![b'x'; 8000];
+        let total_len = 4 + 2 + 4 + val.len();
+        buf.put_u8(b'D');
+        buf.put_u32(total_len as u32);
+        buf.put_u16(1);
+        buf.put_i32(val.len() as i32);
+        buf.put_slice(&val);
+
+        let result = codec.decode(&mut buf).unwrap().unwrap();
+        if let PgMessage::DataRow(row) = result {
+            assert_eq!(row.values[0].as_ref().unwrap().len(), 8000);
+        } else {
+            panic!("Expected DataRow");
+        }
+    }
+
+    #[test]
+    fn test_decode_data_row_with_bogus_column_length() {
+        let mut codec = PostgresCodec::new_upstream();
+        let mut buf = BytesMut::new();
+
+        // DataRow claiming a 1000-byte column but carrying only 4 bytes
+        let total_len = 4 + 2 + 4;
+        buf.put_u8(b'D');
+        buf.put_u32(total_len as u32);
+        buf.put_u16(1);
+        buf.put_i32(1000);
+
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert!(matches!(
+            err,
+            ProxyError::Protocol(ProtocolError::InvalidMessage { .. })
+        ));
+    }
+
+    #[test]
+    fn test_decode_query_missing_terminator() {
+        let mut codec = PostgresCodec::new();
+        codec.is_startup = false;
+        let mut buf = BytesMut::new();
+
+        let query = b"SELECT 1"; // no trailing NUL
+        buf.put_u8(b'Q');
+        buf.put_u32(4 + query.len() as u32);
+        buf.put_slice(query);
+
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert!(matches!(
+            err,
+            ProxyError::Protocol(ProtocolError::Framing { .. })
+        ));
+    }
+
     #[test]
     fn test_zero_copy_field_name() {
         // This test demonstrates zero-copy parsing for RowDescription field names.
         // The decoded field name should share the same underlying buffer as the input.
-        let mut codec = PostgresCodec::new();
-        codec.is_startup = false;
+        let mut codec = PostgresCodec::new_upstream();
         let mut buf = BytesMut::new();
 
         let field_name = b"customer_email";
@@ -642,8 +1487,7 @@ mod tests {
     #[test]
     fn test_zero_copy_data_row() {
         // DataRow values are already BytesMut/Bytes - this test verifies they remain zero-copy
-        let mut codec = PostgresCodec::new();
-        codec.is_startup = false;
+        let mut codec = PostgresCodec::new_upstream();
         let mut buf = BytesMut::new();
 
         let data = b"sensitive_value_12345";
@@ -668,4 +1512,98 @@ mod tests {
             panic!("Expected DataRow");
         }
     }
+
+    #[test]
+    fn test_parse_copy_to_stdout_forms() {
+        // Table with explicit column list, old-style CSV options
+        let stmt =
+            parse_copy_to_stdout("COPY public.users (id, email) TO STDOUT WITH CSV HEADER")
+                .unwrap();
+        assert_eq!(stmt.table.as_deref(), Some("users"));
+        assert_eq!(stmt.columns, vec!["id".to_string(), "email".to_string()]);
+        assert_eq!(stmt.format, CopyFormat::Csv { delimiter: b',' });
+        assert!(stmt.header);
+        assert!(!stmt.binary);
+
+        // Subquery form with the parenthesized option syntax
+        let stmt = parse_copy_to_stdout(
+            "copy (select email from customers) to stdout (format csv, delimiter ';')",
+        )
+        .unwrap();
+        assert_eq!(stmt.query.as_deref(), Some("select email from customers"));
+        assert_eq!(stmt.format, CopyFormat::Csv { delimiter: b';' });
+
+        // Bare table, no options: text format, tab delimiter
+        let stmt = parse_copy_to_stdout("COPY customers TO STDOUT;").unwrap();
+        assert_eq!(stmt.table.as_deref(), Some("customers"));
+        assert!(stmt.columns.is_empty());
+        assert_eq!(stmt.format, CopyFormat::Text { delimiter: b'\t' });
+
+        // Binary is flagged so the proxy can refuse it
+        assert!(
+            parse_copy_to_stdout("COPY t TO STDOUT (FORMAT binary)")
+                .unwrap()
+                .binary
+        );
+
+        // COPY FROM carries data the other way; not ours to mask
+        assert!(parse_copy_to_stdout("COPY t FROM STDIN").is_none());
+        assert!(parse_copy_to_stdout("SELECT 1").is_none());
+    }
+
+    #[test]
+    fn test_copy_row_buffer_reassembles_rows_across_frames() {
+        let mut buffer = CopyRowBuffer::new(CopyFormat::Text { delimiter: b'\t' });
+
+        // One logical row split across two CopyData frames
+        buffer.push(b"1\talice@exa");
+        assert!(buffer.next_row().is_none());
+        buffer.push(b"mple.com\n2\t\\N\n");
+
+        let row = buffer.next_row().unwrap();
+        let values = buffer.decode_row(&row);
+        assert_eq!(values[0].as_deref(), Some(&b"1"[..]));
+        assert_eq!(values[1].as_deref(), Some(&b"alice@example.com"[..]));
+
+        let row = buffer.next_row().unwrap();
+        let values = buffer.decode_row(&row);
+        assert_eq!(values[1], None);
+        assert!(buffer.next_row().is_none());
+        assert!(buffer.take_partial().is_none());
+    }
+
+    #[test]
+    fn test_copy_text_row_escapes_roundtrip() {
+        let buffer = CopyRowBuffer::new(CopyFormat::Text { delimiter: b'\t' });
+        let values = buffer.decode_row(b"a\\tb\\nc\t\\N\td\\\\e");
+        assert_eq!(values[0].as_deref(), Some(&b"a\tb\nc"[..]));
+        assert_eq!(values[1], None);
+        assert_eq!(values[2].as_deref(), Some(&b"d\\e"[..]));
+
+        let mut out = BytesMut::new();
+        buffer.encode_row(&values, &mut out);
+        assert_eq!(&out[..], b"a\\tb\\nc\t\\N\td\\\\e\n");
+    }
+
+    #[test]
+    fn test_copy_csv_quoting_and_embedded_newlines() {
+        let mut buffer = CopyRowBuffer::new(CopyFormat::Csv { delimiter: b',' });
+
+        // A quoted field containing a newline must not terminate the row
+        buffer.push(b"1,\"line one\nline two\",x\n");
+        let row = buffer.next_row().unwrap();
+        let values = buffer.decode_row(&row);
+        assert_eq!(values[1].as_deref(), Some(&b"line one\nline two"[..]));
+
+        // Unquoted empty is NULL, quoted empty is an empty string, and
+        // doubled quotes decode to one
+        let values = buffer.decode_row(b",\"\",\"say \"\"hi\"\"\"");
+        assert_eq!(values[0], None);
+        assert_eq!(values[1].as_deref(), Some(&b""[..]));
+        assert_eq!(values[2].as_deref(), Some(&b"say \"hi\""[..]));
+
+        let mut out = BytesMut::new();
+        buffer.encode_row(&values, &mut out);
+        assert_eq!(&out[..], b",\"\",\"say \"\"hi\"\"\"\n");
+    }
 }
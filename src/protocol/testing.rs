@@ -0,0 +1,208 @@
+//! Fixture support for golden tests and capture/replay tooling.
+//!
+//! A [`ResultSetFixture`] is the protocol-agnostic shape both wire protocols
+//! reduce to: named columns plus rows of nullable text values. Fixtures load
+//! from YAML or JSON and convert to and from the typed protocol messages, so
+//! one file format serves golden masking tests, inspection of captured
+//! traffic, and replaying a captured result set against either protocol.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "mysql")]
+use super::mysql::{ColumnDefinition, ResultRow};
+#[cfg(feature = "postgres")]
+use super::postgres::{DataRow, FieldDescription, RowDescription};
+
+/// A result set in its simplest form: column names plus rows of nullable
+/// values (`null` in a fixture is a SQL NULL).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResultSetFixture {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Option<String>>>,
+}
+
+impl ResultSetFixture {
+    pub fn from_yaml(input: &str) -> Result<Self> {
+        Ok(serde_yaml::from_str(input)?)
+    }
+
+    pub fn from_json(input: &str) -> Result<Self> {
+        Ok(serde_json::from_str(input)?)
+    }
+
+    /// Builds the Postgres messages for this result set (text format, with
+    /// placeholder OIDs — rule matching only looks at column names).
+    #[cfg(feature = "postgres")]
+    pub fn to_postgres(&self) -> (RowDescription, Vec<DataRow>) {
+        let fields = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, name)| FieldDescription {
+                name: bytes::Bytes::copy_from_slice(name.as_bytes()),
+                table_oid: 0,
+                column_index: i as u16,
+                type_oid: 25, // text
+                type_len: -1,
+                type_modifier: -1,
+                format_code: 0,
+            })
+            .collect();
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| DataRow {
+                values: row
+                    .iter()
+                    .map(|value| {
+                        value
+                            .as_ref()
+                            .map(|v| bytes::BytesMut::from(v.as_bytes()))
+                    })
+                    .collect(),
+            })
+            .collect();
+        (RowDescription { fields }, rows)
+    }
+
+    /// Captures Postgres messages back into fixture form (values are
+    /// rendered as lossy UTF-8)
+    #[cfg(feature = "postgres")]
+    pub fn from_postgres(description: &RowDescription, rows: &[DataRow]) -> Self {
+        Self {
+            columns: description
+                .fields
+                .iter()
+                .map(|f| String::from_utf8_lossy(&f.name).into_owned())
+                .collect(),
+            rows: rows
+                .iter()
+                .map(|row| {
+                    row.values
+                        .iter()
+                        .map(|value| {
+                            value
+                                .as_ref()
+                                .map(|v| String::from_utf8_lossy(v).into_owned())
+                        })
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    /// Builds the MySQL messages for this result set, attributing every
+    /// column to `table` (text protocol, sequence ids numbered from 2 as
+    /// they would follow a column-count packet).
+    #[cfg(feature = "mysql")]
+    pub fn to_mysql(&self, table: &str) -> (Vec<ColumnDefinition>, Vec<ResultRow>) {
+        let columns = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, name)| ColumnDefinition {
+                sequence_id: (i + 2) as u8,
+                catalog: bytes::Bytes::from_static(b"def"),
+                schema: bytes::Bytes::new(),
+                table: bytes::Bytes::copy_from_slice(table.as_bytes()),
+                org_table: bytes::Bytes::copy_from_slice(table.as_bytes()),
+                name: bytes::Bytes::copy_from_slice(name.as_bytes()),
+                org_name: bytes::Bytes::copy_from_slice(name.as_bytes()),
+                character_set: 0x21, // utf8_general_ci
+                column_length: 255,
+                column_type: 0xfd, // VAR_STRING
+                flags: 0,
+                decimals: 0,
+            })
+            .collect();
+        let first_row_seq = self.columns.len() + 3; // after columns + EOF
+        let rows = self
+            .rows
+            .iter()
+            .enumerate()
+            .map(|(i, row)| ResultRow {
+                sequence_id: (first_row_seq + i) as u8,
+                values: row
+                    .iter()
+                    .map(|value| {
+                        value
+                            .as_ref()
+                            .map(|v| bytes::BytesMut::from(v.as_bytes()))
+                    })
+                    .collect(),
+            })
+            .collect();
+        (columns, rows)
+    }
+
+    /// Captures MySQL messages back into fixture form (values are rendered
+    /// as lossy UTF-8)
+    #[cfg(feature = "mysql")]
+    pub fn from_mysql(columns: &[ColumnDefinition], rows: &[ResultRow]) -> Self {
+        Self {
+            columns: columns
+                .iter()
+                .map(|c| String::from_utf8_lossy(&c.name).into_owned())
+                .collect(),
+            rows: rows
+                .iter()
+                .map(|row| {
+                    row.values
+                        .iter()
+                        .map(|value| {
+                            value
+                                .as_ref()
+                                .map(|v| String::from_utf8_lossy(v).into_owned())
+                        })
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixture_loads_from_yaml_and_json() {
+        let yaml = r#"
+columns: [email, city]
+rows:
+  - ["a@example.com", "Berlin"]
+  - [null, "Oslo"]
+"#;
+        let fixture = ResultSetFixture::from_yaml(yaml).unwrap();
+        assert_eq!(fixture.columns, vec!["email", "city"]);
+        assert_eq!(fixture.rows[1][0], None);
+
+        let json = serde_json::to_string(&fixture).unwrap();
+        assert_eq!(ResultSetFixture::from_json(&json).unwrap(), fixture);
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn test_postgres_round_trip() {
+        let fixture = ResultSetFixture {
+            columns: vec!["email".to_string()],
+            rows: vec![vec![Some("a@example.com".to_string())], vec![None]],
+        };
+        let (description, rows) = fixture.to_postgres();
+        assert_eq!(description.fields[0].name, "email".as_bytes());
+        assert_eq!(ResultSetFixture::from_postgres(&description, &rows), fixture);
+    }
+
+    #[cfg(feature = "mysql")]
+    #[test]
+    fn test_mysql_round_trip() {
+        let fixture = ResultSetFixture {
+            columns: vec!["email".to_string(), "city".to_string()],
+            rows: vec![vec![Some("a@example.com".to_string()), None]],
+        };
+        let (columns, rows) = fixture.to_mysql("users");
+        assert_eq!(columns[1].table, "users".as_bytes());
+        assert_eq!(ResultSetFixture::from_mysql(&columns, &rows), fixture);
+    }
+}
@@ -0,0 +1,165 @@
+//! Serde representations for raw wire bytes.
+//!
+//! The protocol structs carry values as raw bytes, which have no canonical
+//! text form. For serialization each byte field becomes a map holding the
+//! lossless base64 encoding next to a lossy UTF-8 preview for human readers:
+//!
+//! ```yaml
+//! name:
+//!   base64: ZW1haWw=
+//!   preview: email
+//! ```
+//!
+//! On input the preview is ignored, and a plain string is accepted as a
+//! shorthand for its UTF-8 bytes so fixtures stay hand-writable.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// The serialized form of a byte field
+#[derive(Serialize)]
+struct WireBytes<'a> {
+    base64: String,
+    preview: std::borrow::Cow<'a, str>,
+}
+
+impl<'a> WireBytes<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            base64: BASE64.encode(bytes),
+            preview: String::from_utf8_lossy(bytes),
+        }
+    }
+}
+
+/// Accepted input forms: the full map, or a bare string as UTF-8 shorthand
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum WireBytesInput {
+    Text(String),
+    Encoded {
+        base64: String,
+        #[serde(default)]
+        #[allow(dead_code)]
+        preview: Option<String>,
+    },
+}
+
+impl WireBytesInput {
+    fn into_bytes<E: serde::de::Error>(self) -> Result<Vec<u8>, E> {
+        match self {
+            WireBytesInput::Text(text) => Ok(text.into_bytes()),
+            WireBytesInput::Encoded { base64, .. } => BASE64
+                .decode(&base64)
+                .map_err(|e| E::custom(format!("invalid base64 in byte field: {}", e))),
+        }
+    }
+}
+
+/// `#[serde(with = ...)]` support for [`bytes::Bytes`] fields
+pub mod bytes {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &::bytes::Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+        WireBytes::new(value).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<::bytes::Bytes, D::Error> {
+        let input = WireBytesInput::deserialize(deserializer)?;
+        Ok(::bytes::Bytes::from(input.into_bytes::<D::Error>()?))
+    }
+}
+
+/// `#[serde(with = ...)]` support for [`bytes::BytesMut`] fields
+pub mod bytes_mut {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        value: &::bytes::BytesMut,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        WireBytes::new(value).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<::bytes::BytesMut, D::Error> {
+        let input = WireBytesInput::deserialize(deserializer)?;
+        Ok(::bytes::BytesMut::from(
+            input.into_bytes::<D::Error>()?.as_slice(),
+        ))
+    }
+}
+
+/// `#[serde(with = ...)]` support for the `Vec<Option<BytesMut>>` row-value
+/// shape shared by both protocols (`None` is a SQL NULL)
+pub mod row_values {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        values: &[Option<::bytes::BytesMut>],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(
+            values
+                .iter()
+                .map(|value| value.as_deref().map(WireBytes::new)),
+        )
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Option<::bytes::BytesMut>>, D::Error> {
+        let raw: Vec<Option<WireBytesInput>> = Vec::deserialize(deserializer)?;
+        raw.into_iter()
+            .map(|value| {
+                value
+                    .map(|input| {
+                        Ok(::bytes::BytesMut::from(
+                            input.into_bytes::<D::Error>()?.as_slice(),
+                        ))
+                    })
+                    .transpose()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::bytes::Bytes;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Wrapper {
+        #[serde(with = "super::bytes")]
+        value: Bytes,
+    }
+
+    #[test]
+    fn test_round_trip_preserves_non_utf8_bytes() {
+        let original = Wrapper {
+            value: Bytes::from_static(&[0xff, 0xfe, b'a']),
+        };
+        let yaml = serde_yaml::to_string(&original).unwrap();
+        let restored: Wrapper = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(restored, original);
+    }
+
+    #[test]
+    fn test_serialized_form_has_base64_and_preview() {
+        let wrapper = Wrapper {
+            value: Bytes::from_static(b"email"),
+        };
+        let json = serde_json::to_value(&wrapper).unwrap();
+        assert_eq!(json["value"]["base64"], "ZW1haWw=");
+        assert_eq!(json["value"]["preview"], "email");
+    }
+
+    #[test]
+    fn test_plain_string_accepted_as_shorthand() {
+        let wrapper: Wrapper = serde_yaml::from_str("value: email\n").unwrap();
+        assert_eq!(wrapper.value, Bytes::from_static(b"email"));
+    }
+}
@@ -1,4 +1,8 @@
-use regex::Regex;
+use std::ops::Range;
+
+use regex::{Regex, RegexSet};
+
+use crate::config::{CustomPatternConfig, Locale, PatternSeverity, Strategy};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum PiiType {
@@ -9,16 +13,178 @@ pub enum PiiType {
     IpAddress,
     DateOfBirth,
     Passport,
+    Iban,
+    /// A country-specific identifier enabled via `scanner.locales`,
+    /// carrying the country so reports can tell a Steuer-ID from a NINO.
+    /// US SSNs keep the long-standing [`Ssn`](Self::Ssn) variant.
+    NationalId(Locale),
+    /// A `scanner.custom_patterns` match, carrying the entry's name
+    Custom(String),
+}
+
+impl PiiType {
+    /// Detector names accepted in rule conditions, matching the lowercase
+    /// naming strategies use in config files
+    pub const NAMES: &'static [&'static str] = &[
+        "email",
+        "credit_card",
+        "ssn",
+        "phone",
+        "ip",
+        "dob",
+        "passport",
+        "iban",
+        "national_id_uk",
+        "national_id_de",
+    ];
+
+    /// The config-file name for a detector — for a custom pattern, the
+    /// name its `scanner.custom_patterns` entry was given
+    pub fn name(&self) -> &str {
+        match self {
+            PiiType::Email => "email",
+            PiiType::CreditCard => "credit_card",
+            PiiType::Ssn => "ssn",
+            PiiType::Phone => "phone",
+            PiiType::IpAddress => "ip",
+            PiiType::DateOfBirth => "dob",
+            PiiType::Passport => "passport",
+            PiiType::Iban => "iban",
+            PiiType::NationalId(Locale::Us) => "ssn",
+            PiiType::NationalId(Locale::Uk) => "national_id_uk",
+            PiiType::NationalId(Locale::De) => "national_id_de",
+            PiiType::Custom(name) => name,
+        }
+    }
+
+    /// The built-in detector for a config-file name, `None` for unknown
+    /// (including custom pattern) names
+    pub fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "email" => PiiType::Email,
+            "credit_card" => PiiType::CreditCard,
+            "ssn" => PiiType::Ssn,
+            "phone" => PiiType::Phone,
+            "ip" => PiiType::IpAddress,
+            "dob" => PiiType::DateOfBirth,
+            "passport" => PiiType::Passport,
+            "iban" => PiiType::Iban,
+            "national_id_uk" => PiiType::NationalId(Locale::Uk),
+            "national_id_de" => PiiType::NationalId(Locale::De),
+            _ => return None,
+        })
+    }
+}
+
+// The detector patterns, unanchored. `scan()` compiles them wrapped in
+// `^(?:...)$` so a whole value must match; `scan_all()` uses them as-is
+// with explicit boundary checks instead.
+
+/// Simple email shape
+const EMAIL_PATTERN: &str = r"(?i)[a-z0-9._%+-]+@[a-z0-9.-]+\.[a-z]{2,}";
+/// Credit card: 13-19 digits, optional dashes/spaces
+const CC_PATTERN: &str = r"(?:\d{4}[-\s]?){3}\d{4}";
+/// US SSN, dashed or undashed (never mixed, so zip+4 strings stay out);
+/// the caller filters the never-issued area numbers via the captures
+const SSN_PATTERN: &str = r"(?:(\d{3})-\d{2}-\d{4}|(\d{3})\d{6})";
+/// Phone: NANP-style 3-3-4 groupings with optional separators
+/// (+1-415-555-0133, (415) 555-0133, 415.555.0133, bare 10 digits), plus
+/// E.164 international: '+' and 8-15 digits. The digit counts keep
+/// 13-19-digit card numbers and unseparated timestamps out.
+const PHONE_PATTERN: &str =
+    r"(?:\+\d{1,3}[-.\s])?\(?\d{3}\)?[-.\s]?\d{3}[-.\s]?\d{4}|\+[1-9]\d{7,14}";
+/// Date of birth: YYYY-MM-DD, MM/DD/YYYY, DD/MM/YYYY, DD-MM-YYYY
+const DOB_PATTERN: &str = r"\d{4}[-/]\d{2}[-/]\d{2}|\d{2}[-/]\d{2}[-/]\d{4}";
+/// IBAN shape: country code, check digits, up to 30 BBAN chars; the
+/// caller checks the per-country length and mod-97 checksum
+const IBAN_PATTERN: &str = r"[A-Z]{2}\d{2}[A-Z0-9]{1,30}";
+/// German Steuer-ID: eleven digits, never starting with zero; the caller
+/// verifies the ISO 7064 MOD 11,10 check digit
+const STEUER_ID_PATTERN: &str = r"[1-9]\d{10}";
+/// UK National Insurance number, with or without the customary pair
+/// spacing. The letter class drops D, F, I, Q, U, and V, which HMRC
+/// never issues; the caller rejects the invalid prefixes and a second
+/// letter of O.
+const NINO_PATTERN: &str = r"[A-CEGHJ-PR-TW-Z]{2} ?\d{2} ?\d{2} ?\d{2} ?[A-D]";
+
+/// A compiled `scanner.custom_patterns` entry
+struct CustomPattern {
+    name: String,
+    /// Anchored to the whole value, for [`PiiScanner::scan`]
+    regex: Regex,
+    /// The pattern as written, for [`PiiScanner::scan_all`]
+    substring_regex: Regex,
+    strategy: Option<Strategy>,
+    severity: PatternSeverity,
+}
+
+/// Unanchored counterparts of the detectors for substring mode. Passport
+/// numbers are deliberately absent: `[A-Z]{1,2}\d{6,8}` inside prose is
+/// indistinguishable from order, flight, and ticket numbers. IPv6 is too:
+/// compressed forms are indistinguishable from timestamps and hex words
+/// without anchors, so substring mode detects dotted IPv4 only. The
+/// `scanner.locales` identifiers stay whole-value for the same reason —
+/// eleven bare digits or a NINO's letters-digits-letter shape inside
+/// prose could be any reference number — except US SSNs, which predate
+/// substring mode and keep their slot here (locale-gated).
+struct SubstringRegexes {
+    email: Regex,
+    cc: Regex,
+    ssn: Regex,
+    phone: Regex,
+    ipv4: Regex,
+    dob: Regex,
+    iban: Regex,
 }
 
+// Index of each built-in detector in [`PiiScanner::builtin_set`]; the
+// confirm steps in `scan_scored` run in this order, which is the
+// priority order ties report in.
+const EMAIL_IDX: usize = 0;
+const CC_IDX: usize = 1;
+const SSN_IDX: usize = 2;
+const IP_IDX: usize = 3;
+const DOB_IDX: usize = 4;
+const PHONE_IDX: usize = 5;
+const IBAN_IDX: usize = 6;
+const PASSPORT_IDX: usize = 7;
+
 pub struct PiiScanner {
-    email_regex: Regex,
-    cc_regex: Regex,
+    /// All built-in anchored patterns as one multi-pattern automaton: a
+    /// single pass over the value says which detectors could match, and
+    /// only those run their confirm step (captures, checksums, address
+    /// parsing). Indexed by the `*_IDX` constants.
+    builtin_set: RegexSet,
+    /// SSN keeps its own compiled regex because the confirm step needs
+    /// the capture groups to tell the dashed and bare forms apart
     ssn_regex: Regex,
-    phone_regex: Regex,
-    ip_regex: Regex,
-    dob_regex: Regex,
-    passport_regex: Regex,
+    /// Country-specific identifier patterns per `scanner.locales`: `None`
+    /// while the locale is off, so a disabled detector costs nothing
+    steuer_id_regex: Option<Regex>,
+    nino_regex: Option<Regex>,
+    /// The enabled locales; also gates the US-specific SSN detector
+    locales: Vec<Locale>,
+    /// Lowercased `scanner.dob_column_hints`: column-name fragments that
+    /// mark a date value as a birth date
+    dob_hints: Vec<String>,
+    /// Skip private, loopback, and link-local addresses, per
+    /// `scanner.ignore_private_ips`
+    ignore_private_ips: bool,
+    /// Unanchored detector variants for [`scan_all`](Self::scan_all)
+    substring: SubstringRegexes,
+    /// Operator-defined patterns from `scanner.custom_patterns`, checked
+    /// before the built-in detectors
+    custom_patterns: Vec<CustomPattern>,
+    /// Ruleset generation the custom patterns were compiled against, so
+    /// [`sync_custom_patterns`](Self::sync_custom_patterns) recompiles
+    /// once per reload rather than once per row
+    custom_generation: Option<u64>,
+    /// `masking.defaults` resolved to parsed detector types: the
+    /// operator's strategy overrides for heuristic detections
+    default_strategies: Vec<(PiiType, Strategy)>,
+    /// Ruleset generation the defaults were resolved against, mirroring
+    /// `custom_generation`
+    defaults_generation: Option<u64>,
 }
 
 impl Default for PiiScanner {
@@ -29,51 +195,585 @@ impl Default for PiiScanner {
 
 impl PiiScanner {
     pub fn new() -> Self {
+        let anchored = |pattern: &str| format!("^(?:{})$", pattern);
         Self {
-            // Simple email regex
-            email_regex: Regex::new(r"(?i)^[a-z0-9._%+-]+@[a-z0-9.-]+\.[a-z]{2,}$").unwrap(),
-            // Credit Card regex (13-19 digits, optional dashes/spaces)
-            cc_regex: Regex::new(r"^(?:\d{4}[-\s]?){3}\d{4}$").unwrap(),
-            // US SSN: XXX-XX-XXXX format
-            ssn_regex: Regex::new(r"^\d{3}-\d{2}-\d{4}$").unwrap(),
-            // Phone: Must have at least 10 digits total and include formatting
-            // Matches: +1-555-123-4567, (555) 123-4567, 555-123-4567, +44 20 7946 0958
-            phone_regex: Regex::new(r"^(?:\+\d{1,3}[-.\s])?\(?(\d{3})\)?[-.\s]?\d{3}[-.\s]?\d{4}$").unwrap(),
-            // IPv4 address
-            ip_regex: Regex::new(r"^(?:(?:25[0-5]|2[0-4][0-9]|[01]?[0-9][0-9]?)\.){3}(?:25[0-5]|2[0-4][0-9]|[01]?[0-9][0-9]?)$").unwrap(),
-            // Date of birth: YYYY-MM-DD, MM/DD/YYYY, DD/MM/YYYY, DD-MM-YYYY
-            dob_regex: Regex::new(r"^(?:\d{4}[-/]\d{2}[-/]\d{2}|\d{2}[-/]\d{2}[-/]\d{4})$").unwrap(),
-            // Passport: Basic pattern for common formats (alphanumeric, 6-9 chars)
-            passport_regex: Regex::new(r"^[A-Z]{1,2}\d{6,8}$").unwrap(),
+            // The array order must match the `*_IDX` constants. The IP
+            // entry is a cheap prefilter — the characters IPv4 and IPv6
+            // literals can contain, in their length range — and scan()
+            // parses the survivors with std::net rather than regexing
+            // IPv6's compressed and IPv4-mapped forms exhaustively. The
+            // passport entry covers common formats (alphanumeric, 6-9
+            // chars).
+            builtin_set: RegexSet::new([
+                anchored(EMAIL_PATTERN),
+                anchored(CC_PATTERN),
+                anchored(SSN_PATTERN),
+                r"^[0-9A-Fa-f.:]{2,45}$".to_string(),
+                anchored(DOB_PATTERN),
+                anchored(PHONE_PATTERN),
+                anchored(IBAN_PATTERN),
+                r"^[A-Z]{1,2}\d{6,8}$".to_string(),
+            ])
+            .unwrap(),
+            ssn_regex: Regex::new(&anchored(SSN_PATTERN)).unwrap(),
+            substring: SubstringRegexes {
+                email: Regex::new(EMAIL_PATTERN).unwrap(),
+                cc: Regex::new(CC_PATTERN).unwrap(),
+                ssn: Regex::new(SSN_PATTERN).unwrap(),
+                phone: Regex::new(&format!("(?:{})", PHONE_PATTERN)).unwrap(),
+                ipv4: Regex::new(r"\d{1,3}(?:\.\d{1,3}){3}").unwrap(),
+                dob: Regex::new(&format!("(?:{})", DOB_PATTERN)).unwrap(),
+                iban: Regex::new(IBAN_PATTERN).unwrap(),
+            },
+            steuer_id_regex: None,
+            nino_regex: None,
+            locales: crate::config::DEFAULT_LOCALES.to_vec(),
+            dob_hints: crate::config::default_dob_column_hints().to_vec(),
+            ignore_private_ips: false,
+            custom_patterns: Vec::new(),
+            custom_generation: None,
+            default_strategies: Vec::new(),
+            defaults_generation: None,
+        }
+    }
+
+    /// Applies `scanner.locales`, compiling only the listed countries'
+    /// identifier patterns. Cheap when nothing changed, so the
+    /// interceptors sync it per row like
+    /// [`set_ignore_private_ips`](Self::set_ignore_private_ips).
+    pub fn set_locales(&mut self, locales: &[Locale]) {
+        if self.locales == locales {
+            return;
+        }
+        let anchored = |pattern: &str| Regex::new(&format!("^(?:{})$", pattern)).unwrap();
+        self.steuer_id_regex = locales
+            .contains(&Locale::De)
+            .then(|| anchored(STEUER_ID_PATTERN));
+        self.nino_regex = locales.contains(&Locale::Uk).then(|| anchored(NINO_PATTERN));
+        self.locales = locales.to_vec();
+    }
+
+    /// Applies `scanner.dob_column_hints`, lowercased for matching
+    /// against column names; synced per row like
+    /// [`set_locales`](Self::set_locales), so only a changed list clones
+    pub fn set_dob_column_hints(&mut self, hints: &[String]) {
+        if self.dob_hints.len() == hints.len()
+            && self
+                .dob_hints
+                .iter()
+                .zip(hints)
+                .all(|(have, want)| have.eq_ignore_ascii_case(want))
+        {
+            return;
         }
+        self.dob_hints = hints.iter().map(|h| h.to_lowercase()).collect();
     }
 
+    /// Applies `scanner.ignore_private_ips`; the interceptors sync this
+    /// from config before scanning, so a reload takes effect per row
+    pub fn set_ignore_private_ips(&mut self, ignore: bool) {
+        self.ignore_private_ips = ignore;
+    }
+
+    /// Compiles `scanner.custom_patterns`. Each pattern is anchored to the
+    /// whole value, matching how the built-in detectors work; entries whose
+    /// regex does not compile are dropped, though validation rejects those
+    /// before a config is ever applied.
+    pub fn set_custom_patterns(&mut self, patterns: &[CustomPatternConfig]) {
+        self.custom_patterns = patterns
+            .iter()
+            .filter_map(|def| {
+                let regex = Regex::new(&format!("^(?:{})$", def.pattern)).ok()?;
+                let substring_regex = Regex::new(&def.pattern).ok()?;
+                Some(CustomPattern {
+                    name: def.name.clone(),
+                    regex,
+                    substring_regex,
+                    strategy: def.strategy.clone(),
+                    severity: def.severity,
+                })
+            })
+            .collect();
+    }
+
+    /// Generation-gated [`set_custom_patterns`](Self::set_custom_patterns)
+    /// for the row path: recompiles only when the ruleset generation moved,
+    /// so a reload takes effect without compiling regexes per row
+    pub fn sync_custom_patterns(&mut self, patterns: &[CustomPatternConfig], generation: u64) {
+        if self.custom_generation != Some(generation) {
+            self.set_custom_patterns(patterns);
+            self.custom_generation = Some(generation);
+        }
+    }
+
+    /// Applies `masking.defaults`: per-detector strategy overrides for the
+    /// heuristic path, resolved to their parsed types once so the row path
+    /// never re-parses names. Unknown names are dropped, though validation
+    /// rejects those before a config is ever applied.
+    pub fn set_default_strategies(
+        &mut self,
+        defaults: &std::collections::BTreeMap<String, Strategy>,
+    ) {
+        self.default_strategies = defaults
+            .iter()
+            .filter_map(|(name, strategy)| {
+                PiiType::parse(name).map(|pii_type| (pii_type, strategy.clone()))
+            })
+            .collect();
+    }
+
+    /// Generation-gated
+    /// [`set_default_strategies`](Self::set_default_strategies) for the row
+    /// path, so a reload takes effect without rebuilding the map per row
+    pub fn sync_default_strategies(
+        &mut self,
+        defaults: Option<&std::collections::BTreeMap<String, Strategy>>,
+        generation: u64,
+    ) {
+        if self.defaults_generation != Some(generation) {
+            match defaults {
+                Some(defaults) => self.set_default_strategies(defaults),
+                None => self.default_strategies.clear(),
+            }
+            self.defaults_generation = Some(generation);
+        }
+    }
+
+    /// The operator's strategy override for a detector, `None` when
+    /// `masking.defaults` does not name it
+    pub fn default_strategy(&self, pii_type: &PiiType) -> Option<&Strategy> {
+        self.default_strategies
+            .iter()
+            .find(|(candidate, _)| candidate == pii_type)
+            .map(|(_, strategy)| strategy)
+    }
+
+    /// The configured strategy of a custom pattern, `None` for unknown
+    /// names or entries that did not set one
+    pub fn custom_strategy(&self, name: &str) -> Option<&Strategy> {
+        self.custom_patterns
+            .iter()
+            .find(|p| p.name == name)
+            .and_then(|p| p.strategy.as_ref())
+    }
+
+    /// The configured severity of a custom pattern, `None` for unknown
+    /// names
+    pub fn custom_severity(&self, name: &str) -> Option<PatternSeverity> {
+        self.custom_patterns
+            .iter()
+            .find(|p| p.name == name)
+            .map(|p| p.severity)
+    }
+
+    /// The most confident whole-value detection regardless of threshold,
+    /// for callers without a column in hand (verification, detector
+    /// conditions, nested JSON and composite values)
     pub fn scan(&self, text: &str) -> Option<PiiType> {
-        // Check patterns in order of specificity
-        if self.email_regex.is_match(text) {
-            return Some(PiiType::Email);
+        self.scan_scored(text, None)
+            .into_iter()
+            .next()
+            .map(|(pii_type, _)| pii_type)
+    }
+
+    /// Every detector's verdict on a whole value, scored 0-1 and sorted
+    /// most confident first, so the caller can apply
+    /// `scanner.min_confidence` and log the also-rans for tuning.
+    ///
+    /// A score reflects how specific the matched shape is: separators and
+    /// a passing checksum (Luhn for cards, mod-97 for IBANs) push it up,
+    /// an unseparated digit run pulls it down, and a column name that
+    /// hints at the type adds a flat boost — a bare ten-digit number
+    /// scores 0.6 on its own but 0.85 in a column named `phone_number`.
+    pub fn scan_scored(&self, text: &str, column_hint: Option<&str>) -> Vec<(PiiType, f32)> {
+        let hint = column_hint.map(str::to_lowercase);
+        let hint_boost = |keywords: &[&str]| -> f32 {
+            match &hint {
+                Some(hint) if keywords.iter().any(|k| hint.contains(k)) => 0.25,
+                _ => 0.0,
+            }
+        };
+        let mut candidates: Vec<(PiiType, f32)> = Vec::new();
+        let mut add =
+            |pii_type: PiiType, confidence: f32| candidates.push((pii_type, confidence.min(1.0)));
+
+        // Operator-defined patterns outrank the built-ins: a company
+        // identifier that happens to look like a passport number should
+        // report under its own name. The operator wrote the pattern, so
+        // matches start near certainty.
+        for pattern in &self.custom_patterns {
+            if pattern.regex.is_match(text) {
+                add(
+                    PiiType::Custom(pattern.name.clone()),
+                    0.95 + hint_boost(&[pattern.name.as_str()]),
+                );
+            }
+        }
+        // Every built-in match contains an ASCII digit, an '@' (email),
+        // or a ':' (IPv6), so the plain words that dominate real rows
+        // skip the multi-pattern pass entirely
+        if !text
+            .bytes()
+            .any(|b| b.is_ascii_digit() || b == b'@' || b == b':')
+        {
+            candidates.sort_by(|(_, a), (_, b)| b.partial_cmp(a).expect("scores are finite"));
+            return candidates;
         }
-        if self.cc_regex.is_match(text) {
-            return Some(PiiType::CreditCard);
+        // One pass over the value; only flagged detectors confirm
+        let matched = self.builtin_set.matches(text);
+        if matched.matched(EMAIL_IDX) {
+            add(PiiType::Email, 0.95 + hint_boost(&["email", "mail"]));
         }
-        if self.ssn_regex.is_match(text) {
-            return Some(PiiType::Ssn);
+        if matched.matched(CC_IDX) {
+            let base = if Self::luhn_valid(text) { 0.9 } else { 0.55 };
+            add(
+                PiiType::CreditCard,
+                base + hint_boost(&["card", "cc", "pan"]),
+            );
         }
-        if self.ip_regex.is_match(text) {
-            return Some(PiiType::IpAddress);
+        if matched.matched(SSN_IDX)
+            && self.locales.contains(&Locale::Us)
+            && let Some(caps) = self.ssn_regex.captures(text)
+        {
+            let (area, base) = match caps.get(1) {
+                // Dashed is unmistakable; a bare nine-digit run could be
+                // any internal identifier
+                Some(area) => (area, 0.9),
+                None => (caps.get(2).expect("one alternative matched"), 0.6),
+            };
+            if Self::plausible_ssn_area(area.as_str()) {
+                add(PiiType::Ssn, base + hint_boost(&["ssn", "social"]));
+            }
         }
-        // Check date before phone to avoid false positives
-        if self.dob_regex.is_match(text) {
-            return Some(PiiType::DateOfBirth);
+        if let Some(regex) = &self.steuer_id_regex
+            && regex.is_match(text)
+            && Self::steuer_id_valid(text)
+        {
+            add(
+                PiiType::NationalId(Locale::De),
+                0.9 + hint_boost(&["steuer", "tax", "tin"]),
+            );
         }
-        if self.phone_regex.is_match(text) {
-            return Some(PiiType::Phone);
+        if let Some(regex) = &self.nino_regex
+            && regex.is_match(text)
+            && Self::nino_valid(text)
+        {
+            add(
+                PiiType::NationalId(Locale::Uk),
+                0.9 + hint_boost(&["nino", "insurance"]),
+            );
         }
-        if self.passport_regex.is_match(text) {
-            return Some(PiiType::Passport);
+        if matched.matched(IP_IDX)
+            && let Ok(addr) = text.parse::<std::net::IpAddr>()
+            && !(self.ignore_private_ips && Self::is_private_ip(&addr))
+        {
+            add(PiiType::IpAddress, 0.9 + hint_boost(&["ip", "addr", "host"]));
         }
-        None
+        // Dates are a common shape for plenty of non-birth columns, so
+        // only values inside a plausible birth range count at all, and
+        // the boost keywords come from `scanner.dob_column_hints`
+        if matched.matched(DOB_IDX) && Self::plausible_birth_date(text) {
+            let boost = match &hint {
+                Some(hint) if self.dob_hints.iter().any(|k| hint.contains(k.as_str())) => 0.25,
+                _ => 0.0,
+            };
+            add(PiiType::DateOfBirth, 0.65 + boost);
+        }
+        if matched.matched(PHONE_IDX) {
+            // Separators or a leading '+' are deliberate phone formatting;
+            // ten bare digits could just as well be an account number
+            let base = if text.chars().any(|c| !c.is_ascii_digit()) {
+                0.9
+            } else {
+                0.6
+            };
+            add(
+                PiiType::Phone,
+                base + hint_boost(&["phone", "mobile", "tel", "fax"]),
+            );
+        }
+        if matched.matched(IBAN_IDX) && Self::is_valid_iban(text) {
+            add(PiiType::Iban, 0.95 + hint_boost(&["iban", "account"]));
+        }
+        if matched.matched(PASSPORT_IDX) {
+            add(PiiType::Passport, 0.5 + hint_boost(&["passport"]));
+        }
+        // Stable, so equal scores keep detector priority order
+        candidates.sort_by(|(_, a), (_, b)| b.partial_cmp(a).expect("scores are finite"));
+        candidates
+    }
+
+    /// Luhn checksum over the digits of a card-shaped value; separators
+    /// are skipped, anything else fails
+    fn luhn_valid(text: &str) -> bool {
+        let mut sum = 0u32;
+        let mut double = false;
+        for c in text.chars().rev() {
+            let digit = match c {
+                '0'..='9' => c as u32 - '0' as u32,
+                '-' | ' ' => continue,
+                _ => return false,
+            };
+            sum += if double {
+                let doubled = digit * 2;
+                if doubled > 9 { doubled - 9 } else { doubled }
+            } else {
+                digit
+            };
+            double = !double;
+        }
+        sum.is_multiple_of(10)
+    }
+
+    /// ISO 7064 MOD 11,10 check digit of a German Steuer-ID: the first
+    /// ten digits feed the iteration, the eleventh must equal the result
+    fn steuer_id_valid(text: &str) -> bool {
+        let digits: Vec<u32> = text.chars().filter_map(|c| c.to_digit(10)).collect();
+        if digits.len() != 11 {
+            return false;
+        }
+        let mut product = 10u32;
+        for &digit in &digits[..10] {
+            let sum = match (digit + product) % 10 {
+                0 => 10,
+                sum => sum,
+            };
+            product = (sum * 2) % 11;
+        }
+        digits[10] == (11 - product) % 10
     }
+
+    /// Whether a NINO-shaped candidate uses an issued prefix: HMRC never
+    /// issues a second letter of O nor the administrative prefixes below
+    fn nino_valid(text: &str) -> bool {
+        let mut letters = text.chars().filter(|c| c.is_ascii_alphabetic());
+        let prefix = [letters.next(), letters.next()];
+        let [Some(first), Some(second)] = prefix else {
+            return false;
+        };
+        second != 'O'
+            && !matches!(
+                (first, second),
+                ('B', 'G') | ('G', 'B') | ('K', 'N') | ('N', 'K') | ('N', 'T') | ('T', 'N') | ('Z', 'Z')
+            )
+    }
+
+    /// Finds every PII occurrence inside free text, with byte offsets, for
+    /// `scanner.scan_substrings` mode. Matches run in the same priority
+    /// order as [`scan`](Self::scan) (custom patterns first), overlapping
+    /// candidates lose to the earlier detector, and every match must stand
+    /// on its own token boundary, so `notanemail@x.comfoo` is flagged
+    /// whole or not at all rather than partially. The spans come back
+    /// sorted by start offset.
+    pub fn scan_all(&self, text: &str) -> Vec<(PiiType, Range<usize>)> {
+        let mut found: Vec<(PiiType, Range<usize>)> = Vec::new();
+        let claim = |found: &mut Vec<(PiiType, Range<usize>)>,
+                         pii_type: PiiType,
+                         range: Range<usize>| {
+            if Self::substring_boundary_ok(text, &range)
+                && !found
+                    .iter()
+                    .any(|(_, c)| range.start < c.end && c.start < range.end)
+            {
+                found.push((pii_type, range));
+            }
+        };
+        for pattern in &self.custom_patterns {
+            for m in pattern.substring_regex.find_iter(text) {
+                claim(&mut found, PiiType::Custom(pattern.name.clone()), m.range());
+            }
+        }
+        for m in self.substring.email.find_iter(text) {
+            claim(&mut found, PiiType::Email, m.range());
+        }
+        for m in self.substring.cc.find_iter(text) {
+            claim(&mut found, PiiType::CreditCard, m.range());
+        }
+        if self.locales.contains(&Locale::Us) {
+            for caps in self.substring.ssn.captures_iter(text) {
+                let area = caps
+                    .get(1)
+                    .or_else(|| caps.get(2))
+                    .expect("one alternative matched");
+                if Self::plausible_ssn_area(area.as_str()) {
+                    let m = caps.get(0).expect("whole match");
+                    claim(&mut found, PiiType::Ssn, m.range());
+                }
+            }
+        }
+        for m in self.substring.ipv4.find_iter(text) {
+            if let Ok(addr) = m.as_str().parse::<std::net::IpAddr>()
+                && !(self.ignore_private_ips && Self::is_private_ip(&addr))
+            {
+                claim(&mut found, PiiType::IpAddress, m.range());
+            }
+        }
+        for m in self.substring.dob.find_iter(text) {
+            if Self::plausible_birth_date(m.as_str()) {
+                claim(&mut found, PiiType::DateOfBirth, m.range());
+            }
+        }
+        for m in self.substring.phone.find_iter(text) {
+            claim(&mut found, PiiType::Phone, m.range());
+        }
+        for m in self.substring.iban.find_iter(text) {
+            if Self::is_valid_iban(m.as_str()) {
+                claim(&mut found, PiiType::Iban, m.range());
+            }
+        }
+        found.sort_by_key(|(_, range)| range.start);
+        found
+    }
+
+    /// Whether a substring match stands on its own rather than continuing
+    /// a longer token: the characters around it must not be alphanumeric,
+    /// and a digit edge must not extend a larger dotted or dashed number —
+    /// so the first four octets of `192.168.1.1.1` do not match, while a
+    /// number at the end of a sentence still does.
+    fn substring_boundary_ok(text: &str, range: &Range<usize>) -> bool {
+        let edge_ok = |edge: Option<char>, adjacent: Option<char>, beyond: Option<char>| {
+            let (Some(edge), Some(adjacent)) = (edge, adjacent) else {
+                return true;
+            };
+            if adjacent.is_ascii_alphanumeric() {
+                return false;
+            }
+            !(edge.is_ascii_digit()
+                && matches!(adjacent, '.' | '-' | '/')
+                && beyond.is_some_and(|c| c.is_ascii_digit()))
+        };
+        let mut preceding = text[..range.start].chars().rev();
+        let mut following = text[range.end..].chars();
+        edge_ok(
+            text[range.clone()].chars().next(),
+            preceding.next(),
+            preceding.next(),
+        ) && edge_ok(
+            text[range.clone()].chars().next_back(),
+            following.next(),
+            following.next(),
+        )
+    }
+
+    /// Whether an IBAN-shaped candidate really is one: the length must
+    /// match the country's registered length (or at least span the range
+    /// real IBANs use, for countries not in the table) and the mod-97
+    /// checksum must hold, so random uppercase strings do not match.
+    fn is_valid_iban(text: &str) -> bool {
+        let length_ok = match iban_length(&text[..2]) {
+            Some(len) => text.len() == len,
+            None => (15..=34).contains(&text.len()),
+        };
+        if !length_ok {
+            return false;
+        }
+        // ISO 13616: move the country code and check digits to the end;
+        // a valid IBAN leaves remainder 1
+        let rearranged = format!("{}{}", &text[4..], &text[..4]);
+        iban_mod97(&rearranged) == Some(1)
+    }
+
+    /// Whether the area number (the first three digits) could belong to an
+    /// issued SSN: 000, 666, and 900-999 never are, which keeps arbitrary
+    /// nine-digit identifiers from constantly false-positiving.
+    fn plausible_ssn_area(area: &str) -> bool {
+        area != "000" && area != "666" && !area.starts_with('9')
+    }
+
+    /// Whether a date-shaped value could be someone's birth date: the
+    /// year sits between 1900 and today, and the other two fields read as
+    /// a real day and month under either the day-first or month-first
+    /// convention. Expiry dates, far-future timestamps, and `99-99`
+    /// lookalikes drop out here before any column hint is consulted.
+    fn plausible_birth_date(text: &str) -> bool {
+        use chrono::Datelike;
+        let mut fields = text.split(['-', '/']);
+        let (Some(first), Some(second), Some(third)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            return false;
+        };
+        let (year, day_month) = if first.len() == 4 {
+            (first, (second, third))
+        } else {
+            (third, (first, second))
+        };
+        let (Ok(year), Ok(x), Ok(y)) = (
+            year.parse::<i32>(),
+            day_month.0.parse::<u32>(),
+            day_month.1.parse::<u32>(),
+        ) else {
+            return false;
+        };
+        let day_month_ok = |m: u32, d: u32| (1..=12).contains(&m) && (1..=31).contains(&d);
+        (1900..=chrono::Utc::now().year()).contains(&year) && (day_month_ok(x, y) || day_month_ok(y, x))
+    }
+
+    /// The ranges `ignore_private_ips` treats as non-PII: private,
+    /// loopback, and link-local IPv4, and loopback, unique-local, and
+    /// link-local IPv6
+    fn is_private_ip(addr: &std::net::IpAddr) -> bool {
+        match addr {
+            std::net::IpAddr::V4(v4) => {
+                v4.is_private() || v4.is_loopback() || v4.is_link_local()
+            }
+            std::net::IpAddr::V6(v6) => {
+                v6.is_loopback()
+                    || (v6.segments()[0] & 0xfe00) == 0xfc00
+                    || (v6.segments()[0] & 0xffc0) == 0xfe80
+            }
+        }
+    }
+}
+
+/// Registered IBAN lengths for the major countries; candidates with other
+/// country codes fall back to the checksum alone. Shared with the `iban`
+/// fake strategy, which draws its country from this table.
+pub(crate) const IBAN_LENGTHS: &[(&str, usize)] = &[
+    ("AT", 20),
+    ("BE", 16),
+    ("CH", 21),
+    ("DE", 22),
+    ("DK", 18),
+    ("ES", 24),
+    ("FI", 18),
+    ("FR", 27),
+    ("GB", 22),
+    ("IE", 22),
+    ("IT", 27),
+    ("LU", 20),
+    ("NL", 18),
+    ("NO", 15),
+    ("PL", 28),
+    ("PT", 25),
+    ("SE", 24),
+];
+
+/// The registered length for a country code, `None` when not in the table
+pub(crate) fn iban_length(country: &str) -> Option<usize> {
+    IBAN_LENGTHS
+        .iter()
+        .find(|(code, _)| *code == country)
+        .map(|(_, len)| *len)
+}
+
+/// The mod-97 remainder of an IBAN-alphabet string, with letters mapping
+/// to 10-35 per ISO 13616; `None` if any other character appears.
+pub(crate) fn iban_mod97(text: &str) -> Option<u32> {
+    let mut rem: u32 = 0;
+    for b in text.bytes() {
+        let value = match b {
+            b'0'..=b'9' => u32::from(b - b'0'),
+            b'A'..=b'Z' => u32::from(b - b'A') + 10,
+            _ => return None,
+        };
+        rem = if value < 10 {
+            (rem * 10 + value) % 97
+        } else {
+            (rem * 100 + value) % 97
+        };
+    }
+    Some(rem)
 }
 
 #[cfg(test)]
@@ -122,12 +822,22 @@ mod tests {
     fn test_ssn_detection() {
         let scanner = PiiScanner::new();
 
-        // Valid SSNs
+        // Valid SSNs, dashed and undashed
         assert_eq!(scanner.scan("123-45-6789"), Some(PiiType::Ssn));
-        assert_eq!(scanner.scan("000-00-0000"), Some(PiiType::Ssn));
+        assert_eq!(scanner.scan("123456789"), Some(PiiType::Ssn));
+        assert_eq!(scanner.scan("856-45-6789"), Some(PiiType::Ssn));
 
-        // Invalid SSNs
-        assert_eq!(scanner.scan("123456789"), None);
+        // Never-issued area numbers: 000, 666, 900-999
+        assert_eq!(scanner.scan("000-00-0000"), None);
+        assert_eq!(scanner.scan("666-45-6789"), None);
+        assert_eq!(scanner.scan("900-12-3456"), None);
+        assert_eq!(scanner.scan("987654321"), None);
+
+        // Phone numbers keep their own detector
+        assert_eq!(scanner.scan("555-123-4567"), Some(PiiType::Phone));
+
+        // Zip+4 and malformed groupings
+        assert_eq!(scanner.scan("12345-6789"), None);
         assert_eq!(scanner.scan("123-456-789"), None);
         assert_eq!(scanner.scan("12-345-6789"), None);
     }
@@ -141,11 +851,22 @@ mod tests {
         assert_eq!(scanner.scan("555-123-4567"), Some(PiiType::Phone));
         assert_eq!(scanner.scan("(555) 123-4567"), Some(PiiType::Phone));
         assert_eq!(scanner.scan("555.123.4567"), Some(PiiType::Phone));
+        assert_eq!(scanner.scan("4155550133"), Some(PiiType::Phone));
+
+        // E.164 international
+        assert_eq!(scanner.scan("+14155550133"), Some(PiiType::Phone));
+        assert_eq!(scanner.scan("+442079460958"), Some(PiiType::Phone));
 
         // Invalid phone numbers
         assert_eq!(scanner.scan("phone"), None);
         assert_eq!(scanner.scan("12"), None);
         assert_eq!(scanner.scan("12345"), None);
+        assert_eq!(scanner.scan("+0123456789"), None); // E.164 never starts with 0
+        assert_eq!(scanner.scan("+1234567890123456"), None); // over 15 digits
+
+        // Card numbers and timestamps stay with their own detectors
+        assert_eq!(scanner.scan("1234567890123456"), Some(PiiType::CreditCard));
+        assert_eq!(scanner.scan("2024-01-15 10:30:00"), None);
     }
 
     #[test]
@@ -158,10 +879,44 @@ mod tests {
         assert_eq!(scanner.scan("255.255.255.255"), Some(PiiType::IpAddress));
         assert_eq!(scanner.scan("0.0.0.0"), Some(PiiType::IpAddress));
 
+        // IPv6, including compression and IPv4-mapped forms
+        assert_eq!(scanner.scan("2001:db8::1"), Some(PiiType::IpAddress));
+        assert_eq!(scanner.scan("::1"), Some(PiiType::IpAddress));
+        assert_eq!(
+            scanner.scan("fe80::1ff:fe23:4567:890a"),
+            Some(PiiType::IpAddress)
+        );
+        assert_eq!(
+            scanner.scan("::ffff:192.0.2.128"),
+            Some(PiiType::IpAddress)
+        );
+
         // Invalid IP addresses
         assert_eq!(scanner.scan("256.1.1.1"), None);
         assert_eq!(scanner.scan("192.168.1"), None);
         assert_eq!(scanner.scan("192.168.1.1.1"), None);
+        assert_eq!(scanner.scan("2001:db8::g"), None);
+    }
+
+    #[test]
+    fn test_ignore_private_ips() {
+        let mut scanner = PiiScanner::new();
+
+        // Default: private and loopback addresses are still detections
+        assert_eq!(scanner.scan("127.0.0.1"), Some(PiiType::IpAddress));
+        assert_eq!(scanner.scan("192.168.1.1"), Some(PiiType::IpAddress));
+
+        scanner.set_ignore_private_ips(true);
+        assert_eq!(scanner.scan("127.0.0.1"), None);
+        assert_eq!(scanner.scan("10.0.0.1"), None);
+        assert_eq!(scanner.scan("192.168.1.1"), None);
+        assert_eq!(scanner.scan("169.254.0.5"), None);
+        assert_eq!(scanner.scan("::1"), None);
+        assert_eq!(scanner.scan("fe80::1"), None);
+        assert_eq!(scanner.scan("fd12:3456::1"), None);
+        // Public addresses still match
+        assert_eq!(scanner.scan("8.8.8.8"), Some(PiiType::IpAddress));
+        assert_eq!(scanner.scan("2001:db8::1"), Some(PiiType::IpAddress));
     }
 
     #[test]
@@ -179,6 +934,31 @@ mod tests {
         assert_eq!(scanner.scan("Jan 15, 1990"), None);
     }
 
+    #[test]
+    fn test_dob_birth_range_and_hints() {
+        let mut scanner = PiiScanner::new();
+
+        // Only dates in a plausible birth range qualify at all
+        assert_eq!(scanner.scan("1985-06-15"), Some(PiiType::DateOfBirth));
+        assert_eq!(scanner.scan("2031-01-15"), None);
+        assert_eq!(scanner.scan("1850-01-15"), None);
+        assert_eq!(scanner.scan("2020-99-99"), None);
+
+        // The column name decides whether a birth-range date clears the
+        // default threshold
+        let hinted = scanner.scan_scored("1985-06-15", Some("birth_date"));
+        assert!(hinted[0].1 >= crate::config::DEFAULT_MIN_CONFIDENCE);
+        let unhinted = scanner.scan_scored("1985-06-15", Some("created_at"));
+        assert!(unhinted[0].1 < crate::config::DEFAULT_MIN_CONFIDENCE);
+
+        // A custom hint list replaces the built-in fragments
+        scanner.set_dob_column_hints(&["geburtsdatum".to_string()]);
+        let hinted = scanner.scan_scored("1985-06-15", Some("GeburtsDatum"));
+        assert!(hinted[0].1 >= crate::config::DEFAULT_MIN_CONFIDENCE);
+        let old = scanner.scan_scored("1985-06-15", Some("birth_date"));
+        assert!(old[0].1 < crate::config::DEFAULT_MIN_CONFIDENCE);
+    }
+
     #[test]
     fn test_passport_detection() {
         let scanner = PiiScanner::new();
@@ -192,6 +972,209 @@ mod tests {
         assert_eq!(scanner.scan("12345678"), None); // no letter prefix
     }
 
+    #[test]
+    fn test_iban_detection() {
+        let scanner = PiiScanner::new();
+
+        // Valid IBANs across countries, including a letter-bearing BBAN
+        assert_eq!(
+            scanner.scan("DE89370400440532013000"),
+            Some(PiiType::Iban)
+        );
+        assert_eq!(
+            scanner.scan("GB29NWBK60161331926819"),
+            Some(PiiType::Iban)
+        );
+        assert_eq!(
+            scanner.scan("FR1420041010050500013M02606"),
+            Some(PiiType::Iban)
+        );
+
+        // Right shape, broken checksum
+        assert_eq!(scanner.scan("DE89370400440532013001"), None);
+        // Right checksum country, wrong length for DE
+        assert_eq!(scanner.scan("DE8937040044053201300"), None);
+        // Random uppercase alphanumerics
+        assert_eq!(scanner.scan("AB12CDEFGHIJKLMNOPQR"), None);
+    }
+
+    #[test]
+    fn test_locale_national_ids() {
+        let mut scanner = PiiScanner::new();
+        // US-only by default: the scanner behaves as it always has
+        assert_eq!(scanner.scan("123-45-6789"), Some(PiiType::Ssn));
+        assert_eq!(scanner.scan("12345678903"), None);
+        assert_eq!(scanner.scan("AB123456C"), None);
+
+        scanner.set_locales(&[Locale::De, Locale::Uk]);
+        // SSN detection is a US pattern, so it goes with the locale
+        assert_eq!(scanner.scan("123-45-6789"), None);
+
+        // Steuer-ID: eleven digits whose ISO 7064 check digit holds
+        assert_eq!(
+            scanner.scan("12345678903"),
+            Some(PiiType::NationalId(Locale::De))
+        );
+        assert_eq!(scanner.scan("12345678904"), None);
+        assert_eq!(scanner.scan("02345678903"), None);
+
+        // NINO, compact or with the customary pair spacing
+        assert_eq!(
+            scanner.scan("AB123456C"),
+            Some(PiiType::NationalId(Locale::Uk))
+        );
+        assert_eq!(
+            scanner.scan("AB 12 34 56 C"),
+            Some(PiiType::NationalId(Locale::Uk))
+        );
+        // Never-issued prefixes and a second letter of O stay out
+        assert_eq!(scanner.scan("BG123456C"), None);
+        assert_eq!(scanner.scan("AO123456C"), None);
+        assert_eq!(scanner.scan("QQ123456C"), None);
+
+        // The country travels in the name rule conditions use
+        assert_eq!(PiiType::NationalId(Locale::De).name(), "national_id_de");
+        assert_eq!(
+            PiiType::parse("national_id_uk"),
+            Some(PiiType::NationalId(Locale::Uk))
+        );
+    }
+
+    #[test]
+    fn test_custom_pattern_detection() {
+        let mut scanner = PiiScanner::new();
+        assert_eq!(scanner.scan("MRN-12345678"), None);
+
+        scanner.set_custom_patterns(&[CustomPatternConfig {
+            name: "mrn".to_string(),
+            pattern: r"MRN-\d{8}".to_string(),
+            strategy: None,
+            severity: PatternSeverity::High,
+        }]);
+        assert_eq!(
+            scanner.scan("MRN-12345678"),
+            Some(PiiType::Custom("mrn".to_string()))
+        );
+        // The pattern must cover the whole value, like the built-ins
+        assert_eq!(scanner.scan("MRN-1234"), None);
+        assert_eq!(scanner.scan("note: MRN-12345678 admitted"), None);
+        // Built-in detectors still run after the custom ones
+        assert_eq!(scanner.scan("test@example.com"), Some(PiiType::Email));
+
+        assert_eq!(scanner.custom_severity("mrn"), Some(PatternSeverity::High));
+        assert_eq!(scanner.custom_strategy("mrn"), None);
+        assert_eq!(scanner.custom_severity("unknown"), None);
+    }
+
+    #[test]
+    fn test_custom_pattern_outranks_builtins() {
+        let mut scanner = PiiScanner::new();
+        // Passport-shaped, but the operator says these are ticket numbers
+        scanner.set_custom_patterns(&[CustomPatternConfig {
+            name: "ticket".to_string(),
+            pattern: r"AB\d{7}".to_string(),
+            strategy: Some(Strategy::Redact),
+            severity: PatternSeverity::default(),
+        }]);
+        assert_eq!(
+            scanner.scan("AB1234567"),
+            Some(PiiType::Custom("ticket".to_string()))
+        );
+        assert_eq!(scanner.custom_strategy("ticket"), Some(&Strategy::Redact));
+    }
+
+    #[test]
+    fn test_scan_all_substrings() {
+        let scanner = PiiScanner::new();
+
+        let text = "customer said email is bob@example.com call back at 415-555-0133";
+        let found = scanner.scan_all(text);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].0, PiiType::Email);
+        assert_eq!(&text[found[0].1.clone()], "bob@example.com");
+        assert_eq!(found[1].0, PiiType::Phone);
+        assert_eq!(&text[found[1].1.clone()], "415-555-0133");
+
+        // The anchored whole-value scan stays as strict as before
+        assert_eq!(scanner.scan(text), None);
+
+        assert!(scanner.scan_all("plain note with no identifiers").is_empty());
+    }
+
+    #[test]
+    fn test_scan_all_custom_patterns() {
+        let mut scanner = PiiScanner::new();
+        scanner.set_custom_patterns(&[CustomPatternConfig {
+            name: "mrn".to_string(),
+            pattern: r"MRN-\d{8}".to_string(),
+            strategy: None,
+            severity: PatternSeverity::default(),
+        }]);
+        let text = "note: MRN-12345678 admitted";
+        let found = scanner.scan_all(text);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, PiiType::Custom("mrn".to_string()));
+        assert_eq!(&text[found[0].1.clone()], "MRN-12345678");
+    }
+
+    #[test]
+    fn test_scan_all_boundaries() {
+        let scanner = PiiScanner::new();
+
+        // A match never stops partway through a token: the greedy match
+        // either covers it whole or the trailing word characters kill it
+        let text = "see notanemail@x.comfoo here";
+        let found = scanner.scan_all(text);
+        assert_eq!(found.len(), 1);
+        assert_eq!(&text[found[0].1.clone()], "notanemail@x.comfoo");
+        assert!(scanner.scan_all("id bob@example.com123 x").is_empty());
+
+        // A digit edge must not continue a larger dotted number, but a
+        // sentence-ending period is fine
+        assert!(scanner.scan_all("peer 192.168.1.1.1 logged").is_empty());
+        let text = "ref 123-45-6789.";
+        let found = scanner.scan_all(text);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, PiiType::Ssn);
+        assert_eq!(&text[found[0].1.clone()], "123-45-6789");
+
+        // Digit runs longer than any detector stay unflagged
+        assert!(scanner.scan_all("id 85612345678 x").is_empty());
+    }
+
+    #[test]
+    fn test_scan_scored_column_hints() {
+        let mut scanner = PiiScanner::new();
+
+        // Ten bare digits are ambiguous on their own, but a phone-ish
+        // column name pushes them over the default threshold
+        let unhinted = scanner.scan_scored("4155550133", None);
+        assert_eq!(unhinted[0].0, PiiType::Phone);
+        assert!(unhinted[0].1 < crate::config::DEFAULT_MIN_CONFIDENCE);
+        let hinted = scanner.scan_scored("4155550133", Some("phone_number"));
+        assert_eq!(hinted[0].0, PiiType::Phone);
+        assert!(hinted[0].1 >= crate::config::DEFAULT_MIN_CONFIDENCE);
+
+        // A passing Luhn checksum separates a real card from a lookalike
+        let valid = scanner.scan_scored("4111-1111-1111-1111", None);
+        assert_eq!(valid[0], (PiiType::CreditCard, 0.9));
+        let invalid = scanner.scan_scored("4532-1234-5678-9012", None);
+        assert_eq!(invalid[0], (PiiType::CreditCard, 0.55));
+
+        // Every matching detector reports, sorted most confident first
+        scanner.set_custom_patterns(&[CustomPatternConfig {
+            name: "ticket".to_string(),
+            pattern: r"AB\d{7}".to_string(),
+            strategy: None,
+            severity: PatternSeverity::default(),
+        }]);
+        let scored = scanner.scan_scored("AB1234567", None);
+        assert_eq!(scored[0], (PiiType::Custom("ticket".to_string()), 0.95));
+        assert_eq!(scored[1], (PiiType::Passport, 0.5));
+
+        assert!(scanner.scan_scored("hello", Some("email")).is_empty());
+    }
+
     #[test]
     fn test_non_pii_data() {
         let scanner = PiiScanner::new();
@@ -0,0 +1,454 @@
+//! Best-effort SQL column resolution for rule binding.
+//!
+//! Rule matching keys off the column name in the result description, so
+//! `SELECT email AS contact FROM users` surfaces a field named "contact" and
+//! would silently bypass an `email` rule. This module parses SELECT
+//! statements on their way upstream and maps each output ordinal back to the
+//! underlying table and column where that is statically determinable; the
+//! interceptor prefers that mapping over the display name when binding
+//! rules. DML with a RETURNING clause resolves the same way, since it
+//! produces a result set just like a SELECT. Anything the parser cannot see
+//! through — unparseable statements, `SELECT *`, opaque subqueries —
+//! degrades to the display-name behavior.
+
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+use std::sync::Arc;
+
+use sqlparser::ast::{
+    Expr, FromTable, Query, Select, SelectItem, SetExpr, Statement, TableFactor, TableWithJoins,
+    visit_expressions,
+};
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::Parser;
+
+/// Where the value of one output column comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColumnOrigin {
+    /// Statically resolved to an underlying column
+    Column {
+        table: Option<String>,
+        column: String,
+    },
+    /// An expression computed over the given underlying columns
+    Expression {
+        sources: Vec<(Option<String>, String)>,
+    },
+    /// Not statically determinable
+    Unknown,
+}
+
+/// One output column of a resolved SELECT
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputColumn {
+    /// Display name (alias or column name), when known
+    pub name: Option<String>,
+    pub origin: ColumnOrigin,
+}
+
+/// Cap on cached parse results; the cache is cleared when it fills up
+const CACHE_CAPACITY: usize = 256;
+
+/// Resolves SELECT statements to per-ordinal column origins, caching parse
+/// results per normalized statement (parse failures are cached too).
+#[derive(Default)]
+pub struct QueryResolver {
+    cache: HashMap<String, Option<Arc<Vec<OutputColumn>>>>,
+}
+
+impl QueryResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolves the output columns of `sql`, or `None` when it is not a
+    /// SELECT or RETURNING statement the resolver can see through
+    pub fn resolve(&mut self, sql: &str) -> Option<Arc<Vec<OutputColumn>>> {
+        let key = normalize(sql);
+        if let Some(cached) = self.cache.get(&key) {
+            return cached.clone();
+        }
+        let resolved = resolve_statement(sql).map(Arc::new);
+        if self.cache.len() >= CACHE_CAPACITY {
+            self.cache.clear();
+        }
+        self.cache.insert(key, resolved.clone());
+        resolved
+    }
+}
+
+/// Collapses whitespace so formatting variants share a cache entry
+fn normalize(sql: &str) -> String {
+    sql.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn resolve_statement(sql: &str) -> Option<Vec<OutputColumn>> {
+    let statements = Parser::parse_sql(&GenericDialect {}, sql).ok()?;
+    match statements.as_slice() {
+        [Statement::Query(query)] => resolve_query(query, &HashMap::new()),
+        // DML with RETURNING produces a result set like a SELECT over the
+        // statement's target table
+        [Statement::Insert(insert)] => {
+            let table = insert.table_name.0.last()?.value.clone();
+            let visible = insert
+                .table_alias
+                .as_ref()
+                .map(|a| a.value.clone())
+                .unwrap_or_else(|| table.clone());
+            resolve_projection(
+                insert.returning.as_deref()?,
+                &[(visible, Relation::Table(table))],
+            )
+        }
+        [Statement::Update {
+            table,
+            from,
+            returning,
+            ..
+        }] => {
+            let ctes = HashMap::new();
+            let mut relations = Vec::new();
+            collect_relations(table, &ctes, &mut relations);
+            if let Some(from) = from {
+                collect_relations(from, &ctes, &mut relations);
+            }
+            resolve_projection(returning.as_deref()?, &relations)
+        }
+        [Statement::Delete(delete)] => {
+            let ctes = HashMap::new();
+            let mut relations = Vec::new();
+            let (FromTable::WithFromKeyword(tables) | FromTable::WithoutKeyword(tables)) =
+                &delete.from;
+            for table_with_joins in tables {
+                collect_relations(table_with_joins, &ctes, &mut relations);
+            }
+            resolve_projection(delete.returning.as_deref()?, &relations)
+        }
+        _ => None,
+    }
+}
+
+/// A relation visible in a FROM clause
+enum Relation {
+    /// A base table, after alias resolution
+    Table(String),
+    /// A CTE or derived table whose output columns were resolved
+    Resolved(Vec<OutputColumn>),
+    /// Something the resolver cannot see through
+    Opaque,
+}
+
+fn resolve_query(
+    query: &Query,
+    outer_ctes: &HashMap<String, Vec<OutputColumn>>,
+) -> Option<Vec<OutputColumn>> {
+    let mut ctes = outer_ctes.clone();
+    if let Some(with) = &query.with {
+        for cte in &with.cte_tables {
+            let mut outputs = resolve_query(&cte.query, &ctes)?;
+            // A column list on the CTE renames its outputs
+            for (output, ident) in outputs.iter_mut().zip(&cte.alias.columns) {
+                output.name = Some(ident.value.clone());
+            }
+            ctes.insert(cte.alias.name.value.clone(), outputs);
+        }
+    }
+    match query.body.as_ref() {
+        SetExpr::Select(select) => resolve_select(select, &ctes),
+        _ => None,
+    }
+}
+
+fn resolve_select(
+    select: &Select,
+    ctes: &HashMap<String, Vec<OutputColumn>>,
+) -> Option<Vec<OutputColumn>> {
+    let mut relations = Vec::new();
+    for table_with_joins in &select.from {
+        collect_relations(table_with_joins, ctes, &mut relations);
+    }
+    resolve_projection(&select.projection, &relations)
+}
+
+/// Resolves a projection (SELECT items or a RETURNING list) against the
+/// visible relations
+fn resolve_projection(
+    items: &[SelectItem],
+    relations: &[(String, Relation)],
+) -> Option<Vec<OutputColumn>> {
+    let mut outputs = Vec::with_capacity(items.len());
+    for item in items {
+        match item {
+            SelectItem::UnnamedExpr(expr) => outputs.push(OutputColumn {
+                name: display_name(expr),
+                origin: expr_origin(expr, relations),
+            }),
+            SelectItem::ExprWithAlias { expr, alias } => outputs.push(OutputColumn {
+                name: Some(alias.value.clone()),
+                origin: expr_origin(expr, relations),
+            }),
+            // `SELECT *` breaks ordinal alignment with anything resolvable
+            // statically; let the display names handle the whole statement
+            SelectItem::Wildcard(_) | SelectItem::QualifiedWildcard(..) => return None,
+        }
+    }
+    Some(outputs)
+}
+
+fn collect_relations(
+    table_with_joins: &TableWithJoins,
+    ctes: &HashMap<String, Vec<OutputColumn>>,
+    out: &mut Vec<(String, Relation)>,
+) {
+    push_relation(&table_with_joins.relation, ctes, out);
+    for join in &table_with_joins.joins {
+        push_relation(&join.relation, ctes, out);
+    }
+}
+
+fn push_relation(
+    factor: &TableFactor,
+    ctes: &HashMap<String, Vec<OutputColumn>>,
+    out: &mut Vec<(String, Relation)>,
+) {
+    match factor {
+        TableFactor::Table { name, alias, .. } => {
+            let Some(table) = name.0.last().map(|ident| ident.value.clone()) else {
+                return;
+            };
+            let visible = alias
+                .as_ref()
+                .map(|a| a.name.value.clone())
+                .unwrap_or_else(|| table.clone());
+            let relation = match ctes.get(&table) {
+                Some(columns) => Relation::Resolved(columns.clone()),
+                None => Relation::Table(table),
+            };
+            out.push((visible, relation));
+        }
+        TableFactor::Derived {
+            subquery,
+            alias: Some(alias),
+            ..
+        } => {
+            let relation = resolve_query(subquery, ctes)
+                .map(Relation::Resolved)
+                .unwrap_or(Relation::Opaque);
+            out.push((alias.name.value.clone(), relation));
+        }
+        TableFactor::NestedJoin {
+            table_with_joins, ..
+        } => collect_relations(table_with_joins, ctes, out),
+        _ => out.push((String::new(), Relation::Opaque)),
+    }
+}
+
+/// The name a column surfaces under when the projection has no alias
+fn display_name(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Identifier(ident) => Some(ident.value.clone()),
+        Expr::CompoundIdentifier(parts) => parts.last().map(|ident| ident.value.clone()),
+        _ => None,
+    }
+}
+
+fn expr_origin(expr: &Expr, relations: &[(String, Relation)]) -> ColumnOrigin {
+    match expr {
+        Expr::Identifier(ident) => resolve_column(None, &ident.value, relations),
+        Expr::CompoundIdentifier(parts) => match parts.as_slice() {
+            // schema qualifiers beyond the table are ignored
+            [.., qualifier, column] => {
+                resolve_column(Some(&qualifier.value), &column.value, relations)
+            }
+            _ => ColumnOrigin::Unknown,
+        },
+        Expr::Nested(inner) => expr_origin(inner, relations),
+        _ => {
+            // Any other expression: collect the underlying columns it reads
+            let mut sources = Vec::new();
+            let _ = visit_expressions(expr, |e: &Expr| {
+                let origin = match e {
+                    Expr::Identifier(ident) => {
+                        Some(resolve_column(None, &ident.value, relations))
+                    }
+                    Expr::CompoundIdentifier(parts) => match parts.as_slice() {
+                        [.., qualifier, column] => {
+                            Some(resolve_column(Some(&qualifier.value), &column.value, relations))
+                        }
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                match origin {
+                    Some(ColumnOrigin::Column { table, column }) => {
+                        sources.push((table, column));
+                    }
+                    Some(ColumnOrigin::Expression { sources: mut inner }) => {
+                        sources.append(&mut inner);
+                    }
+                    _ => {}
+                }
+                ControlFlow::<()>::Continue(())
+            });
+            if sources.is_empty() {
+                ColumnOrigin::Unknown
+            } else {
+                ColumnOrigin::Expression { sources }
+            }
+        }
+    }
+}
+
+fn resolve_column(
+    qualifier: Option<&str>,
+    column: &str,
+    relations: &[(String, Relation)],
+) -> ColumnOrigin {
+    match qualifier {
+        Some(q) => match relations.iter().find(|(name, _)| name == q) {
+            Some((_, Relation::Table(table))) => ColumnOrigin::Column {
+                table: Some(table.clone()),
+                column: column.to_string(),
+            },
+            Some((_, Relation::Resolved(columns))) => origin_from_resolved(columns, column),
+            _ => ColumnOrigin::Unknown,
+        },
+        None => match relations {
+            [(_, Relation::Table(table))] => ColumnOrigin::Column {
+                table: Some(table.clone()),
+                column: column.to_string(),
+            },
+            [(_, Relation::Resolved(columns))] => origin_from_resolved(columns, column),
+            // No FROM, or a bare name that is ambiguous across several
+            // relations: the column name is still known, the table is not
+            _ => ColumnOrigin::Column {
+                table: None,
+                column: column.to_string(),
+            },
+        },
+    }
+}
+
+fn origin_from_resolved(columns: &[OutputColumn], name: &str) -> ColumnOrigin {
+    columns
+        .iter()
+        .find(|c| c.name.as_deref() == Some(name))
+        .map(|c| c.origin.clone())
+        .unwrap_or(ColumnOrigin::Unknown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(table: &str, name: &str) -> ColumnOrigin {
+        ColumnOrigin::Column {
+            table: Some(table.to_string()),
+            column: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_alias_resolves_to_underlying_column() {
+        let mut resolver = QueryResolver::new();
+        let outputs = resolver
+            .resolve("SELECT email AS contact, city FROM users")
+            .unwrap();
+        assert_eq!(outputs[0].name.as_deref(), Some("contact"));
+        assert_eq!(outputs[0].origin, column("users", "email"));
+        assert_eq!(outputs[1].origin, column("users", "city"));
+    }
+
+    #[test]
+    fn test_join_qualifiers_and_ambiguous_names() {
+        let mut resolver = QueryResolver::new();
+        let outputs = resolver
+            .resolve(
+                "SELECT u.email, o.email AS order_email, id \
+                 FROM users u JOIN orders o ON o.user_id = u.id",
+            )
+            .unwrap();
+        assert_eq!(outputs[0].origin, column("users", "email"));
+        assert_eq!(outputs[1].origin, column("orders", "email"));
+        // A bare name over two tables keeps the column, drops the table
+        assert_eq!(
+            outputs[2].origin,
+            ColumnOrigin::Column {
+                table: None,
+                column: "id".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_cte_rename_traces_back_to_source() {
+        let mut resolver = QueryResolver::new();
+        let outputs = resolver
+            .resolve(
+                "WITH contacts AS (SELECT email AS contact FROM users) \
+                 SELECT contact FROM contacts",
+            )
+            .unwrap();
+        assert_eq!(outputs[0].origin, column("users", "email"));
+    }
+
+    #[test]
+    fn test_expression_reports_source_columns() {
+        let mut resolver = QueryResolver::new();
+        let outputs = resolver
+            .resolve("SELECT upper(email) AS e, 1 + 2 AS n FROM users")
+            .unwrap();
+        assert_eq!(
+            outputs[0].origin,
+            ColumnOrigin::Expression {
+                sources: vec![(Some("users".to_string()), "email".to_string())]
+            }
+        );
+        assert_eq!(outputs[1].origin, ColumnOrigin::Unknown);
+    }
+
+    #[test]
+    fn test_opaque_statements_resolve_to_none() {
+        let mut resolver = QueryResolver::new();
+        assert!(resolver.resolve("SELECT * FROM users").is_none());
+        assert!(resolver.resolve("this is not sql").is_none());
+        assert!(resolver.resolve("UPDATE users SET email = 'x'").is_none());
+    }
+
+    #[test]
+    fn test_returning_clauses_resolve_like_projections() {
+        let mut resolver = QueryResolver::new();
+
+        let outputs = resolver
+            .resolve("INSERT INTO users (email) VALUES ('x') RETURNING email AS contact, id")
+            .unwrap();
+        assert_eq!(outputs[0].name.as_deref(), Some("contact"));
+        assert_eq!(outputs[0].origin, column("users", "email"));
+        assert_eq!(outputs[1].origin, column("users", "id"));
+
+        let outputs = resolver
+            .resolve("UPDATE users u SET active = false WHERE id = 7 RETURNING u.email")
+            .unwrap();
+        assert_eq!(outputs[0].origin, column("users", "email"));
+
+        let outputs = resolver
+            .resolve("DELETE FROM users WHERE id = 7 RETURNING email")
+            .unwrap();
+        assert_eq!(outputs[0].origin, column("users", "email"));
+
+        // RETURNING * degrades to display names, like SELECT *
+        assert!(
+            resolver
+                .resolve("UPDATE users SET active = false RETURNING *")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_cache_returns_same_resolution_for_formatting_variants() {
+        let mut resolver = QueryResolver::new();
+        let first = resolver.resolve("SELECT email FROM users").unwrap();
+        let second = resolver.resolve("SELECT  email\n  FROM users").unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+    }
+}
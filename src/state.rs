@@ -1,16 +1,18 @@
 use crate::audit::AuditLogger;
-use crate::config::AppConfig;
+use crate::config::{AppConfig, PolicyAction};
+use crate::version::ServerVersion;
 use chrono::{DateTime, Utc};
-use metrics_exporter_prometheus::PrometheusHandle;
+use crate::metrics::MetricsBackend;
 use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::{
     Arc,
-    atomic::{AtomicBool, AtomicUsize, Ordering},
+    atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
 };
 use tokio::sync::RwLock;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(utoipa::ToSchema))]
 pub struct LogEntry {
     pub id: String,
     pub timestamp: DateTime<Utc>,
@@ -22,6 +24,7 @@ pub struct LogEntry {
 
 /// Upstream health status information
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "api", derive(utoipa::ToSchema))]
 pub struct HealthStatus {
     pub healthy: bool,
     pub last_check: Option<DateTime<Utc>>,
@@ -65,6 +68,9 @@ pub struct MaskingStats {
     pub hash: u64,
     pub json: u64,
     pub other: u64,
+    /// Cells a conditional rule examined and deliberately left unmasked;
+    /// not part of [`total`](Self::total), which counts masked cells only
+    pub condition_skipped: u64,
 }
 
 impl MaskingStats {
@@ -132,6 +138,63 @@ pub struct ConnectionDataPoint {
     pub total_masked: u64,
 }
 
+/// Live bookkeeping for one client session, keyed by connection id and
+/// rendered by the admin console's SHOW CLIENTS
+#[derive(Debug, Clone, Serialize)]
+pub struct ClientInfo {
+    pub client_addr: std::net::SocketAddr,
+    /// The `host:port` this session was proxied to, set once the upstream
+    /// connection is up (routing can pick a different target per database)
+    pub upstream: Option<String>,
+    pub username: Option<String>,
+    pub database: Option<String>,
+    pub application_name: Option<String>,
+    pub connected_at: DateTime<Utc>,
+    pub queries: u64,
+    pub rows_masked: u64,
+    pub cells_masked: u64,
+    /// Traffic counters the forwarding loop bumps without taking the
+    /// clients lock
+    #[serde(skip)]
+    pub counters: Arc<ConnectionCounters>,
+    /// Cancelled by `DELETE /connections/{id}`; the forwarding loop
+    /// selects on it so termination is immediate, not at the next read
+    #[serde(skip)]
+    pub cancel: tokio_util::sync::CancellationToken,
+}
+
+/// Lock-free per-connection traffic counters. The forwarding loop touches
+/// these on every frame, so they are atomics shared by `Arc` rather than
+/// fields guarded by the clients lock.
+#[derive(Debug, Default)]
+pub struct ConnectionCounters {
+    /// Bytes received from the client
+    pub bytes_in: AtomicU64,
+    /// Bytes sent to the client
+    pub bytes_out: AtomicU64,
+    /// Result rows forwarded to the client
+    pub rows_forwarded: AtomicU64,
+}
+
+/// One caller's token-bucket state for the management API rate limiter.
+/// The limit itself lives in `api.rate_limit` and is re-read per request,
+/// so a reload takes effect without resetting anyone's bucket.
+#[cfg(feature = "api")]
+#[derive(Debug, Clone, Copy)]
+pub struct RateBucket {
+    /// Requests currently available to this caller
+    pub tokens: f64,
+    /// When the bucket last refilled toward the burst size
+    pub last_refill: std::time::Instant,
+}
+
+/// Protocol-violation bookkeeping for one source address
+#[derive(Debug, Clone, Copy, Default)]
+struct GreylistEntry {
+    violations: u32,
+    blocked_until: Option<std::time::Instant>,
+}
+
 /// Application statistics
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AppStats {
@@ -140,19 +203,90 @@ pub struct AppStats {
     pub total_connections: u64,
 }
 
+/// Runtime artifacts built from a candidate config before it replaces the
+/// running one. Validation catches malformed configs; these builds catch
+/// failures that only appear on apply. Either every artifact comes up and
+/// the candidate is swapped in, or it is discarded wholesale and the old
+/// artifacts keep serving.
+pub(crate) struct StagedArtifacts {
+    pub(crate) tls_acceptor: Option<tokio_rustls::TlsAcceptor>,
+    /// Acceptor for the management API listener, when `api.tls` is enabled
+    #[cfg(feature = "api")]
+    pub(crate) api_tls_acceptor: Option<tokio_rustls::TlsAcceptor>,
+}
+
+impl StagedArtifacts {
+    /// Builds every artifact the config calls for, naming the failing
+    /// component in the error
+    pub(crate) fn build(config: &AppConfig) -> Result<Self, String> {
+        let tls_acceptor = match &config.tls {
+            Some(tls) if tls.enabled => Some(
+                crate::proxy::build_tls_acceptor(tls).map_err(|e| format!("tls: {}", e))?,
+            ),
+            _ => None,
+        };
+        #[cfg(feature = "api")]
+        let api_tls_acceptor = match config.api.as_ref().and_then(|api| api.tls.as_ref()) {
+            Some(tls) if tls.enabled => Some(
+                crate::proxy::build_tls_acceptor(tls).map_err(|e| format!("api tls: {}", e))?,
+            ),
+            _ => None,
+        };
+        if let Some(audit) = &config.audit
+            && audit.enabled
+            && let Some(log_file) = &audit.log_file
+        {
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(log_file)
+                .map_err(|e| format!("audit: cannot open log file '{}': {}", log_file, e))?;
+        }
+        Ok(Self {
+            tls_acceptor,
+            #[cfg(feature = "api")]
+            api_tls_acceptor,
+        })
+    }
+}
+
 #[derive(Clone)]
 pub struct AppState {
     pub config: Arc<RwLock<AppConfig>>,
     pub config_path: Arc<String>,
     pub active_connections: Arc<AtomicUsize>,
     pub logs: Arc<RwLock<VecDeque<LogEntry>>>,
+    /// How many entries `add_log` has evicted from the buffer, so `/logs`
+    /// can say whether older history existed beyond what it returns
+    pub logs_evicted: Arc<AtomicU64>,
+    /// Every `add_log` entry is also published here for `/logs/stream`
+    /// subscribers; a receiver that falls behind drops entries rather
+    /// than backpressuring the data path
+    pub log_stream: tokio::sync::broadcast::Sender<LogEntry>,
+    /// Per-caller token buckets backing the management API rate limiter,
+    /// keyed by token name or client IP
+    #[cfg(feature = "api")]
+    pub api_rate_buckets: Arc<tokio::sync::Mutex<HashMap<String, RateBucket>>>,
+    /// TLS acceptor for the management API listener (`None` when `api.tls`
+    /// is off); swapped on config reload like the data-plane acceptor, so
+    /// certificate rotation is a reload away
+    #[cfg(feature = "api")]
+    pub api_tls_acceptor: Arc<RwLock<Option<tokio_rustls::TlsAcceptor>>>,
     pub upstream_healthy: Arc<AtomicBool>,
     pub health_status: Arc<RwLock<HealthStatus>>,
-    pub metrics_handle: Option<Arc<PrometheusHandle>>,
+    /// Health of named routing upstreams, keyed by their name in
+    /// `routing.upstreams` (empty unless routing is configured)
+    pub route_health: Arc<RwLock<HashMap<String, HealthStatus>>>,
+    /// Installed metrics backend (renders `/metrics` when Prometheus)
+    pub metrics: MetricsBackend,
     /// Upstream database host for scanning
     pub upstream_host: Arc<String>,
     /// Upstream database port for scanning
     pub upstream_port: u16,
+    /// Upstream fields pinned by explicitly passed CLI flags, taking
+    /// precedence over the config's `upstream` section on every
+    /// [`upstream_target`](Self::upstream_target) resolution
+    pub upstream_override: Arc<UpstreamOverride>,
     /// Database protocol (Postgres or MySQL)
     pub db_protocol: DbProtocol,
     /// Audit logger for security events
@@ -161,6 +295,69 @@ pub struct AppState {
     pub stats: Arc<RwLock<AppStats>>,
     /// Connection history for charts (last 60 data points)
     pub connection_history: Arc<RwLock<VecDeque<ConnectionDataPoint>>>,
+    /// Detected upstream server version (from ParameterStatus or the MySQL greeting)
+    pub upstream_version: Arc<RwLock<Option<ServerVersion>>>,
+    /// Bumped on any rule change (add/delete/import/reload) so caches keyed
+    /// on the ruleset can detect staleness without diffing rules
+    pub ruleset_generation: Arc<AtomicU64>,
+    /// Source-policy actions resolved at connection setup, keyed by
+    /// connection id (absent means the default of full masking)
+    pub policy_actions: Arc<RwLock<HashMap<usize, PolicyAction>>>,
+    /// Local address of the listener that accepted each live connection,
+    /// keyed by connection id
+    pub listener_addrs: Arc<RwLock<HashMap<usize, std::net::SocketAddr>>>,
+    /// Per-connection protocol statistics for every live client session,
+    /// keyed by connection id
+    pub clients: Arc<RwLock<HashMap<usize, ClientInfo>>>,
+    /// Whether this process has stopped accepting and is draining its
+    /// remaining sessions ahead of a handover; reported as the role in
+    /// `/health`
+    pub draining: Arc<AtomicBool>,
+    /// Live TLS acceptor read per accepted connection; swapped together with
+    /// the config on a successful staged apply, so a failed reload keeps
+    /// terminating TLS with the previous certificate
+    pub tls_acceptor: Arc<RwLock<Option<tokio_rustls::TlsAcceptor>>>,
+    /// Custom strategies registered by the embedder before the proxy
+    /// started, looked up by the name `custom:` rule stages use
+    pub strategy_registry: Arc<crate::interceptor::StrategyRegistry>,
+    /// Sliding-window state for the configured PII detection alerts
+    pub alerts: Arc<RwLock<crate::alerts::AlertEvaluator>>,
+    /// Columns an alert quarantined: result sets carry a hard redaction for
+    /// them (matched on lowercased display name) until cleared via the API
+    pub quarantined_columns: Arc<RwLock<HashSet<String>>>,
+    /// Protocol-violation counts per source address, with the expiry of any
+    /// greylisting; consulted by the accept loop before protocol traffic
+    greylist: Arc<RwLock<HashMap<std::net::IpAddr, GreylistEntry>>>,
+    /// OID-to-table-name resolution for this upstream, when configured
+    #[cfg(feature = "postgres")]
+    pub oid_cache: Option<Arc<crate::oid_cache::OidCache>>,
+    /// Background PII scans started via `POST /scan`, keyed by scan id;
+    /// at most one is running at a time
+    #[cfg(feature = "postgres")]
+    pub scan_jobs: Arc<RwLock<HashMap<String, crate::db_scanner::ScanJob>>>,
+    /// Last successful live `/schema` fetch, served within its TTL and
+    /// kept as a stale fallback for when the upstream is unreachable
+    #[cfg(feature = "postgres")]
+    pub schema_snapshot: Arc<RwLock<Option<crate::db_scanner::SchemaSnapshot>>>,
+    /// BackendKeyData each upstream session issued, keyed by (process id,
+    /// secret key), so a CancelRequest can be traced to the proxied
+    /// connection it targets
+    backend_keys: Arc<RwLock<HashMap<(u32, u32), usize>>>,
+    /// Detection events on their way to the alert aggregator task
+    detection_tx: tokio::sync::mpsc::UnboundedSender<crate::alerts::Detection>,
+    /// Receiving end, taken by [`crate::proxy`] when it spawns the
+    /// aggregator
+    pub(crate) detection_rx:
+        Arc<std::sync::Mutex<Option<tokio::sync::mpsc::UnboundedReceiver<crate::alerts::Detection>>>>,
+}
+
+/// Upstream fields fixed by explicitly passed CLI flags. A set field wins
+/// over the config's `upstream` section for the process lifetime, so a
+/// reload cannot move a target the operator pinned on the command line.
+#[derive(Debug, Default, Clone)]
+pub struct UpstreamOverride {
+    pub host: Option<String>,
+    pub port: Option<u16>,
 }
 
 impl AppState {
@@ -183,6 +380,7 @@ impl AppState {
                     rotation_enabled: cfg.rotation_enabled,
                     max_file_size_bytes: cfg.max_file_size_bytes,
                     max_rotated_files: cfg.max_rotated_files,
+                    query_max_results: cfg.query_max_results,
                     events: cfg
                         .events
                         .iter()
@@ -214,29 +412,105 @@ impl AppState {
                             crate::config::AuditEventType::ApiAccess => {
                                 crate::audit::AuditEventType::ApiAccess
                             }
+                            crate::config::AuditEventType::ResultTruncated => {
+                                crate::audit::AuditEventType::ResultTruncated
+                            }
+                            crate::config::AuditEventType::ConnectionPolicy => {
+                                crate::audit::AuditEventType::ConnectionPolicy
+                            }
+                            crate::config::AuditEventType::PinnedParameterChange => {
+                                crate::audit::AuditEventType::PinnedParameterChange
+                            }
                         })
                         .collect(),
                 })
             })
             .unwrap_or_else(|| AuditLogger::new(crate::audit::AuditConfig::default()));
 
+        let alerts = crate::alerts::AlertEvaluator::new(config.alerts.as_ref());
+        let (detection_tx, detection_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        #[cfg(feature = "postgres")]
+        let oid_cache = config.oid_resolution.clone().map(|resolution| {
+            Arc::new(crate::oid_cache::OidCache::new(
+                upstream_host.clone(),
+                upstream_port,
+                resolution,
+            ))
+        });
+
         Self {
             config: Arc::new(RwLock::new(config)),
             config_path: Arc::new(config_path),
             active_connections: Arc::new(AtomicUsize::new(0)),
             logs: Arc::new(RwLock::new(VecDeque::with_capacity(100))),
+            logs_evicted: Arc::new(AtomicU64::new(0)),
+            log_stream: tokio::sync::broadcast::channel(256).0,
+            #[cfg(feature = "api")]
+            api_rate_buckets: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
+            #[cfg(feature = "api")]
+            api_tls_acceptor: Arc::new(RwLock::new(None)),
             upstream_healthy: Arc::new(AtomicBool::new(true)),
             health_status: Arc::new(RwLock::new(HealthStatus::default())),
-            metrics_handle: None,
+            route_health: Arc::new(RwLock::new(HashMap::new())),
+            metrics: MetricsBackend::None,
             upstream_host: Arc::new(upstream_host),
             upstream_port,
+            upstream_override: Arc::new(UpstreamOverride::default()),
             db_protocol,
             audit_logger: Arc::new(audit_logger),
             stats: Arc::new(RwLock::new(AppStats::default())),
             connection_history: Arc::new(RwLock::new(VecDeque::with_capacity(60))),
+            upstream_version: Arc::new(RwLock::new(None)),
+            ruleset_generation: Arc::new(AtomicU64::new(0)),
+            policy_actions: Arc::new(RwLock::new(HashMap::new())),
+            listener_addrs: Arc::new(RwLock::new(HashMap::new())),
+            clients: Arc::new(RwLock::new(HashMap::new())),
+            draining: Arc::new(AtomicBool::new(false)),
+            tls_acceptor: Arc::new(RwLock::new(None)),
+            strategy_registry: Arc::new(crate::interceptor::StrategyRegistry::default()),
+            alerts: Arc::new(RwLock::new(alerts)),
+            quarantined_columns: Arc::new(RwLock::new(HashSet::new())),
+            greylist: Arc::new(RwLock::new(HashMap::new())),
+            #[cfg(feature = "postgres")]
+            oid_cache,
+            #[cfg(feature = "postgres")]
+            scan_jobs: Arc::new(RwLock::new(HashMap::new())),
+            #[cfg(feature = "postgres")]
+            schema_snapshot: Arc::new(RwLock::new(None)),
+            backend_keys: Arc::new(RwLock::new(HashMap::new())),
+            detection_tx,
+            detection_rx: Arc::new(std::sync::Mutex::new(Some(detection_rx))),
         }
     }
 
+    /// Report a heuristic PII detection to the alert aggregator. Sends on
+    /// an unbounded channel so the row path never blocks on alerting.
+    pub fn report_detection(&self, detection: crate::alerts::Detection) {
+        let _ = self.detection_tx.send(detection);
+    }
+
+    /// The upstream target new connections dial: a CLI flag pins its
+    /// field, then the config's `upstream` section (so a reload moves new
+    /// connections while established ones keep their current target), then
+    /// the builder/startup value.
+    pub async fn upstream_target(&self) -> (String, u16) {
+        let config = self.config.read().await;
+        let upstream = config.upstream.as_ref();
+        let host = self
+            .upstream_override
+            .host
+            .clone()
+            .or_else(|| upstream.and_then(|u| u.host.clone()))
+            .unwrap_or_else(|| self.upstream_host.to_string());
+        let port = self
+            .upstream_override
+            .port
+            .or_else(|| upstream.and_then(|u| u.port))
+            .unwrap_or(self.upstream_port);
+        (host, port)
+    }
+
     /// Create a new AppState with default upstream settings (for testing)
     #[cfg(test)]
     pub fn new_for_test(config: AppConfig, config_path: String) -> Self {
@@ -249,11 +523,227 @@ impl AppState {
         )
     }
 
-    pub fn with_metrics(mut self, handle: PrometheusHandle) -> Self {
-        self.metrics_handle = Some(Arc::new(handle));
+    pub fn with_metrics(mut self, backend: MetricsBackend) -> Self {
+        self.metrics = backend;
         self
     }
 
+    /// Record the source policy resolved for a connection at setup
+    pub async fn set_policy_action(&self, connection_id: usize, action: PolicyAction) {
+        self.policy_actions.write().await.insert(connection_id, action);
+    }
+
+    /// The source policy governing a connection, if one was resolved
+    pub async fn policy_action(&self, connection_id: usize) -> Option<PolicyAction> {
+        self.policy_actions.read().await.get(&connection_id).copied()
+    }
+
+    /// Drop the policy entry when the connection ends
+    pub async fn clear_policy_action(&self, connection_id: usize) {
+        self.policy_actions.write().await.remove(&connection_id);
+    }
+
+    /// Record which listener accepted a connection
+    pub async fn set_listener_addr(&self, connection_id: usize, addr: std::net::SocketAddr) {
+        self.listener_addrs.write().await.insert(connection_id, addr);
+    }
+
+    /// The local listener address that accepted a connection
+    pub async fn listener_addr(&self, connection_id: usize) -> Option<std::net::SocketAddr> {
+        self.listener_addrs.read().await.get(&connection_id).copied()
+    }
+
+    /// Drop the listener entry when the connection ends
+    pub async fn clear_listener_addr(&self, connection_id: usize) {
+        self.listener_addrs.write().await.remove(&connection_id);
+    }
+
+    /// Record the BackendKeyData an upstream issued for a connection
+    pub async fn set_backend_key(&self, connection_id: usize, process_id: u32, secret_key: u32) {
+        self.backend_keys
+            .write()
+            .await
+            .insert((process_id, secret_key), connection_id);
+    }
+
+    /// The proxied connection a CancelRequest's key pair targets, if known
+    pub async fn cancel_target(&self, process_id: u32, secret_key: u32) -> Option<usize> {
+        self.backend_keys
+            .read()
+            .await
+            .get(&(process_id, secret_key))
+            .copied()
+    }
+
+    /// Drop any recorded keys when the connection ends
+    pub async fn clear_backend_key(&self, connection_id: usize) {
+        self.backend_keys
+            .write()
+            .await
+            .retain(|_, id| *id != connection_id);
+    }
+
+    /// Start per-connection bookkeeping when a connection is accepted
+    pub async fn register_client(&self, connection_id: usize, client_addr: std::net::SocketAddr) {
+        self.clients.write().await.insert(
+            connection_id,
+            ClientInfo {
+                client_addr,
+                upstream: None,
+                username: None,
+                database: None,
+                application_name: None,
+                connected_at: Utc::now(),
+                queries: 0,
+                rows_masked: 0,
+                cells_masked: 0,
+                counters: Arc::new(ConnectionCounters::default()),
+                cancel: tokio_util::sync::CancellationToken::new(),
+            },
+        );
+    }
+
+    /// The cancellation token for a live connection, if it is still open
+    pub async fn client_cancel(
+        &self,
+        connection_id: usize,
+    ) -> Option<tokio_util::sync::CancellationToken> {
+        self.clients
+            .read()
+            .await
+            .get(&connection_id)
+            .map(|client| client.cancel.clone())
+    }
+
+    /// Record the upstream target a connection was proxied to
+    pub async fn set_client_upstream(&self, connection_id: usize, upstream: String) {
+        if let Some(client) = self.clients.write().await.get_mut(&connection_id) {
+            client.upstream = Some(upstream);
+        }
+    }
+
+    /// The upstream a live connection was proxied to, as recorded by
+    /// [`set_client_upstream`](Self::set_client_upstream)
+    pub async fn client_upstream(&self, connection_id: usize) -> Option<String> {
+        self.clients
+            .read()
+            .await
+            .get(&connection_id)
+            .and_then(|client| client.upstream.clone())
+    }
+
+    /// The shared traffic counters for a connection, grabbed once by the
+    /// forwarding loop so frame-level updates skip the clients lock
+    pub async fn client_counters(&self, connection_id: usize) -> Option<Arc<ConnectionCounters>> {
+        self.clients
+            .read()
+            .await
+            .get(&connection_id)
+            .map(|client| client.counters.clone())
+    }
+
+    /// Fill in the identity a client announced in its startup message
+    pub async fn set_client_session(
+        &self,
+        connection_id: usize,
+        username: Option<String>,
+        database: Option<String>,
+        application_name: Option<String>,
+    ) {
+        if let Some(client) = self.clients.write().await.get_mut(&connection_id) {
+            client.username = username;
+            client.database = database;
+            client.application_name = application_name;
+        }
+    }
+
+    /// Count a query against its connection
+    pub async fn record_client_query(&self, connection_id: usize) {
+        if let Some(client) = self.clients.write().await.get_mut(&connection_id) {
+            client.queries += 1;
+        }
+    }
+
+    /// Count a row that left a connection with `cells` masked cells
+    pub async fn record_client_masked_row(&self, connection_id: usize, cells: u64) {
+        if let Some(client) = self.clients.write().await.get_mut(&connection_id) {
+            client.rows_masked += 1;
+            client.cells_masked += cells;
+        }
+    }
+
+    /// Drop the client entry when the connection ends
+    pub async fn clear_client(&self, connection_id: usize) {
+        self.clients.write().await.remove(&connection_id);
+    }
+
+    /// Mark this process as draining: it keeps serving its open sessions
+    /// but reports the draining role so orchestration can tell the old
+    /// process from its replacement during an upgrade
+    pub fn begin_drain(&self) {
+        self.draining.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Whether this process is draining ahead of a handover
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Counts a protocol violation from `ip`. When the limits config sets
+    /// `greylist_after_violations` and the count reaches it, the address is
+    /// greylisted for `greylist_secs`; returns true when that just happened.
+    pub async fn record_protocol_offense(&self, ip: std::net::IpAddr) -> bool {
+        let (threshold, block_for) = {
+            let config = self.config.read().await;
+            let limits = config.limits.as_ref();
+            match limits.and_then(|l| l.greylist_after_violations) {
+                Some(threshold) => (
+                    threshold,
+                    std::time::Duration::from_secs(
+                        limits.map(|l| l.greylist_secs).unwrap_or(60),
+                    ),
+                ),
+                None => return false,
+            }
+        };
+        let mut greylist = self.greylist.write().await;
+        let entry = greylist.entry(ip).or_default();
+        entry.violations += 1;
+        if entry.violations >= threshold && entry.blocked_until.is_none() {
+            entry.blocked_until = Some(std::time::Instant::now() + block_for);
+            return true;
+        }
+        false
+    }
+
+    /// Whether `ip` is currently refused for recent protocol violations.
+    /// Expired entries are pruned on the way out, so the map only holds
+    /// recent offenders.
+    pub async fn is_greylisted(&self, ip: std::net::IpAddr) -> bool {
+        let mut greylist = self.greylist.write().await;
+        match greylist.get(&ip).and_then(|e| e.blocked_until) {
+            Some(until) if std::time::Instant::now() < until => true,
+            Some(_) => {
+                greylist.remove(&ip);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// All live client sessions, ordered by connection id
+    pub async fn client_snapshot(&self) -> Vec<(usize, ClientInfo)> {
+        let mut clients: Vec<(usize, ClientInfo)> = self
+            .clients
+            .read()
+            .await
+            .iter()
+            .map(|(id, info)| (*id, info.clone()))
+            .collect();
+        clients.sort_by_key(|(id, _)| *id);
+        clients
+    }
+
     /// Save current config to the config file
     pub async fn save_config(&self) -> Result<(), std::io::Error> {
         let config = self.config.read().await;
@@ -262,11 +752,21 @@ impl AppState {
         std::fs::write(&*self.config_path, yaml)
     }
 
+    /// Push an entry onto the front of the log buffer, evicting from the
+    /// back so the newest `log_buffer_entries` are kept. Evicting before
+    /// the insert while already at capacity means the buffer never
+    /// overshoots its configured size
     pub async fn add_log(&self, entry: LogEntry) {
+        let capacity = self.config.read().await.log_buffer_entries.max(1);
         let mut logs = self.logs.write().await;
-        if logs.len() >= 100 {
+        // A loop rather than a single pop: a reload can shrink the
+        // configured capacity under a fuller buffer
+        while logs.len() >= capacity {
             logs.pop_back();
+            self.logs_evicted.fetch_add(1, Ordering::Relaxed);
         }
+        // Publish to live subscribers too; Err just means none are connected
+        let _ = self.log_stream.send(entry.clone());
         logs.push_front(entry);
     }
 
@@ -315,8 +815,56 @@ impl AppState {
         }
     }
 
-    /// Reload configuration from disk
-    /// Returns the number of rules in the new config, or an error
+    /// Update the health status of a named routing upstream. Each route
+    /// tracks its own failure counts; one cluster going down never marks
+    /// another (or the default upstream) unhealthy.
+    pub async fn update_route_health(
+        &self,
+        route: &str,
+        healthy: bool,
+        latency_ms: Option<u64>,
+        error: Option<String>,
+    ) {
+        let config = self.config.read().await;
+        let health_config = config.health_check.as_ref();
+        let unhealthy_threshold = health_config.map(|h| h.unhealthy_threshold).unwrap_or(3);
+        let healthy_threshold = health_config.map(|h| h.healthy_threshold).unwrap_or(1);
+        drop(config);
+
+        let mut map = self.route_health.write().await;
+        let status = map.entry(route.to_string()).or_default();
+
+        status.last_check = Some(Utc::now());
+        status.latency_ms = latency_ms;
+
+        if healthy {
+            status.consecutive_successes += 1;
+            status.consecutive_failures = 0;
+            status.last_error = None;
+        } else {
+            status.consecutive_failures += 1;
+            status.consecutive_successes = 0;
+            status.last_error = error;
+        }
+
+        if status.consecutive_failures >= unhealthy_threshold {
+            status.healthy = false;
+        } else if status.consecutive_successes >= healthy_threshold {
+            status.healthy = true;
+        }
+    }
+
+    /// Health of a named routing upstream, if it has been checked
+    pub async fn route_health(&self, route: &str) -> Option<HealthStatus> {
+        self.route_health.read().await.get(route).cloned()
+    }
+
+    /// Reload configuration from disk with a staged apply: the runtime
+    /// artifacts the config calls for are built off to the side first and
+    /// only swapped in when all of them came up, so any failure keeps the
+    /// previous config and artifacts serving.
+    /// Returns the number of rules in the new config, or an error naming the
+    /// failing component.
     pub async fn reload_config(&self) -> Result<usize, String> {
         let path = self.config_path.as_ref();
 
@@ -324,13 +872,44 @@ impl AppState {
         let new_config = AppConfig::load(path)
             .map_err(|e| format!("Failed to load config from {}: {}", path, e))?;
 
+        // Stage: failures that validation cannot catch (a key that does not
+        // match its cert, an unwritable audit log) surface here, before
+        // anything replaces the running config
+        let staged = StagedArtifacts::build(&new_config).map_err(|e| {
+            format!(
+                "Failed to apply config from {}: {}; still serving the previous config",
+                path, e
+            )
+        })?;
+
         let rules_count = new_config.rules.len();
+        let snapshot = serde_yaml::to_string(&new_config);
+        let alerts = crate::alerts::AlertEvaluator::new(new_config.alerts.as_ref());
 
-        // Update the config
+        // Swap: the config and its artifacts change together
         {
             let mut config = self.config.write().await;
             *config = new_config;
         }
+        *self.tls_acceptor.write().await = staged.tls_acceptor;
+        #[cfg(feature = "api")]
+        {
+            *self.api_tls_acceptor.write().await = staged.api_tls_acceptor;
+        }
+        *self.alerts.write().await = alerts;
+        self.bump_ruleset_generation();
+
+        // Snapshot for --last-known-good: a restart against a config that
+        // later broke on disk can fall back to what was last applied
+        match snapshot {
+            Ok(snapshot) => {
+                if let Err(e) = std::fs::write(crate::config::last_known_good_path(path), snapshot)
+                {
+                    tracing::warn!("Failed to write last-known-good snapshot: {}", e);
+                }
+            }
+            Err(e) => tracing::warn!("Failed to serialize last-known-good snapshot: {}", e),
+        }
 
         tracing::info!(
             "Configuration reloaded from {}: {} rules",
@@ -340,12 +919,56 @@ impl AppState {
         Ok(rules_count)
     }
 
+    /// Bump the ruleset generation. Must be called after any change to the
+    /// rule set so consumers holding per-generation caches re-resolve rules.
+    /// Returns the new generation.
+    pub fn bump_ruleset_generation(&self) -> u64 {
+        self.ruleset_generation.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// The current ruleset generation (0 until the rules first change)
+    pub fn current_ruleset_generation(&self) -> u64 {
+        self.ruleset_generation.load(Ordering::Relaxed)
+    }
+
+    /// Record the detected upstream server version.
+    ///
+    /// Warns when the version changes to a release outside the tested range
+    /// (Postgres 12-16, MySQL 5.7/8, MariaDB 10/11).
+    pub async fn set_upstream_version(&self, version: ServerVersion) {
+        let mut current = self.upstream_version.write().await;
+        if current.as_ref() == Some(&version) {
+            return;
+        }
+        if version.in_tested_range() {
+            tracing::info!(version = %version.raw, "Detected upstream server version");
+        } else {
+            tracing::warn!(
+                version = %version.raw,
+                "Upstream server version is outside the tested range \
+                 (Postgres 12-16, MySQL 5.7/8, MariaDB 10/11); proceeding best-effort"
+            );
+        }
+        *current = Some(version);
+    }
+
+    /// Get the detected upstream server version, if any
+    pub async fn get_upstream_version(&self) -> Option<ServerVersion> {
+        self.upstream_version.read().await.clone()
+    }
+
     /// Record a masking operation by strategy
     pub async fn record_masking(&self, strategy: &str) {
         let mut stats = self.stats.write().await;
         stats.masking.increment(strategy);
     }
 
+    /// Record a cell left unmasked because its rule's condition did not hold
+    pub async fn record_condition_skip(&self) {
+        let mut stats = self.stats.write().await;
+        stats.masking.condition_skipped += 1;
+    }
+
     /// Record a query by type (SELECT, INSERT, UPDATE, DELETE, etc.)
     pub async fn record_query(&self, query_type: &str) {
         let mut stats = self.stats.write().await;
@@ -397,7 +1020,7 @@ impl AppState {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::AppConfig;
+    use crate::config::{AppConfig, ApplicationNamePolicy, ExpressionHandling};
 
     #[test]
     fn test_masking_stats_increment() {
@@ -469,14 +1092,35 @@ mod tests {
     async fn test_app_state_record_masking() {
         let config = AppConfig {
             masking_enabled: true,
+            verify_output: None,
             rules: vec![],
+            listen: None,
             tls: None,
             upstream_tls: false,
+            upstream_tls_options: None,
+            routing: None,
             telemetry: None,
+            metrics: None,
             api: None,
             limits: None,
+            policies_by_source: vec![],
+            application_name_policy: ApplicationNamePolicy::Pin,
+            expression_handling: ExpressionHandling::Heuristic,
+            scan_typed_columns: false,
+            memo_entries: 4096,
+            log_buffer_entries: 100,
+            watch: false,
+            upstream: None,
             health_check: None,
             audit: None,
+            alerts: None,
+            admin: None,
+            oid_resolution: None,
+            schema_cache: None,
+            masking: None,
+            scanner: None,
+            hashing: None,
+            custom_strategies: Default::default(),
         };
         let state = AppState::new_for_test(config, "proxy.yaml".to_string());
 
@@ -494,14 +1138,35 @@ mod tests {
     async fn test_app_state_record_query() {
         let config = AppConfig {
             masking_enabled: true,
+            verify_output: None,
             rules: vec![],
+            listen: None,
             tls: None,
             upstream_tls: false,
+            upstream_tls_options: None,
+            routing: None,
             telemetry: None,
+            metrics: None,
             api: None,
             limits: None,
+            policies_by_source: vec![],
+            application_name_policy: ApplicationNamePolicy::Pin,
+            expression_handling: ExpressionHandling::Heuristic,
+            scan_typed_columns: false,
+            memo_entries: 4096,
+            log_buffer_entries: 100,
+            watch: false,
+            upstream: None,
             health_check: None,
             audit: None,
+            alerts: None,
+            admin: None,
+            oid_resolution: None,
+            schema_cache: None,
+            masking: None,
+            scanner: None,
+            hashing: None,
+            custom_strategies: Default::default(),
         };
         let state = AppState::new_for_test(config, "proxy.yaml".to_string());
 
@@ -519,14 +1184,35 @@ mod tests {
     async fn test_app_state_record_connection() {
         let config = AppConfig {
             masking_enabled: true,
+            verify_output: None,
             rules: vec![],
+            listen: None,
             tls: None,
             upstream_tls: false,
+            upstream_tls_options: None,
+            routing: None,
             telemetry: None,
+            metrics: None,
             api: None,
             limits: None,
+            policies_by_source: vec![],
+            application_name_policy: ApplicationNamePolicy::Pin,
+            expression_handling: ExpressionHandling::Heuristic,
+            scan_typed_columns: false,
+            memo_entries: 4096,
+            log_buffer_entries: 100,
+            watch: false,
+            upstream: None,
             health_check: None,
             audit: None,
+            alerts: None,
+            admin: None,
+            oid_resolution: None,
+            schema_cache: None,
+            masking: None,
+            scanner: None,
+            hashing: None,
+            custom_strategies: Default::default(),
         };
         let state = AppState::new_for_test(config, "proxy.yaml".to_string());
 
@@ -542,14 +1228,35 @@ mod tests {
     async fn test_app_state_history_snapshot() {
         let config = AppConfig {
             masking_enabled: true,
+            verify_output: None,
             rules: vec![],
+            listen: None,
             tls: None,
             upstream_tls: false,
+            upstream_tls_options: None,
+            routing: None,
             telemetry: None,
+            metrics: None,
             api: None,
             limits: None,
+            policies_by_source: vec![],
+            application_name_policy: ApplicationNamePolicy::Pin,
+            expression_handling: ExpressionHandling::Heuristic,
+            scan_typed_columns: false,
+            memo_entries: 4096,
+            log_buffer_entries: 100,
+            watch: false,
+            upstream: None,
             health_check: None,
             audit: None,
+            alerts: None,
+            admin: None,
+            oid_resolution: None,
+            schema_cache: None,
+            masking: None,
+            scanner: None,
+            hashing: None,
+            custom_strategies: Default::default(),
         };
         let state = AppState::new_for_test(config, "proxy.yaml".to_string());
 
@@ -570,14 +1277,35 @@ mod tests {
     async fn test_history_max_capacity() {
         let config = AppConfig {
             masking_enabled: true,
+            verify_output: None,
             rules: vec![],
+            listen: None,
             tls: None,
             upstream_tls: false,
+            upstream_tls_options: None,
+            routing: None,
             telemetry: None,
+            metrics: None,
             api: None,
             limits: None,
+            policies_by_source: vec![],
+            application_name_policy: ApplicationNamePolicy::Pin,
+            expression_handling: ExpressionHandling::Heuristic,
+            scan_typed_columns: false,
+            memo_entries: 4096,
+            log_buffer_entries: 100,
+            watch: false,
+            upstream: None,
             health_check: None,
             audit: None,
+            alerts: None,
+            admin: None,
+            oid_resolution: None,
+            schema_cache: None,
+            masking: None,
+            scanner: None,
+            hashing: None,
+            custom_strategies: Default::default(),
         };
         let state = AppState::new_for_test(config, "proxy.yaml".to_string());
 
@@ -589,4 +1317,265 @@ mod tests {
         let history = state.get_connection_history().await;
         assert_eq!(history.len(), 60, "History should be capped at 60 entries");
     }
+
+    #[tokio::test]
+    async fn test_route_health_is_isolated_per_upstream() {
+        let config = AppConfig {
+            masking_enabled: true,
+            verify_output: None,
+            rules: vec![],
+            listen: None,
+            tls: None,
+            upstream_tls: false,
+            upstream_tls_options: None,
+            routing: None,
+            telemetry: None,
+            metrics: None,
+            api: None,
+            limits: None,
+            policies_by_source: vec![],
+            application_name_policy: ApplicationNamePolicy::Pin,
+            expression_handling: ExpressionHandling::Heuristic,
+            scan_typed_columns: false,
+            memo_entries: 4096,
+            log_buffer_entries: 100,
+            watch: false,
+            upstream: None,
+            health_check: None,
+            audit: None,
+            alerts: None,
+            admin: None,
+            oid_resolution: None,
+            schema_cache: None,
+            masking: None,
+            scanner: None,
+            hashing: None,
+            custom_strategies: Default::default(),
+        };
+        let state = AppState::new_for_test(config, "proxy.yaml".to_string());
+
+        // Drive one route past the default unhealthy threshold of 3
+        for _ in 0..3 {
+            state
+                .update_route_health("analytics", false, None, Some("refused".to_string()))
+                .await;
+        }
+        state.update_route_health("app", true, Some(2), None).await;
+
+        let analytics = state.route_health("analytics").await.unwrap();
+        assert!(!analytics.healthy);
+        assert_eq!(analytics.consecutive_failures, 3);
+
+        // The other route and the default upstream are untouched
+        let app = state.route_health("app").await.unwrap();
+        assert!(app.healthy);
+        assert!(state.is_upstream_healthy());
+        assert!(state.route_health("unknown").await.is_none());
+
+        // Recovery clears the failure streak
+        state.update_route_health("analytics", true, Some(5), None).await;
+        assert!(state.route_health("analytics").await.unwrap().healthy);
+    }
+
+    fn tls_config_yaml(cert: &str, key: &str) -> String {
+        format!(
+            "rules: []\ntls:\n  enabled: true\n  cert_path: \"tests/fixtures/tls/{}\"\n  key_path: \"tests/fixtures/tls/{}\"\n",
+            cert, key
+        )
+    }
+
+    /// A reload whose config validates but whose artifacts fail to build —
+    /// here a key that does not match its certificate — is rejected at
+    /// staging: the previous config and TLS acceptor keep serving.
+    #[tokio::test]
+    async fn test_failed_staged_apply_keeps_previous_config_and_tls() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("proxy.yaml");
+        std::fs::write(&path, tls_config_yaml("cert_a.pem", "key_a.pem")).unwrap();
+        let path = path.to_str().unwrap().to_string();
+
+        let config = AppConfig::load(&path).unwrap();
+        let state = AppState::new_for_test(config, path.clone());
+        let good = crate::proxy::build_tls_acceptor(
+            state.config.read().await.tls.as_ref().unwrap(),
+        )
+        .unwrap();
+        *state.tls_acceptor.write().await = Some(good);
+
+        // Cert A with key B validates as a config but cannot build an
+        // acceptor
+        std::fs::write(&path, tls_config_yaml("cert_a.pem", "key_b.pem")).unwrap();
+        let err = state.reload_config().await.unwrap_err();
+        assert!(err.contains("tls:"), "{err}");
+        assert!(err.contains("still serving the previous config"), "{err}");
+
+        // The old artifacts are untouched and no snapshot was written for
+        // the rejected config
+        let config = state.config.read().await;
+        assert!(config.tls.as_ref().unwrap().key_path.ends_with("key_a.pem"));
+        drop(config);
+        assert!(state.tls_acceptor.read().await.is_some());
+        assert!(!std::path::Path::new(&crate::config::last_known_good_path(&path)).exists());
+
+        // A coherent pair applies, swaps the acceptor, and snapshots
+        std::fs::write(&path, tls_config_yaml("cert_b.pem", "key_b.pem")).unwrap();
+        state.reload_config().await.unwrap();
+        let config = state.config.read().await;
+        assert!(config.tls.as_ref().unwrap().key_path.ends_with("key_b.pem"));
+        drop(config);
+        assert!(state.tls_acceptor.read().await.is_some());
+        let snapshot = AppConfig::load(&crate::config::last_known_good_path(&path)).unwrap();
+        assert!(snapshot.tls.as_ref().unwrap().key_path.ends_with("key_b.pem"));
+    }
+
+    /// `api.tls` goes through the same staged apply as the data-plane
+    /// acceptor: a good pair swaps in on reload, a mismatched pair is
+    /// refused with the API listener named and the old acceptor kept.
+    #[cfg(feature = "api")]
+    #[tokio::test]
+    async fn test_api_tls_acceptor_staged_and_reloaded() {
+        fn api_tls_yaml(cert: &str, key: &str) -> String {
+            format!(
+                "rules: []\napi:\n  tls:\n    enabled: true\n    cert_path: \"tests/fixtures/tls/{}\"\n    key_path: \"tests/fixtures/tls/{}\"\n",
+                cert, key
+            )
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("proxy.yaml");
+        std::fs::write(&path, api_tls_yaml("cert_a.pem", "key_a.pem")).unwrap();
+        let path = path.to_str().unwrap().to_string();
+
+        let state = AppState::new_for_test(AppConfig::default(), path.clone());
+        assert!(state.api_tls_acceptor.read().await.is_none());
+        state.reload_config().await.unwrap();
+        assert!(state.api_tls_acceptor.read().await.is_some());
+
+        std::fs::write(&path, api_tls_yaml("cert_a.pem", "key_b.pem")).unwrap();
+        let err = state.reload_config().await.unwrap_err();
+        assert!(err.contains("api tls:"), "{err}");
+        assert!(state.api_tls_acceptor.read().await.is_some());
+    }
+
+    /// An audit log file that cannot be opened is caught at staging too,
+    /// with the failing component named.
+    #[tokio::test]
+    async fn test_staged_apply_names_failing_audit_sink() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("proxy.yaml");
+        std::fs::write(
+            &path,
+            "rules: []\naudit:\n  enabled: true\n  log_file: \"/nonexistent-dir/audit.log\"\n",
+        )
+        .unwrap();
+        let path = path.to_str().unwrap().to_string();
+
+        let state = AppState::new_for_test(
+            AppConfig {
+                masking_enabled: true,
+                verify_output: None,
+                ..Default::default()
+            },
+            path,
+        );
+        let err = state.reload_config().await.unwrap_err();
+        assert!(err.contains("audit:"), "{err}");
+    }
+
+    /// Once the log buffer wraps, the newest `log_buffer_entries` entries
+    /// survive and the eviction counter records what was dropped.
+    #[tokio::test]
+    async fn test_log_buffer_eviction_keeps_newest() {
+        let config = AppConfig {
+            log_buffer_entries: 3,
+            ..Default::default()
+        };
+        let state = AppState::new_for_test(config, "proxy.yaml".to_string());
+
+        for i in 1..=5 {
+            state
+                .add_log(LogEntry {
+                    id: i.to_string(),
+                    timestamp: Utc::now(),
+                    connection_id: i,
+                    event_type: "query".to_string(),
+                    content: format!("entry {}", i),
+                    details: None,
+                })
+                .await;
+        }
+
+        let logs = state.logs.read().await;
+        assert_eq!(logs.len(), 3);
+        // Newest-first: entry 5 at the front, entries 1 and 2 evicted
+        let ids: Vec<&str> = logs.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, ["5", "4", "3"]);
+        assert_eq!(state.logs_evicted.load(Ordering::Relaxed), 2);
+    }
+
+    /// `add_log` publishes each entry to the broadcast channel that backs
+    /// the live `/logs/stream` endpoint.
+    #[tokio::test]
+    async fn test_add_log_publishes_to_stream() {
+        let state = AppState::new_for_test(AppConfig::default(), "proxy.yaml".to_string());
+        let mut rx = state.log_stream.subscribe();
+
+        state
+            .add_log(LogEntry {
+                id: "1".to_string(),
+                timestamp: Utc::now(),
+                connection_id: 7,
+                event_type: "masking".to_string(),
+                content: "masked a row".to_string(),
+                details: None,
+            })
+            .await;
+
+        let entry = rx.recv().await.unwrap();
+        assert_eq!(entry.id, "1");
+        assert_eq!(entry.connection_id, 7);
+        assert_eq!(state.logs.read().await.len(), 1);
+    }
+
+    /// Flags pin their field, the `upstream` section moves with reloads,
+    /// and the startup values fill whatever neither names.
+    #[tokio::test]
+    async fn test_upstream_target_resolution() {
+        let config = AppConfig {
+            upstream: Some(crate::config::UpstreamConfig {
+                host: Some("db.internal".to_string()),
+                port: None,
+                protocol: None,
+                connect_timeout: None,
+            }),
+            ..Default::default()
+        };
+        let mut state = AppState::new_for_test(config, "proxy.yaml".to_string());
+
+        // Config host wins over the startup value; port falls through
+        let (host, port) = state.upstream_target().await;
+        assert_eq!(host, "db.internal");
+        assert_eq!(port, 5432);
+
+        // A reload that changes the section moves new connections
+        state.config.write().await.upstream = Some(crate::config::UpstreamConfig {
+            host: Some("db2.internal".to_string()),
+            port: Some(6432),
+            protocol: None,
+            connect_timeout: None,
+        });
+        assert_eq!(
+            state.upstream_target().await,
+            ("db2.internal".to_string(), 6432)
+        );
+
+        // An explicitly passed flag pins its field across reloads
+        state.upstream_override = Arc::new(UpstreamOverride {
+            host: Some("pinned.internal".to_string()),
+            port: None,
+        });
+        assert_eq!(
+            state.upstream_target().await,
+            ("pinned.internal".to_string(), 6432)
+        );
+    }
 }
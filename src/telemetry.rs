@@ -5,13 +5,18 @@
 
 use crate::config::TelemetryConfig;
 use anyhow::Result;
+#[cfg(feature = "otel")]
 use opentelemetry::KeyValue;
+#[cfg(feature = "otel")]
 use opentelemetry::trace::TracerProvider;
+#[cfg(feature = "otel")]
 use opentelemetry_otlp::WithExportConfig;
+#[cfg(feature = "otel")]
 use opentelemetry_sdk::{
     Resource, runtime,
     trace::{RandomIdGenerator, Sampler, TracerProvider as SdkTracerProvider},
 };
+#[cfg(feature = "otel")]
 use tracing_opentelemetry::OpenTelemetryLayer;
 use tracing_subscriber::{EnvFilter, layer::SubscriberExt, util::SubscriberInitExt};
 
@@ -23,6 +28,7 @@ pub fn init_telemetry(config: Option<&TelemetryConfig>) -> Result<Option<Telemet
         .unwrap_or_else(|_| EnvFilter::new("info,iron_veil=debug"));
 
     match config {
+        #[cfg(feature = "otel")]
         Some(cfg) if cfg.enabled => {
             // Build the OTLP exporter
             let exporter = opentelemetry_otlp::SpanExporter::builder()
@@ -62,7 +68,9 @@ pub fn init_telemetry(config: Option<&TelemetryConfig>) -> Result<Option<Telemet
 
             Ok(Some(TelemetryGuard { provider }))
         }
-        _ => {
+        // Without the `otel` feature an enabled telemetry section is rejected
+        // at config validation, so console logging covers every other case.
+        Some(_) | None => {
             // No telemetry config or disabled - just use console logging
             tracing_subscriber::registry()
                 .with(filter)
@@ -82,9 +90,11 @@ pub fn init_telemetry(config: Option<&TelemetryConfig>) -> Result<Option<Telemet
 /// Guard that ensures proper shutdown of the telemetry provider.
 /// When dropped, it will flush any pending traces.
 pub struct TelemetryGuard {
+    #[cfg(feature = "otel")]
     provider: SdkTracerProvider,
 }
 
+#[cfg(feature = "otel")]
 impl Drop for TelemetryGuard {
     fn drop(&mut self) {
         if let Err(e) = self.provider.shutdown() {
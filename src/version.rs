@@ -0,0 +1,247 @@
+//! Upstream server version detection and quirk handling.
+//!
+//! Different upstream versions change details that matter to interception:
+//! newer Postgres adds message fields and auth variants, MariaDB's handshake
+//! differs from MySQL 8, and `information_schema` layouts vary between
+//! releases. This module parses the version reported by the upstream
+//! (Postgres `ParameterStatus` or the MySQL greeting) into a [`ServerVersion`]
+//! and derives a [`VersionQuirks`] table that the schema discovery queries,
+//! the scan sampler, and auth handling consult to pick version-appropriate
+//! behavior.
+
+use serde::{Deserialize, Serialize};
+
+/// The upstream server flavor, as far as interception is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ServerFlavor {
+    Postgres,
+    MySql,
+    MariaDb,
+}
+
+/// A parsed upstream server version.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerVersion {
+    pub flavor: ServerFlavor,
+    pub major: u32,
+    pub minor: u32,
+    /// The raw version string as reported by the server.
+    pub raw: String,
+}
+
+impl ServerVersion {
+    /// Parse a Postgres `server_version` parameter value.
+    ///
+    /// Accepts values like `"16.1"`, `"15.4 (Debian 15.4-1.pgdg120+1)"` or
+    /// `"9.6.24"`.
+    pub fn parse_postgres(raw: &str) -> Option<Self> {
+        let numeric = raw.split_whitespace().next()?;
+        let (major, minor) = parse_major_minor(numeric)?;
+        Some(Self {
+            flavor: ServerFlavor::Postgres,
+            major,
+            minor,
+            raw: raw.to_string(),
+        })
+    }
+
+    /// Parse a MySQL/MariaDB server version from the initial handshake.
+    ///
+    /// MariaDB servers report versions like
+    /// `"5.5.5-10.6.12-MariaDB-1:10.6.12+maria~ubu2004"` (the `5.5.5-` prefix
+    /// is a compatibility shim), while MySQL reports `"8.0.35"` or
+    /// `"5.7.44-log"`.
+    pub fn parse_mysql(raw: &str) -> Option<Self> {
+        if raw.contains("MariaDB") {
+            // Strip the replication-compatibility prefix if present
+            let version_part = raw.strip_prefix("5.5.5-").unwrap_or(raw);
+            let (major, minor) = parse_major_minor(version_part)?;
+            Some(Self {
+                flavor: ServerFlavor::MariaDb,
+                major,
+                minor,
+                raw: raw.to_string(),
+            })
+        } else {
+            let (major, minor) = parse_major_minor(raw)?;
+            Some(Self {
+                flavor: ServerFlavor::MySql,
+                major,
+                minor,
+                raw: raw.to_string(),
+            })
+        }
+    }
+
+    /// Whether this version is inside the range iron-veil is tested against.
+    ///
+    /// Outside this range the proxy still works on a best-effort basis, but a
+    /// startup warning is emitted.
+    pub fn in_tested_range(&self) -> bool {
+        match self.flavor {
+            ServerFlavor::Postgres => (12..=16).contains(&self.major),
+            ServerFlavor::MySql => self.major == 8 || (self.major == 5 && self.minor == 7),
+            ServerFlavor::MariaDb => self.major == 10 || self.major == 11,
+        }
+    }
+
+    /// Derive the quirk table for this version.
+    pub fn quirks(&self) -> VersionQuirks {
+        match self.flavor {
+            ServerFlavor::Postgres => VersionQuirks {
+                // information_schema.columns.is_generated exists since Pg 12
+                has_is_generated_column: self.major >= 12,
+                // TABLESAMPLE landed in Pg 9.5
+                supports_tablesample: self.major >= 10 || (self.major == 9 && self.minor >= 5),
+                // SCRAM-SHA-256 landed in Pg 10
+                supports_scram_sha256: self.major >= 10,
+                default_auth_plugin: "",
+            },
+            ServerFlavor::MySql => VersionQuirks {
+                has_is_generated_column: false,
+                supports_tablesample: false,
+                supports_scram_sha256: false,
+                // MySQL 8 switched the default to caching_sha2_password
+                default_auth_plugin: if self.major >= 8 {
+                    "caching_sha2_password"
+                } else {
+                    "mysql_native_password"
+                },
+            },
+            ServerFlavor::MariaDb => VersionQuirks {
+                has_is_generated_column: false,
+                supports_tablesample: false,
+                supports_scram_sha256: false,
+                // MariaDB never adopted caching_sha2_password
+                default_auth_plugin: "mysql_native_password",
+            },
+        }
+    }
+}
+
+/// Version-dependent behavior switches consulted by the schema discovery
+/// queries, the scan sampler, and auth handling.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionQuirks {
+    /// `information_schema.columns` has an `is_generated` column, so schema
+    /// discovery can filter out generated columns.
+    pub has_is_generated_column: bool,
+    /// The scan sampler may use `TABLESAMPLE` on large tables.
+    pub supports_tablesample: bool,
+    /// The upstream may negotiate SCRAM-SHA-256 authentication.
+    pub supports_scram_sha256: bool,
+    /// The auth plugin this server version is expected to advertise
+    /// (MySQL/MariaDB only; empty for Postgres).
+    pub default_auth_plugin: &'static str,
+}
+
+impl Default for VersionQuirks {
+    /// Conservative defaults used when the upstream version is unknown.
+    fn default() -> Self {
+        Self {
+            has_is_generated_column: false,
+            supports_tablesample: false,
+            supports_scram_sha256: true,
+            default_auth_plugin: "",
+        }
+    }
+}
+
+fn parse_major_minor(s: &str) -> Option<(u32, u32)> {
+    let mut parts = s.split(|c: char| !c.is_ascii_digit());
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next().and_then(|p| p.parse().ok()).unwrap_or(0);
+    Some((major, minor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_postgres_versions() {
+        let v = ServerVersion::parse_postgres("16.1").unwrap();
+        assert_eq!(v.flavor, ServerFlavor::Postgres);
+        assert_eq!(v.major, 16);
+        assert_eq!(v.minor, 1);
+
+        let v = ServerVersion::parse_postgres("15.4 (Debian 15.4-1.pgdg120+1)").unwrap();
+        assert_eq!(v.major, 15);
+        assert_eq!(v.minor, 4);
+
+        let v = ServerVersion::parse_postgres("9.6.24").unwrap();
+        assert_eq!(v.major, 9);
+        assert_eq!(v.minor, 6);
+
+        assert!(ServerVersion::parse_postgres("devel").is_none());
+    }
+
+    #[test]
+    fn test_parse_mysql_versions() {
+        let v = ServerVersion::parse_mysql("8.0.35").unwrap();
+        assert_eq!(v.flavor, ServerFlavor::MySql);
+        assert_eq!(v.major, 8);
+        assert_eq!(v.minor, 0);
+
+        let v = ServerVersion::parse_mysql("5.7.44-log").unwrap();
+        assert_eq!(v.flavor, ServerFlavor::MySql);
+        assert_eq!(v.major, 5);
+        assert_eq!(v.minor, 7);
+    }
+
+    #[test]
+    fn test_parse_mariadb_versions() {
+        let v = ServerVersion::parse_mysql("5.5.5-10.6.12-MariaDB-1:10.6.12+maria~ubu2004").unwrap();
+        assert_eq!(v.flavor, ServerFlavor::MariaDb);
+        assert_eq!(v.major, 10);
+        assert_eq!(v.minor, 6);
+
+        let v = ServerVersion::parse_mysql("11.2.2-MariaDB").unwrap();
+        assert_eq!(v.flavor, ServerFlavor::MariaDb);
+        assert_eq!(v.major, 11);
+    }
+
+    #[test]
+    fn test_tested_range() {
+        assert!(ServerVersion::parse_postgres("12.0").unwrap().in_tested_range());
+        assert!(ServerVersion::parse_postgres("16.1").unwrap().in_tested_range());
+        assert!(!ServerVersion::parse_postgres("11.22").unwrap().in_tested_range());
+        assert!(!ServerVersion::parse_postgres("17.0").unwrap().in_tested_range());
+
+        assert!(ServerVersion::parse_mysql("8.0.35").unwrap().in_tested_range());
+        assert!(ServerVersion::parse_mysql("5.7.44").unwrap().in_tested_range());
+        assert!(!ServerVersion::parse_mysql("5.6.51").unwrap().in_tested_range());
+
+        assert!(ServerVersion::parse_mysql("10.11.6-MariaDB").unwrap().in_tested_range());
+        assert!(!ServerVersion::parse_mysql("5.5.68-MariaDB").unwrap().in_tested_range());
+    }
+
+    #[test]
+    fn test_postgres_quirks_per_version() {
+        let q = ServerVersion::parse_postgres("16.1").unwrap().quirks();
+        assert!(q.has_is_generated_column);
+        assert!(q.supports_tablesample);
+        assert!(q.supports_scram_sha256);
+
+        let q = ServerVersion::parse_postgres("11.22").unwrap().quirks();
+        assert!(!q.has_is_generated_column);
+        assert!(q.supports_tablesample);
+        assert!(q.supports_scram_sha256);
+
+        let q = ServerVersion::parse_postgres("9.4.26").unwrap().quirks();
+        assert!(!q.supports_tablesample);
+        assert!(!q.supports_scram_sha256);
+    }
+
+    #[test]
+    fn test_mysql_quirks_per_version() {
+        let q = ServerVersion::parse_mysql("8.0.35").unwrap().quirks();
+        assert_eq!(q.default_auth_plugin, "caching_sha2_password");
+
+        let q = ServerVersion::parse_mysql("5.7.44").unwrap().quirks();
+        assert_eq!(q.default_auth_plugin, "mysql_native_password");
+
+        let q = ServerVersion::parse_mysql("10.11.6-MariaDB").unwrap().quirks();
+        assert_eq!(q.default_auth_plugin, "mysql_native_password");
+    }
+}
@@ -0,0 +1,98 @@
+//! WASM masking strategy modules (`strategy: wasm`).
+//!
+//! A rule with `strategy: wasm` names a `.wasm` (or `.wat`) file via
+//! `wasm_module`. The module is compiled once at config load and invoked
+//! per cell under a fuel limit, so a runaway module cannot stall a result
+//! set. The guest ABI is deliberately small:
+//!
+//! - `memory`: an exported linear memory
+//! - `alloc(len: i32) -> i32`: reserve `len` bytes for the host to write
+//!   the input value into
+//! - `mask(ptr: i32, len: i32, seed: i64) -> i64`: mask the input and
+//!   return the output's location packed as `(ptr << 32) | len`
+//!
+//! The cell's deterministic seed is passed as the third argument so
+//! modules can opt in to the proxy's determinism guarantee; a module that
+//! ignores it owns its own determinism. Traps, fuel exhaustion, and ABI
+//! violations surface as errors the caller masks to the redact
+//! placeholder — a failing module must never leak the original value.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::{Context, Result};
+use wasmtime::{Engine, Instance, Module, Store, TypedFunc};
+
+/// Fuel budget per `mask` invocation when the rule sets no `wasm_fuel`
+pub const DEFAULT_WASM_FUEL: u64 = 1_000_000;
+
+/// A compiled module, shared by every rule that references its path.
+#[derive(Clone)]
+pub struct WasmStrategy {
+    engine: Engine,
+    module: Module,
+}
+
+impl std::fmt::Debug for WasmStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("WasmStrategy")
+    }
+}
+
+/// Modules compiled at config load, keyed by path, so validation compiles
+/// each file once and masking never touches the filesystem.
+fn cache() -> &'static Mutex<HashMap<String, WasmStrategy>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, WasmStrategy>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Compile the module at `path`, or fetch its cached compilation. Called
+/// by config validation, so a module that does not load rejects the whole
+/// config instead of failing per cell.
+pub fn load_module(path: &str) -> Result<WasmStrategy> {
+    if let Some(found) = cache().lock().unwrap().get(path) {
+        return Ok(found.clone());
+    }
+    let mut config = wasmtime::Config::new();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config).context("initializing the WASM engine")?;
+    let module = Module::from_file(&engine, path)
+        .with_context(|| format!("loading WASM module '{}'", path))?;
+    let strategy = WasmStrategy {
+        engine,
+        module,
+    };
+    cache()
+        .lock()
+        .unwrap()
+        .insert(path.to_string(), strategy.clone());
+    Ok(strategy)
+}
+
+impl WasmStrategy {
+    /// Run the module's `mask` export over one cell value. Each call gets
+    /// a fresh instance and its own fuel budget, so state cannot bleed
+    /// between cells and one runaway invocation cannot starve the next.
+    pub fn apply(&self, original: &str, seed: u64, fuel: u64) -> Result<String> {
+        let mut store = Store::new(&self.engine, ());
+        store.set_fuel(fuel)?;
+        let instance = Instance::new(&mut store, &self.module, &[])?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .context("module does not export 'memory'")?;
+        let alloc: TypedFunc<i32, i32> = instance.get_typed_func(&mut store, "alloc")?;
+        let mask: TypedFunc<(i32, i32, i64), i64> =
+            instance.get_typed_func(&mut store, "mask")?;
+
+        let input = original.as_bytes();
+        let len = i32::try_from(input.len()).context("value too large for the module")?;
+        let ptr = alloc.call(&mut store, len)?;
+        memory.write(&mut store, ptr as usize, input)?;
+
+        let packed = mask.call(&mut store, (ptr, len, seed as i64))? as u64;
+        let (out_ptr, out_len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+        let mut out = vec![0u8; out_len];
+        memory.read(&store, out_ptr, &mut out)?;
+        Ok(String::from_utf8_lossy(&out).into_owned())
+    }
+}
@@ -0,0 +1,2989 @@
+//! Tests for the library embedding API.
+//!
+//! Unlike the integration tests, these are self-contained: a scripted fake
+//! Postgres upstream runs in-process, so no database container is required.
+
+#![cfg(feature = "postgres")]
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use anyhow::Result;
+use iron_veil::config::{
+    AdminConfig, AlertRuleConfig, AlertsConfig, AppConfig, DatabaseRoute, HealthCheckConfig,
+    LimitsConfig, ListenConfig, MaskingConfig, MaskingRule, PolicyAction, RoutingConfig, SamplingConfig,
+    SourcePolicy, Strategy, TypeMismatchPolicy, UnmatchedDatabase, UpstreamTarget,
+    UpstreamTlsConfig,
+};
+use iron_veil::audit::AuditEventType;
+use iron_veil::config::{VerifyOutputConfig, VerifyOutputMode};
+use iron_veil::error::MaskingError;
+use iron_veil::hooks::{ConnectionRegistry, UserPolicy};
+#[cfg(feature = "mysql")]
+use iron_veil::interceptor::MySqlAnonymizer;
+use iron_veil::interceptor::PacketInterceptor;
+use iron_veil::protocol::postgres::{DataRow, RowDescription};
+use iron_veil::proxy::{InterceptorFactory, ProxyServer};
+use iron_veil::state::{AppState, DbProtocol};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::timeout;
+
+const TEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A test interceptor that replaces every data value with a fixed marker
+/// and counts how many rows it saw.
+struct MarkerInterceptor {
+    rows_seen: Arc<AtomicUsize>,
+}
+
+impl PacketInterceptor for MarkerInterceptor {
+    async fn on_row_description(&mut self, _msg: &RowDescription) {}
+
+    async fn on_data_row(&mut self, mut msg: DataRow) -> Result<DataRow, MaskingError> {
+        self.rows_seen.fetch_add(1, Ordering::Relaxed);
+        for val in msg.values.iter_mut().flatten() {
+            val.clear();
+            val.extend_from_slice(b"INTERCEPTED");
+        }
+        Ok(msg)
+    }
+}
+
+#[derive(Clone)]
+struct MarkerFactory {
+    rows_seen: Arc<AtomicUsize>,
+}
+
+impl InterceptorFactory for MarkerFactory {
+    type Pg = MarkerInterceptor;
+    #[cfg(feature = "mysql")]
+    type MySql = MySqlAnonymizer;
+
+    fn pg(&self, _state: AppState, _connection_id: usize) -> MarkerInterceptor {
+        MarkerInterceptor {
+            rows_seen: self.rows_seen.clone(),
+        }
+    }
+
+    #[cfg(feature = "mysql")]
+    fn mysql(&self, state: AppState, connection_id: usize) -> MySqlAnonymizer {
+        MySqlAnonymizer::new(state, connection_id)
+    }
+}
+
+/// Appends a Postgres backend message (type byte + length-prefixed payload)
+fn push_msg(buf: &mut Vec<u8>, msg_type: u8, payload: &[u8]) {
+    buf.push(msg_type);
+    buf.extend_from_slice(&((payload.len() as u32 + 4).to_be_bytes()));
+    buf.extend_from_slice(payload);
+}
+
+/// A scripted Postgres upstream: answers the startup message with
+/// AuthenticationOk/ParameterStatus/ReadyForQuery, and every query with a
+/// single-row result set containing one email address.
+async fn run_fake_upstream(listener: TcpListener) -> Result<()> {
+    run_fake_upstream_rows(listener, 1, b"test@example.com").await
+}
+
+/// Like [`run_fake_upstream`], but answers every query with `row_count`
+/// copies of `value` in a one-column result set.
+async fn run_fake_upstream_rows(
+    listener: TcpListener,
+    row_count: usize,
+    value: &'static [u8],
+) -> Result<()> {
+    let (socket, _) = listener.accept().await?;
+    fake_upstream_session(socket, row_count, value).await
+}
+
+/// Like [`run_fake_upstream`], but keeps accepting connections so several
+/// clients can go through the proxy in one test
+async fn run_fake_upstream_multi(listener: TcpListener) -> Result<()> {
+    loop {
+        let (socket, _) = listener.accept().await?;
+        tokio::spawn(fake_upstream_session(socket, 1, b"test@example.com"));
+    }
+}
+
+/// One scripted upstream session: startup handshake, then an email result
+/// set for every simple query
+async fn fake_upstream_session(
+    mut socket: TcpStream,
+    row_count: usize,
+    value: &'static [u8],
+) -> Result<()> {
+    // Read startup message (length-prefixed, no type byte)
+    let mut len_buf = [0u8; 4];
+    socket.read_exact(&mut len_buf).await?;
+    let len = u32::from_be_bytes(len_buf) as usize;
+    let mut startup = vec![0u8; len - 4];
+    socket.read_exact(&mut startup).await?;
+
+    let mut greeting = Vec::new();
+    // AuthenticationOk
+    push_msg(&mut greeting, b'R', &0u32.to_be_bytes());
+    // ParameterStatus: server_version
+    push_msg(&mut greeting, b'S', b"server_version\x0016.3\x00");
+    // ReadyForQuery (idle)
+    push_msg(&mut greeting, b'Z', b"I");
+    socket.write_all(&greeting).await?;
+
+    loop {
+        let mut type_buf = [0u8; 1];
+        if socket.read_exact(&mut type_buf).await.is_err() {
+            return Ok(()); // client disconnected
+        }
+        socket.read_exact(&mut len_buf).await?;
+        let len = u32::from_be_bytes(len_buf) as usize;
+        let mut payload = vec![0u8; len - 4];
+        socket.read_exact(&mut payload).await?;
+
+        if type_buf[0] != b'Q' {
+            continue;
+        }
+
+        let mut response = Vec::new();
+        // RowDescription: one column named "email"
+        let mut row_desc = Vec::new();
+        row_desc.extend_from_slice(&1u16.to_be_bytes());
+        row_desc.extend_from_slice(b"email\x00");
+        row_desc.extend_from_slice(&0u32.to_be_bytes()); // table oid
+        row_desc.extend_from_slice(&0u16.to_be_bytes()); // column index
+        row_desc.extend_from_slice(&25u32.to_be_bytes()); // type oid (text)
+        row_desc.extend_from_slice(&(-1i16).to_be_bytes()); // type len
+        row_desc.extend_from_slice(&(-1i32).to_be_bytes()); // type modifier
+        row_desc.extend_from_slice(&0u16.to_be_bytes()); // format code
+        push_msg(&mut response, b'T', &row_desc);
+        // DataRows: one value each
+        for _ in 0..row_count {
+            let mut data_row = Vec::new();
+            data_row.extend_from_slice(&1u16.to_be_bytes());
+            data_row.extend_from_slice(&(value.len() as u32).to_be_bytes());
+            data_row.extend_from_slice(value);
+            push_msg(&mut response, b'D', &data_row);
+        }
+        // CommandComplete + ReadyForQuery
+        push_msg(&mut response, b'C', format!("SELECT {}\x00", row_count).as_bytes());
+        push_msg(&mut response, b'Z', b"I");
+        socket.write_all(&response).await?;
+    }
+}
+
+/// The command tag a statement's leading keyword produces
+fn tag_for(query: &[u8]) -> String {
+    let text = String::from_utf8_lossy(query);
+    match text
+        .split_whitespace()
+        .next()
+        .map(|word| word.to_ascii_uppercase())
+        .as_deref()
+    {
+        Some("INSERT") => "INSERT 0 1".to_string(),
+        Some("UPDATE") => "UPDATE 1".to_string(),
+        Some("DELETE") => "DELETE 1".to_string(),
+        _ => "SELECT 1".to_string(),
+    }
+}
+
+/// Appends a one-column "email" result set (RowDescription, one DataRow,
+/// CommandComplete with `tag`, ReadyForQuery)
+fn push_email_result(response: &mut Vec<u8>, tag: &str) {
+    let mut row_desc = Vec::new();
+    row_desc.extend_from_slice(&1u16.to_be_bytes());
+    row_desc.extend_from_slice(b"email\x00");
+    row_desc.extend_from_slice(&0u32.to_be_bytes());
+    row_desc.extend_from_slice(&0u16.to_be_bytes());
+    row_desc.extend_from_slice(&25u32.to_be_bytes());
+    row_desc.extend_from_slice(&(-1i16).to_be_bytes());
+    row_desc.extend_from_slice(&(-1i32).to_be_bytes());
+    row_desc.extend_from_slice(&0u16.to_be_bytes());
+    push_msg(response, b'T', &row_desc);
+
+    let mut data_row = Vec::new();
+    data_row.extend_from_slice(&1u16.to_be_bytes());
+    data_row.extend_from_slice(&(b"test@example.com".len() as u32).to_be_bytes());
+    data_row.extend_from_slice(b"test@example.com");
+    push_msg(response, b'D', &data_row);
+
+    push_msg(response, b'C', format!("{}\x00", tag).as_bytes());
+    push_msg(response, b'Z', b"I");
+}
+
+/// A scripted upstream for RETURNING tests: answers simple queries and
+/// extended-protocol Syncs with a one-row email result set, echoing a
+/// command tag derived from the statement's leading keyword so affected-row
+/// counts can be checked end to end.
+async fn run_fake_upstream_returning(listener: TcpListener) -> Result<()> {
+    let (mut socket, _) = listener.accept().await?;
+
+    let mut len_buf = [0u8; 4];
+    socket.read_exact(&mut len_buf).await?;
+    let len = u32::from_be_bytes(len_buf) as usize;
+    let mut startup = vec![0u8; len - 4];
+    socket.read_exact(&mut startup).await?;
+
+    let mut greeting = Vec::new();
+    push_msg(&mut greeting, b'R', &0u32.to_be_bytes());
+    push_msg(&mut greeting, b'S', b"server_version\x0016.3\x00");
+    push_msg(&mut greeting, b'Z', b"I");
+    socket.write_all(&greeting).await?;
+
+    let mut tag = "SELECT 1".to_string();
+    loop {
+        let mut type_buf = [0u8; 1];
+        if socket.read_exact(&mut type_buf).await.is_err() {
+            return Ok(()); // client disconnected
+        }
+        socket.read_exact(&mut len_buf).await?;
+        let len = u32::from_be_bytes(len_buf) as usize;
+        let mut payload = vec![0u8; len - 4];
+        socket.read_exact(&mut payload).await?;
+
+        let mut response = Vec::new();
+        match type_buf[0] {
+            b'Q' => {
+                tag = tag_for(&payload);
+                push_email_result(&mut response, &tag);
+            }
+            // Parse: statement name NUL, then the query text
+            b'P' => {
+                let query = payload.split(|b| *b == 0).nth(1).unwrap_or(b"");
+                tag = tag_for(query);
+            }
+            // Sync: complete the extended-protocol exchange
+            b'S' => {
+                push_msg(&mut response, b'1', b""); // ParseComplete
+                push_msg(&mut response, b'2', b""); // BindComplete
+                push_email_result(&mut response, &tag);
+            }
+            _ => continue,
+        }
+        if !response.is_empty() {
+            socket.write_all(&response).await?;
+        }
+    }
+}
+
+/// A scripted upstream for the suspended-portal test: the first Sync gets
+/// ParseComplete/BindComplete, the portal's RowDescription, one row, and
+/// PortalSuspended; the follow-up Execute gets one more row and
+/// CommandComplete with no fresh RowDescription, as Postgres resumes a
+/// portal for real
+async fn run_fake_upstream_portal(listener: TcpListener) -> Result<()> {
+    let (mut socket, _) = listener.accept().await?;
+
+    let mut len_buf = [0u8; 4];
+    socket.read_exact(&mut len_buf).await?;
+    let len = u32::from_be_bytes(len_buf) as usize;
+    let mut startup = vec![0u8; len - 4];
+    socket.read_exact(&mut startup).await?;
+
+    let mut greeting = Vec::new();
+    push_msg(&mut greeting, b'R', &0u32.to_be_bytes());
+    push_msg(&mut greeting, b'S', b"server_version 16.3 ");
+    push_msg(&mut greeting, b'Z', b"I");
+    socket.write_all(&greeting).await?;
+
+    let mut first_sync = true;
+    loop {
+        let mut type_buf = [0u8; 1];
+        if socket.read_exact(&mut type_buf).await.is_err() {
+            return Ok(()); // client disconnected
+        }
+        socket.read_exact(&mut len_buf).await?;
+        let len = u32::from_be_bytes(len_buf) as usize;
+        let mut payload = vec![0u8; len - 4];
+        socket.read_exact(&mut payload).await?;
+
+        if type_buf[0] != b'S' {
+            continue;
+        }
+        let mut response = Vec::new();
+        if first_sync {
+            first_sync = false;
+            push_msg(&mut response, b'1', b""); // ParseComplete
+            push_msg(&mut response, b'2', b""); // BindComplete
+            push_email_row_description(&mut response);
+            push_email_data_row(&mut response);
+            push_msg(&mut response, b's', b""); // PortalSuspended
+            push_msg(&mut response, b'Z', b"I");
+        } else {
+            push_email_data_row(&mut response);
+            push_msg(&mut response, b'C', b"SELECT 2 ");
+            push_msg(&mut response, b'Z', b"I");
+        }
+        socket.write_all(&response).await?;
+    }
+}
+
+/// Appends a one-column "email" RowDescription
+fn push_email_row_description(response: &mut Vec<u8>) {
+    let mut row_desc = Vec::new();
+    row_desc.extend_from_slice(&1u16.to_be_bytes());
+    row_desc.extend_from_slice(b"email ");
+    row_desc.extend_from_slice(&0u32.to_be_bytes());
+    row_desc.extend_from_slice(&0u16.to_be_bytes());
+    row_desc.extend_from_slice(&25u32.to_be_bytes());
+    row_desc.extend_from_slice(&(-1i16).to_be_bytes());
+    row_desc.extend_from_slice(&(-1i32).to_be_bytes());
+    row_desc.extend_from_slice(&0u16.to_be_bytes());
+    push_msg(response, b'T', &row_desc);
+}
+
+/// Appends a one-row DataRow holding the canary email address
+fn push_email_data_row(response: &mut Vec<u8>) {
+    let mut data_row = Vec::new();
+    data_row.extend_from_slice(&1u16.to_be_bytes());
+    data_row.extend_from_slice(&(b"test@example.com".len() as u32).to_be_bytes());
+    data_row.extend_from_slice(b"test@example.com");
+    push_msg(response, b'D', &data_row);
+}
+
+/// A scripted upstream that insists on TLS: answers the proxy's SSLRequest
+/// with 'S', completes a server-side handshake with the fixture
+/// certificate, and then serves the usual one-row email result over the
+/// encrypted stream
+async fn run_fake_tls_upstream(listener: TcpListener) -> Result<()> {
+    let certs = rustls_pemfile::certs(&mut std::io::BufReader::new(std::fs::File::open(
+        "tests/fixtures/tls/upstream_cert.pem",
+    )?))
+    .collect::<Result<Vec<_>, _>>()?;
+    let key = rustls_pemfile::private_key(&mut std::io::BufReader::new(std::fs::File::open(
+        "tests/fixtures/tls/upstream_key.pem",
+    )?))?
+    .expect("fixture key missing");
+    let config = tokio_rustls::rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+    let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+
+    let (mut socket, _) = listener.accept().await?;
+    let mut request = [0u8; 8];
+    socket.read_exact(&mut request).await?;
+    assert_eq!(
+        u32::from_be_bytes(request[4..8].try_into().unwrap()),
+        80877103,
+        "proxy did not open with an SSLRequest"
+    );
+    socket.write_all(b"S").await?;
+    let mut stream = acceptor.accept(socket).await?;
+
+    let mut len_buf = [0u8; 4];
+    stream.read_exact(&mut len_buf).await?;
+    let len = u32::from_be_bytes(len_buf) as usize;
+    let mut startup = vec![0u8; len - 4];
+    stream.read_exact(&mut startup).await?;
+
+    let mut greeting = Vec::new();
+    push_msg(&mut greeting, b'R', &0u32.to_be_bytes());
+    push_msg(&mut greeting, b'S', b"server_version 16.3 ");
+    push_msg(&mut greeting, b'Z', b"I");
+    stream.write_all(&greeting).await?;
+
+    loop {
+        let mut type_buf = [0u8; 1];
+        if stream.read_exact(&mut type_buf).await.is_err() {
+            return Ok(()); // client disconnected
+        }
+        stream.read_exact(&mut len_buf).await?;
+        let len = u32::from_be_bytes(len_buf) as usize;
+        let mut payload = vec![0u8; len - 4];
+        stream.read_exact(&mut payload).await?;
+
+        if type_buf[0] == b'Q' {
+            let mut response = Vec::new();
+            push_email_result(&mut response, "SELECT 1");
+            stream.write_all(&response).await?;
+        }
+    }
+}
+
+/// Sends a startup message and a simple query through the proxy, returning
+/// all response bytes up to the final ReadyForQuery.
+async fn run_test_client(addr: std::net::SocketAddr) -> Result<Vec<u8>> {
+    let mut socket = connect_as(addr, "test").await?;
+    send_query(&mut socket).await
+}
+
+/// Connects through the proxy as `user` and waits for ReadyForQuery
+async fn connect_as(addr: std::net::SocketAddr, user: &str) -> Result<TcpStream> {
+    let mut socket = TcpStream::connect(addr).await?;
+
+    // StartupMessage: protocol 3.0
+    let mut params = Vec::new();
+    params.extend_from_slice(&196608u32.to_be_bytes());
+    params.extend_from_slice(b"user\x00");
+    params.extend_from_slice(user.as_bytes());
+    params.extend_from_slice(b"\x00\x00");
+    let mut startup = Vec::new();
+    startup.extend_from_slice(&((params.len() as u32 + 4).to_be_bytes()));
+    startup.extend_from_slice(&params);
+    socket.write_all(&startup).await?;
+
+    // Wait for ReadyForQuery before issuing queries
+    read_until_ready(&mut socket).await?;
+    Ok(socket)
+}
+
+/// Like [`connect_as`], but the startup message also carries an
+/// application_name parameter
+async fn connect_with_app(
+    addr: std::net::SocketAddr,
+    user: &str,
+    application_name: &str,
+) -> Result<TcpStream> {
+    let mut socket = TcpStream::connect(addr).await?;
+
+    let mut params = Vec::new();
+    params.extend_from_slice(&196608u32.to_be_bytes());
+    params.extend_from_slice(b"user\x00");
+    params.extend_from_slice(user.as_bytes());
+    params.push(0);
+    params.extend_from_slice(b"application_name\x00");
+    params.extend_from_slice(application_name.as_bytes());
+    params.extend_from_slice(b"\x00\x00");
+    let mut startup = Vec::new();
+    startup.extend_from_slice(&((params.len() as u32 + 4).to_be_bytes()));
+    startup.extend_from_slice(&params);
+    socket.write_all(&startup).await?;
+
+    read_until_ready(&mut socket).await?;
+    Ok(socket)
+}
+
+/// Like [`connect_as`], but the startup message also names a database
+async fn connect_with_database(
+    addr: std::net::SocketAddr,
+    user: &str,
+    database: &str,
+) -> Result<TcpStream> {
+    let mut socket = TcpStream::connect(addr).await?;
+
+    let mut params = Vec::new();
+    params.extend_from_slice(&196608u32.to_be_bytes());
+    params.extend_from_slice(b"user\x00");
+    params.extend_from_slice(user.as_bytes());
+    params.push(0);
+    params.extend_from_slice(b"database\x00");
+    params.extend_from_slice(database.as_bytes());
+    params.extend_from_slice(b"\x00\x00");
+    let mut startup = Vec::new();
+    startup.extend_from_slice(&((params.len() as u32 + 4).to_be_bytes()));
+    startup.extend_from_slice(&params);
+    socket.write_all(&startup).await?;
+
+    read_until_ready(&mut socket).await?;
+    Ok(socket)
+}
+
+/// Sends a simple query on an established session, returning all response
+/// bytes up to the final ReadyForQuery
+async fn send_query(socket: &mut TcpStream) -> Result<Vec<u8>> {
+    send_simple(socket, "SELECT email FROM users").await
+}
+
+/// Sends `sql` as a simple query, returning all response bytes up to the
+/// final ReadyForQuery
+async fn send_simple(socket: &mut TcpStream, sql: &str) -> Result<Vec<u8>> {
+    let mut query = Vec::new();
+    push_msg(&mut query, b'Q', format!("{}\x00", sql).as_bytes());
+    socket.write_all(&query).await?;
+
+    read_until_ready(socket).await
+}
+
+/// Sends `sql` through the extended protocol (Parse, Bind, Describe,
+/// Execute, Sync), returning all response bytes up to ReadyForQuery
+async fn send_extended(socket: &mut TcpStream, sql: &str) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    // Parse: unnamed statement, no parameter types
+    let mut parse = Vec::new();
+    parse.push(0); // statement name ""
+    parse.extend_from_slice(sql.as_bytes());
+    parse.push(0);
+    parse.extend_from_slice(&0u16.to_be_bytes());
+    push_msg(&mut buf, b'P', &parse);
+    // Bind: unnamed portal from the unnamed statement, no parameters
+    let mut bind = Vec::new();
+    bind.push(0); // portal ""
+    bind.push(0); // statement ""
+    bind.extend_from_slice(&0u16.to_be_bytes()); // format codes
+    bind.extend_from_slice(&0u16.to_be_bytes()); // parameters
+    bind.extend_from_slice(&0u16.to_be_bytes()); // result format codes
+    push_msg(&mut buf, b'B', &bind);
+    // Describe the unnamed portal
+    push_msg(&mut buf, b'D', b"P\x00");
+    // Execute: no row limit
+    let mut execute = Vec::new();
+    execute.push(0); // portal ""
+    execute.extend_from_slice(&0u32.to_be_bytes());
+    push_msg(&mut buf, b'E', &execute);
+    push_msg(&mut buf, b'S', b"");
+    socket.write_all(&buf).await?;
+
+    read_until_ready(socket).await
+}
+
+/// Reads backend messages until ReadyForQuery, returning everything read
+async fn read_until_ready(socket: &mut TcpStream) -> Result<Vec<u8>> {
+    let mut collected = Vec::new();
+    loop {
+        let mut type_buf = [0u8; 1];
+        socket.read_exact(&mut type_buf).await?;
+        let mut len_buf = [0u8; 4];
+        socket.read_exact(&mut len_buf).await?;
+        let len = u32::from_be_bytes(len_buf) as usize;
+        let mut payload = vec![0u8; len - 4];
+        socket.read_exact(&mut payload).await?;
+
+        collected.push(type_buf[0]);
+        collected.extend_from_slice(&len_buf);
+        collected.extend_from_slice(&payload);
+
+        if type_buf[0] == b'Z' {
+            return Ok(collected);
+        }
+    }
+}
+
+/// Reads until the peer closes the connection, returning everything
+/// received. A reset ends the read the same way as a clean close.
+async fn read_to_close(socket: &mut TcpStream) -> Vec<u8> {
+    let mut received = Vec::new();
+    let mut buf = [0u8; 1024];
+    loop {
+        match socket.read(&mut buf).await {
+            Ok(0) | Err(_) => return received,
+            Ok(n) => received.extend_from_slice(&buf[..n]),
+        }
+    }
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+/// Counts backend messages of `msg_type` in a captured response stream
+fn count_messages(bytes: &[u8], msg_type: u8) -> usize {
+    let mut count = 0;
+    let mut i = 0;
+    while i + 5 <= bytes.len() {
+        let len = u32::from_be_bytes(bytes[i + 1..i + 5].try_into().unwrap()) as usize;
+        if bytes[i] == msg_type {
+            count += 1;
+        }
+        i += 1 + len;
+    }
+    count
+}
+
+/// Default config with upstream health checks disabled
+fn test_config() -> AppConfig {
+    AppConfig {
+        health_check: Some(HealthCheckConfig {
+            enabled: false,
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_embedded_proxy_with_custom_interceptor() {
+    // Scripted upstream on an ephemeral port
+    let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+    tokio::spawn(run_fake_upstream(upstream_listener));
+
+    let rows_seen = Arc::new(AtomicUsize::new(0));
+    let config = test_config();
+
+    let handle = ProxyServer::builder(config)
+        .listen_port(0)
+        .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+        .protocol(DbProtocol::Postgres)
+        .interceptors(MarkerFactory {
+            rows_seen: rows_seen.clone(),
+        })
+        .serve()
+        .await
+        .expect("proxy failed to start");
+
+    let response = timeout(TEST_TIMEOUT, run_test_client(handle.local_addr()))
+        .await
+        .expect("client timed out")
+        .expect("client failed");
+
+    assert!(
+        contains(&response, b"INTERCEPTED"),
+        "custom interceptor did not rewrite the data row"
+    );
+    assert!(
+        !contains(&response, b"test@example.com"),
+        "original value leaked through the proxy"
+    );
+    assert_eq!(rows_seen.load(Ordering::Relaxed), 1);
+
+    // The proxy should have captured the upstream version from ParameterStatus
+    let version = handle.state().get_upstream_version().await;
+    assert!(version.is_some(), "upstream version was not captured");
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+#[tokio::test]
+async fn test_embedded_proxy_hook_rejects_username() {
+    let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+    tokio::spawn(run_fake_upstream(upstream_listener));
+
+    let handle = ProxyServer::builder(test_config())
+        .listen_port(0)
+        .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+        .protocol(DbProtocol::Postgres)
+        .hook(UserPolicy::deny_users(["blocked"]))
+        .serve()
+        .await
+        .expect("proxy failed to start");
+
+    let mut socket = TcpStream::connect(handle.local_addr()).await.unwrap();
+
+    // StartupMessage: protocol 3.0, user=blocked
+    let mut params = Vec::new();
+    params.extend_from_slice(&196608u32.to_be_bytes());
+    params.extend_from_slice(b"user\x00blocked\x00\x00");
+    let mut startup = Vec::new();
+    startup.extend_from_slice(&((params.len() as u32 + 4).to_be_bytes()));
+    startup.extend_from_slice(&params);
+    socket.write_all(&startup).await.unwrap();
+
+    // Expect a single ErrorResponse followed by EOF
+    let mut response = Vec::new();
+    timeout(TEST_TIMEOUT, socket.read_to_end(&mut response))
+        .await
+        .expect("client timed out")
+        .expect("read failed");
+
+    assert_eq!(response.first(), Some(&b'E'), "expected an ErrorResponse");
+    assert!(contains(&response, b"28000"), "expected SQLSTATE 28000");
+    assert!(
+        contains(&response, b"denied by policy"),
+        "expected the hook's rejection message"
+    );
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+/// Config capping results at `max_rows` rows, with "svc" exempt
+fn row_limited_config(max_rows: u64) -> AppConfig {
+    AppConfig {
+        limits: Some(LimitsConfig {
+            max_result_rows: Some(max_rows),
+            result_limit_exempt_users: vec!["svc".to_string()],
+            ..Default::default()
+        }),
+        ..test_config()
+    }
+}
+
+/// Spawns a proxy in front of a scripted upstream that answers every query
+/// with `upstream_rows` rows, returning the running handle
+async fn spawn_row_limited_proxy(
+    upstream_rows: usize,
+    max_rows: u64,
+) -> iron_veil::proxy::ProxyHandle {
+    let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+    tokio::spawn(run_fake_upstream_rows(
+        upstream_listener,
+        upstream_rows,
+        b"payload",
+    ));
+
+    ProxyServer::builder(row_limited_config(max_rows))
+        .listen_port(0)
+        .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+        .protocol(DbProtocol::Postgres)
+        .serve()
+        .await
+        .expect("proxy failed to start")
+}
+
+#[tokio::test]
+async fn test_result_limit_not_hit_at_exact_boundary() {
+    // A result landing exactly on the cap passes through untouched
+    let handle = spawn_row_limited_proxy(3, 3).await;
+
+    let mut socket = timeout(TEST_TIMEOUT, connect_as(handle.local_addr(), "analyst"))
+        .await
+        .expect("client timed out")
+        .expect("connect failed");
+    let response = timeout(TEST_TIMEOUT, send_query(&mut socket))
+        .await
+        .expect("client timed out")
+        .expect("query failed");
+
+    assert_eq!(count_messages(&response, b'D'), 3);
+    assert_eq!(count_messages(&response, b'N'), 0, "unexpected notice");
+    assert!(contains(&response, b"SELECT 3"));
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+#[tokio::test]
+async fn test_result_limit_truncates_and_session_continues() {
+    let handle = spawn_row_limited_proxy(5, 2).await;
+
+    let mut socket = timeout(TEST_TIMEOUT, connect_as(handle.local_addr(), "analyst"))
+        .await
+        .expect("client timed out")
+        .expect("connect failed");
+    let response = timeout(TEST_TIMEOUT, send_query(&mut socket))
+        .await
+        .expect("client timed out")
+        .expect("query failed");
+
+    // Only the capped prefix is forwarded, with a notice and a tag
+    // carrying the forwarded count
+    assert_eq!(count_messages(&response, b'D'), 2, "expected 2 rows");
+    assert_eq!(count_messages(&response, b'N'), 1, "expected a notice");
+    assert!(contains(&response, b"result truncated by proxy"));
+    assert!(contains(&response, b"01000"));
+    assert!(contains(&response, b"SELECT 2"));
+    assert!(!contains(&response, b"SELECT 5"));
+
+    // The session stays usable: a follow-up query on the same connection
+    // gets a complete (equally truncated) response
+    let second = timeout(TEST_TIMEOUT, send_query(&mut socket))
+        .await
+        .expect("client timed out")
+        .expect("second query failed");
+    assert_eq!(count_messages(&second, b'D'), 2);
+    assert!(contains(&second, b"SELECT 2"));
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+#[tokio::test]
+async fn test_result_limit_exempts_listed_user() {
+    let handle = spawn_row_limited_proxy(5, 2).await;
+
+    let mut socket = timeout(TEST_TIMEOUT, connect_as(handle.local_addr(), "svc"))
+        .await
+        .expect("client timed out")
+        .expect("connect failed");
+    let response = timeout(TEST_TIMEOUT, send_query(&mut socket))
+        .await
+        .expect("client timed out")
+        .expect("query failed");
+
+    assert_eq!(count_messages(&response, b'D'), 5, "exempt user was capped");
+    assert_eq!(count_messages(&response, b'N'), 0, "unexpected notice");
+    assert!(contains(&response, b"SELECT 5"));
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+/// Config whose single source policy applies `sampling` to every local
+/// client
+fn sampled_config(sampling: SamplingConfig) -> AppConfig {
+    AppConfig {
+        policies_by_source: vec![SourcePolicy {
+            cidr: Some("127.0.0.1/32".to_string()),
+            application_name: None,
+            parameters: Default::default(),
+            action: PolicyAction::Mask,
+            priority: 0,
+            sampling: Some(sampling),
+        }],
+        ..test_config()
+    }
+}
+
+/// Like [`run_fake_upstream`], but serves two scripted "tables" over the
+/// same ten users: queries naming `orders` get `(note, user_id)` rows,
+/// everything else gets `(user_id, email)` rows
+async fn run_fake_upstream_keyed(listener: TcpListener) -> Result<()> {
+    let (mut socket, _) = listener.accept().await?;
+
+    let mut len_buf = [0u8; 4];
+    socket.read_exact(&mut len_buf).await?;
+    let len = u32::from_be_bytes(len_buf) as usize;
+    let mut startup = vec![0u8; len - 4];
+    socket.read_exact(&mut startup).await?;
+
+    let mut greeting = Vec::new();
+    push_msg(&mut greeting, b'R', &0u32.to_be_bytes());
+    push_msg(&mut greeting, b'S', b"server_version\x0016.3\x00");
+    push_msg(&mut greeting, b'Z', b"I");
+    socket.write_all(&greeting).await?;
+
+    fn push_text_column(row_desc: &mut Vec<u8>, name: &[u8]) {
+        row_desc.extend_from_slice(name);
+        row_desc.push(0);
+        row_desc.extend_from_slice(&0u32.to_be_bytes()); // table oid
+        row_desc.extend_from_slice(&0u16.to_be_bytes()); // column index
+        row_desc.extend_from_slice(&25u32.to_be_bytes()); // type oid (text)
+        row_desc.extend_from_slice(&(-1i16).to_be_bytes()); // type len
+        row_desc.extend_from_slice(&(-1i32).to_be_bytes()); // type modifier
+        row_desc.extend_from_slice(&0u16.to_be_bytes()); // format code
+    }
+
+    loop {
+        let mut type_buf = [0u8; 1];
+        if socket.read_exact(&mut type_buf).await.is_err() {
+            return Ok(()); // client disconnected
+        }
+        socket.read_exact(&mut len_buf).await?;
+        let len = u32::from_be_bytes(len_buf) as usize;
+        let mut payload = vec![0u8; len - 4];
+        socket.read_exact(&mut payload).await?;
+
+        if type_buf[0] != b'Q' {
+            continue;
+        }
+        let orders = contains(&payload, b"orders");
+
+        let mut response = Vec::new();
+        let mut row_desc = Vec::new();
+        row_desc.extend_from_slice(&2u16.to_be_bytes());
+        if orders {
+            push_text_column(&mut row_desc, b"note");
+            push_text_column(&mut row_desc, b"user_id");
+        } else {
+            push_text_column(&mut row_desc, b"user_id");
+            push_text_column(&mut row_desc, b"email");
+        }
+        push_msg(&mut response, b'T', &row_desc);
+
+        for i in 0..10 {
+            let user_id = format!("user-{}", i);
+            let (first, second) = if orders {
+                (format!("note-{}", i), user_id)
+            } else {
+                (user_id, format!("u{}@example.com", i))
+            };
+            let mut data_row = Vec::new();
+            data_row.extend_from_slice(&2u16.to_be_bytes());
+            for value in [first.as_bytes(), second.as_bytes()] {
+                data_row.extend_from_slice(&(value.len() as u32).to_be_bytes());
+                data_row.extend_from_slice(value);
+            }
+            push_msg(&mut response, b'D', &data_row);
+        }
+        push_msg(&mut response, b'C', b"SELECT 10\x00");
+        push_msg(&mut response, b'Z', b"I");
+        socket.write_all(&response).await?;
+    }
+}
+
+/// Extracts column `idx` of every DataRow in a raw response
+fn data_row_column(bytes: &[u8], idx: usize) -> Vec<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i + 5 <= bytes.len() {
+        let len = u32::from_be_bytes(bytes[i + 1..i + 5].try_into().unwrap()) as usize;
+        let payload = &bytes[i + 5..i + 1 + len];
+        if bytes[i] == b'D' {
+            let columns = u16::from_be_bytes(payload[0..2].try_into().unwrap()) as usize;
+            let mut j = 2;
+            for column in 0..columns {
+                let field_len = i32::from_be_bytes(payload[j..j + 4].try_into().unwrap());
+                j += 4;
+                if field_len < 0 {
+                    continue; // NULL
+                }
+                if column == idx {
+                    out.push(payload[j..j + field_len as usize].to_vec());
+                }
+                j += field_len as usize;
+            }
+        }
+        i += 1 + len;
+    }
+    out
+}
+
+#[tokio::test]
+async fn test_sampling_caps_rows_and_session_continues() {
+    let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+    tokio::spawn(run_fake_upstream_rows(upstream_listener, 5, b"payload"));
+
+    let handle = ProxyServer::builder(sampled_config(SamplingConfig {
+        percent: None,
+        key_column: None,
+        max_rows_per_result: Some(2),
+    }))
+    .listen_port(0)
+    .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+    .protocol(DbProtocol::Postgres)
+    .serve()
+    .await
+    .expect("proxy failed to start");
+
+    let mut socket = timeout(TEST_TIMEOUT, connect_as(handle.local_addr(), "analyst"))
+        .await
+        .expect("client timed out")
+        .expect("connect failed");
+    let response = timeout(TEST_TIMEOUT, send_query(&mut socket))
+        .await
+        .expect("client timed out")
+        .expect("query failed");
+
+    // Only the sampled rows are forwarded, with a notice and a tag carrying
+    // the forwarded count
+    assert_eq!(count_messages(&response, b'D'), 2, "expected 2 rows");
+    assert_eq!(count_messages(&response, b'N'), 1, "expected a notice");
+    assert!(contains(&response, b"result sampled by proxy policy"));
+    assert!(contains(&response, b"01000"));
+    assert!(contains(&response, b"SELECT 2"));
+    assert!(!contains(&response, b"SELECT 5"));
+
+    // The session stays usable afterwards
+    let second = timeout(TEST_TIMEOUT, send_query(&mut socket))
+        .await
+        .expect("client timed out")
+        .expect("second query failed");
+    assert_eq!(count_messages(&second, b'D'), 2);
+    assert!(contains(&second, b"SELECT 2"));
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+#[tokio::test]
+async fn test_sampling_percent_keeps_same_users_across_tables() {
+    let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+    tokio::spawn(run_fake_upstream_keyed(upstream_listener));
+
+    let handle = ProxyServer::builder(sampled_config(SamplingConfig {
+        percent: Some(40),
+        key_column: Some("user_id".to_string()),
+        max_rows_per_result: None,
+    }))
+    .listen_port(0)
+    .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+    .protocol(DbProtocol::Postgres)
+    .serve()
+    .await
+    .expect("proxy failed to start");
+
+    let mut socket = timeout(TEST_TIMEOUT, connect_as(handle.local_addr(), "analyst"))
+        .await
+        .expect("client timed out")
+        .expect("connect failed");
+    let users = timeout(TEST_TIMEOUT, send_simple(&mut socket, "SELECT * FROM users"))
+        .await
+        .expect("client timed out")
+        .expect("users query failed");
+    let orders = timeout(TEST_TIMEOUT, send_simple(&mut socket, "SELECT * FROM orders"))
+        .await
+        .expect("client timed out")
+        .expect("orders query failed");
+
+    // The thinning is a proper subset, keyed on user_id even though the
+    // column sits at a different position in each result set, so both
+    // "tables" keep exactly the same users
+    let kept_users = data_row_column(&users, 0);
+    let kept_orders = data_row_column(&orders, 1);
+    assert!(!kept_users.is_empty(), "sampling kept no rows");
+    assert!(kept_users.len() < 10, "sampling kept every row");
+    assert_eq!(kept_users, kept_orders);
+    assert!(contains(&users, b"result sampled by proxy policy"));
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+/// Config with an email masking rule, for RETURNING tests through the
+/// default anonymizer
+fn email_rule_config() -> AppConfig {
+    AppConfig {
+        rules: vec![MaskingRule {
+            id: None,
+            table: None,
+            column: "email".to_string(),
+            case_sensitive: false,
+            table_regex: None,
+            column_regex: None,
+            database: None,
+            compiled: Default::default(),
+            redact_text: None,
+            preserve_length: false,
+            date_shift_days: None,
+            seed_column: None,
+            noise_percent: None,
+            ip_mode: None,
+            preserve_domain: false,
+            domain_allowlist: Vec::new(),
+            json_paths: None,
+            pattern: None,
+            replacement: None,
+            wasm_module: None,
+            wasm_fuel: None,
+            deterministic: true,
+            strategy: Strategy::Email.into(),
+            composite_fields: None,
+            on_type_mismatch: TypeMismatchPolicy::Fallback,
+            when: None,
+        }],
+        ..test_config()
+    }
+}
+
+/// Spawns a proxy with the default anonymizer in front of the RETURNING
+/// upstream, returning the running handle
+async fn spawn_returning_proxy() -> iron_veil::proxy::ProxyHandle {
+    let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+    tokio::spawn(run_fake_upstream_returning(upstream_listener));
+
+    ProxyServer::builder(email_rule_config())
+        .listen_port(0)
+        .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+        .protocol(DbProtocol::Postgres)
+        .serve()
+        .await
+        .expect("proxy failed to start")
+}
+
+#[tokio::test]
+async fn test_returning_masked_through_simple_protocol() {
+    let handle = spawn_returning_proxy().await;
+
+    let mut socket = timeout(TEST_TIMEOUT, connect_as(handle.local_addr(), "test"))
+        .await
+        .expect("client timed out")
+        .expect("connect failed");
+
+    // RETURNING result sets are masked regardless of the originating
+    // command, and the affected-row tag passes through untouched
+    for (sql, tag) in [
+        (
+            "INSERT INTO users (email) VALUES ('x') RETURNING email",
+            "INSERT 0 1",
+        ),
+        ("UPDATE users SET active = false RETURNING *", "UPDATE 1"),
+        ("DELETE FROM users WHERE id = 7 RETURNING email", "DELETE 1"),
+    ] {
+        let response = timeout(TEST_TIMEOUT, send_simple(&mut socket, sql))
+            .await
+            .expect("client timed out")
+            .expect("query failed");
+        assert!(
+            !contains(&response, b"test@example.com"),
+            "RETURNING row leaked unmasked for {sql}"
+        );
+        assert_eq!(count_messages(&response, b'D'), 1);
+        assert!(
+            contains(&response, tag.as_bytes()),
+            "command tag not forwarded untouched for {sql}"
+        );
+    }
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+#[tokio::test]
+async fn test_returning_masked_through_extended_protocol() {
+    let handle = spawn_returning_proxy().await;
+
+    let mut socket = timeout(TEST_TIMEOUT, connect_as(handle.local_addr(), "test"))
+        .await
+        .expect("client timed out")
+        .expect("connect failed");
+
+    let response = timeout(
+        TEST_TIMEOUT,
+        send_extended(
+            &mut socket,
+            "UPDATE users SET email = 'x' WHERE id = 1 RETURNING email",
+        ),
+    )
+    .await
+    .expect("client timed out")
+    .expect("query failed");
+
+    assert_eq!(count_messages(&response, b'1'), 1, "ParseComplete missing");
+    assert!(
+        !contains(&response, b"test@example.com"),
+        "RETURNING row leaked unmasked through the extended protocol"
+    );
+    assert_eq!(count_messages(&response, b'D'), 1);
+    assert!(
+        contains(&response, b"UPDATE 1"),
+        "affected-row tag not forwarded untouched"
+    );
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+#[tokio::test]
+async fn test_suspended_portal_resumes_masked() {
+    let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+    tokio::spawn(run_fake_upstream_portal(upstream_listener));
+
+    let handle = ProxyServer::builder(email_rule_config())
+        .listen_port(0)
+        .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+        .protocol(DbProtocol::Postgres)
+        .serve()
+        .await
+        .expect("proxy failed to start");
+
+    let mut socket = timeout(TEST_TIMEOUT, connect_as(handle.local_addr(), "test"))
+        .await
+        .expect("client timed out")
+        .expect("connect failed");
+
+    // Parse/Bind/Describe, then Execute with a row limit of 1 so the
+    // upstream suspends the portal after the first row
+    let mut buf = Vec::new();
+    let mut parse = Vec::new();
+    parse.push(0); // statement name ""
+    parse.extend_from_slice(b"SELECT email FROM users ");
+    parse.extend_from_slice(&0u16.to_be_bytes());
+    push_msg(&mut buf, b'P', &parse);
+    let mut bind = Vec::new();
+    bind.push(0); // portal ""
+    bind.push(0); // statement ""
+    bind.extend_from_slice(&0u16.to_be_bytes());
+    bind.extend_from_slice(&0u16.to_be_bytes());
+    bind.extend_from_slice(&0u16.to_be_bytes());
+    push_msg(&mut buf, b'B', &bind);
+    push_msg(&mut buf, b'D', b"P ");
+    let mut execute = Vec::new();
+    execute.push(0); // portal ""
+    execute.extend_from_slice(&1u32.to_be_bytes()); // max one row
+    push_msg(&mut buf, b'E', &execute);
+    push_msg(&mut buf, b'S', b"");
+    socket.write_all(&buf).await.unwrap();
+
+    let first = timeout(TEST_TIMEOUT, read_until_ready(&mut socket))
+        .await
+        .expect("client timed out")
+        .expect("first execute failed");
+    assert_eq!(count_messages(&first, b's'), 1, "PortalSuspended missing");
+    assert_eq!(count_messages(&first, b'D'), 1);
+    assert!(
+        !contains(&first, b"test@example.com"),
+        "first portal batch leaked unmasked"
+    );
+
+    // Resume the suspended portal: no new Parse/Bind/Describe, so the rows
+    // must still be masked under the column metadata bound above
+    let mut buf = Vec::new();
+    let mut execute = Vec::new();
+    execute.push(0); // portal ""
+    execute.extend_from_slice(&0u32.to_be_bytes());
+    push_msg(&mut buf, b'E', &execute);
+    push_msg(&mut buf, b'S', b"");
+    socket.write_all(&buf).await.unwrap();
+
+    let resumed = timeout(TEST_TIMEOUT, read_until_ready(&mut socket))
+        .await
+        .expect("client timed out")
+        .expect("resumed execute failed");
+    assert_eq!(count_messages(&resumed, b'D'), 1);
+    assert!(
+        !contains(&resumed, b"test@example.com"),
+        "resumed portal row leaked unmasked"
+    );
+    assert!(contains(&resumed, b"SELECT 2"), "command tag missing");
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+#[tokio::test]
+async fn test_masking_bypass_user_gets_raw_rows() {
+    // Upstream keeps accepting so both sessions run through one proxy
+    let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+    tokio::spawn(run_fake_upstream_multi(upstream_listener));
+
+    let config = AppConfig {
+        masking: Some(MaskingConfig {
+            determinism_key: None,
+            heuristics_enabled: true,
+            bypass_users: vec!["etl_service".to_string()],
+            defaults: Default::default(),
+        }),
+        ..email_rule_config()
+    };
+    let handle = ProxyServer::builder(config)
+        .listen_port(0)
+        .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+        .protocol(DbProtocol::Postgres)
+        .serve()
+        .await
+        .expect("proxy failed to start");
+
+    // An ordinary user still gets the email rule applied
+    let mut masked = timeout(TEST_TIMEOUT, connect_as(handle.local_addr(), "analyst"))
+        .await
+        .expect("client timed out")
+        .expect("connect failed");
+    let response = timeout(TEST_TIMEOUT, send_query(&mut masked))
+        .await
+        .expect("client timed out")
+        .expect("query failed");
+    assert!(
+        !contains(&response, b"test@example.com"),
+        "non-bypass user saw the raw value"
+    );
+
+    // The listed service account reads the row exactly as the upstream
+    // sent it
+    let mut bypassed = timeout(TEST_TIMEOUT, connect_as(handle.local_addr(), "etl_service"))
+        .await
+        .expect("client timed out")
+        .expect("connect failed");
+    let response = timeout(TEST_TIMEOUT, send_query(&mut bypassed))
+        .await
+        .expect("client timed out")
+        .expect("query failed");
+    assert!(
+        contains(&response, b"test@example.com"),
+        "bypass user's rows were still masked"
+    );
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+#[tokio::test]
+async fn test_gssenc_and_ssl_requests_denied_before_startup() {
+    let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+    tokio::spawn(run_fake_upstream(upstream_listener));
+
+    let handle = ProxyServer::builder(email_rule_config())
+        .listen_port(0)
+        .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+        .protocol(DbProtocol::Postgres)
+        .serve()
+        .await
+        .expect("proxy failed to start");
+
+    let mut socket = TcpStream::connect(handle.local_addr()).await.unwrap();
+
+    // GSSENCRequest first, as libpq with gssencmode=prefer sends it
+    let mut gssenc = Vec::new();
+    gssenc.extend_from_slice(&8u32.to_be_bytes());
+    gssenc.extend_from_slice(&80877104u32.to_be_bytes());
+    socket.write_all(&gssenc).await.unwrap();
+    let mut answer = [0u8; 1];
+    timeout(TEST_TIMEOUT, socket.read_exact(&mut answer))
+        .await
+        .expect("no answer to GSSENCRequest")
+        .unwrap();
+    assert_eq!(&answer, b"N", "GSSENCRequest must be denied");
+
+    // The fallback SSLRequest is denied the same way
+    let mut ssl = Vec::new();
+    ssl.extend_from_slice(&8u32.to_be_bytes());
+    ssl.extend_from_slice(&80877103u32.to_be_bytes());
+    socket.write_all(&ssl).await.unwrap();
+    timeout(TEST_TIMEOUT, socket.read_exact(&mut answer))
+        .await
+        .expect("no answer to SSLRequest")
+        .unwrap();
+    assert_eq!(&answer, b"N", "SSLRequest must be denied without TLS");
+
+    // ...after which the session proceeds in cleartext, masked as usual
+    let mut params = Vec::new();
+    params.extend_from_slice(&196608u32.to_be_bytes());
+    params.extend_from_slice(b"user test  ");
+    let mut startup = Vec::new();
+    startup.extend_from_slice(&((params.len() as u32 + 4).to_be_bytes()));
+    startup.extend_from_slice(&params);
+    socket.write_all(&startup).await.unwrap();
+    timeout(TEST_TIMEOUT, read_until_ready(&mut socket))
+        .await
+        .expect("startup timed out")
+        .expect("startup failed");
+
+    let response = timeout(TEST_TIMEOUT, send_query(&mut socket))
+        .await
+        .expect("query timed out")
+        .expect("query failed");
+    assert!(
+        !contains(&response, b"test@example.com"),
+        "row leaked unmasked after negotiation denials"
+    );
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+#[tokio::test]
+async fn test_upstream_tls_hop_with_pinned_ca() {
+    let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+    tokio::spawn(run_fake_tls_upstream(upstream_listener));
+
+    // The fixture certificate is issued for upstream.test, not 127.0.0.1,
+    // so the pinned-CA chain check passes while hostname checking is
+    // relaxed — exactly the shape of a dev setup against a private CA
+    let config = AppConfig {
+        upstream_tls: true,
+        upstream_tls_options: Some(UpstreamTlsConfig {
+            ca_cert_path: Some("tests/fixtures/tls/upstream_ca.pem".to_string()),
+            verify_hostname: false,
+            insecure_skip_verify: false,
+        }),
+        ..email_rule_config()
+    };
+    let handle = ProxyServer::builder(config)
+        .listen_port(0)
+        .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+        .protocol(DbProtocol::Postgres)
+        .serve()
+        .await
+        .expect("proxy failed to start");
+
+    // The client side stays plaintext; only the upstream hop is encrypted
+    let mut socket = timeout(TEST_TIMEOUT, connect_as(handle.local_addr(), "test"))
+        .await
+        .expect("client timed out")
+        .expect("connect failed");
+    let response = timeout(TEST_TIMEOUT, send_query(&mut socket))
+        .await
+        .expect("query timed out")
+        .expect("query failed");
+
+    assert!(
+        !contains(&response, b"test@example.com"),
+        "row leaked unmasked across the TLS upstream hop"
+    );
+    assert_eq!(count_messages(&response, b'D'), 1);
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+#[tokio::test]
+async fn test_embedded_proxy_shutdown_without_connections() {
+    let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+
+    let config = test_config();
+
+    let handle = ProxyServer::builder(config)
+        .listen_port(0)
+        .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+        .serve()
+        .await
+        .expect("proxy failed to start");
+
+    assert_ne!(handle.local_addr().port(), 0);
+
+    timeout(TEST_TIMEOUT, handle.shutdown_and_wait(Duration::from_secs(1)))
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+#[tokio::test]
+async fn test_application_name_scoped_policies() {
+    let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+    tokio::spawn(run_fake_upstream_multi(upstream_listener));
+
+    // Reporting tools see real data; everything else gets the default full
+    // masking. application_name is pinned at connect time (the default).
+    let config = AppConfig {
+        policies_by_source: vec![SourcePolicy {
+            cidr: None,
+            application_name: Some("report*".to_string()),
+            parameters: Default::default(),
+            action: PolicyAction::Unmasked,
+            priority: 10,
+            sampling: None,
+        }],
+        ..email_rule_config()
+    };
+
+    let handle = ProxyServer::builder(config)
+        .listen_port(0)
+        .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+        .serve()
+        .await
+        .expect("proxy failed to start");
+    let addr = handle.local_addr();
+
+    // Two connections differing only in application_name
+    let mut reporting = timeout(TEST_TIMEOUT, connect_with_app(addr, "test", "reporting-suite"))
+        .await
+        .expect("connect timed out")
+        .expect("connect failed");
+    let response = timeout(TEST_TIMEOUT, send_query(&mut reporting))
+        .await
+        .expect("query timed out")
+        .expect("query failed");
+    assert!(
+        contains(&response, b"test@example.com"),
+        "unmasked policy for the reporting tool was not applied"
+    );
+
+    let mut webapp = timeout(TEST_TIMEOUT, connect_with_app(addr, "test", "webapp"))
+        .await
+        .expect("connect timed out")
+        .expect("connect failed");
+    let response = timeout(TEST_TIMEOUT, send_query(&mut webapp))
+        .await
+        .expect("query timed out")
+        .expect("query failed");
+    assert!(
+        !contains(&response, b"test@example.com"),
+        "default masking was bypassed for the webapp connection"
+    );
+
+    // A mid-session SET cannot move the pinned connection onto the
+    // privileged policy
+    timeout(
+        TEST_TIMEOUT,
+        send_simple(&mut webapp, "SET application_name TO 'reporting-suite'"),
+    )
+    .await
+    .expect("SET timed out")
+    .expect("SET failed");
+    let response = timeout(TEST_TIMEOUT, send_query(&mut webapp))
+        .await
+        .expect("query timed out")
+        .expect("query failed");
+    assert!(
+        !contains(&response, b"test@example.com"),
+        "a SET application_name moved a pinned connection onto another policy"
+    );
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+#[tokio::test]
+async fn test_database_routing_to_multiple_upstreams() {
+    // Two scripted upstreams answering with distinct markers, so the test
+    // can tell which one served a session
+    let analytics_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let analytics_addr = analytics_listener.local_addr().unwrap();
+    tokio::spawn(run_fake_upstream_rows(
+        analytics_listener,
+        1,
+        b"served-by-analytics",
+    ));
+    let app_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let app_addr = app_listener.local_addr().unwrap();
+    tokio::spawn(run_fake_upstream_rows(app_listener, 1, b"served-by-app"));
+
+    let config = AppConfig {
+        routing: Some(RoutingConfig {
+            upstreams: [
+                (
+                    "analytics".to_string(),
+                    UpstreamTarget {
+                        host: analytics_addr.ip().to_string(),
+                        port: analytics_addr.port(),
+                        user: None,
+                        protocol: None,
+                    },
+                ),
+                (
+                    "app".to_string(),
+                    UpstreamTarget {
+                        host: app_addr.ip().to_string(),
+                        port: app_addr.port(),
+                        user: None,
+                        protocol: None,
+                    },
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            by_database: vec![
+                DatabaseRoute {
+                    database: "analytics".to_string(),
+                    upstream: "analytics".to_string(),
+                },
+                DatabaseRoute {
+                    database: "app*".to_string(),
+                    upstream: "app".to_string(),
+                },
+            ],
+            default_upstream: None,
+            unmatched: UnmatchedDatabase::Reject,
+        }),
+        ..test_config()
+    };
+
+    let registry = Arc::new(ConnectionRegistry::new());
+    let handle = ProxyServer::builder(config)
+        .listen_port(0)
+        // The builder upstream is never dialed: every session either
+        // matches a route or is rejected
+        .upstream("127.0.0.1", 1)
+        .hook(registry.clone())
+        .serve()
+        .await
+        .expect("proxy failed to start");
+    let addr = handle.local_addr();
+
+    let mut analytics = timeout(TEST_TIMEOUT, connect_with_database(addr, "test", "analytics"))
+        .await
+        .expect("connect timed out")
+        .expect("connect failed");
+    let response = timeout(TEST_TIMEOUT, send_query(&mut analytics))
+        .await
+        .expect("query timed out")
+        .expect("query failed");
+    assert!(
+        contains(&response, b"served-by-analytics"),
+        "analytics session was not routed to the analytics upstream"
+    );
+
+    let mut app = timeout(TEST_TIMEOUT, connect_with_database(addr, "test", "app_eu"))
+        .await
+        .expect("connect timed out")
+        .expect("connect failed");
+    let response = timeout(TEST_TIMEOUT, send_query(&mut app))
+        .await
+        .expect("query timed out")
+        .expect("query failed");
+    assert!(
+        contains(&response, b"served-by-app"),
+        "app session was not routed to the app upstream"
+    );
+
+    // The registry saw both sessions under their route names
+    let mut routes: Vec<Option<String>> = registry
+        .active_sessions()
+        .await
+        .into_iter()
+        .map(|s| s.route)
+        .collect();
+    routes.sort();
+    assert_eq!(
+        routes,
+        vec![Some("analytics".to_string()), Some("app".to_string())]
+    );
+
+    // A database matching no route is refused before any upstream is dialed
+    let rejected = timeout(
+        TEST_TIMEOUT,
+        connect_with_database(addr, "test", "payments"),
+    )
+    .await
+    .expect("connect timed out");
+    assert!(
+        rejected.is_err(),
+        "session for an unrouted database was not rejected"
+    );
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+
+#[tokio::test]
+async fn test_listen_on_v4_and_v6_loopback() {
+    let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+    tokio::spawn(run_fake_upstream_multi(upstream_listener));
+
+    let config = AppConfig {
+        listen: Some(ListenConfig {
+            addresses: vec!["127.0.0.1:0".to_string(), "[::1]:0".to_string()],
+            require_all: true,
+            reuse_port: false,
+        }),
+        ..test_config()
+    };
+
+    let registry = Arc::new(ConnectionRegistry::new());
+    let handle = ProxyServer::builder(config)
+        .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+        .hook(registry.clone())
+        .serve()
+        .await
+        .expect("proxy failed to start");
+    let addrs = handle.local_addrs().to_vec();
+    assert_eq!(addrs.len(), 2);
+    assert!(addrs[0].is_ipv4());
+    assert!(addrs[1].is_ipv6());
+
+    // One session per family, held open so the registry still has both
+    let mut v4 = timeout(TEST_TIMEOUT, connect_as(addrs[0], "v4user"))
+        .await
+        .expect("connect timed out")
+        .expect("v4 connect failed");
+    let mut v6 = timeout(TEST_TIMEOUT, connect_as(addrs[1], "v6user"))
+        .await
+        .expect("connect timed out")
+        .expect("v6 connect failed");
+    for socket in [&mut v4, &mut v6] {
+        let response = timeout(TEST_TIMEOUT, send_query(socket))
+            .await
+            .expect("query timed out")
+            .expect("query failed");
+        assert_eq!(count_messages(&response, b'D'), 1);
+    }
+
+    // The registry recorded which listener accepted each session
+    for session in registry.active_sessions().await {
+        let expected = match session.username.as_deref() {
+            Some("v4user") => addrs[0],
+            Some("v6user") => addrs[1],
+            other => panic!("unexpected session user {:?}", other),
+        };
+        assert_eq!(session.listener_addr, Some(expected));
+    }
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+#[tokio::test]
+async fn test_listen_require_all_controls_partial_binds() {
+    let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+    tokio::spawn(run_fake_upstream_multi(upstream_listener));
+
+    // 203.0.113.0/24 (TEST-NET-3) is never assigned locally, so binding it
+    // fails. With require_all the whole startup fails, naming the address
+    let config = AppConfig {
+        listen: Some(ListenConfig {
+            addresses: vec!["203.0.113.1:1".to_string(), "127.0.0.1:0".to_string()],
+            require_all: true,
+            reuse_port: false,
+        }),
+        ..test_config()
+    };
+    let error = match ProxyServer::builder(config)
+        .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+        .serve()
+        .await
+    {
+        Err(e) => e,
+        Ok(_) => panic!("startup succeeded despite an unbindable address"),
+    };
+    assert!(
+        error.to_string().contains("203.0.113.1:1"),
+        "bind error does not name the failing address: {}",
+        error
+    );
+
+    // Without require_all the proxy continues on the subset that bound
+    let config = AppConfig {
+        listen: Some(ListenConfig {
+            addresses: vec!["203.0.113.1:1".to_string(), "127.0.0.1:0".to_string()],
+            require_all: false,
+            reuse_port: false,
+        }),
+        ..test_config()
+    };
+    let handle = ProxyServer::builder(config)
+        .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+        .serve()
+        .await
+        .expect("proxy failed to start");
+    assert_eq!(handle.local_addrs().len(), 1);
+
+    let response = timeout(TEST_TIMEOUT, run_test_client(handle.local_addr()))
+        .await
+        .expect("client timed out")
+        .expect("client failed");
+    assert_eq!(count_messages(&response, b'D'), 1);
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+
+#[tokio::test]
+async fn test_verify_output_catches_broken_custom_strategy() {
+    let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+    tokio::spawn(run_fake_upstream(upstream_listener));
+
+    // The "broken" strategy returns its input unchanged, simulating a
+    // plugin bug that leaks the original value
+    let config = AppConfig {
+        verify_output: Some(VerifyOutputConfig {
+            mode: VerifyOutputMode::Full,
+            sample_percent: 100,
+        }),
+        rules: vec![MaskingRule {
+            id: None,
+            table: None,
+            column: "email".to_string(),
+            case_sensitive: false,
+            table_regex: None,
+            column_regex: None,
+            database: None,
+            compiled: Default::default(),
+            redact_text: None,
+            preserve_length: false,
+            date_shift_days: None,
+            seed_column: None,
+            noise_percent: None,
+            ip_mode: None,
+            preserve_domain: false,
+            domain_allowlist: Vec::new(),
+            json_paths: None,
+            pattern: None,
+            replacement: None,
+            wasm_module: None,
+            wasm_fuel: None,
+            deterministic: true,
+            strategy: Strategy::Custom("broken".to_string()).into(),
+            composite_fields: None,
+            on_type_mismatch: TypeMismatchPolicy::Fallback,
+            when: None,
+        }],
+        ..test_config()
+    };
+
+    let handle = ProxyServer::builder(config)
+        .listen_port(0)
+        .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+        .strategy("broken", |value: &str, _seed| value.to_string())
+        .serve()
+        .await
+        .expect("proxy failed to start");
+
+    let response = timeout(TEST_TIMEOUT, run_test_client(handle.local_addr()))
+        .await
+        .expect("client timed out")
+        .expect("client failed");
+
+    // The guard converted the leaked value to a hard redaction
+    assert!(
+        !contains(&response, b"test@example.com"),
+        "original PII left the proxy despite the verifier"
+    );
+    assert!(
+        contains(&response, b"[REDACTED]"),
+        "leaked cell was not redacted"
+    );
+
+    // And raised the alarm
+    let alarms = handle
+        .state()
+        .audit_logger
+        .get_entries_by_type(AuditEventType::MaskingVerificationFailed, None)
+        .await;
+    assert!(!alarms.is_empty(), "no verification-failure audit entry");
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+/// A minimal scripted webhook endpoint: accepts one connection, reads the
+/// request, answers 200, and hands the raw bytes back over a channel
+async fn run_fake_webhook(
+    listener: TcpListener,
+    delivered: tokio::sync::oneshot::Sender<Vec<u8>>,
+) -> Result<()> {
+    let (mut socket, _) = listener.accept().await?;
+    let mut request = vec![0u8; 4096];
+    let n = socket.read(&mut request).await?;
+    request.truncate(n);
+    socket
+        .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+        .await?;
+    let _ = delivered.send(request);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_alert_fires_webhook_and_quarantines_column() {
+    let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+    tokio::spawn(run_fake_upstream_multi(upstream_listener));
+
+    let webhook_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let webhook_addr = webhook_listener.local_addr().unwrap();
+    let (delivered_tx, delivered_rx) = tokio::sync::oneshot::channel();
+    tokio::spawn(run_fake_webhook(webhook_listener, delivered_tx));
+
+    // No rule covers the email column, so every query produces one
+    // heuristic detection; the second tips the threshold
+    let config = AppConfig {
+        alerts: Some(AlertsConfig {
+            rules: vec![AlertRuleConfig {
+                name: "email-burst".to_string(),
+                pii_type: Some("email".to_string()),
+                threshold: 1,
+                window_secs: 60,
+                policy: None,
+                webhook: Some(format!("http://{}/alerts", webhook_addr)),
+                quarantine: true,
+            }],
+        }),
+        ..test_config()
+    };
+
+    let handle = ProxyServer::builder(config)
+        .listen_port(0)
+        .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+        .serve()
+        .await
+        .expect("proxy failed to start");
+
+    for _ in 0..2 {
+        timeout(TEST_TIMEOUT, run_test_client(handle.local_addr()))
+            .await
+            .expect("client timed out")
+            .expect("client failed");
+    }
+
+    // The webhook received the alert payload...
+    let request = timeout(TEST_TIMEOUT, delivered_rx)
+        .await
+        .expect("webhook delivery timed out")
+        .expect("webhook listener died");
+    assert!(contains(&request, b"POST /alerts HTTP/1.1"));
+    assert!(contains(&request, b"\"alert\":\"email-burst\""));
+    assert!(contains(&request, b"\"quarantined\":true"));
+
+    // ...the alert was audited...
+    let entries = handle
+        .state()
+        .audit_logger
+        .get_entries_by_type(AuditEventType::Alert, None)
+        .await;
+    assert_eq!(entries.len(), 1);
+
+    // ...and the column is quarantined: subsequent result sets carry a hard
+    // redaction instead of a masked value
+    let response = timeout(TEST_TIMEOUT, run_test_client(handle.local_addr()))
+        .await
+        .expect("client timed out")
+        .expect("client failed");
+    assert!(contains(&response, b"[REDACTED]"));
+    assert!(!contains(&response, b"test@example.com"));
+
+    // Clearing the quarantine (what POST /alerts/clear does) restores
+    // normal masking
+    handle
+        .state()
+        .quarantined_columns
+        .write()
+        .await
+        .remove("email");
+    let response = timeout(TEST_TIMEOUT, run_test_client(handle.local_addr()))
+        .await
+        .expect("client timed out")
+        .expect("client failed");
+    assert!(!contains(&response, b"[REDACTED]"));
+    assert!(!contains(&response, b"test@example.com"));
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+/// Connects to the admin pseudo-database, answering a cleartext password
+/// request with `password`, and waits for ReadyForQuery
+async fn connect_admin(
+    addr: std::net::SocketAddr,
+    user: &str,
+    database: &str,
+    password: &str,
+) -> Result<TcpStream> {
+    let mut socket = TcpStream::connect(addr).await?;
+
+    let mut params = Vec::new();
+    params.extend_from_slice(&196608u32.to_be_bytes());
+    params.extend_from_slice(b"user\x00");
+    params.extend_from_slice(user.as_bytes());
+    params.push(0);
+    params.extend_from_slice(b"database\x00");
+    params.extend_from_slice(database.as_bytes());
+    params.extend_from_slice(b"\x00\x00");
+    let mut startup = Vec::new();
+    startup.extend_from_slice(&((params.len() as u32 + 4).to_be_bytes()));
+    startup.extend_from_slice(&params);
+    socket.write_all(&startup).await?;
+
+    // AuthenticationCleartextPassword
+    let mut header = [0u8; 5];
+    socket.read_exact(&mut header).await?;
+    anyhow::ensure!(header[0] == b'R', "expected auth request, got {:?}", header[0] as char);
+    let len = u32::from_be_bytes(header[1..5].try_into().unwrap()) as usize;
+    let mut payload = vec![0u8; len - 4];
+    socket.read_exact(&mut payload).await?;
+    anyhow::ensure!(payload == 3u32.to_be_bytes(), "expected cleartext password request");
+
+    let mut answer = Vec::new();
+    push_msg(&mut answer, b'p', format!("{}\x00", password).as_bytes());
+    socket.write_all(&answer).await?;
+
+    read_until_ready(&mut socket).await?;
+    Ok(socket)
+}
+
+#[tokio::test]
+async fn test_admin_console_show_commands_over_the_wire() {
+    let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+    tokio::spawn(run_fake_upstream_multi(upstream_listener));
+
+    let config = AppConfig {
+        admin: Some(AdminConfig {
+            database_name: "iron_veil".to_string(),
+            password: Some("sekret".to_string()),
+            allow_cidr: None,
+        }),
+        rules: vec![MaskingRule {
+            id: None,
+            table: None,
+            column: "email".to_string(),
+            case_sensitive: false,
+            table_regex: None,
+            column_regex: None,
+            database: None,
+            compiled: Default::default(),
+            redact_text: None,
+            preserve_length: false,
+            date_shift_days: None,
+            seed_column: None,
+            noise_percent: None,
+            ip_mode: None,
+            preserve_domain: false,
+            domain_allowlist: Vec::new(),
+            json_paths: None,
+            pattern: None,
+            replacement: None,
+            wasm_module: None,
+            wasm_fuel: None,
+            deterministic: true,
+            strategy: Strategy::Email.into(),
+            composite_fields: None,
+            on_type_mismatch: TypeMismatchPolicy::Fallback,
+            when: None,
+        }],
+        ..test_config()
+    };
+
+    let handle = ProxyServer::builder(config)
+        .listen_port(0)
+        .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+        .serve()
+        .await
+        .expect("proxy failed to start");
+    let addr = handle.local_addr();
+
+    // A wrong password is refused before the console starts
+    let refused = timeout(TEST_TIMEOUT, connect_admin(addr, "op", "iron_veil", "wrong")).await;
+    assert!(matches!(refused, Ok(Err(_))), "wrong password was accepted");
+
+    let mut admin = timeout(TEST_TIMEOUT, connect_admin(addr, "op", "iron_veil", "sekret"))
+        .await
+        .expect("connect timed out")
+        .expect("admin connect failed");
+
+    // A data session masks as usual while the console is open
+    let mut data = timeout(TEST_TIMEOUT, connect_as(addr, "appuser"))
+        .await
+        .expect("connect timed out")
+        .expect("data connect failed");
+    let response = timeout(TEST_TIMEOUT, send_query(&mut data))
+        .await
+        .expect("query timed out")
+        .expect("query failed");
+    assert!(!contains(&response, b"test@example.com"));
+
+    // SHOW STATS renders one metric per row
+    let response = timeout(TEST_TIMEOUT, send_simple(&mut admin, "SHOW STATS;"))
+        .await
+        .expect("timed out")
+        .expect("SHOW STATS failed");
+    assert_eq!(count_messages(&response, b'T'), 1);
+    assert!(count_messages(&response, b'D') >= 5);
+    assert!(contains(&response, b"total_queries"));
+    assert!(contains(&response, b"total_masked"));
+
+    // SHOW CLIENTS lists both live sessions with their announced identity
+    let response = timeout(TEST_TIMEOUT, send_simple(&mut admin, "SHOW CLIENTS"))
+        .await
+        .expect("timed out")
+        .expect("SHOW CLIENTS failed");
+    assert_eq!(count_messages(&response, b'D'), 2);
+    assert!(contains(&response, b"appuser"));
+    assert!(contains(&response, b"iron_veil"));
+
+    // SHOW RULES renders the configured masking rules
+    let response = timeout(TEST_TIMEOUT, send_simple(&mut admin, "show rules"))
+        .await
+        .expect("timed out")
+        .expect("SHOW RULES failed");
+    assert_eq!(count_messages(&response, b'D'), 1);
+    assert!(contains(&response, b"email"));
+
+    // Unknown commands get a helpful ErrorResponse, not a dropped session
+    let response = timeout(TEST_TIMEOUT, send_simple(&mut admin, "FLUSH ALL"))
+        .await
+        .expect("timed out")
+        .expect("unknown command hung");
+    assert_eq!(count_messages(&response, b'E'), 1);
+    assert!(contains(&response, b"SHOW STATS"));
+    let response = timeout(TEST_TIMEOUT, send_simple(&mut admin, "SHOW HEALTH"))
+        .await
+        .expect("timed out")
+        .expect("session did not survive the error");
+    assert!(contains(&response, b"upstream"));
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+#[tokio::test]
+async fn test_admin_console_trusts_allow_cidr_without_password() {
+    let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+    tokio::spawn(run_fake_upstream_multi(upstream_listener));
+
+    let config = AppConfig {
+        admin: Some(AdminConfig {
+            database_name: "iron_veil".to_string(),
+            password: None,
+            allow_cidr: Some("127.0.0.0/8".to_string()),
+        }),
+        ..test_config()
+    };
+
+    let handle = ProxyServer::builder(config)
+        .listen_port(0)
+        .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+        .serve()
+        .await
+        .expect("proxy failed to start");
+
+    // Loopback is inside the allowed network, so no password exchange runs
+    let mut admin = timeout(
+        TEST_TIMEOUT,
+        connect_with_database(handle.local_addr(), "op", "iron_veil"),
+    )
+    .await
+    .expect("connect timed out")
+    .expect("admin connect failed");
+
+    let response = timeout(TEST_TIMEOUT, send_simple(&mut admin, "SHOW STATS"))
+        .await
+        .expect("timed out")
+        .expect("SHOW STATS failed");
+    assert!(contains(&response, b"active_connections"));
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+/// Two proxy instances share a port via SO_REUSEPORT while the first
+/// drains: the handover a `--upgrade-from` restart performs, driven here
+/// through the embedding API.
+#[tokio::test]
+async fn test_reuseport_handover_serves_new_connects_while_draining() {
+    let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+    tokio::spawn(run_fake_upstream_multi(upstream_listener));
+
+    let listen_config = |port: u16| {
+        Some(ListenConfig {
+            addresses: vec![format!("127.0.0.1:{}", port)],
+            require_all: true,
+            reuse_port: true,
+        })
+    };
+
+    let old = ProxyServer::builder(AppConfig {
+        listen: listen_config(0),
+        ..test_config()
+    })
+    .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+    .serve()
+    .await
+    .expect("old instance failed to start");
+    let addr = old.local_addr();
+
+    // A session established before the upgrade, held open across it
+    let mut survivor = timeout(TEST_TIMEOUT, connect_as(addr, "longlived"))
+        .await
+        .expect("connect timed out")
+        .expect("connect failed");
+
+    // The replacement binds the same address while the old one still
+    // holds it
+    let new = ProxyServer::builder(AppConfig {
+        listen: listen_config(addr.port()),
+        ..test_config()
+    })
+    .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+    .serve()
+    .await
+    .expect("replacement could not bind alongside the old instance");
+
+    // The old process stops accepting and reports the draining role
+    old.begin_drain();
+    assert!(old.state().is_draining());
+    assert!(!new.state().is_draining());
+
+    // Once its accept loop exits the old listener is closed, so the
+    // kernel routes every new connect to the replacement. Its open
+    // session keeps running: connection tasks outlive the accept loop.
+    let old_state = old.state().clone();
+    timeout(TEST_TIMEOUT, old.join())
+        .await
+        .expect("old accept loop did not stop")
+        .expect("old accept loop failed");
+
+    // Every new connect succeeds after the handover...
+    for _ in 0..10 {
+        let response = timeout(TEST_TIMEOUT, run_test_client(addr))
+            .await
+            .expect("connect timed out during handover")
+            .expect("connection failed during handover");
+        assert_eq!(count_messages(&response, b'D'), 1);
+    }
+    // ...and they all landed on the replacement
+    assert_eq!(new.state().get_stats().await.total_connections, 10);
+
+    // The drained process still serves its surviving session
+    let response = timeout(TEST_TIMEOUT, send_query(&mut survivor))
+        .await
+        .expect("query timed out")
+        .expect("pre-upgrade session broken by the drain");
+    assert_eq!(count_messages(&response, b'D'), 1);
+    assert_eq!(old_state.active_connections.load(Ordering::Relaxed), 1);
+    drop(survivor);
+
+    new.shutdown();
+    timeout(TEST_TIMEOUT, new.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+/// A frame header claiming more than `max_client_message_bytes` must be
+/// rejected on the claim alone: the client gets an error and the
+/// connection closes without the proxy ever buffering the alleged payload
+#[tokio::test]
+async fn test_oversized_frame_claim_closes_connection() {
+    let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+    tokio::spawn(run_fake_upstream(upstream_listener));
+
+    let config = AppConfig {
+        limits: Some(LimitsConfig {
+            max_client_message_bytes: 1024,
+            ..Default::default()
+        }),
+        ..test_config()
+    };
+    let handle = ProxyServer::builder(config)
+        .listen_port(0)
+        .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+        .protocol(DbProtocol::Postgres)
+        .serve()
+        .await
+        .expect("proxy failed to start");
+
+    let mut socket = timeout(TEST_TIMEOUT, connect_as(handle.local_addr(), "appuser"))
+        .await
+        .expect("connect timed out")
+        .expect("connect failed");
+
+    // A 'Q' header claiming 10 KB, with nothing behind it
+    let mut frame = Vec::new();
+    frame.push(b'Q');
+    frame.extend_from_slice(&10_000u32.to_be_bytes());
+    socket.write_all(&frame).await.unwrap();
+
+    let response = timeout(TEST_TIMEOUT, read_to_close(&mut socket))
+        .await
+        .expect("proxy did not close the connection");
+    assert!(
+        contains(&response, b"exceeds maximum"),
+        "expected an oversize error before the close, got {:?}",
+        String::from_utf8_lossy(&response)
+    );
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+/// A half-sent frame must be cut off on the frame timeout rather than
+/// holding its buffer until the idle timeout, and with the greylist
+/// configured the source is then refused before any protocol traffic
+#[tokio::test]
+async fn test_slow_trickle_frame_is_cut_off_and_source_greylisted() {
+    let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+    tokio::spawn(run_fake_upstream(upstream_listener));
+
+    let config = AppConfig {
+        limits: Some(LimitsConfig {
+            frame_timeout_secs: 1,
+            greylist_after_violations: Some(1),
+            ..Default::default()
+        }),
+        ..test_config()
+    };
+    let handle = ProxyServer::builder(config)
+        .listen_port(0)
+        .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+        .protocol(DbProtocol::Postgres)
+        .serve()
+        .await
+        .expect("proxy failed to start");
+
+    let mut socket = timeout(TEST_TIMEOUT, connect_as(handle.local_addr(), "appuser"))
+        .await
+        .expect("connect timed out")
+        .expect("connect failed");
+
+    // Three bytes of a frame header, then silence
+    socket.write_all(&[b'Q', 0, 0]).await.unwrap();
+    let response = timeout(TEST_TIMEOUT, read_to_close(&mut socket))
+        .await
+        .expect("stalled frame was not cut off on the frame timeout");
+    assert!(
+        contains(&response, b"not completed within"),
+        "expected a stalled-frame error before the close, got {:?}",
+        String::from_utf8_lossy(&response)
+    );
+
+    // The source is now greylisted: a fresh connection is dropped before
+    // the proxy reads any protocol traffic
+    let mut refused = TcpStream::connect(handle.local_addr()).await.unwrap();
+    let response = timeout(TEST_TIMEOUT, read_to_close(&mut refused))
+        .await
+        .expect("greylisted connection was not dropped");
+    assert!(response.is_empty(), "expected a silent drop, got {:?}", response);
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+/// A scripted upstream serving COPY TO STDOUT: CSV queries stream two rows
+/// split mid-row across CopyData frames, binary queries answer with a
+/// binary CopyOutResponse, and anything else gets the usual email result
+async fn run_fake_copy_upstream(listener: TcpListener) -> Result<()> {
+    let (mut socket, _) = listener.accept().await?;
+
+    let mut len_buf = [0u8; 4];
+    socket.read_exact(&mut len_buf).await?;
+    let len = u32::from_be_bytes(len_buf) as usize;
+    let mut startup = vec![0u8; len - 4];
+    socket.read_exact(&mut startup).await?;
+
+    let mut greeting = Vec::new();
+    push_msg(&mut greeting, b'R', &0u32.to_be_bytes());
+    push_msg(&mut greeting, b'S', b"server_version\x0016.3\x00");
+    push_msg(&mut greeting, b'Z', b"I");
+    socket.write_all(&greeting).await?;
+
+    loop {
+        let mut type_buf = [0u8; 1];
+        if socket.read_exact(&mut type_buf).await.is_err() {
+            return Ok(());
+        }
+        socket.read_exact(&mut len_buf).await?;
+        let len = u32::from_be_bytes(len_buf) as usize;
+        let mut payload = vec![0u8; len - 4];
+        socket.read_exact(&mut payload).await?;
+
+        if type_buf[0] != b'Q' {
+            continue;
+        }
+        let query = String::from_utf8_lossy(&payload).to_ascii_lowercase();
+
+        let mut response = Vec::new();
+        if query.contains("binary") {
+            // CopyOutResponse declaring binary format, one column
+            push_msg(&mut response, b'H', &[1, 0, 1, 0, 1]);
+            push_msg(&mut response, b'd', b"PGCOPY\n\xff\r\n\x00");
+            push_msg(&mut response, b'c', b"");
+            push_msg(&mut response, b'C', b"COPY 1\x00");
+            push_msg(&mut response, b'Z', b"I");
+        } else if query.starts_with("copy") {
+            // CopyOutResponse: text-mode overall format, two text columns
+            push_msg(&mut response, b'H', &[0, 0, 2, 0, 0, 0, 0]);
+            // Two CSV rows, the first split mid-value across frames
+            push_msg(&mut response, b'd', b"1,alice@exam");
+            push_msg(&mut response, b'd', b"ple.com\n2,bob@example.com\n");
+            push_msg(&mut response, b'c', b"");
+            push_msg(&mut response, b'C', b"COPY 2\x00");
+            push_msg(&mut response, b'Z', b"I");
+        } else {
+            push_email_result(&mut response, "SELECT 1");
+        }
+        socket.write_all(&response).await?;
+    }
+}
+
+/// COPY TO STDOUT bypasses the DataRow path entirely, so its CopyData
+/// stream must be re-framed and masked: rows span frame boundaries, the
+/// email column is rewritten, and the untargeted id column survives intact
+#[tokio::test]
+async fn test_copy_to_stdout_rows_are_masked() {
+    let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+    tokio::spawn(run_fake_copy_upstream(upstream_listener));
+
+    let handle = ProxyServer::builder(email_rule_config())
+        .listen_port(0)
+        .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+        .protocol(DbProtocol::Postgres)
+        .serve()
+        .await
+        .expect("proxy failed to start");
+
+    let mut socket = timeout(TEST_TIMEOUT, connect_as(handle.local_addr(), "analyst"))
+        .await
+        .expect("connect timed out")
+        .expect("connect failed");
+
+    let response = timeout(
+        TEST_TIMEOUT,
+        send_simple(
+            &mut socket,
+            "COPY users (id, email) TO STDOUT (FORMAT csv)",
+        ),
+    )
+    .await
+    .expect("client timed out")
+    .expect("query failed");
+
+    assert!(
+        !contains(&response, b"alice@example.com") && !contains(&response, b"bob@example.com"),
+        "original emails leaked through the COPY stream: {:?}",
+        String::from_utf8_lossy(&response)
+    );
+    assert!(
+        contains(&response, b"1,") && contains(&response, b"\n2,"),
+        "untargeted id column did not survive re-framing: {:?}",
+        String::from_utf8_lossy(&response)
+    );
+    assert!(
+        contains(&response, b"COPY 2"),
+        "CommandComplete tag did not pass through"
+    );
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+/// Binary COPY cannot be parsed for masking; rather than stream it through
+/// unchecked, the proxy refuses it and closes the session
+#[tokio::test]
+async fn test_binary_copy_to_stdout_is_refused() {
+    let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+    tokio::spawn(run_fake_copy_upstream(upstream_listener));
+
+    let handle = ProxyServer::builder(email_rule_config())
+        .listen_port(0)
+        .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+        .protocol(DbProtocol::Postgres)
+        .serve()
+        .await
+        .expect("proxy failed to start");
+
+    let mut socket = timeout(TEST_TIMEOUT, connect_as(handle.local_addr(), "analyst"))
+        .await
+        .expect("connect timed out")
+        .expect("connect failed");
+
+    let mut query = Vec::new();
+    push_msg(
+        &mut query,
+        b'Q',
+        b"COPY users TO STDOUT (FORMAT binary)\x00",
+    );
+    socket.write_all(&query).await.unwrap();
+
+    let response = timeout(TEST_TIMEOUT, read_to_close(&mut socket))
+        .await
+        .expect("binary COPY was not refused");
+    assert!(
+        contains(&response, b"cannot be masked"),
+        "expected a refusal before the close, got {:?}",
+        String::from_utf8_lossy(&response)
+    );
+    assert!(
+        !contains(&response, b"PGCOPY"),
+        "binary COPY data leaked through"
+    );
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+/// A scripted upstream accepting COPY FROM STDIN: it answers the COPY
+/// query with CopyInResponse, counts the rows streamed in CopyData
+/// frames, and completes with a tag carrying that count
+async fn run_fake_copy_in_upstream(listener: TcpListener) -> Result<()> {
+    let (mut socket, _) = listener.accept().await?;
+
+    let mut len_buf = [0u8; 4];
+    socket.read_exact(&mut len_buf).await?;
+    let len = u32::from_be_bytes(len_buf) as usize;
+    let mut startup = vec![0u8; len - 4];
+    socket.read_exact(&mut startup).await?;
+
+    let mut greeting = Vec::new();
+    push_msg(&mut greeting, b'R', &0u32.to_be_bytes());
+    push_msg(&mut greeting, b'Z', b"I");
+    socket.write_all(&greeting).await?;
+
+    let mut rows_received: usize = 0;
+    loop {
+        let mut type_buf = [0u8; 1];
+        if socket.read_exact(&mut type_buf).await.is_err() {
+            return Ok(());
+        }
+        socket.read_exact(&mut len_buf).await?;
+        let len = u32::from_be_bytes(len_buf) as usize;
+        let mut payload = vec![0u8; len - 4];
+        socket.read_exact(&mut payload).await?;
+
+        match type_buf[0] {
+            b'Q' => {
+                // CopyInResponse: text format, two text-format columns
+                let mut response = Vec::new();
+                push_msg(&mut response, b'G', &[0, 0, 2, 0, 0, 0, 0]);
+                socket.write_all(&response).await?;
+            }
+            b'd' => {
+                rows_received += payload.iter().filter(|&&b| b == b'\n').count();
+            }
+            b'c' => {
+                let mut response = Vec::new();
+                push_msg(
+                    &mut response,
+                    b'C',
+                    format!("COPY {}\x00", rows_received).as_bytes(),
+                );
+                push_msg(&mut response, b'Z', b"I");
+                socket.write_all(&response).await?;
+            }
+            b'f' => {
+                let mut response = Vec::new();
+                push_msg(&mut response, b'E', b"SERROR\x00C57014\x00MCOPY aborted\x00\x00");
+                push_msg(&mut response, b'Z', b"I");
+                socket.write_all(&response).await?;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// COPY FROM STDIN round trip: after CopyInResponse the client streams a
+/// few thousand rows of CopyData that must reach the upstream verbatim
+/// rather than being read as query protocol, and the session must come
+/// back to ReadyForQuery without stalling
+#[tokio::test]
+async fn test_copy_from_stdin_rows_pass_through() {
+    let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+    tokio::spawn(run_fake_copy_in_upstream(upstream_listener));
+
+    let handle = ProxyServer::builder(email_rule_config())
+        .listen_port(0)
+        .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+        .protocol(DbProtocol::Postgres)
+        .serve()
+        .await
+        .expect("proxy failed to start");
+
+    let mut socket = timeout(TEST_TIMEOUT, connect_as(handle.local_addr(), "loader"))
+        .await
+        .expect("connect timed out")
+        .expect("connect failed");
+
+    let mut query = Vec::new();
+    push_msg(&mut query, b'Q', b"COPY users (id, email) FROM STDIN\x00");
+    socket.write_all(&query).await.unwrap();
+
+    // Read the CopyInResponse before streaming data
+    let mut header = [0u8; 5];
+    timeout(TEST_TIMEOUT, socket.read_exact(&mut header))
+        .await
+        .expect("CopyInResponse timed out")
+        .unwrap();
+    assert_eq!(header[0], b'G', "expected CopyInResponse");
+    let len = u32::from_be_bytes(header[1..5].try_into().unwrap()) as usize;
+    let mut payload = vec![0u8; len - 4];
+    timeout(TEST_TIMEOUT, socket.read_exact(&mut payload))
+        .await
+        .expect("CopyInResponse payload timed out")
+        .unwrap();
+
+    // Stream 3000 rows, 100 per CopyData frame
+    let mut rows_sent = 0usize;
+    for batch in 0..30 {
+        let mut data = String::new();
+        for i in 0..100 {
+            let id = batch * 100 + i;
+            data.push_str(&format!("{}\tuser{}@example.com\n", id, id));
+            rows_sent += 1;
+        }
+        let mut frame = Vec::new();
+        push_msg(&mut frame, b'd', data.as_bytes());
+        socket.write_all(&frame).await.unwrap();
+    }
+    let mut done = Vec::new();
+    push_msg(&mut done, b'c', b"");
+    socket.write_all(&done).await.unwrap();
+
+    let response = timeout(TEST_TIMEOUT, read_until_ready(&mut socket))
+        .await
+        .expect("COPY completion timed out")
+        .expect("read failed");
+    assert!(
+        contains(&response, format!("COPY {}", rows_sent).as_bytes()),
+        "upstream did not receive every row: {:?}",
+        String::from_utf8_lossy(&response)
+    );
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+/// A scripted upstream for cancel tests: the first connection is a
+/// session greeted with BackendKeyData, the second is expected to be a
+/// relayed CancelRequest whose key pair is reported on the channel
+async fn run_fake_cancel_upstream(
+    listener: TcpListener,
+    cancel_tx: tokio::sync::oneshot::Sender<(u32, u32)>,
+) -> Result<()> {
+    let (mut session, _) = listener.accept().await?;
+    let mut len_buf = [0u8; 4];
+    session.read_exact(&mut len_buf).await?;
+    let len = u32::from_be_bytes(len_buf) as usize;
+    let mut startup = vec![0u8; len - 4];
+    session.read_exact(&mut startup).await?;
+
+    let mut greeting = Vec::new();
+    push_msg(&mut greeting, b'R', &0u32.to_be_bytes());
+    let mut key_data = Vec::new();
+    key_data.extend_from_slice(&4242u32.to_be_bytes());
+    key_data.extend_from_slice(&7777u32.to_be_bytes());
+    push_msg(&mut greeting, b'K', &key_data);
+    push_msg(&mut greeting, b'Z', b"I");
+    session.write_all(&greeting).await?;
+
+    // The cancel arrives on its own fresh connection
+    let (mut cancel, _) = listener.accept().await?;
+    let mut packet = [0u8; 16];
+    cancel.read_exact(&mut packet).await?;
+    assert_eq!(
+        u32::from_be_bytes(packet[4..8].try_into().unwrap()),
+        80877102,
+        "expected the cancel magic"
+    );
+    let process_id = u32::from_be_bytes(packet[8..12].try_into().unwrap());
+    let secret_key = u32::from_be_bytes(packet[12..16].try_into().unwrap());
+    cancel_tx.send((process_id, secret_key)).ok();
+    Ok(())
+}
+
+/// Ctrl-C in psql opens a brand-new connection that carries only a
+/// CancelRequest; the proxy must relay it to the upstream instead of
+/// trying to read a startup message out of it
+#[tokio::test]
+async fn test_cancel_request_is_relayed_to_upstream() {
+    let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+    let (cancel_tx, cancel_rx) = tokio::sync::oneshot::channel();
+    tokio::spawn(run_fake_cancel_upstream(upstream_listener, cancel_tx));
+
+    let handle = ProxyServer::builder(email_rule_config())
+        .listen_port(0)
+        .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+        .protocol(DbProtocol::Postgres)
+        .serve()
+        .await
+        .expect("proxy failed to start");
+
+    // A session whose greeting carried BackendKeyData (pid 4242, key 7777)
+    let _session = timeout(TEST_TIMEOUT, connect_as(handle.local_addr(), "analyst"))
+        .await
+        .expect("connect timed out")
+        .expect("connect failed");
+
+    // The cancel connection: the 16-byte packet and nothing else
+    let mut cancel = TcpStream::connect(handle.local_addr()).await.unwrap();
+    let mut packet = Vec::new();
+    packet.extend_from_slice(&16u32.to_be_bytes());
+    packet.extend_from_slice(&80877102u32.to_be_bytes());
+    packet.extend_from_slice(&4242u32.to_be_bytes());
+    packet.extend_from_slice(&7777u32.to_be_bytes());
+    cancel.write_all(&packet).await.unwrap();
+
+    let relayed = timeout(TEST_TIMEOUT, cancel_rx)
+        .await
+        .expect("cancel was not relayed")
+        .expect("upstream dropped the channel");
+    assert_eq!(relayed, (4242, 7777));
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+/// With `routing.by_database`, a cancel's fresh connection dials the
+/// default upstream (the packet names no database), but the session being
+/// cancelled may sit on a routed one: the relay must follow the target
+/// connection's recorded upstream, and `unmatched: reject` must not
+/// refuse the cancel connection outright.
+#[tokio::test]
+async fn test_cancel_request_follows_database_routing() {
+    let routed_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let routed_addr = routed_listener.local_addr().unwrap();
+    let (cancel_tx, cancel_rx) = tokio::sync::oneshot::channel();
+    tokio::spawn(run_fake_cancel_upstream(routed_listener, cancel_tx));
+
+    // The default upstream only ever sees the cancel connection's
+    // pre-dial; any byte written to it would be a relay to the wrong
+    // upstream
+    let default_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let default_addr = default_listener.local_addr().unwrap();
+    let (wrong_tx, mut wrong_rx) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = default_listener.accept().await {
+            let wrong_tx = wrong_tx.clone();
+            tokio::spawn(async move {
+                let mut buf = [0u8; 1];
+                if let Ok(n) = socket.read(&mut buf).await
+                    && n > 0
+                {
+                    wrong_tx.send(()).ok();
+                }
+            });
+        }
+    });
+
+    let config = AppConfig {
+        routing: Some(RoutingConfig {
+            upstreams: [(
+                "analytics".to_string(),
+                UpstreamTarget {
+                    host: routed_addr.ip().to_string(),
+                    port: routed_addr.port(),
+                    user: None,
+                    protocol: None,
+                },
+            )]
+            .into_iter()
+            .collect(),
+            by_database: vec![DatabaseRoute {
+                database: "analytics".to_string(),
+                upstream: "analytics".to_string(),
+            }],
+            default_upstream: None,
+            unmatched: UnmatchedDatabase::Reject,
+        }),
+        ..test_config()
+    };
+    let handle = ProxyServer::builder(config)
+        .listen_port(0)
+        .upstream(default_addr.ip().to_string(), default_addr.port())
+        .protocol(DbProtocol::Postgres)
+        .serve()
+        .await
+        .expect("proxy failed to start");
+    let addr = handle.local_addr();
+
+    // A routed session greeted with BackendKeyData (pid 4242, key 7777)
+    let _session = timeout(TEST_TIMEOUT, connect_with_database(addr, "analyst", "analytics"))
+        .await
+        .expect("connect timed out")
+        .expect("connect failed");
+
+    let mut cancel = TcpStream::connect(addr).await.unwrap();
+    let mut packet = Vec::new();
+    packet.extend_from_slice(&16u32.to_be_bytes());
+    packet.extend_from_slice(&80877102u32.to_be_bytes());
+    packet.extend_from_slice(&4242u32.to_be_bytes());
+    packet.extend_from_slice(&7777u32.to_be_bytes());
+    cancel.write_all(&packet).await.unwrap();
+
+    let relayed = timeout(TEST_TIMEOUT, cancel_rx)
+        .await
+        .expect("cancel was not relayed to the routed upstream")
+        .expect("upstream dropped the channel");
+    assert_eq!(relayed, (4242, 7777));
+    assert!(
+        wrong_rx.try_recv().is_err(),
+        "cancel bytes reached the default upstream"
+    );
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
+
+/// A scripted upstream running a SCRAM-SHA-256-shaped SASL exchange: it
+/// advertises the mechanism, walks continue/final, and reports every
+/// client auth payload it saw so the test can check them byte-for-byte
+async fn run_fake_scram_upstream(
+    listener: TcpListener,
+    auth_tx: tokio::sync::mpsc::UnboundedSender<Vec<u8>>,
+) -> Result<()> {
+    let (mut socket, _) = listener.accept().await?;
+
+    let mut len_buf = [0u8; 4];
+    socket.read_exact(&mut len_buf).await?;
+    let len = u32::from_be_bytes(len_buf) as usize;
+    let mut startup = vec![0u8; len - 4];
+    socket.read_exact(&mut startup).await?;
+
+    // AuthenticationSASL: code 10 plus the mechanism list
+    let mut sasl = Vec::new();
+    sasl.extend_from_slice(&10u32.to_be_bytes());
+    sasl.extend_from_slice(b"SCRAM-SHA-256\x00\x00");
+    let mut response = Vec::new();
+    push_msg(&mut response, b'R', &sasl);
+    socket.write_all(&response).await?;
+
+    let read_frame = async |socket: &mut TcpStream| -> Result<(u8, Vec<u8>)> {
+        let mut type_buf = [0u8; 1];
+        socket.read_exact(&mut type_buf).await?;
+        let mut len_buf = [0u8; 4];
+        socket.read_exact(&mut len_buf).await?;
+        let len = u32::from_be_bytes(len_buf) as usize;
+        let mut payload = vec![0u8; len - 4];
+        socket.read_exact(&mut payload).await?;
+        Ok((type_buf[0], payload))
+    };
+
+    // SASLInitialResponse
+    let (msg_type, payload) = read_frame(&mut socket).await?;
+    assert_eq!(msg_type, b'p', "expected SASLInitialResponse");
+    auth_tx.send(payload).ok();
+    let mut cont = Vec::new();
+    cont.extend_from_slice(&11u32.to_be_bytes());
+    cont.extend_from_slice(b"r=nonceNONCE,s=c2FsdA==,i=4096");
+    let mut response = Vec::new();
+    push_msg(&mut response, b'R', &cont);
+    socket.write_all(&response).await?;
+
+    // SASLResponse (client-final)
+    let (msg_type, payload) = read_frame(&mut socket).await?;
+    assert_eq!(msg_type, b'p', "expected SASLResponse");
+    auth_tx.send(payload).ok();
+    let mut fin = Vec::new();
+    fin.extend_from_slice(&12u32.to_be_bytes());
+    fin.extend_from_slice(b"v=dmVyaWZpZXI=");
+    let mut response = Vec::new();
+    push_msg(&mut response, b'R', &fin);
+    push_msg(&mut response, b'R', &0u32.to_be_bytes());
+    push_msg(&mut response, b'Z', b"I");
+    socket.write_all(&response).await?;
+
+    // Query phase: serve the usual email result set
+    loop {
+        let (msg_type, _) = match read_frame(&mut socket).await {
+            Ok(frame) => frame,
+            Err(_) => return Ok(()),
+        };
+        if msg_type == b'Q' {
+            let mut response = Vec::new();
+            push_email_result(&mut response, "SELECT 1");
+            socket.write_all(&response).await?;
+        }
+    }
+}
+
+/// A SCRAM exchange must survive the proxy byte-perfectly — the gs2
+/// channel-binding header, nonces and proofs are all integrity-checked by
+/// both ends — and the query phase (with masking) starts only after
+/// AuthenticationOk
+#[tokio::test]
+async fn test_scram_handshake_passes_through_byte_perfect() {
+    let upstream_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+    let (auth_tx, mut auth_rx) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(run_fake_scram_upstream(upstream_listener, auth_tx));
+
+    let handle = ProxyServer::builder(email_rule_config())
+        .listen_port(0)
+        .upstream(upstream_addr.ip().to_string(), upstream_addr.port())
+        .protocol(DbProtocol::Postgres)
+        .serve()
+        .await
+        .expect("proxy failed to start");
+
+    let mut socket = TcpStream::connect(handle.local_addr()).await.unwrap();
+    let mut params = Vec::new();
+    params.extend_from_slice(&196608u32.to_be_bytes());
+    params.extend_from_slice(b"user\x00analyst\x00\x00");
+    let mut startup = Vec::new();
+    startup.extend_from_slice(&((params.len() as u32 + 4).to_be_bytes()));
+    startup.extend_from_slice(&params);
+    socket.write_all(&startup).await.unwrap();
+
+    let read_frame = async |socket: &mut TcpStream| -> (u8, Vec<u8>) {
+        let mut type_buf = [0u8; 1];
+        socket.read_exact(&mut type_buf).await.unwrap();
+        let mut len_buf = [0u8; 4];
+        socket.read_exact(&mut len_buf).await.unwrap();
+        let len = u32::from_be_bytes(len_buf) as usize;
+        let mut payload = vec![0u8; len - 4];
+        socket.read_exact(&mut payload).await.unwrap();
+        (type_buf[0], payload)
+    };
+
+    // AuthenticationSASL with the advertised mechanism
+    let (msg_type, payload) = timeout(TEST_TIMEOUT, read_frame(&mut socket))
+        .await
+        .expect("AuthenticationSASL timed out");
+    assert_eq!(msg_type, b'R');
+    assert!(contains(&payload, b"SCRAM-SHA-256"));
+
+    // SASLInitialResponse: gs2 header "n,," = no channel binding (no TLS)
+    let client_first = b"n,,n=analyst,r=clientNONCE";
+    let mut initial = Vec::new();
+    initial.extend_from_slice(b"SCRAM-SHA-256\x00");
+    initial.extend_from_slice(&(client_first.len() as u32).to_be_bytes());
+    initial.extend_from_slice(client_first);
+    let mut frame = Vec::new();
+    push_msg(&mut frame, b'p', &initial);
+    socket.write_all(&frame).await.unwrap();
+
+    // SASLContinue must carry the server-first message unchanged
+    let (msg_type, payload) = timeout(TEST_TIMEOUT, read_frame(&mut socket))
+        .await
+        .expect("SASLContinue timed out");
+    assert_eq!(msg_type, b'R');
+    assert_eq!(&payload[0..4], &11u32.to_be_bytes());
+    assert_eq!(&payload[4..], b"r=nonceNONCE,s=c2FsdA==,i=4096");
+
+    // SASLResponse (client-final with the proof)
+    let client_final = b"c=biws,r=nonceNONCE,p=cHJvb2Y=";
+    let mut frame = Vec::new();
+    push_msg(&mut frame, b'p', client_final);
+    socket.write_all(&frame).await.unwrap();
+
+    let response = timeout(TEST_TIMEOUT, read_until_ready(&mut socket))
+        .await
+        .expect("auth completion timed out")
+        .expect("read failed");
+    assert!(
+        contains(&response, b"v=dmVyaWZpZXI="),
+        "SASLFinal did not reach the client intact"
+    );
+
+    // The upstream saw exactly the bytes the client sent
+    assert_eq!(auth_rx.recv().await.unwrap(), initial);
+    assert_eq!(auth_rx.recv().await.unwrap(), client_final);
+
+    // Interception starts after AuthenticationOk: masking still applies
+    let response = timeout(TEST_TIMEOUT, send_simple(&mut socket, "SELECT * FROM users"))
+        .await
+        .expect("query timed out")
+        .expect("query failed");
+    assert!(!contains(&response, b"test@example.com"));
+
+    handle.shutdown();
+    timeout(TEST_TIMEOUT, handle.join())
+        .await
+        .expect("shutdown timed out")
+        .expect("accept loop failed");
+}
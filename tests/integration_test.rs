@@ -424,6 +424,82 @@ mod mysql_tests {
     }
 }
 
+/// Self-contained: runs an in-process API server against a temp config
+/// file, so no containers are needed
+#[cfg(all(unix, feature = "api"))]
+mod reload_tests {
+    use super::*;
+    use iron_veil::config::AppConfig;
+    use iron_veil::state::{AppState, DbProtocol};
+
+    /// Distinct from API_PORT so this never collides with a proxy the
+    /// other tests expect to find running
+    const SIGHUP_API_PORT: u16 = 3911;
+
+    fn write_config(path: &std::path::Path, columns: &[&str]) {
+        let rules = columns
+            .iter()
+            .map(|c| format!("  - column: {}\n    strategy: redact\n", c))
+            .collect::<String>();
+        std::fs::write(path, format!("rules:\n{}", rules)).unwrap();
+    }
+
+    /// SIGHUP reloads the config in place: the rule count served by
+    /// `/rules` changes without a restart
+    #[tokio::test]
+    async fn test_sighup_reloads_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("proxy.yaml");
+        write_config(&path, &["email"]);
+
+        let config = AppConfig::load(path.to_str().unwrap()).unwrap();
+        let state = AppState::new(
+            config,
+            path.to_str().unwrap().to_string(),
+            "localhost".to_string(),
+            5432,
+            DbProtocol::Postgres,
+        );
+
+        // The reloader must be installed before the signal is sent:
+        // SIGHUP's default action would kill the test process
+        tokio::spawn(iron_veil::proxy::run_sighup_reloader(state.clone()));
+        tokio::spawn(iron_veil::api::start_api_server(
+            SIGHUP_API_PORT,
+            None,
+            state,
+        ));
+
+        let client = reqwest::Client::new();
+        let url = format!("http://{}:{}/rules", PROXY_HOST, SIGHUP_API_PORT);
+        let mut rules_before = None;
+        for _ in 0..50 {
+            if let Ok(resp) = client.get(&url).send().await {
+                let body: serde_json::Value = resp.json().await.expect("Failed to parse JSON");
+                rules_before = Some(body["rules"].as_array().expect("rules array").len());
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(rules_before, Some(1), "API should serve the initial rule");
+
+        write_config(&path, &["email", "phone"]);
+        unsafe { libc::kill(std::process::id() as i32, libc::SIGHUP) };
+
+        let mut rules_after = 0;
+        for _ in 0..50 {
+            let resp = client.get(&url).send().await.expect("Failed to send request");
+            let body: serde_json::Value = resp.json().await.expect("Failed to parse JSON");
+            rules_after = body["rules"].as_array().expect("rules array").len();
+            if rules_after == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(rules_after, 2, "SIGHUP should reload the modified config");
+    }
+}
+
 mod masking_tests {
     /// Test that email patterns are detected correctly
     #[test]